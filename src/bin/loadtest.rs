@@ -0,0 +1,269 @@
+//! Load test for the mud server
+//!
+//! Connects a number of scripted clients (bots) to a running server. Every
+//! bot switches to robot mode and runs the script for a number of rounds,
+//! timing how long the server takes to respond to each command. At the end
+//! the latency percentiles per command are reported.
+//!
+//! Usage:
+//! ```text
+//!     loadtest <host:port> <private key> [clients] [rounds] [script]
+//! ```
+//! The public key of the private key must be an allowed key of the server.
+//! The script is a semicolon separated list of commands and defaults to
+//! "look;who;say hello".
+#![warn(missing_debug_implementations, rust_2018_idioms)]
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Context};
+use thrussh::client;
+use thrussh::ChannelMsg;
+use thrussh_keys::key;
+use tokio::time::{timeout, Instant};
+
+/// Default number of bots
+const DEFAULT_CLIENTS: usize = 100;
+
+/// Default number of times every bot runs the script
+const DEFAULT_ROUNDS: usize = 10;
+
+/// Default script of every bot
+const DEFAULT_SCRIPT: &str = "look;who;say hello";
+
+/// Time to wait for a response before the command counts as timed out
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Delay between connecting two bots, so the server is not hit by all
+/// handshakes at once
+const RAMP_UP_DELAY: Duration = Duration::from_millis(10);
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() < 3 {
+        bail!("Usage: {} <host:port> <private key> [clients] [rounds] [script]", args[0]);
+    }
+    let addr = args[1].clone();
+    let key = thrussh_keys::load_secret_key(&args[2], None)
+        .with_context(|| format!("Could not load private key {}", args[2]))?;
+    let key = Arc::new(key);
+    let clients = match args.get(3) {
+        Some(c) => c.parse().context("Invalid number of clients")?,
+        None => DEFAULT_CLIENTS,
+    };
+    let rounds = match args.get(4) {
+        Some(r) => r.parse().context("Invalid number of rounds")?,
+        None => DEFAULT_ROUNDS,
+    };
+    let script: Vec<String> = args.get(5).map(|s| s.as_str()).unwrap_or(DEFAULT_SCRIPT)
+        .split(';')
+        .map(|c| c.trim().to_string())
+        .filter(|c| !c.is_empty())
+        .collect();
+
+    println!("Running {} clients for {} rounds of \"{}\" against {}", clients, rounds,
+        script.join(";"), addr);
+    let config = Arc::new(client::Config::default());
+    let started = Instant::now();
+    let mut bots = Vec::new();
+    for i in 0..clients {
+        let bot = Bot {
+            name: format!("loadbot{:03}", i),
+            addr: addr.clone(),
+            key: key.clone(),
+            config: config.clone(),
+        };
+        let script = script.clone();
+        bots.push(tokio::spawn(async move { bot.run(&script, rounds).await }));
+        tokio::time::sleep(RAMP_UP_DELAY).await;
+    }
+
+    let mut report = Report::default();
+    for bot in bots {
+        match bot.await {
+            Ok(Ok(result)) => report.merge(result),
+            Ok(Err(e)) => {
+                eprintln!("Bot failed: {:#}", e);
+                report.failed_clients += 1;
+            },
+            Err(e) => {
+                eprintln!("Bot panicked: {}", e);
+                report.failed_clients += 1;
+            },
+        }
+    }
+    report.print(started.elapsed());
+    Ok(())
+}
+
+/// A scripted client
+struct Bot {
+    name: String,
+    addr: String,
+    key: Arc<key::KeyPair>,
+    config: Arc<client::Config>,
+}
+
+impl Bot {
+    /// Connect and run the script for the given number of rounds
+    async fn run(self, script: &[String], rounds: usize) -> anyhow::Result<Report> {
+        let mut session = client::connect(self.config.clone(), self.addr.as_str(), Handler {}).await
+            .with_context(|| format!("{} could not connect", self.name))?;
+        if !session.authenticate_publickey(self.name.as_str(), self.key.clone()).await? {
+            bail!("{} was not authenticated", self.name);
+        }
+        let mut connection = Connection {
+            channel: session.channel_open_session().await?,
+            buffer: Vec::new(),
+        };
+
+        // Robot mode makes every response a single line of JSON
+        connection.send("robot on").await?;
+        loop {
+            let line = connection.next_line().await?;
+            if let Ok(message) = serde_json::from_str::<serde_json::Value>(&line) {
+                if message["fields"]["robot"] == true {
+                    break;
+                }
+            }
+        }
+
+        let mut report = Report::default();
+        for _ in 0..rounds {
+            for command in script {
+                let start = Instant::now();
+                connection.send(command).await?;
+                match timeout(RESPONSE_TIMEOUT, connection.response()).await {
+                    Ok(Ok(_)) => report.record(command, start.elapsed()),
+                    Ok(Err(e)) => return Err(e),
+                    Err(_) => report.timeouts += 1,
+                }
+            }
+        }
+        connection.channel.eof().await?;
+        Ok(report)
+    }
+}
+
+/// The session of a bot
+struct Connection {
+    channel: client::Channel,
+    buffer: Vec<u8>,
+}
+
+impl Connection {
+    /// Send a command
+    ///
+    /// The server expects the CR ending the command in a packet of its own.
+    async fn send(&mut self, command: &str) -> anyhow::Result<()> {
+        self.channel.data(command.as_bytes()).await?;
+        self.channel.data(&b"\r"[..]).await?;
+        Ok(())
+    }
+
+    /// Wait for the next line from the server
+    async fn next_line(&mut self) -> anyhow::Result<String> {
+        loop {
+            if let Some(end) = self.buffer.windows(2).position(|w| w == b"\r\n") {
+                let line: Vec<u8> = self.buffer.drain(..end + 2).take(end).collect();
+                return Ok(String::from_utf8_lossy(&line).to_string());
+            }
+            match self.channel.wait().await {
+                Some(ChannelMsg::Data { data }) => self.buffer.extend_from_slice(&data),
+                Some(ChannelMsg::Eof) | Some(ChannelMsg::Close) | None => {
+                    return Err(anyhow!("Connection closed by the server"));
+                },
+                Some(_) => {},
+            }
+        }
+    }
+
+    /// Wait for the response to the last command
+    ///
+    /// Chat of other players is skipped. The response to a command of the bot
+    /// itself that causes chat (eg. "say") is the chat line starting with
+    /// "You".
+    async fn response(&mut self) -> anyhow::Result<serde_json::Value> {
+        loop {
+            let line = self.next_line().await?;
+            let message: serde_json::Value = match serde_json::from_str(&line) {
+                Ok(m) => m,
+                Err(_) => continue,
+            };
+            let own_chat = message["text"].as_str().is_some_and(|t| t.starts_with("You"));
+            if message["event"] != "chat" || own_chat {
+                return Ok(message);
+            }
+        }
+    }
+}
+
+/// SSH client handler of a bot
+struct Handler {}
+
+impl client::Handler for Handler {
+    type Error = anyhow::Error;
+    type FutureUnit = futures::future::Ready<Result<(Self, client::Session), anyhow::Error>>;
+    type FutureBool = futures::future::Ready<Result<(Self, bool), anyhow::Error>>;
+
+    fn finished_bool(self, b: bool) -> Self::FutureBool {
+        futures::future::ready(Ok((self, b)))
+    }
+    fn finished(self, session: client::Session) -> Self::FutureUnit {
+        futures::future::ready(Ok((self, session)))
+    }
+    // The load test runs against our own server
+    fn check_server_key(self, _server_public_key: &key::PublicKey) -> Self::FutureBool {
+        self.finished_bool(true)
+    }
+}
+
+/// Latencies measured by the bots
+#[derive(Debug, Default)]
+struct Report {
+    latencies: BTreeMap<String, Vec<Duration>>,
+    timeouts: usize,
+    failed_clients: usize,
+}
+
+impl Report {
+    /// Record the latency of a command
+    fn record(&mut self, command: &str, latency: Duration) {
+        self.latencies.entry(command.to_string()).or_default().push(latency);
+    }
+
+    /// Add the latencies of another report
+    fn merge(&mut self, other: Report) {
+        for (command, mut latencies) in other.latencies {
+            self.latencies.entry(command).or_default().append(&mut latencies);
+        }
+        self.timeouts += other.timeouts;
+        self.failed_clients += other.failed_clients;
+    }
+
+    /// Print the latency percentiles per command
+    fn print(mut self, elapsed: Duration) {
+        println!("Finished after {:.1}s ({} failed clients, {} timeouts)", elapsed.as_secs_f64(),
+            self.failed_clients, self.timeouts);
+        println!("{:<20} {:>8} {:>8} {:>8} {:>8} {:>8}", "command", "count", "p50 ms", "p90 ms",
+            "p99 ms", "max ms");
+        for (command, latencies) in self.latencies.iter_mut() {
+            latencies.sort();
+            println!("{:<20} {:>8} {:>8} {:>8} {:>8} {:>8}", command, latencies.len(),
+                percentile(latencies, 50), percentile(latencies, 90), percentile(latencies, 99),
+                percentile(latencies, 100));
+        }
+    }
+}
+
+/// Percentile of sorted latencies in milliseconds
+fn percentile(sorted: &[Duration], p: usize) -> u128 {
+    if sorted.is_empty() {
+        return 0;
+    }
+    let index = ((sorted.len() - 1) * p + 50) / 100;
+    sorted[index].as_millis()
+}
@@ -0,0 +1,162 @@
+//! Authorized keys
+//!
+//! The keys allowed to connect are listed in a file in the format of the
+//! OpenSSH `authorized_keys` file (`security.authorized_keys` in the
+//! settings), one key per line:
+//! ```ignore
+//!     # The staff
+//!     ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIC8Z... fschuetz@ieee.org
+//!     player="neo" ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIMTL... neo's badge
+//! ```
+//! A line holds the options (optional), the algorithm, the key in base64
+//! and a comment (optional). Empty lines and lines starting with "#" are
+//! skipped. The option `player="<handle>"` assigns the key to a player: it
+//! logs in as that player only. Keys without it log in as any player. Other
+//! options are ignored.
+//!
+//! Keys are looked up by their fingerprint, the SHA256 of the key as shown
+//! by `ssh-keygen -l` (eg. "SHA256:uNiVztksCsDhcc0u9e8BujQXVUpKZIDTMczCvj3tD2s").
+
+use std::fmt;
+use std::io;
+use std::path::Path;
+
+/// A key of the authorized keys
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuthorizedKey {
+    /// The algorithm (eg. "ssh-ed25519")
+    pub algorithm: String,
+    /// The key in base64
+    pub key: String,
+    pub comment: Option<String>,
+    /// The player the key is assigned to, if any
+    pub player: Option<String>,
+}
+
+impl AuthorizedKey {
+    /// Parse a line of an authorized_keys file
+    ///
+    /// Returns None for empty lines and comments, or why the line is no key.
+    pub fn parse(line: &str) -> Result<Option<AuthorizedKey>, String> {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            return Ok(None);
+        }
+        // The options come first if the line does not start with the algorithm
+        let (options, rest) = match line.split_whitespace().next() {
+            Some(first) if is_algorithm(first) => ("", line),
+            _ => split_options(line),
+        };
+        let mut fields = rest.split_whitespace();
+        let algorithm = fields.next().filter(|a| is_algorithm(a)).ok_or("The algorithm is missing or unknown.")?;
+        let key = fields.next().ok_or("The key is missing.")?;
+        let parsed = thrussh_keys::parse_public_key_base64(key).map_err(|_| "The key is no valid public key.")?;
+        if parsed.name() != algorithm {
+            return Err(format!("The key is a {} key, not a {} key.", parsed.name(), algorithm));
+        }
+        let comment = fields.collect::<Vec<&str>>().join(" ");
+        let mut player = None;
+        for option in options_of(options) {
+            if let Some(value) = option.strip_prefix("player=") {
+                let value = value.trim_matches('"');
+                if value.is_empty() {
+                    return Err("The player of the key is empty.".to_string());
+                }
+                player = Some(value.to_string());
+            }
+        }
+        Ok(Some(AuthorizedKey {
+            algorithm: algorithm.to_string(),
+            key: key.to_string(),
+            comment: Some(comment).filter(|c| !c.is_empty()),
+            player,
+        }))
+    }
+
+    /// The fingerprint of the key (eg. "SHA256:uNiVztksCsDh...")
+    pub fn fingerprint(&self) -> String {
+        fingerprint(&self.key).unwrap_or_default()
+    }
+}
+
+impl fmt::Display for AuthorizedKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(player) = &self.player {
+            write!(f, "player=\"{}\" ", player)?;
+        }
+        write!(f, "{} {}", self.algorithm, self.key)?;
+        match &self.comment {
+            Some(comment) => write!(f, " {}", comment),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The fingerprint of a key given in base64, None if it is no valid key
+pub fn fingerprint(key: &str) -> Option<String> {
+    thrussh_keys::parse_public_key_base64(key).ok().map(|k| format!("SHA256:{}", k.fingerprint()))
+}
+
+/// Parse an authorized_keys file
+///
+/// Returns the keys, or the number of the first line that is no key and
+/// why.
+pub fn parse(text: &str) -> Result<Vec<AuthorizedKey>, String> {
+    let mut keys = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        match AuthorizedKey::parse(line) {
+            Ok(Some(key)) => keys.push(key),
+            Ok(None) => {},
+            Err(reason) => return Err(format!("Line {}: {}", number + 1, reason)),
+        }
+    }
+    Ok(keys)
+}
+
+/// Load an authorized_keys file
+///
+/// A missing file holds no keys.
+pub fn load(path: &Path) -> io::Result<Vec<AuthorizedKey>> {
+    let text = match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    parse(&text).map_err(|reason| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), reason)))
+}
+
+fn is_algorithm(word: &str) -> bool {
+    word.starts_with("ssh-") || word.starts_with("ecdsa-sha2-") || word.starts_with("sk-")
+}
+
+/// Split the options (up to the first blank outside of quotes) from the
+/// rest of a line
+fn split_options(line: &str) -> (&str, &str) {
+    let mut quoted = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            c if c.is_whitespace() && !quoted => return (&line[..i], &line[i..]),
+            _ => {},
+        }
+    }
+    (line, "")
+}
+
+/// The options, separated by commas outside of quotes
+fn options_of(options: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let (mut quoted, mut start) = (false, 0);
+    for (i, c) in options.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            ',' if !quoted => {
+                parts.push(&options[start..i]);
+                start = i + 1;
+            },
+            _ => {},
+        }
+    }
+    parts.push(&options[start..]);
+    parts.into_iter().filter(|o| !o.is_empty()).collect()
+}
@@ -0,0 +1,91 @@
+//! Output buffers
+//!
+//! The world renders every message into a buffer that is pushed to the
+//! connection handler of the client. The connection handler writes the data
+//! (the ssh connection handler copies it into the buffer type of the ssh
+//! server) and returns the buffer to the pool, so the world does not need to
+//! allocate a new buffer for every message.
+//!
+//! The pool counts the buffers taken and how many of them were reused, the
+//! admins see the counts with "@stats" (eg. under the load test).
+
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use lazy_static::lazy_static;
+
+use super::Output;
+
+/// Maximum number of buffers kept in the pool
+const MAX_POOLED_BUFFERS: usize = 256;
+
+/// Buffers that held more than this (eg. a screen) are not pooled
+const MAX_POOLED_LEN: usize = 4_096;
+
+/// Initial capacity of new buffers, enough for most messages
+const INITIAL_CAPACITY: usize = 256;
+
+lazy_static! {
+    static ref POOL: Mutex<Vec<Output>> = Mutex::new(Vec::new());
+}
+
+/// Buffers taken
+static TAKEN: AtomicU64 = AtomicU64::new(0);
+
+/// Buffers taken from the pool instead of allocated
+static REUSED: AtomicU64 = AtomicU64::new(0);
+
+/// How many buffers were taken and how many of them were reused
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Usage {
+    pub taken: u64,
+    pub reused: u64,
+}
+
+/// Display the usage for the admins
+impl fmt::Display for Usage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} buffers taken, {} reused, {} allocated", self.taken, self.reused, self.taken - self.reused)
+    }
+}
+
+/// Take an empty buffer from the pool
+pub fn take() -> Output {
+    TAKEN.fetch_add(1, Ordering::Relaxed);
+    let pooled = match POOL.lock() {
+        Ok(mut pool) => pool.pop(),
+        Err(_) => None,
+    };
+    match pooled {
+        Some(buffer) => {
+            REUSED.fetch_add(1, Ordering::Relaxed);
+            buffer
+        },
+        None => Output::with_capacity(INITIAL_CAPACITY),
+    }
+}
+
+/// Take a buffer from the pool holding the given data
+pub fn from_slice(data: &[u8]) -> Output {
+    let mut buffer = take();
+    buffer.extend_from_slice(data);
+    buffer
+}
+
+/// Return a buffer to the pool
+pub fn give(mut buffer: Output) {
+    if buffer.len() > MAX_POOLED_LEN {
+        return;
+    }
+    buffer.clear();
+    if let Ok(mut pool) = POOL.lock() {
+        if pool.len() < MAX_POOLED_BUFFERS {
+            pool.push(buffer);
+        }
+    }
+}
+
+/// How many buffers were taken and reused since the start
+pub fn usage() -> Usage {
+    Usage { taken: TAKEN.load(Ordering::Relaxed), reused: REUSED.load(Ordering::Relaxed) }
+}
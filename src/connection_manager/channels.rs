@@ -0,0 +1,294 @@
+//! Channels between the connection handlers and the world
+//!
+//! All channels are bounded. What happens to a message if its channel is full
+//! depends on the type of the message (see `Overflow`): messages that can be
+//! repeated (eg. a line typed by a player) are dropped, while messages the
+//! other side depends on (eg. registering a client) wait until there is room.
+//! Messages the world sends but must not wait for (closing a connection) wait
+//! for room on their own.
+//! Every channel keeps statistics, so the staff can see how close a channel
+//! came to its capacity.
+
+use std::fmt;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use tokio::sync::mpsc;
+use tokio::sync::mpsc::error::TrySendError;
+
+use super::{Command, DataMessage, Push};
+
+/// What to do with a message if its channel is full
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Overflow {
+    /// Drop the message
+    Drop,
+    /// Wait until there is room in the channel
+    Wait,
+    /// Wait until there is room in the channel in a task of its own, so the
+    /// sender does not wait
+    Defer,
+}
+
+/// Messages that define what happens to them if their channel is full
+pub trait OverflowPolicy {
+    /// The overflow policy for this message
+    fn overflow(&self) -> Overflow;
+}
+
+/// Registering and hanging up must never get lost. An admin command can be
+/// repeated, like the lines of a player.
+impl OverflowPolicy for Command {
+    fn overflow(&self) -> Overflow {
+        match self {
+            Command::Admin(_) => Overflow::Drop,
+            _ => Overflow::Wait,
+        }
+    }
+}
+
+/// A player can repeat a command that got lost
+impl OverflowPolicy for DataMessage {
+    fn overflow(&self) -> Overflow {
+        Overflow::Drop
+    }
+}
+
+/// Output for a client that cannot keep up is dropped, so a slow client does
+/// not stall the world. Closing the connection must never get lost, but the
+/// world does not wait for it either.
+impl OverflowPolicy for Push {
+    fn overflow(&self) -> Overflow {
+        match self {
+            Push::Data(_) | Push::Animation(_) => Overflow::Drop,
+            Push::Close => Overflow::Defer,
+        }
+    }
+}
+
+/// Capacities of the channels
+#[derive(Debug, Clone)]
+pub struct Capacities {
+    /// Capacity of the command channel to the world
+    pub command: usize,
+    /// Capacity of the data channel to the world
+    pub data: usize,
+    /// Capacity of the push channel of every client
+    pub push: usize,
+}
+
+impl Default for Capacities {
+    fn default() -> Capacities {
+        Capacities {
+            command: 1_024,
+            data: 1_024,
+            push: 256,
+        }
+    }
+}
+
+/// Statistics of a channel
+#[derive(Debug)]
+pub struct ChannelStats {
+    /// Name of the channel
+    pub name: &'static str,
+    /// Capacity of the channel
+    pub capacity: usize,
+    // Atomics, as the statistics are shared between all senders
+    queued: AtomicUsize,
+    high_water_mark: AtomicUsize,
+    sent: AtomicU64,
+    dropped: AtomicU64,
+}
+
+impl ChannelStats {
+    /// Create statistics for a channel
+    pub fn new(name: &'static str, capacity: usize) -> ChannelStats {
+        ChannelStats {
+            name,
+            capacity,
+            queued: AtomicUsize::new(0),
+            high_water_mark: AtomicUsize::new(0),
+            sent: AtomicU64::new(0),
+            dropped: AtomicU64::new(0),
+        }
+    }
+
+    /// Number of messages currently queued in the channel (in all push
+    /// channels for the push statistics)
+    pub fn queued(&self) -> usize {
+        self.queued.load(Ordering::Relaxed)
+    }
+
+    /// Highest number of messages that were queued in the channel at once
+    pub fn high_water_mark(&self) -> usize {
+        self.high_water_mark.load(Ordering::Relaxed)
+    }
+
+    /// Number of messages sent over the channel
+    pub fn sent(&self) -> u64 {
+        self.sent.load(Ordering::Relaxed)
+    }
+
+    /// Number of messages dropped because the channel was full
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+/// Display the statistics of a channel
+impl fmt::Display for ChannelStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} sent, {} dropped, {} queued, high water mark {}/{}", self.name,
+            self.sent(), self.dropped(), self.queued(), self.high_water_mark(), self.capacity)
+    }
+}
+
+/// Statistics of all channels
+///
+/// The push channels of all clients share their statistics.
+#[derive(Debug, Clone)]
+pub struct Metrics {
+    /// Statistics of the command channel to the world
+    pub command: Arc<ChannelStats>,
+    /// Statistics of the data channel to the world
+    pub data: Arc<ChannelStats>,
+    /// Statistics of the push channels to the clients
+    pub push: Arc<ChannelStats>,
+}
+
+impl Metrics {
+    /// Create the statistics for channels with the given capacities
+    pub fn new(capacities: &Capacities) -> Metrics {
+        Metrics {
+            command: Arc::new(ChannelStats::new("command", capacities.command)),
+            data: Arc::new(ChannelStats::new("data", capacities.data)),
+            push: Arc::new(ChannelStats::new("push", capacities.push)),
+        }
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Metrics {
+        Metrics::new(&Capacities::default())
+    }
+}
+
+/// Reasons a message could not be sent
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SendError {
+    /// The channel was full and the message was dropped
+    Full,
+    /// The receiving side is gone
+    Closed,
+}
+
+/// The sending side of a channel that applies the overflow policy of its
+/// messages and records statistics
+pub struct MeteredSender<T> {
+    tx: mpsc::Sender<T>,
+    stats: Arc<ChannelStats>,
+}
+
+// Derive would require T: Clone
+impl<T> Clone for MeteredSender<T> {
+    fn clone(&self) -> Self {
+        MeteredSender { tx: self.tx.clone(), stats: self.stats.clone() }
+    }
+}
+
+// Derive would require T: Debug
+impl<T> fmt::Debug for MeteredSender<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MeteredSender")
+         .field("channel", &self.stats.name)
+         .finish()
+    }
+}
+
+impl<T: OverflowPolicy + Send + 'static> MeteredSender<T> {
+    /// Send a message according to its overflow policy
+    pub async fn send(&self, message: T) -> Result<(), SendError> {
+        // Count the message as queued before sending, so the receiver never
+        // sees it before it is counted
+        self.stats.queued.fetch_add(1, Ordering::Relaxed);
+        let result = match message.overflow() {
+            Overflow::Wait => self.tx.send(message).await.map_err(|_| SendError::Closed),
+            Overflow::Drop => {
+                match self.tx.try_send(message) {
+                    Ok(_) => Ok(()),
+                    Err(TrySendError::Full(_)) => {
+                        self.stats.dropped.fetch_add(1, Ordering::Relaxed);
+                        Err(SendError::Full)
+                    },
+                    Err(TrySendError::Closed(_)) => Err(SendError::Closed),
+                }
+            },
+            Overflow::Defer => {
+                match self.tx.try_send(message) {
+                    Ok(_) => Ok(()),
+                    Err(TrySendError::Full(message)) => {
+                        let (tx, stats) = (self.tx.clone(), self.stats.clone());
+                        tokio::spawn(async move {
+                            if tx.send(message).await.is_err() {
+                                stats.queued.fetch_sub(1, Ordering::Relaxed);
+                            }
+                        });
+                        Ok(())
+                    },
+                    Err(TrySendError::Closed(_)) => Err(SendError::Closed),
+                }
+            },
+        };
+        if result.is_err() {
+            self.stats.queued.fetch_sub(1, Ordering::Relaxed);
+            return result;
+        }
+        self.stats.sent.fetch_add(1, Ordering::Relaxed);
+        let queued = self.stats.capacity.saturating_sub(self.tx.capacity());
+        self.stats.high_water_mark.fetch_max(queued, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+/// The receiving side of a channel that records statistics
+pub struct MeteredReceiver<T> {
+    rx: mpsc::Receiver<T>,
+    stats: Arc<ChannelStats>,
+}
+
+impl<T> MeteredReceiver<T> {
+    /// Receive the next message
+    pub async fn recv(&mut self) -> Option<T> {
+        let message = self.rx.recv().await;
+        if message.is_some() {
+            self.stats.queued.fetch_sub(1, Ordering::Relaxed);
+        }
+        message
+    }
+}
+
+/// Messages left in the channel are no longer queued once the receiver is
+/// gone (eg. the push channel of a client that hung up)
+impl<T> Drop for MeteredReceiver<T> {
+    fn drop(&mut self) {
+        self.rx.close();
+        while self.rx.try_recv().is_ok() {
+            self.stats.queued.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+}
+
+// Derive would require T: Debug
+impl<T> fmt::Debug for MeteredReceiver<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MeteredReceiver")
+         .field("channel", &self.stats.name)
+         .finish()
+    }
+}
+
+/// Create a bounded channel with the capacity and statistics given
+pub fn channel<T>(stats: &Arc<ChannelStats>) -> (MeteredSender<T>, MeteredReceiver<T>) {
+    let (tx, rx) = mpsc::channel(stats.capacity);
+    (MeteredSender { tx, stats: stats.clone() }, MeteredReceiver { rx, stats: stats.clone() })
+}
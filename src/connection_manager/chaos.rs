@@ -0,0 +1,82 @@
+//! Fault injection
+//!
+//! Only built with the `chaos` feature. Disrupts the data the world pushes to
+//! the clients the way the WiFi at a con does: pushes are delayed or dropped
+//! and connections are severed at random. This is used to test how the world
+//! copes with flaky clients.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// How often and how hard to disrupt the pushes to the clients
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Chaos {
+    /// Probability that a push is delayed
+    pub delay_probability: f64,
+    /// Maximum delay of a push in milliseconds
+    pub max_delay_ms: u64,
+    /// Probability that a push is dropped
+    pub drop_probability: f64,
+    /// Probability that the connection is severed instead of pushing
+    pub sever_probability: f64,
+}
+
+/// What happens to a push
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Disruption {
+    /// The push is sent as usual
+    None,
+    /// The push is sent after a delay
+    Delay(Duration),
+    /// The push is dropped
+    Drop,
+    /// The connection is severed
+    Sever,
+}
+
+impl Chaos {
+    /// Decide what happens to the next push
+    pub fn disrupt(&self, rng: &mut Rng) -> Disruption {
+        if rng.chance(self.sever_probability) {
+            Disruption::Sever
+        } else if rng.chance(self.drop_probability) {
+            Disruption::Drop
+        } else if self.max_delay_ms > 0 && rng.chance(self.delay_probability) {
+            Disruption::Delay(Duration::from_millis(rng.next() % self.max_delay_ms + 1))
+        } else {
+            Disruption::None
+        }
+    }
+}
+
+/// A small xorshift random number generator, good enough to pick disruptions
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    /// Create a generator from a seed (eg. the client id)
+    ///
+    /// The seed is mixed with the current time, so runs differ.
+    pub fn new(seed: u64) -> Rng {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.subsec_nanos() as u64)
+            .unwrap_or(0);
+        Rng { state: (seed ^ nanos).wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1 }
+    }
+
+    /// The next random number
+    pub fn next(&mut self) -> u64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        self.state
+    }
+
+    /// True with the given probability
+    pub fn chance(&mut self, probability: f64) -> bool {
+        probability > 0.0 && (self.next() as f64 / u64::MAX as f64) < probability
+    }
+}
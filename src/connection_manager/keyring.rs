@@ -0,0 +1,176 @@
+//! Keyring
+//!
+//! The public keys allowed to connect, as base64 blobs. Clones share the
+//! keys, so keys provisioned at runtime (see `provisioning`) are accepted
+//! by the ssh server without a restart.
+//!
+//! Keys are either allowed for any player (eg. the authorized keys without
+//! player, see `authorized_keys`) or bound to a single player: assigned to
+//! it in the authorized keys, looked up by fingerprint, or bound when a
+//! player logs in with a login token (see `tokens`). Bound keys are stored
+//! as JSON file inside the data directory of the world. They are written
+//! after the lock on the keys is released, one write at a time, so the ssh
+//! server keeps checking keys while they are stored.
+
+use std::collections::BTreeMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+use tokio::fs;
+use tokio::sync::Mutex;
+
+use super::authorized_keys::{self, AuthorizedKey};
+
+/// Name of the file the bound keys are stored in (inside the data directory)
+pub const BOUND_KEYS_FILE: &str = "keys.json";
+
+/// The public keys allowed to connect
+#[derive(Debug, Clone, Default)]
+pub struct Keyring {
+    inner: Arc<RwLock<Inner>>,
+    /// Held while the bound keys are stored, so an older snapshot never
+    /// overwrites a newer one
+    storing: Arc<Mutex<()>>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    keys: Vec<String>,
+    /// Bound keys, the player they are bound to by key
+    bound: BTreeMap<String, String>,
+    /// Keys assigned to a player in the authorized keys, the player by
+    /// fingerprint
+    assigned: BTreeMap<String, String>,
+    /// Where the bound keys are stored, if they are
+    data_dir: Option<PathBuf>,
+}
+
+impl Keyring {
+    /// Create a keyring holding the given keys
+    pub fn new(keys: Vec<String>) -> Keyring {
+        Keyring { inner: Arc::new(RwLock::new(Inner { keys, ..Inner::default() })), storing: Arc::default() }
+    }
+
+    /// Load the bound keys stored in the data directory and store keys bound
+    /// from now on there
+    pub fn open(&self, data_dir: &Path) -> io::Result<()> {
+        let bound = match std::fs::read(data_dir.join(BOUND_KEYS_FILE)) {
+            Ok(buf) => serde_json::from_slice(&buf)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(e),
+        };
+        let mut inner = self.inner.write().unwrap_or_else(|e| e.into_inner());
+        inner.bound = bound;
+        inner.data_dir = Some(data_dir.to_path_buf());
+        Ok(())
+    }
+
+    /// Allow the authorized keys to connect: the keys assigned to a player
+    /// as that player, the others as any player
+    pub fn authorize(&self, keys: &[AuthorizedKey]) {
+        for key in keys {
+            match &key.player {
+                Some(player) => {
+                    let mut inner = self.inner.write().unwrap_or_else(|e| e.into_inner());
+                    inner.assigned.insert(key.fingerprint(), player.clone());
+                },
+                None => self.add(&key.key),
+            }
+        }
+    }
+
+    /// The player the key with the given fingerprint is assigned to in the
+    /// authorized keys, if any
+    pub fn owner(&self, fingerprint: &str) -> Option<String> {
+        self.inner.read().unwrap_or_else(|e| e.into_inner()).assigned.get(fingerprint).cloned()
+    }
+
+    /// True if the key is allowed to connect as the player (ignoring case)
+    ///
+    /// A key assigned to a player in the authorized keys connects as that
+    /// player only. A player a key is bound or assigned to (eg. the handle of
+    /// a badge) is only connected as with such a key, not with a key allowed
+    /// for any player.
+    pub fn allows(&self, key: &str, player: &str) -> bool {
+        if let Some(owner) = authorized_keys::fingerprint(key).and_then(|f| self.owner(&f)) {
+            return owner.eq_ignore_ascii_case(player);
+        }
+        if self.binds(player) {
+            return self.verifies(key, player);
+        }
+        let inner = self.inner.read().unwrap_or_else(|e| e.into_inner());
+        inner.keys.iter().any(|k| k.eq_ignore_ascii_case(key))
+    }
+
+    /// True if the key is assigned or bound to the player (ignoring case), so
+    /// it proves the identity of the player
+    pub fn verifies(&self, key: &str, player: &str) -> bool {
+        if let Some(owner) = authorized_keys::fingerprint(key).and_then(|f| self.owner(&f)) {
+            return owner.eq_ignore_ascii_case(player);
+        }
+        let inner = self.inner.read().unwrap_or_else(|e| e.into_inner());
+        inner.bound.get(key).is_some_and(|p| p.eq_ignore_ascii_case(player))
+    }
+
+    /// True if the key is allowed to connect as any player (ignoring case)
+    pub fn contains(&self, key: &str) -> bool {
+        let inner = self.inner.read().unwrap_or_else(|e| e.into_inner());
+        inner.keys.iter().any(|k| k.eq_ignore_ascii_case(key))
+    }
+
+    /// True if a key is bound or assigned to the player (ignoring case)
+    pub fn binds(&self, player: &str) -> bool {
+        let inner = self.inner.read().unwrap_or_else(|e| e.into_inner());
+        inner.bound.values().chain(inner.assigned.values()).any(|p| p.eq_ignore_ascii_case(player))
+    }
+
+    /// Allow a key to connect as any player
+    pub fn add(&self, key: &str) {
+        if !self.contains(key) {
+            self.inner.write().unwrap_or_else(|e| e.into_inner()).keys.push(key.to_string());
+        }
+    }
+
+    /// Allow a key to connect as the given player only
+    ///
+    /// A key bound before is bound to the player instead.
+    pub async fn bind(&self, key: &str, player: &str) -> io::Result<()> {
+        self.store(|bound| { bound.insert(key.to_string(), player.to_string()); }).await
+    }
+
+    /// No longer allow a bound key to connect
+    pub async fn unbind(&self, key: &str) -> io::Result<()> {
+        self.store(|bound| { bound.remove(key); }).await
+    }
+
+    /// The keys bound to the player (ignoring case)
+    pub fn bound_to(&self, player: &str) -> Vec<String> {
+        let inner = self.inner.read().unwrap_or_else(|e| e.into_inner());
+        inner.bound.iter().filter(|(_, p)| p.eq_ignore_ascii_case(player)).map(|(k, _)| k.clone()).collect()
+    }
+
+    /// No longer allow the keys bound to the player (ignoring case) to
+    /// connect (eg. when the player is forgotten)
+    pub async fn unbind_all(&self, player: &str) -> io::Result<()> {
+        self.store(|bound| bound.retain(|_, p| !p.eq_ignore_ascii_case(player))).await
+    }
+
+    /// Change the bound keys and store them, if they are stored
+    ///
+    /// The keys are serialized under the lock, but written after it is
+    /// released.
+    async fn store(&self, change: impl FnOnce(&mut BTreeMap<String, String>)) -> io::Result<()> {
+        let _storing = self.storing.lock().await;
+        let (dir, buf) = {
+            let mut inner = self.inner.write().unwrap_or_else(|e| e.into_inner());
+            change(&mut inner.bound);
+            match &inner.data_dir {
+                Some(dir) => (dir.clone(), serde_json::to_vec_pretty(&inner.bound)?),
+                None => return Ok(()),
+            }
+        };
+        fs::create_dir_all(&dir).await?;
+        fs::write(dir.join(BOUND_KEYS_FILE), buf).await
+    }
+}
@@ -0,0 +1,348 @@
+//! Line editor
+//!
+//! Ssh clients send every key as it is typed, the server assembles the
+//! lines. The line editor keeps the line being typed and a cursor in it:
+//!  * backspace and delete remove the character before and at the cursor
+//!  * the left and right arrows move the cursor, home and end (or ctrl-a
+//!    and ctrl-e) move it to the start and the end of the line
+//!  * ctrl-w removes the word before the cursor, ctrl-u everything before
+//!    the cursor
+//!  * CR (or LF) completes the line
+//!  * the up and down arrows recall the earlier and later lines of the
+//!    history, to edit them like a typed line
+//!
+//! The history keeps the last lines completed (as many as the depth, none
+//! if it is 0), without empty lines and repeats. Along with the completed
+//! lines the editor returns what renders the edits on the terminal of the
+//! client, sent back if echo is on. Other keys are dropped.
+
+use std::collections::VecDeque;
+
+use super::Data;
+use super::telnet_server::MAX_LINE;
+
+/// Escape, starts the sequences sent by the arrow keys and others
+const ESC: u8 = 0x1b;
+/// Ctrl-a: to the start of the line
+const CTRL_A: u8 = 0x01;
+/// Ctrl-e: to the end of the line
+const CTRL_E: u8 = 0x05;
+/// Ctrl-h: backspace on some terminals
+const CTRL_H: u8 = 0x08;
+/// Ctrl-u: remove everything before the cursor
+const CTRL_U: u8 = 0x15;
+/// Ctrl-w: remove the word before the cursor
+const CTRL_W: u8 = 0x17;
+/// Backspace on most terminals
+const DEL: u8 = 0x7f;
+
+/// Longest parameters of a sequence kept, a longer sequence is dropped
+pub const MAX_CSI: usize = 16;
+
+/// Where the editor is in the input
+#[derive(Debug, Clone, PartialEq)]
+enum State {
+    Text,
+    /// After a carriage return, a line feed following it is dropped
+    Return,
+    /// After ESC
+    Escape,
+    /// After ESC [, the parameters of the sequence so far
+    Csi(Data),
+    /// After ESC O
+    Ss3,
+}
+
+/// An edit of the line
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Edit {
+    Insert(u8),
+    Backspace,
+    Delete,
+    Left,
+    Right,
+    Home,
+    End,
+    KillWord,
+    KillLine,
+    Earlier,
+    Later,
+}
+
+/// What an input did
+#[derive(Debug, Default, PartialEq)]
+pub struct Edited {
+    /// The lines completed
+    pub lines: Vec<Data>,
+    /// Renders the edits on the terminal of the client
+    pub echo: Data,
+}
+
+/// Edits the line an ssh client types
+#[derive(Debug, Clone)]
+pub struct LineEditor {
+    line: Data,
+    /// Position of the cursor in the line (in bytes, on a character boundary)
+    cursor: usize,
+    state: State,
+    /// The lines completed, the latest last
+    history: VecDeque<Data>,
+    depth: usize,
+    /// The line of the history recalled and the line typed before, while
+    /// the player goes through the history
+    recalled: Option<(usize, Data)>,
+}
+
+impl Default for LineEditor {
+    fn default() -> LineEditor {
+        LineEditor::new(0)
+    }
+}
+
+impl LineEditor {
+    /// Create a line editor keeping the given number of lines in its history
+    pub fn new(depth: usize) -> LineEditor {
+        LineEditor {
+            line: Data::new(),
+            cursor: 0,
+            state: State::Text,
+            history: VecDeque::new(),
+            depth,
+            recalled: None,
+        }
+    }
+
+    /// True if nothing was typed since the last line
+    pub fn is_empty(&self) -> bool {
+        self.line.is_empty()
+    }
+
+    /// Edit the line with the input of the client
+    ///
+    /// Returns the lines completed by the input and the echo, the rest of the
+    /// line is kept for the next call.
+    pub fn feed(&mut self, input: &[u8]) -> Edited {
+        let mut edited = Edited::default();
+        for &b in input {
+            let (state, edit) = match (std::mem::replace(&mut self.state, State::Text), b) {
+                (State::Escape, b'[') => (State::Csi(Data::new()), None),
+                (State::Escape, b'O') => (State::Ss3, None),
+                (State::Escape, _) => (State::Text, None),
+                // Parameters and intermediate bytes, the final byte ends the sequence
+                (State::Csi(parameters), 0x20..=0x3f) if parameters.len() >= MAX_CSI => (State::Text, None),
+                (State::Csi(mut parameters), 0x20..=0x3f) => {
+                    parameters.push(b);
+                    (State::Csi(parameters), None)
+                },
+                (State::Csi(parameters), b) => (State::Text, csi(&parameters, b)),
+                (State::Ss3, b) => (State::Text, csi(&[], b)),
+                (State::Return, b'\n') => (State::Text, None),
+                (_, b'\r') | (_, b'\n') => {
+                    let line = std::mem::take(&mut self.line);
+                    self.remember(&line);
+                    edited.lines.push(line);
+                    edited.echo.extend_from_slice(b"\r\n");
+                    self.cursor = 0;
+                    (if b == b'\r' { State::Return } else { State::Text }, None)
+                },
+                (_, ESC) => (State::Escape, None),
+                (_, DEL) | (_, CTRL_H) => (State::Text, Some(Edit::Backspace)),
+                (_, CTRL_A) => (State::Text, Some(Edit::Home)),
+                (_, CTRL_E) => (State::Text, Some(Edit::End)),
+                (_, CTRL_U) => (State::Text, Some(Edit::KillLine)),
+                (_, CTRL_W) => (State::Text, Some(Edit::KillWord)),
+                (_, b) if b < 0x20 => (State::Text, None),
+                (_, b) => (State::Text, Some(Edit::Insert(b))),
+            };
+            self.state = state;
+            if let Some(edit) = edit {
+                self.apply(edit, &mut edited.echo);
+            }
+        }
+        edited
+    }
+
+    /// Apply an edit to the line, rendering it to the echo
+    fn apply(&mut self, edit: Edit, echo: &mut Data) {
+        let before = self.cursor;
+        let (from, to) = match edit {
+            Edit::Insert(b) => {
+                if self.line.len() >= MAX_LINE {
+                    return;
+                }
+                self.line.insert(self.cursor, b);
+                self.cursor += 1;
+                // A character is rendered once all of its bytes are in
+                let start = self.previous(self.cursor);
+                if self.cursor - start < char_len(self.line[start]) {
+                    return;
+                }
+                echo.extend_from_slice(&self.line[start..]);
+                move_cursor(echo, -(columns(&self.line[self.cursor..]) as isize));
+                return;
+            },
+            Edit::Left => {
+                self.cursor = self.previous(self.cursor);
+                move_cursor(echo, -(columns(&self.line[self.cursor..before]) as isize));
+                return;
+            },
+            Edit::Right => {
+                self.cursor = self.next(self.cursor);
+                move_cursor(echo, columns(&self.line[before..self.cursor]) as isize);
+                return;
+            },
+            Edit::Home => {
+                self.cursor = 0;
+                move_cursor(echo, -(columns(&self.line[..before]) as isize));
+                return;
+            },
+            Edit::End => {
+                self.cursor = self.line.len();
+                move_cursor(echo, columns(&self.line[before..]) as isize);
+                return;
+            },
+            Edit::Earlier | Edit::Later => {
+                let recalled = match (edit, &self.recalled) {
+                    (Edit::Earlier, None) => self.history.len().checked_sub(1),
+                    (Edit::Earlier, Some((i, _))) => i.checked_sub(1),
+                    (_, Some((i, _))) => Some(i + 1),
+                    (_, None) => None,
+                };
+                let line = match recalled {
+                    Some(i) if i < self.history.len() => {
+                        let typed = self.recalled.take().map_or_else(|| self.line.clone(), |(_, typed)| typed);
+                        self.recalled = Some((i, typed));
+                        self.history[i].clone()
+                    },
+                    // Later than the latest line is the line typed before
+                    Some(_) => match self.recalled.take() {
+                        Some((_, typed)) => typed,
+                        None => return,
+                    },
+                    None => return,
+                };
+                self.replace(line, echo);
+                return;
+            },
+            Edit::Backspace => (self.previous(self.cursor), self.cursor),
+            Edit::Delete => (self.cursor, self.next(self.cursor)),
+            Edit::KillLine => (0, self.cursor),
+            Edit::KillWord => {
+                let line = &self.line[..self.cursor];
+                let end = line.iter().rposition(|b| *b != b' ').map_or(0, |p| p + 1);
+                (line[..end].iter().rposition(|b| *b == b' ').map_or(0, |p| p + 1), self.cursor)
+            },
+        };
+        if from == to {
+            return;
+        }
+        // Redraw the line from the first character removed, blanking the
+        // columns it got shorter
+        let removed = columns(&self.line[from..to]);
+        move_cursor(echo, -(columns(&self.line[from..before]) as isize));
+        self.line.drain(from..to);
+        self.cursor = from;
+        echo.extend_from_slice(&self.line[from..]);
+        echo.extend(std::iter::repeat_n(b' ', removed));
+        move_cursor(echo, -((columns(&self.line[from..]) + removed) as isize));
+    }
+
+    /// Replace the line (eg. with a line of the history), the cursor at its
+    /// end
+    fn replace(&mut self, line: Data, echo: &mut Data) {
+        let shown = columns(&self.line);
+        move_cursor(echo, -(columns(&self.line[..self.cursor]) as isize));
+        self.line = line;
+        self.cursor = self.line.len();
+        echo.extend_from_slice(&self.line);
+        let blanks = shown.saturating_sub(columns(&self.line));
+        echo.extend(std::iter::repeat_n(b' ', blanks));
+        move_cursor(echo, -(blanks as isize));
+    }
+
+    /// Add a completed line to the history
+    fn remember(&mut self, line: &[u8]) {
+        self.recalled = None;
+        if self.depth == 0 || line.is_empty() || self.history.back().is_some_and(|l| l == line) {
+            return;
+        }
+        if self.history.len() == self.depth {
+            self.history.pop_front();
+        }
+        self.history.push_back(line.to_vec());
+    }
+
+    /// True if a position of the line is the start of a character (or the
+    /// end of the line)
+    fn is_boundary(&self, position: usize) -> bool {
+        self.line.get(position).is_none_or(|b| !is_continuation(*b))
+    }
+
+    /// The start of the character before a position
+    fn previous(&self, position: usize) -> usize {
+        let mut position = position.saturating_sub(1);
+        while position > 0 && !self.is_boundary(position) {
+            position -= 1;
+        }
+        position
+    }
+
+    /// The start of the character after a position
+    fn next(&self, position: usize) -> usize {
+        let mut position = (position + 1).min(self.line.len());
+        while !self.is_boundary(position) {
+            position += 1;
+        }
+        position
+    }
+}
+
+/// The edit of a control sequence (ESC [ or ESC O, the parameters and the
+/// final byte), if any
+fn csi(parameters: &[u8], last: u8) -> Option<Edit> {
+    match (parameters, last) {
+        (_, b'A') => Some(Edit::Earlier),
+        (_, b'B') => Some(Edit::Later),
+        (_, b'D') => Some(Edit::Left),
+        (_, b'C') => Some(Edit::Right),
+        (_, b'H') | (b"1", b'~') | (b"7", b'~') => Some(Edit::Home),
+        (_, b'F') | (b"4", b'~') | (b"8", b'~') => Some(Edit::End),
+        (b"3", b'~') => Some(Edit::Delete),
+        _ => None,
+    }
+}
+
+/// True if the input is the up or down arrow, recalling the history
+pub fn recalls(input: &[u8]) -> bool {
+    matches!(input, b"\x1b[A" | b"\x1b[B" | b"\x1bOA" | b"\x1bOB")
+}
+
+/// The bytes following the first byte of a character in UTF-8
+fn is_continuation(b: u8) -> bool {
+    b & 0xc0 == 0x80
+}
+
+/// The number of bytes of the character a byte starts in UTF-8
+fn char_len(first: u8) -> usize {
+    match first {
+        0xf0..=0xff => 4,
+        0xe0..=0xef => 3,
+        0xc0..=0xdf => 2,
+        _ => 1,
+    }
+}
+
+/// The columns the text takes on the terminal (one per character)
+fn columns(text: &[u8]) -> usize {
+    text.iter().filter(|b| !is_continuation(**b)).count()
+}
+
+/// Move the cursor of the terminal by the given columns (to the left if
+/// negative)
+fn move_cursor(echo: &mut Data, columns: isize) {
+    match columns {
+        0 => {},
+        c if c < 0 => echo.extend_from_slice(format!("\x1b[{}D", -c).as_bytes()),
+        c => echo.extend_from_slice(format!("\x1b[{}C", c).as_bytes()),
+    }
+}
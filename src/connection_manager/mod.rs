@@ -1,22 +1,172 @@
 //! Module for Infrastructure Elements
 //!
-//! TODO.
+//! The connection handlers are the frontends of the world: the ssh server
+//! (see `ssh_server`) and the telnet server (see `telnet_server`). The world
+//! does not depend on the transport of a client. It knows a client by its id
+//! and reaches it through its connection (see `ClientConnection`),
+//! registered with `Command::Register`. The handlers of both servers pass a
+//! `PushConnection`: the handler forwards what it gets on the push channel
+//! to the client and closes the connection on `Push::Close`. A new frontend
+//! (eg. websockets) registers its clients the same way, and tests drive the
+//! world with a plain push channel, without a server.
 pub mod ssh_server;
-//pub mod telnet_server;
+pub mod channels;
+pub mod buffers;
+pub mod keyring;
+pub mod authorized_keys;
+pub mod tokens;
+#[cfg(any(test, feature = "chaos"))]
+pub mod chaos;
+pub mod telnet_server;
+pub mod line_editor;
+
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use futures::future::{BoxFuture, FutureExt};
 
 /// A type for client ids
 pub type ClientId = usize;
+
+/// Hands out the client ids
+///
+/// Clones share the counter, so the clients of the ssh and the telnet server
+/// never get the same id.
+#[derive(Debug, Clone, Default)]
+pub struct ClientIds {
+    next: Arc<AtomicUsize>,
+}
+
+impl ClientIds {
+    /// The id of the next client
+    pub fn next(&self) -> ClientId {
+        self.next.fetch_add(1, Ordering::Relaxed)
+    }
+}
 /// A type for data
 pub type Data = Vec<u8>;
 
+/// Output pushed to a client
+///
+/// Plain bytes, so the world does not depend on the transport. The buffers
+/// come from a pool (see `buffers`).
+pub type Output = Vec<u8>;
+
 /// Types for valid commands sent over the command channel from a connection
 /// handler to the world.
-#[derive(Clone)]
+///
+/// The command channel is the control lane to the world: the world handles
+/// all pending commands before it turns to the data of the players, so a
+/// flood of player actions cannot delay them.
 pub enum Command {
-    /// Command to register new client and the communication channel to it
-    Register(ClientId, String, thrussh::ChannelId, thrussh::server::Handle),
+    /// Command to register new client, its handle and the connection to it
+    Register(ClientId, String, Box<dyn ClientConnection>),
     /// Client request to terminate session
     Hangup(ClientId),
+    /// Admin command (a line starting with "@") sent by a client
+    Admin(DataMessage),
+    /// News from the link to the peer world (see `federation`)
+    Federation(crate::federation::Incoming),
+    /// A message written in the Matrix room (see `matrix`)
+    Matrix(crate::matrix::Incoming),
+    /// A player arrives from another world of the server, before it is
+    /// registered with the world (see `worlds`)
+    Arrival(crate::federation::Traveler),
+}
+
+/// How a client is connected
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Transport {
+    Ssh,
+    Telnet,
+}
+
+impl fmt::Display for Transport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Transport::Ssh => write!(f, "ssh"),
+            Transport::Telnet => write!(f, "telnet"),
+        }
+    }
+}
+
+/// What the world knows about a client, besides its id and handle
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClientInfo {
+    pub transport: Transport,
+    /// True if the client proved its handle with a key assigned or bound to
+    /// it (see `keyring`). Only then the client gets the role of the handle.
+    pub verified: bool,
+}
+
+/// The connection to a client, as the world sees it
+///
+/// The world reaches a client only through its connection, it never talks
+/// to the transport.
+pub trait ClientConnection: fmt::Debug + Send + Sync {
+    /// What the world knows about the client
+    fn info(&self) -> &ClientInfo;
+
+    /// Send data or an animation to the client
+    fn send(&self, push: Push) -> BoxFuture<'_, Result<(), channels::SendError>>;
+
+    /// Close the connection, after what was sent before
+    fn close(&self) -> BoxFuture<'_, Result<(), channels::SendError>>;
+}
+
+/// A connection over a push channel, the handler of the transport forwards
+/// the pushes to the client
+#[derive(Debug)]
+pub struct PushConnection {
+    info: ClientInfo,
+    push_tx: channels::MeteredSender<Push>,
+}
+
+impl PushConnection {
+    /// Create a connection over the given push channel
+    pub fn new(info: ClientInfo, push_tx: channels::MeteredSender<Push>) -> PushConnection {
+        PushConnection { info, push_tx }
+    }
+}
+
+impl ClientConnection for PushConnection {
+    fn info(&self) -> &ClientInfo {
+        &self.info
+    }
+
+    fn send(&self, push: Push) -> BoxFuture<'_, Result<(), channels::SendError>> {
+        self.push_tx.send(push).boxed()
+    }
+
+    fn close(&self) -> BoxFuture<'_, Result<(), channels::SendError>> {
+        self.push_tx.send(Push::Close).boxed()
+    }
+}
+
+/// Types for messages pushed over the push channel from the world to a
+/// connection handler.
+///
+/// Every client has its own push channel. The connection handler forwards
+/// everything it receives to the client, so the world can reach a client at
+/// any time, not only in response to data the client sent.
+#[derive(Debug, Clone)]
+pub enum Push {
+    /// Data to be sent to the client
+    Data(Output),
+    /// Frames of an animation to be played to the client
+    Animation(Arc<[Frame]>),
+    /// Close the connection to the client
+    Close,
+}
+
+/// A frame of an animation
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    /// The data of the frame (eg. ANSI art)
+    pub data: Data,
+    /// How long the frame is shown before the next frame
+    pub delay: Duration,
 }
 
 #[derive(Clone)]
@@ -1,6 +1,8 @@
 
 use futures;
 use tokio;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
 use std::pin::Pin;
 use std::sync::Arc;
 use thrussh::*;
@@ -9,28 +11,44 @@ use thrussh::server::{Auth, Session};
 use tracing::{instrument, debug, error, info, warn};
 use futures::FutureExt;
 use anyhow;
-use tokio::sync::mpsc;
-use tokio::sync::mpsc::{Receiver, Sender};
-use super::{Command, Data, DataMessage};
+use super::{buffers, ClientIds, ClientInfo, Command, Data, DataMessage, Frame, Push, PushConnection, Transport};
+use super::keyring::Keyring;
+use super::line_editor::{self, LineEditor};
+use super::telnet_server::TelnetServer;
+use super::tokens::{LoginTokens, TOKEN_PREFIX};
+use super::channels::{self, ChannelStats, MeteredReceiver, MeteredSender, Metrics, SendError};
 use termion::color;
 
 
 #[derive(Clone, Debug)]
 pub struct Server {
     client_id: usize,
+    client_ids: ClientIds,
     client_username: Option<String>,
+    // True if the key is assigned or bound to the player
+    verified: bool,
     echo: bool,
-    data_buffer: Data,
-    tx_data_channel: Sender<DataMessage>,
-    tx_command_channel: Sender<Command>, 
-    server_allowed_keys: Vec<String>,
+    editor: LineEditor,
+    tx_data_channel: MeteredSender<DataMessage>,
+    tx_command_channel: MeteredSender<Command>, 
+    push_stats: Arc<ChannelStats>,
+    #[cfg(feature = "chaos")]
+    chaos: super::chaos::Chaos,
+    server_allowed_keys: Keyring,
+    // The handles of the staff, only allowed with a key assigned or bound to
+    // them
+    staff: Arc<Vec<String>>,
+    login_tokens: LoginTokens,
+    // The user name carrying a login token and the key it logs in with,
+    // until the session starts
+    pending_token: Option<(String, String)>,
 }
 
 impl server::Server for Server {
     type Handler = Self;
     fn new(&mut self, _: Option<std::net::SocketAddr>) -> Self {
-        let s = self.clone();
-        self.client_id += 1;
+        let mut s = self.clone();
+        s.client_id = self.client_ids.next();
         s
     }
 }
@@ -59,12 +77,40 @@ impl server::Handler for Server {
         //TODO - no verification yet implemented
         debug!("Server {}: Authenticating user {} with method public key.", self.client_id, user);
         debug!("Public Key is: {:?} with fingerprint {:?}", pubkey, pubkey.fingerprint());
+        let key = pubkey.public_key_base64();
+        // The token is only used up once the client proved it has the key
+        if user.starts_with(TOKEN_PREFIX) {
+            return match self.login_tokens.peek(user) {
+                Some(player) => {
+                    info!("Accepted login token for {}.", player);
+                    self.pending_token = Some((user.to_string(), key));
+                    self.client_username = Some(player);
+                    self.verified = true;
+                    futures::future::ready(Ok((self, server::Auth::Accept)))
+                },
+                None => {
+                    info!("Authentication by login token failed: Token unknown or expired.");
+                    futures::future::ready(Ok((self, server::Auth::Reject)))
+                },
+            };
+        }
+        // The world a player chooses at login is passed on to the router
+        // (see `worlds`), the key belongs to the player
         self.client_username = Some(user.to_string());
-        for key in &self.server_allowed_keys {
-            if key.eq_ignore_ascii_case(pubkey.public_key_base64().as_str()) {
-                info!("Successfully authenticated {} by public key.", user);
-                return futures::future::ready(Ok((self, server::Auth::Accept)));
-            }
+        let (player, _) = crate::worlds::split_login(user);
+        let staff = self.staff.iter().any(|s| s.eq_ignore_ascii_case(player));
+        if staff && !self.server_allowed_keys.verifies(&key, player) {
+            info!("Authentication by public key for {} failed: The key is not assigned to the staff handle.", user);
+            return futures::future::ready(Ok((self, server::Auth::Reject)));
+        }
+        if self.server_allowed_keys.allows(&key, player) {
+            info!("Successfully authenticated {} by public key.", user);
+            self.verified = self.server_allowed_keys.verifies(&key, player);
+            return futures::future::ready(Ok((self, server::Auth::Accept)));
+        }
+        if let Some(owner) = self.server_allowed_keys.owner(&format!("SHA256:{}", pubkey.fingerprint())) {
+            info!("Authentication by public key for {} failed: The key is assigned to {}.", user, owner);
+            return futures::future::ready(Ok((self, server::Auth::Reject)));
         }
         info!("Authentication by public key for {} failed: Identity not found.", user);
         futures::future::ready(Ok((self, server::Auth::Reject)))
@@ -76,15 +122,36 @@ impl server::Handler for Server {
         futures::future::ready(Ok((self, server::Auth::Reject)))
     }
 
-    fn channel_open_session(self, channel: ChannelId, mut session: Session) -> Self::FutureUnit {
-        let handle = session.handle().clone();
-        let registration_command = Command::Register(self.client_id, self.client_username.clone().unwrap(), channel, handle);
+    fn channel_open_session(mut self, channel: ChannelId, mut session: Session) -> Self::FutureUnit {
+        // Bind the key a login token was used with to the player
+        let mut binding = None;
+        if let Some((user, key)) = self.pending_token.take() {
+            match self.login_tokens.redeem(&user) {
+                Some(player) => binding = Some((key, player)),
+                None => {
+                    session.data(channel, CryptoVec::from_slice(b"Your login token expired.\r\n"));
+                    session.close(channel);
+                    return self.finished(session);
+                },
+            }
+        }
+        let push_tx = spawn_push_task(self.client_id, channel, session.handle(), &self.push_stats,
+            #[cfg(feature = "chaos")] self.chaos.clone());
+        let info = ClientInfo { transport: Transport::Ssh, verified: self.verified };
+        let connection = Box::new(PushConnection::new(info, push_tx));
+        let registration_command = Command::Register(self.client_id, self.client_username.clone().unwrap(), connection);
         async move {
-            // Register client with the world - pass the handle to world thread
+            if let Some((key, player)) = binding {
+                if let Err(e) = self.server_allowed_keys.bind(&key, &player).await {
+                    error!("Could not bind the key of {}: {}", player, e);
+                }
+            }
+
+            // Register client with the world - pass the connection to world thread
             //
             // This needs to be done to enable the world thread to send data to the
             // ssh user (eg. a description or a result).
-            if let Err(_) = self.tx_command_channel.send(registration_command).await {
+            if self.tx_command_channel.send(registration_command).await.is_err() {
                 error!("channel_open_session(): receiver dropped");
             } else {
                 debug!("channel_open_session(): Sent client id and handle to world.")
@@ -97,58 +164,77 @@ impl server::Handler for Server {
     }
 
     fn data(mut self, channel: ChannelId, data: &[u8], mut session: server::Session) -> Self::FutureUnit { 
-        //Check if the data contains a CR, which is the indicator that the command
-        //should either be processed by the ssh server or be sent to the world.
-        let process_condition = data.as_ref() == "\u{000d}".as_bytes();
-        let mut data_to_send = None;
-
-        // If echo is on, then echo the received data back to the client
-        // TODO - properly process deltion. Maybe add cursor movement and line editing.
-        if self.echo {
-            // We need to fix CR/LF as we only receive a CR when the user hits enter.
-            // If we would not do this, then the next message sent to the client will
-            // overwrite the echoed command (as the cursor is simply moved to the 
-            // beginning of the line).
-            if process_condition {
-                session.data(channel, CryptoVec::from_slice("\r\n".as_ref()));
-            } else {
-                session.data(channel, CryptoVec::from_slice(data.clone()));
-            }
-        }
-     
-        // If CR was not hit, we append to the buffer. Otherwise we process the
-        // buffer.
-        if !process_condition {
-            self.data_buffer.extend_from_slice(data);
+        let mut data_to_send = Vec::new();
+
+        // A key (eg. a button of the badge) pressed at the start of a line is
+        // sent to the world on its own, it runs the command bound to it.
+        // Otherwise the input edits the line (see `line_editor`). With echo
+        // on, the up and down arrows recall the history instead, as the
+        // player sees the line recalled.
+        let key_pressed = is_key(data) && !(self.echo && line_editor::recalls(data));
+        let lines = if self.editor.is_empty() && key_pressed {
+            data_to_send.push(data.to_vec());
+            Vec::new()
         } else {
+            let edited = self.editor.feed(data);
+            // If echo is on, render the edits. A completed line ends with
+            // CR LF, so the next message sent to the client does not
+            // overwrite the echoed command.
+            if self.echo && !edited.echo.is_empty() {
+                session.data(channel, CryptoVec::from_slice(&edited.echo));
+            }
+            edited.lines
+        };
+
+        for line in lines {
             // Evaluate if we deal with a command to the ssh server. If not,
             // send the data command to the world.
             // Currently there is only one server command implemented: Echo
             // TODO - implement hangup command
-            if self.data_buffer.eq_ignore_ascii_case(b"echo on") {
+            if line.eq_ignore_ascii_case(b"echo on") {
                 self.echo = true;
-            } else if self.data_buffer.eq_ignore_ascii_case(b"echo off") {
+            } else if line.eq_ignore_ascii_case(b"echo off") {
                 self.echo = false;
-            } else if self.data_buffer.eq_ignore_ascii_case(b"echo") {
+            } else if line.eq_ignore_ascii_case(b"echo") {
                 self.echo = !self.echo;
             } else {
                 // We have a data messge that we need to send to the world
-                data_to_send = Some(self.data_buffer.clone());
+                data_to_send.push(line);
             }
-            // Data message was processed. Purge the buffer.
-            self.data_buffer.clear();
         }
 
+        // Admin commands of the staff take the control lane. Those of the
+        // players go with the data, or a flood of them would starve the world,
+        // which handles commands first.
+        let staff = self.is_staff();
+        let (admin_commands, data_to_send): (Vec<Data>, Vec<Data>) =
+            data_to_send.into_iter().partition(|data| staff && data.starts_with(b"@"));
+
         let tx = self.tx_data_channel.clone();
         async move {
-            match data_to_send {
-                Some(data) => {
-                    let data_message = DataMessage::new(self.client_id, data);
-                    if let Err(_) = tx.send(data_message).await { 
-                        println!("data(): receiver dropped");
-                    };
-                },
-                None => {}
+            for data in admin_commands {
+                let command = Command::Admin(DataMessage::new(self.client_id, data));
+                match self.tx_command_channel.send(command).await {
+                    Ok(_) => {},
+                    Err(SendError::Full) => {
+                        warn!("Command channel full. Dropped admin command of client {}.", self.client_id);
+                        session.data(channel, CryptoVec::from_slice(
+                            "The matrix is overloaded. Your command was lost, try again.\r\n".as_ref()));
+                    },
+                    Err(SendError::Closed) => error!("data(): receiver dropped"),
+                };
+            }
+            for data in data_to_send {
+                let data_message = DataMessage::new(self.client_id, data);
+                match tx.send(data_message).await {
+                    Ok(_) => {},
+                    Err(SendError::Full) => {
+                        warn!("Data channel full. Dropped data of client {}.", self.client_id);
+                        session.data(channel, CryptoVec::from_slice(
+                            "The matrix is overloaded. Your command was lost, try again.\r\n".as_ref()));
+                    },
+                    Err(SendError::Closed) => error!("data(): receiver dropped"),
+                };
             }
             Ok((self, session))
         }.boxed()
@@ -160,39 +246,225 @@ impl server::Handler for Server {
     }
 }
 
-#[instrument]
-pub fn init_ssh_server(allowed_keys: Vec<String>) -> (Server, Arc<thrussh::server::Config>,
-                             Receiver<DataMessage>, Receiver<Command>) {
+/// Check if data was sent by a key instead of typed text
+/// 
+/// Keys send escape sequences (eg. ESC[A for up) or single control bytes.
+/// The CR ending a line is not a key.
+fn is_key(data: &[u8]) -> bool {
+    match data {
+        [0x1b, ..] => true,
+        [b] => *b < 0x20 && *b != b'\r' && *b != b'\n',
+        _ => false,
+    }
+}
+
+/// Spawn the task forwarding pushes from the world to a client
+///
+/// Returns the sending side of the push channel of the client. The task ends
+/// when the connection is closed or when the world drops the sender.
+fn spawn_push_task(client_id: usize, channel: ChannelId, mut handle: server::Handle, 
+                   stats: &Arc<ChannelStats>, 
+                   #[cfg(feature = "chaos")] chaos: super::chaos::Chaos) -> MeteredSender<Push> {
+    let (push_tx, mut push_rx) = channels::channel(stats);
+    tokio::spawn(async move {
+        #[cfg(feature = "chaos")]
+        let mut rng = super::chaos::Rng::new(client_id as u64);
+        while let Some(push) = push_rx.recv().await {
+            #[cfg(feature = "chaos")]
+            match chaos.disrupt(&mut rng) {
+                super::chaos::Disruption::None => {},
+                super::chaos::Disruption::Delay(delay) => tokio::time::sleep(delay).await,
+                super::chaos::Disruption::Drop => {
+                    debug!("Chaos: dropping push to client {}.", client_id);
+                    continue;
+                },
+                super::chaos::Disruption::Sever => {
+                    warn!("Chaos: severing connection to client {}.", client_id);
+                    let _ = handle.close(channel).await;
+                    break;
+                },
+            }
+            match push {
+                Push::Data(data) => {
+                    let cryptovec = CryptoVec::from_slice(&data);
+                    buffers::give(data);
+                    if handle.data(channel, cryptovec).await.is_err() {
+                        debug!("Client {} is gone. Stop pushing.", client_id);
+                        break;
+                    }
+                },
+                Push::Animation(frames) => {
+                    if !play(&mut handle, channel, &frames).await {
+                        debug!("Client {} is gone. Stop pushing.", client_id);
+                        break;
+                    }
+                },
+                Push::Close => {
+                    if handle.close(channel).await.is_err() {
+                        debug!("Client {} is already gone.", client_id);
+                    }
+                    break;
+                },
+            }
+        }
+    });
+    push_tx
+}
+
+/// Play an animation to a client
+///
+/// Every frame is shown for its delay. If sending a frame takes longer than
+/// its delay, the link is too slow for the animation and the final frame is
+/// sent right away. Returns false if the client is gone.
+async fn play(handle: &mut server::Handle, channel: ChannelId, frames: &[Frame]) -> bool {
+    for (i, frame) in frames.iter().enumerate() {
+        let start = tokio::time::Instant::now();
+        if handle.data(channel, CryptoVec::from_slice(&frame.data)).await.is_err() {
+            return false;
+        }
+        let last = match frames.last() {
+            Some(last) if i + 1 < frames.len() => last,
+            _ => break,
+        };
+        if start.elapsed() > frame.delay {
+            debug!("Link too slow for the animation. Skipping to the final frame.");
+            return handle.data(channel, CryptoVec::from_slice(&last.data)).await.is_ok();
+        }
+        tokio::time::sleep_until(start + frame.delay).await;
+    }
+    true
+}
+
+/// Load the host key of the server
+///
+/// The ed25519 key is read from the given path. On the first start there is
+/// none yet: a new key is generated and saved there, readable by the owner
+/// only, so clients see the same host key after every restart.
+pub fn load_host_key(path: &Path) -> io::Result<key::KeyPair> {
+    match std::fs::read_to_string(path) {
+        Ok(pem) => return decode_secret_key(&pem, None)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), e))),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {},
+        Err(e) => return Err(e),
+    }
+    let key = key::KeyPair::generate_ed25519()
+        .ok_or_else(|| io::Error::other("Could not generate a host key."))?;
+    let mut pem = Vec::new();
+    encode_pkcs8_pem(&key, &mut pem).map_err(|e| io::Error::other(e.to_string()))?;
+    if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(path)?.write_all(&pem)?;
+    info!("Generated a new host key at {}.", path.display());
+    Ok(key)
+}
+
+#[instrument(skip(host_key))]
+pub fn init_ssh_server(host_key: key::KeyPair, keyring: Keyring, metrics: &Metrics)
+                       -> (Server, Arc<thrussh::server::Config>,
+                           MeteredReceiver<DataMessage>, MeteredReceiver<Command>) {
     // Configure the server
-    let mut config = thrussh::server::Config::default();
-    config.methods = MethodSet::PUBLICKEY | MethodSet::PASSWORD;
-    config.connection_timeout = Some(std::time::Duration::from_secs(600));
-    config.auth_rejection_time = std::time::Duration::from_secs(3);
-    config.keys.push(thrussh_keys::key::KeyPair::generate_ed25519().unwrap());
-    config.auth_banner = None;
+    let config = thrussh::server::Config {
+        methods: MethodSet::PUBLICKEY | MethodSet::PASSWORD,
+        connection_timeout: Some(std::time::Duration::from_secs(600)),
+        auth_rejection_time: std::time::Duration::from_secs(3),
+        keys: vec![host_key],
+        auth_banner: None,
+        ..Default::default()
+    };
     let config = Arc::new(config);
 
     // The data channel: The channel players use to send actions etc....
-    let (data_tx, data_rx) = mpsc::channel(1_024);
+    let (data_tx, data_rx) = channels::channel(&metrics.data);
 
     // The command channel: The channel used to send requests from the session to the world
-    let (command_tx, command_rx) = mpsc::channel(1_024);
+    let (command_tx, command_rx) = channels::channel(&metrics.command);
 
 
     // Create the server
     let sh = Server{
         client_username: None,
+        verified: false,
         client_id: 0,
+        client_ids: ClientIds::default(),
         echo: false,
-        data_buffer: Data::new(),
+        editor: LineEditor::default(),
         tx_data_channel: data_tx.clone(),
         tx_command_channel: command_tx.clone(),
-        server_allowed_keys: allowed_keys,
+        push_stats: metrics.push.clone(),
+        #[cfg(feature = "chaos")]
+        chaos: super::chaos::Chaos::default(),
+        server_allowed_keys: keyring,
+        staff: Arc::new(Vec::new()),
+        login_tokens: LoginTokens::new(std::time::Duration::from_secs(15 * 60)),
+        pending_token: None,
     };
 
     (sh, config, data_rx, command_rx)
 }
 
+impl Server {
+    /// A sender on the command channel to the world (eg. for the federation
+    /// link)
+    pub fn command_sender(&self) -> MeteredSender<Command> {
+        self.tx_command_channel.clone()
+    }
+
+    /// The keys allowed to connect (eg. to add provisioned keys)
+    pub fn keyring(&self) -> Keyring {
+        self.server_allowed_keys.clone()
+    }
+
+    /// Set the handles of the staff, which may only connect with a key
+    /// assigned or bound to them (see `Keyring::verifies`)
+    pub fn set_staff(&mut self, staff: Vec<String>) {
+        self.staff = Arc::new(staff);
+    }
+
+    /// Set the login tokens redeemed by the server
+    pub fn set_login_tokens(&mut self, login_tokens: LoginTokens) {
+        self.login_tokens = login_tokens;
+    }
+
+    /// A telnet server feeding the same channels to the world, sharing the
+    /// client ids, the keys and the login tokens (see `telnet_server`)
+    ///
+    /// The staff may only connect by ssh, the players with a stored account
+    /// in the data directory of the main world or of the hosted world they
+    /// choose (by name) by ssh or with a login token.
+    pub fn telnet_server(&self, data_dir: &Path, worlds: Vec<(String, PathBuf)>) -> TelnetServer {
+        TelnetServer::new(self.client_ids.clone(), self.tx_data_channel.clone(), self.tx_command_channel.clone(),
+            self.push_stats.clone(), self.server_allowed_keys.clone(), self.login_tokens.clone(), self.staff.to_vec(),
+            data_dir, worlds)
+    }
+}
+
+impl Server {
+    /// Keep the given number of commands in the history of every session
+    pub fn set_history_depth(&mut self, depth: usize) {
+        self.editor = LineEditor::new(depth);
+    }
+
+    /// True if the client proved a handle of the staff with its key
+    fn is_staff(&self) -> bool {
+        let player = self.client_username.as_deref().map(|u| crate::worlds::split_login(u).0);
+        self.verified && player.is_some_and(|p| self.staff.iter().any(|s| s.eq_ignore_ascii_case(p)))
+    }
+}
+
+#[cfg(feature = "chaos")]
+impl Server {
+    /// Disrupt the pushes to the clients (see `chaos`)
+    pub fn set_chaos(&mut self, chaos: super::chaos::Chaos) {
+        self.chaos = chaos;
+    }
+}
+
+#[allow(dead_code)]
 #[derive(Debug, Clone)]
 pub struct SSHKey {
     pub algorithm: String,
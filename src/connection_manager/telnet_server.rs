@@ -1,284 +1,366 @@
-extern crate futures;
-extern crate tokio;
-use std::sync::{Mutex, Arc};
-use std::collections::HashMap;
-
-
-use ansi_term::Colour;
-use ansi_term::Style;
-use anyhow;
-use futures::Future;
-use tokio::net::tcp::WriteHalf;
-use crate::world::states::ScreenType;
-use tokio::sync::mpsc;
-use tokio::sync::mpsc::{Receiver, Sender};
-
-
-
-#[derive(Clone)]
-pub struct TelnetServer {
-    clients: Arc<Mutex<HashMap<(usize, ChannelId), thrussh::server::Handle>>>,
-    id: usize,
-    tx_data_channel: Arc<Mutex<Sender<String>>>,
-    tx_command_channel: Arc<Mutex<Sender<WriteHalf>>>, // TODO use a type for commands
-    command_buffer: String,
+//! Telnet server
+//!
+//! Serves the players without ssh keys over plain telnet, if enabled in the
+//! settings (`[telnet_server]`). A client logs in by typing its handle (or
+//! "handle+world" to choose a world, see `worlds`) or a login token (see
+//! `tokens`) at the prompt. From then on every line it sends goes to the
+//! world over the same data channel as the lines of ssh clients, and the
+//! world pushes its output over a push channel per client.
+//!
+//! Telnet carries no identity and is not encrypted: the handles of the staff
+//! and the handles a key is bound to (see `keyring`) are refused, these
+//! players connect by ssh. So are the handles of the accounts stored in the
+//! chosen world (ignoring case), anybody could take them over by typing the
+//! handle. Players come back to their account with a login token, which is
+//! used up, but binds no key.
+//!
+//! The server does not negotiate options. The telnet commands a client sends
+//! are dropped from its input, so clients stay in their default line mode
+//! and echo locally.
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::{TcpListener, TcpStream};
+use tracing::{debug, error, info, warn};
+use termion::color;
+
+use super::{buffers, ClientId, ClientIds, ClientInfo, Command, Data, DataMessage, Frame, Push, PushConnection,
+    Transport};
+use super::channels::{self, ChannelStats, MeteredSender, SendError};
+use super::keyring::Keyring;
+use super::tokens::{LoginTokens, TOKEN_PREFIX};
+use crate::world::accounts::Account;
+
+/// Telnet: interpret as command
+const IAC: u8 = 255;
+/// Telnet: first option command (WILL), followed by WONT, DO and DONT
+const WILL: u8 = 251;
+/// Telnet: last option command (DONT)
+const DONT: u8 = 254;
+/// Telnet: subnegotiation begin
+const SB: u8 = 250;
+/// Telnet: subnegotiation end
+const SE: u8 = 240;
+
+/// Longest line accepted, the rest of a longer line is dropped
+pub const MAX_LINE: usize = 1_024;
+
+/// Time a client gets to log in
+const LOGIN_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Prompt for the login
+const LOGIN_PROMPT: &[u8] = b"Handle (or login token): ";
+
+/// Where the decoder is in the input
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum State {
+    Text,
+    /// After a carriage return, a line feed or NUL following it is dropped
+    Return,
+    /// After IAC
+    Command,
+    /// After an option command, the option follows
+    Option,
+    /// In a subnegotiation
+    Subnegotiation,
+    /// After IAC in a subnegotiation
+    SubnegotiationCommand,
 }
 
-#[derive(Debug, Error)]
-pub enum Error {
-
-    /// The protocol is in an inconsistent state.
-    #[error("Inconsistent state of the protocol")]
-    Inconsistent,
-
-    /// Index out of bounds.
-    #[error("Index out of bounds")]
-    IndexOutOfBounds,
-
-    /// Message received/sent on unopened channel.
-    #[error("Channel not open")]
-    WrongChannel,
-
-    /// Disconnected
-    #[error("Disconnected")]
-    Disconnect,
-
-    /// Connection closed by the remote side.
-    #[error("Connection closed by the remote side")]
-    HUP,
-
-    /// Connection timeout.
-    #[error("Connection timeout")]
-    ConnectionTimeout,
-
-    #[error("Channel send error")]
-    SendError,
-
-    #[error("Pending buffer limit reached")]
-    Pending,
-
-    #[error(transparent)]
-    IO(#[from] std::io::Error),
-
-    #[error(transparent)]
-    Utf8(#[from] std::str::Utf8Error),
-
-    #[error(transparent)]
-    Join(#[from] tokio::task::JoinError),
-
-    #[error(transparent)]
-    Elapsed(#[from] tokio::time::error::Elapsed),
+/// Splits the input of a telnet client into lines
+///
+/// Telnet commands and control characters are dropped, a backspace removes
+/// the last byte of the line. Lines end with CR, CR LF, CR NUL or LF.
+#[derive(Debug)]
+pub struct LineDecoder {
+    line: Data,
+    state: State,
 }
 
-/// Server handler. Each client will have their own handler.
-pub trait Handler: Sized {
-    type Error: From<Error> + Send;
-
-    /// The type of units returned by some parts of this handler.
-    type FutureUnit: Future<Output = Result<(Self, Session), Self::Error>> + Send;
-
-    /// The type of future bools returned by some parts of this handler.
-    type FutureBool: Future<Output = Result<(Self, Session, bool), Self::Error>> + Send;
-
-    /// Convert a `bool` to `Self::FutureBool`. This is used to
-    /// produce the default handlers.
-    fn finished_bool(self, b: bool, session: Session) -> Self::FutureBool;
-
-    /// Produce a `Self::FutureUnit`. This is used to produce the
-    /// default handlers.
-    fn finished(self, session: Session) -> Self::FutureUnit;
-
-    /// Called when the client closes a channel.
-    #[allow(unused_variables)]
-    fn channel_close(self, channel: ChannelId, session: Session) -> Self::FutureUnit {
-        self.finished(session)
+impl Default for LineDecoder {
+    fn default() -> LineDecoder {
+        LineDecoder { line: Data::new(), state: State::Text }
     }
+}
 
-    /// Called when the client sends EOF to a channel.
-    #[allow(unused_variables)]
-    fn channel_eof(self, channel: ChannelId, session: Session) -> Self::FutureUnit {
-        self.finished(session)
+impl LineDecoder {
+    /// Decode input of the client
+    ///
+    /// Returns the lines completed by the input, the rest of the input is
+    /// kept for the next call.
+    pub fn feed(&mut self, input: &[u8]) -> Vec<Data> {
+        let mut lines = Vec::new();
+        for &b in input {
+            self.state = match (self.state, b) {
+                (State::Command, IAC) => {
+                    self.push(IAC);
+                    State::Text
+                },
+                (State::Command, WILL..=DONT) => State::Option,
+                (State::Command, SB) => State::Subnegotiation,
+                (State::Command, _) | (State::Option, _) => State::Text,
+                (State::Subnegotiation, IAC) => State::SubnegotiationCommand,
+                (State::Subnegotiation, _) => State::Subnegotiation,
+                (State::SubnegotiationCommand, SE) => State::Text,
+                (State::SubnegotiationCommand, _) => State::Subnegotiation,
+                (_, IAC) => State::Command,
+                (State::Return, b'\n') | (State::Return, 0) => State::Text,
+                (_, b'\r') | (_, b'\n') => {
+                    lines.push(std::mem::take(&mut self.line));
+                    if b == b'\r' { State::Return } else { State::Text }
+                },
+                (_, 0x08) | (_, 0x7f) => {
+                    self.line.pop();
+                    State::Text
+                },
+                (_, b) if b < 0x20 => State::Text,
+                (_, b) => {
+                    self.push(b);
+                    State::Text
+                },
+            };
+        }
+        lines
     }
 
-    /// Called when a new session channel is created.
-    #[allow(unused_variables)]
-    fn channel_open_session(self, channel: ChannelId, session: Session) -> Self::FutureUnit {
-        self.finished(session)
+    fn push(&mut self, b: u8) {
+        if self.line.len() < MAX_LINE {
+            self.line.push(b);
+        }
     }
+}
 
-    /// Called when a new channel is created.
-    #[allow(unused_variables)]
-    fn channel_open_direct_tcpip(
-        self,
-        channel: ChannelId,
-        host_to_connect: &str,
-        port_to_connect: u32,
-        originator_address: &str,
-        originator_port: u32,
-        session: Session,
-    ) -> Self::FutureUnit {
-        self.finished(session)
-    }
+/// The telnet server
+///
+/// Created from the ssh server (see `Server::telnet_server`), so both hand
+/// out client ids from the same counter and feed the same channels.
+#[derive(Debug, Clone)]
+pub struct TelnetServer {
+    client_ids: ClientIds,
+    tx_data_channel: MeteredSender<DataMessage>,
+    tx_command_channel: MeteredSender<Command>,
+    push_stats: Arc<ChannelStats>,
+    keyring: Keyring,
+    login_tokens: LoginTokens,
+    /// The handles of the staff, refused
+    staff: Vec<String>,
+    /// Directory the main world stores the accounts in, their handles are
+    /// refused
+    data_dir: PathBuf,
+    /// Directories the hosted worlds store the accounts in, by world
+    worlds: Vec<(String, PathBuf)>,
+}
 
-    /// Called when a data packet is received. A response can be
-    /// written to the `response` argument.
-    #[allow(unused_variables)]
-    fn data(self, channel: ChannelId, data: &[u8], session: Session) -> Self::FutureUnit {
-        self.finished(session)
+impl TelnetServer {
+    /// Create a telnet server
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(client_ids: ClientIds, tx_data_channel: MeteredSender<DataMessage>,
+               tx_command_channel: MeteredSender<Command>, push_stats: Arc<ChannelStats>, keyring: Keyring,
+               login_tokens: LoginTokens, staff: Vec<String>, data_dir: &Path, worlds: Vec<(String, PathBuf)>)
+               -> TelnetServer {
+        TelnetServer { client_ids, tx_data_channel, tx_command_channel, push_stats, keyring, login_tokens, staff,
+            data_dir: data_dir.to_path_buf(), worlds }
     }
 
-
-    /// Called when the network window is adjusted, meaning that we
-    /// can send more bytes.
-    #[allow(unused_variables)]
-    fn window_adjusted(
-        self,
-        channel: ChannelId,
-        new_window_size: usize,
-        mut session: Session,
-    ) -> Self::FutureUnit {
-        if let Some(ref mut enc) = session.common.encrypted {
-            enc.flush_pending(channel);
-        }
-        self.finished(session)
+    /// Directory the chosen world stores the accounts in (ignoring case)
+    ///
+    /// Like the router (see `worlds`), a client choosing no world or a world
+    /// not hosted here enters the main world.
+    fn data_dir_of(&self, world: Option<&str>) -> &Path {
+        world.and_then(|w| self.worlds.iter().find(|(name, _)| name.eq_ignore_ascii_case(w)))
+            .map_or(&self.data_dir, |(_, dir)| dir)
     }
 
-    /// Called when this server adjusts the network window. Return the
-    /// next target window.
-    #[allow(unused_variables)]
-    fn adjust_window(&mut self, channel: ChannelId, current: u32) -> u32 {
-        current
+    /// The login a client logs in with, given the line it typed at the
+    /// prompt
+    ///
+    /// A login token is used up and replaced by the handle it was issued
+    /// for. Returns why the login is refused otherwise.
+    pub async fn login(&self, typed: &str) -> Result<String, &'static str> {
+        let typed = typed.trim();
+        if typed.starts_with(TOKEN_PREFIX) {
+            return self.login_tokens.redeem(typed).ok_or("Your login token is unknown or expired.");
+        }
+        let (player, world) = crate::worlds::split_login(typed);
+        let player = player.to_lowercase();
+        if player.is_empty() {
+            Err("Type your handle to log in.")
+        } else if self.staff.iter().any(|s| s.eq_ignore_ascii_case(&player)) || self.keyring.binds(&player) {
+            Err("This handle is protected by a key. Connect with ssh.")
+        } else if Account::exists(self.data_dir_of(world), &player).await {
+            Err("This handle is taken. Ask the staff for a login token to come back to your account.")
+        } else {
+            Ok(typed.to_string())
+        }
     }
 
-   
-    /// The client's pseudo-terminal window size has changed.
-    #[allow(unused_variables)]
-    fn window_change_request(
-        self,
-        channel: ChannelId,
-        col_width: u32,
-        row_height: u32,
-        pix_width: u32,
-        pix_height: u32,
-        session: Session,
-    ) -> Self::FutureUnit {
-        self.finished(session)
-    }
+    /// Serve a client until it hangs up
+    async fn serve_client(&self, stream: TcpStream) -> io::Result<()> {
+        let (mut reader, mut writer) = stream.into_split();
+        let mut decoder = LineDecoder::default();
+        let login = match tokio::time::timeout(LOGIN_TIMEOUT, self.prompt(&mut reader, &mut writer, &mut decoder))
+            .await {
+            Ok(login) => login?,
+            Err(_) => {
+                writer.write_all(b"\r\nLogin timed out.\r\n").await?;
+                return writer.shutdown().await;
+            },
+        };
+        let login = match login {
+            Some(l) => l,
+            None => return Ok(()),
+        };
+
+        let client_id = self.client_ids.next();
+        writer.write_all(format!("{}Welcome.{}\r\n", color::Fg(color::Cyan), color::Fg(color::Reset)).as_bytes())
+            .await?;
+        let push_tx = spawn_push_task(client_id, writer, &self.push_stats);
+        // Register client with the world - pass the connection to world thread
+        let info = ClientInfo { transport: Transport::Telnet, verified: false };
+        let connection = Box::new(PushConnection::new(info, push_tx.clone()));
+        if self.tx_command_channel.send(Command::Register(client_id, login, connection)).await.is_err() {
+            error!("serve_client(): receiver dropped");
+            return Ok(());
+        }
 
-    /// The client is sending a signal (usually to pass to the
-    /// currently running process).
-    #[allow(unused_variables)]
-    fn signal(self, channel: ChannelId, signal_name: Sig, session: Session) -> Self::FutureUnit {
-        self.finished(session)
+        let mut chunk = [0u8; 512];
+        loop {
+            let n = match reader.read(&mut chunk).await {
+                Ok(0) => break,
+                Ok(n) => n,
+                Err(e) => {
+                    debug!("Could not read from telnet client {}: {}", client_id, e);
+                    break;
+                },
+            };
+            for line in decoder.feed(&chunk[..n]) {
+                self.forward(client_id, line, &push_tx).await;
+            }
+        }
+        debug!("Telnet client {} hung up.", client_id);
+        if self.tx_command_channel.send(Command::Hangup(client_id)).await.is_err() {
+            error!("serve_client(): receiver dropped");
+        }
+        Ok(())
     }
 
-    /// Used for reverse-forwarding ports, see
-    /// [RFC4254](https://tools.ietf.org/html/rfc4254#section-7).
-    #[allow(unused_variables)]
-    fn tcpip_forward(self, address: &str, port: u32, session: Session) -> Self::FutureBool {
-        self.finished_bool(false, session)
-    }
-    /// Used to stop the reverse-forwarding of a port, see
-    /// [RFC4254](https://tools.ietf.org/html/rfc4254#section-7).
-    #[allow(unused_variables)]
-    fn cancel_tcpip_forward(self, address: &str, port: u32, session: Session) -> Self::FutureBool {
-        self.finished_bool(false, session)
+    /// Prompt for the login until the client typed an acceptable one
+    ///
+    /// Returns None if the client hung up.
+    async fn prompt(&self, reader: &mut OwnedReadHalf, writer: &mut OwnedWriteHalf, decoder: &mut LineDecoder)
+                    -> io::Result<Option<String>> {
+        writer.write_all(LOGIN_PROMPT).await?;
+        let mut chunk = [0u8; 512];
+        loop {
+            let n = reader.read(&mut chunk).await?;
+            if n == 0 {
+                return Ok(None);
+            }
+            for line in decoder.feed(&chunk[..n]) {
+                match self.login(&String::from_utf8_lossy(&line)).await {
+                    Ok(login) => {
+                        info!("Telnet login as {}.", login);
+                        return Ok(Some(login));
+                    },
+                    Err(reason) => {
+                        writer.write_all(format!("{}\r\n", reason).as_bytes()).await?;
+                        writer.write_all(LOGIN_PROMPT).await?;
+                    },
+                }
+            }
+        }
     }
-}
-
-
-pub trait Server {
-    /// The type of handlers.
-    type Handler: Handler + Send;
-    /// Called when a new client connects.
-    fn new(&mut self, peer_addr: Option<std::net::SocketAddr>) -> Self::Handler;
-}
 
-impl Server for TelnetServer {
-    type Handler = Self;
-    fn new(&mut self, _: Option<std::net::SocketAddr>) -> Self {
-        let s = self.clone();
-        self.id += 1;
-        self.command_buffer.clear();
-        s
+    /// Send a line to the world
+    ///
+    /// The staff does not connect by telnet, so admin commands go with the
+    /// data like any line instead of taking the control lane.
+    async fn forward(&self, client_id: ClientId, line: Data, push_tx: &MeteredSender<Push>) {
+        match self.tx_data_channel.send(DataMessage::new(client_id, line)).await {
+            Ok(_) => {},
+            Err(SendError::Full) => {
+                warn!("Data channel full. Dropped data of client {}.", client_id);
+                let message = "The matrix is overloaded. Your command was lost, try again.\r\n";
+                if push_tx.send(Push::Data(buffers::from_slice(message.as_bytes()))).await.is_err() {
+                    debug!("Client {} is gone.", client_id);
+                }
+            },
+            Err(SendError::Closed) => error!("forward(): receiver dropped"),
+        }
     }
 }
 
-
-#[derive(Debug)]
-/// Configuration of a server.
-pub struct Config {
-    /// The banner, usually a warning message shown to the client.
-    pub banner: Option<&'static str>,
-    /// The initial size of a channel (used for flow control).
-    pub window_size: u32,
-    /// The maximal size of a single packet.
-    pub maximum_packet_size: u32,
-    /// Time after which the connection is garbage-collected.
-    pub connection_timeout: Option<std::time::Duration>,
+/// Spawn the task forwarding pushes from the world to a client
+///
+/// Returns the sending side of the push channel of the client. The task ends
+/// when the connection is closed or when the world drops the sender.
+fn spawn_push_task(client_id: ClientId, mut writer: OwnedWriteHalf, stats: &Arc<ChannelStats>) -> MeteredSender<Push> {
+    let (push_tx, mut push_rx) = channels::channel(stats);
+    tokio::spawn(async move {
+        while let Some(push) = push_rx.recv().await {
+            let sent = match push {
+                Push::Data(data) => {
+                    let sent = writer.write_all(&data).await.is_ok();
+                    buffers::give(data);
+                    sent
+                },
+                Push::Animation(frames) => play(&mut writer, &frames).await,
+                Push::Close => {
+                    if writer.shutdown().await.is_err() {
+                        debug!("Client {} is already gone.", client_id);
+                    }
+                    break;
+                },
+            };
+            if !sent {
+                debug!("Client {} is gone. Stop pushing.", client_id);
+                break;
+            }
+        }
+    });
+    push_tx
 }
 
-impl Default for Config {
-    fn default() -> Config {
-        Config {
-            banner: None,
-            window_size: 2097152,
-            maximum_packet_size: 32768,
-            connection_timeout: Some(std::time::Duration::from_secs(600)),
+/// Play an animation to a client
+///
+/// Every frame is shown for its delay. If sending a frame takes longer than
+/// its delay, the link is too slow for the animation and the final frame is
+/// sent right away. Returns false if the client is gone.
+async fn play(writer: &mut OwnedWriteHalf, frames: &[Frame]) -> bool {
+    for (i, frame) in frames.iter().enumerate() {
+        let start = tokio::time::Instant::now();
+        if writer.write_all(&frame.data).await.is_err() {
+            return false;
         }
+        let last = match frames.last() {
+            Some(last) if i + 1 < frames.len() => last,
+            _ => break,
+        };
+        if start.elapsed() > frame.delay {
+            debug!("Link too slow for the animation. Skipping to the final frame.");
+            return writer.write_all(&last.data).await.is_ok();
+        }
+        tokio::time::sleep_until(start + frame.delay).await;
     }
+    true
 }
 
-
-pub fn init_telnet_server() -> (TelnetServer, Arc<Config>,
-    Receiver<String>, Receiver<String>) {
-// Configure the server
-
-let mut config = Config::default();
-config.connection_timeout = Some(std::time::Duration::from_secs(600));
-config.banner = Some("Banner Test\n");
-let config = Arc::new(config);
-
-// The data channel: The channel players use to send actions etc....
-let (data_tx, data_rx) = mpsc::channel(1_024);
-
-// The command channel: The channel used to send requests from the session to the world
-//let (command_tx, command_rx) = mpsc::unbounded_channel();
-let (command_tx, command_rx) = mpsc::channel(1_024);
-
-
-// Create the server
-let sh = TelnetServer{
-    clients: Arc::new(Mutex::new(HashMap::new())),
-    id: 0,
-    command_buffer: String::new(),
-    tx_data_channel: Arc::new(Mutex::new(data_tx.clone())),
-    tx_command_channel: Arc::new(Mutex::new(command_tx.clone()))
-};
-
-(sh, config, data_rx, command_rx)
-}
-
-/// Run a server.
-/// Create a new `Connection` from the server's configuration, a
-/// stream and a [`Handler`](trait.Handler.html).
-pub async fn run<H: TelnetServer + Send + 'static>(
-    config: Arc<Config>,
-    addr: &str,
-    mut server: H,
-) -> Result<(), std::io::Error> {
-    let addr = addr.to_socket_addrs().unwrap().next().unwrap();
-    let socket = TcpListener::bind(&addr).await?;
-    if config.maximum_packet_size > 65535 {
-        error!(
-            "Maximum packet size ({:?}) should not larger than a TCP packet (65535)",
-            config.maximum_packet_size
-        );
-    }
-    while let Ok((socket, _)) = socket.accept().await {
-        let config = config.clone();
-        let server = server.new(socket.peer_addr().ok());
-        tokio::spawn(run_stream(config, socket, server));
+/// Serve telnet clients at the given address
+pub async fn serve(addr: &str, server: TelnetServer) -> io::Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+    info!("Telnet server listening at: {}", addr);
+    loop {
+        let (stream, peer) = listener.accept().await?;
+        let server = server.clone();
+        tokio::spawn(async move {
+            if let Err(e) = server.serve_client(stream).await {
+                debug!("Telnet client {} failed: {}", peer, e);
+            }
+        });
     }
-    Ok(())
 }
@@ -0,0 +1,82 @@
+//! Login tokens
+//!
+//! Short-lived, one-time tokens to onboard players (eg. by scanning a QR
+//! code). A player connects with the token as user name
+//! (`ssh token-ab12cd34ef56@server`) and any key. The key is then bound to
+//! the player the token was issued for (see `keyring`) and the token is used
+//! up.
+//!
+//! Clones share the tokens, so the world can issue them while the ssh server
+//! redeems them.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use rand::RngCore;
+use tokio::time::Instant;
+
+/// Prefix of the user name that carries a token
+pub const TOKEN_PREFIX: &str = "token-";
+
+/// An issued token
+#[derive(Debug, Clone)]
+struct Issued {
+    player: String,
+    expires: Instant,
+}
+
+/// The issued login tokens
+#[derive(Debug, Clone)]
+pub struct LoginTokens {
+    tokens: Arc<Mutex<HashMap<String, Issued>>>,
+    ttl: Duration,
+}
+
+impl LoginTokens {
+    /// Create an empty set of tokens, valid for the given time once issued
+    pub fn new(ttl: Duration) -> LoginTokens {
+        LoginTokens { tokens: Arc::new(Mutex::new(HashMap::new())), ttl }
+    }
+
+    /// The time tokens are valid once issued
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// Issue a token for a player
+    ///
+    /// Returns the user name to connect with ("token-" and the token).
+    pub fn issue(&self, player: &str) -> String {
+        let mut bytes = [0u8; 6];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        let token: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+        let now = Instant::now();
+        let mut tokens = self.tokens.lock().unwrap_or_else(|e| e.into_inner());
+        tokens.retain(|_, i| i.expires > now);
+        tokens.insert(token.clone(), Issued { player: player.to_string(), expires: now + self.ttl });
+        format!("{}{}", TOKEN_PREFIX, token)
+    }
+
+    /// The player a user name carrying a valid token logs in as
+    ///
+    /// The token is not used up.
+    pub fn peek(&self, user: &str) -> Option<String> {
+        let token = user.strip_prefix(TOKEN_PREFIX)?;
+        let tokens = self.tokens.lock().unwrap_or_else(|e| e.into_inner());
+        tokens.get(token)
+            .filter(|i| i.expires > Instant::now())
+            .map(|i| i.player.clone())
+    }
+
+    /// Use up the token carried by a user name
+    ///
+    /// Returns the player the token was issued for, if it is still valid.
+    pub fn redeem(&self, user: &str) -> Option<String> {
+        let token = user.strip_prefix(TOKEN_PREFIX)?;
+        let mut tokens = self.tokens.lock().unwrap_or_else(|e| e.into_inner());
+        tokens.remove(token)
+            .filter(|i| i.expires > Instant::now())
+            .map(|i| i.player)
+    }
+}
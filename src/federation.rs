@@ -0,0 +1,354 @@
+//! Federation
+//!
+//! Links the world to the world of another mud-server instance (its peer),
+//! intermud style. One side dials the other, both authenticate with a shared
+//! secret. Over the link
+//!
+//! - players travel to the peer through a portal (a port leading out of the
+//!   world). The peer learns the name and a minimal state of the player
+//!   (title, score, quest flags), which it applies when the player connects
+//!   to it.
+//! - players tell players in the peer world ("tell neo@hackerspace hi").
+//!
+//! The link carries one JSON message per line. It starts with a handshake:
+//! both sides send a hello with a random nonce and answer the nonce of the
+//! other side with an HMAC-SHA256 over it, keyed with the shared secret.
+//! Incoming messages are passed to the world over the command channel.
+
+use std::collections::{BTreeSet, HashMap};
+use std::io;
+use std::time::Duration;
+
+use hmac::{Hmac, Mac, NewMac};
+use rand::RngCore;
+use sha2::Sha256;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+use tracing::{debug, info, warn};
+
+use crate::connection_manager::Command;
+use crate::connection_manager::channels::MeteredSender;
+
+/// Longest message accepted from the peer
+const MAX_MESSAGE: u64 = 16 * 1024;
+
+/// Time the peer gets to complete the handshake
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Time to wait before dialing the peer again
+const RETRY_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Number of messages to the peer that can be queued
+pub const QUEUE_CAPACITY: usize = 64;
+
+/// Time a traveler has to connect to the world it traveled to
+pub const ARRIVAL_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+/// Configuration of the link
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// Name of this world, as players of the peer address it
+    pub name: String,
+    /// Name of the peer world (the link is refused if the peer is named
+    /// differently)
+    pub peer_name: String,
+    /// Address to listen for the peer at (empty to dial the peer)
+    pub listen: String,
+    /// Address to dial the peer at (empty to listen for the peer)
+    pub connect: String,
+    /// The shared secret both sides authenticate with
+    pub secret: String,
+    /// Address players connect to this world with (eg. "con.example:2222")
+    pub ssh: String,
+}
+
+/// The part of a player that travels to the peer world
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Traveler {
+    pub name: String,
+    pub title: Option<String>,
+    pub score: u64,
+    pub flags: BTreeSet<String>,
+}
+
+/// The travelers a world expects, by their name in lower case
+///
+/// A traveler that does not connect within `ARRIVAL_TIMEOUT` is forgotten,
+/// so a peer cannot fill the world with travelers that never arrive.
+#[derive(Debug, Default)]
+pub struct Arrivals {
+    pending: HashMap<String, (Instant, Traveler)>,
+}
+
+impl Arrivals {
+    /// Expect a traveler, replacing the traveler of the same name
+    pub fn expect(&mut self, traveler: Traveler, now: Instant) {
+        self.pending.retain(|_, (since, _)| now.saturating_duration_since(*since) < ARRIVAL_TIMEOUT);
+        self.pending.insert(traveler.name.to_lowercase(), (now, traveler));
+    }
+
+    /// The traveler of the given name (ignoring case) that arrived in time,
+    /// if any
+    pub fn arrive(&mut self, name: &str, now: Instant) -> Option<Traveler> {
+        self.pending.remove(&name.to_lowercase())
+            .filter(|(since, _)| now.saturating_duration_since(*since) < ARRIVAL_TIMEOUT)
+            .map(|(_, traveler)| traveler)
+    }
+}
+
+/// Messages sent over the link
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Message {
+    /// Start of the handshake
+    Hello { world: String, ssh: String, nonce: String },
+    /// Answer to the nonce of the other side
+    Auth { mac: String },
+    /// A player travels to the peer world
+    Transfer { traveler: Traveler },
+    /// A player tells a player of the peer world something
+    Tell { from: String, to: String, text: String },
+    /// A tell could not be delivered, as the player is not connected
+    Undeliverable { from: String, to: String },
+}
+
+/// What the link tells the world
+#[derive(Debug, Clone, PartialEq)]
+pub enum Incoming {
+    /// The link to the peer is up
+    Linked { world: String, ssh: String },
+    /// The link to the peer is down
+    Unlinked,
+    /// A message from the peer
+    Received(Message),
+}
+
+/// The state of the link, as the world sees it
+#[derive(Debug)]
+pub struct Link {
+    /// Name of this world
+    pub name: String,
+    /// Name of the peer world
+    pub peer_name: String,
+    /// Address players connect to the peer with, while the link is up
+    pub peer_ssh: Option<String>,
+    tx: mpsc::Sender<Message>,
+}
+
+impl Link {
+    /// Create the state of a link that is not up yet
+    ///
+    /// Returns the receiver of the messages to the peer, for `run`.
+    pub fn new(config: &Config) -> (Link, mpsc::Receiver<Message>) {
+        let (tx, rx) = mpsc::channel(QUEUE_CAPACITY);
+        let link = Link { name: config.name.clone(), peer_name: config.peer_name.clone(), peer_ssh: None, tx };
+        (link, rx)
+    }
+
+    /// True if the link to the peer is up
+    pub fn is_up(&self) -> bool {
+        self.peer_ssh.is_some()
+    }
+
+    /// True if the name is the name of the peer world (ignoring case)
+    pub fn is_peer(&self, world: &str) -> bool {
+        self.peer_name.eq_ignore_ascii_case(world)
+    }
+
+    /// Send a message to the peer
+    ///
+    /// Does not block the world. Returns false if the link is down or too
+    /// many messages are queued.
+    pub fn send(&self, message: Message) -> bool {
+        self.is_up() && self.tx.try_send(message).is_ok()
+    }
+}
+
+/// Run the link
+///
+/// Dials the peer or listens for it and serves one link at a time. A link
+/// that goes down is set up again.
+pub async fn run(config: Config, command_tx: MeteredSender<Command>, mut outgoing: mpsc::Receiver<Message>) {
+    let listener = if config.connect.is_empty() {
+        match TcpListener::bind(&config.listen).await {
+            Ok(l) => {
+                info!("Federation listening for {} at: {}", config.peer_name, config.listen);
+                Some(l)
+            },
+            Err(e) => {
+                warn!("Federation could not listen at {}: {}", config.listen, e);
+                return;
+            },
+        }
+    } else {
+        None
+    };
+
+    loop {
+        let stream = match &listener {
+            Some(l) => l.accept().await.map(|(s, _)| s),
+            None => TcpStream::connect(&config.connect).await,
+        };
+        match stream {
+            Ok(stream) => {
+                let result = serve(stream, &config, &command_tx, &mut outgoing).await;
+                if command_tx.send(Command::Federation(Incoming::Unlinked)).await.is_err() {
+                    return;
+                }
+                match result {
+                    Ok(_) => info!("Link to {} closed.", config.peer_name),
+                    Err(e) => warn!("Link to {} failed: {}", config.peer_name, e),
+                }
+            },
+            Err(e) => debug!("Could not link to {}: {}", config.peer_name, e),
+        }
+        if listener.is_none() {
+            tokio::time::sleep(RETRY_INTERVAL).await;
+        }
+    }
+}
+
+/// Serve a link until it goes down
+///
+/// Performs the handshake, then passes the messages of the peer to the world
+/// and the messages of the world to the peer.
+pub async fn serve<S>(stream: S, config: &Config, command_tx: &MeteredSender<Command>,
+                      outgoing: &mut mpsc::Receiver<Message>) -> io::Result<()>
+        where S: AsyncRead + AsyncWrite + Send + 'static {
+    let (reader, mut writer) = tokio::io::split(stream);
+    let mut reader = BufReader::new(reader);
+
+    let (world, ssh) = match tokio::time::timeout(HANDSHAKE_TIMEOUT,
+            handshake(&mut reader, &mut writer, config)).await {
+        Ok(result) => result?,
+        Err(_) => return Err(io::Error::new(io::ErrorKind::TimedOut, "handshake timed out")),
+    };
+    info!("Linked to {} ({}).", world, ssh);
+
+    // Messages queued while the link was down are stale
+    while outgoing.try_recv().is_ok() {}
+    if command_tx.send(Command::Federation(Incoming::Linked { world, ssh })).await.is_err() {
+        return Ok(());
+    }
+
+    // Read in a task of its own, as reading a line cannot be cancelled
+    let command_tx = command_tx.clone();
+    let mut receiving = tokio::spawn(async move {
+        loop {
+            let message = read_message(&mut reader).await?;
+            if command_tx.send(Command::Federation(Incoming::Received(message))).await.is_err() {
+                return Ok(());
+            }
+        }
+    });
+    loop {
+        tokio::select! {
+            result = &mut receiving => {
+                return result.unwrap_or_else(|e| Err(io::Error::other(e)));
+            },
+            message = outgoing.recv() => match message {
+                Some(m) => write_message(&mut writer, &m).await?,
+                None => {
+                    receiving.abort();
+                    return Ok(());
+                },
+            },
+        }
+    }
+}
+
+/// Authenticate the peer
+///
+/// Returns the name of the peer world and the address its players connect
+/// with.
+async fn handshake<R, W>(reader: &mut R, writer: &mut W, config: &Config) -> io::Result<(String, String)>
+        where R: AsyncBufRead + Unpin, W: AsyncWrite + Unpin {
+    let mut nonce = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut nonce);
+    let nonce = hex(&nonce);
+    write_message(writer, &Message::Hello { world: config.name.clone(), ssh: config.ssh.clone(),
+        nonce: nonce.clone() }).await?;
+
+    let (world, ssh, peer_nonce) = match read_message(reader).await? {
+        Message::Hello { world, ssh, nonce } => (world, ssh, nonce),
+        _ => return Err(invalid("expected hello")),
+    };
+    if !world.eq_ignore_ascii_case(&config.peer_name) {
+        return Err(invalid(&format!("unexpected peer {}", world)));
+    }
+    write_message(writer, &Message::Auth { mac: mac(&config.secret, &peer_nonce, &config.name) }).await?;
+
+    match read_message(reader).await? {
+        Message::Auth { mac } if verify(&config.secret, &nonce, &world, &mac) => Ok((world, ssh)),
+        Message::Auth { .. } => Err(invalid("authentication failed")),
+        _ => Err(invalid("expected auth")),
+    }
+}
+
+/// The answer of a world to a nonce
+pub fn mac(secret: &str, nonce: &str, world: &str) -> String {
+    hex(&keyed(secret, nonce, world).finalize().into_bytes())
+}
+
+/// Check the answer of a world to a nonce (in constant time)
+pub fn verify(secret: &str, nonce: &str, world: &str, mac: &str) -> bool {
+    match unhex(mac) {
+        Some(tag) => keyed(secret, nonce, world).verify(&tag).is_ok(),
+        None => false,
+    }
+}
+
+/// The HMAC over a nonce and the name of the answering world
+fn keyed(secret: &str, nonce: &str, world: &str) -> Hmac<Sha256> {
+    // HMAC takes keys of any length
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key");
+    mac.update(nonce.as_bytes());
+    mac.update(b"\n");
+    mac.update(world.to_lowercase().as_bytes());
+    mac
+}
+
+/// Read a message from the peer
+async fn read_message<R>(reader: &mut R) -> io::Result<Message>
+        where R: AsyncBufRead + Unpin {
+    let mut line = String::new();
+    let n = (&mut *reader).take(MAX_MESSAGE).read_line(&mut line).await?;
+    if n == 0 {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "link closed"));
+    }
+    if !line.ends_with('\n') {
+        return Err(invalid("message too long"));
+    }
+    serde_json::from_str(&line).map_err(|e| invalid(&e.to_string()))
+}
+
+/// Write a message to the peer
+async fn write_message<W>(writer: &mut W, message: &Message) -> io::Result<()>
+        where W: AsyncWrite + Unpin {
+    let mut buf = serde_json::to_vec(message)?;
+    buf.push(b'\n');
+    writer.write_all(&buf).await?;
+    writer.flush().await
+}
+
+/// An error for a peer that does not follow the protocol
+fn invalid(reason: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, reason.to_string())
+}
+
+/// Hex encode bytes
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Decode hex encoded bytes
+fn unhex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) || !text.is_ascii() {
+        return None;
+    }
+    (0..text.len()).step_by(2)
+        .map(|i| u8::from_str_radix(&text[i..i + 2], 16).ok())
+        .collect()
+}
@@ -0,0 +1,124 @@
+//! News feed
+//!
+//! Serves the recent news of the world (see `world::news`) over HTTP, so the
+//! con website can embed them:
+//!
+//! - `GET /news.json` answers a JSON Feed (version 1.1).
+//! - `GET /news.atom` answers an Atom feed.
+//!
+//! Both may be fetched from any origin.
+
+use std::io;
+use serde_json::json;
+
+use crate::http::{self, Request};
+use crate::world::news::{Item, News};
+
+/// Longest request accepted
+const MAX_REQUEST: usize = 1_024;
+
+/// What the feed serves
+#[derive(Debug, Clone)]
+pub struct Feed {
+    /// The news of the world
+    pub news: News,
+    /// Title of the feed
+    pub title: String,
+    /// URL the feed is reachable at from outside, without the file name
+    /// (eg. "https://mud.example.org")
+    pub url: String,
+    /// Number of news in the feed
+    pub max_items: usize,
+}
+
+impl Feed {
+    /// The news as JSON Feed
+    pub fn json(&self) -> String {
+        let items: Vec<_> = self.news.latest(self.max_items).iter()
+            .map(|i| json!({
+                "id": i.id.to_string(),
+                "title": i.title,
+                "content_text": i.text,
+                "date_published": rfc3339(i.timestamp),
+                "tags": [i.kind.name()],
+            }))
+            .collect();
+        json!({
+            "version": "https://jsonfeed.org/version/1.1",
+            "title": self.title,
+            "feed_url": format!("{}/news.json", self.url),
+            "items": items,
+        }).to_string()
+    }
+
+    /// The news as Atom feed
+    pub fn atom(&self) -> String {
+        let items = self.news.latest(self.max_items);
+        let updated = items.first().map_or(0, |i| i.timestamp);
+        let mut xml = format!("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+            <feed xmlns=\"http://www.w3.org/2005/Atom\">\n\
+            <id>{url}/news.atom</id>\n<title>{title}</title>\n<updated>{updated}</updated>\n\
+            <author><name>{title}</name></author>\n\
+            <link rel=\"self\" href=\"{url}/news.atom\"/>\n",
+            url = escape(&self.url), title = escape(&self.title), updated = rfc3339(updated));
+        for item in items.iter() {
+            xml += &entry(&self.url, item);
+        }
+        xml += "</feed>\n";
+        xml
+    }
+}
+
+/// An entry of the Atom feed
+fn entry(url: &str, item: &Item) -> String {
+    format!("<entry>\n<id>{}/news/{}</id>\n<title>{}</title>\n<updated>{}</updated>\n\
+        <category term=\"{}\"/>\n<content type=\"text\">{}</content>\n</entry>\n",
+        escape(url), item.id, escape(&item.title), rfc3339(item.timestamp), item.kind.name(), escape(&item.text))
+}
+
+/// Serve the feed at the given address
+pub async fn serve(addr: &str, feed: Feed) -> io::Result<()> {
+    http::serve(addr, "News feed", MAX_REQUEST, move |request| {
+        let feed = feed.clone();
+        async move { answer(&request, &feed) }
+    }).await
+}
+
+/// Answer a single request
+fn answer(request: &Request, feed: &Feed) -> String {
+    match request.target() {
+        (Some("GET"), Some("/news.json")) => response("200 OK", "application/feed+json", &feed.json()),
+        (Some("GET"), Some("/news.atom")) => response("200 OK", "application/atom+xml", &feed.atom()),
+        (Some("GET"), Some(_)) => response("404 Not Found", "text/plain", "not found"),
+        _ => response("405 Method Not Allowed", "text/plain", "method not allowed"),
+    }
+}
+
+/// Build a response the website may fetch from any origin
+fn response(status: &str, content_type: &str, body: &str) -> String {
+    http::response(status, &format!("{}; charset=utf-8", content_type), "Access-Control-Allow-Origin: *\r\n", body)
+}
+
+/// Escape a text for XML
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Format seconds since the epoch as RFC 3339 date (UTC)
+pub fn rfc3339(timestamp: u64) -> String {
+    let (days, secs) = (timestamp / 86_400, timestamp % 86_400);
+    // Civil date from days since the epoch (Howard Hinnant's algorithm)
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z", year, month, day, secs / 3_600, secs % 3_600 / 60, secs % 60)
+}
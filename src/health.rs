@@ -0,0 +1,136 @@
+//! Health and readiness endpoints
+//!
+//! A tiny HTTP server for supervisors and monitoring, so a wedged server can
+//! be restarted automatically:
+//!
+//! - `GET /health` answers 200 if the world loop ticked recently (liveness).
+//! - `GET /ready` answers 200 if the world loop is alive, the channels to the
+//!   world are not full and the data directory is writable (readiness).
+//!
+//! Both answer 503 otherwise and carry the details as JSON.
+
+use std::io;
+use std::path::PathBuf;
+use std::time::Duration;
+use tracing::warn;
+
+use crate::connection_manager::channels::{ChannelStats, Metrics};
+use crate::http::{self, Request};
+use crate::world::stats::Heartbeat;
+
+/// Name of the file written to probe the storage
+const PROBE_FILE: &str = ".health";
+
+/// Longest request accepted
+const MAX_REQUEST: usize = 1_024;
+
+/// What the health check looks at
+#[derive(Debug, Clone)]
+pub struct HealthCheck {
+    /// Heartbeat of the world loop
+    pub heartbeat: Heartbeat,
+    /// Statistics of the channels
+    pub metrics: Metrics,
+    /// Directory the world stores its data in
+    pub data_dir: PathBuf,
+    /// The world counts as wedged if it did not beat for this long
+    pub max_heartbeat_age: Duration,
+}
+
+/// Depth of a channel
+#[derive(Debug, Serialize)]
+pub struct ChannelDepth {
+    name: &'static str,
+    queued: usize,
+    capacity: usize,
+}
+
+impl From<&ChannelStats> for ChannelDepth {
+    fn from(stats: &ChannelStats) -> ChannelDepth {
+        ChannelDepth { name: stats.name, queued: stats.queued(), capacity: stats.capacity }
+    }
+}
+
+/// Result of a health check
+#[derive(Debug, Serialize)]
+pub struct Report {
+    /// True if everything checked is fine
+    pub ok: bool,
+    /// Time since the world loop last ticked
+    pub heartbeat_age_ms: u64,
+    /// True if the data directory is writable (only checked for readiness)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub storage: Option<bool>,
+    /// Depths of the channels (the push channels of all clients summed up)
+    pub channels: Vec<ChannelDepth>,
+}
+
+impl HealthCheck {
+    /// Check if the world loop is alive
+    pub fn liveness(&self) -> Report {
+        let age = self.heartbeat.age();
+        Report {
+            ok: age <= self.max_heartbeat_age,
+            heartbeat_age_ms: age.as_millis() as u64,
+            storage: None,
+            channels: vec![
+                ChannelDepth::from(&*self.metrics.command),
+                ChannelDepth::from(&*self.metrics.data),
+                ChannelDepth::from(&*self.metrics.push),
+            ],
+        }
+    }
+
+    /// Check if the server can serve players
+    ///
+    /// The push channels are not checked, as a single slow client can fill
+    /// its channel without affecting anyone else.
+    pub async fn readiness(&self) -> Report {
+        let mut report = self.liveness();
+        let storage = self.probe_storage().await;
+        if let Err(e) = &storage {
+            warn!("Health check could not write to {:?}: {}", self.data_dir, e);
+        }
+        let channels_full = [&self.metrics.command, &self.metrics.data].iter()
+            .any(|c| c.queued() >= c.capacity);
+        report.ok = report.ok && storage.is_ok() && !channels_full;
+        report.storage = Some(storage.is_ok());
+        report
+    }
+
+    /// Write and remove a file in the data directory
+    async fn probe_storage(&self) -> io::Result<()> {
+        tokio::fs::create_dir_all(&self.data_dir).await?;
+        let path = self.data_dir.join(PROBE_FILE);
+        tokio::fs::write(&path, b"ok").await?;
+        tokio::fs::remove_file(&path).await
+    }
+}
+
+/// Serve the health endpoints at the given address
+pub async fn serve(addr: &str, check: HealthCheck) -> io::Result<()> {
+    http::serve(addr, "Health endpoints", MAX_REQUEST, move |request| {
+        let check = check.clone();
+        async move { answer(&request, &check).await }
+    }).await
+}
+
+/// Answer a single request
+async fn answer(request: &Request, check: &HealthCheck) -> String {
+    match request.target() {
+        (Some("GET"), Some("/health")) => report_response(&check.liveness()),
+        (Some("GET"), Some("/ready")) => report_response(&check.readiness().await),
+        (Some("GET"), Some(_)) => http::json("404 Not Found", "{\"error\":\"not found\"}"),
+        _ => http::json("405 Method Not Allowed", "{\"error\":\"method not allowed\"}"),
+    }
+}
+
+/// Build the response for a health report
+fn report_response(report: &Report) -> String {
+    let status = if report.ok { "200 OK" } else { "503 Service Unavailable" };
+    match serde_json::to_string(report) {
+        Ok(body) => http::json(status, &body),
+        Err(_) => http::json("500 Internal Server Error", "{\"error\":\"serialization failed\"}"),
+    }
+}
+
@@ -0,0 +1,120 @@
+//! HTTP endpoints
+//!
+//! Just enough HTTP for the small endpoints of the server (see `health`,
+//! `feed` and `provisioning`): every connection carries a single request,
+//! which is read with its body, answered and closed.
+
+use std::future::Future;
+use std::io;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tracing::{debug, info};
+
+/// Time a client gets to send its request
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A request
+#[derive(Debug)]
+pub struct Request {
+    /// The request line and the headers
+    pub head: String,
+    /// The body, as long as the Content-Length header says
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    /// The method and the path of the request, if given
+    pub fn target(&self) -> (Option<&str>, Option<&str>) {
+        let mut parts = self.head.split_whitespace();
+        (parts.next(), parts.next())
+    }
+
+    /// The value of a header (the name ignoring case), if given
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.head.lines()
+            .filter_map(|l| l.split_once(':'))
+            .find(|(n, _)| n.trim().eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.trim())
+    }
+}
+
+/// Serve the endpoints named at the given address, answering every request
+/// with the response the handler builds
+///
+/// Requests longer than `max_request` (head and body) are not answered.
+pub async fn serve<H, F>(addr: &str, name: &str, max_request: usize, handler: H) -> io::Result<()>
+    where H: Fn(Request) -> F + Clone + Send + 'static,
+          F: Future<Output = String> + Send {
+    let listener = TcpListener::bind(addr).await?;
+    info!("{} listening at: {}", name, addr);
+    loop {
+        let (stream, peer) = listener.accept().await?;
+        let handler = handler.clone();
+        let name = name.to_string();
+        tokio::spawn(async move {
+            if let Err(e) = handle(stream, max_request, handler).await {
+                debug!("{} request from {} failed: {}", name, peer, e);
+            }
+        });
+    }
+}
+
+/// Answer a single request and close the connection
+async fn handle<H, F>(mut stream: TcpStream, max_request: usize, handler: H) -> io::Result<()>
+    where H: Fn(Request) -> F,
+          F: Future<Output = String> {
+    let request = match tokio::time::timeout(REQUEST_TIMEOUT, read_request(&mut stream, max_request)).await {
+        Ok(request) => request?,
+        Err(_) => return Err(io::Error::new(io::ErrorKind::TimedOut, "request timed out")),
+    };
+    let response = handler(request).await;
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+/// Read the head and the body of a request
+async fn read_request(stream: &mut TcpStream, max_request: usize) -> io::Result<Request> {
+    let mut buffer = Vec::with_capacity(1_024);
+    let mut chunk = [0u8; 1_024];
+    let head_end = loop {
+        if let Some(i) = buffer.windows(4).position(|w| w == b"\r\n\r\n") {
+            break i + 4;
+        }
+        if buffer.len() >= max_request {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "request too long"));
+        }
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "request incomplete"));
+        }
+        buffer.extend_from_slice(&chunk[..n]);
+    };
+    let mut request = Request { head: String::from_utf8_lossy(&buffer[..head_end]).to_string(), body: Vec::new() };
+    let length = request.header("content-length").and_then(|l| l.parse::<usize>().ok()).unwrap_or(0);
+    let end = match head_end.checked_add(length) {
+        Some(end) if end <= max_request => end,
+        _ => return Err(io::Error::new(io::ErrorKind::InvalidData, "request too long")),
+    };
+    while buffer.len() < end {
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "request incomplete"));
+        }
+        buffer.extend_from_slice(&chunk[..n]);
+    }
+    buffer.truncate(end);
+    request.body = buffer.split_off(head_end);
+    Ok(request)
+}
+
+/// Build a response, the extra headers each ending with CRLF
+pub fn response(status: &str, content_type: &str, extra_headers: &str, body: &str) -> String {
+    format!("HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\n{}Connection: close\r\n\r\n{}",
+        status, content_type, body.len(), extra_headers, body)
+}
+
+/// Build a response with a JSON body
+pub fn json(status: &str, body: &str) -> String {
+    response(status, "application/json", "", body)
+}
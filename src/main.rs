@@ -7,60 +7,123 @@
 mod connection_manager;
 mod world;
 mod settings;
+mod health;
+mod feed;
+mod http;
+mod provisioning;
+mod federation;
+mod matrix;
+mod worlds;
+mod telemetry;
 #[cfg(test)] mod tests;
 
 #[macro_use] extern crate serde_derive;
 
 use settings::Settings;
-use tracing::{instrument, info, debug};
-use world::GameWorld;
-//use tracing_subscriber;
-// use tracing_subscriber::EnvFilter;
+use tracing::{instrument, info, debug, error, warn};
+use world::{GameWorld, Role};
+use world::handles::HandleRules;
+use world::filter::ContentFilter;
+use connection_manager::channels::{Capacities, Metrics};
+use connection_manager::tokens::LoginTokens;
+use connection_manager::keyring::Keyring;
+use connection_manager::authorized_keys::{self, AuthorizedKey};
+use world::states::Screens;
+use world::banners::Banners;
+use world::help::Help;
+use world::stats::Heartbeat;
+use world::visitors::Visitors;
+use world::news::News;
+use world::hooks::Hook;
+use world::puzzles::{Kind as PuzzleKind, Puzzle};
+use world::challenges::{Challenge, Goal, Leaderboard};
+use world::resets::{Step as ResetStep, ZoneReset};
+use world::properties::{Color, Lighting, Property};
+use world::ai::Behavior;
+use world::npcs::{Prototype, Spawner};
+use world::bosses::{Boss, Phase, Share, Telegraph};
+use world::ice::Ice;
+use world::goals::{Change, Deed, Goals};
+use world::market::Market;
+use world::timers::{Effect, Timers};
+use world::schedule::Schedule;
+use world::checkpoint::Checkpoint;
+use world::casino::{Casino, Limits};
+use world::economy::{Ledger, Vendor};
+use world::analytics::Analytics;
+use world::seasons::Season;
+use world::safety::Safety;
+use world::streaks::Rewards;
+use world::appearance::Wardrobe;
+use world::assets::{AssetID, GameAsset};
+use health::HealthCheck;
+use generational_arena::Index;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::signal::unix::{signal, SignalKind};
 
 
 #[instrument]
 #[tokio::main]
 async fn main() { 
-    // Choose one of the subscribers, either console or tracing
-
-    // EXPERIMENTAL - We use the experimental tokio-console to monitor threads
-    // --> tokio-console can take a long time for recompiles, thus comment out
-    //      if you experience problems during development and switch back on for
-    //      testing.
-    console_subscriber::init();
-    
-    // We use tracing for debug information as it is better suited for multitasking
-    // applications than traditional logging. Set up the tracing subscriber here
-    /*
-    tracing_subscriber::fmt()
-        .pretty()
-        .with_env_filter(EnvFilter::from_default_env())
-        .init();
-    */
-
     // Load the settings
     let settings = match Settings::new() {
-        Ok(s) => {
-            debug!("Settings loaded: {:?}", s);
-            s
-        },
+        Ok(s) => s,
         Err(e) => {
             panic!("Error reading settings: {}", e)
         },
     };
 
-    // Extract allowed keys from config
-    let mut allowed_keys = Vec::new();
-    for key_info in settings.security.allowed_keys {
-        allowed_keys.push(key_info[1].clone());
+    // We use tracing for debug information as it is better suited for multitasking
+    // applications than traditional logging. The experimental tokio-console is
+    // always served to monitor tasks, the export to an OTLP collector is optional.
+    let telemetry = telemetry::init(&settings.telemetry);
+    debug!("Settings loaded: {:?}", settings);
+
+    // The keys allowed to connect
+    let keyring = Keyring::new(Vec::new());
+    let authorized_path = Path::new(&settings.security.authorized_keys);
+    match authorized_keys::load(authorized_path) {
+        Ok(keys) => {
+            info!("{} authorized keys loaded from {}.", keys.len(), authorized_path.display());
+            keyring.authorize(&keys);
+        },
+        Err(e) => panic!("Could not load the authorized keys: {}", e),
+    }
+    if !settings.security.allowed_keys.is_empty() {
+        warn!("security.allowed_keys is deprecated, list the keys in {} instead.", authorized_path.display());
+        for key_info in &settings.security.allowed_keys {
+            match AuthorizedKey::parse(&key_info.join(" ")) {
+                Ok(Some(key)) => keyring.authorize(&[key]),
+                _ => warn!("Ignoring the allowed key {:?}, it is no valid key.", key_info),
+            }
+        }
     }
 
     // Configure the ssh server
-    let (sh, config,
+    let capacities = Capacities {
+        command: settings.channels.command_capacity,
+        data: settings.channels.data_capacity,
+        push: settings.channels.push_capacity,
+    };
+    let channel_metrics = Metrics::new(&capacities);
+    let host_key_path = Path::new(&settings.ssh_server.host_key_path);
+    let host_key = match connection_manager::ssh_server::load_host_key(host_key_path) {
+        Ok(key) => key,
+        Err(e) => panic!("Could not load the host key from {}: {}", host_key_path.display(), e),
+    };
+    let (mut sh, config,
         sender_data_rx, sender_command_rx)
-        = connection_manager::ssh_server::init_ssh_server(allowed_keys);
-    let mut addr = String::from(settings.ssh_server.host);
-    addr.push_str(":");
+        = connection_manager::ssh_server::init_ssh_server(host_key, keyring, &channel_metrics);
+    sh.set_history_depth(settings.ssh_server.history_depth);
+    sh.set_staff(settings.security.admins.iter().chain(settings.security.builders.iter()).cloned().collect());
+    #[cfg(feature = "chaos")]
+    {
+        info!("Chaos enabled: {:?}", settings.chaos);
+        sh.set_chaos(settings.chaos.clone());
+    }
+    let mut addr = settings.ssh_server.host;
+    addr.push(':');
     addr.push_str(settings.ssh_server.port.to_string().as_ref());
 
     // In this part we instantiate the world
@@ -68,42 +131,665 @@ async fn main() {
     // 1. Load the world configuration
     // 2. Run the world instance
 
-    // TODO - Make world loadable from disk
-    let mut world = GameWorld::new(format!("Testworld"));
+    let mut world = GameWorld::new(settings.world.name.clone());
+    world.set_data_dir(PathBuf::from(&settings.world.data_dir));
+    world.set_keyring(sh.keyring());
+    if let Err(e) = sh.keyring().open(Path::new(&settings.world.data_dir)) {
+        error!("Could not load the bound keys: {}", e);
+    }
+    if settings.login_tokens.enabled {
+        let tokens = LoginTokens::new(Duration::from_secs(settings.login_tokens.ttl_minutes * 60));
+        sh.set_login_tokens(tokens.clone());
+        world.set_login_tokens(tokens, &settings.login_tokens.ssh_host, settings.login_tokens.ssh_port);
+    }
+    match Visitors::load(Path::new(&settings.world.data_dir)).await {
+        Ok(visitors) => world.set_visitors(visitors),
+        Err(e) => error!("Could not load the visitors: {}", e),
+    }
+    match Leaderboard::load(Path::new(&settings.world.data_dir)).await {
+        Ok(leaderboard) => world.set_leaderboard(leaderboard),
+        Err(e) => error!("Could not load the leaderboard: {}", e),
+    }
+    world.set_casino(Casino::new(settings.casino.seed, Limits { 
+        max_wager: settings.casino.max_wager, 
+        max_loss: settings.casino.max_loss,
+    }));
+    for (name, season) in &settings.seasons {
+        world.add_season(name, Season { start: season.start, end: season.end });
+    }
+    world.set_wardrobe(Wardrobe::default()
+        .with_flair("neon jacket", "A neon jacket cycles through the colors of the con.")
+        .with_flair("mirrorshades", "Mirrorshades hide the eyes, reflecting the glow of the matrix."));
+    world.set_rewards(Rewards::default()
+        .on_day(1, Hook::Credits(10))
+        .on_day(2, Hook::Credits(20))
+        .on_day(2, Hook::Item("stim patch".to_string()))
+        .on_day(3, Hook::Credits(30))
+        .on_day(3, Hook::Title("the regular".to_string()))
+        .on_day(4, Hook::Credits(40))
+        .on_day(4, Hook::Item("mirrorshades".to_string()))
+        .on_day(5, Hook::Credits(50))
+        .on_day(5, Hook::Item("stim patch".to_string()))
+        .milestone(3)
+        .milestone(5));
+    match Market::load(Path::new(&settings.world.data_dir)).await {
+        Ok(market) => world.set_market(market),
+        Err(e) => error!("Could not load the market: {}", e),
+    }
+    match Timers::load(Path::new(&settings.world.data_dir)).await {
+        Ok(timers) => world.set_timers(timers),
+        Err(e) => error!("Could not load the timers: {}", e),
+    }
+    match Schedule::load(Path::new(&settings.world.data_dir)).await {
+        Ok(schedule) => world.set_schedule(schedule),
+        Err(e) => error!("Could not load the schedule: {}", e),
+    }
+    let mut goals = match Goals::load(Path::new(&settings.world.data_dir)).await {
+        Ok(goals) => goals,
+        Err(e) => {
+            error!("Could not load the goals: {}", e);
+            Goals::default()
+        },
+    };
+    world.set_channel_metrics(channel_metrics.clone());
+    let heartbeat = Heartbeat::new();
+    world.set_heartbeat(heartbeat.clone());
+    world.set_log_filter(telemetry.log_filter.clone());
+    let news = News::new();
+    world.set_news(news.clone());
+    let ledger = Ledger::new();
+    world.set_ledger(ledger.clone());
+    let analytics = if settings.analytics.enabled {
+        Analytics::enabled(Duration::from_secs(settings.analytics.log_interval_s))
+    } else {
+        Analytics::default()
+    };
+    world.set_analytics(analytics.clone());
+    telemetry::observe(&channel_metrics, &heartbeat, &ledger, &analytics);
+    world.set_screens(Screens::load(Path::new(&settings.world.screen_dir)).await);
+    world.set_help(Help::load(Path::new(&settings.world.help_dir)).await);
+    let color = Some(settings.banners.color.clone()).filter(|c| !c.is_empty());
+    world.set_banners(Banners::load(Path::new(&settings.banners.font_dir)).await
+        .with_default_font(&settings.banners.default_font)
+        .with_color(color)
+        .with_max_width(settings.banners.max_width));
+    world.set_media_url(settings.media.url.clone());
+    for admin in &settings.security.admins {
+        world.set_role(admin, Role::Admin);
+    }
+    for builder in &settings.security.builders {
+        world.set_role(builder, Role::Builder);
+    }
+    let handle_rules = HandleRules {
+        min_length: settings.handles.min_length,
+        max_length: settings.handles.max_length,
+        max_title_length: settings.handles.max_title_length,
+        max_description_length: settings.handles.max_description_length,
+        reserved: settings.handles.reserved.clone(),
+        profanity: settings.content.profanity.clone(),
+    };
+    world.set_handle_rules(handle_rules.clone());
+    world.set_content_filter(ContentFilter::new(settings.content.filter, 
+        settings.content.profanity.clone()));
+    let federation = if settings.federation.enabled {
+        if settings.federation.secret.is_empty() {
+            panic!("The federation needs a shared secret. Set [federation] secret.");
+        }
+        let config = federation::Config {
+            name: settings.federation.name.clone(),
+            peer_name: settings.federation.peer_name.clone(),
+            listen: settings.federation.listen.clone(),
+            connect: settings.federation.connect.clone(),
+            secret: settings.federation.secret.clone(),
+            ssh: settings.federation.ssh.clone(),
+        };
+        let (link, outgoing) = federation::Link::new(&config);
+        world.set_federation(link);
+        Some((config, outgoing))
+    } else {
+        None
+    };
+    let matrix = if settings.matrix.enabled {
+        let config = matrix::Config {
+            homeserver: settings.matrix.homeserver.clone(),
+            access_token: settings.matrix.access_token.clone(),
+            user_id: settings.matrix.user_id.clone(),
+            room_id: settings.matrix.room_id.clone(),
+            zones: settings.matrix.zones.clone(),
+        };
+        let (bridge, outgoing) = matrix::Bridge::new(&config);
+        world.set_matrix(bridge);
+        Some((config, outgoing))
+    } else {
+        None
+    };
+
+    // Verbs beyond the grammar, registered like a plugin would
+    let wave = test_wave();
+    let name = wave.name();
+    match world::verbs::register(wave) {
+        Ok(()) => info!("Registered verb {}, the verbs registered are: {}", name, world::verbs::names().join(", ")),
+        Err(e) => error!("Could not register verb {}: {}", name, e),
+    }
     
-    // Build first node and make it a spawn node
-    // TODO - generate global array of assets
+    // Build the world from the world file, or the test world. The portals
+    // to the other worlds and the services of the lobby go into the first
+    // spawn node. The ports between the nodes are connected once all nodes
+    // are in the world.
+    let (mut node, mut id_counter, guarded, links) = if settings.world.file.is_empty() {
+        let (node, purple_port) = test_lobby();
+        let id_counter = node.assets().map(|a| a.uid()).max().unwrap_or_default();
+        let (vault, exit, hatch) = test_vault(id_counter + 1);
+        let id_counter = vault.assets().map(|a| a.uid()).max().unwrap_or(exit);
+        let (mainframe, back) = test_mainframe(id_counter + 1);
+        let links = vec![(purple_port, vault.uid()), (exit, node.uid()), (hatch, mainframe.uid()),
+            (back, vault.uid())];
+        let id_counter = back;
+        goals.add(test_liberation(mainframe.uid(), hatch));
+        world.add_node(vault);
+        if let Some(mainframe) = world.add_node(mainframe) {
+            test_raid(&mut world, mainframe);
+        }
+        (node, id_counter, Some(purple_port), links)
+    } else {
+        let world_file = match world::loader::load_world(Path::new(&settings.world.file)).await {
+            Ok(f) => f,
+            Err(e) => panic!("Could not load the world from {}: {}", settings.world.file, e),
+        };
+        let mut nodes = match world_file.build() {
+            Ok(nodes) => nodes,
+            Err(e) => panic!("Invalid world {}: {}", settings.world.file, e),
+        };
+        let first_spawn = nodes.iter().position(|(_, spawn)| *spawn).unwrap_or_default();
+        let (node, _) = nodes.remove(first_spawn);
+        for (other, spawn) in nodes {
+            if spawn {
+                world.add_spwan_node(other);
+            } else {
+                world.add_node(other);
+            }
+        }
+        info!("Loaded the world from {}.", settings.world.file);
+        (node, world_file.max_uid(), None, world_file.links())
+    };
+
+    if let Some((config, _)) = &federation {
+        id_counter += 1;
+        let mut port = world::assets::Port::new(id_counter);
+        port.update_description(&format!("A port rimmed with static. Through it you glimpse {}.", 
+            config.peer_name));
+        port.update_portal(Some(&config.peer_name));
+        node.add_asset(Box::new(port));
+    }
+    for (name, hosted) in settings.worlds.iter().filter(|(_, w)| w.portal) {
+        id_counter += 1;
+        let mut port = world::assets::Port::new(id_counter);
+        port.update_description(&format!("A port framed in bare wireframe. Through it you glimpse the {} of {}.", 
+            hosted.spawn, name));
+        port.update_portal(Some(name));
+        node.add_asset(Box::new(port));
+    }
+    let lobby = world.add_spwan_node(node);
+    for (port, to) in links {
+        if !world.connect(port, to) {
+            error!("Cannot connect port {} to node {}.", port, to);
+        }
+    }
+    if let Some(lobby) = lobby {
+        world.add_market(lobby);
+        world.add_bank(lobby);
+        let banker = Prototype::new("banker", "A banker program in a pinstriped avatar guards the vaults of \
+            the deckers. It takes deposits and pays out withdrawals.");
+        world.add_spawner(Spawner::new(lobby, banker, 1));
+        world.add_casino(lobby);
+        let dealer = Prototype::new("dealer", "Behind the bar in the corner of the lobby, a dealer program \
+            shuffles a deck of glowing cards. It plays dice and net-poker for credits.");
+        world.add_spawner(Spawner::new(lobby, dealer, 1));
+        world.add_vendor(lobby, Vendor::new("fixer", "runners")
+            .with_ware("icebreaker", 40)
+            .with_ware("ono-sendai keycard", 60)
+            .with_ware("neon jacket", 30)
+            .with_gated_ware("black ice shard", 150, 20));
+        let fixer = Prototype::new("fixer", "A fixer of the runners leans against a pillar, a case of \
+            programs and hardware at their feet. They deal with deckers the runners trust.")
+            .with_line("Subnet-7 is still under corporate ICE. Break the warden often enough and it is ours.");
+        world.add_spawner(Spawner::new(lobby, fixer, 1));
+        let herald = Prototype::new("herald", "A herald program in a shimmering badge lanyard calls out the \
+            talks of the con.");
+        world.add_spawner(Spawner::new(lobby, herald, 1).during("con"));
+        let mut sentinel = Prototype::new("sentinel", "A sentinel of black ICE circles the lobby, \
+            its sensors sweeping over every decker.").hostile()
+            .with_integrity(40);
+        if let Some(port) = guarded {
+            sentinel = sentinel.with_behavior(Behavior::Guard(port));
+        }
+        let sentinel = sentinel
+            .with_behavior(Behavior::Pursue { trace_time: Duration::from_secs(5 * 60) })
+            .with_behavior(Behavior::Reinforce(1))
+            .with_behavior(Behavior::Strike { interval: Duration::from_secs(10), jam: Duration::from_secs(3) })
+            .with_behavior(Behavior::Perceive { interval: Duration::from_secs(5), perception: 20 });
+        world.add_spawner(Spawner::new(lobby, sentinel, 2).respawn_after(Duration::from_secs(90)));
+    }
+    world.set_responder(Prototype::new("enforcer", "An enforcer of corporate security, a faceless avatar of \
+            chrome and red warning glyphs.").hostile()
+        .with_integrity(60)
+        .with_behavior(Behavior::Pursue { trace_time: Duration::from_secs(5 * 60) })
+        .with_behavior(Behavior::Strike { interval: Duration::from_secs(8), jam: Duration::from_secs(4) }));
+    world.add_challenge(Challenge::new("lobby-rush", "Open the pattern lock and the purple port.", 
+        Goal::Crack(vec!["lobby/pattern-lock".to_string(), "lobby/purple-port".to_string()]), 
+        Duration::from_secs(120)));
+    world.add_reset(ZoneReset::new("Lobby")
+        .every(Duration::from_secs(30 * 60))
+        .with_step(ResetStep::Puzzles)
+        .with_step(ResetStep::Ice)
+        .with_step(ResetStep::Announce("The lobby flickers. Its locks and ICE reset for the next wave.".to_string())));
+
+    //Increase ID counter for next node
+    //id_counter += 1;
+
+    // Pick up where the world was at the last checkpoint
+    world.set_autosave(Duration::from_secs(settings.world.autosave_s));
+    restore(&mut world, Path::new(&settings.world.data_dir)).await;
+    world.set_goals(goals);
+
+    // Build the worlds hosted next to the main world, each with its spawn
+    // node. Every world gets a gate to the others.
+    let (transfer_tx, transfer_rx) = tokio::sync::mpsc::channel(worlds::QUEUE_CAPACITY);
+    let names: Vec<String> = std::iter::once(world.name().to_string())
+        .chain(settings.worlds.keys().cloned())
+        .collect();
+    let gate = |name: &str| worlds::Gate::new(names.iter().filter(|n| *n != name).cloned().collect(), 
+        transfer_tx.clone());
+    world.set_gate(gate(&settings.world.name));
+    let mut hosted_worlds = Vec::new();
+    for (name, hosted) in &settings.worlds {
+        let mut hosted_world = GameWorld::new(name.clone());
+        hosted_world.set_data_dir(PathBuf::from(&hosted.data_dir));
+        hosted_world.set_gate(gate(name));
+        hosted_world.set_screens(Screens::load(Path::new(&settings.world.screen_dir)).await);
+        hosted_world.set_help(Help::load(Path::new(&settings.world.help_dir)).await);
+        hosted_world.set_log_filter(telemetry.log_filter.clone());
+        for admin in &settings.security.admins {
+            hosted_world.set_role(admin, Role::Admin);
+        }
+        for builder in &settings.security.builders {
+            hosted_world.set_role(builder, Role::Builder);
+        }
+        hosted_world.set_handle_rules(handle_rules.clone());
+        hosted_world.set_content_filter(ContentFilter::new(settings.content.filter, 
+            settings.content.profanity.clone()));
+        let mut node = world::assets::Node::new(0);
+        node.update_name(&hosted.spawn);
+        node.update_description(&hosted.description);
+        if hosted.portal {
+            let mut port = world::assets::Port::new(1);
+            port.update_description(&format!("A port pulsing in ultraviolet. Through it you glimpse the lobby of {}.", 
+                settings.world.name));
+            port.update_portal(Some(&settings.world.name));
+            node.add_asset(Box::new(port));
+        }
+        hosted_world.add_spwan_node(node);
+        hosted_world.set_autosave(Duration::from_secs(settings.world.autosave_s));
+        restore(&mut hosted_world, Path::new(&hosted.data_dir)).await;
+        hosted_worlds.push(hosted_world);
+    }
+
+
+    // Serve the health endpoints for supervisors and monitoring
+    if settings.health.enabled {
+        let check = HealthCheck {
+            heartbeat,
+            metrics: channel_metrics,
+            data_dir: PathBuf::from(&settings.world.data_dir),
+            max_heartbeat_age: Duration::from_millis(settings.health.max_heartbeat_age_ms),
+        };
+        let health_addr = format!("{}:{}", settings.health.host, settings.health.port);
+        tokio::spawn(async move {
+            if let Err(e) = health::serve(&health_addr, check).await {
+                error!("Health endpoints at {} failed: {}", health_addr, e);
+            }
+        });
+    }
+
+    // Link to the peer world
+    if let Some((config, outgoing)) = federation {
+        tokio::spawn(federation::run(config, sh.command_sender(), outgoing));
+    }
+
+    // Bridge the chat to the Matrix room
+    if let Some((config, outgoing)) = matrix {
+        tokio::spawn(matrix::run(config, sh.command_sender(), outgoing));
+    }
+
+    // Serve the news feed for the con website
+    if settings.feed.enabled {
+        let news_feed = feed::Feed {
+            news,
+            title: settings.feed.title.clone(),
+            url: settings.feed.url.trim_end_matches('/').to_string(),
+            max_items: settings.feed.max_items,
+        };
+        let feed_addr = format!("{}:{}", settings.feed.host, settings.feed.port);
+        tokio::spawn(async move {
+            if let Err(e) = feed::serve(&feed_addr, news_feed).await {
+                error!("News feed at {} failed: {}", feed_addr, e);
+            }
+        });
+    }
+
+    // Provision badges for the flashing pipeline
+    if settings.provisioning.enabled {
+        let data_dir = Path::new(&settings.world.data_dir);
+        let badges = match provisioning::Badges::load(data_dir).await {
+            Ok(b) => b,
+            Err(e) => panic!("Could not load the badges: {}", e),
+        };
+        let provisioner = provisioning::Provisioner::new(&settings.provisioning.token, data_dir,
+            &settings.provisioning.handle_prefix, handle_rules, &settings.provisioning.ssh_host,
+            settings.provisioning.ssh_port, sh.keyring(), badges).await;
+        let provisioning_addr = format!("{}:{}", settings.provisioning.host, settings.provisioning.port);
+        tokio::spawn(async move {
+            if let Err(e) = provisioning::serve(&provisioning_addr, provisioner).await {
+                error!("Badge provisioning at {} failed: {}", provisioning_addr, e);
+            }
+        });
+    }
+
+    // Spawn a thread for every world and the router passing the clients to
+    // the world they are in (the main world first)
+    let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
+    let mut routes = Vec::new();
+    let mut running = Vec::new();
+    for game_world in std::iter::once(world).chain(hosted_worlds) {
+        let (route, command_rx, data_rx) = worlds::Route::new(game_world.name(), &Metrics::new(&capacities));
+        routes.push(route);
+        let shutdown_rx = shutdown_rx.clone();
+        running.push(tokio::spawn(async move{
+            world::run(command_rx, data_rx, game_world, shutdown_rx).await;
+        }));
+    }
+    tokio::spawn(worlds::run(sender_command_rx, sender_data_rx, transfer_rx, worlds::Router::new(routes)));
+
+    // On SIGTERM or ctrl-c let the worlds save their checkpoints, then exit
+    tokio::spawn(async move {
+        shutdown_signal().await;
+        info!("Shutting down, the worlds save their checkpoints.");
+        if shutdown_tx.send(true).is_err() {
+            error!("The worlds are gone, nothing to save.");
+        }
+        for world in running {
+            if let Err(e) = world.await {
+                error!("A world failed while shutting down: {}", e);
+            }
+        }
+        std::process::exit(0);
+    });
+
+    // Serve the players without ssh keys over telnet
+    if settings.telnet_server.enabled {
+        let worlds = settings.worlds.iter().map(|(name, w)| (name.clone(), PathBuf::from(&w.data_dir))).collect();
+        let telnet = sh.telnet_server(Path::new(&settings.world.data_dir), worlds);
+        let telnet_addr = format!("{}:{}", settings.telnet_server.host, settings.telnet_server.port);
+        tokio::spawn(async move {
+            if let Err(e) = connection_manager::telnet_server::serve(&telnet_addr, telnet).await {
+                error!("Telnet server at {} failed: {}", telnet_addr, e);
+            }
+        });
+    }
+
+    // Start the ssh server and listen for incoming connections
+    //
+    // Not that we do not need to spawn a thread but can just await the run function.
+    // This is because the run function spawns a thread whenever a new client calls.
+    // Otherwise it keeps looping and thus keeps our main function nice and active as
+    // long as the server runs.
+    info!("Spawning ssh server listening at: {}", addr);
+    thrussh::server::run(config, addr.as_ref(), sh).await.unwrap();
+}
+
+/// Apply the checkpoint stored in the data directory to a world built
+async fn restore(world: &mut GameWorld, data_dir: &Path) {
+    match Checkpoint::load(data_dir).await {
+        Ok(Some(checkpoint)) => {
+            let restored = world.restore(checkpoint);
+            info!("Restored {} nodes and assets of world {} from the checkpoint.", restored, world.name());
+        },
+        Ok(None) => info!("World {} has no checkpoint, it starts as built.", world.name()),
+        Err(e) => error!("Could not load the checkpoint of world {}: {}", world.name(), e),
+    }
+}
+
+/// Wait for ctrl-c or SIGTERM (eg. from the supervisor stopping the server)
+async fn shutdown_signal() {
+    let mut terminate = match signal(SignalKind::terminate()) {
+        Ok(s) => Some(s),
+        Err(e) => {
+            error!("Could not listen for SIGTERM: {}", e);
+            None
+        },
+    };
+    let sigterm = async {
+        match terminate.as_mut() {
+            Some(s) => s.recv().await,
+            None => std::future::pending().await,
+        }
+    };
+    tokio::select! {
+        Ok(()) = tokio::signal::ctrl_c() => info!("Received ctrl-c."),
+        _ = sigterm => info!("Received SIGTERM."),
+    }
+}
+
+/// Build the lobby of the test world
+///
+/// Returns the lobby and the uid of its purple port, guarded by the sentinel.
+fn test_lobby() -> (world::assets::Node, AssetID) {
     let mut id_counter = 0;
     let mut node = world::assets::Node::new(id_counter);
+    node.update_name("Lobby");
     node.update_description("Around you its dark. You feel more than you see a \
         pulsing ultraviolet light.");
+    node.update_sound(Some("ultraviolet_hum.wav"));
+    node.add_property(Property::Lighting(Lighting::Pulsing));
+    // New players solve a puzzle before they fight the ICE or gamble
+    node.update_safety(Safety { safe: false, min_level: 2 });
+    node.update_security(1);
     
     id_counter += 1;
     let mut port = world::assets::Port::new(id_counter);
     port.update_description("A simple port that looks absolutely normal.");
+    port.update_puzzle(Some(Puzzle::new("lobby/pattern-lock", PuzzleKind::Pattern { dots: vec![1, 5, 9, 6] }, 
+            "A pattern lock glows next to the port.")
+        .with_hint("The pattern starts in the upper left corner.", 5)
+        .with_points(20)
+        .with_max_attempts(5)
+        .on_solve(Hook::Flag("achievement/pattern-lock".to_string()))
+        .on_solve(Hook::Drone("wasp".to_string()))
+        .on_solve(Hook::Reputation { faction: "runners".to_string(), amount: 10 })));
     node.add_asset(Box::new(port));
     
     id_counter += 1;
     let mut port = world::assets::Port::new(id_counter);
+    let purple_port = id_counter;
     port.update_description("A port that has a slight purple shimmering edge.");
+    port.update_sound(Some("port_hum.wav"));
+    port.add_property(Property::Color(Color::Purple));
+    port.update_puzzle(Some(Puzzle::new("lobby/purple-port", Puzzle::hash("2342"), 
+            "The port asks for an access code.")
+        .with_hint("The code has four digits.", 10)
+        .with_hint("The code is noted in the vault of the terminal.", 20)
+        .with_points(50)
+        .on_solve(Hook::Flag("quest/purple-port".to_string()))
+        .on_solve(Hook::Credits(100))
+        .on_solve(Hook::Reputation { faction: "runners".to_string(), amount: 15 })));
     node.add_asset(Box::new(port));
-    world.add_spwan_node(node);
 
-    //Increase ID counter for next node
-    //id_counter += 1;
+    id_counter += 1;
+    let mut terminal = world::terminals::Terminal::new(id_counter, "ono-sendai");
+    terminal.update_description("A battered terminal, its screen flickering green.");
+    terminal.fs_mut().add_file("/readme.txt", "ONO-SENDAI Cyberspace VII\n\
+        Authorized personnel only. All access is logged.");
+    terminal.fs_mut().add_file("/logs/access.log", "23:42 root login from 10.0.0.23\n\
+        23:47 root logout");
+    // Reading the access log trips the alarm, it goes off ten seconds later
+    terminal.fs_mut().on_read("/logs/access.log", Hook::Timer {
+        after: Duration::from_secs(10),
+        effect: Effect::Alarm { zone: "Lobby".to_string() },
+    });
+    terminal.fs_mut().add_dir("/home/guest");
+    terminal.fs_mut().add_program("/home/guest/icebreaker", "Icebreaker v0.3 - cracks ICE up to level 1", 1);
+    terminal.fs_mut().add_file("/vault/ports.txt", "Purple port access code: 2342");
+    terminal.fs_mut().lock("/vault", 1);
+    terminal.fs_mut().on_read("/vault/ports.txt", 
+        Hook::Note("Purple port access code: 2342".to_string()));
+    terminal.fs_mut().add_file("/home/guest/.plan", "Find the vault. Crack it. Tell nobody.");
+    terminal.fs_mut().hide("/home/guest/.plan");
+    terminal.fs_mut().on_read("/home/guest/.plan", Hook::Flag("ono-sendai/plan".to_string()));
+    terminal.update_puzzle(Some(Puzzle::new("ono-sendai/cipher", 
+            Puzzle::substitution("The sky above the port", "qwertyuiopasdfghjklzxcvbnm"), 
+            "A scrambled message scrolls over the screen.")
+        .with_hint("Every letter stands for another one.", 5)
+        .with_points(30)
+        .on_solve(Hook::Note("The sky above the port".to_string()))
+        .on_solve(Hook::Item("ono-sendai keycard".to_string()))));
+    node.add_asset(Box::new(terminal));
 
+    id_counter += 1;
+    let mut port = world::assets::Port::new(id_counter);
+    port.update_description("A port plastered with con stickers. It only hums while the con is on.");
+    port.update_puzzle(Some(Puzzle::new("con/sticker-port", Puzzle::hash("hack the planet"), 
+            "The port asks for the motto of the con.")
+        .with_hint("The motto is printed on the back of your badge.", 5)
+        .with_points(25)
+        .during("con")
+        .on_solve(Hook::Flag("quest/con-motto".to_string()))
+        .on_solve(Hook::Reputation { faction: "runners".to_string(), amount: 5 })));
+    node.add_asset(Box::new(port));
 
-    // Spawn World Thread
-    tokio::spawn(async move{
-        world::run(sender_command_rx, sender_data_rx, world).await;
-    });
+    (node, purple_port)
+}
 
-    // Start the ssh server and listen for incoming connections
-    //
-    // Not that we do not need to spawn a thread but can just await the run function.
-    // This is because the run function spawns a thread whenever a new client calls.
-    // Otherwise it keeps looping and thus keeps our main function nice and active as
-    // long as the server runs.
-    info!("Spawning ssh server listening at: {}", addr);
-    thrussh::server::run(config, addr.as_ref(), sh).await.unwrap();
+/// Build the verb "wave", waving to the players in the node or at one of
+/// them (eg. "wave at trinity")
+fn test_wave() -> world::verbs::Verb {
+    world::verbs::Verb::new("wave", |call| {
+        let node = call.node.map(|n| n.name()).ok_or("In limbo nobody sees you wave.")?;
+        Ok(match call.arguments.first() {
+            Some(other) => world::verbs::Reply {
+                text: format!("You wave at {}.", other),
+                node: Some(format!("{} waves at {}.", call.player, other)),
+            },
+            None => world::verbs::Reply {
+                text: format!("You wave to everybody in the {}.", node),
+                node: Some(format!("{} waves.", call.player)),
+            },
+        })
+    })
+    .with_parser(|arguments| {
+        let words: Vec<String> = arguments.split_whitespace()
+            .filter(|w| !w.eq_ignore_ascii_case("at") && !w.eq_ignore_ascii_case("to"))
+            .map(str::to_string)
+            .collect();
+        if words.len() > 1 { Err(world::errors::Error::VerbEncodingError) } else { Ok(words) }
+    })
+    .with_duration(Duration::from_millis(500))
+}
+
+/// Build the vault of the test world, behind the purple port of the lobby
+///
+/// Returns the vault, the uid of its open port back to the lobby and the uid
+/// of its hatch to the mainframe, guarded by ICE.
+fn test_vault(uid: AssetID) -> (world::assets::Node, AssetID, AssetID) {
+    let mut node = world::assets::Node::new(uid);
+    node.update_name("Vault");
+    node.update_description("Racks of cold storage hum in the dark. Data ghosts flicker between them.");
+    node.update_safety(Safety { safe: false, min_level: 2 });
+    node.update_security(2);
+
+    let mut port = world::assets::Port::new(uid + 1);
+    port.update_description("A port back to the lobby, glowing a faint ultraviolet.");
+    port.update_open(true);
+    node.add_asset(Box::new(port));
+
+    let mut quickhack = world::inventory::Item::new(uid + 2, "quickhack");
+    quickhack.update_description("A quickhack on a data shard, left behind by a careless runner.");
+    node.add_asset(Box::new(quickhack));
+
+    let mut credstick = world::inventory::Item::new(uid + 3, "credstick");
+    credstick.update_description("A credstick wedged between two racks, its owner tag still glowing.");
+    credstick.update_ownership(Some(world::ownership::Ownership { owner: "razor".to_string(), lootable: true }));
+    node.add_asset(Box::new(credstick));
+
+    let mut hatch = world::assets::Port::new(uid + 4);
+    hatch.update_description("A maintenance hatch in the floor, a cold glare leaking from the mainframe below.");
+    node.add_asset(Box::new(hatch));
+
+    // The hatch is closed, deckers break the ICE on it to get through
+    let mut ice = Ice::new(uid + 5, uid + 4);
+    ice.update_description("A lattice of black ICE is woven over the hatch, humming with tracer routines.");
+    ice.update_strength(40);
+    ice.update_trace(25);
+    node.add_asset(Box::new(ice));
+    (node, uid + 1, uid + 4)
+}
+
+/// Build the corporate mainframe of the test world, below the vault
+///
+/// Returns the mainframe and the uid of its open port back to the vault.
+fn test_mainframe(uid: AssetID) -> (world::assets::Node, AssetID) {
+    let mut node = world::assets::Node::new(uid);
+    node.update_name("Mainframe");
+    node.update_description("The core of the corporate grid, a cathedral of monolithic processors. Something \
+        vast stirs in their glare when the raid is on.");
+    node.update_safety(Safety { safe: false, min_level: 3 });
+    node.update_security(3);
+
+    let mut port = world::assets::Port::new(uid + 1);
+    port.update_description("A ladder of light back up to the vault.");
+    port.update_open(true);
+    node.add_asset(Box::new(port));
+    (node, uid + 1)
+}
+
+/// Build the goal of the test world: destroying the warden of the mainframe
+/// liberates subnet-7, leaving the hatch to the mainframe open for good
+fn test_liberation(mainframe: AssetID, hatch: AssetID) -> world::goals::Goal {
+    world::goals::Goal::new("subnet-7-liberated", "Destroy the warden of the mainframe to liberate subnet-7",
+            Deed::Destroy("warden".to_string()), 3)
+        .announcing("Subnet-7 is liberated! The corporate ICE on the mainframe falls silent for good.")
+        .with_change(Change::Describe { uid: mainframe, text: "The core of the liberated subnet-7, its monolithic \
+            processors painted with the tags of the deckers that broke the warden.".to_string() })
+        .with_change(Change::Open { port: hatch })
+        .with_change(Change::Say { npc: "fixer".to_string(),
+            line: "Subnet-7 is ours now. Drinks are on the runners tonight.".to_string() })
+}
+
+/// Add the raid on the mainframe of the test world: its warden spawns while
+/// the "raid" season is on (see `seasons`)
+fn test_raid(world: &mut GameWorld, mainframe: Index) {
+    let warden = Prototype::new("warden", "The warden of the mainframe, a colossus of black ICE whose \
+            processors pulse in warning patterns.").hostile()
+        .with_integrity(300);
+    world.add_spawner(Spawner::new(mainframe, warden, 1).during("raid"));
+    world.add_boss(Boss::new("warden", Phase {
+            below: 100,
+            announce: "The warden wakes, its processors flaring to full power.".to_string(),
+            behaviors: vec![Behavior::Strike { interval: Duration::from_secs(12), jam: Duration::from_secs(3) }],
+            telegraph: Some(Telegraph { name: "purge sweep".to_string(), counter: "cloak".to_string(),
+                warning: Duration::from_secs(6), interval: Duration::from_secs(30), jam: Duration::from_secs(8) }),
+        })
+        .with_phase(Phase {
+            below: 50,
+            announce: "The warden splits its cores, sweeping the mainframe for cloaked deckers.".to_string(),
+            behaviors: vec![
+                Behavior::Strike { interval: Duration::from_secs(8), jam: Duration::from_secs(3) },
+                Behavior::Perceive { interval: Duration::from_secs(5), perception: 30 },
+            ],
+            telegraph: Some(Telegraph { name: "trace burst".to_string(), counter: "scan".to_string(),
+                warning: Duration::from_secs(5), interval: Duration::from_secs(20), jam: Duration::from_secs(10) }),
+        })
+        .with_loot(Hook::Credits(300))
+        .with_loot(Hook::Item("warden core".to_string()))
+        .with_loot(Hook::Flag("achievement/warden".to_string()))
+        .sharing(Share::Damage));
 }
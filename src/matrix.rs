@@ -0,0 +1,229 @@
+//! Matrix bridge
+//!
+//! Mirrors the chat of selected zones into a Matrix room and back, as the
+//! con community coordinates on Matrix. The bridge is a plain bot account on
+//! a homeserver (no appservice registration needed):
+//!
+//! - what players say in a mirrored zone is sent to the room as
+//!   "[Lobby] neo: hi".
+//! - what is written in the room is shown to the players in all mirrored
+//!   zones as "[Matrix] alice: hi".
+//!
+//! The bot polls the room with the sync API of the client-server API. The
+//! messages of the bot itself are skipped, so nothing is mirrored twice.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use serde_json::{json, Value};
+use tokio::sync::mpsc;
+use tracing::{debug, info, warn};
+
+use crate::connection_manager::Command;
+use crate::connection_manager::channels::MeteredSender;
+use crate::world::timestamp;
+
+/// Time the homeserver may hold a sync request open (in milliseconds)
+const SYNC_TIMEOUT_MS: u64 = 30_000;
+
+/// Time to wait before retrying a failed request
+const RETRY_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Longest message taken from the room (in characters)
+pub const MAX_MESSAGE: usize = 280;
+
+/// Number of messages to the room that can be queued
+pub const QUEUE_CAPACITY: usize = 64;
+
+/// Configuration of the bridge
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// URL of the homeserver (eg. "https://matrix.example.org")
+    pub homeserver: String,
+    /// Access token of the bot account
+    pub access_token: String,
+    /// Matrix ID of the bot account (eg. "@mud:example.org")
+    pub user_id: String,
+    /// ID of the room (eg. "!abc:example.org")
+    pub room_id: String,
+    /// Names of the zones mirrored into the room
+    pub zones: Vec<String>,
+}
+
+/// A message written in the room
+#[derive(Debug, Clone, PartialEq)]
+pub struct Incoming {
+    /// Matrix ID of the author
+    pub sender: String,
+    /// The text, stripped of control characters
+    pub body: String,
+}
+
+impl Incoming {
+    /// The name the author is shown with (the localpart of its Matrix ID)
+    pub fn author(&self) -> &str {
+        let name = self.sender.trim_start_matches('@');
+        name.split(':').next().unwrap_or(name)
+    }
+}
+
+/// The bridge, as the world sees it
+#[derive(Debug)]
+pub struct Bridge {
+    zones: Vec<String>,
+    tx: mpsc::Sender<String>,
+}
+
+impl Bridge {
+    /// Create the bridge
+    ///
+    /// Returns the receiver of the messages to the room, for `run`.
+    pub fn new(config: &Config) -> (Bridge, mpsc::Receiver<String>) {
+        let (tx, rx) = mpsc::channel(QUEUE_CAPACITY);
+        (Bridge { zones: config.zones.clone(), tx }, rx)
+    }
+
+    /// True if the chat of a zone is mirrored (ignoring case)
+    pub fn mirrors(&self, zone: &str) -> bool {
+        self.zones.iter().any(|z| z.eq_ignore_ascii_case(zone))
+    }
+
+    /// Mirror what a player said in a zone into the room
+    ///
+    /// Does not block the world. Chat is dropped if the room cannot keep up.
+    pub fn say(&self, zone: &str, player: &str, text: &str) {
+        if self.tx.try_send(format!("[{}] {}: {}", zone, player, text)).is_err() {
+            debug!("Dropped chat of {} for the Matrix room.", player);
+        }
+    }
+}
+
+/// Run the bridge
+///
+/// Sends the chat of the world to the room and passes the messages of the
+/// room to the world over the command channel, until the world is gone.
+pub async fn run(config: Config, command_tx: MeteredSender<Command>, mut outgoing: mpsc::Receiver<String>) {
+    let client = match reqwest::Client::builder()
+            .timeout(Duration::from_millis(SYNC_TIMEOUT_MS) + RETRY_INTERVAL)
+            .build() {
+        Ok(c) => c,
+        Err(e) => {
+            warn!("Matrix bridge could not start: {}", e);
+            return;
+        },
+    };
+    info!("Matrix bridge mirroring {:?} into {}", config.zones, config.room_id);
+
+    let sending = {
+        let (client, config) = (client.clone(), config.clone());
+        tokio::spawn(async move {
+            while let Some(text) = outgoing.recv().await {
+                if let Err(e) = send(&client, &config, &text).await {
+                    warn!("Could not send to the Matrix room: {}", e);
+                }
+            }
+        })
+    };
+
+    // The first sync only tells where the room stands, so older messages
+    // are not replayed
+    let mut since: Option<String> = None;
+    loop {
+        let response = match sync(&client, &config, since.as_deref()).await {
+            Ok(r) => r,
+            Err(e) => {
+                warn!("Could not sync with the Matrix room: {}", e);
+                tokio::time::sleep(RETRY_INTERVAL).await;
+                continue;
+            },
+        };
+        if since.is_some() {
+            for message in messages(&response, &config.room_id, &config.user_id) {
+                if command_tx.send(Command::Matrix(message)).await.is_err() {
+                    sending.abort();
+                    return;
+                }
+            }
+        }
+        match response["next_batch"].as_str() {
+            Some(b) => since = Some(b.to_string()),
+            None => {
+                warn!("The homeserver sent a sync without a batch token.");
+                tokio::time::sleep(RETRY_INTERVAL).await;
+            },
+        }
+    }
+}
+
+/// Fetch what happened in the room since the given batch
+async fn sync(client: &reqwest::Client, config: &Config, since: Option<&str>) -> reqwest::Result<Value> {
+    let filter = json!({ "room": { "rooms": [config.room_id], "timeline": { "limit": 50 } } });
+    let mut query = vec![("filter", filter.to_string()), ("timeout", SYNC_TIMEOUT_MS.to_string())];
+    if let Some(since) = since {
+        query.push(("since", since.to_string()));
+    }
+    client.get(format!("{}/_matrix/client/v3/sync", config.homeserver.trim_end_matches('/')))
+        .bearer_auth(&config.access_token)
+        .query(&query)
+        .send().await?
+        .error_for_status()?
+        .json().await
+}
+
+/// Send a text to the room
+async fn send(client: &reqwest::Client, config: &Config, text: &str) -> reqwest::Result<()> {
+    // The transaction id makes retries of the homeserver idempotent
+    static TRANSACTIONS: AtomicU64 = AtomicU64::new(0);
+    let txn_id = format!("mud-{}-{}", timestamp(), TRANSACTIONS.fetch_add(1, Ordering::Relaxed));
+    let url = format!("{}/_matrix/client/v3/rooms/{}/send/m.room.message/{}",
+        config.homeserver.trim_end_matches('/'), encode(&config.room_id), txn_id);
+    client.put(url)
+        .bearer_auth(&config.access_token)
+        .json(&json!({ "msgtype": "m.text", "body": text }))
+        .send().await?
+        .error_for_status()?;
+    Ok(())
+}
+
+/// The text messages written in the room by others than the bot
+///
+/// Takes the response of a sync request.
+pub fn messages(sync: &Value, room_id: &str, user_id: &str) -> Vec<Incoming> {
+    let events = match sync["rooms"]["join"][room_id]["timeline"]["events"].as_array() {
+        Some(e) => e,
+        None => return Vec::new(),
+    };
+    events.iter()
+        .filter(|e| e["type"] == "m.room.message" && e["sender"] != user_id)
+        .filter(|e| e["content"]["msgtype"] == "m.text" || e["content"]["msgtype"] == "m.emote")
+        .filter_map(|e| {
+            let sender = e["sender"].as_str()?.to_string();
+            let body = sanitize(e["content"]["body"].as_str()?);
+            Some(Incoming { sender, body }).filter(|m| !m.body.is_empty())
+        })
+        .collect()
+}
+
+/// Make a text from the room safe to show on a terminal
+///
+/// Line breaks become blanks, other control characters (eg. ANSI escape
+/// sequences) are removed and the text is cut at `MAX_MESSAGE` characters.
+pub fn sanitize(text: &str) -> String {
+    text.chars()
+        .map(|c| if c == '\n' || c == '\t' { ' ' } else { c })
+        .filter(|c| !c.is_control())
+        .take(MAX_MESSAGE)
+        .collect::<String>()
+        .trim()
+        .to_string()
+}
+
+/// Percent encode a path segment
+fn encode(segment: &str) -> String {
+    segment.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
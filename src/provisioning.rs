@@ -0,0 +1,313 @@
+//! Badge provisioning
+//!
+//! An HTTP endpoint for the badge flashing pipeline. For every badge it is
+//! given the serial of, it
+//!
+//! - generates a keypair for the badge, or registers the public key the
+//!   pipeline submits,
+//! - allows the key to connect as the player of the badge only (without a
+//!   restart of the server),
+//! - creates a provisional account for the badge ("badge-<serial>"),
+//! - answers the connection details as JSON.
+//!
+//! `POST /badges` takes `{"serial": "...", "public_key": "ssh-ed25519 ..."}`
+//! (the key is optional) and must carry the token of the pipeline as bearer
+//! token. Provisioning a badge again replaces its key.
+//!
+//! The badges are stored as JSON file inside the data directory of the world
+//! and their keys are allowed to connect again after a restart. Forgotten
+//! players lose their badges (see `privacy`).
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use thrussh_keys::PublicKeyBase64;
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+
+use crate::connection_manager::keyring::Keyring;
+use crate::http;
+use crate::world::accounts::Account;
+use crate::world::errors::Error as HandleError;
+use crate::world::handles::HandleRules;
+use crate::world::timestamp;
+
+/// Name of the file the badges are stored in (inside the data directory)
+pub const BADGES_FILE: &str = "badges.json";
+
+/// Longest serial accepted
+pub const MAX_SERIAL: usize = 32;
+
+/// Longest request accepted (head and body)
+const MAX_REQUEST: usize = 8 * 1024;
+
+/// A provisioned badge
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Badge {
+    /// The player the badge connects as
+    pub player: String,
+    /// The public key of the badge (base64 blob)
+    pub public_key: String,
+    /// Seconds since the epoch when the badge was provisioned
+    pub provisioned: u64,
+}
+
+/// The provisioned badges, by serial
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Badges {
+    badges: BTreeMap<String, Badge>,
+}
+
+impl Badges {
+    /// Load the badges stored in the data directory
+    pub async fn load(data_dir: &Path) -> io::Result<Badges> {
+        match tokio::fs::read(Badges::path(data_dir)).await {
+            Ok(buf) => Ok(serde_json::from_slice(&buf)?),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Badges::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Save the badges to the data directory
+    pub async fn save(&self, data_dir: &Path) -> io::Result<()> {
+        tokio::fs::create_dir_all(data_dir).await?;
+        tokio::fs::write(Badges::path(data_dir), serde_json::to_vec_pretty(self)?).await
+    }
+
+    /// The public keys of all badges and the players they connect as
+    pub fn keys(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.badges.values().map(|b| (b.public_key.as_str(), b.player.as_str()))
+    }
+
+    /// The badges of a player (ignoring case), by serial
+    pub fn of(&self, player: &str) -> Vec<(String, Badge)> {
+        self.badges.iter()
+            .filter(|(_, b)| b.player.eq_ignore_ascii_case(player))
+            .map(|(s, b)| (s.clone(), b.clone()))
+            .collect()
+    }
+
+    /// Remove the badges of a player from the badges file
+    pub async fn remove_stored(data_dir: &Path, player: &str) -> io::Result<()> {
+        let mut badges = Badges::load(data_dir).await?;
+        if badges.of(player).is_empty() {
+            return Ok(());
+        }
+        badges.badges.retain(|_, b| !b.player.eq_ignore_ascii_case(player));
+        badges.save(data_dir).await
+    }
+
+    /// Path of the badges file
+    fn path(data_dir: &Path) -> PathBuf {
+        data_dir.join(BADGES_FILE)
+    }
+}
+
+/// Errors provisioning a badge
+#[derive(Debug)]
+pub enum Error {
+    /// The serial is empty, too long or contains other characters than
+    /// letters, digits and dashes
+    InvalidSerial,
+    /// The submitted public key cannot be parsed
+    InvalidKey,
+    /// The serial does not make a valid handle
+    InvalidHandle(HandleError),
+    /// The badge or the account could not be stored
+    Storage(io::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InvalidSerial => write!(f, "invalid serial"),
+            Error::InvalidKey => write!(f, "invalid public key"),
+            Error::InvalidHandle(e) => write!(f, "the serial makes no valid handle ({})", e),
+            Error::Storage(e) => write!(f, "storage failed ({})", e),
+        }
+    }
+}
+
+/// A request of the pipeline
+#[derive(Debug, Clone, Deserialize)]
+pub struct Request {
+    pub serial: String,
+    /// Public key in OpenSSH format ("ssh-ed25519 AAAA... comment") or as
+    /// base64 blob
+    #[serde(default)]
+    pub public_key: Option<String>,
+}
+
+/// The connection details of a provisioned badge
+#[derive(Debug, Clone, Serialize)]
+pub struct Provisioned {
+    pub serial: String,
+    /// The player name the badge connects as
+    pub player: String,
+    pub host: String,
+    pub port: u32,
+    /// The public key of the badge in OpenSSH format
+    pub public_key: String,
+    /// The generated private key (PKCS#8 PEM), if no key was submitted
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub private_key: Option<String>,
+}
+
+/// Provisions badges
+#[derive(Debug, Clone)]
+pub struct Provisioner {
+    /// Token the pipeline authenticates with
+    pub token: String,
+    /// Directory the world stores its data in
+    pub data_dir: PathBuf,
+    /// Prefix of the player names of badges (eg. "badge-")
+    pub handle_prefix: String,
+    /// Rules the player names of badges must follow
+    pub rules: HandleRules,
+    /// Host and port badges connect to
+    pub ssh_host: String,
+    pub ssh_port: u32,
+    /// The keys allowed to connect to the ssh server
+    pub keyring: Keyring,
+    /// Held while a badge is provisioned, so the badges file is changed by
+    /// one request at a time
+    provisioning: Arc<Mutex<()>>,
+}
+
+impl Provisioner {
+    /// Create a provisioner for the given badges
+    ///
+    /// The keys of the badges are allowed to connect as the players of
+    /// their badges.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn new(token: &str, data_dir: &Path, handle_prefix: &str, rules: HandleRules, ssh_host: &str,
+                     ssh_port: u32, keyring: Keyring, badges: Badges) -> Provisioner {
+        for (key, player) in badges.keys() {
+            if let Err(e) = keyring.bind(key, player).await {
+                warn!("Could not bind the key of {}: {}", player, e);
+            }
+        }
+        Provisioner {
+            token: token.to_string(),
+            data_dir: data_dir.to_path_buf(),
+            handle_prefix: handle_prefix.to_string(),
+            rules,
+            ssh_host: ssh_host.to_string(),
+            ssh_port,
+            keyring,
+            provisioning: Arc::new(Mutex::new(())),
+        }
+    }
+
+    /// Provision a badge
+    pub async fn provision(&self, request: &Request) -> Result<Provisioned, Error> {
+        let serial = request.serial.trim();
+        if serial.is_empty() || serial.len() > MAX_SERIAL
+            || !serial.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+            return Err(Error::InvalidSerial);
+        }
+        let player = format!("{}{}", self.handle_prefix, serial.to_lowercase());
+        self.rules.validate_handle(&player).map_err(Error::InvalidHandle)?;
+
+        let (key, private_key) = match &request.public_key {
+            Some(k) => (parse_key(k).ok_or(Error::InvalidKey)?, None),
+            None => {
+                let pair = thrussh_keys::key::KeyPair::generate_ed25519().ok_or(Error::InvalidKey)?;
+                let mut pem = Vec::new();
+                thrussh_keys::encode_pkcs8_pem(&pair, &mut pem).map_err(|_| Error::InvalidKey)?;
+                (pair.clone_public_key(), Some(String::from_utf8_lossy(&pem).to_string()))
+            },
+        };
+        let public_key = key.public_key_base64();
+
+        // The badges are read again, the world removes those of forgotten
+        // players
+        let _provisioning = self.provisioning.lock().await;
+        let mut badges = Badges::load(&self.data_dir).await.map_err(Error::Storage)?;
+        let mut account = Account::load(&self.data_dir, &player).await.map_err(Error::Storage)?;
+        account.badge = Some(serial.to_string());
+        account.save(&self.data_dir).await.map_err(Error::Storage)?;
+        let badge = Badge { player: player.clone(), public_key: public_key.clone(), provisioned: timestamp() };
+        if let Some(old) = badges.badges.insert(serial.to_string(), badge) {
+            if old.public_key != public_key {
+                self.keyring.unbind(&old.public_key).await.map_err(Error::Storage)?;
+            }
+        }
+        badges.save(&self.data_dir).await.map_err(Error::Storage)?;
+        self.keyring.bind(&public_key, &player).await.map_err(Error::Storage)?;
+        info!("Provisioned badge {} for {}.", serial, player);
+
+        Ok(Provisioned {
+            serial: serial.to_string(),
+            player,
+            host: self.ssh_host.clone(),
+            port: self.ssh_port,
+            public_key: format!("{} {}", key.name(), public_key),
+            private_key,
+        })
+    }
+
+    /// True if the head of a request carries the token of the pipeline
+    pub fn is_authorized(&self, head: &str) -> bool {
+        let expected = format!("Bearer {}", self.token);
+        !self.token.is_empty() && head.lines()
+            .filter_map(|l| l.split_once(':'))
+            .filter(|(name, _)| name.trim().eq_ignore_ascii_case("authorization"))
+            .any(|(_, value)| constant_time_eq(value.trim().as_bytes(), expected.as_bytes()))
+    }
+}
+
+/// Parse a public key in OpenSSH format or as base64 blob
+fn parse_key(key: &str) -> Option<thrussh_keys::key::PublicKey> {
+    let mut parts = key.split_whitespace();
+    let first = parts.next()?;
+    let blob = parts.next().unwrap_or(first);
+    thrussh_keys::parse_public_key_base64(blob).ok()
+}
+
+/// Compare two byte strings in time independent of where they differ
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Serve the provisioning endpoint at the given address
+pub async fn serve(addr: &str, provisioner: Provisioner) -> io::Result<()> {
+    http::serve(addr, "Badge provisioning", MAX_REQUEST, move |request| {
+        let provisioner = provisioner.clone();
+        async move { answer(&request, &provisioner).await }
+    }).await
+}
+
+/// Answer a single request
+async fn answer(request: &http::Request, provisioner: &Provisioner) -> String {
+    match request.target() {
+        (Some("POST"), Some("/badges")) if !provisioner.is_authorized(&request.head) => {
+            warn!("Unauthorized provisioning request.");
+            http::json("401 Unauthorized", &error_body("unauthorized"))
+        },
+        (Some("POST"), Some("/badges")) => match serde_json::from_slice::<Request>(&request.body) {
+            Ok(request) => match provisioner.provision(&request).await {
+                Ok(provisioned) => match serde_json::to_string(&provisioned) {
+                    Ok(body) => http::json("200 OK", &body),
+                    Err(_) => http::json("500 Internal Server Error", &error_body("serialization failed")),
+                },
+                Err(Error::Storage(e)) => {
+                    warn!("Could not provision badge {}: {}", request.serial, e);
+                    http::json("500 Internal Server Error", &error_body("storage failed"))
+                },
+                Err(e) => http::json("422 Unprocessable Entity", &error_body(&e.to_string())),
+            },
+            Err(e) => http::json("400 Bad Request", &error_body(&e.to_string())),
+        },
+        (Some(_), Some("/badges")) => http::json("405 Method Not Allowed", &error_body("method not allowed")),
+        _ => http::json("404 Not Found", &error_body("not found")),
+    }
+}
+
+/// Build an error body
+fn error_body(error: &str) -> String {
+    serde_json::json!({ "error": error }).to_string()
+}
+
@@ -1,27 +1,211 @@
 use config::{ConfigError, Config, File};
+use std::collections::BTreeMap;
 
+#[allow(dead_code)]
 #[derive(Debug, Deserialize)]
 pub struct General {
     pub debug: bool,
 }
 
+#[allow(dead_code)]
 #[derive(Debug, Deserialize)]
 pub struct SSHServer {
     pub start_ssh: bool,
     pub port: u32,
     pub host: String,
+    pub host_key_path: String,
+    pub history_depth: usize,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TelnetServer {
+    pub enabled: bool,
+    pub port: u32,
+    pub host: String,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct Security {
-    pub allowed_keys: Vec<Vec<String>>
+    /// Deprecated, use authorized_keys
+    #[serde(default)]
+    pub allowed_keys: Vec<Vec<String>>,
+    #[serde(default)]
+    pub authorized_keys: String,
+    #[serde(default)]
+    pub admins: Vec<String>,
+    #[serde(default)]
+    pub builders: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct World {
+    pub name: String,
+    pub data_dir: String,
+    pub screen_dir: String,
+    pub help_dir: String,
+    pub autosave_s: u64,
+    pub file: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct HostedWorld {
+    pub data_dir: String,
+    pub spawn: String,
+    pub description: String,
+    #[serde(default)]
+    pub portal: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Handles {
+    pub min_length: usize,
+    pub max_length: usize,
+    pub max_title_length: usize,
+    pub max_description_length: usize,
+    pub reserved: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Channels {
+    pub command_capacity: usize,
+    pub data_capacity: usize,
+    pub push_capacity: usize,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Health {
+    pub enabled: bool,
+    pub host: String,
+    pub port: u32,
+    pub max_heartbeat_age_ms: u64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Feed {
+    pub enabled: bool,
+    pub host: String,
+    pub port: u32,
+    pub title: String,
+    pub url: String,
+    pub max_items: usize,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Provisioning {
+    pub enabled: bool,
+    pub host: String,
+    pub port: u32,
+    pub token: String,
+    pub handle_prefix: String,
+    pub ssh_host: String,
+    pub ssh_port: u32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LoginTokens {
+    pub enabled: bool,
+    pub ttl_minutes: u64,
+    pub ssh_host: String,
+    pub ssh_port: u32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Telemetry {
+    pub otlp_enabled: bool,
+    pub otlp_endpoint: String,
+    pub otlp_filter: String,
+    pub service_name: String,
+    pub metrics_interval_s: u64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Analytics {
+    pub enabled: bool,
+    pub log_interval_s: u64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Banners {
+    pub font_dir: String,
+    pub default_font: String,
+    pub color: String,
+    pub max_width: usize,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Media {
+    pub url: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Content {
+    pub profanity: Vec<String>,
+    pub filter: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Federation {
+    pub enabled: bool,
+    pub name: String,
+    pub peer_name: String,
+    pub listen: String,
+    pub connect: String,
+    pub secret: String,
+    pub ssh: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Matrix {
+    pub enabled: bool,
+    pub homeserver: String,
+    pub access_token: String,
+    pub user_id: String,
+    pub room_id: String,
+    pub zones: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Casino {
+    pub seed: u64,
+    pub max_wager: u64,
+    pub max_loss: u64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Season {
+    pub start: u64,
+    pub end: u64,
 }
 
+#[allow(dead_code)]
 #[derive(Debug, Deserialize)]
 pub struct Settings {
     pub general: General,
     pub ssh_server: SSHServer,
+    pub telnet_server: TelnetServer,
     pub security: Security,
+    pub world: World,
+    pub handles: Handles,
+    pub content: Content,
+    pub channels: Channels,
+    pub health: Health,
+    pub feed: Feed,
+    pub provisioning: Provisioning,
+    pub login_tokens: LoginTokens,
+    pub telemetry: Telemetry,
+    pub analytics: Analytics,
+    pub banners: Banners,
+    pub media: Media,
+    pub federation: Federation,
+    pub matrix: Matrix,
+    pub casino: Casino,
+    #[serde(default)]
+    pub seasons: BTreeMap<String, Season>,
+    #[serde(default)]
+    pub worlds: BTreeMap<String, HostedWorld>,
+    #[cfg(feature = "chaos")]
+    #[serde(default)]
+    pub chaos: crate::connection_manager::chaos::Chaos,
 }
 
 impl Settings {
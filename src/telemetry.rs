@@ -0,0 +1,305 @@
+//! Tracing and telemetry
+//!
+//! The server always runs the console subscriber (for tokio-console) and
+//! logs to stdout as configured by `RUST_LOG`. If enabled in the settings,
+//! spans and metrics are also exported to an OTLP collector (eg. Jaeger or
+//! Tempo), so the server can be watched during the event. The log levels
+//! can be changed at runtime by the admins (see `LogFilter`).
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use opentelemetry::{global, KeyValue};
+use opentelemetry::sdk::{trace, Resource};
+use opentelemetry::sdk::metrics::{PushController, PushControllerWorker};
+use opentelemetry_otlp::WithExportConfig;
+use tokio_stream::wrappers::IntervalStream;
+use tracing_subscriber::filter::{LevelFilter, Targets};
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::reload;
+
+use crate::connection_manager::channels::Metrics;
+use crate::settings;
+use crate::world::stats::Heartbeat;
+use crate::world::economy::Ledger;
+use crate::world::analytics::Analytics;
+
+/// Name of the meter the server reports its metrics with
+const METER: &str = "mud-server";
+
+/// Short names of targets for changing the log level
+const TARGET_ALIASES: &[(&str, &str)] = &[
+    ("server", "mud_server"),
+    ("world", "mud_server::world"),
+    ("ssh", "mud_server::connection_manager"),
+    ("health", "mud_server::health"),
+];
+
+/// Target that changes the default log level
+const DEFAULT_TARGET: &str = "all";
+
+/// Handle of the telemetry, must be kept alive while the server runs
+#[derive(Debug)]
+pub struct Telemetry {
+    /// Filter of the stdout log
+    pub log_filter: LogFilter,
+    // Pushes the metrics to the collector until dropped
+    _metrics: Option<PushController>,
+}
+
+/// Function applying a new filter to the log
+type Reload = Box<dyn Fn(Targets) -> Result<(), String> + Send>;
+
+/// Filter of the stdout log that can be changed at runtime
+///
+/// Clones share the filter.
+#[derive(Clone)]
+pub struct LogFilter {
+    inner: Arc<Mutex<FilterState>>,
+}
+
+struct FilterState {
+    default: LevelFilter,
+    targets: BTreeMap<String, LevelFilter>,
+    reload: Reload,
+}
+
+impl LogFilter {
+    /// Create a filter starting with the given targets and applying changes
+    /// with the reload function
+    pub fn new(initial: &Targets, reload: Reload) -> LogFilter {
+        let state = FilterState {
+            default: initial.default_level().unwrap_or(LevelFilter::OFF),
+            targets: initial.iter().map(|(t, l)| (t.to_string(), l)).collect(),
+            reload,
+        };
+        LogFilter { inner: Arc::new(Mutex::new(state)) }
+    }
+
+    /// Change the levels of the targets given
+    ///
+    /// Targets can be given by their short names (eg. "world"), "all" changes
+    /// the default level.
+    pub fn set(&self, directives: &[(String, LevelFilter)]) -> Result<(), String> {
+        let mut state = self.inner.lock().map_err(|_| "Log filter poisoned".to_string())?;
+        let mut default = state.default;
+        let mut targets = state.targets.clone();
+        for (target, level) in directives {
+            if target == DEFAULT_TARGET {
+                default = *level;
+                continue;
+            }
+            let target = TARGET_ALIASES.iter()
+                .find(|(alias, _)| alias == target)
+                .map_or(target.as_str(), |(_, t)| t);
+            targets.insert(target.to_string(), *level);
+        }
+        let filter = Targets::new().with_default(default).with_targets(targets.clone());
+        (state.reload)(filter)?;
+        state.default = default;
+        state.targets = targets;
+        Ok(())
+    }
+}
+
+/// A filter not connected to a log (eg. in tests)
+impl Default for LogFilter {
+    fn default() -> LogFilter {
+        LogFilter::new(&Targets::new().with_default(LevelFilter::TRACE), Box::new(|_| Ok(())))
+    }
+}
+
+/// Display the current levels, eg. "all=error mud_server::world=debug"
+impl fmt::Display for LogFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.inner.lock().map_err(|_| fmt::Error)?;
+        write!(f, "{}={}", DEFAULT_TARGET, state.default.to_string().to_lowercase())?;
+        for (target, level) in state.targets.iter() {
+            write!(f, " {}={}", target, level.to_string().to_lowercase())?;
+        }
+        Ok(())
+    }
+}
+
+// Derive is not possible for the reload function
+impl fmt::Debug for LogFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "LogFilter({})", self)
+    }
+}
+
+/// Set up tracing and, if enabled, the export to an OTLP collector
+///
+/// Failing to set up the export is not fatal, the server runs without it.
+pub fn init(settings: &settings::Telemetry) -> Telemetry {
+    // Same default as the console subscriber: log errors only
+    let fmt_filter = std::env::var("RUST_LOG").ok()
+        .and_then(|filter| filter.parse::<Targets>().ok())
+        .unwrap_or_else(|| Targets::new().with_default(LevelFilter::ERROR));
+    let console_layer = console_subscriber::ConsoleLayer::builder()
+        .with_default_env()
+        .spawn();
+
+    let mut errors = Vec::new();
+    let otlp_layer = if settings.otlp_enabled {
+        match (tracer(settings), settings.otlp_filter.parse::<Targets>()) {
+            (Ok(tracer), Ok(filter)) => {
+                Some(tracing_opentelemetry::layer().with_tracer(tracer).with_filter(filter))
+            },
+            (Err(e), _) => {
+                errors.push(format!("Could not set up the OTLP span export: {}", e));
+                None
+            },
+            (_, Err(e)) => {
+                errors.push(format!("Invalid OTLP filter {}: {}", settings.otlp_filter, e));
+                None
+            },
+        }
+    } else {
+        None
+    };
+    let metrics = if settings.otlp_enabled {
+        match meter(settings) {
+            Ok(controller) => Some(controller),
+            Err(e) => {
+                errors.push(format!("Could not set up the OTLP metrics export: {}", e));
+                None
+            },
+        }
+    } else {
+        None
+    };
+
+    let (reloadable_filter, reload_handle) = reload::Layer::new(fmt_filter.clone());
+    tracing_subscriber::registry()
+        .with(console_layer)
+        .with(tracing_subscriber::fmt::layer().with_filter(reloadable_filter))
+        .with(otlp_layer)
+        .init();
+    for e in errors {
+        tracing::error!("{}", e);
+    }
+    let reload = Box::new(move |filter| reload_handle.reload(filter).map_err(|e| e.to_string()));
+    Telemetry {
+        log_filter: LogFilter::new(&fmt_filter, reload),
+        _metrics: metrics,
+    }
+}
+
+/// Build the tracer exporting spans in batches
+fn tracer(settings: &settings::Telemetry) -> Result<trace::Tracer, opentelemetry::trace::TraceError> {
+    opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(opentelemetry_otlp::new_exporter()
+            .tonic()
+            .with_endpoint(&settings.otlp_endpoint))
+        .with_trace_config(trace::config().with_resource(resource(settings)))
+        .install_batch(opentelemetry::runtime::Tokio)
+}
+
+/// Build the controller pushing the metrics periodically
+fn meter(settings: &settings::Telemetry) -> opentelemetry::metrics::Result<PushController> {
+    opentelemetry_otlp::new_pipeline()
+        .metrics(spawn_worker, interval)
+        .with_exporter(opentelemetry_otlp::new_exporter()
+            .tonic()
+            .with_endpoint(&settings.otlp_endpoint))
+        .with_resource(resource(settings).iter().map(|(k, v)| KeyValue::new(k.clone(), v.clone())))
+        .with_period(Duration::from_secs(settings.metrics_interval_s))
+        .build()
+}
+
+/// Attributes identifying the server
+fn resource(settings: &settings::Telemetry) -> Resource {
+    Resource::new(vec![KeyValue::new("service.name", settings.service_name.clone())])
+}
+
+/// Run the worker of the metrics controller
+fn spawn_worker(worker: PushControllerWorker) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(worker)
+}
+
+/// Ticks of the metrics controller, the first one after a full period
+fn interval(period: Duration) -> IntervalStream {
+    let start = tokio::time::Instant::now() + period;
+    IntervalStream::new(tokio::time::interval_at(start, period))
+}
+
+/// Report the statistics of the channels, the world loop, the economy and the
+/// analytics as metrics
+///
+/// Does nothing if the metrics export is not enabled.
+pub fn observe(metrics: &Metrics, heartbeat: &Heartbeat, ledger: &Ledger, analytics: &Analytics) {
+    let meter = global::meter(METER);
+    let channels = metrics.clone();
+    meter.u64_value_observer("mud.channel.queued", move |result| {
+            for stats in [&channels.command, &channels.data, &channels.push] {
+                result.observe(stats.queued() as u64, &[KeyValue::new("channel", stats.name)]);
+            }
+        })
+        .with_description("Messages queued in the channel")
+        .init();
+    let channels = metrics.clone();
+    meter.u64_sum_observer("mud.channel.sent", move |result| {
+            for stats in [&channels.command, &channels.data, &channels.push] {
+                result.observe(stats.sent(), &[KeyValue::new("channel", stats.name)]);
+            }
+        })
+        .with_description("Messages sent over the channel")
+        .init();
+    let channels = metrics.clone();
+    meter.u64_sum_observer("mud.channel.dropped", move |result| {
+            for stats in [&channels.command, &channels.data, &channels.push] {
+                result.observe(stats.dropped(), &[KeyValue::new("channel", stats.name)]);
+            }
+        })
+        .with_description("Messages dropped because the channel was full")
+        .init();
+    let heartbeat = heartbeat.clone();
+    meter.u64_value_observer("mud.world.heartbeat_age", move |result| {
+            result.observe(heartbeat.age().as_millis() as u64, &[]);
+        })
+        .with_description("Milliseconds since the world loop last ticked")
+        .init();
+    let ledger = ledger.clone();
+    meter.u64_sum_observer("mud.economy.credits", move |result| {
+            for (flow, system, credits) in ledger.flows() {
+                result.observe(credits, &[KeyValue::new("flow", flow.to_string()), KeyValue::new("system", system)]);
+            }
+        })
+        .with_description("Credits created (faucets) and destroyed (sinks) by the systems of the world")
+        .init();
+    let actions = analytics.clone();
+    meter.u64_sum_observer("mud.analytics.actions", move |result| {
+            for (verb, usage) in actions.actions() {
+                result.observe(usage.performed, &[KeyValue::new("verb", verb)]);
+            }
+        })
+        .with_description("Actions performed by the players")
+        .init();
+    let failures = analytics.clone();
+    meter.u64_sum_observer("mud.analytics.failed", move |result| {
+            for (verb, usage) in failures.actions() {
+                result.observe(usage.failed, &[KeyValue::new("verb", verb)]);
+            }
+        })
+        .with_description("Actions the world answered with an error")
+        .init();
+    let misunderstood = analytics.clone();
+    meter.u64_sum_observer("mud.analytics.misunderstood", move |result| {
+            for (reason, count) in misunderstood.misunderstood_commands() {
+                result.observe(count, &[KeyValue::new("reason", reason.to_string())]);
+            }
+        })
+        .with_description("Commands the grammar did not understand")
+        .init();
+    let nodes = analytics.clone();
+    meter.u64_sum_observer("mud.analytics.node_actions", move |result| {
+            for (uid, count) in nodes.nodes() {
+                result.observe(count, &[KeyValue::new("node", uid.to_string())]);
+            }
+        })
+        .with_description("Actions performed in a node")
+        .init();
+}
@@ -2,15 +2,105 @@
 ///
 use std::sync::Arc;
 use thrussh::*;
-use thrussh::server::{Auth, Session};
 use thrussh::server::Handler;
 use thrussh_keys::*;
 use thrussh_keys::key::KeyPair;
-use futures::Future;
-use std::io::Read;
 use crate::settings::Settings;
 use crate::connection_manager;
 use crate::connection_manager::ssh_server::Server;
+use crate::connection_manager::channels::{self, Capacities, Metrics, SendError};
+use crate::connection_manager::buffers;
+use crate::connection_manager::{ClientConnection, ClientInfo, Command, DataMessage, Frame, Push, PushConnection,
+    Transport};
+use crate::connection_manager::chaos::{Chaos, Disruption, Rng};
+use crate::world::actions::Action;
+use crate::world::assets::{self, Actor, AssetID, AssetResponse, GameAsset, Node, Port, StateChange};
+use crate::world::messages::{Event, Message, OutputMode};
+use crate::world::accounts::Account;
+use crate::world::handles::HandleRules;
+use crate::world::filter::ContentFilter;
+use crate::world::errors::Error;
+use crate::world::reports::BugReport;
+use crate::world::privacy;
+use crate::world::transcripts::Transcript;
+use crate::world::states::{Screen, ScreenType, Screens, DEFAULT_FRAME_DELAY};
+use crate::world::banners::{Banners, Font};
+use crate::world::intern::{self, intern};
+use crate::world::triggers::{self, Firing, Trigger};
+use crate::world::keys;
+use crate::world::media::{self, Protocol};
+use crate::world::terminals::{Reply, Shell, Terminal};
+use crate::world::hooks::Hook;
+use crate::world::puzzles::{self, Outcome, Progress, Puzzle};
+use crate::world::challenges::{self, Challenge, Goal, Leaderboard, Run};
+use crate::world::visitors::Visitors;
+use crate::world::stats::{self, Activity, Heartbeat, SessionStats, Totals};
+use crate::health::HealthCheck;
+use crate::federation::{self, Incoming, Link, Traveler};
+use crate::worlds::{self, Gate, Route, Router, Transfer};
+use crate::matrix::{self, Bridge};
+use crate::feed::{self, Feed};
+use crate::provisioning::{self, Badges, Provisioner};
+use crate::connection_manager::keyring::Keyring;
+use crate::connection_manager::authorized_keys::{self, AuthorizedKey};
+use crate::connection_manager::tokens::LoginTokens;
+use crate::connection_manager::telnet_server::{LineDecoder, TelnetServer, MAX_LINE};
+use crate::connection_manager::line_editor::{self, LineEditor, MAX_CSI};
+use crate::world::qr;
+use crate::world::clock::Clock;
+use crate::world::resets::{Resets, Step, ZoneReset};
+use crate::world::timers::{Effect, Timers};
+use crate::world::propagation::{Level, Noise};
+use crate::world::ownership::{self, Ownership};
+use crate::world::security::{Crime, Response, Security, BRIBE_PER_HEAT};
+use crate::world::help::{Help, PAGE_LINES};
+use crate::world::difficulty::{self, BASE_SCALE, MAX_SCALE};
+use crate::world::checkpoint::Checkpoint;
+use crate::world::bosses::{self, Blow, Boss, Bosses, Phase, Share, Telegraph};
+use crate::world::ice::{self, Exchange, Ice, Intrusions, Move};
+use crate::world::goals::{self, Deed, Goals};
+use crate::world::polls::Poll;
+use crate::world::schedule::{self, Schedule, Scheduling};
+use crate::world::inspect;
+use crate::world::edits::{self, AssetKind, Drafts, Edit, History};
+use crate::world::properties::{Color, Property};
+use crate::world::admin::{PollStep, SandboxStep, ScheduleStep, Target};
+use crate::world::news::{self, News};
+use crate::world::archive::{Archive, ARCHIVE_FORMAT};
+use crate::world::sandboxes::Sandboxes;
+use crate::world::analytics::{Analytics, Misunderstood, Usage};
+use crate::world::coverage::Coverage;
+use crate::world::loader::{self, WorldFile};
+use crate::telemetry::LogFilter;
+use crate::world::admin::AdminCommand;
+use crate::world::{GameWorld, Identifiable, Role, Spawnable, MAX_QUEUED_ACTIONS};
+use crate::world::acl::Acl;
+use crate::world::npcs::{Change, Npcs, Prototype, Spawner};
+use crate::world::ai::{self, Act, Behavior, Blackboard, View};
+use crate::world::threat::Threat;
+use crate::world::combat::{CombatEvent, Verbosity};
+use crate::world::stealth::Cloak;
+use crate::world::drones::{Drone, Order};
+use crate::world::market::{self, Market, Trade};
+use crate::world::bank::{self, Banking, Funds};
+use crate::world::casino::{self, Casino, Game, Limits, Refusal};
+use crate::world::economy::{Deal, Economy, Flow, Ledger, Vendor};
+use crate::world::seasons::{Season, Seasons};
+use crate::world::safety::{self, Safety};
+use crate::world::idle::{self, Idle};
+use crate::world::streaks::{Rewards, Streak};
+use crate::world::appearance::{self, Profile, Wardrobe};
+use crate::world::consent::{Interaction, Pref};
+use crate::world::adverbs::Adverb;
+use crate::world::inventory::{self, Inventory, Item};
+use crate::world::fuzzy;
+use crate::world::verbs::{self, Verb};
+use crate::world::delays::{self, Delays};
+use generational_arena::Index;
+use tracing::level_filters::LevelFilter;
+use tracing_subscriber::filter::Targets;
+use std::convert::TryFrom;
+use std::time::Duration;
 
 /// Verify pbulic key as allowed
 ///
@@ -51,6 +141,3525 @@ async fn check_rejected_pubkey_ssh_ed25519() {
     assert_eq!(auth_result, thrussh::server::Auth::Reject);
 }
 
+/// Parse numbered object references
+///
+/// Both the suffix ("port 2") and the prefix ("2.port") notation must yield
+/// the same target and ordinal.
+#[test]
+fn parse_numbered_object_reference() {
+    for command in ["look at port 2", "look at 2.port"] {
+        match Action::try_from(command) {
+            Ok(Action::Look { target, ordinal, .. }) => {
+                assert_eq!(target, Some("port".to_string()));
+                assert_eq!(ordinal, Some(2));
+            },
+            _ => panic!("\"{}\" was not parsed as look command", command),
+        }
+    }
+}
+
+/// Resolve numbered object references
+///
+/// The nth asset matching the noun must be chosen, counting from one.
+#[test]
+fn resolve_numbered_object_reference() {
+    let mut node = Node::new(0);
+    let mut port = Port::new(1);
+    port.update_description("First port.");
+    node.add_asset(Box::new(port));
+    let mut port = Port::new(2);
+    port.update_description("Second port.");
+    node.add_asset(Box::new(port));
+
+    let puzzles = std::collections::BTreeMap::new();
+    let actor = Actor::new("neo", &puzzles);
+    let response = node.react_to(&actor, &Action::try_from("look at 2.port").unwrap());
+    assert!(response.text.starts_with("Second port."));
+    let response = node.react_to(&actor, &Action::try_from("look at port 3").unwrap());
+    assert_eq!(response, AssetResponse::from("There is no port 3 here."));
+}
+
+/// Render a message in robot mode
+///
+/// The message must be a single line of JSON carrying the event type, the
+/// text without trailing line breaks and the structured fields.
+#[test]
+fn render_message_in_robot_mode() {
+    let message = Message::new(Event::Error, "Error 23: Command not found.\r\n")
+        .with_field("code", 23);
+    assert_eq!(message.render(OutputMode::Robot),
+        "{\"event\":\"error\",\"text\":\"Error 23: Command not found.\",\"fields\":{\"code\":23}}\r\n");
+    assert_eq!(message.render(OutputMode::Text), "Error 23: Command not found.\r\n\r\n");
+}
+
+/// Persist the journal with the account
+///
+/// Notes must survive a save and load cycle and be searchable ignoring case.
+/// Names that could escape the account directory must not do so.
+#[tokio::test]
+async fn persist_and_search_journal() {
+    let data_dir = std::env::temp_dir().join(format!("mud-server-test-{}", std::process::id()));
+    let mut account = Account::new("../neo");
+    account.add_note("Purple port code is 2342");
+    account.add_note("Node 7 has a terminal");
+    account.save(&data_dir).await.unwrap();
+
+    let account = Account::load(&data_dir, "../neo").await.unwrap();
+    let found = account.search_notes("PURPLE");
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].0, 1);
+    assert!(data_dir.join("accounts").join("%2E%2E%2Fneo.json").exists());
+    std::fs::remove_dir_all(&data_dir).unwrap();
+}
+
+/// Bug reports must be appended to the bug report file, which is created
+/// with the data directory, and load again with their context.
+#[tokio::test]
+async fn file_bug_reports() {
+    let data_dir = std::env::temp_dir().join(format!("mud-server-bugs-{}", std::process::id()));
+    assert!(BugReport::load_all(&data_dir).await.unwrap().is_empty());
+    let history = vec!["look".to_string(), "open door".to_string()];
+    BugReport::new("neo", Some(7), history.clone(), "Door is stuck").append_to(&data_dir).await.unwrap();
+    BugReport::new("trinity", None, Vec::new(), "Spoon bends").append_to(&data_dir).await.unwrap();
+
+    let reports = BugReport::load_all(&data_dir).await.unwrap();
+    assert_eq!(reports.len(), 2);
+    assert_eq!((reports[0].player.as_str(), reports[0].location), ("neo", Some(7)));
+    assert_eq!(reports[0].history, history);
+    assert_eq!(reports[0].description, "Door is stuck");
+    assert_eq!(reports[0].version, env!("CARGO_PKG_VERSION"));
+    assert!(reports[0].timestamp > 0);
+    assert_eq!((reports[1].player.as_str(), reports[1].location), ("trinity", None));
+    assert!(reports[1].history.is_empty());
+    std::fs::remove_dir_all(&data_dir).unwrap();
+}
+
+/// Export and forget players
+///
+/// The export must hold the data of every store naming the player. Once
+/// forgotten, no store may name the player anymore, while the data of other
+/// players is kept.
+#[tokio::test]
+async fn export_and_forget_player() {
+    let data_dir = std::env::temp_dir().join(format!("mud-server-test-privacy-{}", std::process::id()));
+    let mut account = Account::new("neo");
+    account.add_note("Follow the white rabbit");
+    account.save(&data_dir).await.unwrap();
+    BugReport::new("neo", None, vec!["look".to_string()], "Spoon bends").append_to(&data_dir).await.unwrap();
+    BugReport::new("trinity", None, Vec::new(), "Door is stuck").append_to(&data_dir).await.unwrap();
+    let transcript = Transcript::start(&data_dir, "neo").await.unwrap();
+    transcript.record_input("look").await.unwrap();
+    transcript.record_output("You see a spoon.\r\n").await.unwrap();
+    let mut visitors = Visitors::default();
+    assert!(visitors.visit(&data_dir, "neo").await.unwrap());
+    assert!(!visitors.visit(&data_dir, "neo").await.unwrap());
+    assert!(visitors.visit(&data_dir, "trinity").await.unwrap());
+    assert_eq!(Visitors::load(&data_dir).await.unwrap().count(), 2);
+    let mut leaderboard = Leaderboard::default();
+    leaderboard.record("lobby-rush", vec!["neo".to_string(), "trinity".to_string()], Duration::from_secs(42));
+    leaderboard.save(&data_dir).await.unwrap();
+    let players = vec![("neo".to_string(), 3), ("trinity".to_string(), 4)].into_iter().collect();
+    Checkpoint { timestamp: 1000, nodes: Vec::new(), players }.save(&data_dir).unwrap();
+    let mut schedule = Schedule::default();
+    let raid = schedule.add(schedule::Kind::World, 2000, "Raid on the mainframe");
+    schedule.subscribe(raid, "neo", 1000).unwrap();
+    schedule.subscribe(raid, "trinity", 1000).unwrap();
+    schedule.save(&data_dir).await.unwrap();
+    let mut market = Market::default();
+    market.list("neo", "icebreaker", 50, 1000);
+    market.list("trinity", "deck", 20, 1000);
+    market.send("neo", market::Parcel { text: "Sold stim patch to trinity.".to_string(), item: None, credits: 5 });
+    market.send("trinity", market::Parcel { text: "Sold purple keycard to Neo.".to_string(), item: None, credits: 9 });
+    market.save(&data_dir).await.unwrap();
+    let trinity = Transcript::start(&data_dir, "trinity").await.unwrap();
+    trinity.record_output("Neo says: Whoa.").await.unwrap();
+    let keyring = Keyring::default();
+    keyring.open(&data_dir).unwrap();
+    keyring.bind("AAAAneo", "neo").await.unwrap();
+    keyring.bind("AAAAtrinity", "trinity").await.unwrap();
+    let badges = serde_json::json!({ "badges": {
+        "BC24-0001": { "player": "neo", "public_key": "AAAAneo", "provisioned": 1000 },
+        "BC24-0002": { "player": "trinity", "public_key": "AAAAtrinity", "provisioned": 1000 },
+    }});
+    std::fs::write(data_dir.join(provisioning::BADGES_FILE), badges.to_string()).unwrap();
+
+    let path = privacy::export(&data_dir, &keyring, "neo").await.unwrap();
+    let export: serde_json::Value = serde_json::from_slice(&std::fs::read(path).unwrap()).unwrap();
+    assert_eq!(export["account"]["notes"][0]["text"], "Follow the white rabbit");
+    assert_eq!(export["bug_reports"].as_array().unwrap().len(), 1);
+    assert!(export["transcripts"][0][1].as_str().unwrap().ends_with("> look\nYou see a spoon.\n"));
+    assert_eq!(export["visitor"], true);
+    assert_eq!(export["records"][0][0], "lobby-rush");
+    assert_eq!(export["reminders"][0][1], "Raid on the mainframe");
+    assert_eq!(export["location"], 3);
+    assert_eq!(export["listings"][0]["item"], "icebreaker");
+    assert_eq!(export["mail"][0]["credits"], 5);
+    assert_eq!(export["keys"][0], "AAAAneo");
+    assert_eq!(export["badges"][0][0], "BC24-0001");
+
+    privacy::forget(&data_dir, &keyring, "neo").await.unwrap();
+    assert!(Transcript::read_all(&data_dir, "neo").await.unwrap().is_empty());
+    assert!(Account::load_stored(&data_dir, "neo").await.unwrap().is_none());
+    let visitors = Visitors::load(&data_dir).await.unwrap();
+    assert!(!visitors.contains("neo"));
+    assert!(visitors.contains("trinity"));
+    let leaderboard = Leaderboard::load(&data_dir).await.unwrap();
+    assert_eq!(leaderboard.records("lobby-rush")[0].players, vec!["trinity".to_string()]);
+    let reports = BugReport::load_all(&data_dir).await.unwrap();
+    assert_eq!(reports[0].player, privacy::ANONYMOUS);
+    assert!(reports[0].history.is_empty());
+    assert_eq!(reports[1].player, "trinity");
+    let checkpoint = Checkpoint::load(&data_dir).await.unwrap().unwrap();
+    assert_eq!(checkpoint.players.keys().collect::<Vec<_>>(), vec!["trinity"]);
+    let schedule = Schedule::load(&data_dir).await.unwrap();
+    assert!(schedule.subscriptions("neo").is_empty());
+    assert_eq!(schedule.subscriptions("trinity").len(), 1);
+    let mut market = Market::load(&data_dir).await.unwrap();
+    assert!(market.listings_of("neo").is_empty());
+    assert_eq!(market.listings_of("trinity").len(), 1);
+    assert!(market.mail_of("neo").is_empty());
+    assert_eq!(market.collect("trinity")[0].text, "Sold purple keycard to anonymous.");
+    let transcripts = Transcript::read_all(&data_dir, "trinity").await.unwrap();
+    assert!(transcripts[0].1.ends_with("anonymous says: Whoa.\n"));
+    assert!(!keyring.allows("AAAAneo", "neo"));
+    assert!(keyring.allows("AAAAtrinity", "trinity"));
+    let reopened = Keyring::default();
+    reopened.open(&data_dir).unwrap();
+    assert!(reopened.bound_to("neo").is_empty());
+    let badges = Badges::load(&data_dir).await.unwrap();
+    assert!(badges.of("neo").is_empty());
+    assert_eq!(badges.of("trinity").len(), 1);
+    std::fs::remove_dir_all(&data_dir).unwrap();
+}
+
+/// Drop data on a full channel
+///
+/// Player data and admin commands sent to a full channel must be dropped and
+/// counted, closing a connection must be delivered once there is room
+/// without the sender waiting for it.
+#[tokio::test]
+async fn drop_data_on_full_channel() {
+    let metrics = Metrics::new(&Capacities { command: 1, data: 2, push: 1 });
+    let (data_tx, mut data_rx) = channels::channel(&metrics.data);
+    for _ in 0..3 {
+        let _ = data_tx.send(DataMessage::new(0, b"look".to_vec())).await;
+    }
+    assert_eq!(metrics.data.sent(), 2);
+    assert_eq!(metrics.data.dropped(), 1);
+    assert_eq!(metrics.data.high_water_mark(), 2);
+    assert!(data_rx.recv().await.is_some());
+
+    let (command_tx, _command_rx) = channels::channel(&metrics.command);
+    command_tx.send(Command::Hangup(0)).await.unwrap();
+    assert_eq!(command_tx.send(Command::Admin(DataMessage::new(0, b"@stats".to_vec()))).await, Err(SendError::Full));
+    assert_eq!(metrics.command.dropped(), 1);
+
+    // Closing the connection waits for room instead of being dropped, but
+    // not in the sender
+    let (push_tx, mut push_rx) = channels::channel(&metrics.push);
+    push_tx.send(Push::Data(buffers::from_slice(b"bye"))).await.unwrap();
+    assert_eq!(push_tx.send(Push::Data(buffers::from_slice(b"lost"))).await, Err(SendError::Full));
+    assert_eq!(push_tx.send(Push::Close).await, Ok(()));
+    assert!(matches!(push_rx.recv().await, Some(Push::Data(_))));
+    assert!(matches!(push_rx.recv().await, Some(Push::Close)));
+    assert_eq!(metrics.push.queued(), 0);
+}
+
+/// Report the health of the server
+///
+/// A full channel to the world must fail the readiness but not the liveness,
+/// a world loop that did not beat for too long must fail the liveness.
+#[tokio::test]
+async fn report_health() {
+    let data_dir = std::env::temp_dir().join(format!("mud-health-{}", std::process::id()));
+    let metrics = Metrics::new(&Capacities { command: 1, data: 1, push: 1 });
+    let mut check = HealthCheck {
+        heartbeat: Heartbeat::new(),
+        metrics: metrics.clone(),
+        data_dir: data_dir.clone(),
+        max_heartbeat_age: std::time::Duration::from_secs(60),
+    };
+    assert!(check.liveness().ok);
+    let report = check.readiness().await;
+    assert!(report.ok);
+    assert_eq!(report.storage, Some(true));
+
+    // A full channel to the world is not ready, but still alive
+    let (data_tx, mut data_rx) = channels::channel(&metrics.data);
+    data_tx.send(DataMessage::new(0, b"look".to_vec())).await.unwrap();
+    assert_eq!(metrics.data.queued(), 1);
+    assert!(check.liveness().ok);
+    assert!(!check.readiness().await.ok);
+    assert!(data_rx.recv().await.is_some());
+    assert_eq!(metrics.data.queued(), 0);
+    assert!(check.readiness().await.ok);
+
+    // The world did not beat for too long
+    check.max_heartbeat_age = std::time::Duration::from_millis(10);
+    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    assert!(!check.liveness().ok);
+    check.heartbeat.beat();
+    assert!(check.liveness().ok);
+    std::fs::remove_dir_all(&data_dir).unwrap();
+}
+
+/// Name keys
+///
+/// Escape sequences and control characters must be named as typed in
+/// "bind", unknown sequences must keep their escape sequence.
+#[test]
+fn name_keys() {
+    assert_eq!(keys::name_of(b"\x1b[A").as_deref(), Some("up"));
+    assert_eq!(keys::name_of(b"\x1bOB").as_deref(), Some("down"));
+    assert_eq!(keys::name_of(b"\x1bOP").as_deref(), Some("f1"));
+    assert_eq!(keys::name_of(b"\x1b[1;5a").as_deref(), Some("ESC[1;5A"));
+    assert_eq!(keys::name_of(&[0x01]).as_deref(), Some("ctrl-a"));
+    assert_eq!(keys::name_of(b"\r"), None);
+    assert_eq!(keys::name_of(b"look"), None);
+    assert_eq!(keys::name_of(b"\x1b"), None);
+
+    assert_eq!(keys::parse_name("UP").as_deref(), Some("up"));
+    assert_eq!(keys::parse_name("ESC[B").as_deref(), Some("down"));
+    assert_eq!(keys::parse_name("esc[1;5a").as_deref(), Some("ESC[1;5A"));
+    assert_eq!(keys::parse_name("^x").as_deref(), Some("ctrl-x"));
+    assert_eq!(keys::parse_name("ctrl-m"), None);
+    assert_eq!(keys::parse_name("space"), None);
+
+    match Action::try_from("bind up look at port 2") {
+        Ok(Action::Bind { key: Some(k), command: Some(c) }) => {
+            assert_eq!((k.as_str(), c.as_str()), ("up", "look at port 2"));
+        },
+        _ => panic!("bind not parsed"),
+    }
+    assert!(matches!(Action::try_from("bind up"), Ok(Action::Bind { key: Some(_), command: None })));
+    assert!(matches!(Action::try_from("bind"), Ok(Action::Bind { key: None, command: None })));
+}
+
+/// Fire triggers with limits
+///
+/// Triggers must only run actions in the world and fire at most
+/// `MAX_FIRES` times per window, the rest must be counted as skipped.
+#[test]
+fn fire_triggers_with_limits() {
+    match Action::try_from("trigger \"ICE detected\" look") {
+        Ok(Action::Trigger(Some((p, c)))) => assert_eq!((p.as_str(), c.as_str()), ("ICE detected", "look")),
+        _ => panic!("trigger not parsed"),
+    }
+    assert!(matches!(Action::try_from("trigger"), Ok(Action::Trigger(None))));
+    assert!(matches!(Action::try_from("untrigger 2"), Ok(Action::Untrigger(2))));
+    assert!(Action::try_from("trigger ICE look").is_err());
+
+    // Only actions in the world can be triggered
+    assert!(Trigger::new("ICE detected", "look").is_ok());
+    assert_eq!(Trigger::new("ICE detected", "stop"), Err(Error::InvalidCommand));
+    assert_eq!(Trigger::new("ICE", "trigger \"ICE\" look"), Err(Error::InvalidCommand));
+    assert_eq!(Trigger::new("ICE", "!!!"), Err(Error::VerbUnknownError));
+    assert_eq!(Trigger::new("", "look"), Err(Error::InvalidLength));
+
+    let triggers = vec![Trigger::new("ICE detected", "look").unwrap()];
+    let trigger = &triggers[0];
+    assert!(trigger.matches("Warning: ice DETECTED at the port"));
+    assert!(!trigger.matches("All quiet"));
+
+    // Triggers fire at most MAX_FIRES times per window
+    let mut firing = Firing::default();
+    let now = tokio::time::Instant::now();
+    for _ in 0..triggers::MAX_FIRES {
+        firing.check(&triggers, "ICE detected");
+    }
+    firing.check(&triggers, "All quiet");
+    assert_eq!(firing.take(now), (vec!["look".to_string(); triggers::MAX_FIRES], 0));
+    firing.check(&triggers, "ICE detected");
+    assert_eq!(firing.take(now), (Vec::new(), 1));
+    firing.check(&triggers, "ICE detected");
+    assert_eq!(firing.take(now + triggers::FIRE_WINDOW), (vec!["look".to_string()], 0));
+}
+
+/// Change log levels
+///
+/// "@loglevel" must set the level per module and keep the levels if the
+/// filter cannot be reloaded.
+#[test]
+fn change_log_levels() {
+    let command = AdminCommand::try_from("@loglevel world=debug ssh=INFO").unwrap();
+    assert_eq!(command.to_string(), "@loglevel world=debug ssh=info");
+    assert!(AdminCommand::try_from("@loglevel world").is_err());
+    assert!(AdminCommand::try_from("@loglevel world=loud").is_err());
+
+    let applied = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let reloads = applied.clone();
+    let filter = LogFilter::new(&Targets::new().with_default(LevelFilter::ERROR),
+        Box::new(move |targets| {
+            reloads.lock().unwrap().push(targets.to_string());
+            Ok(())
+        }));
+    let directives = match command {
+        AdminCommand::LogLevel(d) => d,
+        _ => unreachable!(),
+    };
+    filter.set(&directives).unwrap();
+    filter.set(&[("all".to_string(), LevelFilter::WARN)]).unwrap();
+    assert_eq!(filter.to_string(),
+        "all=warn mud_server::connection_manager=info mud_server::world=debug");
+    assert_eq!(applied.lock().unwrap().len(), 2);
+
+    // A failed reload keeps the levels
+    let filter = LogFilter::new(&Targets::new(), Box::new(|_| Err("gone".to_string())));
+    assert!(filter.set(&directives).is_err());
+    assert_eq!(filter.to_string(), "all=off");
+}
+
+/// Preload screens
+///
+/// Every screen must be loaded at startup, a missing directory must leave
+/// the screens empty instead of failing.
+#[tokio::test]
+async fn preload_screens() {
+    let screens = Screens::load(std::path::Path::new("screens")).await;
+    for screen in ScreenType::ALL.iter() {
+        assert!(!screens.get(*screen).unwrap().final_frame().is_empty());
+    }
+
+    let screens = Screens::load(std::path::Path::new("no-such-dir")).await;
+    assert!(screens.get(ScreenType::Welcome).is_none());
+}
+
+/// Split animated screens into frames
+///
+/// Text before the first marker must be a frame without delay, markers
+/// without a valid delay must use the default delay.
+#[test]
+fn parse_animated_screens() {
+    let screen = Screen::parse(b"\x1b[2J\r\n%%frame 200\r\n\x1b[HJACK\r\n%%frame fast\r\n\x1b[HJACKED IN\r\n");
+    assert!(screen.is_animated());
+    let frames = screen.frames();
+    assert_eq!(frames.len(), 3);
+    assert_eq!(frames[0], Frame { data: b"\x1b[2J\r\n".to_vec(), delay: std::time::Duration::ZERO });
+    assert_eq!(frames[1], Frame { data: b"\x1b[HJACK\r\n".to_vec(), delay: std::time::Duration::from_millis(200) });
+    assert_eq!(frames[2].delay, DEFAULT_FRAME_DELAY);
+    assert_eq!(screen.final_frame(), b"\x1b[HJACKED IN\r\n");
+
+    let screen = Screen::parse(b"%%frame 50\nstill\n");
+    assert!(!screen.is_animated());
+    assert_eq!(screen.final_frame(), b"still\n");
+    assert!(matches!(Action::try_from("animations OFF"), Ok(Action::Animations(Some(false)))));
+}
+
+/// Render banners
+///
+/// Fonts in the font directory must be loaded next to the bundled font.
+/// Characters must be fitted together unless the font asks for full width,
+/// and the text must be wrapped to the width of the banner.
+#[tokio::test]
+async fn render_banners() {
+    // A font two rows high where only A and V are drawn
+    let font = |layout: i32| {
+        let mut data = format!("flf2a$ 2 2 4 {} 1\nTest font\n", layout);
+        for code in 32..127u8 {
+            match code {
+                b'A' => data.push_str("A  @\nAA @@\n"),
+                b'V' => data.push_str("  V@\n V @@\n"),
+                _ => data.push_str("$@\n$@@\n"),
+            }
+        }
+        data
+    };
+    let font_dir = std::env::temp_dir().join(format!("mud-server-test-fonts-{}", std::process::id()));
+    std::fs::create_dir_all(&font_dir).unwrap();
+    std::fs::write(font_dir.join("fitted.flf"), font(0)).unwrap();
+    std::fs::write(font_dir.join("Full.flf"), font(-1)).unwrap();
+    std::fs::write(font_dir.join("broken.flf"), "flf2a$ 2 2 4 0 0\nA@\n").unwrap();
+    let banners = Banners::load(&font_dir).await;
+    std::fs::remove_dir_all(&font_dir).unwrap();
+    assert_eq!(banners.fonts(), vec!["block", "fitted", "full"]);
+    assert_eq!(Font::parse("flf2a$ 2 2 4 0 0\nA@\n").err(), Some(Error::InvalidFont));
+
+    assert_eq!(banners.render("AV", Some("fitted")).unwrap(), "A  V\r\nAAV\r\n");
+    assert_eq!(banners.render("AV", Some("full")).unwrap(), "A    V\r\nAA  V\r\n");
+    assert_eq!(banners.render("AV", Some("standard")), Err(Error::UnknownFont));
+
+    let banners = banners.with_default_font("fitted")
+        .with_max_width(5)
+        .with_color(Some("1;35".to_string()));
+    let banner = banners.render("AV AV", None).unwrap();
+    assert_eq!(banner.lines().count(), 4);
+    assert!(banner.starts_with("\x1b[1;35mA  V\x1b[0m\r\n"));
+
+    let banner = Banners::default().render("Lobby", None).unwrap();
+    assert_eq!(banner.lines().count(), 5);
+
+    let command = AdminCommand::try_from("@banner font=Block Hack the planet").unwrap();
+    assert_eq!(command.to_string(), "@banner font=block Hack the planet");
+    assert!(AdminCommand::try_from("@banner font=block").is_err());
+}
+
+/// Play the sounds of assets
+///
+/// The sound of the asset looked at must be chosen and rendered in the
+/// protocol the client announced.
+#[test]
+fn play_asset_sounds() {
+    let mut node = Node::new(0);
+    node.update_sound(Some("hum.wav"));
+    node.add_asset(Box::new(Port::new(1)));
+    let mut port = Port::new(2);
+    port.update_sound(Some("door_open.wav"));
+    node.add_asset(Box::new(port));
+
+    assert_eq!(node.sound_for(&Action::try_from("look").unwrap()), Some("hum.wav"));
+    assert_eq!(node.sound_for(&Action::try_from("look at port 2").unwrap()), Some("door_open.wav"));
+    assert_eq!(node.sound_for(&Action::try_from("look at port 1").unwrap()), None);
+    assert_eq!(node.sound_for(&Action::try_from("look at port").unwrap()), None);
+
+    assert_eq!(Protocol::Msp.packet("door_open.wav", ""), b"!!SOUND(door_open.wav)\r\n");
+    assert_eq!(Protocol::Msp.packet("door_open.wav", "https://mud.example/sounds/"),
+        b"!!SOUND(door_open.wav U=https://mud.example/sounds/)\r\n");
+    let packet = Protocol::Gmcp.packet("door_open.wav", "");
+    assert_eq!(&packet[..3], &[255, 250, 201]);
+    assert_eq!(&packet[packet.len() - 2..], &[255, 240]);
+    assert_eq!(String::from_utf8_lossy(&packet[3..packet.len() - 2]),
+        "Client.Media.Play {\"name\":\"door_open.wav\",\"type\":\"sound\"}");
+    assert!(matches!(Action::try_from("media GMCP"), Ok(Action::Media(Some(Protocol::Gmcp)))));
+    assert!(Action::try_from("media").is_err());
+}
+
+/// Use the shell of a terminal
+///
+/// The shell must resolve relative paths against its working directory and
+/// answer unknown paths and commands like a unix shell.
+#[test]
+fn use_terminal_shell() {
+    let mut terminal = Terminal::new(3, "ono-sendai");
+    terminal.fs_mut().add_file("/readme.txt", "Authorized personnel only.\n");
+    terminal.fs_mut().add_file("/logs/access.log", "root login\nroot logout\n");
+    terminal.fs_mut().add_dir("/home/guest");
+    let mut node = Node::new(0);
+    node.add_asset(Box::new(terminal));
+    let terminal = node.find_terminal(3).unwrap();
+
+    let mut arena = generational_arena::Arena::new();
+    let mut shell = Shell::new(arena.insert(()), terminal.uid());
+    assert_eq!(shell.prompt(terminal, "case"), "case@ono-sendai:/$ ");
+    let mut run = |line: &str| match shell.run(terminal, line) {
+        Reply::Output(o) | Reply::Hooked(o, _) => o,
+        Reply::Exit => "exit".to_string(),
+    };
+    assert_eq!(run("ls"), "home/  logs/  readme.txt");
+    assert_eq!(run("cd logs"), "");
+    assert_eq!(run("pwd"), "/logs");
+    assert_eq!(run("cat access.log"), "root login\r\nroot logout");
+    assert_eq!(run("cat ../readme.txt"), "Authorized personnel only.");
+    assert_eq!(run("cd ../../home/guest/"), "");
+    assert_eq!(run("ls"), "");
+    assert_eq!(run("cd /readme.txt"), "cd: /readme.txt: Not a directory");
+    assert_eq!(run("cat /etc/passwd"), "cat: /etc/passwd: No such file or directory");
+    assert_eq!(run("rm -rf /"), "rm: command not found");
+    assert_eq!(run("exit"), "exit");
+
+    assert!(matches!(Action::try_from("access the 2.terminal"), 
+        Ok(Action::Access { target: Some(t), ordinal: Some(2), manner: None }) if t == "terminal"));
+    assert!(matches!(Action::try_from("access"), Ok(Action::Access { target: None, ordinal: None, manner: None })));
+    assert_eq!(node.find_asset("port", None).err(), Some("There is no port here.".to_string()));
+}
+
+/// Crack the ICE on a terminal
+///
+/// Locked entries must only be accessible once a strong enough program
+/// cracked all ICE on the way. Hidden entries must only be listed with
+/// "ls -a", and reading a hooked file must return its hook.
+#[test]
+fn crack_terminal_ice() {
+    let mut terminal = Terminal::new(3, "ono-sendai");
+    let fs = terminal.fs_mut();
+    fs.add_program("/bin/icebreaker", "Icebreaker v0.3", 1);
+    fs.add_program("/bin/black-ice-breaker", "Black ICE breaker", 3);
+    fs.add_file("/vault/ports.txt", "Purple port: 2342");
+    fs.add_file("/vault/core/secret.txt", "Wintermute");
+    fs.add_file("/vault/.plan", "Tell nobody.");
+    fs.lock("/vault", 1);
+    fs.lock("/vault/core", 3);
+    fs.hide("/vault/.plan");
+    fs.on_read("/vault/ports.txt", Hook::Note("Purple port: 2342".to_string()));
+
+    let mut arena = generational_arena::Arena::new();
+    let mut shell = Shell::new(arena.insert(()), terminal.uid());
+    assert_eq!(shell.run(&terminal, "cat /vault/ports.txt"), 
+        Reply::Output("cat: /vault/ports.txt: Permission denied (ICE)".to_string()));
+    assert_eq!(shell.run(&terminal, "cd vault"), 
+        Reply::Output("cd: /vault: Permission denied (ICE)".to_string()));
+    assert_eq!(shell.run(&terminal, "run /bin/icebreaker /vault/core/secret.txt"), 
+        Reply::Output("ICE on /vault cracked (level 1).\r\n\
+            The ICE on /vault/core is too strong (level 3). The program fails.".to_string()));
+    assert_eq!(shell.run(&terminal, "cat /vault/ports.txt"), 
+        Reply::Hooked("Purple port: 2342".to_string(), Hook::Note("Purple port: 2342".to_string())));
+    assert_eq!(shell.run(&terminal, "cd vault"), Reply::Output(String::new()));
+    assert_eq!(shell.run(&terminal, "ls"), Reply::Output("core/  ports.txt".to_string()));
+    assert_eq!(shell.run(&terminal, "ls -a"), Reply::Output(".plan  core/  ports.txt".to_string()));
+    assert_eq!(shell.run(&terminal, "cat core/secret.txt"), 
+        Reply::Output("cat: /vault/core/secret.txt: Permission denied (ICE)".to_string()));
+    assert_eq!(shell.run(&terminal, "run /bin/black-ice-breaker core"), 
+        Reply::Output("ICE on /vault/core cracked (level 3).".to_string()));
+    assert_eq!(shell.run(&terminal, "cat core/secret.txt"), Reply::Output("Wintermute".to_string()));
+    assert_eq!(shell.run(&terminal, "run ports.txt core"), 
+        Reply::Output("run: /vault/ports.txt: Not a program".to_string()));
+
+    // Leaving the shell forgets the cracked ICE
+    let mut shell = Shell::new(arena.insert(()), terminal.uid());
+    assert_eq!(shell.run(&terminal, "ls /vault"), 
+        Reply::Output("ls: /vault: Permission denied (ICE)".to_string()));
+}
+
+/// Solve puzzles
+///
+/// Every kind of puzzle must accept its answer in the forms players type it.
+/// Attempts must be limited, hints must lower the points earned, and the
+/// commands must parse with and without an ordinal.
+#[test]
+fn solve_puzzles() {
+    let cipher = Puzzle::substitution("The sky, 1984.", "qwertyuiopasdfghjklzxcvbnm");
+    assert!(matches!(&cipher, puzzles::Kind::Substitution { ciphertext, .. } if &**ciphertext == "Zit lan, 1984."));
+    let cipher = Puzzle::new("cipher", cipher, "A scrambled message.");
+    assert!(cipher.describe().ends_with("The cipher reads \"Zit lan, 1984.\"."));
+    assert!(cipher.check("the sky 1984"));
+    assert!(!cipher.check("the sky"));
+
+    let pattern = Puzzle::new("pattern", puzzles::Kind::Pattern { dots: vec![1, 5, 9] }, "A keypad.");
+    assert!(pattern.check("1-5-9"));
+    assert!(pattern.check("159"));
+    assert!(!pattern.check("951"));
+
+    let hash = Puzzle::new("hash", Puzzle::hash("2342"), "An access code.")
+        .with_hint("Four digits.", 10)
+        .with_hint("Check the vault.", 20)
+        .with_points(25)
+        .with_max_attempts(2)
+        .on_solve(Hook::Flag("quest/purple-port".to_string()));
+    assert!(hash.describe().contains("93a2b2c6229835801c3b573276911bbdbee50cd36940a911ca1d6b6d46a7d226"));
+    let mut progress = Progress::default();
+    assert_eq!(hash.hint(&mut progress).map(|h| h.cost), Some(10));
+    assert_eq!(hash.attempt(&mut progress, "1234"), Outcome::Wrong { attempts_left: Some(1) });
+    assert_eq!(hash.attempt(&mut progress, " 2342 "), Outcome::Solved { points: 15 });
+    assert_eq!(hash.attempt(&mut progress, "2342"), Outcome::AlreadySolved);
+    assert_eq!(hash.hooks(), &[Hook::Flag("quest/purple-port".to_string())]);
+
+    let mut progress = Progress::default();
+    hash.hint(&mut progress);
+    hash.hint(&mut progress);
+    assert!(hash.hint(&mut progress).is_none());
+    assert_eq!(hash.points_for(&progress), 0);
+    hash.attempt(&mut progress, "0000");
+    hash.attempt(&mut progress, "1111");
+    assert_eq!(hash.attempt(&mut progress, "2342"), Outcome::Locked);
+
+    assert!(matches!(Action::try_from("solve port 2 the sky above"), 
+        Ok(Action::Solve { target, ordinal: Some(2), answer }) if target == "port" && answer == "the sky above"));
+    assert!(matches!(Action::try_from("solve the port 2342"), 
+        Ok(Action::Solve { target, ordinal: None, answer }) if target == "port" && answer == "2342"));
+    assert!(matches!(Action::try_from("solve port"), Err(Error::VerbEncodingError)));
+    assert!(matches!(Action::try_from("hint 2.terminal"), 
+        Ok(Action::Hint { target, ordinal: Some(2) }) if target == "terminal"));
+}
+
+/// Run challenges
+///
+/// The countdown of a run must be pushed in growing frequency towards the
+/// deadline. A run must be won once its goal is reached, and the leaderboard
+/// must keep the fastest results in order.
+#[test]
+fn run_challenges() {
+    let challenge = Challenge::new("rush", "Crack both ports.", 
+        Goal::Crack(vec!["a".to_string(), "b".to_string()]), Duration::from_secs(75));
+    assert_eq!(challenge.describe(), "rush (1:15): Crack both ports.");
+    let start = tokio::time::Instant::now();
+    let mut run = Run::new(&challenge, 1, start);
+    let countdown: Vec<u64> = (0..=750)
+        .filter_map(|t| run.countdown(start + Duration::from_millis(t * 100)))
+        .map(|d| d.as_secs())
+        .collect();
+    assert_eq!(countdown, vec![75, 60, 50, 40, 30, 20, 10, 9, 8, 7, 6, 5, 4, 3, 2, 1, 0]);
+    assert!(!run.is_won(&challenge, &[0]));
+    run.crack("a");
+    run.crack("b");
+    assert!(run.is_won(&challenge, &[0]));
+    let reach = Challenge::new("reach", "Reach the core.", Goal::Reach(7), Duration::from_secs(60));
+    assert!(run.is_won(&reach, &[0, 7]));
+    assert!(!run.is_won(&reach, &[0]));
+
+    let mut leaderboard = Leaderboard::default();
+    for (i, secs) in [50, 30, 40, 30].iter().enumerate() {
+        leaderboard.record("rush", vec![format!("decker{}", i)], Duration::from_secs(*secs));
+    }
+    let players: Vec<&str> = leaderboard.records("rush").iter().map(|r| r.players[0].as_str()).collect();
+    assert_eq!(players, vec!["decker1", "decker3", "decker2", "decker0"]);
+    for _ in 0..challenges::LEADERBOARD_SIZE {
+        leaderboard.record("rush", vec!["neo".to_string()], Duration::from_secs(20));
+    }
+    assert_eq!(leaderboard.record("rush", vec!["smith".to_string()], Duration::from_secs(25)), None);
+    assert_eq!(leaderboard.record("rush", vec!["trinity".to_string()], Duration::from_secs(10)), Some(1));
+    assert_eq!(leaderboard.records("rush").len(), challenges::LEADERBOARD_SIZE);
+    assert_eq!(challenges::run_time(Duration::from_millis(65_340)), "1:05.3");
+
+    assert!(matches!(Action::try_from("challenge Rush"), Ok(Action::Challenge(Some(c))) if c == "rush"));
+    assert!(matches!(Action::try_from("join Neo"), Ok(Action::Join(p)) if p == "Neo"));
+    assert!(matches!(Action::try_from("join"), Err(Error::VerbEncodingError)));
+    assert!(matches!(Action::try_from("leaderboard"), Ok(Action::Leaderboard(None))));
+}
+
+/// Spectate zones
+///
+/// Admins must be able to flag zones by name, including names with blanks,
+/// and zones must be found by their name ignoring case.
+#[test]
+fn spectate_zones() {
+    assert_eq!(AdminCommand::try_from("@spectate The Core ON").unwrap(), 
+        AdminCommand::Spectate { zone: "The Core".to_string(), enabled: true });
+    let command = AdminCommand::try_from("@spectate Lobby off").unwrap();
+    assert_eq!(command.to_string(), "@spectate Lobby off");
+    assert!(AdminCommand::try_from("@spectate Lobby").is_err());
+    assert!(AdminCommand::try_from("@spectate Lobby maybe").is_err());
+
+    assert!(matches!(Action::try_from("watch The Core"), Ok(Action::Watch(Some(z))) if z == "The Core"));
+    assert!(matches!(Action::try_from("watch"), Ok(Action::Watch(None))));
+
+    let mut world = GameWorld::new("Testworld".to_string());
+    let mut node = Node::new(0);
+    node.update_name("Lobby");
+    let lobby = world.add_spwan_node(node).unwrap();
+    world.add_node(Node::new(1));
+    assert_eq!(world.find_zone("lobby"), Some(lobby));
+    assert_eq!(world.find_zone(""), None);
+    assert_eq!(world.find_zone("The Core"), None);
+}
+
+/// Two worlds with the same secret must link and pass tells and travelers
+/// over the link, worlds with different secrets must not link. A traveler
+/// must be forgotten if it does not connect in time.
+#[tokio::test]
+async fn link_worlds() {
+    assert!(matches!(Action::try_from("tell neo@hackerspace wake up"), 
+        Ok(Action::Tell { to, world: Some(w), text }) if to == "neo" && w == "hackerspace" && text == "wake up"));
+    assert!(matches!(Action::try_from("tell neo wake up"), Ok(Action::Tell { world: None, .. })));
+    assert!(Action::try_from("tell neo@ wake up").is_err());
+    assert!(Action::try_from("tell neo").is_err());
+    assert!(matches!(Action::try_from("enter"), Ok(Action::Enter { target: None, ordinal: None, manner: None })));
+    assert!(matches!(Action::try_from("enter port 2"), 
+        Ok(Action::Enter { target: Some(t), ordinal: Some(2), manner: None }) if t == "port"));
+
+    let mac = federation::mac("secret", "nonce", "Balccon");
+    assert!(federation::verify("secret", "nonce", "balccon", &mac));
+    assert!(!federation::verify("secret", "other nonce", "balccon", &mac));
+    assert!(!federation::verify("other secret", "nonce", "balccon", &mac));
+    assert!(!federation::verify("secret", "nonce", "hackerspace", &mac));
+    assert!(!federation::verify("secret", "nonce", "balccon", "not hex"));
+
+    let config = |name: &str, peer_name: &str, secret: &str| federation::Config {
+        name: name.to_string(),
+        peer_name: peer_name.to_string(),
+        listen: String::new(),
+        connect: String::new(),
+        secret: secret.to_string(),
+        ssh: format!("{}.example:2222", name),
+    };
+    let metrics = Metrics::new(&Capacities { command: 8, data: 1, push: 1 });
+    let link = |config: federation::Config, stream: tokio::io::DuplexStream| {
+        let (command_tx, command_rx) = channels::channel(&metrics.command);
+        let (link, mut outgoing) = Link::new(&config);
+        let serving = tokio::spawn(async move {
+            federation::serve(stream, &config, &command_tx, &mut outgoing).await
+        });
+        (link, command_rx, serving)
+    };
+
+    let (a, b) = tokio::io::duplex(4096);
+    let (mut balccon, mut balccon_rx, _) = link(config("balccon", "hackerspace", "secret"), a);
+    let (hackerspace, mut hackerspace_rx, _) = link(config("hackerspace", "Balccon", "secret"), b);
+    assert!(matches!(balccon_rx.recv().await, 
+        Some(Command::Federation(Incoming::Linked { world, ssh })) 
+            if world == "hackerspace" && ssh == "hackerspace.example:2222"));
+    assert!(matches!(hackerspace_rx.recv().await, Some(Command::Federation(Incoming::Linked { .. }))));
+
+    // The world only sends once it learned that the link is up
+    let tell = federation::Message::Tell { from: "trinity".to_string(), to: "neo".to_string(), 
+        text: "follow the white rabbit".to_string() };
+    assert!(!balccon.send(tell.clone()));
+    balccon.peer_ssh = Some("hackerspace.example:2222".to_string());
+    assert!(balccon.is_peer("HackerSpace"));
+    assert!(balccon.send(tell.clone()));
+    assert!(matches!(hackerspace_rx.recv().await, 
+        Some(Command::Federation(Incoming::Received(m))) if m == tell));
+    let traveler = Traveler { name: "trinity".to_string(), title: Some("the hacker".to_string()), score: 50, 
+        flags: ["quest/purple-port".to_string()].iter().cloned().collect() };
+    assert!(balccon.send(federation::Message::Transfer { traveler: traveler.clone() }));
+    assert!(matches!(hackerspace_rx.recv().await, 
+        Some(Command::Federation(Incoming::Received(federation::Message::Transfer { traveler: t }))) 
+            if t == traveler));
+    drop(hackerspace);
+
+    let mut arrivals = federation::Arrivals::default();
+    let now = tokio::time::Instant::now();
+    arrivals.expect(traveler.clone(), now);
+    assert_eq!(arrivals.arrive("Trinity", now + Duration::from_secs(60)), Some(traveler.clone()));
+    assert_eq!(arrivals.arrive("trinity", now), None);
+    arrivals.expect(traveler, now);
+    assert_eq!(arrivals.arrive("trinity", now + federation::ARRIVAL_TIMEOUT), None);
+
+    let (a, b) = tokio::io::duplex(4096);
+    let (_, _, serving_a) = link(config("balccon", "hackerspace", "secret"), a);
+    let (_, _, serving_b) = link(config("hackerspace", "balccon", "guessed"), b);
+    assert!(serving_a.await.unwrap().is_err());
+    assert!(serving_b.await.unwrap().is_err());
+}
+
+/// Only text messages of others than the bot must be taken from a sync of
+/// the room, stripped of anything that could mess with a terminal.
+#[tokio::test]
+async fn bridge_matrix_room() {
+    let sync = serde_json::json!({
+        "next_batch": "s42",
+        "rooms": { "join": { "!room:example.org": { "timeline": { "events": [
+            { "type": "m.room.message", "sender": "@alice:example.org",
+              "content": { "msgtype": "m.text", "body": "meet at\nthe \u{1b}[31mbar" } },
+            { "type": "m.room.message", "sender": "@mud:example.org",
+              "content": { "msgtype": "m.text", "body": "[Lobby] neo: hi" } },
+            { "type": "m.room.message", "sender": "@bob:example.org",
+              "content": { "msgtype": "m.image", "body": "cat.png" } },
+            { "type": "m.room.member", "sender": "@bob:example.org", "content": { "membership": "join" } },
+            { "type": "m.room.message", "sender": "@bob:example.org",
+              "content": { "msgtype": "m.text", "body": " \u{7} " } },
+        ] } } } }
+    });
+    let messages = matrix::messages(&sync, "!room:example.org", "@mud:example.org");
+    assert_eq!(messages, vec![matrix::Incoming { sender: "@alice:example.org".to_string(), 
+        body: "meet at the [31mbar".to_string() }]);
+    assert_eq!(messages[0].author(), "alice");
+    assert!(matrix::messages(&sync, "!other:example.org", "@mud:example.org").is_empty());
+    assert_eq!(matrix::sanitize(&"x".repeat(1000)).len(), matrix::MAX_MESSAGE);
+
+    let config = matrix::Config {
+        homeserver: "https://example.org".to_string(),
+        access_token: String::new(),
+        user_id: "@mud:example.org".to_string(),
+        room_id: "!room:example.org".to_string(),
+        zones: vec!["Lobby".to_string()],
+    };
+    let (bridge, mut outgoing) = Bridge::new(&config);
+    assert!(bridge.mirrors("lobby"));
+    assert!(!bridge.mirrors("The Core"));
+    bridge.say("Lobby", "neo", "hi");
+    assert_eq!(outgoing.recv().await.unwrap(), "[Lobby] neo: hi");
+}
+
+/// The feed must list the latest news first, in both formats, and drop the
+/// news about a forgotten player.
+#[test]
+fn publish_news_feed() {
+    assert_eq!(feed::rfc3339(0), "1970-01-01T00:00:00Z");
+    assert_eq!(feed::rfc3339(951_827_696), "2000-02-29T12:34:56Z");
+    assert_eq!(feed::rfc3339(1_789_000_000), "2026-09-10T00:26:40Z");
+
+    let news = News::new();
+    let feed = Feed { news: news.clone(), title: "News & more".to_string(), 
+        url: "https://mud.example.org".to_string(), max_items: 2 };
+    news.publish(news::Kind::Announcement, "Announcement", "Doors open at <23:00>", Vec::new());
+    news.publish(news::Kind::Record, "#1 on the leaderboard of lobby-rush", "neo won challenge lobby-rush.", 
+        vec!["neo".to_string()]);
+    news.publish(news::Kind::Announcement, "Announcement", "Party in the lobby", Vec::new());
+
+    let json: serde_json::Value = serde_json::from_str(&feed.json()).unwrap();
+    assert_eq!(json["version"], "https://jsonfeed.org/version/1.1");
+    let items = json["items"].as_array().unwrap();
+    assert_eq!(items.len(), 2);
+    assert_eq!(items[0]["id"], "3");
+    assert_eq!(items[1]["tags"][0], "record");
+
+    let atom = feed.atom();
+    assert!(atom.contains("<title>News &amp; more</title>"));
+    assert!(atom.contains("<id>https://mud.example.org/news/2</id>"));
+    assert!(!atom.contains("Doors open"));
+
+    news.remove("neo");
+    let latest = news.latest(10);
+    assert_eq!(latest.len(), 2);
+    assert_eq!(latest[1].text, "Doors open at <23:00>");
+    assert!(feed.atom().contains("Doors open at &lt;23:00&gt;"));
+}
+
+/// Provisioning a badge must allow its key to connect as the player of the
+/// badge only and seed its account, provisioning it again must replace the
+/// key.
+#[tokio::test]
+async fn provision_badges() {
+    let data_dir = std::env::temp_dir().join(format!("mud-server-badges-{}", std::process::id()));
+    let keyring = Keyring::new(Vec::new());
+    let provisioner = Provisioner::new("s3cret", &data_dir, "badge-", HandleRules::default(), "con.example", 2222, 
+        keyring.clone(), Badges::default()).await;
+    assert!(provisioner.is_authorized("POST /badges HTTP/1.1\r\nauthorization: Bearer s3cret\r\n\r\n"));
+    assert!(!provisioner.is_authorized("POST /badges HTTP/1.1\r\nAuthorization: Bearer guessed\r\n\r\n"));
+    assert!(!provisioner.is_authorized("POST /badges HTTP/1.1\r\n\r\n"));
+
+    // A generated key
+    let request = provisioning::Request { serial: "BC24-0042".to_string(), public_key: None };
+    let provisioned = provisioner.provision(&request).await.unwrap();
+    assert_eq!(provisioned.player, "badge-bc24-0042");
+    assert_eq!((provisioned.host.as_str(), provisioned.port), ("con.example", 2222));
+    let pair = thrussh_keys::decode_secret_key(provisioned.private_key.as_ref().unwrap(), None).unwrap();
+    assert_eq!(provisioned.public_key, format!("ssh-ed25519 {}", pair.public_key_base64()));
+    assert!(keyring.allows(&pair.public_key_base64(), "Badge-BC24-0042"));
+    assert!(!keyring.contains(&pair.public_key_base64()));
+    assert!(!keyring.allows(&pair.public_key_base64(), "badge-bc24-0043"));
+    assert!(!keyring.allows(&pair.public_key_base64(), "admin"));
+    assert_eq!(Account::load(&data_dir, "badge-bc24-0042").await.unwrap().badge.as_deref(), Some("BC24-0042"));
+
+    // A submitted key replaces the generated one
+    let submitted = KeyPair::generate_ed25519().unwrap();
+    let request = provisioning::Request { serial: "BC24-0042".to_string(), 
+        public_key: Some(format!("ssh-ed25519 {} badge", submitted.public_key_base64())) };
+    let provisioned = provisioner.provision(&request).await.unwrap();
+    assert!(provisioned.private_key.is_none());
+    assert!(keyring.allows(&submitted.public_key_base64(), "badge-bc24-0042"));
+    assert!(!keyring.allows(&pair.public_key_base64(), "badge-bc24-0042"));
+
+    // The badges are allowed to connect again after a restart, as their
+    // players only
+    let keyring = Keyring::default();
+    Provisioner::new("s3cret", &data_dir, "badge-", HandleRules::default(), "con.example", 2222, keyring.clone(), 
+        Badges::load(&data_dir).await.unwrap()).await;
+    assert!(keyring.allows(&submitted.public_key_base64(), "badge-bc24-0042"));
+    assert!(!keyring.allows(&submitted.public_key_base64(), "admin"));
+
+    let invalid = |serial: &str, public_key: Option<&str>| provisioning::Request { 
+        serial: serial.to_string(), public_key: public_key.map(String::from) };
+    assert!(matches!(provisioner.provision(&invalid("../0042", None)).await, Err(provisioning::Error::InvalidSerial)));
+    assert!(matches!(provisioner.provision(&invalid("BC24-0043", Some("ssh-ed25519 nope"))).await, 
+        Err(provisioning::Error::InvalidKey)));
+    assert!(matches!(provisioner.provision(&invalid("BC24-00000000043", None)).await, 
+        Err(provisioning::Error::InvalidHandle(Error::InvalidLength))));
+    std::fs::remove_dir_all(&data_dir).unwrap();
+}
+
+/// A login token must let any key in once, for the player it was issued
+/// for, bound keys must only be allowed for their player and their player
+/// must only be allowed with them.
+#[tokio::test]
+async fn log_in_with_token() {
+    assert_eq!(AdminCommand::try_from("@token neo").unwrap(), AdminCommand::Token("neo".to_string()));
+    assert!(AdminCommand::try_from("@token").is_err());
+    let code = qr::render("ssh://token-ab12cd34ef56@con.example:2222").unwrap();
+    let lines: Vec<&str> = code.split("\r\n").collect();
+    assert!(lines.iter().all(|l| l.chars().count() == lines[0].chars().count()));
+    assert!(lines[0].chars().all(|c| c == '█'));
+
+    let tokens = LoginTokens::new(Duration::from_secs(60));
+    let user = tokens.issue("neo");
+    assert!(user.starts_with("token-"));
+    assert_eq!(tokens.peek(&user).as_deref(), Some("neo"));
+    assert_eq!(tokens.peek("token-guessed"), None);
+    assert_eq!(tokens.redeem(&user).as_deref(), Some("neo"));
+    assert_eq!(tokens.redeem(&user), None);
+    let expired = LoginTokens::new(Duration::ZERO);
+    let user = expired.issue("neo");
+    assert_eq!(expired.peek(&user), None);
+
+    let mut test_environment = TestEnvironment::setup(Vec::new());
+    test_environment.server.set_login_tokens(tokens.clone());
+    let key = KeyPair::generate_ed25519().unwrap();
+    let (_, auth) = test_environment.server.clone()
+        .auth_publickey(&tokens.issue("neo"), &key.clone_public_key()).into_inner().unwrap();
+    assert_eq!(auth, thrussh::server::Auth::Accept);
+    let (_, auth) = test_environment.server
+        .auth_publickey("token-guessed", &key.clone_public_key()).into_inner().unwrap();
+    assert_eq!(auth, thrussh::server::Auth::Reject);
+
+    let data_dir = std::env::temp_dir().join(format!("mud-server-keys-{}", std::process::id()));
+    let keyring = Keyring::default();
+    keyring.open(&data_dir).unwrap();
+    keyring.bind(&key.public_key_base64(), "neo").await.unwrap();
+    assert!(keyring.allows(&key.public_key_base64(), "Neo"));
+    assert!(!keyring.allows(&key.public_key_base64(), "trinity"));
+    keyring.add("AAAAanybody");
+    assert!(keyring.allows("AAAAanybody", "trinity"));
+    assert!(!keyring.allows("AAAAanybody", "NEO"));
+    let keyring = Keyring::default();
+    keyring.open(&data_dir).unwrap();
+    assert!(keyring.allows(&key.public_key_base64(), "neo"));
+    std::fs::remove_dir_all(&data_dir).unwrap();
+}
+
+/// Maintenance must be switched on (optionally reopening after some
+/// minutes) and off, anything else must be rejected.
+#[test]
+fn schedule_maintenance() {
+    assert_eq!(AdminCommand::try_from("@maintenance on 30").unwrap(), 
+        AdminCommand::Maintenance { enabled: true, minutes: Some(30) });
+    assert_eq!(AdminCommand::try_from("@maintenance ON").unwrap().to_string(), "@maintenance on");
+    assert_eq!(AdminCommand::try_from("@maintenance off").unwrap(), 
+        AdminCommand::Maintenance { enabled: false, minutes: None });
+    assert!(AdminCommand::try_from("@maintenance").is_err());
+    assert!(AdminCommand::try_from("@maintenance on soon").is_err());
+    assert!(AdminCommand::try_from("@maintenance off 30").is_err());
+}
+
+/// A paused world must keep its time, move only by the steps and continue
+/// from there when resumed.
+#[test]
+fn pause_and_step_world() {
+    assert_eq!(AdminCommand::try_from("@step").unwrap(), AdminCommand::Step(1));
+    assert_eq!(AdminCommand::try_from("@step 5").unwrap().to_string(), "@step 5");
+    assert!(AdminCommand::try_from("@step 0").is_err());
+    assert!(AdminCommand::try_from("@pause now").is_err());
+
+    let tick = Duration::from_millis(100);
+    let mut clock = Clock::default();
+    let start = tokio::time::Instant::now();
+    let paused = clock.tick(start, tick).unwrap();
+    clock.pause(start);
+    clock.step(2);
+    let later = start + Duration::from_secs(60);
+    assert_eq!(clock.now(later), paused);
+    assert_eq!(clock.tick(later, tick), Some(paused + tick));
+    assert_eq!(clock.tick(later, tick), Some(paused + tick * 2));
+    assert_eq!(clock.tick(later, tick), None);
+
+    clock.resume(later);
+    assert_eq!(clock.tick(later + tick, tick), Some(paused + tick * 3));
+    clock.step(1);
+    assert_eq!(clock.steps(), 0);
+}
+
+/// Zone resets must run on their timers only (starting with the first
+/// check) and be found by the staff ignoring case.
+#[test]
+fn reset_zones() {
+    assert_eq!(AdminCommand::try_from("@reset Data Haven").unwrap(), AdminCommand::Reset("Data Haven".to_string()));
+    assert!(AdminCommand::try_from("@reset").is_err());
+
+    let mut resets = Resets::default();
+    resets.add(ZoneReset::new("Lobby")
+        .every(Duration::from_secs(60))
+        .with_step(Step::Puzzles)
+        .with_step(Step::Ice));
+    resets.add(ZoneReset::new("Vault").with_step(Step::Announce("The vault seals itself.".to_string())));
+    assert_eq!(resets.get("lobby").unwrap().steps(), &[Step::Puzzles, Step::Ice]);
+    assert!(resets.get("Arcade").is_none());
+
+    let start = tokio::time::Instant::now();
+    assert!(resets.due(start).is_empty());
+    assert!(resets.due(start + Duration::from_secs(59)).is_empty());
+    let due = resets.due(start + Duration::from_secs(60));
+    assert_eq!(due.len(), 1);
+    assert_eq!(due[0].zone(), "Lobby");
+    assert!(resets.due(start + Duration::from_secs(61)).is_empty());
+    assert_eq!(resets.due(start + Duration::from_secs(120)).len(), 1);
+
+    resets.add(ZoneReset::new("LOBBY"));
+    assert!(resets.get("lobby").unwrap().steps().is_empty());
+}
+
+/// Builders must find assets by their fields and see where they are.
+#[test]
+fn inspect_assets() {
+    assert_eq!(AdminCommand::try_from("@find port color=purple").unwrap(), 
+        AdminCommand::Find { noun: "port".to_string(), filters: vec![("color".to_string(), "purple".to_string())] });
+    assert_eq!(AdminCommand::try_from("@show node 12").unwrap(), AdminCommand::Show(Target::Node(12)));
+    assert_eq!(AdminCommand::try_from("@where asset 7").unwrap().to_string(), "@where asset 7");
+    assert!(AdminCommand::try_from("@find color=purple").is_err());
+    assert!(AdminCommand::try_from("@where node 7").is_err());
+    assert!(AdminCommand::try_from("@show asset seven").is_err());
+    assert!(AdminCommand::try_from("@show node 12").unwrap().is_for_builders());
+    assert!(!AdminCommand::try_from("@forget neo").unwrap().is_for_builders());
+
+    let mut nodes = generational_arena::Arena::new();
+    let mut node = Node::new(0);
+    node.update_name("Lobby");
+    let mut port = Port::new(1);
+    port.update_description("A plain port.");
+    node.add_asset(Box::new(port));
+    let mut port = Port::new(2);
+    port.update_description("A port with a purple edge.");
+    port.add_property(Property::Color(Color::Purple));
+    node.add_asset(Box::new(port));
+    nodes.insert(node);
+
+    assert_eq!(inspect::find(&nodes, "port", &[("color".to_string(), "Purple".to_string())]), 
+        vec!["port #2 in Lobby (node #0)".to_string()]);
+    assert_eq!(inspect::find(&nodes, "asset", &[]).len(), 2);
+    assert_eq!(inspect::find(&nodes, "node", &[("description".to_string(), "port".to_string())]).len(), 0);
+    let (node, asset) = inspect::locate(&nodes, 2).unwrap();
+    assert_eq!(inspect::dump(asset, Some(node)), "port #2 in Lobby (node #0)\r\n  \
+        description: A port with a purple edge. The port is closed.\r\n  color: purple");
+    assert!(inspect::locate(&nodes, 0).is_none());
+    assert!(inspect::dump_node(inspect::node(&nodes, 0).unwrap()).ends_with("asset: port #2"));
+}
+
+/// Builder edits must change the world and be undone in reverse order,
+/// per builder, and show up in the change log.
+#[test]
+fn undo_builder_edits() {
+    assert_eq!(AdminCommand::try_from("@describe 2 A humming port.").unwrap(), 
+        AdminCommand::Edit(Edit::Describe { uid: 2, description: "A humming port.".to_string() }));
+    assert_eq!(AdminCommand::try_from("@create terminal wintermute 0 A cold terminal.").unwrap().to_string(), 
+        "@create terminal wintermute 0 A cold terminal.");
+    assert_eq!(AdminCommand::try_from("@create port 0").unwrap(), 
+        AdminCommand::Edit(Edit::Create { node: 0, kind: AssetKind::Port, description: String::new() }));
+    assert!(AdminCommand::try_from("@create terminal 0").is_err());
+    assert!(AdminCommand::try_from("@create door 0").is_err());
+    assert!(AdminCommand::try_from("@describe two ports").is_err());
+    assert!(!AdminCommand::try_from("@changes").unwrap().is_for_builders());
+
+    let mut nodes = generational_arena::Arena::new();
+    let mut node = Node::new(0);
+    node.update_name("Lobby");
+    node.update_description("A dark lobby.");
+    node.add_asset(Box::new(Port::new(1)));
+    nodes.insert(node);
+    let mut history = History::default();
+
+    let describe = Edit::Describe { uid: 0, description: "A bright lobby.".to_string() };
+    let undo = edits::apply(&mut nodes, &describe).unwrap();
+    history.record("neo", describe, undo);
+    let create = Edit::Create { node: 0, kind: AssetKind::Port, description: "A new port.".to_string() };
+    let undo = edits::apply(&mut nodes, &create).unwrap();
+    assert_eq!(undo, Edit::Remove { node: 0, uid: 2 });
+    history.record("neo", create, undo);
+    assert_eq!(inspect::locate(&nodes, 2).unwrap().1.description(), "A new port.");
+    assert!(edits::apply(&mut nodes, &Edit::Describe { uid: 7, description: String::new() }).is_err());
+
+    assert!(history.pop("trinity").is_none());
+    edits::apply(&mut nodes, &history.pop("Neo").unwrap()).unwrap();
+    assert!(inspect::locate(&nodes, 2).is_none());
+    assert!(inspect::locate(&nodes, 1).is_some());
+    let undo = history.pop("neo").unwrap();
+    edits::apply(&mut nodes, &undo).unwrap();
+    history.record_undo("neo", undo);
+    assert_eq!(inspect::node(&nodes, 0).unwrap().description(), "A dark lobby.");
+    assert!(history.pop("neo").is_none());
+
+    let changes: Vec<String> = history.changes(10).map(|c| c.edit.to_string()).collect();
+    assert_eq!(changes, vec!["describe #0 \"A dark lobby.\"", "create port in #0 \"A new port.\"", 
+        "describe #0 \"A bright lobby.\""]);
+    assert!(history.changes(10).next().unwrap().undo);
+}
+
+/// Staged edits must only change a shadow copy of the affected nodes until
+/// they are published, and a draft with an invalid edit must not apply.
+#[test]
+fn stage_edits_in_drafts() {
+    assert_eq!(AdminCommand::try_from("@publish").unwrap(), AdminCommand::Publish);
+    assert!(AdminCommand::try_from("@draft now").is_err());
+    assert!(AdminCommand::try_from("@preview").unwrap().is_for_builders());
+
+    let mut nodes = generational_arena::Arena::new();
+    for uid in [0, 10] {
+        let mut node = Node::new(uid);
+        node.update_description("A dark node.");
+        node.add_asset(Box::new(Port::new(uid + 1)));
+        nodes.insert(node);
+    }
+    let mut drafts = Drafts::default();
+    let describe = Edit::Describe { uid: 1, description: "A purple port.".to_string() };
+    assert_eq!(drafts.stage("neo", describe.clone()), None);
+    assert!(drafts.open("neo"));
+    assert!(!drafts.open("Neo"));
+    assert_eq!(drafts.stage("neo", describe), Some(1));
+    let create = Edit::Create { node: 0, kind: AssetKind::Terminal("wintermute".to_string()), 
+        description: "A cold terminal.".to_string() };
+    assert_eq!(drafts.stage("neo", create), Some(2));
+
+    let shadow = edits::preview(&nodes, drafts.get("neo").unwrap()).unwrap();
+    assert_eq!(shadow.len(), 1);
+    assert_eq!(inspect::locate(&shadow, 1).unwrap().1.description(), "A purple port.");
+    assert_eq!(inspect::locate(&shadow, 2).unwrap().1.name(), "terminal");
+    assert_eq!(inspect::locate(&nodes, 1).unwrap().1.description(), "");
+    assert!(inspect::locate(&nodes, 2).is_none());
+
+    assert_eq!(drafts.stage("neo", Edit::Describe { uid: 42, description: String::new() }), Some(3));
+    assert!(edits::preview(&nodes, drafts.get("neo").unwrap()).is_err());
+    assert_eq!(drafts.unstage("neo"), Some(Edit::Describe { uid: 42, description: String::new() }));
+    for edit in drafts.close("neo").unwrap() {
+        edits::apply(&mut nodes, &edit).unwrap();
+    }
+    assert_eq!(inspect::locate(&nodes, 1).unwrap().1.description(), "A purple port.");
+    assert_eq!(inspect::locate(&nodes, 12).unwrap().1.description(), "A cold terminal.");
+    assert!(drafts.get("neo").is_none());
+}
+
+/// A restricted node must only let players with one of its roles or flags
+/// in, and spawning must skip the spawn nodes a player may not enter.
+#[test]
+fn restrict_node_access() {
+    let acl = Acl::try_from("role=builder flag=quest/purple-port").unwrap();
+    assert_eq!(acl.to_string(), "role=builder flag=quest/purple-port");
+    assert!(Acl::try_from("role=janitor").is_err());
+    assert!(Acl::try_from("").is_err());
+    let mut flags = std::collections::BTreeSet::new();
+    assert!(!acl.allows(Role::Player, &flags));
+    assert!(acl.allows(Role::Builder, &flags));
+    assert!(acl.allows(Role::Admin, &flags));
+    flags.insert("quest/purple-port".to_string());
+    assert!(acl.allows(Role::Player, &flags));
+    assert_eq!(AdminCommand::try_from("@acl 0 open").unwrap(), AdminCommand::Edit(Edit::Restrict { node: 0, acl: None }));
+    assert_eq!(AdminCommand::try_from("@acl 0 role=ADMIN").unwrap().to_string(), "@acl 0 role=admin");
+
+    struct Visitor(Role, Option<Index>);
+    impl Spawnable for Visitor {
+        fn set_spawn_point_index(&mut self, index: Index) {
+            self.1 = Some(index);
+        }
+        fn may_enter(&self, node: &Node) -> bool {
+            node.acl().is_none_or(|acl| acl.allows(self.0, &std::collections::BTreeSet::new()))
+        }
+    }
+    let mut world = GameWorld::new("test".to_string());
+    let mut backstage = Node::new(0);
+    backstage.update_acl(Some(Acl::try_from("role=builder").unwrap()));
+    let backstage = world.add_spwan_node(backstage).unwrap();
+    let lobby = world.add_spwan_node(Node::new(1)).unwrap();
+    assert_eq!(world.spawn(&mut Visitor(Role::Builder, None)).unwrap(), backstage);
+    let mut player = Visitor(Role::Player, None);
+    assert_eq!(world.spawn(&mut player).unwrap(), lobby);
+    assert_eq!(player.1, Some(lobby));
+
+    let mut nodes = generational_arena::Arena::new();
+    nodes.insert(Node::new(0));
+    let undo = edits::apply(&mut nodes, &Edit::Restrict { node: 0, acl: Some(acl) }).unwrap();
+    assert!(inspect::dump_node(inspect::node(&nodes, 0).unwrap()).contains("acl: role=builder"));
+    edits::apply(&mut nodes, &undo).unwrap();
+    assert!(inspect::node(&nodes, 0).unwrap().acl().is_none());
+}
+
+/// Spawners must keep their population while players are in the node,
+/// respawn killed NPCs after the kill timer and despawn all NPCs of empty
+/// nodes.
+#[test]
+fn spawn_npcs() {
+    assert_eq!(AdminCommand::try_from("@slay 3").unwrap(), AdminCommand::Slay(3));
+    assert!(AdminCommand::try_from("@slay sentinel").is_err());
+
+    let mut arena = generational_arena::Arena::new();
+    let lobby = arena.insert(());
+    let vault = arena.insert(());
+    let mut npcs = Npcs::default();
+    npcs.add_spawner(Spawner::new(lobby, Prototype::new("sentinel", "Black ICE.").hostile(), 2)
+        .respawn_after(Duration::from_secs(30)));
+    let start = tokio::time::Instant::now();
+
+    assert!(npcs.tick(|_| false, |_| true, start).is_empty());
+    let changes = npcs.tick(|n| n == lobby, |_| true, start);
+    assert_eq!(changes.len(), 2);
+    assert!(matches!(&changes[0], Change::Spawned(n) if n.is_hostile() && n.location == lobby));
+    assert!(npcs.tick(|n| n == lobby, |_| true, start).is_empty());
+    assert_eq!(npcs.find(lobby, "SENTINEL", None).unwrap().description(), "Black ICE.");
+    assert!(npcs.find(vault, "sentinel", None).is_none());
+
+    let uid = npcs.in_node(lobby).next().unwrap().uid;
+    assert_eq!(npcs.kill(uid, start).unwrap().uid, uid);
+    assert!(npcs.kill(uid, start).is_none());
+    assert!(npcs.tick(|n| n == lobby, |_| true, start + Duration::from_secs(29)).is_empty());
+    assert_eq!(npcs.tick(|n| n == lobby, |_| true, start + Duration::from_secs(30)).len(), 1);
+    assert_eq!(npcs.in_node(lobby).count(), 2);
+
+    let changes = npcs.tick(|_| false, |_| true, start + Duration::from_secs(31));
+    assert!(changes.iter().all(|c| matches!(c, Change::Despawned(_))));
+    assert_eq!(changes.len(), 2);
+    assert_eq!(npcs.in_node(lobby).count(), 0);
+}
+
+/// Guards must trace players touching their asset, pursuers must trace
+/// players staying in their node and follow them, reinforcements must be
+/// called once per target and patrols must walk their route.
+#[test]
+fn npc_behaviors() {
+    let mut arena = generational_arena::Arena::new();
+    let lobby = arena.insert(());
+    let vault = arena.insert(());
+    let start = tokio::time::Instant::now();
+    let guard = [Behavior::Guard(2), Behavior::Reinforce(2)];
+    let pursuer = [Behavior::Pursue { trace_time: Duration::from_secs(10) }];
+
+    let mut blackboard = Blackboard::default();
+    let view = View { cloaked: Vec::new(), players: vec![("neo".to_string(), lobby)],
+        touched: vec![(lobby, 1, "neo".to_string())], hurried: Vec::new(), heard: Vec::new(),
+        caught: Vec::new(), wanted: Vec::new() };
+    assert!(ai::think(&guard, lobby, &mut blackboard, &view, start).is_empty());
+    let view = View { cloaked: Vec::new(), players: vec![("neo".to_string(), lobby)],
+        touched: vec![(lobby, 2, "neo".to_string())], hurried: Vec::new(), heard: Vec::new(),
+        caught: Vec::new(), wanted: Vec::new() };
+    assert_eq!(ai::think(&guard, lobby, &mut blackboard, &view, start),
+        vec![Act::Trace("neo".to_string()), Act::Reinforce(2)]);
+    assert!(ai::think(&guard, lobby, &mut blackboard, &view, start).is_empty());
+    assert_eq!(ai::think(&guard, lobby, &mut blackboard, &View::default(), start), vec![Act::Lose("neo".to_string())]);
+    assert!(blackboard.target.is_none());
+
+    let mut blackboard = Blackboard::default();
+    let view = View { cloaked: Vec::new(), players: vec![("neo".to_string(), lobby)],
+        touched: Vec::new(), hurried: Vec::new(), heard: Vec::new(), caught: Vec::new(), wanted: Vec::new() };
+    assert!(ai::think(&pursuer, lobby, &mut blackboard, &view, start).is_empty());
+    assert!(ai::think(&pursuer, lobby, &mut blackboard, &view, start + Duration::from_secs(9)).is_empty());
+    assert_eq!(ai::think(&pursuer, lobby, &mut blackboard, &view, start + Duration::from_secs(10)),
+        vec![Act::Trace("neo".to_string())]);
+    let view = View { cloaked: Vec::new(), players: vec![("neo".to_string(), vault)],
+        touched: Vec::new(), hurried: Vec::new(), heard: Vec::new(), caught: Vec::new(), wanted: Vec::new() };
+    assert_eq!(ai::think(&pursuer, lobby, &mut blackboard, &view, start), vec![Act::Move(vault)]);
+    assert!(ai::think(&pursuer, vault, &mut blackboard, &view, start).is_empty());
+
+    let patrol = [Behavior::Patrol { route: vec![lobby, vault], interval: Duration::from_secs(30) }];
+    let mut blackboard = Blackboard::default();
+    assert!(ai::think(&patrol, lobby, &mut blackboard, &View::default(), start).is_empty());
+    assert!(ai::think(&patrol, lobby, &mut blackboard, &View::default(), start + Duration::from_secs(29)).is_empty());
+    assert_eq!(ai::think(&patrol, lobby, &mut blackboard, &View::default(), start + Duration::from_secs(30)),
+        vec![Act::Move(vault)]);
+    assert_eq!(ai::think(&patrol, vault, &mut blackboard, &View::default(), start + Duration::from_secs(60)),
+        vec![Act::Move(lobby)]);
+
+    let mut npcs = Npcs::default();
+    npcs.add_spawner(Spawner::new(lobby, Prototype::new("sentinel", "Black ICE.").hostile()
+        .with_behavior(Behavior::Guard(2))
+        .with_behavior(Behavior::Reinforce(1)), 1));
+    npcs.tick(|n| n == lobby, |_| true, start);
+    let view = View { cloaked: Vec::new(), players: vec![("neo".to_string(), lobby)],
+        touched: vec![(lobby, 2, "neo".to_string())], hurried: Vec::new(), heard: Vec::new(),
+        caught: Vec::new(), wanted: Vec::new() };
+    let acts = npcs.think(&view, start);
+    assert_eq!(acts.len(), 2);
+    let (uid, _) = acts[0];
+    let reinforcements = npcs.reinforce(uid, 1);
+    assert_eq!(reinforcements.len(), 1);
+    assert_eq!(reinforcements[0].target(), Some("neo"));
+    assert!(npcs.think(&view, start).is_empty());
+    assert_eq!(npcs.relocate(uid, vault).unwrap().location, vault);
+
+    // The NPC away from its spawner stays while its node is occupied
+    assert_eq!(npcs.tick(|n| n == vault, |_| true, start).len(), 1);
+    assert_eq!(npcs.in_node(vault).count(), 1);
+}
+
+/// Hostile NPCs must target the player with the most threat, hacks must add
+/// their damage as threat and destroy the NPC when its integrity runs out,
+/// taunts must put the player on top and targets in the node are struck.
+#[test]
+fn track_threat() {
+    assert!(matches!(Action::try_from("hack the sentinel 2"),
+        Ok(Action::Hack { target, ordinal: Some(2) }) if target == "sentinel"));
+    assert!(matches!(Action::try_from("taunt 2.sentinel"),
+        Ok(Action::Taunt { target, ordinal: Some(2) }) if target == "sentinel"));
+    assert!(Action::try_from("hack").is_err());
+
+    let mut threat = Threat::default();
+    assert!(threat.top().is_none());
+    threat.add("neo", 10);
+    threat.add("trinity", 10);
+    assert_eq!(threat.top(), Some("neo"));
+    threat.add("trinity", 5);
+    assert_eq!(threat.top(), Some("trinity"));
+    threat.taunt("neo");
+    assert_eq!(threat.top(), Some("neo"));
+    threat.retain(|p| p != "neo");
+    assert_eq!(threat.top(), Some("trinity"));
+
+    let mut arena = generational_arena::Arena::new();
+    let lobby = arena.insert(());
+    let start = tokio::time::Instant::now();
+    let mut npcs = Npcs::default();
+    npcs.add_spawner(Spawner::new(lobby, Prototype::new("sentinel", "Black ICE.").hostile().with_integrity(15)
+        .with_behavior(Behavior::Strike { interval: Duration::from_secs(5), jam: Duration::from_secs(2) }), 2));
+    npcs.add_spawner(Spawner::new(lobby, Prototype::new("daemon", "A helpful daemon."), 1));
+    npcs.tick(|n| n == lobby, |_| true, start);
+    let first = npcs.find(lobby, "sentinel", None).unwrap().uid;
+    let second = npcs.find(lobby, "sentinel", Some(2)).unwrap().uid;
+    assert_ne!(first, second);
+    assert!(npcs.find(lobby, "sentinel", Some(3)).is_none());
+    let daemon = npcs.find(lobby, "daemon", None).unwrap().uid;
+    assert!(npcs.hack(daemon, "neo", 10, start).is_none());
+    assert!(!npcs.taunt(daemon, "neo"));
+
+    // Hacking the port makes neo the target, trinity hacking the sentinel
+    // draws it away until neo taunts it
+    let players = vec![("neo".to_string(), lobby), ("trinity".to_string(), lobby)];
+    let view = View { cloaked: Vec::new(), players: players.clone(),
+        touched: vec![(lobby, 2, "neo".to_string())], hurried: Vec::new(), heard: Vec::new(),
+        caught: Vec::new(), wanted: Vec::new() };
+    assert!(npcs.think(&view, start).contains(&(first, Act::Trace("neo".to_string()))));
+    assert_eq!(npcs.hack(first, "trinity", 10, start), Some(5));
+    let view = View { cloaked: Vec::new(), players, touched: Vec::new(), hurried: Vec::new(), heard: Vec::new(),
+        caught: Vec::new(), wanted: Vec::new() };
+    assert!(npcs.think(&view, start).contains(&(first, Act::Trace("trinity".to_string()))));
+    assert!(npcs.taunt(first, "neo"));
+    let acts = npcs.think(&view, start + Duration::from_secs(5));
+    assert!(acts.contains(&(first, Act::Trace("neo".to_string()))));
+    assert!(acts.contains(&(first, Act::Strike { player: "neo".to_string(), jam: Duration::from_secs(2) })));
+    assert_eq!(npcs.get(first).unwrap().target(), Some("neo"));
+
+    assert_eq!(npcs.hack(first, "trinity", 10, start), Some(0));
+    assert!(npcs.get(first).is_none());
+    assert_eq!(npcs.in_node(lobby).count(), 2);
+}
+
+/// Combat events must be rendered at the verbosity of each player, carry
+/// the structured event for robots and mirror the vitals as GMCP.
+#[test]
+fn combat_messages() {
+    assert!(matches!(Action::try_from("combat"), Ok(Action::Combat(None))));
+    assert!(matches!(Action::try_from("combat Summary"), Ok(Action::Combat(Some(Verbosity::Summary)))));
+    assert!(Action::try_from("combat loud").is_err());
+    let account: Account = serde_json::from_str(r#"{"name": "neo"}"#).unwrap();
+    assert_eq!(account.combat, Verbosity::Full);
+
+    let hack = CombatEvent::Hack { player: "trinity".to_string(), drone: false, npc: 3, name: "sentinel".to_string(),
+        damage: 10, integrity: 5, max: 15 };
+    assert_eq!(hack.text("neo", Verbosity::Full).unwrap(), "trinity hacks the sentinel for 10 damage (5/15 integrity left).");
+    assert_eq!(hack.text("trinity", Verbosity::Full).unwrap(), "You hack the sentinel for 10 damage (5/15 integrity left).");
+    assert_eq!(hack.text("neo", Verbosity::Summary).unwrap(), "trinity > sentinel -10 (5/15)");
+    assert!(hack.text("neo", Verbosity::Minimal).is_none());
+    assert_eq!(hack.text("trinity", Verbosity::Minimal).unwrap(), "you > sentinel -10 (5/15)");
+
+    let robot = hack.message("neo", Verbosity::Full).unwrap().render(OutputMode::Robot);
+    let json: serde_json::Value = serde_json::from_str(robot.trim()).unwrap();
+    assert_eq!(json["fields"]["combat"]["kind"], "hack");
+    assert_eq!(json["fields"]["combat"]["integrity"], 5);
+    let (package, data) = hack.vitals("neo").unwrap();
+    assert_eq!(package, "Char.Enemy");
+    assert_eq!(data["max"], 15);
+    let packet = media::gmcp(package, &data);
+    assert!(String::from_utf8_lossy(&packet).contains("Char.Enemy {"));
+
+    let strike = CombatEvent::Strike { player: "neo".to_string(), npc: 3, name: "sentinel".to_string(), jam: 3 };
+    assert_eq!(strike.text("neo", Verbosity::Full).unwrap(), "The sentinel strikes you. Your deck is jammed for 3s.");
+    assert_eq!(strike.text("trinity", Verbosity::Full).unwrap(), "The sentinel strikes neo.");
+    assert_eq!(strike.vitals("neo").unwrap().1["jammed"], 3);
+    assert!(strike.vitals("trinity").is_none());
+
+    let taunt = CombatEvent::Taunt { player: "neo".to_string(), npc: 3, name: "sentinel".to_string() };
+    assert_eq!(taunt.text("neo", Verbosity::Full).unwrap(), "You run a decoy at the sentinel. It turns on you.");
+    assert_eq!(taunt.text("trinity", Verbosity::Summary).unwrap(), "neo taunts sentinel");
+}
+
+/// Cloaks must wear out under the perception checks of ICE in the node,
+/// which NPCs only run while cloaked players are there, and noisy actions
+/// must be told apart from quiet ones.
+#[test]
+fn stealth_and_detection() {
+    assert!(matches!(Action::try_from("cloak"), Ok(Action::Cloak(true))));
+    assert!(matches!(Action::try_from("cloak off"), Ok(Action::Cloak(false))));
+    assert!(matches!(Action::try_from("scan"), Ok(Action::Scan)));
+    assert!(Action::try_from("hack sentinel").unwrap().is_noisy());
+    assert!(Action::try_from("say hi").unwrap().is_noisy());
+    assert!(!Action::try_from("look").unwrap().is_noisy());
+    assert!(!Action::try_from("cloak").unwrap().is_noisy());
+
+    let mut cloak = Cloak::new();
+    for _ in 0..4 {
+        assert!(!cloak.wear(20));
+    }
+    assert!(cloak.wear(20));
+
+    let mut arena = generational_arena::Arena::new();
+    let lobby = arena.insert(());
+    let vault = arena.insert(());
+    let start = tokio::time::Instant::now();
+    let behaviors = [Behavior::Pursue { trace_time: Duration::ZERO },
+        Behavior::Perceive { interval: Duration::from_secs(5), perception: 20 }];
+    let mut blackboard = Blackboard::default();
+    let hidden = View { cloaked: vec![lobby], ..View::default() };
+    assert!(ai::think(&behaviors, lobby, &mut blackboard, &hidden, start).is_empty());
+    assert!(ai::think(&behaviors, lobby, &mut blackboard, &hidden, start + Duration::from_secs(4)).is_empty());
+    assert_eq!(ai::think(&behaviors, lobby, &mut blackboard, &hidden, start + Duration::from_secs(5)),
+        vec![Act::Perceive(20)]);
+    let elsewhere = View { cloaked: vec![vault], ..View::default() };
+    assert!(ai::think(&behaviors, lobby, &mut blackboard, &elsewhere, start + Duration::from_secs(10)).is_empty());
+    assert!(blackboard.target.is_none());
+
+    // Once spotted, the player is traced
+    let spotted = View { players: vec![("neo".to_string(), lobby)], ..View::default() };
+    assert_eq!(ai::think(&behaviors, lobby, &mut blackboard, &spotted, start + Duration::from_secs(11)),
+        vec![Act::Trace("neo".to_string())]);
+}
+
+/// Drones must take orders, keep their integrity in the account and be
+/// wrecked when it runs out, and their fights must be told apart from the
+/// fights of their owners.
+#[test]
+fn companion_drones() {
+    assert!(matches!(Action::try_from("drone"), Ok(Action::Drone(Order::Status))));
+    assert!(matches!(Action::try_from("drone Deploy"), Ok(Action::Drone(Order::Deploy))));
+    assert!(matches!(Action::try_from("drone recall"), Ok(Action::Drone(Order::Recall))));
+    assert!(matches!(Action::try_from("drone scan"), Ok(Action::Drone(Order::Scan))));
+    assert!(matches!(Action::try_from("drone attack the sentinel 2"),
+        Ok(Action::Drone(Order::Attack { target, ordinal: Some(2) })) if target == "sentinel"));
+    assert!(Action::try_from("drone attack").is_err());
+    assert!(Action::try_from("drone dance").is_err());
+    assert!(Action::try_from("drone attack sentinel").unwrap().is_noisy());
+    assert!(!Action::try_from("drone scan").unwrap().is_noisy());
+    assert_eq!(Action::try_from("drone attack sentinel 2").unwrap().to_string(), "drone attack sentinel 2");
+
+    let mut account = Account::new("neo");
+    account.drone = Some(Drone::new("wasp"));
+    let stored: Account = serde_json::from_str(&serde_json::to_string(&account).unwrap()).unwrap();
+    assert_eq!(stored.drone, account.drone);
+    let mut drone = stored.drone.unwrap();
+    assert!(!drone.damage(20));
+    assert!(drone.damage(20));
+    assert_eq!(drone.integrity, 0);
+
+    let hack = CombatEvent::Hack { player: "neo".to_string(), drone: true, npc: 3, name: "sentinel".to_string(),
+        damage: 5, integrity: 10, max: 40 };
+    assert_eq!(hack.text("neo", Verbosity::Full).unwrap(), "Your drone hacks the sentinel for 5 damage (10/40 integrity left).");
+    assert_eq!(hack.text("trinity", Verbosity::Full).unwrap(),
+        "The drone of neo hacks the sentinel for 5 damage (10/40 integrity left).");
+    assert_eq!(hack.text("trinity", Verbosity::Summary).unwrap(), "neo's drone > sentinel -5 (10/40)");
+
+    let shield = CombatEvent::Shield { player: "neo".to_string(), npc: 3, name: "sentinel".to_string(), integrity: 0 };
+    assert_eq!(shield.text("neo", Verbosity::Full).unwrap(), "Your drone takes the strike of the sentinel and is wrecked.");
+    assert_eq!(shield.text("trinity", Verbosity::Summary).unwrap(), "sentinel > neo's drone wrecked");
+    assert_eq!(shield.vitals("neo").unwrap().0, "Char.Drone");
+}
+
+/// The market must hold listed items in escrow, deliver trades and expired
+/// listings by mail and survive a restart. Sellers match ignoring case.
+#[tokio::test]
+async fn trade_at_market() {
+    assert!(matches!(Action::try_from("market"), Ok(Action::Market(Trade::Browse(None)))));
+    assert!(matches!(Action::try_from("market search Keycard"),
+        Ok(Action::Market(Trade::Browse(Some(term)))) if term == "Keycard"));
+    assert!(matches!(Action::try_from("market sell Ono-Sendai keycard 50"),
+        Ok(Action::Market(Trade::Sell { item, price: 50 })) if item == "ono-sendai keycard"));
+    assert!(matches!(Action::try_from("market buy #3"), Ok(Action::Market(Trade::Buy(3)))));
+    assert!(matches!(Action::try_from("market cancel 3"), Ok(Action::Market(Trade::Cancel(3)))));
+    assert!(matches!(Action::try_from("mail"), Ok(Action::Mail)));
+    assert!(Action::try_from("market sell keycard").is_err());
+    assert!(Action::try_from("market sell keycard 0").is_err());
+    assert!(Action::try_from("market buy keycard").is_err());
+    assert_eq!(Action::try_from("market sell keycard 50").unwrap().to_string(), "market sell keycard 50");
+
+    let mut market = Market::default();
+    let first = market.list("neo", "ono-sendai keycard", 50, 1000).id;
+    let second = market.list("neo", "icebreaker", 80, 2000).id;
+    assert_eq!(market.search(Some("KEYCARD")).len(), 1);
+    assert_eq!(market.search(None).len(), 2);
+    assert_eq!(market.listings_of("Neo").len(), 2);
+    assert!(market.cancel(first, "trinity").is_none());
+
+    let sold = market.buy(first, "trinity").unwrap();
+    assert_eq!(sold.item, "ono-sendai keycard");
+    assert!(market.buy(first, "morpheus").is_none());
+    assert_eq!(market.collect("trinity")[0].item.as_deref(), Some("ono-sendai keycard"));
+    assert_eq!(market.mail_count("neo"), 1);
+
+    assert!(market.expire(2000 + market::LISTING_TIME - 1).is_empty());
+    assert_eq!(market.expire(2000 + market::LISTING_TIME)[0].id, second);
+    let mail = market.collect("neo");
+    assert_eq!(mail.iter().map(|p| p.credits).sum::<u64>(), 50);
+    assert_eq!(mail[1].item.as_deref(), Some("icebreaker"));
+    assert!(market.collect("neo").is_empty());
+
+    let data_dir = std::env::temp_dir().join(format!("mud-server-market-{}", std::process::id()));
+    assert!(Market::load(&data_dir).await.unwrap().search(None).is_empty());
+    let third = market.list("neo", "icebreaker", 60, 3000).id;
+    market.save(&data_dir).await.unwrap();
+    let mut stored = Market::load(&data_dir).await.unwrap();
+    assert_eq!(stored.get(third).unwrap().price, 60);
+    assert!(stored.list("neo", "deck", 10, 3000).id > third);
+    stored.remove("NEO");
+    assert!(stored.search(None).is_empty());
+    std::fs::remove_dir_all(&data_dir).unwrap();
+}
+
+/// Deposits and withdrawals must move exactly what the player carries or
+/// the vault holds, and the vault must be kept with the account.
+#[test]
+fn bank_vault() {
+    assert!(matches!(Action::try_from("balance"), Ok(Action::Bank(Banking::Balance))));
+    assert!(matches!(Action::try_from("deposit 50 credits"), Ok(Action::Bank(Banking::Deposit(Funds::Credits(50))))));
+    assert!(matches!(Action::try_from("withdraw 20"), Ok(Action::Bank(Banking::Withdraw(Funds::Credits(20))))));
+    assert!(matches!(Action::try_from("deposit Ono-Sendai keycard"),
+        Ok(Action::Bank(Banking::Deposit(Funds::Item(item)))) if item == "ono-sendai keycard"));
+    assert!(Action::try_from("deposit").is_err());
+    assert!(Action::try_from("withdraw 0").is_err());
+    assert_eq!(Action::try_from("deposit 50").unwrap().to_string(), "deposit 50 credits");
+
+    let mut account = Account::new("neo");
+    account.credits = 100;
+    account.items.push("ono-sendai keycard".to_string());
+    assert_eq!(bank::deposit(&mut account, &Funds::Credits(60)), Some(Funds::Credits(60)));
+    assert_eq!(bank::deposit(&mut account, &Funds::Credits(60)), None);
+    assert_eq!(bank::deposit(&mut account, &Funds::Item("ONO-SENDAI keycard".to_string())),
+        Some(Funds::Item("ono-sendai keycard".to_string())));
+    assert!(account.items.is_empty());
+    assert_eq!(bank::withdraw(&mut account, &Funds::Item("icebreaker".to_string())), None);
+    assert_eq!(bank::withdraw(&mut account, &Funds::Credits(10)), Some(Funds::Credits(10)));
+    assert_eq!((account.credits, account.vault.credits), (50, 50));
+
+    let stored: Account = serde_json::from_str(&serde_json::to_string(&account).unwrap()).unwrap();
+    assert_eq!(stored.vault, account.vault);
+    assert_eq!(stored.vault.items, vec!["ono-sendai keycard".to_string()]);
+}
+
+/// Games must be replayed by their seed, stay within the limits of the
+/// house and rank poker hands by the rules.
+#[test]
+fn play_at_casino() {
+    assert!(matches!(Action::try_from("dice 10"), Ok(Action::Gamble(Game::Dice(10)))));
+    assert!(matches!(Action::try_from("Poker 5"), Ok(Action::Gamble(Game::Poker(5)))));
+    assert!(Action::try_from("dice").is_err());
+    assert!(Action::try_from("poker 0").is_err());
+    assert_eq!(AdminCommand::try_from("@casino 20 100").unwrap(),
+        AdminCommand::Casino(Some(Limits { max_wager: 20, max_loss: 100 })));
+    assert_eq!(AdminCommand::try_from("@casino").unwrap(), AdminCommand::Casino(None));
+    assert!(AdminCommand::try_from("@casino 20").is_err());
+
+    let limits = Limits { max_wager: 50, max_loss: 100 };
+    let (mut first, mut second) = (Casino::new(42, limits), Casino::new(42, limits));
+    for game in [Game::Dice(10), Game::Poker(10), Game::Dice(5)] {
+        assert_eq!(first.play("neo", &game, 1000, 0), second.play("neo", &game, 1000, 0));
+    }
+
+    let mut casino = Casino::new(7, limits);
+    assert_eq!(casino.play("neo", &Game::Dice(60), 1000, 0), Err(Refusal::Wager(50)));
+    assert_eq!(casino.play("neo", &Game::Dice(20), 10, 0), Err(Refusal::Credits));
+    let mut lost = 0;
+    while lost + 50 <= 100 {
+        match casino.play("neo", &Game::Poker(50), 1000, 0) {
+            Ok(outcome) if outcome.net < 0 => lost += 50,
+            Ok(_) => {},
+            Err(r) => panic!("Refused before the loss limit: {}", r),
+        }
+    }
+    assert_eq!(casino.play("neo", &Game::Dice(10), 1000, 0), Err(Refusal::Losses(0)));
+    assert!(casino.play("trinity", &Game::Dice(10), 1000, 0).is_ok());
+    assert!(casino.play("neo", &Game::Dice(10), 1000, 24 * 60 * 60).is_ok());
+
+    // Ranks count from two (0) to ace (12)
+    let full_house = casino::rank(&[(3, 0), (3, 1), (3, 2), (9, 0), (9, 1)]);
+    let flush = casino::rank(&[(1, 2), (4, 2), (6, 2), (9, 2), (12, 2)]);
+    let wheel = casino::rank(&[(12, 0), (0, 1), (1, 2), (2, 3), (3, 0)]);
+    let straight = casino::rank(&[(4, 0), (0, 1), (1, 2), (2, 3), (3, 0)]);
+    let two_pair = casino::rank(&[(5, 0), (5, 1), (2, 2), (2, 3), (12, 0)]);
+    assert_eq!(full_house.0, 6);
+    assert_eq!(flush.0, 5);
+    assert_eq!((wheel.0, straight.0, two_pair.0), (4, 4, 2));
+    assert!(full_house > flush && flush > straight && straight > wheel && wheel > two_pair);
+}
+
+/// Vendors must hold back wares from players without the reputation, give
+/// trusted players a discount and follow supply and demand.
+#[test]
+fn vendor_prices() {
+    assert!(matches!(Action::try_from("wares"), Ok(Action::Vendor(Deal::Wares))));
+    assert!(matches!(Action::try_from("buy Black ICE shard"),
+        Ok(Action::Vendor(Deal::Buy(item))) if item == "black ice shard"));
+    assert!(matches!(Action::try_from("sell icebreaker"), Ok(Action::Vendor(Deal::Sell(item))) if item == "icebreaker"));
+    assert!(Action::try_from("buy").is_err());
+
+    let vendor = Vendor::new("fixer", "runners")
+        .with_ware("icebreaker", 100)
+        .with_gated_ware("black ice shard", 150, 20);
+    assert_eq!(vendor.wares(0).count(), 1);
+    assert!(vendor.ware("BLACK ICE SHARD", 0).is_none());
+    assert!(vendor.ware("black ice shard", 20).is_some());
+
+    let mut account = Account::new("neo");
+    assert_eq!(account.reputation("runners"), 0);
+    account.reputation.insert("runners".to_string(), 100);
+    let icebreaker = vendor.ware("icebreaker", 0).unwrap();
+    let mut economy = Economy::default();
+    assert_eq!(economy.price(icebreaker, 0, 0), 100);
+    assert_eq!(economy.price(icebreaker, account.reputation("runners"), 0), 80);
+    assert_eq!(economy.price(icebreaker, -100, 0), 120);
+    assert_eq!(economy.offer(icebreaker, 0, 0), 50);
+
+    economy.bought("icebreaker", 0);
+    economy.bought("icebreaker", 0);
+    assert_eq!(economy.price(icebreaker, 0, 0), 120);
+    assert_eq!(economy.price(icebreaker, 0, 60 * 60), 110);
+    for _ in 0..20 {
+        economy.sold("icebreaker", 60 * 60);
+    }
+    assert_eq!(economy.price(icebreaker, 0, 60 * 60), 50);
+    assert!(economy.price(icebreaker, 0, 100 * 60 * 60) >= 99);
+}
+
+/// The ledger must book the credits of every system separately, shared by
+/// its clones, and report the net flow.
+#[test]
+fn economy_ledger() {
+    assert_eq!(AdminCommand::try_from("@economy").unwrap(), AdminCommand::Economy);
+    assert!(AdminCommand::try_from("@economy now").is_err());
+
+    let ledger = Ledger::new();
+    let exported = ledger.clone();
+    ledger.create("rewards", 100);
+    ledger.create("casino", 30);
+    ledger.destroy("casino", 50);
+    ledger.destroy("vendors", 0);
+    assert_eq!(exported.flows(), vec![(Flow::Created, "casino", 30), (Flow::Created, "rewards", 100),
+        (Flow::Destroyed, "casino", 50)]);
+    assert_eq!((exported.total(Flow::Created), exported.total(Flow::Destroyed)), (130, 50));
+    let report = ledger.to_string();
+    assert!(report.contains("created 130"));
+    assert!(report.contains("net +80"));
+    assert!(report.contains("casino destroyed 50"));
+}
+
+/// Seasonal content must only be active between the dates of its season,
+/// unless an admin forces the season, and NPCs must leave with their season.
+#[test]
+fn seasonal_content() {
+    assert_eq!(AdminCommand::try_from("@flags").unwrap(), AdminCommand::Flags(None));
+    assert_eq!(AdminCommand::try_from("@flags Con on").unwrap(),
+        AdminCommand::Flags(Some(("con".to_string(), Some(true)))));
+    assert_eq!(AdminCommand::try_from("@flags con auto").unwrap(), AdminCommand::Flags(Some(("con".to_string(), None))));
+    assert!(AdminCommand::try_from("@flags con").is_err());
+
+    let mut seasons = Seasons::default();
+    seasons.add("con", Season { start: 1000, end: 2000 });
+    assert!(!seasons.is_active("con", 999));
+    assert!(seasons.is_active("con", 1000));
+    assert!(!seasons.is_active("con", 2000));
+    assert!(!seasons.is_active("halloween", 1500));
+    assert!(seasons.allows(None, 0));
+    assert!(!seasons.allows(Some("con"), 0));
+    assert!(seasons.set_override("con", Some(true)));
+    assert_eq!(seasons.active(0), vec!["con"]);
+    assert!(seasons.describe(0).contains("con 1970-01-01T00:16:40Z to 1970-01-01T00:33:20Z (active, forced)"));
+    assert!(seasons.set_override("con", None));
+    assert!(seasons.active(0).is_empty());
+    assert!(!seasons.set_override("halloween", Some(true)));
+
+    let puzzle = Puzzle::new("con/motto", Puzzle::hash("hack the planet"), "Motto?").during("con");
+    assert_eq!(puzzle.season(), Some("con"));
+
+    let mut arena = generational_arena::Arena::new();
+    let lobby = arena.insert(());
+    let mut npcs = Npcs::default();
+    npcs.add_spawner(Spawner::new(lobby, Prototype::new("herald", "Calls out talks."), 1).during("con"));
+    let start = tokio::time::Instant::now();
+    assert!(npcs.tick(|_| true, |s| seasons.allows(s, 0), start).is_empty());
+    assert_eq!(npcs.tick(|_| true, |s| seasons.allows(s, 1500), start).len(), 1);
+    let changes = npcs.tick(|_| true, |s| seasons.allows(s, 2000), start);
+    assert!(matches!(&changes[..], [Change::Despawned(n)] if n.name() == "herald"));
+}
+
+/// Safe zones must refuse fights, and zones must keep players below their
+/// level away from dangerous commands only.
+#[test]
+fn zone_safety() {
+    assert_eq!(safety::level(0), 1);
+    assert_eq!(safety::level(49), 1);
+    assert_eq!(safety::level(50), 2);
+    assert_eq!(Node::new(0).safety(), Safety::default());
+
+    let hack = Action::try_from("hack sentinel").unwrap();
+    let dice = Action::try_from("dice 10").unwrap();
+    let look = Action::try_from("look").unwrap();
+    let safe = Safety { safe: true, min_level: 0 };
+    assert_eq!(safe.check(&hack, 1), Err(safety::Refusal::Safe));
+    assert_eq!(safe.check(&dice, 1), Ok(()));
+    assert_eq!(safe.check(&look, 1), Ok(()));
+
+    let newbies = Safety { safe: false, min_level: 2 };
+    assert_eq!(newbies.check(&hack, 1), Err(safety::Refusal::Level(2)));
+    assert_eq!(newbies.check(&dice, 1), Err(safety::Refusal::Level(2)));
+    assert_eq!(newbies.check(&look, 1), Ok(()));
+    assert_eq!(newbies.check(&hack, 2), Ok(()));
+    assert_eq!(safety::Refusal::Level(2).to_string(),
+        "You need level 2 for that here (50 points). Solve puzzles to level up.");
+}
+
+/// Idle players must progress once per cycle up to the cap of the session,
+/// and hear what accrued on their next input only.
+#[test]
+fn idle_progression() {
+    let start = tokio::time::Instant::now();
+    let mut account = Account::new("neo");
+    let mut drone = Drone::new("wasp");
+    drone.damage(7);
+    account.drone = Some(drone);
+    let mut idle = Idle::new(start);
+
+    assert!(!idle.tick(&mut account, start + idle::IDLE_AFTER));
+    assert!(idle.tick(&mut account, start + idle::IDLE_AFTER + idle::CYCLE));
+    assert!(!idle.tick(&mut account, start + idle::IDLE_AFTER + idle::CYCLE));
+    assert!(idle.tick(&mut account, start + idle::IDLE_AFTER + idle::CYCLE * 2));
+    assert_eq!(account.credits, 2 * idle::INTEL_CREDITS);
+    assert_eq!(account.drone.as_ref().map(|d| d.integrity), Some(30));
+
+    let back = start + idle::IDLE_AFTER + idle::CYCLE * 2;
+    let report = idle.input(back).unwrap();
+    assert_eq!(report, idle::Report { cycles: 2, credits: 10, repaired: 7 });
+    assert_eq!(report.to_string(), "While you were away for 25m, your scanners gathered intel worth 10 credits \
+        and your deck recompiled your drone (+7 integrity).");
+    assert_eq!(idle.input(back), None);
+
+    let mut later = back;
+    for _ in 0..20 {
+        later += idle::IDLE_AFTER + idle::CYCLE;
+        idle.tick(&mut account, later);
+        idle.input(later);
+    }
+    assert_eq!(account.credits, u64::from(idle::MAX_CYCLES) * idle::INTEL_CREDITS);
+}
+
+/// Streaks must count the first login of consecutive days only, start over
+/// after a missed day, and reward longer streaks with the last day
+/// configured.
+#[test]
+fn login_streaks() {
+    const DAY: u64 = 24 * 60 * 60;
+    let mut streak = Streak::default();
+    assert_eq!(streak.login(10 * DAY + 5), Some(1));
+    assert_eq!(streak.login(10 * DAY + 500), None);
+    assert_eq!(streak.login(11 * DAY), Some(2));
+    assert_eq!(streak.login(12 * DAY + 42), Some(3));
+    assert_eq!(streak.login(14 * DAY), Some(1));
+    assert_eq!(streak.best, 3);
+    assert_eq!(streak.login(13 * DAY), None);
+
+    let rewards = Rewards::default()
+        .on_day(1, Hook::Credits(10))
+        .on_day(3, Hook::Credits(30))
+        .on_day(3, Hook::Title("the regular".to_string()))
+        .milestone(3);
+    assert_eq!(rewards.for_day(1), &[Hook::Credits(10)]);
+    assert_eq!(rewards.for_day(2), &[Hook::Credits(10)]);
+    assert_eq!(rewards.for_day(7), &[Hook::Credits(30), Hook::Title("the regular".to_string())]);
+    assert!(Rewards::default().for_day(1).is_empty());
+    assert!(rewards.is_milestone(3));
+    assert!(!rewards.is_milestone(4));
+}
+
+/// Players must describe themselves with valid descriptions only, and others
+/// must see the description with the flair of the cosmetic items carried.
+#[test]
+fn player_appearance() {
+    assert!(matches!(Action::try_from("describe me a tall decker in chrome").unwrap(),
+        Action::Describe(Some(d)) if d == "a tall decker in chrome"));
+    assert!(matches!(Action::try_from("describe Me").unwrap(), Action::Describe(None)));
+    assert!(Action::try_from("describe you").is_err());
+    assert!(matches!(Action::try_from("look at trinity").unwrap(),
+        Action::Look { target: Some(t), .. } if t == "trinity"));
+
+    let rules = HandleRules { profanity: vec!["darn".to_string()], ..HandleRules::default() };
+    assert_eq!(rules.validate_description("A tall decker in chrome."), Ok(()));
+    assert_eq!(rules.validate_description(""), Err(Error::InvalidLength));
+    assert_eq!(rules.validate_description(&"x".repeat(241)), Err(Error::InvalidLength));
+    assert_eq!(rules.validate_description("two\nlines"), Err(Error::InvalidCharacters));
+    assert_eq!(rules.validate_description("a darn decker"), Err(Error::ProfaneContent));
+
+    let wardrobe = Wardrobe::default().with_flair("Neon Jacket", "A neon jacket glows.");
+    let items = vec!["neon jacket".to_string(), "icebreaker".to_string(), "NEON JACKET".to_string()];
+    let profile = Profile { name: "neo the One".to_string(), description: Some("A tall decker."), items: &items,
+        ..Profile::default() };
+    assert_eq!(wardrobe.appearance(&profile), "neo the One\r\nA tall decker.\r\n  A neon jacket glows.");
+    let profile = Profile { name: "neo".to_string(), ..Profile::default() };
+    assert_eq!(wardrobe.appearance(&profile), format!("neo\r\n{}", appearance::DEFAULT_DESCRIPTION));
+}
+
+/// Looking at a player must show its faction and run tags and its deployed
+/// drone.
+#[test]
+fn look_at_players() {
+    let mut reputation = std::collections::BTreeMap::new();
+    reputation.insert("runners".to_string(), appearance::TAG_REPUTATION);
+    reputation.insert("zaibatsu".to_string(), appearance::TAG_REPUTATION - 1);
+    let profile = Profile { name: "trinity".to_string(), drone: Some("wasp"), reputation: Some(&reputation),
+        run: Some("lobby-rush"), ..Profile::default() };
+    assert_eq!(profile.tags(), vec!["[runners]", "[run lobby-rush]"]);
+    assert_eq!(Wardrobe::default().appearance(&profile), format!("trinity [runners] [run lobby-rush]\r\n{}\r\n  \
+        A wasp drone hovers at their shoulder.", appearance::DEFAULT_DESCRIPTION));
+    assert!(Profile::default().tags().is_empty());
+}
+
+/// The ignore list must parse, match names ignoring case and default to
+/// empty for accounts stored before it existed.
+#[test]
+fn ignore_players() {
+    assert!(matches!(Action::try_from("ignore").unwrap(), Action::Ignore(None)));
+    assert!(matches!(Action::try_from("ignore Smith").unwrap(), Action::Ignore(Some(p)) if p == "Smith"));
+    assert!(matches!(Action::try_from("unignore smith@zion").unwrap(), Action::Unignore(p) if p == "smith@zion"));
+    assert!(Action::try_from("ignore agent smith").is_err());
+    assert!(Action::try_from("unignore").is_err());
+
+    let mut account: Account = serde_json::from_str(r#"{"name": "neo"}"#).unwrap();
+    assert!(account.ignored.is_empty());
+    account.ignored.insert("smith".to_string());
+    account.ignored.insert("brown@zion".to_string());
+    assert!(account.ignores("Smith"));
+    assert!(account.ignores("Brown@Zion"));
+    assert!(!account.ignores("trinity"));
+}
+
+/// Consent flags must parse from "prefs", default to accepting all but duels
+/// and only keep strangers from telling when turned off.
+#[test]
+fn consent_flags() {
+    assert!(matches!(Action::try_from("prefs").unwrap(), Action::Prefs(None)));
+    assert!(matches!(Action::try_from("prefs Tells OFF").unwrap(), Action::Prefs(Some((Pref::Tells, false)))));
+    assert!(matches!(Action::try_from("prefs duels on").unwrap(), Action::Prefs(Some((Pref::Duels, true)))));
+    assert!(Action::try_from("prefs hugs on").is_err());
+    assert!(Action::try_from("prefs tells").is_err());
+
+    let mut account: Account = serde_json::from_str(r#"{"name": "neo"}"#).unwrap();
+    let consent = &mut account.consent;
+    assert!(consent.allows(Interaction::Tell { stranger: true }));
+    assert!(consent.allows(Interaction::Trade) && consent.allows(Interaction::Follow));
+    assert!(!consent.allows(Interaction::Duel));
+    consent.set(Pref::Tells, false);
+    assert!(!consent.allows(Interaction::Tell { stranger: true }));
+    assert!(consent.allows(Interaction::Tell { stranger: false }));
+    consent.set(Pref::Duels, true);
+    assert!(consent.allows(Interaction::Duel));
+    assert!(consent.to_string().contains("tells off (from players not in your node)"));
+}
+
+/// Sessions must add up to the activity of an account, stored with it, and
+/// the activity of all accounts to totals that do not count players that
+/// never played.
+#[tokio::test]
+async fn activity_statistics() {
+    assert!(matches!(Action::try_from("score").unwrap(), Action::Score));
+    assert!(matches!(AdminCommand::try_from("@totals announce").unwrap(), AdminCommand::Totals { announce: true }));
+    assert!(AdminCommand::try_from("@totals now").is_err());
+    assert_eq!(stats::duration(42), "42s");
+    assert_eq!(stats::duration(3_723), "1h 2m 3s");
+
+    let mut session = SessionStats::new();
+    session.commands = 12;
+    session.explored.extend([1, 2]);
+    let mut neo = Activity::default();
+    neo.record(&session);
+    assert_eq!(neo.with(&session).sessions, 2);
+    assert_eq!((neo.sessions, neo.commands, neo.nodes.len()), (1, 12, 2));
+
+    let data_dir = std::env::temp_dir().join(format!("mud-server-test-activity-{}", std::process::id()));
+    let mut account = Account::new("neo");
+    account.activity = neo.clone();
+    account.save(&data_dir).await.unwrap();
+    Account::new("trinity").save(&data_dir).await.unwrap();
+    let accounts = Account::load_all(&data_dir).await.unwrap();
+    std::fs::remove_dir_all(&data_dir).unwrap();
+    assert_eq!(accounts.len(), 2);
+    assert!(accounts.iter().any(|a| a.activity == neo));
+
+    let mut totals = Totals::default();
+    session.explored.insert(3);
+    totals.add(&Activity::default().with(&session));
+    for account in &accounts {
+        totals.add(&account.activity);
+    }
+    assert_eq!((totals.players, totals.sessions, totals.commands, totals.nodes()), (2, 2, 24, 3));
+    assert!(totals.to_string().contains("2 deckers jacked in 2 times"));
+    assert!(Account::load_all(&data_dir).await.unwrap().is_empty());
+}
+
+/// The archive must hold the nodes with their assets, the leaderboards and
+/// the announcements, but not the names of the players.
+#[test]
+fn world_archive() {
+    let mut node = Node::new(0);
+    node.update_name("Lobby");
+    node.add_asset(Box::new(Port::new(1)));
+    let mut nodes = generational_arena::Arena::new();
+    nodes.insert(node);
+    let mut challenges = challenges::Challenges::default();
+    challenges.add(Challenge::new("rush", "Crack the port.", Goal::Crack(vec!["a".to_string()]),
+        Duration::from_secs(60)));
+    challenges.leaderboard.record("rush", vec!["neo".to_string(), "trinity".to_string()], Duration::from_secs(42));
+    let news = News::new();
+    news.publish(news::Kind::Announcement, "Announcement", "The con is over.", Vec::new());
+    news.publish(news::Kind::Record, "#1 on the leaderboard of rush", "neo won challenge rush.",
+        vec!["neo".to_string()]);
+    let mut totals = Totals::default();
+    totals.add(&Activity::default().with(&SessionStats::new()));
+
+    let archive = Archive::new(&nodes, &challenges, &news, &totals, 3);
+    let json = serde_json::to_value(&archive).unwrap();
+    assert_eq!(json["format"], ARCHIVE_FORMAT);
+    assert_eq!(json["world"][0]["name"], "Lobby");
+    assert_eq!(json["world"][0]["assets"][0]["uid"], 1);
+    assert_eq!(json["leaderboards"]["rush"]["records"][0]["team"], 2);
+    assert_eq!(json["leaderboards"]["rush"]["records"][0]["millis"], 42_000);
+    assert_eq!(json["boards"].as_array().unwrap().len(), 1);
+    assert_eq!(json["statistics"]["visitors"], 3);
+    assert_eq!(json["statistics"]["players"], 1);
+    assert!(!json.to_string().contains("neo") && !json.to_string().contains("trinity"));
+}
+
+/// The router must pass a client to the world chosen at login (or the
+/// default world), and move it to another world with what it brings along.
+#[tokio::test]
+async fn route_worlds() {
+    assert_eq!(worlds::split_login("neo+workshop"), ("neo", Some("workshop")));
+    assert_eq!(worlds::split_login("neo"), ("neo", None));
+    let (transfer_tx, mut transfer_rx) = tokio::sync::mpsc::channel(worlds::QUEUE_CAPACITY);
+    let gate = Gate::new(vec!["workshop".to_string()], transfer_tx);
+    assert_eq!(gate.leads_to("WorkShop"), Some("workshop"));
+    assert_eq!(gate.leads_to("balccon"), None);
+
+    let metrics = Metrics::new(&Capacities { command: 8, data: 8, push: 1 });
+    let (main, mut main_commands, _main_data) = Route::new("balccon", &metrics);
+    let (workshop, mut workshop_commands, mut workshop_data) = Route::new("workshop", &metrics);
+    let mut router = Router::new(vec![main, workshop]);
+    let (push_tx, _push_rx) = channels::channel(&metrics.push);
+    let info = ClientInfo { transport: Transport::Ssh, verified: false };
+    let connection = || Box::new(PushConnection::new(info.clone(), push_tx.clone()));
+    router.command(Command::Register(1, "neo+workshop".to_string(), connection())).await;
+    assert!(matches!(workshop_commands.recv().await, Some(Command::Register(1, name, _)) if name == "neo"));
+    router.command(Command::Register(2, "trinity+nowhere".to_string(), connection())).await;
+    assert!(matches!(main_commands.recv().await, Some(Command::Register(2, name, _)) if name == "trinity"));
+    router.data(DataMessage::new(1, b"look".to_vec())).await;
+    assert_eq!(workshop_data.recv().await.unwrap().data, b"look".to_vec());
+
+    let traveler = Traveler { name: "neo".to_string(), title: None, score: 20, flags: Default::default() };
+    assert!(gate.has_room());
+    assert!(gate.send(Transfer { client_id: 1, world: "balccon".to_string(), connection: connection(), 
+        traveler: traveler.clone() }).is_ok());
+    router.transfer(transfer_rx.recv().await.unwrap()).await;
+    assert!(matches!(main_commands.recv().await, Some(Command::Arrival(t)) if t == traveler));
+    assert!(matches!(main_commands.recv().await, Some(Command::Register(1, name, _)) if name == "neo"));
+    router.command(Command::Hangup(1)).await;
+    assert!(matches!(main_commands.recv().await, Some(Command::Hangup(1))));
+}
+
+/// Edits in a sandbox must only change the copy of the zone, and promoting
+/// them must apply them to the live zone, including the assets created.
+#[test]
+fn builder_sandboxes() {
+    assert_eq!(AdminCommand::try_from("@sandbox enter").unwrap(), AdminCommand::Sandbox(SandboxStep::Enter(None)));
+    assert_eq!(AdminCommand::try_from("@sandbox enter 10").unwrap().to_string(), "@sandbox enter 10");
+    assert!(AdminCommand::try_from("@sandbox promote").unwrap().is_for_builders());
+    assert!(AdminCommand::try_from("@sandbox enter lobby").is_err());
+    assert!(AdminCommand::try_from("@sandbox").is_err());
+
+    let mut nodes = generational_arena::Arena::new();
+    for uid in [0, 10] {
+        let mut node = Node::new(uid);
+        node.update_name("Lobby");
+        node.update_description("A dark node.");
+        node.add_asset(Box::new(Port::new(uid + 1)));
+        nodes.insert(node);
+    }
+    let mut sandboxes = Sandboxes::default();
+    let index = sandboxes.enter(&mut nodes, "neo", 0, None).unwrap();
+    assert!(sandboxes.enter(&mut nodes, "Neo", 10, None).is_err());
+    assert!(sandboxes.enter(&mut nodes, "trinity", 12, None).is_err());
+    assert!(sandboxes.contains(Some(index)));
+    assert_eq!(nodes[index].uid(), 12);
+    assert_eq!(nodes[index].name(), "Lobby [sandbox]");
+    assert_eq!(inspect::locate(&nodes, 13).unwrap().0.uid(), 12);
+
+    assert!(sandboxes.apply(&mut nodes, "neo", Edit::Describe { uid: 1, description: String::new() }).is_err());
+    assert!(sandboxes.apply(&mut nodes, "trinity", Edit::Describe { uid: 13, description: String::new() }).is_err());
+    let describe = Edit::Describe { uid: 13, description: "A purple port.".to_string() };
+    sandboxes.apply(&mut nodes, "neo", describe).unwrap();
+    let create = Edit::Create { node: 12, kind: AssetKind::Port, description: "A new port.".to_string() };
+    assert_eq!(sandboxes.apply(&mut nodes, "neo", create).unwrap(), Edit::Remove { node: 12, uid: 14 });
+    sandboxes.apply(&mut nodes, "neo", Edit::Describe { uid: 14, description: "A blue port.".to_string() }).unwrap();
+    sandboxes.apply(&mut nodes, "neo", Edit::Describe { uid: 12, description: "A lit node.".to_string() }).unwrap();
+    assert!(sandboxes.undo(&mut nodes, "neo").unwrap().is_ok());
+    assert_eq!(inspect::node(&nodes, 12).unwrap().description(), "A dark node.");
+    assert_eq!(inspect::locate(&nodes, 1).unwrap().1.description(), "");
+    assert_eq!(sandboxes.get("neo").unwrap().edits(), 3);
+
+    let applied = sandboxes.get("neo").unwrap().promote(&mut nodes).unwrap();
+    assert_eq!(applied.len(), 3);
+    assert_eq!(applied[0].0, Edit::Describe { uid: 1, description: "A purple port.".to_string() });
+    assert_eq!(inspect::locate(&nodes, 1).unwrap().1.description(), "A purple port.");
+    assert_eq!(inspect::locate(&nodes, 15).unwrap().0.uid(), 0);
+    assert_eq!(inspect::locate(&nodes, 15).unwrap().1.description(), "A blue port.");
+
+    assert_eq!(sandboxes.close(&mut nodes, "NEO").unwrap().zone, 0);
+    assert!(!sandboxes.contains(Some(index)));
+    assert!(inspect::node(&nodes, 12).is_none());
+    assert!(sandboxes.close(&mut nodes, "neo").is_none());
+}
+
+/// Analytics must only count when enabled, keep failure rates per verb and
+/// log their summary at most once per interval.
+#[test]
+fn count_analytics() {
+    let disabled = Analytics::default();
+    disabled.action("look", Some(0), false);
+    disabled.misunderstood(&Error::VerbUnknownError);
+    assert!(disabled.actions().is_empty());
+    assert!(disabled.summary(tokio::time::Instant::now()).is_none());
+
+    let analytics = Analytics::enabled(Duration::from_secs(60));
+    let shared = analytics.clone();
+    for failed in [false, false, true, false] {
+        shared.action("look", Some(10), failed);
+    }
+    shared.action("solve", Some(0), true);
+    shared.action("who", None, false);
+    shared.action("look", Some(0), false);
+    shared.misunderstood(&Error::VerbUnknownError);
+    shared.misunderstood(&Error::VerbEncodingError);
+    shared.misunderstood(&Error::VerbUnknownError);
+
+    let look = analytics.actions().into_iter().find(|(v, _)| *v == "look").unwrap().1;
+    assert_eq!(look, Usage { performed: 5, failed: 1 });
+    assert!((look.failure_rate() - 0.2).abs() < 1e-9);
+    assert_eq!(analytics.misunderstood_commands(),
+        vec![(Misunderstood::UnknownVerb, 2), (Misunderstood::InvalidArguments, 1)]);
+    assert_eq!(analytics.nodes(), vec![(10, 4), (0, 2)]);
+
+    let start = tokio::time::Instant::now();
+    assert_eq!(analytics.summary(start).unwrap(), "verbs: look 5 (20% failed), solve 1 (100% failed), \
+        who 1 (0% failed); misunderstood: unknown_verb 2, invalid_arguments 1; nodes: #10 4, #0 2");
+    assert!(analytics.summary(start + Duration::from_secs(59)).is_none());
+    assert!(analytics.summary(start + Duration::from_secs(60)).is_some());
+}
+
+/// Replaying transcripts must group the commands with an unknown verb by
+/// their first token and skip admin commands and shell sessions.
+#[tokio::test]
+async fn grammar_coverage() {
+    assert_eq!(AdminCommand::try_from("@coverage neo").unwrap(), AdminCommand::Coverage(Some("neo".to_string())));
+    assert!(AdminCommand::try_from("@coverage neo trinity").is_err());
+    assert!(!AdminCommand::try_from("@coverage").unwrap().is_for_builders());
+
+    let data_dir = std::env::temp_dir().join(format!("mud-server-coverage-{}", std::process::id()));
+    let neo = Transcript::start(&data_dir, "neo").await.unwrap();
+    for command in ["look", "dance", "@stats", "access terminal"] {
+        neo.record_input(command).await.unwrap();
+    }
+    neo.record_output("You jack into wintermute. Type \"help\" for the commands, \"exit\" to jack out.").await.unwrap();
+    for command in ["ls", "exit", "Dance wildly", "say"] {
+        neo.record_input(command).await.unwrap();
+    }
+    let trinity = Transcript::start(&data_dir, "trinity").await.unwrap();
+    trinity.record_input("fly").await.unwrap();
+
+    let coverage = Coverage::load(&data_dir, Some("neo")).await.unwrap();
+    assert_eq!((coverage.replayed, coverage.parsed, coverage.invalid), (5, 2, 1));
+    assert_eq!(coverage.unknown(), vec![("dance", 2)]);
+    let coverage = Coverage::load(&data_dir, None).await.unwrap();
+    assert_eq!(coverage.unknown(), vec![("dance", 2), ("fly", 1)]);
+    assert_eq!(coverage.to_string(), "Replayed 6 commands: 2 parsed, 1 with invalid arguments, \
+        3 with an unknown verb.\r\n  dance 2\r\n  fly 1");
+    assert_eq!(Coverage::load(&data_dir, Some("morpheus")).await.unwrap().replayed, 0);
+    std::fs::remove_dir_all(&data_dir).unwrap();
+}
+
+/// A world file must build the same nodes from TOML and RON, and a world
+/// with a uid used twice or without a spawn node must be refused.
+#[test]
+fn load_world_files() {
+    let toml = r#"
+        [[nodes]]
+        uid = 0
+        name = "Lobby"
+        description = "Around you its dark."
+        spawn = true
+        min_level = 2
+
+        [[nodes.ports]]
+        uid = 1
+        description = "A port rimmed with static."
+        portal = "hackerspace"
+
+        [[nodes.terminals]]
+        uid = 2
+        hostname = "ono-sendai"
+        files = { "/readme.txt" = "Authorized personnel only." }
+
+        [[nodes]]
+        uid = 10
+        name = "Backstage"
+        season = "con"
+    "#;
+    let ron = r#"(nodes: [
+        (uid: 0, name: "Lobby", description: "Around you its dark.", spawn: true, min_level: 2,
+            ports: [(uid: 1, description: "A port rimmed with static.", portal: Some("hackerspace"))],
+            terminals: [(uid: 2, hostname: "ono-sendai", files: { "/readme.txt": "Authorized personnel only." })]),
+        (uid: 10, name: "Backstage", season: Some("con")),
+    ])"#;
+    for (file, data) in [("world.toml", toml), ("world.ron", ron)] {
+        let world_file = WorldFile::parse(std::path::Path::new(file), data.as_bytes()).unwrap();
+        assert_eq!(world_file.max_uid(), 10);
+        let nodes = world_file.build().unwrap();
+        let spawns: Vec<bool> = nodes.iter().map(|(_, spawn)| *spawn).collect();
+        assert_eq!(spawns, vec![true, false]);
+        let (lobby, backstage) = (&nodes[0].0, &nodes[1].0);
+        assert_eq!(lobby.description(), "Around you its dark.");
+        assert_eq!(lobby.safety(), Safety { safe: false, min_level: 2 });
+        assert_eq!(lobby.find_portal().unwrap().portal(), Some("hackerspace"));
+        let terminal = lobby.find_terminal(2).unwrap();
+        assert_eq!(terminal.hostname(), "ono-sendai");
+        let mut shell = Shell::new(Index::from_raw_parts(0, 0), 2);
+        assert_eq!(shell.run(terminal, "cat /readme.txt"), Reply::Output("Authorized personnel only.".to_string()));
+        assert_eq!(backstage.season(), Some("con"));
+    }
+
+    let mut world_file = WorldFile::parse(std::path::Path::new("world.toml"), toml.as_bytes()).unwrap();
+    world_file.nodes[1].ports = world_file.nodes[0].ports.clone();
+    assert_eq!(world_file.build().unwrap_err(), "The uid 1 is used more than once.");
+    world_file.nodes.remove(0);
+    assert!(world_file.build().is_err());
+    assert!(WorldFile::parse(std::path::Path::new("world.toml"), b"nodes = 3").is_err());
+}
+
+/// Adverbs must be parsed into the actions taking them, contradicting ones
+/// refused, and must pace the actions: quick actions leave a trace with the
+/// hostile NPCs in the node, slow ones keep the cloak of the player.
+#[test]
+fn adverb_semantics() {
+    assert!(matches!(Action::try_from("access quickly the 2.terminal"),
+        Ok(Action::Access { target: Some(t), ordinal: Some(2), manner: Some(Adverb::Quickly) }) if t == "terminal"));
+    assert!(matches!(Action::try_from("enter slowly, slowly"),
+        Ok(Action::Enter { target: None, manner: Some(Adverb::Slowly), .. })));
+    assert!(matches!(Action::try_from("look Slowly at the port"),
+        Ok(Action::Look { target: Some(t), preposition: Some(p), manner: Some(Adverb::Slowly), .. })
+            if t == "port" && p == "at"));
+    assert!(matches!(Action::try_from("look quickly."),
+        Ok(Action::Look { target: None, manner: Some(Adverb::Quickly), .. })));
+    assert!(Action::try_from("access quickly slowly terminal").is_err());
+    assert_eq!(Action::try_from("access quickly terminal 2").unwrap().to_string(), "access quickly terminal 2");
+
+    let access = |command: &str| Action::try_from(command).unwrap();
+    assert_eq!(access("access terminal").duration(), Duration::from_secs(1));
+    assert_eq!(access("access quickly terminal").duration(), Duration::from_millis(500));
+    assert_eq!(access("access slowly terminal").duration(), Duration::from_secs(2));
+    assert!(access("access quickly terminal").is_noisy());
+    assert!(!access("access slowly terminal").is_noisy());
+    assert_eq!(access("look quickly").manner(), Some(Adverb::Quickly));
+    assert_eq!(access("hack sentinel").manner(), None);
+
+    let mut arena = generational_arena::Arena::new();
+    let lobby = arena.insert(());
+    let start = tokio::time::Instant::now();
+    let mut npcs = Npcs::default();
+    npcs.add_spawner(Spawner::new(lobby, Prototype::new("sentinel", "Black ICE.").hostile(), 1));
+    npcs.tick(|n| n == lobby, |_| true, start);
+    let sentinel = npcs.find(lobby, "sentinel", None).unwrap().uid;
+    let players = vec![("neo".to_string(), lobby), ("trinity".to_string(), lobby)];
+    let view = View { players: players.clone(), ..View::default() };
+    assert!(npcs.think(&view, start).is_empty());
+    let view = View { players, hurried: vec![(lobby, "trinity".to_string())], ..View::default() };
+    assert_eq!(npcs.think(&view, start), vec![(sentinel, Act::Trace("trinity".to_string()))]);
+}
+
+/// Ports listed with a destination in a world file must be connected to
+/// their node in the world, "enter" and "connect" must take a port, and
+/// a node must offer its only exit when no port is named.
+#[test]
+fn move_between_nodes() {
+    assert!(matches!(Action::try_from("connect"), Ok(Action::Connect { target: None, ordinal: None, manner: None })));
+    assert!(matches!(Action::try_from("connect the port 2"),
+        Ok(Action::Connect { target: Some(t), ordinal: Some(2), .. }) if t == "port"));
+    assert_eq!(Action::try_from("connect quickly 2.port").unwrap().to_string(), "connect quickly port 2");
+
+    let toml = r#"
+        [[nodes]]
+        uid = 0
+        name = "Lobby"
+        spawn = true
+
+        [[nodes.ports]]
+        uid = 1
+        to = 10
+
+        [[nodes]]
+        uid = 10
+        name = "Vault"
+
+        [[nodes.ports]]
+        uid = 11
+        to = 0
+        open = true
+
+        [[nodes.ports]]
+        uid = 12
+    "#;
+    let mut world_file = WorldFile::parse(std::path::Path::new("world.toml"), toml.as_bytes()).unwrap();
+    assert_eq!(world_file.links(), vec![(1, 10), (11, 0)]);
+    let mut nodes = generational_arena::Arena::new();
+    let indices: Vec<Index> = world_file.build().unwrap().into_iter().map(|(node, _)| nodes.insert(node)).collect();
+    for (port, to) in world_file.links() {
+        assert!(loader::connect(&mut nodes, port, to));
+    }
+    assert!(!loader::connect(&mut nodes, 12, 42));
+    assert!(!loader::connect(&mut nodes, 42, 0));
+
+    let (lobby, vault) = (&nodes[indices[0]], &nodes[indices[1]]);
+    let exit = lobby.find_exit().unwrap().port().unwrap();
+    assert_eq!(exit.destination(), Some(indices[1]));
+    assert!(!exit.is_open());
+    let back = vault.find_asset("port", Some(1)).unwrap().port().unwrap();
+    assert_eq!(back.destination(), Some(indices[0]));
+    assert!(back.is_open());
+    assert_eq!(vault.find_exit().unwrap().uid(), 11);
+    assert!(vault.find_portal().is_none());
+
+    world_file.nodes[1].ports[1].to = Some(42);
+    assert_eq!(world_file.build().unwrap_err(), "Port 12 leads to node 42, which does not exist.");
+}
+
+/// Articles and filler words must be dropped from the objects of all verbs,
+/// a polite "please" before any command, but never from the answer to a
+/// puzzle.
+#[test]
+fn tolerate_fillers() {
+    assert!(matches!(Action::try_from("look at the purple port"),
+        Ok(Action::Look { target: Some(t), preposition: Some(p), properties: Some(props), .. })
+            if t == "port" && p == "at" && matches!(props[..], [Property::Color(Color::Purple)])));
+    assert!(matches!(Action::try_from("please look at a port 2 please."),
+        Ok(Action::Look { target: Some(t), ordinal: Some(2), properties: None, .. }) if t == "port"));
+    assert!(matches!(Action::try_from("look port"), Ok(Action::Look { target: Some(t), preposition: None, .. })
+        if t == "port"));
+    assert!(matches!(Action::try_from("look the"), Ok(Action::Look { target: None, .. })));
+    assert!(Action::try_from("look at").is_err());
+    assert!(matches!(Action::try_from("connect to the port"),
+        Ok(Action::Connect { target: Some(t), .. }) if t == "port"));
+    assert!(matches!(Action::try_from("Please enter an 2.port"),
+        Ok(Action::Enter { target: Some(t), ordinal: Some(2), .. }) if t == "port"));
+    assert!(matches!(Action::try_from("hack at the sentinel please"),
+        Ok(Action::Hack { target, ordinal: None }) if target == "sentinel"));
+    assert!(Action::try_from("hint for the port").is_err());
+    assert!(matches!(Action::try_from("solve the a port to be or not to be"),
+        Ok(Action::Solve { target, answer, .. }) if target == "port" && answer == "to be or not to be"));
+    assert!(matches!(Action::try_from("please say the end"), Ok(Action::Say(t)) if t == "the end"));
+}
+
+/// Items must be takeable unlike ports, an inventory must refuse items
+/// beyond its capacity and put down the item referenced, and "take",
+/// "drop" and "inventory" must parse.
+#[test]
+fn carry_items() {
+    assert!(matches!(Action::try_from("take the quickhack"),
+        Ok(Action::Take { target, ordinal: None }) if target == "quickhack"));
+    assert_eq!(Action::try_from("drop 2.quickhack").unwrap().to_string(), "drop quickhack 2");
+    assert!(matches!(Action::try_from("inventory"), Ok(Action::Inventory)));
+    assert!(!Action::Inventory.is_queued());
+    assert!(Action::try_from("take").is_err());
+
+    let toml = r#"
+        [[nodes]]
+        uid = 0
+        name = "Vault"
+        spawn = true
+
+        [[nodes.ports]]
+        uid = 1
+
+        [[nodes.items]]
+        uid = 2
+        name = "Quickhack"
+        description = "A quickhack on a data shard."
+    "#;
+    let world_file = WorldFile::parse(std::path::Path::new("world.toml"), toml.as_bytes()).unwrap();
+    assert_eq!(world_file.max_uid(), 2);
+    let mut nodes = generational_arena::Arena::new();
+    let vault = nodes.insert(world_file.build().unwrap().remove(0).0);
+    assert!(!nodes[vault].find_asset("port", None).unwrap().is_takeable());
+    assert!(nodes[vault].find_asset("quickhack", None).unwrap().is_takeable());
+
+    let mut inventory = Inventory::default();
+    let item = nodes[vault].take_asset(2).unwrap();
+    assert!(nodes[vault].find_asset("quickhack", None).is_err());
+    assert!(inventory.carry(item, vault).is_ok());
+    for uid in 3..inventory::CAPACITY as u64 + 2 {
+        assert!(inventory.carry(Box::new(Item::new(uid, "shard")), vault).is_ok());
+    }
+    assert!(inventory.is_full());
+    assert!(inventory.carry(Box::new(Item::new(100, "shard")), vault).is_err());
+    assert_eq!(inventory.names()[0], "quickhack");
+
+    assert_eq!(inventory.put_down("shard", None).unwrap_err(),
+        "You carry more than one shard. Which one do you mean (eg. \"shard 2\")?");
+    assert_eq!(inventory.put_down("shard", Some(2)).unwrap().uid(), 4);
+    assert_eq!(inventory.put_down("deck", None).unwrap_err(), "You carry no deck.");
+    assert_eq!(inventory.put_down("quickhack", None).unwrap().name(), "quickhack");
+    let left = inventory.empty();
+    assert_eq!(left.len(), inventory::CAPACITY - 2);
+    assert!(left.iter().all(|(_, from)| *from == vault));
+    assert!(inventory.names().is_empty());
+}
+
+/// The telnet decoder must split lines on any line ending, drop telnet
+/// commands and control characters, and the telnet server must refuse the
+/// handles of the staff, handles bound to a key and handles with a stored
+/// account in the chosen world (ignoring case) unless logged in with a login
+/// token.
+#[tokio::test]
+async fn telnet_logins() {
+    let mut decoder = LineDecoder::default();
+    assert_eq!(decoder.feed(b"look\r\nsay hi\r"), vec![b"look".to_vec(), b"say hi".to_vec()]);
+    assert_eq!(decoder.feed(b"\0who\nlo"), vec![b"who".to_vec()]);
+    assert_eq!(decoder.feed(b"ok\x7f\x7fok\r\n"), vec![b"look".to_vec()]);
+    // Option negotiation, a subnegotiation and an escaped IAC
+    assert_eq!(decoder.feed(&[255, 253, 1, b'a', 255, 250, 201, b'x', 255, 240, 255, 255, b'\x07', b'\n']),
+        vec![vec![b'a', 255]]);
+    assert_eq!(decoder.feed(&vec![b'x'; MAX_LINE + 10]), Vec::<Vec<u8>>::new());
+    assert_eq!(decoder.feed(b"\n")[0].len(), MAX_LINE);
+
+    let keyring = Keyring::new(Vec::new());
+    keyring.bind("AAAAC3NzaC1lZDI1NTE5", "trinity").await.unwrap();
+    let tokens = LoginTokens::new(Duration::from_secs(60));
+    let token = tokens.issue("switch");
+    let (metrics, ids) = (Metrics::default(), connection_manager::ClientIds::default());
+    let (data_tx, _data_rx) = channels::channel(&metrics.data);
+    let (command_tx, _command_rx) = channels::channel(&metrics.command);
+    let data_dir = std::env::temp_dir().join(format!("mud-server-telnet-{}", std::process::id()));
+    Account::new("oracle").save(&data_dir).await.unwrap();
+    let comeback = tokens.issue("oracle");
+    let lab_dir = data_dir.join("lab");
+    Account::new("Smith").save(&lab_dir).await.unwrap();
+    let telnet = TelnetServer::new(ids.clone(), data_tx, command_tx, metrics.push.clone(), keyring, tokens,
+        vec!["Morpheus".to_string()], &data_dir, vec![("lab".to_string(), lab_dir)]);
+    assert_eq!(telnet.login(" neo ").await, Ok("neo".to_string()));
+    assert_eq!(telnet.login("neo+workshop").await, Ok("neo+workshop".to_string()));
+    assert!(telnet.login("morpheus").await.is_err());
+    assert!(telnet.login("Trinity+workshop").await.is_err());
+    assert!(telnet.login("").await.is_err());
+    assert_eq!(telnet.login(&token).await, Ok("switch".to_string()));
+    assert!(telnet.login(&token).await.is_err());
+    assert!(telnet.login("oracle+workshop").await.is_err());
+    assert!(telnet.login("Oracle").await.is_err());
+    assert!(telnet.login("smith+LAB").await.is_err());
+    assert_eq!(telnet.login("smith").await, Ok("smith".to_string()));
+    assert_eq!(telnet.login("oracle+lab").await, Ok("oracle+lab".to_string()));
+    assert_eq!(telnet.login(&comeback).await, Ok("oracle".to_string()));
+    assert_eq!((ids.next(), ids.clone().next()), (0, 1));
+    std::fs::remove_dir_all(&data_dir).unwrap();
+}
+
+/// Plurals and typos must resolve to the name of an asset, a noun only
+/// plausibly close must get a clarification question and unrelated nouns
+/// no match at all.
+#[test]
+fn fuzzy_nouns() {
+    assert_eq!(fuzzy::stem("Ports"), "port");
+    assert_eq!(fuzzy::stem("proxies"), "proxy");
+    assert_eq!(fuzzy::stem("switches"), "switch");
+    assert_eq!(fuzzy::stem("glass"), "glass");
+    assert_eq!(fuzzy::distance("prot", "port"), 1);
+    assert_eq!(fuzzy::distance("terminal", "termnial"), 1);
+    assert_eq!(fuzzy::distance("", "port"), 4);
+    assert_eq!(fuzzy::confidence("ports", "port"), 1.0);
+    assert_eq!(fuzzy::resolve("prot", ["terminal", "port"]), Ok(Some("port")));
+    assert_eq!(fuzzy::resolve("trmnl", ["terminal", "port"]), Err(vec!["terminal"]));
+    assert_eq!(fuzzy::resolve("sentinel", ["terminal", "port"]), Ok(None));
+    assert_eq!(fuzzy::resolve("pots", ["pod", "pit", "Pod"]), Err(vec!["pod", "pit"]));
+    assert_eq!(fuzzy::question(&["port", "pod"]), "Did you mean the port or the pod?");
+
+    let mut node = Node::new(0);
+    node.add_asset(Box::new(Port::new(1)));
+    node.add_asset(Box::new(Terminal::new(2, "ono-sendai")));
+    assert_eq!(node.find_assets("ports").len(), 1);
+    assert_eq!(node.find_asset("prot", None).unwrap().uid(), 1);
+    assert_eq!(node.find_asset("terminl", None).unwrap().uid(), 2);
+    assert_eq!(node.find_asset("prot", Some(2)).unwrap_err(), "There is no port 2 here.");
+    assert_eq!(node.find_asset("trmnl", None).unwrap_err(), "There is no trmnl here. Did you mean the terminal?");
+    assert_eq!(node.find_asset("deck", None).unwrap_err(), "There is no deck here.");
+    assert!(node.find_assets("prot").is_empty());
+
+    let mut inventory = Inventory::default();
+    assert!(inventory.carry(Box::new(Item::new(3, "quickhack")), Index::from_raw_parts(0, 0)).is_ok());
+    assert_eq!(inventory.put_down("quickhak", None).unwrap().uid(), 3);
+}
+
+/// A verb registered at runtime must parse with its own parser, take its
+/// time, reach its handler and never replace a built-in verb.
+#[test]
+fn register_verbs() {
+    let verb = Verb::new("Ping", |call| match call.arguments {
+        [] => Err("Ping whom?".to_string()),
+        args => Ok(verbs::Reply { text: format!("{} pings {}.", call.player, args.join(" and ")), node: None }),
+    })
+    .with_duration(Duration::from_millis(300));
+    assert!(matches!(Action::try_from("ping neo"), Err(Error::VerbUnknownError)));
+    assert!(verbs::register(verb).is_ok());
+    assert!(verbs::names().contains(&"ping"));
+
+    let action = Action::try_from("PING neo  trinity").unwrap();
+    assert!(matches!(&action, Action::Plugin { verb: "ping", arguments } if arguments == &["neo", "trinity"]));
+    assert_eq!(action.to_string(), "ping neo trinity");
+    assert_eq!((action.verb(), action.duration(), action.is_queued()), ("ping", Duration::from_millis(300), true));
+    let arguments = vec!["neo".to_string()];
+    let call = verbs::Call { player: "morpheus", node: None, arguments: &arguments };
+    assert_eq!(verbs::perform("ping", &call).unwrap().text, "morpheus pings neo.");
+    assert_eq!(verbs::perform("ping", &verbs::Call { arguments: &[], ..call }).unwrap_err(), "Ping whom?");
+    assert!(verbs::perform("pong", &call).is_err());
+
+    let strict = Verb::new("strict", |_| Ok(verbs::Reply::default())).with_parser(|_| Err(Error::VerbEncodingError));
+    assert!(verbs::register(strict).is_ok());
+    assert!(matches!(Action::try_from("strict"), Err(Error::VerbEncodingError)));
+    assert!(verbs::register(Verb::new("look", |_| Ok(verbs::Reply::default()))).is_err());
+    assert!(verbs::register(Verb::new("two words", |_| Ok(verbs::Reply::default()))).is_err());
+    assert!(matches!(Action::try_from("look"), Ok(Action::Look { .. })));
+}
+
+/// "shout" must take the rest of the command as text and a shout must be
+/// heard in the nodes connected by a port in either direction, but not
+/// further.
+#[test]
+fn shout_within_earshot() {
+    assert!(matches!(Action::try_from("shout run!"), Ok(Action::Shout(t)) if t == "run!"));
+    assert!(Action::try_from("shout").is_err());
+    assert!(Action::Shout("run!".to_string()).is_noisy());
+
+    let mut world = GameWorld::new("test".to_string());
+    let mut nodes = Vec::new();
+    for (uid, port) in [(0, Some(1)), (10, None), (20, Some(21)), (30, None)] {
+        let mut node = Node::new(uid);
+        if let Some(port) = port {
+            node.add_asset(Box::new(Port::new(port)));
+        }
+        nodes.push(world.add_node(node).unwrap());
+    }
+    assert!(world.connect(1, 10));
+    assert!(world.connect(21, 10));
+    let (lobby, vault, attic, closet) = (nodes[0], nodes[1], nodes[2], nodes[3]);
+    assert_eq!(world.within_earshot(lobby), vec![lobby, vault]);
+    assert_eq!(world.within_earshot(vault), vec![vault, lobby, attic]);
+    assert_eq!(world.within_earshot(attic), vec![attic, vault]);
+    assert_eq!(world.within_earshot(closet), vec![closet]);
+}
+
+/// "quit" must be a game action waiting for the pending actions, which
+/// triggers may run.
+#[test]
+fn quit_session() {
+    assert!(matches!(Action::try_from("quit"), Ok(Action::Quit)));
+    assert!(matches!(Action::try_from("please quit"), Ok(Action::Quit)));
+    assert!(Action::Quit.is_queued());
+    assert_eq!(Action::Quit.duration(), Duration::ZERO);
+    assert_eq!(Action::Quit.to_string(), "quit");
+    assert!(Trigger::new("black ice", "quit").is_ok());
+}
+
+/// Assets must list the verbs they support, actions aimed at an asset with
+/// another verb must be refused as such (not as a missing asset) and
+/// looking at an asset must list what else can be done with it.
+#[test]
+fn asset_verbs() {
+    let mut node = Node::new(0);
+    let mut port = Port::new(1);
+    port.update_puzzle(Some(Puzzle::new("hash", Puzzle::hash("2342"), "An access code.")));
+    node.add_asset(Box::new(port));
+    node.add_asset(Box::new(Terminal::new(2, "ono-sendai")));
+    node.add_asset(Box::new(Item::new(3, "quickhack")));
+    assert_eq!(node.verbs(), vec!["look"]);
+    assert_eq!(node.find_asset("port", None).unwrap().verbs(), vec!["look", "enter", "solve", "hint"]);
+    assert_eq!(node.find_asset("terminal", None).unwrap().verbs(), vec!["look", "access"]);
+    assert!(node.find_asset("quickhack", None).unwrap().supports("take"));
+
+    let refusal = |command: &str| node.refusal(&Action::try_from(command).unwrap());
+    assert_eq!(refusal("access the port").unwrap(), "You cannot access the port.");
+    assert_eq!(refusal("connect to the port").unwrap(), "You cannot connect to the port.");
+    assert_eq!(refusal("hint the terminal").unwrap(), "You cannot get a hint for the terminal.");
+    assert_eq!(refusal("take the terminal").unwrap(), "You cannot take the terminal.");
+    assert_eq!(refusal("solve the port 2342"), None);
+    assert_eq!(refusal("take the quickhack"), None);
+    assert_eq!(refusal("access the mainframe"), None);
+    assert_eq!(refusal("look"), None);
+
+    let puzzles = std::collections::BTreeMap::new();
+    let actor = Actor::new("neo", &puzzles);
+    let response = node.react_to(&actor, &Action::try_from("look at the port").unwrap());
+    assert!(response.text.ends_with("\r\nTry: enter, solve, hint."));
+    let response = node.react_to(&actor, &Action::try_from("look at the terminal").unwrap());
+    assert!(response.text.ends_with("\r\nTry: access."));
+}
+
+/// A delayed action must report its progress until it completes, complete
+/// at the end of its duration and be cancelled on request.
+#[test]
+fn delay_actions() {
+    assert!(Action::Scan.is_delayed());
+    assert!(!Action::Cloak(true).is_delayed());
+
+    let start = tokio::time::Instant::now();
+    let mut delays = Delays::default();
+    delays.start(1, Action::Scan, None, false, start);
+    delays.start(2, Action::Scan, None, true, start);
+    assert_eq!(delays.pending(1).unwrap().action.verb(), "scan");
+    assert!(delays.pending(3).is_none());
+    assert_eq!(delays.progress(start), vec![
+        (1, "Your scanner sweeps the node... (2s left)".to_string()),
+        (2, "Your scanner sweeps the node... (2s left)".to_string()),
+    ]);
+    assert!(delays.progress(start + Duration::from_millis(500)).is_empty());
+    assert_eq!(delays.cancel(2).unwrap().client_id, 2);
+    assert!(delays.cancel(2).is_none());
+    assert_eq!(delays.progress(start + delays::PROGRESS_EVERY).len(), 1);
+    assert!(delays.due(start + Duration::from_millis(1_500)).is_empty());
+    let due = delays.due(start + Action::Scan.duration());
+    assert_eq!(due.len(), 1);
+    assert_eq!(due[0].client_id, 1);
+    assert!(delays.pending(1).is_none());
+    assert!(delays.progress(start + Duration::from_secs(3)).is_empty());
+}
+
+/// The host key must be generated and saved on the first start, readable by
+/// the owner only, and loaded unchanged on the next start.
+#[test]
+fn persist_host_key() {
+    use std::os::unix::fs::PermissionsExt;
+    let data_dir = std::env::temp_dir().join(format!("mud-server-test-host-key-{}", std::process::id()));
+    let path = data_dir.join("keys").join("host_key.pem");
+    let generated = connection_manager::ssh_server::load_host_key(&path).unwrap();
+    assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+    let loaded = connection_manager::ssh_server::load_host_key(&path).unwrap();
+    assert_eq!(loaded.clone_public_key().public_key_base64(), generated.clone_public_key().public_key_base64());
+    std::fs::write(&path, "not a key").unwrap();
+    assert!(connection_manager::ssh_server::load_host_key(&path).is_err());
+    std::fs::remove_dir_all(&data_dir).unwrap();
+}
+
+/// An authorized_keys file must parse with options, comments and blank
+/// lines, name the first bad line, and a key assigned to a player must
+/// connect as that player only, looked up by its fingerprint. Only keys
+/// assigned or bound to a player prove its identity (eg. for the staff).
+#[tokio::test]
+async fn authorize_keys() {
+    let (staff, neo) = (KeyPair::generate_ed25519().unwrap(), KeyPair::generate_ed25519().unwrap());
+    let text = format!("# The staff\n\nssh-ed25519 {} fschuetz@ieee.org\n\
+        no-pty,player=\"neo\",command=\"echo a, b\" ssh-ed25519 {} neo's badge\n",
+        staff.public_key_base64(), neo.public_key_base64());
+    let keys = authorized_keys::parse(&text).unwrap();
+    assert_eq!(keys.len(), 2);
+    assert_eq!((keys[0].algorithm.as_str(), keys[0].comment.as_deref(), keys[0].player.as_deref()),
+        ("ssh-ed25519", Some("fschuetz@ieee.org"), None));
+    assert_eq!((keys[1].comment.as_deref(), keys[1].player.as_deref()), (Some("neo's badge"), Some("neo")));
+    assert_eq!(keys[1].fingerprint(), format!("SHA256:{}", neo.clone_public_key().fingerprint()));
+    assert_eq!(AuthorizedKey::parse(&keys[1].to_string()).unwrap().unwrap(), AuthorizedKey {
+        comment: Some("neo's badge".to_string()), ..keys[1].clone() });
+    assert_eq!(authorized_keys::parse("ssh-ed25519 AAAA\n").unwrap_err(), "Line 1: The key is no valid public key.");
+    assert!(authorized_keys::parse(&format!("# ok\nssh-dss {}", neo.public_key_base64())).unwrap_err()
+        .starts_with("Line 2: "));
+    assert!(authorized_keys::load(std::path::Path::new("/nonexistent/authorized_keys")).unwrap().is_empty());
+
+    let keyring = Keyring::new(Vec::new());
+    keyring.authorize(&keys);
+    assert!(keyring.allows(&staff.public_key_base64(), "trinity"));
+    assert!(keyring.allows(&neo.public_key_base64(), "NEO"));
+    assert!(!keyring.allows(&neo.public_key_base64(), "trinity"));
+    assert_eq!(keyring.owner(&keys[1].fingerprint()).as_deref(), Some("neo"));
+    assert_eq!(keyring.owner(&keys[0].fingerprint()), None);
+    assert!(keyring.binds("neo"));
+    assert!(!keyring.binds("trinity"));
+    assert!(keyring.verifies(&neo.public_key_base64(), "Neo"));
+    assert!(!keyring.verifies(&staff.public_key_base64(), "trinity"));
+    let bound = KeyPair::generate_ed25519().unwrap();
+    keyring.bind(&bound.public_key_base64(), "trinity").await.unwrap();
+    assert!(keyring.verifies(&bound.public_key_base64(), "trinity"));
+    assert!(!keyring.verifies(&bound.public_key_base64(), "neo"));
+}
+
+/// A timer must fire once it is due, not be started twice for the same
+/// effect, and pending timers must survive a restart unless cancelled.
+#[tokio::test]
+async fn schedule_timers() {
+    let data_dir = std::env::temp_dir().join(format!("mud-server-test-timers-{}", std::process::id()));
+    let timers = Timers::load(&data_dir).await.unwrap();
+    let close = timers.start(Duration::from_secs(30), Effect::Close { port: 3 }, 1000);
+    assert_eq!(timers.start(Duration::from_secs(60), Effect::Close { port: 3 }, 1010), close);
+    let reset = timers.start(Duration::from_secs(10), Effect::Reset { zone: "Lobby".to_string() }, 1000);
+    let announce = timers.start(Duration::from_secs(90),
+        Effect::Announce { zone: "Lobby".to_string(), text: "The alarm stops.".to_string() }, 1000);
+    assert_eq!(timers.pending().iter().map(|t| t.id).collect::<Vec<u64>>(), vec![reset, close, announce]);
+    timers.save(&data_dir).await.unwrap();
+
+    let timers = Timers::load(&data_dir).await.unwrap();
+    assert_eq!(timers.pending().len(), 3);
+    assert!(timers.due(1009).is_empty());
+    assert_eq!(AdminCommand::try_from("@timers cancel 3").unwrap(), AdminCommand::Timers(Some(3)));
+    assert_eq!(AdminCommand::Timers(None).to_string(), "@timers");
+    assert!(AdminCommand::try_from("@timers stop 3").is_err());
+    let due = timers.due(1030);
+    assert_eq!(due.iter().map(|t| &t.effect).collect::<Vec<&Effect>>(),
+        vec![&Effect::Close { port: 3 }, &Effect::Reset { zone: "Lobby".to_string() }]);
+    assert_eq!(timers.cancel(announce).map(|t| t.due), Some(1090));
+    assert_eq!(timers.cancel(announce), None);
+    timers.save(&data_dir).await.unwrap();
+    assert!(Timers::load(&data_dir).await.unwrap().pending().is_empty());
+    std::fs::remove_dir_all(&data_dir).unwrap();
+}
+
+/// A noise must be heard clearly in its node, muffled on the way and faint
+/// at its range over ports in either direction, and hostile NPCs hearing a
+/// player must turn on it.
+#[test]
+fn propagate_noise() {
+    let mut world = GameWorld::new("test".to_string());
+    let mut nodes = Vec::new();
+    for uid in [0, 10, 20, 30, 40] {
+        let mut node = Node::new(uid);
+        node.add_asset(Box::new(Port::new(uid + 1)));
+        nodes.push(world.add_node(node).unwrap());
+    }
+    // The lobby leads to the hall, the vault and the attic lead back to it
+    assert!(world.connect(1, 10) && world.connect(21, 10) && world.connect(31, 20) && world.connect(41, 30));
+    let (lobby, hall, vault, attic, closet) = (nodes[0], nodes[1], nodes[2], nodes[3], nodes[4]);
+    assert_eq!(world.propagate(lobby, Noise::Shout), vec![(lobby, Level::Clear), (hall, Level::Muffled),
+        (vault, Level::Faint)]);
+    assert_eq!(world.propagate(lobby, Noise::Alarm), vec![(lobby, Level::Clear), (hall, Level::Muffled),
+        (vault, Level::Muffled), (attic, Level::Faint)]);
+    assert_eq!(world.propagate(closet, Noise::Shout), vec![(closet, Level::Clear), (attic, Level::Muffled),
+        (vault, Level::Faint)]);
+
+    let start = tokio::time::Instant::now();
+    let mut npcs = Npcs::default();
+    npcs.add_spawner(Spawner::new(vault, Prototype::new("sentinel", "Black ICE.").hostile(), 1));
+    npcs.tick(|n| n == vault, |_| true, start);
+    let sentinel = npcs.find(vault, "sentinel", None).unwrap().uid;
+    let players = vec![("neo".to_string(), lobby)];
+    let heard = world.propagate(lobby, Noise::Shout).into_iter().map(|(n, _)| (n, "neo".to_string())).collect();
+    let view = View { players, heard, ..View::default() };
+    assert_eq!(npcs.think(&view, start), vec![(sentinel, Act::Trace("neo".to_string()))]);
+}
+
+/// The line editor must apply backspace, delete, the arrow keys, ctrl-w and
+/// ctrl-u at the cursor, complete lines on CR or LF, and render the edits
+/// for the echo.
+#[test]
+fn edit_lines() {
+    let mut editor = LineEditor::default();
+    let line = |editor: &mut LineEditor, input: &[u8]| editor.feed(input).lines;
+    assert_eq!(line(&mut editor, b"lookx\x7f\r\n"), vec![b"look".to_vec()]);
+    assert!(editor.is_empty());
+    assert_eq!(line(&mut editor, b"ac\x1b[Db\x1b[C\x08\x1bOHx\x1b[3~\x05d\r"), vec![b"xbd".to_vec()]);
+    assert_eq!(line(&mut editor, b"access the terminal\x17\x17ssh\n"), vec![b"access ssh".to_vec()]);
+    assert_eq!(line(&mut editor, b"say hello\x1b[D\x1b[D\x15\x1b[Ato\x1b[4~\x7f"), Vec::<Vec<u8>>::new());
+    assert_eq!(line(&mut editor, b"\r"), vec![b"tol".to_vec()]);
+    // Cursor movement counts characters, not bytes
+    assert_eq!(line(&mut editor, "caf\u{e9}\x1b[Dx\r".as_bytes()), vec!["cafx\u{e9}".as_bytes().to_vec()]);
+    assert_eq!(line(&mut editor, &vec![b'x'; MAX_LINE + 10]), Vec::<Vec<u8>>::new());
+    assert_eq!(line(&mut editor, b"\r")[0].len(), MAX_LINE);
+    // An overlong sequence is dropped, what follows is typed
+    let overlong = [b"\x1b[".to_vec(), vec![b'1'; MAX_CSI + 3], b"D\r".to_vec()].concat();
+    assert_eq!(line(&mut editor, &overlong), vec![b"11D".to_vec()]);
+
+    assert_eq!(editor.feed(b"ac").echo, b"ac".to_vec());
+    assert_eq!(editor.feed(b"\x1b[D").echo, b"\x1b[1D".to_vec());
+    assert_eq!(editor.feed(b"b").echo, b"bc\x1b[1D".to_vec());
+    assert_eq!(editor.feed(b"\x7f").echo, b"\x1b[1Dc \x1b[2D".to_vec());
+    assert_eq!(editor.feed(b"\x15").echo, b"\x1b[1Dc \x1b[2D".to_vec());
+    assert_eq!(editor.feed(b"\r").echo, b"\r\n".to_vec());
+}
+
+/// The up and down arrows must recall the earlier and later commands of the
+/// history, back to the line typed, and the history must keep the given
+/// number of commands without empty lines and repeats.
+#[test]
+fn recall_history() {
+    let mut editor = LineEditor::new(3);
+    for command in ["look", "", "who", "who", "say hi", "scan"] {
+        editor.feed(format!("{}\r", command).as_bytes());
+    }
+    let line = |editor: &mut LineEditor, input: &[u8]| editor.feed(input).lines;
+    assert_eq!(line(&mut editor, b"\x1b[A\x1b[A\x1b[A\x1b[A\r"), vec![b"who".to_vec()]);
+    assert_eq!(line(&mut editor, b"\x1b[A\x1b[A\x1b[A\x1b[B\r"), vec![b"scan".to_vec()]);
+    assert_eq!(line(&mut editor, b"sa\x1b[A\x1b[B\x1b[Bx\r"), vec![b"sax".to_vec()]);
+    // A recalled command is edited like a typed one
+    assert_eq!(line(&mut editor, b"\x1bOA\x7f\x7fhi\r"), vec![b"shi".to_vec()]);
+    assert_eq!(editor.feed(b"abcd").echo, b"abcd".to_vec());
+    assert_eq!(editor.feed(b"\x1b[A").echo, b"\x1b[4Dshi \x1b[1D".to_vec());
+    assert_eq!(editor.feed(b"\x1b[A").echo, b"\x1b[3Dsax".to_vec());
+    assert_eq!(editor.feed(b"\x1b[B\x1b[B").lines, Vec::<Vec<u8>>::new());
+    assert_eq!(editor.feed(b"\r").lines, vec![b"abcd".to_vec()]);
+    assert!(line_editor::recalls(b"\x1b[A") && line_editor::recalls(b"\x1bOB"));
+    assert!(!line_editor::recalls(b"\x1b[C"));
+
+    let mut editor = LineEditor::new(0);
+    assert_eq!(line(&mut editor, b"look\r\x1b[A\r"), vec![b"look".to_vec(), Vec::new()]);
+}
+
+/// "look through" must keep its preposition and a glimpse of a node must
+/// show its name, the first sentence of its description and the nouns of
+/// its assets only.
+#[test]
+fn look_through_ports() {
+    assert!(matches!(Action::try_from("look through the purple port"),
+        Ok(Action::Look { target: Some(t), preposition: Some(p), .. }) if t == "port" && p == "through"));
+    let mut vault = Node::new(0);
+    vault.update_name("Vault");
+    vault.update_description("A cold room. Racks hum in the dark.");
+    let mut terminal = Terminal::new(2, "zaibatsu");
+    terminal.update_description("The terminal of the vault, guarded by ICE.");
+    vault.add_asset(Box::new(Port::new(1)));
+    vault.add_asset(Box::new(terminal));
+    assert_eq!(vault.glimpse(), "Vault: A cold room.\r\nYou make out a port, a terminal.");
+    let mut closet = Node::new(3);
+    closet.update_description("Dust everywhere");
+    assert_eq!(closet.glimpse(), "Dust everywhere");
+}
+
+/// Items must tell who they belong to and offer to be stolen only if they
+/// are lootable, a theft must wear the cloak down and succeed by the roll,
+/// and hostile NPCs must trace a thief caught in their node.
+#[test]
+fn steal_items() {
+    assert!(matches!(Action::try_from("steal the credstick 2"),
+        Ok(Action::Steal { target, ordinal: Some(2) }) if target == "credstick"));
+    let mut credstick = Item::new(1, "credstick");
+    credstick.update_description("A credstick.");
+    credstick.update_ownership(Some(Ownership { owner: "razor".to_string(), lootable: false }));
+    assert_eq!(credstick.describe(), "A credstick. The credstick belongs to razor.");
+    assert!(!credstick.supports("steal"));
+    credstick.update_ownership(Some(Ownership { owner: "razor".to_string(), lootable: true }));
+    assert!(credstick.supports("steal"));
+    assert!(credstick.ownership().is_some_and(|o| o.is_owner("Razor") && !o.is_owner("neo")));
+    let lobby = Index::from_raw_parts(0, 0);
+    let mut inventory = Inventory::default();
+    assert!(inventory.carry(Box::new(credstick), lobby).is_ok());
+    assert_eq!(inventory.ownership("credsticks").map(|o| o.owner.as_str()), Some("razor"));
+
+    // A fresh cloak keeps 75 after the theft, then 50, 25 and nothing
+    let mut cloak = Cloak::new();
+    assert!(ownership::steal(&mut cloak, 74));
+    assert!(!ownership::steal(&mut cloak, 50));
+    assert_eq!(cloak.strength(), 50);
+    assert!(ownership::steal(&mut cloak, 0));
+    assert!(!ownership::steal(&mut cloak, 0));
+
+    let start = tokio::time::Instant::now();
+    let mut npcs = Npcs::default();
+    npcs.add_spawner(Spawner::new(lobby, Prototype::new("sentinel", "Black ICE.").hostile(), 1));
+    npcs.tick(|n| n == lobby, |_| true, start);
+    let sentinel = npcs.find(lobby, "sentinel", None).unwrap().uid;
+    let view = View { players: vec![("neo".to_string(), lobby)], caught: vec![(lobby, "neo".to_string())],
+        ..View::default() };
+    assert_eq!(npcs.think(&view, start), vec![(sentinel, Act::Trace("neo".to_string()))]);
+}
+
+/// Looking at an asset by its adjectives must pick the asset having all of
+/// the properties, and ask which one is meant if more than one does.
+#[test]
+fn look_at_described_assets() {
+    let puzzles = std::collections::BTreeMap::new();
+    let actor = Actor::new("neo", &puzzles);
+    let look = |node: &mut Node, sentence: &str| node.react_to(&actor, &Action::try_from(sentence).unwrap()).text;
+    let mut node = Node::new(0);
+    for (uid, color, description) in [(1, Color::Purple, "A purple port."), (2, Color::Red, "A red port.")] {
+        let mut port = Port::new(uid);
+        port.update_description(description);
+        port.add_property(Property::Color(color));
+        port.add_property(Property::from("shiny"));
+        node.add_asset(Box::new(port));
+    }
+    assert!(look(&mut node, "look at the purple port").starts_with("A purple port. The port is closed."));
+    assert!(look(&mut node, "look at the shiny, red port").starts_with("A red port."));
+    assert!(look(&mut node, "look at the shiny port").starts_with("There is more than one shiny port here."));
+    assert!(look(&mut node, "look at the shiny port 2").starts_with("A red port."));
+    assert_eq!(look(&mut node, "look at the green port"), "There is no green port here.");
+    assert!(look(&mut node, "look at the red prot").starts_with("A red port."));
+    let port = node.find_asset("port", Some(1)).unwrap();
+    assert!(port.has_property(&Property::from("Purple")) && !port.has_property(&Property::from("dull")));
+}
+
+/// Crimes must heat the offender up only in secured zones, dispatch the ICE
+/// of security once at the dispatch heat, cool down over time or be bought
+/// off, and guards must trace players with heat in their node.
+#[test]
+fn respond_to_crimes() {
+    let start = tokio::time::Instant::now();
+    let mut security = Security::default();
+    assert_eq!(security.commit("neo", Crime::Theft, 0, start), None);
+    assert_eq!(security.commit("neo", Crime::Cracking, 1, start), Some(Response { heat: 10, dispatch: false }));
+    let enforcer = Prototype::new("enforcer", "Corporate security.").hostile();
+    security.set_responder(enforcer.clone());
+    assert_eq!(security.commit("neo", Crime::Theft, 1, start), Some(Response { heat: 30, dispatch: true }));
+    security.dispatched("neo", 7);
+    assert_eq!(security.commit("neo", Crime::Cracking, 1, start), Some(Response { heat: 40, dispatch: false }));
+    assert_eq!(security.wanted(start), vec!["neo".to_string()]);
+    assert_eq!(security.heat("neo", start + Duration::from_secs(60)), 30);
+    assert_eq!(security.bribe("neo", start), Some(40 * BRIBE_PER_HEAT));
+    assert_eq!(security.bribe("trinity", start), None);
+    assert!(security.cool_down(start + Duration::from_secs(60)).is_empty());
+    assert_eq!(security.cool_down(start + Duration::from_secs(240)), vec![("neo".to_string(), vec![7])]);
+    assert!(security.wanted(start).is_empty());
+    security.commit("trinity", Crime::Theft, 2, start);
+    assert_eq!(security.clear("trinity"), Some(Vec::new()));
+
+    let lobby = Index::from_raw_parts(0, 0);
+    let mut npcs = Npcs::default();
+    npcs.add_spawner(Spawner::new(lobby, Prototype::new("sentinel", "Black ICE.").hostile()
+        .with_behavior(Behavior::Guard(1)), 1));
+    npcs.tick(|n| n == lobby, |_| true, start);
+    let sentinel = npcs.find(lobby, "sentinel", None).unwrap().uid;
+    let players = vec![("neo".to_string(), lobby)];
+    assert!(npcs.think(&View { players: players.clone(), ..View::default() }, start).is_empty());
+    let view = View { players, wanted: vec!["neo".to_string()], ..View::default() };
+    assert_eq!(npcs.think(&view, start), vec![(sentinel, Act::Trace("neo".to_string()))]);
+    let responder = npcs.dispatch(&enforcer, lobby, "neo");
+    assert_eq!((responder.name(), responder.target()), ("enforcer", Some("neo")));
+}
+
+/// "help" lists the topics, "help <topic> [page]" shows a topic in pages
+#[test]
+fn read_help() {
+    assert!(matches!(Action::try_from("help"), Ok(Action::Help { topic: None, page: None })));
+    assert!(matches!(Action::try_from("help Combat 2"),
+        Ok(Action::Help { topic: Some(topic), page: Some(2) }) if topic == "combat"));
+    assert!(matches!(Action::try_from("help combat"),
+        Ok(Action::Help { topic: Some(topic), page: None }) if topic == "combat"));
+    assert!(!Action::Help { topic: None, page: None }.is_queued());
+
+    let mut help = Help::default();
+    assert_eq!(help.index(), "There are no help topics. Ask the staff.");
+    let verbs: Vec<String> = (1..=PAGE_LINES + 5).map(|n| format!("verb {}", n)).collect();
+    help.add_topic("Verbs", &verbs.join("\n"));
+    help.add_topic("combat", "Combat\n  hack <npc>\n");
+    help.add_topic("inventory", "Inventory\n");
+    assert_eq!(help.index(), "Help topics: combat, inventory, verbs.\r\n\
+        Type \"help <topic>\" to read one (eg. \"help combat\").");
+    assert_eq!(help.page("combat", 1), Ok("Combat\r\n  hack <npc>".to_string()));
+    assert_eq!(help.page("combt", 1), Ok("Combat\r\n  hack <npc>".to_string()));
+    let first = help.page("verbs", 1).unwrap();
+    assert_eq!(first.lines().count(), PAGE_LINES + 1);
+    assert!(first.ends_with("-- Page 1 of 2, \"help verbs 2\" shows the next --"));
+    assert_eq!(help.page("verbs", 2).unwrap(), "verb 21\r\nverb 22\r\nverb 23\r\nverb 24\r\nverb 25\r\n\
+        -- Page 2 of 2 --");
+    assert_eq!(help.page("verbs", 3), Err("The help on verbs has 2 pages.".to_string()));
+    assert_eq!(help.page("hacking", 1),
+        Err("There is no help on hacking. Type \"help\" for the topics.".to_string()));
+}
+
+/// ICE must scale with the number and the level of the deckers in its node,
+/// keeping the share of its integrity left and jamming decks for longer
+#[test]
+fn scale_ice() {
+    assert_eq!(difficulty::scale(&[]), BASE_SCALE);
+    assert_eq!(difficulty::scale(&[1]), BASE_SCALE);
+    assert_eq!(difficulty::scale(&[1, 1]), 150);
+    assert_eq!(difficulty::scale(&[3, 5]), 180);
+    assert_eq!(difficulty::scale(&[50; 6]), MAX_SCALE);
+
+    let mut arena = generational_arena::Arena::new();
+    let (lobby, vault) = (arena.insert(()), arena.insert(()));
+    let start = tokio::time::Instant::now();
+    let mut npcs = Npcs::default();
+    npcs.add_spawner(Spawner::new(lobby, Prototype::new("sentinel", "Black ICE.").hostile().with_integrity(20)
+        .with_behavior(Behavior::Strike { interval: Duration::from_secs(5), jam: Duration::from_secs(2) }), 1));
+    npcs.add_spawner(Spawner::new(lobby, Prototype::new("daemon", "A helpful daemon."), 1));
+    npcs.tick(|n| n == lobby, |_| true, start);
+    let sentinel = npcs.find(lobby, "sentinel", None).unwrap().uid;
+    assert_eq!(npcs.hack(sentinel, "neo", 10, start), Some(10));
+    npcs.scale(|n| Some(difficulty::scale(if n == lobby { &[1, 1, 1] } else { &[1] })));
+    let npc = npcs.get(sentinel).unwrap();
+    assert_eq!((npc.scale(), npc.max_integrity()), (200, 40));
+    assert_eq!(npcs.find(lobby, "daemon", None).unwrap().scale(), BASE_SCALE);
+    npcs.scale(|n| if n == vault { Some(BASE_SCALE) } else { None });
+    assert_eq!(npcs.get(sentinel).unwrap().scale(), 200);
+    let view = View { players: vec![("neo".to_string(), lobby)], ..View::default() };
+    npcs.think(&view, start);
+    assert!(npcs.taunt(sentinel, "neo"));
+    let acts = npcs.think(&view, start + Duration::from_secs(5));
+    assert!(acts.contains(&(sentinel, Act::Strike { player: "neo".to_string(), jam: Duration::from_secs(4) })));
+    assert_eq!(npcs.hack(sentinel, "neo", 15, start), Some(5));
+}
+
+/// A checkpoint must survive a restart and bring back the descriptions,
+/// ACLs, open ports and item locations of the world built, and the nodes
+/// the players were in
+#[tokio::test]
+async fn checkpoint_world() {
+    let build = || {
+        let mut nodes = generational_arena::Arena::new();
+        let mut lobby = Node::new(0);
+        lobby.update_description("Around you its dark.");
+        lobby.add_asset(Box::new(Port::new(1)));
+        lobby.add_asset(Box::new(Item::new(2, "quickhack")));
+        let mut vault = Node::new(3);
+        vault.add_asset(Box::new(Item::new(4, "credstick")));
+        (nodes.insert(lobby), nodes.insert(vault), nodes)
+    };
+    let (lobby, vault, mut nodes) = build();
+    nodes[lobby].rewrite("Neon hums.");
+    nodes[lobby].asset_mut(1).and_then(|a| a.port_mut()).unwrap().update_open(true);
+    let quickhack = nodes[lobby].take_asset(2).unwrap();
+    nodes[vault].add_asset(quickhack);
+    nodes[vault].update_acl(Some(Acl::try_from("role=builder").unwrap()));
+    let mut inventory = Inventory::default();
+    assert!(inventory.carry(nodes[vault].take_asset(4).unwrap(), vault).is_ok());
+    let checkpoint = Checkpoint::take(&nodes, inventory.items(), vec![("Neo", vault)].into_iter(), 1000);
+    assert_eq!(checkpoint.players.get("neo"), Some(&3));
+
+    let data_dir = std::env::temp_dir().join(format!("mud-server-test-checkpoint-{}", std::process::id()));
+    assert_eq!(Checkpoint::load(&data_dir).await.unwrap(), None);
+    checkpoint.save(&data_dir).unwrap();
+    let loaded = Checkpoint::load(&data_dir).await.unwrap().unwrap();
+    assert_eq!(loaded, checkpoint);
+    std::fs::remove_dir_all(&data_dir).unwrap();
+
+    let (lobby, vault, mut nodes) = build();
+    assert_eq!(loaded.restore(&mut nodes), 5);
+    assert_eq!(nodes[lobby].description(), "Neon hums.");
+    assert!(nodes[lobby].assets().find_map(|a| a.port()).unwrap().is_open());
+    assert_eq!(nodes[lobby].assets().count(), 1);
+    let mut items: Vec<AssetID> = nodes[vault].assets().map(|a| a.uid()).collect();
+    items.sort_unstable();
+    assert_eq!(items, vec![2, 4]);
+    assert_eq!(nodes[vault].acl().map(|a| a.to_string()), Some("role=builder".to_string()));
+}
+
+/// A boss must change phases as its integrity drops, land its telegraphed
+/// attacks on the players not countering them and share its loot between
+/// the deckers that hacked it
+#[test]
+fn fight_bosses() {
+    let sweep = Telegraph { name: "purge sweep".to_string(), counter: "cloak".to_string(),
+        warning: Duration::from_secs(5), interval: Duration::from_secs(10), jam: Duration::from_secs(8) };
+    let strike = Behavior::Strike { interval: Duration::from_secs(5), jam: Duration::from_secs(2) };
+    let mut bosses = Bosses::default();
+    bosses.add(Boss::new("warden", Phase { below: 100, announce: "It wakes.".to_string(), behaviors: vec![],
+            telegraph: None })
+        .with_phase(Phase { below: 50, announce: "It rages.".to_string(), behaviors: vec![strike.clone()],
+            telegraph: Some(sweep.clone()) })
+        .with_loot(Hook::Credits(100))
+        .with_loot(Hook::Item("warden core".to_string()))
+        .with_loot(Hook::Flag("achievement/warden".to_string())));
+    let start = tokio::time::Instant::now();
+    assert!(bosses.engage(1, "sentinel", start).is_none());
+    assert_eq!(bosses.engage(2, "Warden", start).map(|p| p.announce.as_str()), Some("It wakes."));
+    assert!(bosses.tick(start + Duration::from_secs(60)).is_empty());
+
+    assert!(bosses.hit(2, "neo", 40, 60, 100, start).is_none());
+    assert_eq!(bosses.hit(2, "trinity", 20, 40, 100, start).map(|p| p.behaviors.clone()), Some(vec![strike]));
+    assert!(bosses.hit(2, "neo", 10, 30, 100, start).is_none());
+    assert!(bosses.tick(start + Duration::from_secs(9)).is_empty());
+    assert_eq!(bosses.tick(start + Duration::from_secs(10)), vec![(2, Blow::Warn(sweep.clone()))]);
+    bosses.counter(vec![1, 2].into_iter(), "neo", "scan");
+    bosses.counter(vec![2].into_iter(), "trinity", "cloak");
+    assert!(bosses.tick(start + Duration::from_secs(14)).is_empty());
+    let countered = vec!["trinity".to_string()].into_iter().collect();
+    assert_eq!(bosses.tick(start + Duration::from_secs(15)), vec![(2, Blow::Land { telegraph: sweep, countered })]);
+    assert!(bosses.tick(start + Duration::from_secs(24)).is_empty());
+    assert_eq!(bosses.tick(start + Duration::from_secs(25)).len(), 1);
+
+    // Items go to the top damage dealer first, credits split evenly
+    let loot = bosses.defeat(2).unwrap();
+    assert_eq!(loot, vec![
+        ("neo".to_string(), Hook::Credits(50)),
+        ("trinity".to_string(), Hook::Credits(50)),
+        ("neo".to_string(), Hook::Item("warden core".to_string())),
+        ("neo".to_string(), Hook::Flag("achievement/warden".to_string())),
+        ("trinity".to_string(), Hook::Flag("achievement/warden".to_string())),
+    ]);
+    assert!(bosses.defeat(2).is_none());
+
+    // Split by damage, the remainder goes to the top damage dealer
+    let damage = [("neo".to_string(), 10), ("trinity".to_string(), 20), ("morpheus".to_string(), 0)];
+    let loot = [Hook::Credits(100), Hook::Item("a".to_string()), Hook::Item("b".to_string())];
+    assert_eq!(bosses::share(&loot, &damage, Share::Damage), vec![
+        ("trinity".to_string(), Hook::Credits(67)),
+        ("neo".to_string(), Hook::Credits(33)),
+        ("trinity".to_string(), Hook::Item("a".to_string())),
+        ("neo".to_string(), Hook::Item("b".to_string())),
+    ]);
+    assert!(bosses::share(&loot, &[], Share::Even).is_empty());
+}
+
+/// ICE must take damage and trace the deckers back on every exchange, purge
+/// the intrusion of a decker traced fully and stay broken until it reboots
+#[test]
+fn break_ice() {
+    assert!(matches!(Action::try_from("attack the ice"),
+        Ok(Action::Attack { target, ordinal: None }) if target == "ice"));
+    assert!(matches!(Action::try_from("crack ice 2"),
+        Ok(Action::Crack { target, ordinal: Some(2) }) if target == "ice"));
+    let crack = Action::try_from("crack ice").unwrap();
+    assert!(crack.is_combat() && !crack.is_noisy());
+    assert!(Action::try_from("attack ice").unwrap().is_noisy());
+
+    let mut ice = Ice::new(3, 2);
+    ice.update_strength(30);
+    ice.update_trace(40);
+    let mut node = Node::new(0);
+    node.add_asset(Box::new(Port::new(2)));
+    node.add_asset(Box::new(ice.clone()));
+    assert_eq!(node.find_asset("ice", None).unwrap().ice().map(|i| i.protects()), Some(2));
+    assert_eq!(node.refusal(&Action::try_from("attack port").unwrap()),
+        Some("You cannot attack the port.".to_string()));
+    assert!(node.refusal(&crack).is_none());
+
+    let start = tokio::time::Instant::now();
+    let mut intrusions = Intrusions::default();
+    assert_eq!(intrusions.exchange(&ice, "neo", Move::Crack, start),
+        Exchange::Held { damage: ice::CRACK_DAMAGE, integrity: 25, max: 30, trace: 20 });
+    assert_eq!(intrusions.exchange(&ice, "neo", Move::Attack, start),
+        Exchange::Held { damage: ice::ATTACK_DAMAGE, integrity: 15, max: 30, trace: 60 });
+    assert_eq!(intrusions.exchange(&ice, "neo", Move::Attack, start),
+        Exchange::Traced { damage: ice::ATTACK_DAMAGE, jam: ice::TRACE_JAM });
+    assert_eq!(intrusions.exchange(&ice, "trinity", Move::Attack, start),
+        Exchange::Held { damage: ice::ATTACK_DAMAGE, integrity: 20, max: 30, trace: 40 });
+    assert_eq!(intrusions.exchange(&ice, "neo", Move::Attack, start),
+        Exchange::Held { damage: ice::ATTACK_DAMAGE, integrity: 10, max: 30, trace: 40 });
+    assert!(!intrusions.is_broken(3));
+    assert_eq!(intrusions.exchange(&ice, "trinity", Move::Attack, start), Exchange::Broken { damage: 10 });
+    assert!(intrusions.is_broken(3));
+    assert_eq!(intrusions.exchange(&ice, "neo", Move::Crack, start), Exchange::Down);
+    assert!(intrusions.reboot(start + ice::REBOOT - Duration::from_secs(1)).is_empty());
+    assert_eq!(intrusions.reboot(start + ice::REBOOT), vec![3]);
+    assert!(!intrusions.is_broken(3));
+
+    let toml = r#"
+        [[nodes]]
+        uid = 0
+        name = "Vault"
+        spawn = true
+
+        [[nodes.ports]]
+        uid = 1
+
+        [[nodes.ice]]
+        uid = 2
+        protects = 1
+        strength = 50
+    "#;
+    let mut world_file = WorldFile::parse(std::path::Path::new("world.toml"), toml.as_bytes()).unwrap();
+    assert_eq!(world_file.max_uid(), 2);
+    let nodes = world_file.build().unwrap();
+    assert_eq!(nodes[0].0.find_asset("ice", None).unwrap().ice().map(|i| i.protects()), Some(1));
+    world_file.nodes[0].ice[0].protects = 0;
+    assert_eq!(world_file.build().unwrap_err(), "ICE 2 protects port 0, which is not in its node.");
+}
+
+/// Goals must count the deeds of every player, set their flag once reached
+/// and keep their progress in the data directory
+#[tokio::test]
+async fn reach_goals() {
+    assert!(matches!(Action::try_from("goals"), Ok(Action::Goals)));
+    let mut goals = Goals::default();
+    assert_eq!(goals.describe(), "There are no goals. The grid is quiet.");
+    goals.add(goals::Goal::new("subnet-7-liberated", "Destroy the warden", Deed::Destroy("warden".to_string()), 2)
+        .announcing("Subnet-7 is liberated!")
+        .with_change(goals::Change::Open { port: 4 }));
+    goals.add(goals::Goal::new("hatch-broken", "Break the ICE on the hatch", Deed::Break(5), 1));
+
+    assert!(goals.record(&Deed::Destroy("sentinel".to_string())).is_none());
+    assert!(goals.record(&Deed::Destroy("warden".to_string())).unwrap().is_empty());
+    assert_eq!(goals.describe(), "Goals of the community:\r\n  Destroy the warden (1/2)\r\n  \
+        Break the ICE on the hatch (0/1)");
+    let reached = goals.record(&Deed::Destroy("warden".to_string())).unwrap();
+    assert_eq!(reached.iter().map(|g| g.flag()).collect::<Vec<_>>(), vec!["subnet-7-liberated"]);
+    assert_eq!(reached[0].announce(), Some("Subnet-7 is liberated!"));
+    assert_eq!(reached[0].changes(), &[goals::Change::Open { port: 4 }]);
+    // Flags are set only once
+    assert!(goals.record(&Deed::Destroy("warden".to_string())).is_none());
+    assert_eq!(goals.reached().count(), 1);
+
+    let data_dir = std::env::temp_dir().join(format!("mud-server-goals-{}", std::process::id()));
+    goals.save(&data_dir).await.unwrap();
+    let mut loaded = Goals::load(&data_dir).await.unwrap();
+    assert_eq!(loaded.reached().count(), 0);
+    loaded.add(goals::Goal::new("subnet-7-liberated", "Destroy the warden", Deed::Destroy("warden".to_string()), 2));
+    assert_eq!(loaded.reached().map(|g| g.flag()).collect::<Vec<_>>(), vec!["subnet-7-liberated"]);
+    std::fs::remove_dir_all(&data_dir).unwrap();
+    assert_eq!(Goals::load(&data_dir).await.unwrap().describe(), "There are no goals. The grid is quiet.");
+
+    let mut arena = generational_arena::Arena::new();
+    let lobby = arena.insert(());
+    let mut npcs = Npcs::default();
+    npcs.add_spawner(Spawner::new(lobby, Prototype::new("fixer", "A fixer.").with_line("Subnet-7 is lost."), 1));
+    npcs.tick(|n| n == lobby, |_| true, tokio::time::Instant::now());
+    assert_eq!(npcs.find(lobby, "fixer", None).unwrap().line(), Some("Subnet-7 is lost."));
+    npcs.set_line("FIXER", "Subnet-7 is ours.");
+    assert_eq!(npcs.find(lobby, "fixer", None).unwrap().line(), Some("Subnet-7 is ours."));
+}
+
+/// Opening a port must take the player that solved its puzzle, change the
+/// port for everybody and respond with the change, what the others see and
+/// the timer closing the port again
+#[test]
+fn open_solved_ports() {
+    assert_eq!(Action::try_from("open").unwrap().to_string(), "open");
+    let mut node = Node::new(0);
+    let mut port = Port::new(1);
+    port.update_puzzle(Some(Puzzle::new("hash", Puzzle::hash("2342"), "An access code.")));
+    node.add_asset(Box::new(port));
+    let open = Action::try_from("open").unwrap();
+
+    let mut puzzles = std::collections::BTreeMap::new();
+    assert_eq!(node.react_to(&Actor::new("neo", &puzzles), &open).text, "There is no closed port here you solved.");
+    puzzles.insert("hash".to_string(), Progress { attempts: 1, hints: 0, solved: true });
+    let mut guarded = node.clone();
+    guarded.add_asset(Box::new(Ice::new(2, 1)));
+    assert_eq!(guarded.react_to(&Actor::new("neo", &puzzles), &open).text, "The ICE on the port holds it shut.");
+
+    let response = node.react_to(&Actor::new("neo", &puzzles), &open);
+    assert!(response.text.starts_with("You open the port for everybody."));
+    assert_eq!(response.changes, vec![StateChange::Opened(1)]);
+    assert_eq!(response.events, vec!["neo opens the port.".to_string()]);
+    assert_eq!(response.effects, vec![Hook::Timer { after: assets::HOLD_OPEN, effect: Effect::Close { port: 1 } }]);
+    assert!(node.find_asset("port", None).unwrap().port().unwrap().is_open());
+    assert_eq!(node.react_to(&Actor::new("neo", &puzzles), &open).text, "There is no closed port here you solved.");
+
+    let mut port = Port::new(3);
+    port.update_puzzle(Some(Puzzle::new("other", Puzzle::hash("1"), "A code.")));
+    assert_eq!(port.react_to(&Actor::new("neo", &puzzles), &open).text, "You cannot open the port, solve it first.");
+}
+
+/// Polls must take the votes by number or text once per account and tally
+/// them, admins must create polls with at least two options
+#[test]
+fn vote_in_polls() {
+    assert!(matches!(Action::try_from("vote"), Ok(Action::Vote(None))));
+    assert!(matches!(Action::try_from("vote  2 "), Ok(Action::Vote(Some(c))) if c == "2"));
+    assert_eq!(AdminCommand::try_from("@poll create Which zone unlocks tonight? | Vault | Mainframe").unwrap(),
+        AdminCommand::Poll(PollStep::Create { question: "Which zone unlocks tonight?".to_string(),
+            options: vec!["Vault".to_string(), "Mainframe".to_string()] }));
+    assert_eq!(AdminCommand::try_from("@poll close").unwrap(), AdminCommand::Poll(PollStep::Close));
+    assert_eq!(AdminCommand::try_from("@poll").unwrap().to_string(), "@poll");
+    assert!(AdminCommand::try_from("@poll create Which zone?").is_err());
+    assert!(AdminCommand::try_from("@poll open").is_err());
+
+    assert!(Poll::new("Which zone?", vec!["Vault".to_string()]).is_none());
+    let mut poll = Poll::new("Which zone?", vec!["Vault".to_string(), "Mainframe".to_string()]).unwrap();
+    assert_eq!(poll.ballot(), "Poll: Which zone? Vote with \"vote <option>\": 1. Vault, 2. Mainframe");
+    assert_eq!(poll.vote("Neo", "2"), Ok("Mainframe"));
+    assert_eq!(poll.vote("neo", "vault"), Err("You voted already, every account has one vote.".to_string()));
+    assert!(poll.vote("trinity", "3").unwrap_err().starts_with("There is no option 3."));
+    assert_eq!(poll.vote("trinity", "MAINFRAME"), Ok("Mainframe"));
+    assert_eq!(poll.vote("morpheus", "1"), Ok("Vault"));
+    assert_eq!(poll.tally(), vec![("Vault", 1), ("Mainframe", 2)]);
+    assert_eq!(poll.turnout(), 3);
+    assert_eq!(poll.results(), "Poll: Which zone? (3 votes)\r\n  1. Vault: 1 votes\r\n  2. Mainframe: 2 votes");
+}
+
+/// The schedule must list the upcoming events, remind the subscribers of an
+/// event once before it starts and keep the events in the data directory
+#[tokio::test]
+async fn remind_of_scheduled_events() {
+    assert!(matches!(Action::try_from("schedule"), Ok(Action::Schedule(Scheduling::List))));
+    assert!(matches!(Action::try_from("schedule remind #2"), Ok(Action::Schedule(Scheduling::Remind(2)))));
+    assert_eq!(Action::try_from("schedule forget 2").unwrap().to_string(), "schedule forget 2");
+    assert!(Action::try_from("schedule remind soon").is_err());
+    assert_eq!(AdminCommand::try_from("@schedule add con 30 Keynote: Hack the planet").unwrap(),
+        AdminCommand::Schedule(ScheduleStep::Add { kind: schedule::Kind::Con, minutes: 30,
+            title: "Keynote: Hack the planet".to_string() }));
+    assert_eq!(AdminCommand::try_from("@schedule remove #3").unwrap().to_string(), "@schedule remove 3");
+    assert_eq!(AdminCommand::try_from("@schedule reload").unwrap(), AdminCommand::Schedule(ScheduleStep::Reload));
+    assert!(AdminCommand::try_from("@schedule add party 30 Afterparty").is_err());
+
+    let now = 1_000_000;
+    let mut schedule = Schedule::default();
+    assert_eq!(schedule.describe(now, "neo"), "There are no upcoming events.");
+    let raid = schedule.add(schedule::Kind::World, now + 3_600, "Raid on the mainframe");
+    let keynote = schedule.add(schedule::Kind::Con, now + 1_800, "Keynote");
+    schedule.add(schedule::Kind::Con, now - 60, "Opening");
+    assert_eq!(schedule.upcoming(now).iter().map(|e| e.id).collect::<Vec<_>>(), vec![keynote, raid]);
+
+    assert_eq!(schedule.subscribe(raid, "Neo", now).unwrap().title, "Raid on the mainframe");
+    assert!(schedule.subscribe(3, "neo", now).is_err());
+    assert!(schedule.describe(now, "neo").ends_with("#1 in 1h 0m 0s: Raid on the mainframe (world, reminder set)"));
+    assert!(schedule.due(now).is_empty());
+    let due = schedule.due(now + 3_600 - schedule::REMIND_BEFORE);
+    assert_eq!(due.iter().map(|e| e.id).collect::<Vec<_>>(), vec![raid]);
+    assert!(due[0].subscribers.contains("neo"));
+    assert!(schedule.due(now + 3_600).is_empty());
+    assert!(schedule.subscribe(raid, "trinity", now + 3_500).is_err());
+    assert!(schedule.unsubscribe(keynote, "neo").is_none());
+
+    let data_dir = std::env::temp_dir().join(format!("mud-server-schedule-{}", std::process::id()));
+    assert!(Schedule::load(&data_dir).await.unwrap().upcoming(0).is_empty());
+    schedule.save(&data_dir).await.unwrap();
+    let mut loaded = Schedule::load(&data_dir).await.unwrap();
+    std::fs::remove_dir_all(&data_dir).unwrap();
+    assert_eq!(loaded.upcoming(0).len(), 3);
+    assert_eq!(loaded.unsubscribe(raid, "NEO").unwrap().title, "Raid on the mainframe");
+    assert_eq!(loaded.remove(keynote).unwrap().title, "Keynote");
+    assert!(loaded.remove(keynote).is_none());
+}
+
+/// Read what the world pushes to a client until it says the given text
+async fn read_until(push_rx: &mut channels::MeteredReceiver<Push>, text: &str) -> String {
+    let mut received = String::new();
+    while !received.contains(text) {
+        match tokio::time::timeout(Duration::from_secs(5), push_rx.recv()).await {
+            Ok(Some(Push::Data(data))) => received.push_str(&String::from_utf8_lossy(&data)),
+            Ok(Some(_)) => (),
+            _ => panic!("The world did not say {:?}, only: {}", text, received),
+        }
+    }
+    received
+}
+
+/// The world must serve a client over a plain push channel, without a
+/// server: it must perform the commands of the client, and grant the role
+/// of a handle only to a client that proved the handle.
+#[tokio::test]
+async fn serve_clients_without_server() {
+    let data_dir = std::env::temp_dir().join(format!("mud-server-clients-{}", std::process::id()));
+    let mut world = GameWorld::new("test".to_string());
+    world.set_data_dir(data_dir.clone());
+    let mut lobby = Node::new(0);
+    lobby.update_description("A dusty lobby.");
+    world.add_spwan_node(lobby);
+    world.set_role("Morpheus", Role::Admin);
+    let metrics = Metrics::new(&Capacities { command: 8, data: 8, push: 64 });
+    let (command_tx, command_rx) = channels::channel(&metrics.command);
+    let (data_tx, data_rx) = channels::channel(&metrics.data);
+    let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
+    let running = tokio::spawn(crate::world::run(command_rx, data_rx, world, shutdown_rx));
+
+    let (push_tx, mut push_rx) = channels::channel(&metrics.push);
+    let connection: Box<dyn ClientConnection> = Box::new(PushConnection::new(
+        ClientInfo { transport: Transport::Telnet, verified: false }, push_tx));
+    command_tx.send(Command::Register(1, "morpheus".to_string(), connection)).await.unwrap();
+    assert!(read_until(&mut push_rx, "Welcome, morpheus.").await.contains("decker #1"));
+    data_tx.send(DataMessage::new(1, b"look".to_vec())).await.unwrap();
+    read_until(&mut push_rx, "A dusty lobby.").await;
+    command_tx.send(Command::Admin(DataMessage::new(1, b"@flags".to_vec()))).await.unwrap();
+    read_until(&mut push_rx, "Command not found.").await;
+    command_tx.send(Command::Hangup(1)).await.unwrap();
+
+    // Proven by its key, the handle is staff
+    let (push_tx, mut push_rx) = channels::channel(&metrics.push);
+    let connection = Box::new(PushConnection::new(ClientInfo { transport: Transport::Ssh, verified: true }, push_tx));
+    command_tx.send(Command::Register(2, "Morpheus".to_string(), connection)).await.unwrap();
+    command_tx.send(Command::Admin(DataMessage::new(2, b"@flags".to_vec()))).await.unwrap();
+    read_until(&mut push_rx, "There are no seasons.").await;
+    shutdown_tx.send(true).unwrap();
+    running.await.unwrap();
+    std::fs::remove_dir_all(&data_dir).unwrap();
+}
+
+/// A handle must play one session at a time: jacking in again must close
+/// the old session, which saves its account before the new session loads
+/// it. A session of the handle in another case must be refused unless it
+/// proved the handle.
+#[tokio::test]
+async fn replace_sessions() {
+    let data_dir = std::env::temp_dir().join(format!("mud-server-sessions-{}", std::process::id()));
+    let mut world = GameWorld::new("test".to_string());
+    world.set_data_dir(data_dir.clone());
+    world.add_spwan_node(Node::new(0));
+    let metrics = Metrics::new(&Capacities { command: 8, data: 8, push: 64 });
+    let (command_tx, command_rx) = channels::channel(&metrics.command);
+    let (_data_tx, data_rx) = channels::channel(&metrics.data);
+    let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
+    let running = tokio::spawn(crate::world::run(command_rx, data_rx, world, shutdown_rx));
+
+    let (old_tx, mut old_rx) = channels::channel(&metrics.push);
+    let (new_tx, mut new_rx) = channels::channel(&metrics.push);
+    let (other_tx, mut other_rx) = channels::channel(&metrics.push);
+    let info = ClientInfo { transport: Transport::Ssh, verified: false };
+    let verified = ClientInfo { transport: Transport::Ssh, verified: true };
+    let (old, new) = (PushConnection::new(info.clone(), old_tx), PushConnection::new(verified, new_tx));
+    command_tx.send(Command::Register(1, "neo".to_string(), Box::new(old))).await.unwrap();
+    command_tx.send(Command::Register(3, "NEO".to_string(), Box::new(PushConnection::new(info, other_tx))))
+        .await.unwrap();
+    let mut received = String::new();
+    while let Some(Push::Data(data)) = other_rx.recv().await {
+        received.push_str(&String::from_utf8_lossy(&data));
+    }
+    assert!(received.contains("is in use"));
+    command_tx.send(Command::Register(2, "Neo".to_string(), Box::new(new))).await.unwrap();
+    let mut received = String::new();
+    while let Some(push) = old_rx.recv().await {
+        match push {
+            Push::Data(data) => received.push_str(&String::from_utf8_lossy(&data)),
+            Push::Close => break,
+            Push::Animation(_) => (),
+        }
+    }
+    assert!(received.contains("You jacked in from another session."));
+    assert!(Account::exists(&data_dir, "neo").await);
+
+    // The new session is still in the world
+    shutdown_tx.send(true).unwrap();
+    running.await.unwrap();
+    let mut received = String::new();
+    while let Some(Push::Data(data)) = new_rx.recv().await {
+        received.push_str(&String::from_utf8_lossy(&data));
+    }
+    assert!(received.contains("The server is going down."));
+    std::fs::remove_dir_all(&data_dir).unwrap();
+}
+
+/// A player must get its transcripts by mail, without learning where the
+/// server stores them.
+#[tokio::test]
+async fn mail_transcripts() {
+    let data_dir = std::env::temp_dir().join(format!("mud-server-mail-transcripts-{}", std::process::id()));
+    let mut world = GameWorld::new("test".to_string());
+    world.set_data_dir(data_dir.clone());
+    let mut lobby = Node::new(0);
+    lobby.update_description("A dusty lobby.");
+    world.add_spwan_node(lobby);
+    let metrics = Metrics::new(&Capacities { command: 8, data: 8, push: 64 });
+    let (command_tx, command_rx) = channels::channel(&metrics.command);
+    let (data_tx, data_rx) = channels::channel(&metrics.data);
+    let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
+    let running = tokio::spawn(crate::world::run(command_rx, data_rx, world, shutdown_rx));
+
+    let (push_tx, mut push_rx) = channels::channel(&metrics.push);
+    let connection = Box::new(PushConnection::new(ClientInfo { transport: Transport::Ssh, verified: false }, push_tx));
+    command_tx.send(Command::Register(1, "neo".to_string(), connection)).await.unwrap();
+    assert_eq!(Action::try_from("Transcript mail me").unwrap().to_string(), "transcript mail me");
+    data_tx.send(DataMessage::new(1, b"transcript mail me".to_vec())).await.unwrap();
+    read_until(&mut push_rx, "You have no transcripts.").await;
+    data_tx.send(DataMessage::new(1, b"transcript on".to_vec())).await.unwrap();
+    let recording = read_until(&mut push_rx, "Your session is now recorded.").await;
+    assert!(!recording.contains(&data_dir.display().to_string()));
+    data_tx.send(DataMessage::new(1, b"look".to_vec())).await.unwrap();
+    read_until(&mut push_rx, "A dusty lobby.").await;
+    data_tx.send(DataMessage::new(1, b"transcript mail me".to_vec())).await.unwrap();
+    read_until(&mut push_rx, "You mail yourself your transcripts (1).").await;
+    data_tx.send(DataMessage::new(1, b"mail".to_vec())).await.unwrap();
+    let mail = read_until(&mut push_rx, "You own").await;
+    assert!(mail.contains("Your transcript:"));
+    assert!(mail.contains("> look"));
+    assert!(!mail.contains(&data_dir.display().to_string()));
+    shutdown_tx.send(true).unwrap();
+    running.await.unwrap();
+    std::fs::remove_dir_all(&data_dir).unwrap();
+}
+
+/// "stop" must cancel a delayed action in progress (eg. a scan), so it never
+/// completes and the next one starts right away.
+#[tokio::test]
+async fn stop_delayed_actions() {
+    let mut world = GameWorld::new("test".to_string());
+    world.add_spwan_node(Node::new(0));
+    let metrics = Metrics::new(&Capacities { command: 8, data: 8, push: 64 });
+    let (command_tx, command_rx) = channels::channel(&metrics.command);
+    let (data_tx, data_rx) = channels::channel(&metrics.data);
+    let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
+    let running = tokio::spawn(crate::world::run(command_rx, data_rx, world, shutdown_rx));
+
+    let (push_tx, mut push_rx) = channels::channel(&metrics.push);
+    let connection = Box::new(PushConnection::new(ClientInfo { transport: Transport::Ssh, verified: false }, push_tx));
+    command_tx.send(Command::Register(1, "neo".to_string(), connection)).await.unwrap();
+    data_tx.send(DataMessage::new(1, b"scan".to_vec())).await.unwrap();
+    read_until(&mut push_rx, "Your scanner sweeps the node").await;
+    data_tx.send(DataMessage::new(1, b"stop".to_vec())).await.unwrap();
+    let stopped = read_until(&mut push_rx, "You stop what you are doing (1 pending actions cancelled).").await;
+    assert!(!stopped.contains("Your scanner finds"));
+    data_tx.send(DataMessage::new(1, b"scan".to_vec())).await.unwrap();
+    let restarted = read_until(&mut push_rx, "Your scanner sweeps the node").await;
+    assert!(!restarted.contains("still running"));
+    assert!(!restarted.contains("Your scanner finds"));
+    shutdown_tx.send(true).unwrap();
+    running.await.unwrap();
+}
+
+/// Queued actions must be performed in order, each after the one before took
+/// its time. A full queue must refuse more actions and "stop" must cancel
+/// the queued ones.
+#[tokio::test]
+async fn queue_actions() {
+    let mut world = GameWorld::new("test".to_string());
+    let mut lobby = Node::new(0);
+    lobby.update_description("A dusty lobby.");
+    world.add_spwan_node(lobby);
+    let metrics = Metrics::new(&Capacities { command: 8, data: 32, push: 64 });
+    let (command_tx, command_rx) = channels::channel(&metrics.command);
+    let (data_tx, data_rx) = channels::channel(&metrics.data);
+    let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
+    let running = tokio::spawn(crate::world::run(command_rx, data_rx, world, shutdown_rx));
+
+    let (push_tx, mut push_rx) = channels::channel(&metrics.push);
+    let connection = Box::new(PushConnection::new(ClientInfo { transport: Transport::Ssh, verified: false }, push_tx));
+    command_tx.send(Command::Register(1, "neo".to_string(), connection)).await.unwrap();
+    read_until(&mut push_rx, "Welcome, neo.").await;
+
+    // "who" waits for "look" to take its time
+    data_tx.send(DataMessage::new(1, b"look".to_vec())).await.unwrap();
+    data_tx.send(DataMessage::new(1, b"who".to_vec())).await.unwrap();
+    let looked = read_until(&mut push_rx, "A dusty lobby.").await;
+    let started = std::time::Instant::now();
+    assert!(!looked.contains("deckers are jacked in"));
+    read_until(&mut push_rx, "1 deckers are jacked in").await;
+    assert!(started.elapsed() >= Action::try_from("look").unwrap().duration() - Duration::from_millis(100));
+
+    // A full queue refuses more actions, "stop" empties it
+    for _ in 0..MAX_QUEUED_ACTIONS + 2 {
+        data_tx.send(DataMessage::new(1, b"look".to_vec())).await.unwrap();
+    }
+    read_until(&mut push_rx, "You cannot plan that far ahead.").await;
+    data_tx.send(DataMessage::new(1, b"stop".to_vec())).await.unwrap();
+    read_until(&mut push_rx, "pending actions cancelled).").await;
+    data_tx.send(DataMessage::new(1, b"who".to_vec())).await.unwrap();
+    assert!(!read_until(&mut push_rx, "1 deckers are jacked in").await.contains("A dusty lobby."));
+    shutdown_tx.send(true).unwrap();
+    running.await.unwrap();
+}
+
+/// Intern asset strings
+///
+/// Equal strings must share one allocation, strings nobody holds any more
+/// must be evicted and output buffers must be reused.
+#[test]
+fn intern_asset_strings() {
+    let first = intern("A port that has a slight purple shimmering edge.");
+    let second = intern(&String::from("A port that has a slight purple shimmering edge."));
+    assert!(std::sync::Arc::ptr_eq(&first, &second));
+    assert!(!std::sync::Arc::ptr_eq(&first, &intern("A simple port.")));
+
+    // Strings nobody holds are evicted, those held are kept
+    for i in 0..5_000 {
+        intern(&format!("A port rewritten {} times.", i));
+    }
+    assert!(intern::count() < 5_000);
+    assert!(std::sync::Arc::ptr_eq(&first, &intern("A port that has a slight purple shimmering edge.")));
+
+    // Buffers returned to the pool are reused
+    let usage = buffers::usage();
+    buffers::give(buffers::from_slice(b"look"));
+    assert!(buffers::take().is_empty());
+    assert!(buffers::usage().reused > usage.reused);
+}
+
+/// Disrupt pushes by chance
+///
+/// Pushes must be dropped, delayed up to the maximum delay or severed as
+/// often as configured.
+#[test]
+fn disrupt_pushes_by_chance() {
+    let mut rng = Rng::new(42);
+    assert_eq!(Chaos::default().disrupt(&mut rng), Disruption::None);
+
+    let chaos = Chaos { drop_probability: 1.0, ..Chaos::default() };
+    assert_eq!(chaos.disrupt(&mut rng), Disruption::Drop);
+
+    let chaos = Chaos { delay_probability: 1.0, max_delay_ms: 50, ..Chaos::default() };
+    for _ in 0..100 {
+        match chaos.disrupt(&mut rng) {
+            Disruption::Delay(d) => assert!(d > std::time::Duration::ZERO && d.as_millis() <= 50),
+            d => panic!("Expected a delay, got {:?}", d),
+        }
+    }
+
+    let chaos = Chaos { sever_probability: 0.5, ..Chaos::default() };
+    let severed = (0..1000).filter(|_| chaos.disrupt(&mut rng) == Disruption::Sever).count();
+    assert!(severed > 350 && severed < 650);
+}
+
+/// Validate handles
+///
+/// Handles must have a valid length and charset and must neither be reserved
+/// nor contain profanity.
+#[test]
+fn validate_handles() {
+    let rules = HandleRules {
+        reserved: vec!["admin".to_string()],
+        profanity: vec!["darn".to_string()],
+        ..HandleRules::default()
+    };
+    assert_eq!(rules.validate_handle("neo_23"), Ok(()));
+    assert_eq!(rules.validate_handle("ne"), Err(Error::InvalidLength));
+    assert_eq!(rules.validate_handle("23neo"), Err(Error::InvalidCharacters));
+    assert_eq!(rules.validate_handle("ne/o"), Err(Error::InvalidCharacters));
+    assert_eq!(rules.validate_handle("Admin"), Err(Error::ReservedName));
+    assert_eq!(rules.validate_handle("DarnDecker"), Err(Error::ProfaneContent));
+    assert_eq!(rules.validate_title("the darn One"), Err(Error::ProfaneContent));
+}
+
+/// Filter profanity
+///
+/// Words containing a word of the word list must be masked, everything else
+/// must be kept as is.
+#[test]
+fn filter_profanity() {
+    let filter = ContentFilter::new(true, vec!["darn".to_string()]);
+    assert_eq!(filter.apply("Darn, this darned ICE!"), "****, this ****** ICE!");
+    let filter = ContentFilter::new(false, vec!["darn".to_string()]);
+    assert_eq!(filter.apply("Darn it"), "Darn it");
+}
+
 /** A structure that serves the test environment
 
     This structure cares for loading the settings and potential other 
@@ -60,6 +3669,7 @@ async fn check_rejected_pubkey_ssh_ed25519() {
             interfering with other test environments. Maybe we can store
             the used ports in a shared variable or the like.
 **/
+#[allow(dead_code)]
 pub struct TestEnvironment {
     settings: Settings,
     keys: Vec<thrussh_keys::key::KeyPair>,
@@ -94,10 +3704,11 @@ impl TestEnvironment {
             allowed_keys.push(key.public_key_base64());
         }
         let (server, server_config,
-            sender_data_rx, sender_command_rx)
-            = connection_manager::ssh_server::init_ssh_server(allowed_keys);
-        let mut addr = String::from(settings.ssh_server.host.clone());
-        addr.push_str(":");
+            _sender_data_rx, _sender_command_rx)
+            = connection_manager::ssh_server::init_ssh_server(KeyPair::generate_ed25519().unwrap(),
+                                                              Keyring::new(allowed_keys), &Metrics::default());
+        let mut addr = settings.ssh_server.host.clone();
+        addr.push(':');
         addr.push_str(settings.ssh_server.port.to_string().as_ref());
 
         TestEnvironment {
@@ -129,7 +3740,7 @@ impl client::Handler for Client {
        println!("check_server_key: {:?}", server_public_key);
        self.finished_bool(true)
    }
-   fn channel_open_confirmation(self, channel: ChannelId, max_packet_size: u32, window_size: u32, session: client::Session) -> Self::FutureUnit {
+   fn channel_open_confirmation(self, channel: ChannelId, _max_packet_size: u32, _window_size: u32, session: client::Session) -> Self::FutureUnit {
        println!("channel_open_confirmation: {:?}", channel);
        self.finished(session)
    }
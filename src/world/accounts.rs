@@ -0,0 +1,304 @@
+//! Accounts
+//!
+//! Everything the world remembers about a player between sessions. Accounts
+//! are stored as one JSON file per player in the `accounts` directory inside
+//! the data directory of the world.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::io;
+use std::path::{Path, PathBuf};
+use tokio::fs;
+use tracing::error;
+
+use super::bank::Vault;
+use super::combat::Verbosity;
+use super::consent::Consent;
+use super::drones::Drone;
+use super::messages::{Event, Message};
+use super::puzzles::Progress;
+use super::stats::Activity;
+use super::streaks::Streak;
+use super::triggers::Trigger;
+use super::{timestamp, GameWorld, Player};
+
+/// Name of the directory accounts are stored in (inside the data directory)
+pub const ACCOUNT_DIR: &str = "accounts";
+
+/// A note in the journal of a player
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Note {
+    /// Seconds since the epoch when the note was taken
+    pub timestamp: u64,
+    /// The note as written by the player
+    pub text: String,
+}
+
+/// The persistent part of a player
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Account {
+    /// Name of the player the account belongs to
+    pub name: String,
+    /// The journal of the player, oldest note first
+    #[serde(default)]
+    pub notes: Vec<Note>,
+    /// Title shown after the handle of the player
+    #[serde(default)]
+    pub title: Option<String>,
+    /// Description of the avatar of the player, seen by others looking at
+    /// it
+    #[serde(default)]
+    pub description: Option<String>,
+    /// The player opted out of the content filter
+    #[serde(default)]
+    pub unfiltered: bool,
+    /// The player opted out of animated screens (only the final frame is
+    /// shown)
+    #[serde(default)]
+    pub still_screens: bool,
+    /// The player muted sounds
+    #[serde(default)]
+    pub muted: bool,
+    /// How much combat output the player sees
+    #[serde(default)]
+    pub combat: Verbosity,
+    /// Triggers running commands when the player sees a text
+    #[serde(default)]
+    pub triggers: Vec<Trigger>,
+    /// Commands bound to keys, by the name of the key
+    #[serde(default)]
+    pub keymap: BTreeMap<String, String>,
+    /// Quest flags the player reached (eg. by reading a file on a terminal)
+    #[serde(default)]
+    pub flags: BTreeSet<String>,
+    /// Progress on puzzles, by the id of the puzzle
+    #[serde(default)]
+    pub puzzles: BTreeMap<String, Progress>,
+    /// Points earned by solving puzzles
+    #[serde(default)]
+    pub score: u64,
+    /// Serial of the badge the account was provisioned for
+    #[serde(default)]
+    pub badge: Option<String>,
+    /// The companion drone of the player
+    #[serde(default)]
+    pub drone: Option<Drone>,
+    /// Credits the player owns (see `market`)
+    #[serde(default)]
+    pub credits: u64,
+    /// Items the player carries, by name
+    #[serde(default)]
+    pub items: Vec<String>,
+    /// Credits and items the player keeps in the bank
+    #[serde(default)]
+    pub vault: Vault,
+    /// Reputation of the player with factions, by the name of the faction
+    #[serde(default)]
+    pub reputation: BTreeMap<String, i64>,
+    /// The login streak of the player
+    #[serde(default)]
+    pub streak: Streak,
+    /// Players whose says and tells the player does not see, by the
+    /// lowercase name (eg. "neo" or "neo@otherworld" for a linked world)
+    #[serde(default)]
+    pub ignored: BTreeSet<String>,
+    /// The interactions the player lets other players start
+    #[serde(default)]
+    pub consent: Consent,
+    /// Time played, commands sent and nodes visited over all sessions
+    #[serde(default)]
+    pub activity: Activity,
+}
+
+impl Account {
+    /// Create a new, empty account
+    pub fn new(name: &str) -> Account {
+        Account {
+            name: name.to_string(),
+            notes: Vec::new(),
+            title: None,
+            description: None,
+            unfiltered: false,
+            still_screens: false,
+            muted: false,
+            combat: Verbosity::Full,
+            triggers: Vec::new(),
+            keymap: BTreeMap::new(),
+            flags: BTreeSet::new(),
+            puzzles: BTreeMap::new(),
+            score: 0,
+            badge: None,
+            drone: None,
+            credits: 0,
+            items: Vec::new(),
+            vault: Vault::default(),
+            reputation: BTreeMap::new(),
+            streak: Streak::default(),
+            ignored: BTreeSet::new(),
+            consent: Consent::default(),
+            activity: Activity::default(),
+        }
+    }
+
+    /// Load the account of the player with the given name
+    ///
+    /// If the player has no account yet, a new account is returned. It is
+    /// only written to disk once it is saved.
+    pub async fn load(data_dir: &Path, name: &str) -> io::Result<Account> {
+        Ok(Account::load_stored(data_dir, name).await?.unwrap_or_else(|| Account::new(name)))
+    }
+
+    /// Load the stored account of the player with the given name
+    ///
+    /// Returns None if the player has no stored account.
+    pub async fn load_stored(data_dir: &Path, name: &str) -> io::Result<Option<Account>> {
+        let path = Account::path(data_dir, name);
+        match fs::read(&path).await {
+            Ok(buf) => Ok(Some(serde_json::from_slice(&buf)?)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// True if the player with the given name has a stored account (ignoring
+    /// case)
+    pub async fn exists(data_dir: &Path, name: &str) -> bool {
+        let file_name = format!("{}.json", encode_name(name));
+        let mut entries = match fs::read_dir(data_dir.join(ACCOUNT_DIR)).await {
+            Ok(e) => e,
+            Err(_) => return false,
+        };
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            if entry.file_name().to_string_lossy().eq_ignore_ascii_case(&file_name) {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Load all stored accounts
+    pub async fn load_all(data_dir: &Path) -> io::Result<Vec<Account>> {
+        let mut entries = match fs::read_dir(data_dir.join(ACCOUNT_DIR)).await {
+            Ok(e) => e,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        let mut accounts = Vec::new();
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if path.extension().is_some_and(|e| e == "json") {
+                accounts.push(serde_json::from_slice(&fs::read(&path).await?)?);
+            }
+        }
+        Ok(accounts)
+    }
+
+    /// Save the account
+    ///
+    /// The account directory is created if it does not exist yet.
+    pub async fn save(&self, data_dir: &Path) -> io::Result<()> {
+        fs::create_dir_all(data_dir.join(ACCOUNT_DIR)).await?;
+        let buf = serde_json::to_vec_pretty(self)?;
+        fs::write(Account::path(data_dir, &self.name), buf).await
+    }
+
+    /// Delete the stored account of the player with the given name
+    ///
+    /// Returns false if there was no stored account.
+    pub async fn delete(data_dir: &Path, name: &str) -> io::Result<bool> {
+        match fs::remove_file(Account::path(data_dir, name)).await {
+            Ok(_) => Ok(true),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Add a note to the journal
+    pub fn add_note(&mut self, text: &str) {
+        self.notes.push(Note { timestamp: timestamp(), text: text.to_string() });
+    }
+
+    /// The reputation of the player with a faction, zero if it never dealt
+    /// with the faction
+    pub fn reputation(&self, faction: &str) -> i64 {
+        self.reputation.get(faction).copied().unwrap_or(0)
+    }
+
+    /// True if the player ignores the player with the name (ignoring case)
+    pub fn ignores(&self, name: &str) -> bool {
+        self.ignored.contains(&name.to_lowercase())
+    }
+
+    /// Search the journal
+    ///
+    /// Returns the notes containing the search term (ignoring case) together
+    /// with their number in the journal, counting from one.
+    pub fn search_notes(&self, term: &str) -> Vec<(usize, &Note)> {
+        let term = term.to_lowercase();
+        self.notes.iter()
+            .enumerate()
+            .filter(|(_, n)| n.text.to_lowercase().contains(&term))
+            .map(|(i, n)| (i + 1, n))
+            .collect()
+    }
+
+    /// Path of the account file of the player with the given name
+    fn path(data_dir: &Path, name: &str) -> PathBuf {
+        data_dir.join(ACCOUNT_DIR).join(format!("{}.json", encode_name(name)))
+    }
+}
+
+/// Encode a player name for use as file name
+///
+/// Player names are chosen by the client, so every character that could
+/// escape the data directory is percent encoded.
+pub(crate) fn encode_name(name: &str) -> String {
+    let mut file_name = String::new();
+    for c in name.chars() {
+        if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+            file_name.push(c);
+        } else {
+            let mut buf = [0; 4];
+            for b in c.encode_utf8(&mut buf).bytes() {
+                file_name.push_str(&format!("%{:02X}", b));
+            }
+        }
+    }
+    file_name
+}
+
+/// Add a note to the journal of a player
+pub(super) async fn note(text: &str, world: &GameWorld, player: &mut Player) {
+    if text.is_empty() {
+        player.send(Message::new(Event::Error, 
+            "What do you want to note? Eg. \"note the code of the purple port is 2342\".")).await;
+        return;
+    }
+    player.account.add_note(text);
+    match player.account.save(&world.data_dir).await {
+        Ok(_) => {
+            player.send(Message::new(Event::Info, "You add a note to your journal.")
+                .with_field("note", player.account.notes.len())).await;
+        },
+        Err(e) => {
+            error!("Could not save account of {}: {}", player.player_name, e);
+            player.send(Message::new(Event::Error, 
+                "Your journal glitches. The note may not survive the session.")).await;
+        },
+    }
+}
+
+/// Show the notes of a player, optionally only those mentioning a term
+pub(super) async fn journal(search: Option<&str>, player: &Player) {
+    let notes = player.account.search_notes(search.unwrap_or(""));
+    let mut text = match (search, notes.is_empty()) {
+        (None, true) => "Your journal is empty.".to_string(),
+        (Some(term), true) => format!("Your journal does not mention \"{}\".", term),
+        (_, false) => "Your journal:".to_string(),
+    };
+    for (number, note) in notes.iter() {
+        text += format!("\r\n {:>3}. {}", number, note.text).as_str();
+    }
+    let notes: Vec<&Note> = notes.into_iter().map(|(_, n)| n).collect();
+    player.send(Message::new(Event::Info, &text)
+        .with_field("notes", notes)).await;
+}
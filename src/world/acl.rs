@@ -0,0 +1,71 @@
+//! Access control lists
+//!
+//! Nodes (and thus zones, which are named nodes) may restrict who enters
+//! them, eg. staff-only backstage areas ("role=builder"), faction-locked
+//! zones ("flag=faction/zaibatsu") or regions gated by a quest
+//! ("flag=quest/purple-port"). A player may enter if it has one of the roles
+//! or reached one of the quest flags of the list. Admins may enter
+//! everywhere.
+//!
+//! ACLs are set when the world is built and changed by builders with
+//! "@acl <node uid> <rules>" (see `edits`).
+
+use std::collections::BTreeSet;
+use std::convert::TryFrom;
+use std::fmt;
+
+use super::errors::Error;
+use super::Role;
+
+/// Who may enter a node
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Acl {
+    pub roles: Vec<Role>,
+    pub flags: Vec<String>,
+}
+
+impl Acl {
+    /// True if a player with the role and quest flags may enter
+    pub fn allows(&self, role: Role, flags: &BTreeSet<String>) -> bool {
+        role == Role::Admin || self.roles.contains(&role) || self.flags.iter().any(|f| flags.contains(f))
+    }
+}
+
+/// Parse the rules of an ACL ("role=<role>" or "flag=<flag>", separated by
+/// blanks)
+impl TryFrom<&str> for Acl {
+    type Error = Error;
+
+    fn try_from(item: &str) -> Result<Self, Error> {
+        let mut acl = Acl::default();
+        for rule in item.split_whitespace() {
+            match rule.split_once('=') {
+                Some(("role", role)) => acl.roles.push(match role.to_lowercase().as_str() {
+                    "player" => Role::Player,
+                    "builder" => Role::Builder,
+                    "admin" => Role::Admin,
+                    _ => return Err(Error::InvalidCommand),
+                }),
+                Some(("flag", flag)) if !flag.is_empty() => acl.flags.push(flag.to_string()),
+                _ => return Err(Error::InvalidCommand),
+            }
+        }
+        if acl.roles.is_empty() && acl.flags.is_empty() {
+            return Err(Error::InvalidCommand);
+        }
+        Ok(acl)
+    }
+}
+
+/// Display the rules of an ACL
+impl fmt::Display for Acl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let roles = self.roles.iter().map(|r| format!("role={}", match r {
+            Role::Player => "player",
+            Role::Builder => "builder",
+            Role::Admin => "admin",
+        }));
+        let flags = self.flags.iter().map(|f| format!("flag={}", f));
+        write!(f, "{}", roles.chain(flags).collect::<Vec<String>>().join(" "))
+    }
+}
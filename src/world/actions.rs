@@ -4,23 +4,405 @@
 //! Contains the different acitons that can be performed in the game.
 
 use crate::world::properties::Property;
+use crate::world::media::Protocol;
+use crate::world::combat::Verbosity;
+use crate::world::drones::Order;
+use crate::world::market::Trade;
+use crate::world::bank::Banking;
+use crate::world::casino::Game;
+use crate::world::economy::Deal;
+use crate::world::consent::Pref;
+use crate::world::schedule::Scheduling;
+use crate::world::adverbs::Adverb;
+use crate::world::verbs;
 use std::fmt;
+use std::time::Duration;
 
 /// An enum denominating all the possible actions
+#[derive(Debug)]
 pub enum Action {
-    Look{target: Option<String>, preposition: Option<String>, properties: Option<Vec<Property>>}, //{target: Option<Box<dyn Observable + Send + Sync>>},
+    Look{target: Option<String>, ordinal: Option<usize>, preposition: Option<String>, properties: Option<Vec<Property>>,
+        manner: Option<Adverb>}, //{target: Option<Box<dyn Observable + Send + Sync>>},
     Read,
-    Enter,
-    Connect,
-    Access,
+    /// Enter an asset (eg. a portal to another world), the only port in
+    /// the node if no target is given
+    Enter { target: Option<String>, ordinal: Option<usize>, manner: Option<Adverb> },
+    /// Connect through a port to the node it leads to, the only such port
+    /// in the node if no target is given
+    Connect { target: Option<String>, ordinal: Option<usize>, manner: Option<Adverb> },
+    /// Access an asset (eg. a terminal), the only one in the node if no
+    /// target is given
+    Access { target: Option<String>, ordinal: Option<usize>, manner: Option<Adverb> },
+    /// Answer the puzzle on an asset
+    Solve { target: String, ordinal: Option<usize>, answer: String },
+    /// Buy the next hint for the puzzle on an asset
+    Hint { target: String, ordinal: Option<usize> },
     Open,
+    /// Take an item from the node and carry it (see `inventory`)
+    Take { target: String, ordinal: Option<usize> },
+    /// Steal an item belonging to another player from the node (see
+    /// `ownership`)
+    Steal { target: String, ordinal: Option<usize> },
+    /// Put down an item the player carries in the node
+    Drop { target: String, ordinal: Option<usize> },
+    /// List the items the player carries
+    Inventory,
+    /// Show the help topics, or a page of a topic (see `help`)
+    Help { topic: Option<String>, page: Option<usize> },
+    /// Cancel all pending actions
+    Stop,
+    /// Leave the world once the pending actions are performed and close the
+    /// connection
+    Quit,
+    /// Switch robot mode on, off or toggle it (None)
+    Robot(Option<bool>),
+    /// File a bug report with the given description
+    Bug(String),
+    /// Add a note to the journal
+    Note(String),
+    /// Show the journal, optionally only the notes matching a search term
+    Journal(Option<String>),
+    /// Set (Some) or clear (None) the title of the player
+    Title(Option<String>),
+    /// Set (Some) or clear (None) the description of the avatar of the
+    /// player (see `appearance`)
+    Describe(Option<String>),
+    /// Ignore a player (Some) or list the players ignored (None)
+    Ignore(Option<String>),
+    /// Stop ignoring a player
+    Unignore(String),
+    /// Turn a consent flag on or off (Some) or show the flags (None)
+    Prefs(Option<(Pref, bool)>),
+    /// Show the score, level and activity of the player
+    Score,
+    /// Show how far the goals of the community are (see `goals`)
+    Goals,
+    /// Vote for an option of the poll running, or show the poll (None, see
+    /// `polls`)
+    Vote(Option<String>),
+    /// Show the upcoming events of the con or set a reminder (see
+    /// `schedule`)
+    Schedule(Scheduling),
+    /// List the connected players
+    Who,
+    /// Say something to everybody in the same node
+    Say(String),
+    /// Shout something, heard in the same node and the nodes next to it
+    Shout(String),
+    /// Switch the content filter for received text on or off
+    Filter(bool),
+    /// Switch the transcript of the session on, off or show its state (None)
+    Transcript(Option<bool>),
+    /// Mail the transcripts of the player to its mailbox (see `market`)
+    MailTranscript,
+    /// Add a trigger running a command (Some((pattern, command))) or list
+    /// the triggers (None)
+    Trigger(Option<(String, String)>),
+    /// Remove the trigger with the given number (counting from one)
+    Untrigger(usize),
+    /// Bind a command to a key (Some), unbind the key (None) or list the
+    /// bound keys (no key)
+    Bind { key: Option<String>, command: Option<String> },
+    /// Switch the animation of screens on, off or toggle it (None)
+    Animations(Option<bool>),
+    /// Announce the protocol the client plays sounds with (Some) or that it
+    /// cannot play sounds (None)
+    Media(Option<Protocol>),
+    /// Mute sounds, unmute them or toggle (None)
+    Mute(Option<bool>),
+    /// Start a run of a challenge (Some) or list the challenges (None)
+    Challenge(Option<String>),
+    /// Join the challenge run of another player
+    Join(String),
+    /// Show the leaderboard of a challenge (Some) or of all challenges (None)
+    Leaderboard(Option<String>),
+    /// Watch the feed of a spectated zone (Some) or stop watching (None)
+    Watch(Option<String>),
+    /// Tell a player something, in this world or in a linked world
+    Tell { to: String, world: Option<String>, text: String },
+    /// Hack a hostile NPC in the node
+    Hack { target: String, ordinal: Option<usize> },
+    /// Run a decoy program at a hostile NPC in the node, drawing its attacks
+    Taunt { target: String, ordinal: Option<usize> },
+    /// Hit the ICE of a port in the node with brute force (see `ice`)
+    Attack { target: String, ordinal: Option<usize> },
+    /// Hit the ICE of a port in the node slowly, drawing less trace
+    Crack { target: String, ordinal: Option<usize> },
+    /// Set the verbosity of combat output (Some) or show it (None)
+    Combat(Option<Verbosity>),
+    /// Engage (true) or drop (false) the cloak of the player
+    Cloak(bool),
+    /// Run a scanner revealing the cloaked players in the node
+    Scan,
+    /// Give an order to the drone of the player
+    Drone(Order),
+    /// Trade at the market of the node
+    Market(Trade),
+    /// Collect the mail waiting for the player
+    Mail,
+    /// Deposit, withdraw or show the balance at the bank of the node
+    Bank(Banking),
+    /// Wager credits on a game at the casino of the node
+    Gamble(Game),
+    /// Trade with the vendor of the node
+    Vendor(Deal),
+    /// Buy off the heat of security (see `security`)
+    Bribe,
+    /// Perform a verb registered at runtime, with the arguments its parser
+    /// returned (see `verbs`)
+    Plugin { verb: &'static str, arguments: Vec<String> },
+}
+
+impl Action {
+    /// Duration
+    /// 
+    /// The time it takes to perform the action, paced by its adverb. A
+    /// player cannot perform the next queued action before this time has
+    /// passed.
+    pub fn duration(&self) -> Duration {
+        let duration = match self {
+            Action::Look { .. } => Duration::from_millis(500),
+            Action::Read => Duration::from_secs(2),
+            Action::Enter { .. } => Duration::from_secs(1),
+            Action::Connect { .. } => Duration::from_secs(1),
+            Action::Access { .. } => Duration::from_secs(1),
+            Action::Solve { .. } => Duration::from_secs(1),
+            Action::Hint { .. } => Duration::from_millis(500),
+            Action::Open => Duration::from_secs(1),
+            Action::Take { .. } => Duration::from_secs(1),
+            Action::Steal { .. } => Duration::from_secs(2),
+            Action::Drop { .. } => Duration::from_millis(500),
+            Action::Inventory => Duration::ZERO,
+            Action::Help { .. } => Duration::ZERO,
+            Action::Stop => Duration::ZERO,
+            Action::Quit => Duration::ZERO,
+            Action::Robot(_) => Duration::ZERO,
+            Action::Bug(_) => Duration::ZERO,
+            Action::Note(_) => Duration::ZERO,
+            Action::Journal(_) => Duration::ZERO,
+            Action::Title(_) => Duration::ZERO,
+            Action::Describe(_) => Duration::ZERO,
+            Action::Ignore(_) => Duration::ZERO,
+            Action::Unignore(_) => Duration::ZERO,
+            Action::Prefs(_) => Duration::ZERO,
+            Action::Score => Duration::ZERO,
+            Action::Goals => Duration::ZERO,
+            Action::Vote(_) => Duration::ZERO,
+            Action::Schedule(_) => Duration::ZERO,
+            Action::Who => Duration::ZERO,
+            Action::Say(_) => Duration::ZERO,
+            Action::Shout(_) => Duration::from_secs(1),
+            Action::Filter(_) => Duration::ZERO,
+            Action::Transcript(_) => Duration::ZERO,
+            Action::MailTranscript => Duration::ZERO,
+            Action::Trigger(_) => Duration::ZERO,
+            Action::Untrigger(_) => Duration::ZERO,
+            Action::Bind { .. } => Duration::ZERO,
+            Action::Animations(_) => Duration::ZERO,
+            Action::Media(_) => Duration::ZERO,
+            Action::Mute(_) => Duration::ZERO,
+            Action::Challenge(_) => Duration::from_millis(500),
+            Action::Join(_) => Duration::from_millis(500),
+            Action::Leaderboard(_) => Duration::ZERO,
+            Action::Watch(_) => Duration::ZERO,
+            Action::Tell { .. } => Duration::ZERO,
+            Action::Hack { .. } => Duration::from_secs(2),
+            Action::Taunt { .. } => Duration::from_secs(3),
+            Action::Attack { .. } => Duration::from_secs(2),
+            Action::Crack { .. } => Duration::from_secs(3),
+            Action::Combat(_) => Duration::ZERO,
+            Action::Cloak(_) => Duration::from_secs(2),
+            Action::Scan => Duration::from_secs(2),
+            Action::Drone(Order::Status) => Duration::ZERO,
+            Action::Drone(Order::Attack { .. }) | Action::Drone(Order::Scan) => Duration::from_secs(2),
+            Action::Drone(_) => Duration::from_secs(1),
+            Action::Market(Trade::Browse(_)) => Duration::ZERO,
+            Action::Market(_) => Duration::from_secs(1),
+            Action::Mail => Duration::from_millis(500),
+            Action::Bank(Banking::Balance) => Duration::ZERO,
+            Action::Bank(_) => Duration::from_millis(500),
+            Action::Gamble(_) => Duration::from_secs(2),
+            Action::Vendor(Deal::Wares) => Duration::ZERO,
+            Action::Vendor(_) => Duration::from_secs(1),
+            Action::Bribe => Duration::from_secs(1),
+            Action::Plugin { verb, .. } => verbs::duration(verb),
+        };
+        match self.manner() {
+            Some(adverb) => adverb.pace(duration),
+            None => duration,
+        }
+    }
+
+    /// Manner
+    ///
+    /// The adverb telling how the action is performed, if any (see
+    /// `adverbs`).
+    pub fn manner(&self) -> Option<Adverb> {
+        match self {
+            Action::Look { manner, .. } | Action::Enter { manner, .. } | Action::Connect { manner, .. }
+                | Action::Access { manner, .. } => *manner,
+            _ => None,
+        }
+    }
+
+    /// Target
+    ///
+    /// The noun and ordinal of the asset in the node the action is aimed
+    /// at, if any.
+    pub fn target(&self) -> Option<(&str, Option<usize>)> {
+        match self {
+            Action::Look { target: Some(t), ordinal, .. } | Action::Enter { target: Some(t), ordinal, .. }
+                | Action::Connect { target: Some(t), ordinal, .. } | Action::Access { target: Some(t), ordinal, .. }
+                | Action::Solve { target: t, ordinal, .. } | Action::Hint { target: t, ordinal }
+                | Action::Take { target: t, ordinal } | Action::Steal { target: t, ordinal }
+                | Action::Attack { target: t, ordinal } | Action::Crack { target: t, ordinal } => Some((t, *ordinal)),
+            _ => None,
+        }
+    }
+
+    /// Is delayed
+    ///
+    /// True for the actions taking effect when they complete, at the end of
+    /// their duration (see `delays`).
+    pub fn is_delayed(&self) -> bool {
+        matches!(self, Action::Scan)
+    }
+
+    /// Is queued
+    /// 
+    /// True for the actions performed in the world on the world tick, false
+    /// for the session commands handled immediately.
+    pub fn is_queued(&self) -> bool {
+        match self {
+            Action::Look { .. } | Action::Read | Action::Enter { .. } | Action::Connect { .. }
+                | Action::Access { .. } | Action::Solve { .. } | Action::Hint { .. } 
+                | Action::Open | Action::Take { .. } | Action::Steal { .. } | Action::Drop { .. } | Action::Who
+                | Action::Say(_)
+                | Action::Shout(_) | Action::Challenge(_) 
+                | Action::Join(_) | Action::Leaderboard(_) | Action::Tell { .. } 
+                | Action::Hack { .. } | Action::Taunt { .. } | Action::Cloak(_) | Action::Scan 
+                | Action::Attack { .. } | Action::Crack { .. }
+                | Action::Drone(_) | Action::Market(_) | Action::Mail | Action::MailTranscript | Action::Bank(_) 
+                | Action::Gamble(_) | Action::Vendor(_) | Action::Bribe | Action::Vote(_) | Action::Schedule(_)
+                | Action::Plugin { .. }
+                | Action::Quit => true,
+            Action::Stop | Action::Robot(_) | Action::Bug(_) | Action::Note(_) 
+                | Action::Journal(_) | Action::Title(_) | Action::Describe(_) | Action::Filter(_) 
+                | Action::Ignore(_) | Action::Unignore(_) | Action::Prefs(_) | Action::Score | Action::Goals
+                | Action::Inventory | Action::Help { .. } | Action::Transcript(_)
+                | Action::Trigger(_)
+                | Action::Untrigger(_) 
+                | Action::Bind { .. } | Action::Animations(_) | Action::Media(_) 
+                | Action::Mute(_) | Action::Watch(_) | Action::Combat(_) => false,
+        }
+    }
+
+    /// Verb
+    /// 
+    /// The verb of the action without any of its arguments (eg. "look").
+    pub fn verb(&self) -> &'static str {
+        match self {
+            Action::Look { .. } => "look",
+            Action::Read => "read",
+            Action::Enter { .. } => "enter",
+            Action::Connect { .. } => "connect",
+            Action::Access { .. } => "access",
+            Action::Solve { .. } => "solve",
+            Action::Hint { .. } => "hint",
+            Action::Open => "open",
+            Action::Take { .. } => "take",
+            Action::Steal { .. } => "steal",
+            Action::Drop { .. } => "drop",
+            Action::Inventory => "inventory",
+            Action::Help { .. } => "help",
+            Action::Stop => "stop",
+            Action::Quit => "quit",
+            Action::Robot(_) => "robot",
+            Action::Bug(_) => "bug",
+            Action::Note(_) => "note",
+            Action::Journal(_) => "journal",
+            Action::Title(_) => "title",
+            Action::Describe(_) => "describe",
+            Action::Ignore(_) => "ignore",
+            Action::Unignore(_) => "unignore",
+            Action::Prefs(_) => "prefs",
+            Action::Score => "score",
+            Action::Goals => "goals",
+            Action::Vote(_) => "vote",
+            Action::Schedule(_) => "schedule",
+            Action::Who => "who",
+            Action::Say(_) => "say",
+            Action::Shout(_) => "shout",
+            Action::Filter(_) => "filter",
+            Action::Transcript(_) | Action::MailTranscript => "transcript",
+            Action::Trigger(_) => "trigger",
+            Action::Untrigger(_) => "untrigger",
+            Action::Bind { .. } => "bind",
+            Action::Animations(_) => "animations",
+            Action::Media(_) => "media",
+            Action::Mute(_) => "mute",
+            Action::Challenge(_) => "challenge",
+            Action::Join(_) => "join",
+            Action::Leaderboard(_) => "leaderboard",
+            Action::Watch(_) => "watch",
+            Action::Tell { .. } => "tell",
+            Action::Hack { .. } => "hack",
+            Action::Taunt { .. } => "taunt",
+            Action::Attack { .. } => "attack",
+            Action::Crack { .. } => "crack",
+            Action::Combat(_) => "combat",
+            Action::Cloak(_) => "cloak",
+            Action::Scan => "scan",
+            Action::Drone(_) => "drone",
+            Action::Market(_) => "market",
+            Action::Mail => "mail",
+            Action::Bank(Banking::Balance) => "balance",
+            Action::Bank(Banking::Deposit(_)) => "deposit",
+            Action::Bank(Banking::Withdraw(_)) => "withdraw",
+            Action::Gamble(Game::Dice(_)) => "dice",
+            Action::Gamble(Game::Poker(_)) => "poker",
+            Action::Vendor(Deal::Wares) => "wares",
+            Action::Vendor(Deal::Buy(_)) => "buy",
+            Action::Vendor(Deal::Sell(_)) => "sell",
+            Action::Bribe => "bribe",
+            Action::Plugin { verb, .. } => verb,
+        }
+    }
+
+    /// Is noisy
+    /// 
+    /// True for the actions that drop the cloak of the player performing
+    /// them (see `stealth`), unless performed slowly.
+    pub fn is_noisy(&self) -> bool {
+        self.manner() != Some(Adverb::Slowly) && matches!(self, Action::Enter { .. } | Action::Connect { .. }
+            | Action::Access { .. } | Action::Solve { .. } | Action::Hint { .. } | Action::Say(_)
+            | Action::Shout(_) | Action::Challenge(Some(_)) | Action::Join(_) | Action::Hack { .. } | Action::Taunt { .. }
+            | Action::Scan | Action::Drone(Order::Attack { .. }) | Action::Attack { .. })
+    }
+
+    /// Is combat
+    ///
+    /// True for the actions that start or carry on a fight, refused in safe
+    /// zones (see `safety`).
+    pub fn is_combat(&self) -> bool {
+        matches!(self, Action::Hack { .. } | Action::Taunt { .. } | Action::Drone(Order::Attack { .. })
+            | Action::Attack { .. } | Action::Crack { .. })
+    }
+
+    /// Is dangerous
+    ///
+    /// True for the actions zones may restrict to players of a level (see
+    /// `safety`).
+    pub fn is_dangerous(&self) -> bool {
+        self.is_combat() || matches!(self, Action::Gamble(_))
+    }
 }
 
 /// Display an action
 impl fmt::Display for Action {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Action::Look { target, preposition, properties } => {
+            Action::Look { target, ordinal, preposition, properties, manner } => {
                 // TODO - print the properties
                 match target {
                     Some(t) => {
@@ -37,20 +419,140 @@ impl fmt::Display for Action {
                             None => "",
                         };
 
-                        write!(f, "look {}{}{}", prep, prop, t)
+                        let ord = match ordinal {
+                            Some(n) => format!(" {}", n),
+                            None => "".to_string(),
+                        };
+
+                        let man = match manner {
+                            Some(m) => format!("{} ", m),
+                            None => "".to_string(),
+                        };
+
+                        write!(f, "look {}{}{}{}{}", man, prep, prop, t, ord)
                     },
                     None => {
                         // There is no legitimate look command with prepostion and properties but no target
                         // so we need to only consider the one case.
-                        write!(f, "look")
+                        match manner {
+                            Some(m) => write!(f, "look {}", m),
+                            None => write!(f, "look"),
+                        }
                     },
                 }
             },
             Action::Read => write!(f, "read (todo)"),
-            Action::Enter => write!(f, "enter (todo)"),
-            Action::Connect => write!(f, "connect (todo)"),
-            Action::Access => write!(f, "access (todo)"),
-            Action::Open => write!(f, "open (todo)"),
+            Action::Enter { target, ordinal, manner } => write_object(f, "enter", target, *ordinal, *manner),
+            Action::Connect { target, ordinal, manner } => write_object(f, "connect", target, *ordinal, *manner),
+            Action::Access { target, ordinal, manner } => write_object(f, "access", target, *ordinal, *manner),
+            Action::Solve { target, ordinal: None, answer } => write!(f, "solve {} {}", target, answer),
+            Action::Solve { target, ordinal: Some(n), answer } => write!(f, "solve {} {} {}", target, n, answer),
+            Action::Hint { target, ordinal: None } => write!(f, "hint {}", target),
+            Action::Hint { target, ordinal: Some(n) } => write!(f, "hint {} {}", target, n),
+            Action::Open => write!(f, "open"),
+            Action::Take { target, ordinal: None } => write!(f, "take {}", target),
+            Action::Take { target, ordinal: Some(n) } => write!(f, "take {} {}", target, n),
+            Action::Steal { target, ordinal: None } => write!(f, "steal {}", target),
+            Action::Steal { target, ordinal: Some(n) } => write!(f, "steal {} {}", target, n),
+            Action::Drop { target, ordinal: None } => write!(f, "drop {}", target),
+            Action::Drop { target, ordinal: Some(n) } => write!(f, "drop {} {}", target, n),
+            Action::Inventory => write!(f, "inventory"),
+            Action::Help { topic: None, .. } => write!(f, "help"),
+            Action::Help { topic: Some(topic), page: None } => write!(f, "help {}", topic),
+            Action::Help { topic: Some(topic), page: Some(page) } => write!(f, "help {} {}", topic, page),
+            Action::Stop => write!(f, "stop"),
+            Action::Quit => write!(f, "quit"),
+            Action::Robot(None) => write!(f, "robot"),
+            Action::Robot(Some(true)) => write!(f, "robot on"),
+            Action::Robot(Some(false)) => write!(f, "robot off"),
+            Action::Bug(d) => write!(f, "bug {}", d),
+            Action::Note(n) => write!(f, "note {}", n),
+            Action::Journal(None) => write!(f, "journal"),
+            Action::Journal(Some(s)) => write!(f, "journal {}", s),
+            Action::Title(None) => write!(f, "title"),
+            Action::Title(Some(t)) => write!(f, "title {}", t),
+            Action::Describe(None) => write!(f, "describe me"),
+            Action::Describe(Some(d)) => write!(f, "describe me {}", d),
+            Action::Ignore(None) => write!(f, "ignore"),
+            Action::Ignore(Some(p)) => write!(f, "ignore {}", p),
+            Action::Unignore(p) => write!(f, "unignore {}", p),
+            Action::Prefs(None) => write!(f, "prefs"),
+            Action::Prefs(Some((pref, on))) => write!(f, "prefs {} {}", pref, if *on { "on" } else { "off" }),
+            Action::Score => write!(f, "score"),
+            Action::Goals => write!(f, "goals"),
+            Action::Vote(None) => write!(f, "vote"),
+            Action::Vote(Some(choice)) => write!(f, "vote {}", choice),
+            Action::Schedule(scheduling) => write!(f, "{}", scheduling),
+            Action::Who => write!(f, "who"),
+            Action::Say(t) => write!(f, "say {}", t),
+            Action::Shout(t) => write!(f, "shout {}", t),
+            Action::Filter(true) => write!(f, "filter on"),
+            Action::Filter(false) => write!(f, "filter off"),
+            Action::Transcript(None) => write!(f, "transcript"),
+            Action::Transcript(Some(true)) => write!(f, "transcript on"),
+            Action::Transcript(Some(false)) => write!(f, "transcript off"),
+            Action::MailTranscript => write!(f, "transcript mail me"),
+            Action::Trigger(None) => write!(f, "trigger"),
+            Action::Trigger(Some((p, c))) => write!(f, "trigger \"{}\" {}", p, c),
+            Action::Untrigger(n) => write!(f, "untrigger {}", n),
+            Action::Bind { key: None, .. } => write!(f, "bind"),
+            Action::Bind { key: Some(k), command: None } => write!(f, "bind {}", k),
+            Action::Bind { key: Some(k), command: Some(c) } => write!(f, "bind {} {}", k, c),
+            Action::Animations(None) => write!(f, "animations"),
+            Action::Animations(Some(true)) => write!(f, "animations on"),
+            Action::Animations(Some(false)) => write!(f, "animations off"),
+            Action::Media(Some(p)) => write!(f, "media {}", p),
+            Action::Media(None) => write!(f, "media off"),
+            Action::Mute(None) => write!(f, "mute"),
+            Action::Mute(Some(true)) => write!(f, "mute on"),
+            Action::Mute(Some(false)) => write!(f, "mute off"),
+            Action::Challenge(None) => write!(f, "challenge"),
+            Action::Challenge(Some(c)) => write!(f, "challenge {}", c),
+            Action::Join(p) => write!(f, "join {}", p),
+            Action::Leaderboard(None) => write!(f, "leaderboard"),
+            Action::Leaderboard(Some(c)) => write!(f, "leaderboard {}", c),
+            Action::Watch(None) => write!(f, "watch"),
+            Action::Watch(Some(z)) => write!(f, "watch {}", z),
+            Action::Tell { to, world: None, text } => write!(f, "tell {} {}", to, text),
+            Action::Tell { to, world: Some(w), text } => write!(f, "tell {}@{} {}", to, w, text),
+            Action::Hack { target, ordinal: None } => write!(f, "hack {}", target),
+            Action::Hack { target, ordinal: Some(n) } => write!(f, "hack {} {}", target, n),
+            Action::Taunt { target, ordinal: None } => write!(f, "taunt {}", target),
+            Action::Taunt { target, ordinal: Some(n) } => write!(f, "taunt {} {}", target, n),
+            Action::Attack { target, ordinal: None } => write!(f, "attack {}", target),
+            Action::Attack { target, ordinal: Some(n) } => write!(f, "attack {} {}", target, n),
+            Action::Crack { target, ordinal: None } => write!(f, "crack {}", target),
+            Action::Crack { target, ordinal: Some(n) } => write!(f, "crack {} {}", target, n),
+            Action::Combat(None) => write!(f, "combat"),
+            Action::Combat(Some(v)) => write!(f, "combat {}", v),
+            Action::Cloak(true) => write!(f, "cloak"),
+            Action::Cloak(false) => write!(f, "cloak off"),
+            Action::Scan => write!(f, "scan"),
+            Action::Drone(order) => write!(f, "{}", order),
+            Action::Market(trade) => write!(f, "{}", trade),
+            Action::Mail => write!(f, "mail"),
+            Action::Bank(banking) => write!(f, "{}", banking),
+            Action::Gamble(game) => write!(f, "{}", game),
+            Action::Vendor(deal) => write!(f, "{}", deal),
+            Action::Bribe => write!(f, "bribe"),
+            Action::Plugin { verb, arguments } if arguments.is_empty() => write!(f, "{}", verb),
+            Action::Plugin { verb, arguments } => write!(f, "{} {}", verb, arguments.join(" ")),
         }
     }
-}
\ No newline at end of file
+}
+
+/// Write a verb with its adverb and optional object
+fn write_object(f: &mut fmt::Formatter<'_>, verb: &str, target: &Option<String>, ordinal: Option<usize>,
+                manner: Option<Adverb>) -> fmt::Result {
+    write!(f, "{}", verb)?;
+    if let Some(m) = manner {
+        write!(f, " {}", m)?;
+    }
+    if let Some(t) = target {
+        write!(f, " {}", t)?;
+    }
+    if let (Some(_), Some(n)) = (target, ordinal) {
+        write!(f, " {}", n)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,549 @@
+//! Admin commands
+//!
+//! Commands for the staff running the world. Admin commands start with an
+//! "@" and are only available to players with the admin role, except for
+//! the commands for builders (eg. "@find"), which players with the builder
+//! role may use as well.
+//!
+//! The grammar supported is:
+//! ```ignore
+//!     <admin_command> ::= "@" <name> (<blank> <arguments> | E)
+//!     <name> ::= "title" | "filter" | "export" | "forget" | "channels" | "stats"
+//!              | "loglevel" | "banner" | "spectate" | "token" | "maintenance"
+//!              | "pause" | "step" | "resume" | "reset" | "find" | "show" | "where"
+//!              | "describe" | "create" | "undo" | "changes" | "draft" | "preview"
+//!              | "publish" | "discard" | "acl" | "slay" | "casino" | "economy"
+//!              | "flags" | "totals" | "archive" | "sandbox" | "coverage" | "timers"
+//!              | "poll" | "schedule"
+//!     <blank> ::= " "+
+//!     <loglevel_arguments> ::= <target> "=" <level> (<blank> <loglevel_arguments> | E)
+//!     <banner_arguments> ::= ("font=" <font> <blank> | E) <text>
+//!     <spectate_arguments> ::= <zone> <blank> ("on" | "off")
+//!     <maintenance_arguments> ::= "on" (<blank> <minutes> | E) | "off"
+//!     <step_arguments> ::= <ticks> | E
+//!     <reset_arguments> ::= <zone>
+//!     <timers_arguments> ::= "cancel" <blank> <id> | E
+//!     <find_arguments> ::= <noun> (<blank> <key> "=" <value>)*
+//!     <show_arguments> ::= ("node" | "asset") <blank> <uid>
+//!     <where_arguments> ::= "asset" <blank> <uid>
+//!     <describe_arguments> ::= <uid> <blank> <text>
+//!     <create_arguments> ::= ("port" | "terminal" <blank> <hostname>) <blank> <node uid> (<blank> <text> | E)
+//!     <changes_arguments> ::= <count> | E
+//!     <acl_arguments> ::= <node uid> <blank> ("open" | <rule> (<blank> <rule>)*)
+//!     <slay_arguments> ::= <npc uid>
+//!     <casino_arguments> ::= <max wager> <blank> <max loss> | E
+//!     <flags_arguments> ::= <season> <blank> ("on" | "off" | "auto") | E
+//!     <totals_arguments> ::= "announce" | E
+//!     <coverage_arguments> ::= <player> | E
+//!     <sandbox_arguments> ::= "enter" (<blank> <node uid> | E) | "promote" | "discard"
+//!     <poll_arguments> ::= "create" <blank> <question> ("|" <option>)+ | "close" | E
+//!     <schedule_arguments> ::= "add" <blank> ("con" | "world") <blank> <minutes> <blank> <title>
+//!         | "remove" <blank> <id> | "reload"
+//!     <rule> ::= "role=" ("player" | "builder" | "admin") | "flag=" <flag>
+//! ```
+
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+use tracing::level_filters::LevelFilter;
+
+use crate::world::acl::Acl;
+use crate::world::assets::AssetID;
+use crate::world::edits::{AssetKind, Edit};
+use crate::world::npcs::NpcID;
+use crate::world::casino::Limits;
+use crate::world::schedule::Kind;
+use crate::world::errors::Error;
+
+/// What to show
+#[derive(Debug, Clone, PartialEq)]
+pub enum Target {
+    /// A node by its uid
+    Node(AssetID),
+    /// An asset in a node by its uid
+    Asset(AssetID),
+}
+
+/// What a builder does with its sandbox (see `sandboxes`)
+#[derive(Debug, Clone, PartialEq)]
+pub enum SandboxStep {
+    /// Copy a node by its uid, or the node the builder is in (None)
+    Enter(Option<AssetID>),
+    /// Apply the edits to the live zone
+    Promote,
+    /// Drop the copy
+    Discard,
+}
+
+/// What an admin does with the poll (see `polls`)
+#[derive(Debug, Clone, PartialEq)]
+pub enum PollStep {
+    /// Show the votes of the poll running
+    Show,
+    /// Open a poll, telling everybody the question and the options
+    Create { question: String, options: Vec<String> },
+    /// End the poll, telling everybody the results
+    Close,
+}
+
+/// What an admin does with the schedule of the con (see `schedule`)
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScheduleStep {
+    /// Add an event starting in the given minutes
+    Add { kind: Kind, minutes: u64, title: String },
+    /// Remove an event by its id
+    Remove(u64),
+    /// Load the schedule file again (eg. after editing it by hand)
+    Reload,
+}
+
+/// An enum denominating all the admin commands
+#[derive(Debug, Clone, PartialEq)]
+pub enum AdminCommand {
+    /// Set (Some) or clear (None) the title of a player
+    Title { player: String, title: Option<String> },
+    /// Switch the content filter on or off
+    Filter(bool),
+    /// Export all personal data stored about a player
+    Export(String),
+    /// Delete all personal data stored about a player
+    Forget(String),
+    /// Show the statistics of the channels to the world
+    Channels,
+    /// Show the statistics of the world loop
+    Stats,
+    /// Show the credits created and destroyed by the systems of the world
+    Economy,
+    /// Show the anonymized totals of the activity of all players, to the
+    /// admin or announced to everybody online (eg. at the end of the con)
+    Totals { announce: bool },
+    /// Export the world, the leaderboards, the announcements and the totals
+    /// as archive (see `archive`)
+    Archive,
+    /// Replay the transcripts of a player, or of all players (None), through
+    /// the grammar (see `coverage`)
+    Coverage(Option<String>),
+    /// Change the log level of targets (eg. "world=debug"), or show the
+    /// current levels if empty
+    LogLevel(Vec<(String, LevelFilter)>),
+    /// Announce a text to all players as banner, in the given font or the
+    /// default font (None)
+    Banner { font: Option<String>, text: String },
+    /// Let players watch a zone (eg. the finals of an event) or stop it
+    Spectate { zone: String, enabled: bool },
+    /// Issue a one-time login token for a player
+    Token(String),
+    /// Take the world down for maintenance (only staff may log in), to
+    /// reopen it automatically after the given minutes (Some), or reopen it
+    Maintenance { enabled: bool, minutes: Option<u64> },
+    /// Pause the world tick
+    Pause,
+    /// Let the paused world perform the given number of ticks
+    Step(u32),
+    /// Let the paused world run again
+    Resume,
+    /// Run the reset of a zone now
+    Reset(String),
+    /// Show the pending timers (None) or cancel a timer (by id)
+    Timers(Option<u64>),
+    /// Find the assets referenced by a noun ("node", "asset" or eg. "port")
+    /// whose fields match the filters (key and value)
+    Find { noun: String, filters: Vec<(String, String)> },
+    /// Dump a node or asset
+    Show(Target),
+    /// Show the node an asset is in
+    Where(AssetID),
+    /// Change the world
+    Edit(Edit),
+    /// Undo the last edit of the builder
+    Undo,
+    /// Show the latest changes to the world
+    Changes(usize),
+    /// Stage the edits of the builder from now on
+    Draft,
+    /// Show the nodes changed by the staged edits
+    Preview,
+    /// Apply the staged edits to the world
+    Publish,
+    /// Drop the staged edits
+    Discard,
+    /// Experiment in a copy of a zone, or leave it
+    Sandbox(SandboxStep),
+    /// Kill an NPC (it respawns as if killed by a player)
+    Slay(NpcID),
+    /// Set the limits of the casino (Some) or show them (None)
+    Casino(Option<Limits>),
+    /// Force a season on or off, or let it follow its dates again (None),
+    /// or show the seasons (no season)
+    Flags(Option<(String, Option<bool>)>),
+    /// Run a poll among the players online
+    Poll(PollStep),
+    /// Change the schedule of the con
+    Schedule(ScheduleStep),
+}
+
+impl AdminCommand {
+    /// True if players with the builder role may use the command
+    pub fn is_for_builders(&self) -> bool {
+        matches!(self, AdminCommand::Find { .. } | AdminCommand::Show(_) | AdminCommand::Where(_)
+            | AdminCommand::Edit(_) | AdminCommand::Undo | AdminCommand::Draft | AdminCommand::Preview
+            | AdminCommand::Publish | AdminCommand::Discard | AdminCommand::Sandbox(_))
+    }
+}
+
+/// Try to parse a string into an admin command
+impl TryFrom<&str> for AdminCommand {
+    type Error = Error;
+
+    fn try_from(item: &str) -> Result<Self, Error> {
+        let item = item.trim();
+        let item = match item.strip_prefix('@') {
+            Some(i) => i,
+            None => return Err(Error::InvalidCommand),
+        };
+        let (name, arguments) = match item.split_once(char::is_whitespace) {
+            Some((n, a)) => (n, a.trim()),
+            None => (item, ""),
+        };
+
+        match name.to_lowercase().as_str() {
+            "title" => {
+                let (player, title) = match arguments.split_once(char::is_whitespace) {
+                    Some((p, t)) => (p, Some(t.trim().to_string())),
+                    None => (arguments, None),
+                };
+                if player.is_empty() {
+                    return Err(Error::InvalidCommand);
+                }
+                Ok(AdminCommand::Title { player: player.to_string(), title })
+            },
+            "filter" => {
+                match arguments.to_lowercase().as_str() {
+                    "on" => Ok(AdminCommand::Filter(true)),
+                    "off" => Ok(AdminCommand::Filter(false)),
+                    _ => Err(Error::InvalidCommand),
+                }
+            },
+            "export" | "forget" | "token" => {
+                if arguments.is_empty() || arguments.contains(char::is_whitespace) {
+                    return Err(Error::InvalidCommand);
+                }
+                match name.to_lowercase().as_str() {
+                    "export" => Ok(AdminCommand::Export(arguments.to_string())),
+                    "forget" => Ok(AdminCommand::Forget(arguments.to_string())),
+                    _ => Ok(AdminCommand::Token(arguments.to_string())),
+                }
+            },
+            "channels" if arguments.is_empty() => Ok(AdminCommand::Channels),
+            "stats" if arguments.is_empty() => Ok(AdminCommand::Stats),
+            "economy" if arguments.is_empty() => Ok(AdminCommand::Economy),
+            "archive" if arguments.is_empty() => Ok(AdminCommand::Archive),
+            "coverage" if arguments.is_empty() => Ok(AdminCommand::Coverage(None)),
+            "coverage" if !arguments.contains(char::is_whitespace) => {
+                Ok(AdminCommand::Coverage(Some(arguments.to_string())))
+            },
+            "totals" => {
+                match arguments.to_lowercase().as_str() {
+                    "" => Ok(AdminCommand::Totals { announce: false }),
+                    "announce" => Ok(AdminCommand::Totals { announce: true }),
+                    _ => Err(Error::InvalidCommand),
+                }
+            },
+            "find" => {
+                let mut words = arguments.split_whitespace();
+                let noun = match words.next() {
+                    Some(n) if !n.contains('=') => n.to_lowercase(),
+                    _ => return Err(Error::InvalidCommand),
+                };
+                let mut filters = Vec::new();
+                for filter in words {
+                    match filter.split_once('=') {
+                        Some((k, v)) if !k.is_empty() && !v.is_empty() => filters.push((k.to_lowercase(), v.to_string())),
+                        _ => return Err(Error::InvalidCommand),
+                    }
+                }
+                Ok(AdminCommand::Find { noun, filters })
+            },
+            "show" | "where" => {
+                let (kind, uid) = match arguments.split_once(char::is_whitespace) {
+                    Some((k, u)) => (k.to_lowercase(), u.trim().parse::<AssetID>().map_err(|_| Error::InvalidCommand)?),
+                    None => return Err(Error::InvalidCommand),
+                };
+                match (name.to_lowercase().as_str(), kind.as_str()) {
+                    ("show", "node") => Ok(AdminCommand::Show(Target::Node(uid))),
+                    ("show", "asset") => Ok(AdminCommand::Show(Target::Asset(uid))),
+                    ("where", "asset") => Ok(AdminCommand::Where(uid)),
+                    _ => Err(Error::InvalidCommand),
+                }
+            },
+            "describe" => match arguments.split_once(char::is_whitespace) {
+                Some((uid, description)) => {
+                    let uid = uid.parse::<AssetID>().map_err(|_| Error::InvalidCommand)?;
+                    Ok(AdminCommand::Edit(Edit::Describe { uid, description: description.trim().to_string() }))
+                },
+                None => Err(Error::InvalidCommand),
+            },
+            "create" => {
+                let (kind, rest) = match arguments.split_once(char::is_whitespace) {
+                    Some((k, r)) => (k.to_lowercase(), r.trim()),
+                    None => return Err(Error::InvalidCommand),
+                };
+                let (kind, rest) = match (kind.as_str(), rest.split_once(char::is_whitespace)) {
+                    ("port", _) => (AssetKind::Port, rest),
+                    ("terminal", Some((hostname, r))) => (AssetKind::Terminal(hostname.to_string()), r.trim()),
+                    _ => return Err(Error::InvalidCommand),
+                };
+                let (node, description) = match rest.split_once(char::is_whitespace) {
+                    Some((n, d)) => (n, d.trim()),
+                    None => (rest, ""),
+                };
+                let node = node.parse::<AssetID>().map_err(|_| Error::InvalidCommand)?;
+                Ok(AdminCommand::Edit(Edit::Create { node, kind, description: description.to_string() }))
+            },
+            "acl" => {
+                let (node, rules) = match arguments.split_once(char::is_whitespace) {
+                    Some((n, r)) => (n.parse::<AssetID>().map_err(|_| Error::InvalidCommand)?, r.trim()),
+                    None => return Err(Error::InvalidCommand),
+                };
+                let acl = if rules.eq_ignore_ascii_case("open") { None } else { Some(Acl::try_from(rules)?) };
+                Ok(AdminCommand::Edit(Edit::Restrict { node, acl }))
+            },
+            "slay" => arguments.parse::<NpcID>().map(AdminCommand::Slay).map_err(|_| Error::InvalidCommand),
+            "flags" if arguments.is_empty() => Ok(AdminCommand::Flags(None)),
+            "flags" => {
+                let (season, state) = match arguments.split_once(char::is_whitespace) {
+                    Some((s, state)) => (s.to_lowercase(), state.trim().to_lowercase()),
+                    None => return Err(Error::InvalidCommand),
+                };
+                match state.as_str() {
+                    "on" => Ok(AdminCommand::Flags(Some((season, Some(true))))),
+                    "off" => Ok(AdminCommand::Flags(Some((season, Some(false))))),
+                    "auto" => Ok(AdminCommand::Flags(Some((season, None)))),
+                    _ => Err(Error::InvalidCommand),
+                }
+            },
+            "casino" if arguments.is_empty() => Ok(AdminCommand::Casino(None)),
+            "casino" => match arguments.split_once(char::is_whitespace) {
+                Some((wager, loss)) => {
+                    let max_wager = wager.parse::<u64>().map_err(|_| Error::InvalidCommand)?;
+                    let max_loss = loss.trim().parse::<u64>().map_err(|_| Error::InvalidCommand)?;
+                    Ok(AdminCommand::Casino(Some(Limits { max_wager, max_loss })))
+                },
+                None => Err(Error::InvalidCommand),
+            },
+            "undo" if arguments.is_empty() => Ok(AdminCommand::Undo),
+            "draft" if arguments.is_empty() => Ok(AdminCommand::Draft),
+            "preview" if arguments.is_empty() => Ok(AdminCommand::Preview),
+            "publish" if arguments.is_empty() => Ok(AdminCommand::Publish),
+            "discard" if arguments.is_empty() => Ok(AdminCommand::Discard),
+            "sandbox" => {
+                let mut words = arguments.split_whitespace();
+                let step = words.next().map(|w| w.to_lowercase());
+                match (step.as_deref(), words.next(), words.next()) {
+                    (Some("enter"), None, None) => Ok(AdminCommand::Sandbox(SandboxStep::Enter(None))),
+                    (Some("enter"), Some(uid), None) => uid.parse::<AssetID>()
+                        .map(|uid| AdminCommand::Sandbox(SandboxStep::Enter(Some(uid))))
+                        .map_err(|_| Error::InvalidCommand),
+                    (Some("promote"), None, None) => Ok(AdminCommand::Sandbox(SandboxStep::Promote)),
+                    (Some("discard"), None, None) => Ok(AdminCommand::Sandbox(SandboxStep::Discard)),
+                    _ => Err(Error::InvalidCommand),
+                }
+            },
+            "changes" if arguments.is_empty() => Ok(AdminCommand::Changes(10)),
+            "changes" => match arguments.parse::<usize>() {
+                Ok(count) if count > 0 => Ok(AdminCommand::Changes(count)),
+                _ => Err(Error::InvalidCommand),
+            },
+            "reset" if !arguments.is_empty() => Ok(AdminCommand::Reset(arguments.to_string())),
+            "poll" if arguments.is_empty() => Ok(AdminCommand::Poll(PollStep::Show)),
+            "poll" => match arguments.split_once(char::is_whitespace) {
+                Some((create, rest)) if create.eq_ignore_ascii_case("create") => {
+                    let mut parts = rest.split('|').map(str::trim);
+                    let question = parts.next().unwrap_or_default().to_string();
+                    let options: Vec<String> = parts.filter(|o| !o.is_empty()).map(str::to_string).collect();
+                    if question.is_empty() || options.is_empty() {
+                        return Err(Error::InvalidCommand);
+                    }
+                    Ok(AdminCommand::Poll(PollStep::Create { question, options }))
+                },
+                None if arguments.eq_ignore_ascii_case("close") => Ok(AdminCommand::Poll(PollStep::Close)),
+                _ => Err(Error::InvalidCommand),
+            },
+            "schedule" => match arguments.splitn(4, char::is_whitespace).collect::<Vec<&str>>().as_slice() {
+                [reload] if reload.eq_ignore_ascii_case("reload") => Ok(AdminCommand::Schedule(ScheduleStep::Reload)),
+                [remove, id] if remove.eq_ignore_ascii_case("remove") => id.trim_start_matches('#').parse::<u64>()
+                    .map(|id| AdminCommand::Schedule(ScheduleStep::Remove(id)))
+                    .map_err(|_| Error::InvalidCommand),
+                [add, kind, minutes, title] if add.eq_ignore_ascii_case("add") && !title.trim().is_empty() => {
+                    let kind = match kind.to_lowercase().as_str() {
+                        "con" => Kind::Con,
+                        "world" => Kind::World,
+                        _ => return Err(Error::InvalidCommand),
+                    };
+                    let minutes = minutes.parse::<u64>().map_err(|_| Error::InvalidCommand)?;
+                    Ok(AdminCommand::Schedule(ScheduleStep::Add { kind, minutes, title: title.trim().to_string() }))
+                },
+                _ => Err(Error::InvalidCommand),
+            },
+            "timers" if arguments.is_empty() => Ok(AdminCommand::Timers(None)),
+            "timers" => match arguments.split_whitespace().collect::<Vec<&str>>().as_slice() {
+                [cancel, id] if cancel.eq_ignore_ascii_case("cancel") => id.parse::<u64>()
+                    .map(|id| AdminCommand::Timers(Some(id)))
+                    .map_err(|_| Error::InvalidCommand),
+                _ => Err(Error::InvalidCommand),
+            },
+            "pause" if arguments.is_empty() => Ok(AdminCommand::Pause),
+            "resume" if arguments.is_empty() => Ok(AdminCommand::Resume),
+            "step" if arguments.is_empty() => Ok(AdminCommand::Step(1)),
+            "step" => match arguments.parse::<u32>() {
+                Ok(ticks) if ticks > 0 => Ok(AdminCommand::Step(ticks)),
+                _ => Err(Error::InvalidCommand),
+            },
+            "loglevel" => {
+                let mut directives = Vec::new();
+                for directive in arguments.split_whitespace() {
+                    let (target, level) = match directive.split_once('=') {
+                        Some((t, l)) if !t.is_empty() => (t, l),
+                        _ => return Err(Error::InvalidCommand),
+                    };
+                    let level = LevelFilter::from_str(level).map_err(|_| Error::InvalidCommand)?;
+                    directives.push((target.to_lowercase(), level));
+                }
+                Ok(AdminCommand::LogLevel(directives))
+            },
+            "banner" => {
+                let (font, text) = match arguments.strip_prefix("font=") {
+                    Some(rest) => match rest.split_once(char::is_whitespace) {
+                        Some((f, t)) => (Some(f.to_lowercase()), t.trim()),
+                        None => return Err(Error::InvalidCommand),
+                    },
+                    None => (None, arguments),
+                };
+                if text.is_empty() {
+                    return Err(Error::InvalidCommand);
+                }
+                Ok(AdminCommand::Banner { font, text: text.to_string() })
+            },
+            "spectate" => {
+                // Zone names may contain blanks, the switch is the last word
+                let (zone, enabled) = match arguments.rsplit_once(char::is_whitespace) {
+                    Some((z, s)) => (z.trim(), s.to_lowercase()),
+                    None => return Err(Error::InvalidCommand),
+                };
+                match enabled.as_str() {
+                    "on" => Ok(AdminCommand::Spectate { zone: zone.to_string(), enabled: true }),
+                    "off" => Ok(AdminCommand::Spectate { zone: zone.to_string(), enabled: false }),
+                    _ => Err(Error::InvalidCommand),
+                }
+            },
+            "maintenance" => {
+                let mut words = arguments.split_whitespace();
+                let switch = words.next().map(|w| w.to_lowercase());
+                let minutes = match words.next() {
+                    Some(m) => Some(m.parse::<u64>().map_err(|_| Error::InvalidCommand)?),
+                    None => None,
+                };
+                match (switch.as_deref(), minutes, words.next()) {
+                    (Some("on"), _, None) => Ok(AdminCommand::Maintenance { enabled: true, minutes }),
+                    (Some("off"), None, None) => Ok(AdminCommand::Maintenance { enabled: false, minutes: None }),
+                    _ => Err(Error::InvalidCommand),
+                }
+            },
+            _ => Err(Error::InvalidCommand),
+        }
+    }
+}
+
+/// Display an admin command
+impl fmt::Display for AdminCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AdminCommand::Title { player, title: Some(t) } => write!(f, "@title {} {}", player, t),
+            AdminCommand::Title { player, title: None } => write!(f, "@title {}", player),
+            AdminCommand::Filter(true) => write!(f, "@filter on"),
+            AdminCommand::Filter(false) => write!(f, "@filter off"),
+            AdminCommand::Export(p) => write!(f, "@export {}", p),
+            AdminCommand::Forget(p) => write!(f, "@forget {}", p),
+            AdminCommand::Channels => write!(f, "@channels"),
+            AdminCommand::Stats => write!(f, "@stats"),
+            AdminCommand::Economy => write!(f, "@economy"),
+            AdminCommand::Totals { announce: false } => write!(f, "@totals"),
+            AdminCommand::Totals { announce: true } => write!(f, "@totals announce"),
+            AdminCommand::Archive => write!(f, "@archive"),
+            AdminCommand::Coverage(None) => write!(f, "@coverage"),
+            AdminCommand::Coverage(Some(p)) => write!(f, "@coverage {}", p),
+            AdminCommand::LogLevel(directives) => {
+                write!(f, "@loglevel")?;
+                for (target, level) in directives {
+                    write!(f, " {}={}", target, level.to_string().to_lowercase())?;
+                }
+                Ok(())
+            },
+            AdminCommand::Banner { font: Some(font), text } => write!(f, "@banner font={} {}", font, text),
+            AdminCommand::Banner { font: None, text } => write!(f, "@banner {}", text),
+            AdminCommand::Spectate { zone, enabled: true } => write!(f, "@spectate {} on", zone),
+            AdminCommand::Spectate { zone, enabled: false } => write!(f, "@spectate {} off", zone),
+            AdminCommand::Token(p) => write!(f, "@token {}", p),
+            AdminCommand::Maintenance { enabled: true, minutes: Some(m) } => write!(f, "@maintenance on {}", m),
+            AdminCommand::Maintenance { enabled: true, minutes: None } => write!(f, "@maintenance on"),
+            AdminCommand::Maintenance { enabled: false, .. } => write!(f, "@maintenance off"),
+            AdminCommand::Pause => write!(f, "@pause"),
+            AdminCommand::Step(ticks) => write!(f, "@step {}", ticks),
+            AdminCommand::Resume => write!(f, "@resume"),
+            AdminCommand::Reset(zone) => write!(f, "@reset {}", zone),
+            AdminCommand::Timers(None) => write!(f, "@timers"),
+            AdminCommand::Timers(Some(id)) => write!(f, "@timers cancel {}", id),
+            AdminCommand::Find { noun, filters } => {
+                write!(f, "@find {}", noun)?;
+                for (key, value) in filters {
+                    write!(f, " {}={}", key, value)?;
+                }
+                Ok(())
+            },
+            AdminCommand::Show(Target::Node(uid)) => write!(f, "@show node {}", uid),
+            AdminCommand::Show(Target::Asset(uid)) => write!(f, "@show asset {}", uid),
+            AdminCommand::Where(uid) => write!(f, "@where asset {}", uid),
+            AdminCommand::Edit(Edit::Describe { uid, description }) => write!(f, "@describe {} {}", uid, description),
+            AdminCommand::Edit(Edit::Create { node, kind, description }) => {
+                match kind {
+                    AssetKind::Port => write!(f, "@create port {}", node)?,
+                    AssetKind::Terminal(hostname) => write!(f, "@create terminal {} {}", hostname, node)?,
+                }
+                if !description.is_empty() {
+                    write!(f, " {}", description)?;
+                }
+                Ok(())
+            },
+            AdminCommand::Edit(Edit::Restrict { node, acl: Some(acl) }) => write!(f, "@acl {} {}", node, acl),
+            AdminCommand::Edit(Edit::Restrict { node, acl: None }) => write!(f, "@acl {} open", node),
+            AdminCommand::Edit(e) => write!(f, "@{}", e),
+            AdminCommand::Undo => write!(f, "@undo"),
+            AdminCommand::Slay(uid) => write!(f, "@slay {}", uid),
+            AdminCommand::Flags(None) => write!(f, "@flags"),
+            AdminCommand::Flags(Some((season, Some(true)))) => write!(f, "@flags {} on", season),
+            AdminCommand::Flags(Some((season, Some(false)))) => write!(f, "@flags {} off", season),
+            AdminCommand::Flags(Some((season, None))) => write!(f, "@flags {} auto", season),
+            AdminCommand::Casino(None) => write!(f, "@casino"),
+            AdminCommand::Casino(Some(limits)) => write!(f, "@casino {}", limits),
+            AdminCommand::Draft => write!(f, "@draft"),
+            AdminCommand::Preview => write!(f, "@preview"),
+            AdminCommand::Publish => write!(f, "@publish"),
+            AdminCommand::Discard => write!(f, "@discard"),
+            AdminCommand::Sandbox(SandboxStep::Enter(None)) => write!(f, "@sandbox enter"),
+            AdminCommand::Sandbox(SandboxStep::Enter(Some(uid))) => write!(f, "@sandbox enter {}", uid),
+            AdminCommand::Sandbox(SandboxStep::Promote) => write!(f, "@sandbox promote"),
+            AdminCommand::Sandbox(SandboxStep::Discard) => write!(f, "@sandbox discard"),
+            AdminCommand::Changes(count) => write!(f, "@changes {}", count),
+            AdminCommand::Poll(PollStep::Show) => write!(f, "@poll"),
+            AdminCommand::Poll(PollStep::Create { question, options }) => {
+                write!(f, "@poll create {} | {}", question, options.join(" | "))
+            },
+            AdminCommand::Poll(PollStep::Close) => write!(f, "@poll close"),
+            AdminCommand::Schedule(ScheduleStep::Add { kind, minutes, title }) => {
+                write!(f, "@schedule add {} {} {}", kind, minutes, title)
+            },
+            AdminCommand::Schedule(ScheduleStep::Remove(id)) => write!(f, "@schedule remove {}", id),
+            AdminCommand::Schedule(ScheduleStep::Reload) => write!(f, "@schedule reload"),
+        }
+    }
+}
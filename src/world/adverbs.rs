@@ -0,0 +1,82 @@
+//! Adverbs
+//!
+//! The verbs of the grammar taking an object ("look", "enter", "connect",
+//! "access") take an adverb telling how the player acts, eg. "access quickly
+//! the terminal" or "look slowly at the port":
+//!  * quickly: the action takes half the time, but the haste leaves a trace,
+//!    the hostile NPCs in the node take it as threat (see `threat`)
+//!  * slowly: the action takes twice the time, but is stealthy, it does not
+//!    drop the cloak of the player (see `stealth`)
+//!
+//! An adverb may be repeated ("quickly, quickly"), contradicting adverbs are
+//! refused.
+
+use std::convert::TryFrom;
+use std::fmt;
+use std::time::Duration;
+
+use super::errors::Error;
+
+/// How a player acts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Adverb {
+    Quickly,
+    Slowly,
+}
+
+impl Adverb {
+    /// The time an action taking the given time takes when performed this
+    /// way
+    pub fn pace(&self, duration: Duration) -> Duration {
+        match self {
+            Adverb::Quickly => duration / 2,
+            Adverb::Slowly => duration * 2,
+        }
+    }
+}
+
+/// Parse an adverb
+impl TryFrom<&str> for Adverb {
+    type Error = Error;
+
+    fn try_from(item: &str) -> Result<Self, Error> {
+        match item.to_lowercase().as_str() {
+            "quickly" => Ok(Adverb::Quickly),
+            "slowly" => Ok(Adverb::Slowly),
+            _ => Err(Error::VerbEncodingError),
+        }
+    }
+}
+
+/// Display an adverb as typed
+impl fmt::Display for Adverb {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Adverb::Quickly => write!(f, "quickly"),
+            Adverb::Slowly => write!(f, "slowly"),
+        }
+    }
+}
+
+/// Split the adverbs off the start of the arguments of a verb
+///
+/// Returns the adverb, if any, and the arguments following it, or an error
+/// if the adverbs contradict each other.
+pub fn split(arguments: &str) -> Result<(Option<Adverb>, &str), Error> {
+    let separator = |c: char| c.is_whitespace() || c == ',';
+    let mut manner = None;
+    let mut rest = arguments.trim_start();
+    loop {
+        let end = rest.find(|c: char| separator(c) || c == '.').unwrap_or(rest.len());
+        let adverb = match Adverb::try_from(&rest[..end]) {
+            Ok(a) => a,
+            Err(_) => break,
+        };
+        if manner.is_some_and(|m| m != adverb) {
+            return Err(Error::VerbEncodingError);
+        }
+        manner = Some(adverb);
+        rest = rest[end..].trim_start_matches(separator);
+    }
+    Ok((manner, rest))
+}
@@ -0,0 +1,211 @@
+//! AI
+//!
+//! Behaviors of NPCs, eg. hostile ICE. The behaviors of a prototype are
+//! evaluated on every tick for each of its NPCs, in order of priority like
+//! the selector of a behavior tree: an NPC tracing a player pursues it, an
+//! NPC without a target watches its node and patrols.
+//!
+//! Every NPC keeps what it knows in its blackboard (eg. the player it traces,
+//! since when it sees the players in its node and the threat of the players
+//! fighting it). An NPC targets the player with the most threat (see
+//! `threat`). Thinking only decides what the NPC does, the world carries it
+//! out (see `Act`).
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use generational_arena::Index;
+use tokio::time::Instant;
+
+use super::assets::AssetID;
+use super::threat::Threat;
+
+/// A behavior of NPCs
+#[derive(Debug, Clone, PartialEq)]
+pub enum Behavior {
+    /// Move along the nodes of a route, to the next node every interval
+    #[allow(dead_code)]
+    Patrol { route: Vec<Index>, interval: Duration },
+    /// Trace players touching the asset (eg. solving the puzzle of a port)
+    /// and players wanted by security in the node (see `security`)
+    Guard(AssetID),
+    /// Trace players that stay in the node for the given time and follow
+    /// the player traced to its node
+    Pursue { trace_time: Duration },
+    /// Call the given number of NPCs of the same prototype when tracing a
+    /// player
+    Reinforce(usize),
+    /// Strike the target in the node every interval, jamming its deck for
+    /// the given time
+    Strike { interval: Duration, jam: Duration },
+    /// Check the node for cloaked players every interval, wearing their
+    /// cloaks down by the perception (see `stealth`)
+    Perceive { interval: Duration, perception: u32 },
+}
+
+/// What an NPC knows
+#[derive(Debug, Clone, Default)]
+pub struct Blackboard {
+    /// The player the NPC traces
+    pub target: Option<String>,
+    /// Since when the NPC sees the players in its node, by name
+    seen: HashMap<String, Instant>,
+    /// Index of the node on the patrol route the NPC is at
+    waypoint: usize,
+    /// When the NPC moves on along its route
+    next_move: Option<Instant>,
+    /// The NPC called reinforcements for its target
+    pub reinforced: bool,
+    /// The threat of the players fighting the NPC
+    pub threat: Threat,
+    /// When the NPC strikes its target next
+    next_strike: Option<Instant>,
+    /// When the NPC checks for cloaked players next
+    next_check: Option<Instant>,
+}
+
+/// What an NPC decided to do
+#[derive(Debug, Clone, PartialEq)]
+pub enum Act {
+    /// Move to another node
+    Move(Index),
+    /// Start tracing a player
+    Trace(String),
+    /// Lose the trace of a player (eg. the player left the world)
+    Lose(String),
+    /// Call reinforcements
+    Reinforce(usize),
+    /// Strike a player
+    Strike { player: String, jam: Duration },
+    /// Check the node for cloaked players with a perception
+    Perceive(u32),
+}
+
+/// What NPCs perceive of the world on a tick
+#[derive(Debug, Default)]
+pub struct View {
+    /// The players and the nodes they are in, without the cloaked players
+    pub players: Vec<(String, Index)>,
+    /// The nodes cloaked players are in
+    pub cloaked: Vec<Index>,
+    /// The assets players touched since the last tick, the node and player
+    pub touched: Vec<(Index, AssetID, String)>,
+    /// The players that acted quickly since the last tick and their node
+    pub hurried: Vec<(Index, String)>,
+    /// The players heard making noise since the last tick and the nodes
+    /// they are heard in (see `propagation`)
+    pub heard: Vec<(Index, String)>,
+    /// The players caught stealing since the last tick and their node (see
+    /// `ownership`)
+    pub caught: Vec<(Index, String)>,
+    /// The players with heat (see `security`)
+    pub wanted: Vec<String>,
+}
+
+impl View {
+    /// The node a player is in, if the player is in the world
+    fn location_of(&self, player: &str) -> Option<Index> {
+        self.players.iter().find(|(p, _)| p == player).map(|(_, l)| *l)
+    }
+}
+
+/// Decide what an NPC in a node does on a tick
+pub fn think(behaviors: &[Behavior], location: Index, blackboard: &mut Blackboard, view: &View,
+             now: Instant) -> Vec<Act> {
+    let mut acts = Vec::new();
+
+    // Forget players that left the world or the node
+    blackboard.threat.retain(|p| view.location_of(p).is_some());
+    if let Some(target) = &blackboard.target {
+        if view.location_of(target).is_none() {
+            acts.push(Act::Lose(target.clone()));
+            blackboard.target = None;
+            blackboard.reinforced = false;
+        }
+    }
+    blackboard.seen.retain(|p, _| view.location_of(p) == Some(location));
+
+    // Turn on the player with the most threat
+    if let Some(top) = blackboard.threat.top() {
+        if blackboard.target.as_deref() != Some(top) {
+            blackboard.target = Some(top.to_string());
+            acts.push(Act::Trace(top.to_string()));
+        }
+    }
+
+    for behavior in behaviors {
+        match behavior {
+            Behavior::Guard(asset) if blackboard.target.is_none() => {
+                let touching = view.touched.iter().find(|(n, a, _)| *n == location && a == asset).map(|(_, _, p)| p);
+                let wanted = view.players.iter()
+                    .find(|(p, l)| *l == location && view.wanted.contains(p))
+                    .map(|(p, _)| p);
+                if let Some(player) = touching.or(wanted) {
+                    blackboard.target = Some(player.clone());
+                    acts.push(Act::Trace(player.clone()));
+                }
+            },
+            Behavior::Pursue { trace_time } => match &blackboard.target {
+                None => {
+                    for (player, _) in view.players.iter().filter(|(_, l)| *l == location) {
+                        blackboard.seen.entry(player.clone()).or_insert(now);
+                    }
+                    let traced = blackboard.seen.iter()
+                        .filter(|(_, since)| now.saturating_duration_since(**since) >= *trace_time)
+                        .map(|(p, _)| p.clone())
+                        .min();
+                    if let Some(player) = traced {
+                        blackboard.target = Some(player.clone());
+                        acts.push(Act::Trace(player));
+                    }
+                },
+                Some(target) => match view.location_of(target) {
+                    Some(node) if node != location => acts.push(Act::Move(node)),
+                    _ => {},
+                },
+            },
+            Behavior::Reinforce(count) if blackboard.target.is_some() && !blackboard.reinforced => {
+                blackboard.reinforced = true;
+                acts.push(Act::Reinforce(*count));
+            },
+            Behavior::Patrol { route, interval } if blackboard.target.is_none() && !route.is_empty() => {
+                match blackboard.next_move {
+                    Some(t) if t <= now => {
+                        blackboard.waypoint = (blackboard.waypoint + 1) % route.len();
+                        blackboard.next_move = Some(now + *interval);
+                        if route[blackboard.waypoint] != location {
+                            acts.push(Act::Move(route[blackboard.waypoint]));
+                        }
+                    },
+                    Some(_) => {},
+                    None => blackboard.next_move = Some(now + *interval),
+                }
+            },
+            Behavior::Strike { interval, jam } => {
+                let target = blackboard.target.as_deref().filter(|t| view.location_of(t) == Some(location));
+                match (target, blackboard.next_strike) {
+                    (Some(target), Some(t)) if t <= now => {
+                        blackboard.next_strike = Some(now + *interval);
+                        acts.push(Act::Strike { player: target.to_string(), jam: *jam });
+                    },
+                    (Some(_), Some(_)) => {},
+                    (Some(_), None) => blackboard.next_strike = Some(now + *interval),
+                    (None, _) => blackboard.next_strike = None,
+                }
+            },
+            Behavior::Perceive { interval, perception } => {
+                match (view.cloaked.contains(&location), blackboard.next_check) {
+                    (true, Some(t)) if t <= now => {
+                        blackboard.next_check = Some(now + *interval);
+                        acts.push(Act::Perceive(*perception));
+                    },
+                    (true, Some(_)) => {},
+                    (true, None) => blackboard.next_check = Some(now + *interval),
+                    (false, _) => blackboard.next_check = None,
+                }
+            },
+            _ => {},
+        }
+    }
+    acts
+}
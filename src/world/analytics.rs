@@ -0,0 +1,175 @@
+//! Analytics
+//!
+//! If enabled in the settings (opt-in), the world counts what the players do,
+//! to guide improvements of the grammar and the content:
+//!  * actions: the actions performed by verb, and how many of them failed
+//!    (ie. the world answered with an error)
+//!  * misunderstood commands: input the grammar did not parse, by reason
+//!    (an unknown verb or invalid arguments to a known verb)
+//!  * popular nodes: the actions performed in a node, by the uid of the node
+//!
+//! Only aggregate counters are kept, never the text the players typed or who
+//! typed it. The counters are exported as metrics (see `telemetry`) and
+//! logged as a summary at most once per interval.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::Instant;
+
+use super::assets::AssetID;
+use super::errors::Error;
+
+/// Number of verbs and nodes listed in the summary
+const SUMMARY_LENGTH: usize = 5;
+
+/// How often the actions of a verb were performed
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Usage {
+    pub performed: u64,
+    /// Actions answered with an error
+    pub failed: u64,
+}
+
+impl Usage {
+    /// Share of the actions that failed, from 0 to 1
+    pub fn failure_rate(&self) -> f64 {
+        if self.performed == 0 { 0.0 } else { self.failed as f64 / self.performed as f64 }
+    }
+}
+
+/// Why the grammar did not parse a command
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Misunderstood {
+    UnknownVerb,
+    InvalidArguments,
+}
+
+impl From<&Error> for Misunderstood {
+    fn from(error: &Error) -> Misunderstood {
+        match error {
+            Error::VerbUnknownError => Misunderstood::UnknownVerb,
+            _ => Misunderstood::InvalidArguments,
+        }
+    }
+}
+
+impl fmt::Display for Misunderstood {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Misunderstood::UnknownVerb => write!(f, "unknown_verb"),
+            Misunderstood::InvalidArguments => write!(f, "invalid_arguments"),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    actions: BTreeMap<&'static str, Usage>,
+    misunderstood: BTreeMap<Misunderstood, u64>,
+    nodes: BTreeMap<AssetID, u64>,
+    logged: Option<Instant>,
+}
+
+/// The counters of the analytics
+///
+/// Clones share the counters, so the world can count while the telemetry
+/// exports them. Counting does nothing unless the analytics are enabled.
+#[derive(Debug, Clone, Default)]
+pub struct Analytics {
+    enabled: bool,
+    /// Shortest time between two summaries in the log
+    interval: Duration,
+    counters: Arc<Mutex<Counters>>,
+}
+
+impl Analytics {
+    /// Create analytics that count and log a summary at most once per
+    /// interval
+    pub fn enabled(interval: Duration) -> Analytics {
+        Analytics { enabled: true, interval, counters: Arc::default() }
+    }
+
+    /// Count an action performed in a node, and if it failed
+    pub fn action(&self, verb: &'static str, node: Option<AssetID>, failed: bool) {
+        if !self.enabled {
+            return;
+        }
+        let mut counters = self.counters.lock().unwrap_or_else(|e| e.into_inner());
+        let usage = counters.actions.entry(verb).or_default();
+        usage.performed += 1;
+        usage.failed += u64::from(failed);
+        if let Some(uid) = node {
+            *counters.nodes.entry(uid).or_default() += 1;
+        }
+    }
+
+    /// Count a command the grammar did not parse
+    pub fn misunderstood(&self, error: &Error) {
+        if !self.enabled {
+            return;
+        }
+        let mut counters = self.counters.lock().unwrap_or_else(|e| e.into_inner());
+        *counters.misunderstood.entry(Misunderstood::from(error)).or_default() += 1;
+    }
+
+    /// The usage of the verbs
+    pub fn actions(&self) -> Vec<(&'static str, Usage)> {
+        let counters = self.counters.lock().unwrap_or_else(|e| e.into_inner());
+        counters.actions.iter().map(|(verb, usage)| (*verb, *usage)).collect()
+    }
+
+    /// The commands not parsed, by reason
+    pub fn misunderstood_commands(&self) -> Vec<(Misunderstood, u64)> {
+        let counters = self.counters.lock().unwrap_or_else(|e| e.into_inner());
+        counters.misunderstood.iter().map(|(reason, count)| (*reason, *count)).collect()
+    }
+
+    /// The actions performed by node, the most popular node first
+    pub fn nodes(&self) -> Vec<(AssetID, u64)> {
+        let counters = self.counters.lock().unwrap_or_else(|e| e.into_inner());
+        let mut nodes: Vec<(AssetID, u64)> = counters.nodes.iter().map(|(uid, count)| (*uid, *count)).collect();
+        nodes.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        nodes
+    }
+
+    /// The summary to log, if the analytics are enabled and the last one was
+    /// logged at least an interval ago
+    pub fn summary(&self, now: Instant) -> Option<String> {
+        if !self.enabled {
+            return None;
+        }
+        {
+            let mut counters = self.counters.lock().unwrap_or_else(|e| e.into_inner());
+            if counters.logged.is_some_and(|t| now.duration_since(t) < self.interval) {
+                return None;
+            }
+            counters.logged = Some(now);
+        }
+        Some(self.to_string())
+    }
+}
+
+/// Display the most used verbs with their failure rates, the commands not
+/// parsed and the most popular nodes
+impl fmt::Display for Analytics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut actions = self.actions();
+        actions.sort_by(|a, b| b.1.performed.cmp(&a.1.performed).then(a.0.cmp(b.0)));
+        let verbs: Vec<String> = actions.iter()
+            .take(SUMMARY_LENGTH)
+            .map(|(verb, usage)| format!("{} {} ({:.0}% failed)", verb, usage.performed,
+                usage.failure_rate() * 100.0))
+            .collect();
+        let misunderstood: Vec<String> = self.misunderstood_commands().iter()
+            .map(|(reason, count)| format!("{} {}", reason, count))
+            .collect();
+        let nodes: Vec<String> = self.nodes().iter()
+            .take(SUMMARY_LENGTH)
+            .map(|(uid, count)| format!("#{} {}", uid, count))
+            .collect();
+        write!(f, "verbs: {}; misunderstood: {}; nodes: {}", verbs.join(", "), misunderstood.join(", "),
+            nodes.join(", "))
+    }
+}
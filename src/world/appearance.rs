@@ -0,0 +1,124 @@
+//! Appearance
+//!
+//! What other players see when they look at a player ("look at neo", or
+//! "look at me" for the own avatar). Players describe their avatar with
+//! "describe me <text>" (validated like titles, see `handles`) and clear the
+//! description with "describe me". The appearance shows:
+//!  * tags: the factions the player is known to (see `economy`) and the
+//!    challenge run it takes part in (see `challenges`)
+//!  * the description of the avatar
+//!  * equipment highlights: the deployed drone and a line of flair for each
+//!    cosmetic item the player carries (eg. a neon jacket bought from a
+//!    vendor). The wardrobe of the world lists the cosmetic items and their
+//!    flair.
+
+use std::collections::BTreeMap;
+use tracing::error;
+
+use crate::connection_manager::ClientId;
+use crate::world::messages::{Event, Message};
+use crate::world::{GameWorld, Player};
+
+/// What a player without a description looks like
+pub const DEFAULT_DESCRIPTION: &str = "A decker in a plain, grey default avatar.";
+
+/// Reputation from which a player is tagged with a faction
+pub const TAG_REPUTATION: i64 = 10;
+
+/// What others see of a player
+#[derive(Debug, Clone, Default)]
+pub struct Profile<'a> {
+    /// The name the player is shown with (see `Player::display_name`)
+    pub name: String,
+    /// The description of the avatar
+    pub description: Option<&'a str>,
+    /// The items the player carries
+    pub items: &'a [String],
+    /// The model of the deployed drone of the player
+    pub drone: Option<&'a str>,
+    /// Reputation of the player with factions
+    pub reputation: Option<&'a BTreeMap<String, i64>>,
+    /// The challenge the player is on a run of
+    pub run: Option<&'a str>,
+}
+
+impl Profile<'_> {
+    /// The tags of the player, eg. "[runners]" or "[run lobby-rush]"
+    pub fn tags(&self) -> Vec<String> {
+        let factions = self.reputation.into_iter()
+            .flatten()
+            .filter(|(_, r)| **r >= TAG_REPUTATION)
+            .map(|(f, _)| format!("[{}]", f));
+        factions.chain(self.run.map(|r| format!("[run {}]", r))).collect()
+    }
+}
+
+/// The cosmetic items of the world and their flair
+#[derive(Debug, Clone, Default)]
+pub struct Wardrobe {
+    /// Flair by the item (lowercase)
+    flair: BTreeMap<String, String>,
+}
+
+impl Wardrobe {
+    /// Make an item cosmetic, adding a line of flair to whoever carries it
+    pub fn with_flair(mut self, item: &str, flair: &str) -> Wardrobe {
+        self.flair.insert(item.to_lowercase(), flair.to_string());
+        self
+    }
+
+    /// The flair of the cosmetic items among the items carried, each once
+    pub fn flair<'a>(&'a self, items: &[String]) -> Vec<&'a str> {
+        let mut flair: Vec<&str> = Vec::new();
+        for f in items.iter().filter_map(|i| self.flair.get(&i.to_lowercase())) {
+            if !flair.contains(&f.as_str()) {
+                flair.push(f);
+            }
+        }
+        flair
+    }
+
+    /// The appearance of a player
+    pub fn appearance(&self, profile: &Profile<'_>) -> String {
+        let mut text = profile.name.clone();
+        for tag in profile.tags() {
+            text += " ";
+            text += &tag;
+        }
+        text += "\r\n";
+        text += profile.description.unwrap_or(DEFAULT_DESCRIPTION);
+        if let Some(model) = profile.drone {
+            text += &format!("\r\n  A {} drone hovers at their shoulder.", model);
+        }
+        for flair in self.flair(profile.items) {
+            text += "\r\n  ";
+            text += flair;
+        }
+        text
+    }
+}
+
+/// Describe the avatar of a player or clear its description
+pub(super) async fn describe(description: Option<String>, world: &GameWorld, client_id: ClientId, player: &mut Player) {
+    let result = match &description {
+        Some(d) => world.handle_rules.validate_description(d),
+        None => Ok(()),
+    };
+    match result {
+        Ok(_) => {
+            player.account.description = description;
+            if let Err(e) = player.account.save(&world.data_dir).await {
+                error!("Could not save account of {}: {}", player.player_name, e);
+            }
+            let run = world.challenges.joined(client_id).map(|r| r.challenge());
+            let appearance = world.wardrobe.appearance(&player.profile(run));
+            player.send(Message::new(Event::Info, &format!("Others now see you as:\r\n{}", appearance))
+                .with_field("description", player.account.description.clone())).await;
+        },
+        Err(e) => {
+            let message = format!("The matrix does not accept this description ({}). \
+                Descriptions are at most {} characters.", e, world.handle_rules.max_description_length);
+            player.send(Message::new(Event::Error, &message)).await;
+        },
+    }
+}
@@ -0,0 +1,181 @@
+//! Archive
+//!
+//! At the end of the con the admins export the world as archive
+//! ("@archive"), to publish what happened and to seed the world of next
+//! year. The archive is a JSON document written to the export directory (see
+//! `privacy`). It holds no personal data: the leaderboards only tell how
+//! many deckers a run had, the boards only carry the announcements of the
+//! staff and the statistics are the totals of all accounts (see `stats`).
+//!
+//! The format of the archive (version 1):
+//! ```ignore
+//!     {
+//!       "format": 1,
+//!       "timestamp": <seconds since the epoch>,
+//!       "world": [{
+//!         "uid": <uid>, "name": <name>, "fields": [[<key>, <value>], ...],
+//!         "season": <season> | null, "safe": <bool>, "min_level": <level>,
+//!         "assets": [{ "uid": <uid>, "name": <name>, "fields": [[<key>, <value>], ...] }, ...]
+//!       }, ...],
+//!       "leaderboards": { <challenge>: {
+//!         "description": <text>,
+//!         "records": [{ "rank": <rank>, "team": <deckers>, "millis": <time>, "timestamp": <seconds> }, ...]
+//!       }, ... },
+//!       "boards": [{ "title": <title>, "text": <text>, "timestamp": <seconds> }, ...],
+//!       "statistics": { "visitors": <count>, "players": <count>, "sessions": <count>,
+//!                       "played_s": <seconds>, "commands": <count>, "nodes": <count> }
+//!     }
+//! ```
+//! The fields of nodes and assets are the fields the staff inspects and
+//! searches (see `inspect`), eg. ["hostname", "sphinx"] for a terminal. The
+//! boards only reach back as far as the news kept in memory (see `news`),
+//! newest first.
+
+use std::collections::BTreeMap;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use generational_arena::Arena;
+
+use super::assets::{AssetID, GameAsset, Node};
+use super::challenges::Challenges;
+use super::inspect;
+use super::news::{self, News};
+use super::privacy::EXPORT_DIR;
+use super::stats::Totals;
+use super::timestamp;
+
+/// Version of the format of the archive
+pub const ARCHIVE_FORMAT: u32 = 1;
+
+/// The world at the end of the con
+#[derive(Debug, Serialize)]
+pub struct Archive {
+    /// Version of the format
+    pub format: u32,
+    /// Seconds since the epoch when the archive was made
+    pub timestamp: u64,
+    /// The nodes of the world
+    pub world: Vec<ArchivedNode>,
+    /// The leaderboards by challenge
+    pub leaderboards: BTreeMap<String, ArchivedLeaderboard>,
+    /// The announcements of the staff, newest first
+    pub boards: Vec<Post>,
+    /// What all players did together
+    pub statistics: Statistics,
+}
+
+/// A node of the world
+#[derive(Debug, Serialize)]
+pub struct ArchivedNode {
+    pub uid: AssetID,
+    pub name: String,
+    pub fields: Vec<(String, String)>,
+    pub season: Option<String>,
+    pub safe: bool,
+    pub min_level: u64,
+    /// The assets in the node
+    pub assets: Vec<ArchivedAsset>,
+}
+
+/// An asset in a node
+#[derive(Debug, Serialize)]
+pub struct ArchivedAsset {
+    pub uid: AssetID,
+    pub name: String,
+    pub fields: Vec<(String, String)>,
+}
+
+/// The leaderboard of a challenge, without the names of the players
+#[derive(Debug, Serialize)]
+pub struct ArchivedLeaderboard {
+    pub description: String,
+    /// The results, fastest first
+    pub records: Vec<Entry>,
+}
+
+/// A result on a leaderboard
+#[derive(Debug, Serialize)]
+pub struct Entry {
+    /// The rank, starting at 1
+    pub rank: usize,
+    /// Number of deckers in the run
+    pub team: usize,
+    /// The time the run took in milliseconds
+    pub millis: u64,
+    /// Seconds since the epoch when the run was won
+    pub timestamp: u64,
+}
+
+/// An announcement of the staff
+#[derive(Debug, Serialize)]
+pub struct Post {
+    pub title: String,
+    pub text: String,
+    /// Seconds since the epoch when it was announced
+    pub timestamp: u64,
+}
+
+/// The totals of the activity of all players
+#[derive(Debug, Serialize)]
+pub struct Statistics {
+    /// Deckers that jacked in during the con
+    pub visitors: usize,
+    /// Players that played at least one session
+    pub players: u64,
+    pub sessions: u64,
+    pub played_s: u64,
+    pub commands: u64,
+    /// Nodes visited by anybody
+    pub nodes: usize,
+}
+
+impl Archive {
+    /// Archive the world
+    pub fn new(nodes: &Arena<Node>, challenges: &Challenges, news: &News, totals: &Totals, visitors: usize) -> Archive {
+        let world = nodes.iter()
+            .map(|(_, node)| ArchivedNode {
+                uid: node.uid(),
+                name: node.name().to_string(),
+                fields: inspect::fields(node),
+                season: node.season().map(str::to_string),
+                safe: node.safety().safe,
+                min_level: node.safety().min_level,
+                assets: node.assets()
+                    .map(|a| ArchivedAsset { uid: a.uid(), name: a.name().to_string(), fields: inspect::fields(a) })
+                    .collect(),
+            })
+            .collect();
+        let leaderboards = challenges.all().iter()
+            .map(|c| {
+                let records = challenges.leaderboard.records(c.id()).iter()
+                    .enumerate()
+                    .map(|(i, r)| Entry { rank: i + 1, team: r.players.len(), millis: r.millis,
+                        timestamp: r.timestamp })
+                    .collect();
+                (c.id().to_string(), ArchivedLeaderboard { description: c.describe(), records })
+            })
+            .collect();
+        let boards = news.latest(news::MAX_NEWS).into_iter()
+            .filter(|i| i.kind == news::Kind::Announcement)
+            .map(|i| Post { title: i.title, text: i.text, timestamp: i.timestamp })
+            .collect();
+        let statistics = Statistics {
+            visitors,
+            players: totals.players,
+            sessions: totals.sessions,
+            played_s: totals.played_s,
+            commands: totals.commands,
+            nodes: totals.nodes(),
+        };
+        Archive { format: ARCHIVE_FORMAT, timestamp: timestamp(), world, leaderboards, boards, statistics }
+    }
+
+    /// Write the archive to the export directory and return its path
+    pub async fn save(&self, data_dir: &Path) -> io::Result<PathBuf> {
+        tokio::fs::create_dir_all(data_dir.join(EXPORT_DIR)).await?;
+        let path = data_dir.join(EXPORT_DIR).join(format!("archive-{}.json", self.timestamp));
+        tokio::fs::write(&path, serde_json::to_vec_pretty(self)?).await?;
+        Ok(path)
+    }
+}
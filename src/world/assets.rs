@@ -4,13 +4,109 @@
 //!  * Node (the "room" that contain stuff)
 //!  * Port (entry and exit points from nodes)
 //!  * Connection (connections between ports that allow to travel from and to nodes)
+//!
+//! Assets react to the actions of players (see `GameAsset::react_to`) and may
+//! change their state doing so, eg. a port a player opens. The response tells
+//! the world what changed, what the others in the node see and which effects
+//! follow for the player (see `AssetResponse`).
 
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use generational_arena::Index;
+
+use super::acl::Acl;
 use super::actions::Action;
+use super::fuzzy;
+use super::hooks::Hook;
+use super::ice::Ice;
+use super::intern::intern;
+use super::ownership::Ownership;
+use super::Identifiable;
 use super::properties::Property;
+use super::puzzles::{self, Puzzle};
+use super::safety::Safety;
+use super::terminals::Terminal;
+use super::timers::Effect;
 
 // TODO start using generational indices
 pub type AssetID = u64;
 
+/// Time a port opened by a player stays open for everybody
+pub const HOLD_OPEN: Duration = Duration::from_secs(5 * 60);
+
+/// The player acting on an asset
+#[derive(Debug, Clone, Copy)]
+pub struct Actor<'a> {
+    name: &'a str,
+    puzzles: &'a BTreeMap<String, puzzles::Progress>,
+}
+
+impl<'a> Actor<'a> {
+    /// The player of the name, with its progress on the puzzles
+    pub fn new(name: &'a str, puzzles: &'a BTreeMap<String, puzzles::Progress>) -> Actor<'a> {
+        Actor { name, puzzles }
+    }
+
+    /// True if the player solved the puzzle
+    pub fn has_solved(&self, puzzle: &Puzzle) -> bool {
+        self.puzzles.get(puzzle.id()).is_some_and(|p| p.solved)
+    }
+}
+
+/// A change of the state of an asset reacting to an action
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StateChange {
+    /// The port of the uid was opened
+    Opened(AssetID),
+}
+
+/// The response of an asset to an action
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AssetResponse {
+    /// Told to the player acting
+    pub text: String,
+    /// How the asset changed
+    pub changes: Vec<StateChange>,
+    /// Told to the others in the node
+    pub events: Vec<String>,
+    /// Run for the player acting afterwards (see `hooks`)
+    pub effects: Vec<Hook>,
+}
+
+impl AssetResponse {
+    /// Note a change of the asset
+    pub fn with_change(mut self, change: StateChange) -> AssetResponse {
+        self.changes.push(change);
+        self
+    }
+
+    /// Tell the others in the node what happened
+    pub fn with_event(mut self, event: String) -> AssetResponse {
+        self.events.push(event);
+        self
+    }
+
+    /// Run an effect for the player acting afterwards
+    pub fn with_effect(mut self, effect: Hook) -> AssetResponse {
+        self.effects.push(effect);
+        self
+    }
+}
+
+impl From<String> for AssetResponse {
+    fn from(text: String) -> AssetResponse {
+        AssetResponse { text, ..AssetResponse::default() }
+    }
+}
+
+impl From<&str> for AssetResponse {
+    fn from(text: &str) -> AssetResponse {
+        AssetResponse::from(text.to_string())
+    }
+}
+
 /// Trait that is common to all game assets
 pub trait GameAsset : std::fmt::Debug + Send + Sync {
     /// UID
@@ -21,11 +117,12 @@ pub trait GameAsset : std::fmt::Debug + Send + Sync {
     /// Name
     /// 
     /// Get the name of the asset
-    fn name(&self) -> String;
+    fn name(&self) -> &str;
 
     /// Properties
     /// 
     /// Return the properties of the asset
+    #[allow(dead_code)]
     fn properties(&self) -> Option<&Vec<Property>>;
 
     /// Describe
@@ -37,40 +134,199 @@ pub trait GameAsset : std::fmt::Debug + Send + Sync {
     /// TODO - maybe remove as redundant due to interact
     fn describe(&self) -> String;
 
+    /// Description
+    /// 
+    /// The description of the asset as written by its builder, without what
+    /// `describe` adds (eg. whether a port is open).
+    fn description(&self) -> &str;
+
+    /// Rewrite
+    /// 
+    /// Replace the description of the asset (eg. by a builder).
+    fn rewrite(&mut self, description: &str);
+
     /// React to
     /// 
-    /// React to an interaction with the game asset. Interaction are based on
-    /// verbs. The object responses to the verb by returning an Option, NONE
-    /// if the object canot reatct to this and Some() if it does.
+    /// React to an interaction of a player with the game asset. Interaction
+    /// are based on verbs. The asset may change its state (eg. a port
+    /// opening) and responds with the text for the player, the changes, what
+    /// the others in the node see and the effects that follow. An empty text
+    /// if the asset cannot react to this.
+    fn react_to(&mut self, actor: &Actor<'_>, a: &Action) -> AssetResponse;
+
+    /// Sound
+    /// 
+    /// The sound played to players interacting with the asset, if any (see
+    /// `media`).
+    fn sound(&self) -> Option<&str>;
+
+    /// Terminal
+    /// 
+    /// The asset as terminal, if it is one (see `terminals`).
+    fn terminal(&self) -> Option<&Terminal>;
+
+    /// Port
+    /// 
+    /// The asset as port, if it is one.
+    fn port(&self) -> Option<&Port>;
+
+    /// Port mut
+    /// 
+    /// The asset as port, to change it (eg. to connect it to a node).
+    fn port_mut(&mut self) -> Option<&mut Port>;
+
+    /// Puzzle
+    /// 
+    /// The puzzle attached to the asset, if any (see `puzzles`).
+    fn puzzle(&self) -> Option<&Puzzle>;
+
+    /// Portal
+    /// 
+    /// The name of the linked world entering the asset leads to, if any
+    /// (see `federation`).
+    fn portal(&self) -> Option<&str>;
+
+    /// Takeable
+    /// 
+    /// True if players may take the asset and carry it (see `inventory`).
+    fn is_takeable(&self) -> bool;
+
+    /// Ownership
+    /// 
+    /// The account the asset belongs to, if any (see `ownership`).
+    fn ownership(&self) -> Option<&Ownership> {
+        None
+    }
+
+    /// ICE
+    /// 
+    /// The asset as ICE guarding a port, if it is ICE (see `ice`).
+    fn ice(&self) -> Option<&Ice> {
+        None
+    }
+
+    /// Verbs
+    /// 
+    /// The verbs (see `Action::verb`) the asset supports. Actions aimed at
+    /// the asset with other verbs are refused, and looking at the asset
+    /// lists what else players can do with it.
+    fn verbs(&self) -> Vec<&'static str>;
+
+    /// Supports
+    /// 
+    /// True if the asset supports the verb.
+    fn supports(&self, verb: &str) -> bool {
+        self.verbs().contains(&verb)
+    }
+
+    /// Clone box
     /// 
-    /// TODO - maybe add the subject that does the interaction to the signature
-    /// TOTO - return a more generic result than String
-    fn react_to(&self, a: &Action) -> String;
+    /// A copy of the asset (eg. for the shadow copies of drafts, see
+    /// `edits`).
+    fn clone_box(&self) -> Box<dyn GameAsset>;
+
+    /// Renumber
+    /// 
+    /// Give the asset another uid (eg. a copy in a sandbox, see
+    /// `sandboxes`).
+    fn renumber(&mut self, uid: AssetID);
+}
+
+impl Clone for Box<dyn GameAsset> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
 }
 
 /// Structure that descibes a node
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Node {
     uid: AssetID,
-    name: String,
+    name: Arc<str>,
     properties: Option<Vec<Property>>,
-    description: String,
+    description: Arc<str>,
+    sound: Option<Arc<str>>,
     sub_assets: Vec<Box<dyn GameAsset>>,
+    acl: Option<Acl>,
+    season: Option<Arc<str>>,
+    safety: Safety,
+    security: u32,
 }
 
 impl Node {
     /// Create a new empty node
     pub fn new(uid: AssetID) -> Node {
-        let name = String::from("");
+        let name = intern("");
         let properties = None;
-        let description = String::from("");
+        let description = intern("");
         let sub_assets = Vec::new();
-        Node { uid, name, properties, description, sub_assets }
+        Node { uid, name, properties, description, sound: None, sub_assets, acl: None, season: None,
+            safety: Safety::default(), security: 0 }
     }
 
     /// Update the description of the node
     pub fn update_description(&mut self, description: &str) {
-        self.description = String::from(description);
+        self.description = intern(description);
+    }
+
+    /// Update the name of the node (shown as title when entering it)
+    pub fn update_name(&mut self, name: &str) {
+        self.name = intern(name);
+    }
+
+    /// Update the sound played when looking around in the node
+    pub fn update_sound(&mut self, sound: Option<&str>) {
+        self.sound = sound.map(intern);
+    }
+
+    /// Restrict who may enter the node (or let everybody enter it)
+    pub fn update_acl(&mut self, acl: Option<Acl>) {
+        self.acl = acl;
+    }
+
+    /// Who may enter the node, if it is restricted
+    pub fn acl(&self) -> Option<&Acl> {
+        self.acl.as_ref()
+    }
+
+    /// Only open the node while a season runs (or always)
+    pub fn update_season(&mut self, season: Option<&str>) {
+        self.season = season.map(intern);
+    }
+
+    /// The season the node is open in (see `seasons`)
+    pub fn season(&self) -> Option<&str> {
+        self.season.as_deref()
+    }
+
+    /// Update the safety rules of the node
+    pub fn update_safety(&mut self, safety: Safety) {
+        self.safety = safety;
+    }
+
+    /// The safety rules of the node (see `safety`)
+    pub fn safety(&self) -> Safety {
+        self.safety
+    }
+
+    /// Update the security level of the node (0 for none)
+    pub fn update_security(&mut self, security: u32) {
+        self.security = security;
+    }
+
+    /// The security level of the node (see `security`)
+    pub fn security(&self) -> u32 {
+        self.security
+    }
+
+    /// Add a property to the node (eg. its lighting)
+    pub fn add_property(&mut self, property: Property) {
+        self.properties.get_or_insert_with(Vec::new).push(property);
+    }
+
+    /// The sub assets of the node, in the order they were added
+    pub fn assets(&self) -> impl Iterator<Item = &dyn GameAsset> {
+        self.sub_assets.iter().map(|a| a.as_ref())
     }
 
     /// Add a port to this node. If the node already has this port nothing
@@ -85,7 +341,197 @@ impl Node {
     /// Remove a port from this node. If a port is multiple times in the node,
     /// then all occurences will be removed (as this should never be the case).
     pub fn remove_asset(&mut self, asset_uid: AssetID) {
-        self.sub_assets.retain(|a| a.uid() == asset_uid);
+        self.sub_assets.retain(|a| a.uid() != asset_uid);
+    }
+
+    /// Take a sub asset out of this node (eg. an item a player picks up)
+    pub fn take_asset(&mut self, asset_uid: AssetID) -> Option<Box<dyn GameAsset>> {
+        let position = self.sub_assets.iter().position(|a| a.uid() == asset_uid)?;
+        Some(self.sub_assets.remove(position))
+    }
+
+    /// The sub assets of the node, to change them
+    pub fn assets_mut(&mut self) -> impl Iterator<Item = &mut dyn GameAsset> {
+        self.sub_assets.iter_mut().map(|a| a.as_mut() as &mut dyn GameAsset)
+    }
+
+    /// The sub asset with the given uid, to change it
+    pub fn asset_mut(&mut self, asset_uid: AssetID) -> Option<&mut dyn GameAsset> {
+        self.sub_assets.iter_mut()
+            .find(|a| a.uid() == asset_uid)
+            .map(|a| a.as_mut() as &mut dyn GameAsset)
+    }
+
+    /// Find assets
+    /// 
+    /// Returns all sub assets of this node that can be referenced by the given
+    /// noun (or its plural, see `fuzzy`), in the order they were added to the
+    /// node. The order is what numbered references ("port 2", "2.port") count
+    /// on.
+    pub fn find_assets(&self, noun: &str) -> Vec<&dyn GameAsset> {
+        self.sub_assets.iter()
+            .filter(|a| a.is_object(noun))
+            .map(|a| a.as_ref())
+            .collect()
+    }
+
+    /// Find asset
+    /// 
+    /// Returns the asset referenced by a noun and an optional ordinal
+    /// (counting from one), or the text telling the player why there is
+    /// no such asset. A mistyped noun is resolved to the closest name of the
+    /// assets (see `fuzzy`), or the player is asked which one it meant.
+    pub fn find_asset(&self, noun: &str, ordinal: Option<usize>) -> Result<&dyn GameAsset, String> {
+        let (noun, matches) = self.resolve(noun)?;
+        Node::pick(matches, &noun, ordinal)
+    }
+
+    /// Find described asset
+    /// 
+    /// Like `find_asset`, but only the assets having all the given
+    /// properties match (eg. the purple port for "look at the purple port",
+    /// see `Identifiable`).
+    pub fn find_described(&self, noun: &str, properties: &[Property], ordinal: Option<usize>)
+            -> Result<&dyn GameAsset, String> {
+        if properties.is_empty() {
+            return self.find_asset(noun, ordinal);
+        }
+        let (noun, mut matches) = self.resolve(noun)?;
+        matches.retain(|a| properties.iter().all(|p| a.has_property(p)));
+        let adjectives: Vec<String> = properties.iter().map(Property::value).collect();
+        Node::pick(matches, &format!("{} {}", adjectives.join(" "), noun), ordinal)
+    }
+
+    /// The assets referenced by a noun, a mistyped noun resolved to the
+    /// closest name of the assets (see `fuzzy`)
+    /// 
+    /// Returns the noun resolved and the assets, or the question which asset
+    /// the player meant.
+    fn resolve(&self, noun: &str) -> Result<(String, Vec<&dyn GameAsset>), String> {
+        let matches = self.find_assets(noun);
+        if !matches.is_empty() {
+            return Ok((noun.to_string(), matches));
+        }
+        let resolved = fuzzy::resolve(noun, self.sub_assets.iter().map(|a| a.name()))
+            .map_err(|names| format!("There is no {} here. {}", noun, fuzzy::question(&names)))?;
+        match resolved {
+            Some(name) => Ok((name.to_string(), self.find_assets(name))),
+            None => Ok((noun.to_string(), matches)),
+        }
+    }
+
+    /// The asset of the matches an ordinal picks (the only one if None), or
+    /// why there is no such asset
+    fn pick<'a>(matches: Vec<&'a dyn GameAsset>, noun: &str, ordinal: Option<usize>)
+            -> Result<&'a dyn GameAsset, String> {
+        match ordinal {
+            Some(n) => n.checked_sub(1)
+                .and_then(|i| matches.get(i).copied())
+                .ok_or_else(|| format!("There is no {} {} here.", noun, n)),
+            None => match matches.len() {
+                0 => Err(format!("There is no {} here.", noun)),
+                1 => Ok(matches[0]),
+                _ => Err(format!("There is more than one {} here. Which one do you mean \
+                    (eg. \"{} 2\" or \"2.{}\")?", noun, noun, noun)),
+            },
+        }
+    }
+
+    /// Refusal
+    /// 
+    /// Why the asset an action is aimed at does not support it (eg. "You
+    /// cannot access the port."). None if it does, or if there is no such
+    /// asset: the action tells the player itself.
+    pub fn refusal(&self, a: &Action) -> Option<String> {
+        let (noun, ordinal) = a.target()?;
+        let asset = self.find_asset(noun, ordinal).ok()?;
+        if asset.supports(a.verb()) {
+            return None;
+        }
+        let verb = match a {
+            Action::Connect { .. } => "connect to",
+            Action::Hint { .. } => "get a hint for",
+            _ => a.verb(),
+        };
+        Some(format!("You cannot {} the {}.", verb, asset.name()))
+    }
+
+    /// Find portal
+    /// 
+    /// Returns the first asset leading to another world, if there is one.
+    pub fn find_portal(&self) -> Option<&dyn GameAsset> {
+        self.sub_assets.iter()
+            .map(|a| a.as_ref())
+            .find(|a| a.portal().is_some())
+    }
+
+    /// Find exit
+    /// 
+    /// Returns the port leading to another node of the world, if there is
+    /// exactly one.
+    pub fn find_exit(&self) -> Option<&dyn GameAsset> {
+        let mut exits = self.sub_assets.iter()
+            .map(|a| a.as_ref())
+            .filter(|a| a.port().is_some_and(|p| p.destination().is_some()));
+        match (exits.next(), exits.next()) {
+            (Some(exit), None) => Some(exit),
+            _ => None,
+        }
+    }
+
+    /// Exits
+    /// 
+    /// The nodes of the world the ports of this node lead to.
+    pub fn exits(&self) -> impl Iterator<Item = Index> + '_ {
+        self.sub_assets.iter().filter_map(|a| a.port().and_then(|p| p.destination()))
+    }
+
+    /// Glimpse
+    /// 
+    /// What is seen of the node from afar (eg. through a port): its name,
+    /// the first sentence of its description and the nouns of its assets,
+    /// but not their descriptions.
+    pub fn glimpse(&self) -> String {
+        let first = self.description.split_inclusive(['.', '!', '?']).next().unwrap_or_default().trim();
+        let mut glimpse = match self.name.is_empty() {
+            true => first.to_string(),
+            false => format!("{}: {}", self.name, first),
+        };
+        let nouns: Vec<String> = self.sub_assets.iter().map(|a| format!("a {}", a.name())).collect();
+        if !nouns.is_empty() {
+            glimpse += &format!("\r\nYou make out {}.", nouns.join(", "));
+        }
+        glimpse
+    }
+
+    /// Find terminal
+    /// 
+    /// Returns the terminal with the given uid, if it is in this node.
+    pub fn find_terminal(&self, uid: AssetID) -> Option<&Terminal> {
+        self.sub_assets.iter()
+            .filter_map(|a| a.terminal())
+            .find(|t| t.uid() == uid)
+    }
+
+    /// Puzzles
+    /// 
+    /// Returns the puzzles attached to the assets of this node.
+    pub fn puzzles(&self) -> impl Iterator<Item = &Puzzle> {
+        self.sub_assets.iter().filter_map(|a| a.puzzle())
+    }
+
+    /// Sound for
+    /// 
+    /// The sound played for an action, if any: the sound of the node when
+    /// looking around, the sound of the asset looked at otherwise.
+    pub fn sound_for(&self, a: &Action) -> Option<&str> {
+        match a {
+            Action::Look { target: None, .. } => self.sound(),
+            Action::Look { target: Some(t), ordinal, .. } => {
+                self.find_asset(t, *ordinal).ok().and_then(|a| a.sound())
+            },
+            _ => None,
+        }
     }
 }
 
@@ -99,72 +545,169 @@ impl GameAsset for Node {
     /// 
     /// Node numbers are usually not known by default, but once discovered
     /// they may be used to manipulate nodes or fast travel.
-    fn name(&self) -> String {
-        self.name.clone()
+    fn name(&self) -> &str {
+        &self.name
     }
 
     /// Returns the properties of the node
     /// 
     /// TODO - maybe use some node properties to induce eg. damage to player
     fn properties(&self) -> Option<&Vec<Property>> {
-        match &self.properties {
-            Some(p) => Some(&p),
-            None => None,
-        }
+        self.properties.as_ref()
     }
 
     /// Describes the room an all visible objects in it
     /// 
     /// TODO - deal with empty descriptions
     fn describe(&self) -> String {
-        self.description.clone()
+        self.description.to_string()
+    }
+
+    /// Returns the description of the node
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    /// Replace the description of the node
+    fn rewrite(&mut self, description: &str) {
+        self.update_description(description);
     }
 
     /// React to
     /// 
-    /// Response to interactions with this node depending on the verb
-    fn react_to(&self, a: &Action) -> String {
+    /// Response to interactions with this node depending on the verb.
+    /// Opening is relayed to the closed port of the node the player solved.
+    fn react_to(&mut self, actor: &Actor<'_>, a: &Action) -> AssetResponse {
         match a {
             Action::Look{ target: None, ..} => {
-                let mut description = format!("{}\r\n", self.description.clone());
+                let mut description = String::with_capacity(256);
+                description.push_str(&self.description);
+                description.push_str("\r\n");
                 for asset in self.sub_assets.iter() {
-                    description += format!("{}\r\n", asset.describe()).as_str();
+                    description.push_str(&asset.describe());
+                    description.push_str("\r\n");
                 }
-                description
+                description.into()
             },
-            Action::Look{ target: Some(t), preposition, properties} => {
-                // TODO
-                let description = format!("Not implemented!\r\n");
-                description
-            }
-            Action::Read => format!("Read what?"),
-            Action::Enter => format!("Enter what?"),
-            Action::Connect => format!("Connect to what?"),
-            Action::Access => format!("Access what?"),
-            Action::Open => format!("Open what?"),
+            Action::Look{ target: Some(t), ordinal, properties, ..} => {
+                match self.find_described(t, properties.as_deref().unwrap_or_default(), *ordinal) {
+                    Ok(asset) => {
+                        let verbs: Vec<&str> = asset.verbs().into_iter().filter(|v| *v != "look").collect();
+                        match verbs.is_empty() {
+                            true => asset.describe().into(),
+                            false => format!("{}\r\nTry: {}.", asset.describe(), verbs.join(", ")).into(),
+                        }
+                    },
+                    Err(reason) => reason.into(),
+                }
+            },
+            Action::Read => "Read what?".into(),
+            Action::Enter { .. } => "Enter what?".into(),
+            Action::Connect { .. } => "Connect to what?".into(),
+            Action::Access { .. } => "Access what?".into(),
+            Action::Solve { .. } | Action::Hint { .. } => "There is no puzzle here.".into(),
+            Action::Open => {
+                let guarded: Vec<AssetID> = self.sub_assets.iter().filter_map(|a| a.ice()).map(Ice::protects).collect();
+                let solved = self.sub_assets.iter_mut()
+                    .find(|a| a.port().is_some_and(|p| !p.is_open() && p.puzzle().is_some_and(|z| actor.has_solved(z))));
+                match solved {
+                    Some(port) if guarded.contains(&port.uid()) => "The ICE on the port holds it shut.".into(),
+                    Some(port) => port.react_to(actor, a),
+                    None => "There is no closed port here you solved.".into(),
+                }
+            },
+            // Handled by the world, never relayed to assets
+            Action::Stop | Action::Quit | Action::Robot(_) | Action::Bug(_) 
+                | Action::Note(_) | Action::Journal(_) | Action::Title(_) | Action::Describe(_) 
+                | Action::Ignore(_) | Action::Unignore(_) | Action::Prefs(_) | Action::Score | Action::Goals
+                | Action::Vote(_) | Action::Schedule(_)
+                | Action::Take { .. } | Action::Steal { .. } | Action::Drop { .. } | Action::Inventory
+                | Action::Help { .. }
+                | Action::Plugin { .. }
+                | Action::Who | Action::Say(_) | Action::Shout(_) | Action::Filter(_)
+                | Action::Transcript(_) | Action::Trigger(_) | Action::Untrigger(_)
+                | Action::Bind { .. } | Action::Animations(_) | Action::Media(_) 
+                | Action::Mute(_) | Action::Challenge(_) | Action::Join(_) 
+                | Action::Leaderboard(_) | Action::Watch(_) | Action::Tell { .. } 
+                | Action::Hack { .. } | Action::Taunt { .. } | Action::Combat(_) 
+                | Action::Attack { .. } | Action::Crack { .. }
+                | Action::Cloak(_) | Action::Scan | Action::Drone(_) | Action::Market(_)
+                | Action::Mail | Action::MailTranscript | Action::Bank(_) | Action::Gamble(_) | Action::Vendor(_)
+                | Action::Bribe => AssetResponse::default(),
         }
     }
+
+    /// Returns the ambient sound of the node
+    fn sound(&self) -> Option<&str> {
+        self.sound.as_deref()
+    }
+
+    /// A node is not a terminal
+    fn terminal(&self) -> Option<&Terminal> {
+        None
+    }
+
+    /// A node is not a port
+    fn port(&self) -> Option<&Port> {
+        None
+    }
+
+    /// A node is not a port
+    fn port_mut(&mut self) -> Option<&mut Port> {
+        None
+    }
+
+    /// Puzzles are attached to the assets in a node, not the node itself
+    fn puzzle(&self) -> Option<&Puzzle> {
+        None
+    }
+
+    /// A node is not a portal
+    fn portal(&self) -> Option<&str> {
+        None
+    }
+
+    /// A node cannot be carried
+    fn is_takeable(&self) -> bool {
+        false
+    }
+
+    /// A node is looked around in
+    fn verbs(&self) -> Vec<&'static str> {
+        vec!["look"]
+    }
+
+    /// Returns a copy of the node and its assets
+    fn clone_box(&self) -> Box<dyn GameAsset> {
+        Box::new(self.clone())
+    }
+
+    /// Gives the node another uid, its assets keep theirs
+    fn renumber(&mut self, uid: AssetID) {
+        self.uid = uid;
+    }
 }
 
 /// Port
 /// 
-/// A port is used to move from one node to others. A port can be connected to
-///     * no other nodes (NONE)
-///     * one other node
-///     * multiple other nodes
-/// TODO - we need to somehow implement how to choose the destination node if 
-///         a port leads to multiple other nodes.
+/// A port is used to move from one node to another. A port can be connected to
+///     * no other node (NONE)
+///     * one other node, players entering the port move there
+///
 /// Ports can either be open - thus accessible - or closed - and thus inaccessible.
-/// A closed port can be protected by ICE or other means. In that case in order to use
-/// the port the barrier must first be removed. 
+/// A closed port can be protected by a puzzle. In that case in order to use
+/// the port the puzzle must first be solved. 
 /// TODO - decide if we need to add visibility flag or if we update description for visibility changes
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Port {
     id: AssetID,
     properties: Option<Vec<Property>>,
     is_open: bool,
-    connects_to: Option<Vec<Node>>,
-    description: String,
+    connects_to: Option<Index>,
+    description: Arc<str>,
+    sound: Option<Arc<str>>,
+    puzzle: Option<Puzzle>,
+    portal: Option<Arc<str>>,
     // TODO: Protections etc.....
 }
 
@@ -176,17 +719,63 @@ impl Port {
             properties: None,
             is_open: false,
             connects_to: None,
-            description: format!(""),
+            description: intern(""),
+            sound: None,
+            puzzle: None,
+            portal: None,
         }
     }
 
     /// Get the id 
     /// TODO - remove
+    #[allow(dead_code)]
     pub fn get_id(&self) -> AssetID { self.id }
 
     /// Describe a port
     pub fn update_description(&mut self, description: &str) {
-        self.description = String::from(description);
+        self.description = intern(description);
+    }
+
+    /// Update the sound played when interacting with the port (eg. a door
+    /// opening)
+    pub fn update_sound(&mut self, sound: Option<&str>) {
+        self.sound = sound.map(intern);
+    }
+
+    /// Add a property to the port (eg. its color)
+    pub fn add_property(&mut self, property: Property) {
+        self.properties.get_or_insert_with(Vec::new).push(property);
+    }
+
+    /// Attach a puzzle to the port (or remove it)
+    pub fn update_puzzle(&mut self, puzzle: Option<Puzzle>) {
+        self.puzzle = puzzle;
+    }
+
+    /// Make the port a portal to a linked world (or a plain port again)
+    pub fn update_portal(&mut self, world: Option<&str>) {
+        self.portal = world.map(intern);
+    }
+
+    /// Open or close the port
+    pub fn update_open(&mut self, open: bool) {
+        self.is_open = open;
+    }
+
+    /// True if the port is open to everybody, a closed port only lets the
+    /// players pass that solved its puzzle
+    pub fn is_open(&self) -> bool {
+        self.is_open
+    }
+
+    /// Connect the port to a node of the world (or disconnect it)
+    pub fn connect(&mut self, node: Option<Index>) {
+        self.connects_to = node;
+    }
+
+    /// The node of the world the port leads to, if any
+    pub fn destination(&self) -> Option<Index> {
+        self.connects_to
     }
 }
 
@@ -199,53 +788,159 @@ impl GameAsset for Port {
     /// Returns the port id
     /// 
     /// TODO - maybe replace with something else?
-    fn name(&self) -> String {
-        "port".to_string()
+    fn name(&self) -> &str {
+        "port"
     }
 
     /// Returns the properties of the node
     /// 
     /// TODO - maybe use some node properties to induce eg. damage to player
     fn properties(&self) -> Option<&Vec<Property>> {
-        match &self.properties {
-            Some(p) => Some(&p),
-            None => None,
-        }
+        self.properties.as_ref()
     }
 
     /// Describe the port
     fn describe(&self) -> String {
         //TODO
-        if self.is_open {
+        let mut description = if self.is_open {
             format!("{} The port is open.", self.description)
         } else {
             format!("{} The port is closed.", self.description)
+        };
+        if let Some(puzzle) = &self.puzzle {
+            description.push(' ');
+            description.push_str(&puzzle.describe());
         }
+        description
+    }
+
+    /// Returns the description of the port
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    /// Replace the description of the port
+    fn rewrite(&mut self, description: &str) {
+        self.update_description(description);
     }
 
     /// React to
     /// 
-    /// Response to interactions with this node depending on the verb
-    fn react_to(&self, a: &Action) -> String {
+    /// Response to interactions with this node depending on the verb. A
+    /// player that solved the port opens it for everybody, it closes again
+    /// after `HOLD_OPEN`.
+    fn react_to(&mut self, actor: &Actor<'_>, a: &Action) -> AssetResponse {
         match a {
             Action::Look { target: None, .. } => {
                 if self.is_open {
-                    format!("{}\n The port is open.", self.description)
+                    format!("{}\n The port is open.", self.description).into()
                 } else {
-                    format!("{}\n The port is closed.", self.description)
+                    format!("{}\n The port is closed.", self.description).into()
+                }
+            },
+            Action::Look { target: Some(t), properties, .. } => {
+                // A port holds no assets, it can only be looked at itself
+                let described = properties.iter().flatten().all(|p| self.has_property(p));
+                match (self.is_object(t), described, self.is_open) {
+                    (true, true, true) => format!("{}\n The port is open.", self.description).into(),
+                    (true, true, false) => format!("{}\n The port is closed.", self.description).into(),
+                    _ => format!("There is no {} on the port.", t).into(),
                 }
             },
-            Action::Look{ target: Some(_t), preposition, properties} => {
-                // TODO -- try to find out what child object the interacting thing wants to
-                // look at.
-                let description = format!("Not implemented!\r\n");
-                description
-            }
-            Action::Read => format!("Read what?"),
-            Action::Enter => format!("Enter what?"),
-            Action::Connect => format!("Connect to what?"),
-            Action::Access => format!("Access what?"),
-            Action::Open => format!("Open what?"),
+            Action::Read => "Read what?".into(),
+            Action::Enter { .. } => "Enter what?".into(),
+            Action::Connect { .. } => "Connect to what?".into(),
+            Action::Access { .. } => "Access what?".into(),
+            Action::Solve { .. } | Action::Hint { .. } => "There is no puzzle here.".into(),
+            Action::Open if self.is_open => "The port is open already.".into(),
+            Action::Open => match &self.puzzle {
+                Some(puzzle) if actor.has_solved(puzzle) => {
+                    self.is_open = true;
+                    let text = format!("You open the port for everybody. It holds open for {} minutes.",
+                        HOLD_OPEN.as_secs() / 60);
+                    AssetResponse::from(text)
+                        .with_change(StateChange::Opened(self.id))
+                        .with_event(format!("{} opens the port.", actor.name))
+                        .with_effect(Hook::Timer { after: HOLD_OPEN, effect: Effect::Close { port: self.id } })
+                },
+                _ => "You cannot open the port, solve it first.".into(),
+            },
+            // Handled by the world, never relayed to assets
+            Action::Stop | Action::Quit | Action::Robot(_) | Action::Bug(_) 
+                | Action::Note(_) | Action::Journal(_) | Action::Title(_) | Action::Describe(_) 
+                | Action::Ignore(_) | Action::Unignore(_) | Action::Prefs(_) | Action::Score | Action::Goals
+                | Action::Vote(_) | Action::Schedule(_)
+                | Action::Take { .. } | Action::Steal { .. } | Action::Drop { .. } | Action::Inventory
+                | Action::Help { .. }
+                | Action::Plugin { .. }
+                | Action::Who | Action::Say(_) | Action::Shout(_) | Action::Filter(_)
+                | Action::Transcript(_) | Action::Trigger(_) | Action::Untrigger(_)
+                | Action::Bind { .. } | Action::Animations(_) | Action::Media(_) 
+                | Action::Mute(_) | Action::Challenge(_) | Action::Join(_) 
+                | Action::Leaderboard(_) | Action::Watch(_) | Action::Tell { .. } 
+                | Action::Hack { .. } | Action::Taunt { .. } | Action::Combat(_) 
+                | Action::Attack { .. } | Action::Crack { .. }
+                | Action::Cloak(_) | Action::Scan | Action::Drone(_) | Action::Market(_)
+                | Action::Mail | Action::MailTranscript | Action::Bank(_) | Action::Gamble(_) | Action::Vendor(_)
+                | Action::Bribe => AssetResponse::default(),
         }
     }
+
+    /// Returns the sound of the port
+    fn sound(&self) -> Option<&str> {
+        self.sound.as_deref()
+    }
+
+    /// A port is not a terminal
+    fn terminal(&self) -> Option<&Terminal> {
+        None
+    }
+
+    /// A port is a port
+    fn port(&self) -> Option<&Port> {
+        Some(self)
+    }
+
+    /// A port is a port
+    fn port_mut(&mut self) -> Option<&mut Port> {
+        Some(self)
+    }
+
+    /// Returns the puzzle locking the port
+    fn puzzle(&self) -> Option<&Puzzle> {
+        self.puzzle.as_ref()
+    }
+
+    /// Returns the world the port leads to, if it is a portal
+    fn portal(&self) -> Option<&str> {
+        self.portal.as_deref()
+    }
+
+    /// A port cannot be carried
+    fn is_takeable(&self) -> bool {
+        false
+    }
+
+    /// A port is entered, connected to if it leads to a node of the world
+    /// and solved if a puzzle locks it
+    fn verbs(&self) -> Vec<&'static str> {
+        let mut verbs = vec!["look", "enter"];
+        if self.connects_to.is_some() {
+            verbs.push("connect");
+        }
+        if self.puzzle.is_some() {
+            verbs.extend(["solve", "hint"]);
+        }
+        verbs
+    }
+
+    /// Returns a copy of the port
+    fn clone_box(&self) -> Box<dyn GameAsset> {
+        Box::new(self.clone())
+    }
+
+    /// Gives the port another uid
+    fn renumber(&mut self, uid: AssetID) {
+        self.id = uid;
+    }
 }
\ No newline at end of file
@@ -0,0 +1,142 @@
+//! Bank
+//!
+//! Players keep credits and items safe in the vault of their account. The
+//! vault is reached at the banks of the world (eg. the banker in the lobby):
+//! "deposit <amount|item>" moves what the player carries into the vault,
+//! "withdraw <amount|item>" takes it out again and "balance" shows both.
+//! What lies in the vault is stored with the account and never lost, unlike
+//! what the player carries.
+
+use std::collections::HashMap;
+use std::fmt;
+use tracing::info;
+
+use crate::connection_manager::ClientId;
+use super::accounts::Account;
+use super::actions::Action;
+use super::messages::{Event, Message};
+use super::{save_account, GameWorld, Player};
+
+/// Credits or an item moved between the player and its vault
+#[derive(Debug, Clone, PartialEq)]
+pub enum Funds {
+    Credits(u64),
+    Item(String),
+}
+
+/// Display funds as typed by the player
+impl fmt::Display for Funds {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Funds::Credits(credits) => write!(f, "{} credits", credits),
+            Funds::Item(item) => write!(f, "{}", item),
+        }
+    }
+}
+
+/// An order to the bank
+#[derive(Debug, Clone, PartialEq)]
+pub enum Banking {
+    /// Show the credits and items carried and in the vault
+    Balance,
+    /// Move funds into the vault
+    Deposit(Funds),
+    /// Take funds out of the vault
+    Withdraw(Funds),
+}
+
+/// Display an order as typed by the player
+impl fmt::Display for Banking {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Banking::Balance => write!(f, "balance"),
+            Banking::Deposit(funds) => write!(f, "deposit {}", funds),
+            Banking::Withdraw(funds) => write!(f, "withdraw {}", funds),
+        }
+    }
+}
+
+/// The credits and items an account keeps in the bank
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Vault {
+    pub credits: u64,
+    pub items: Vec<String>,
+}
+
+/// Move funds the player carries into its vault
+///
+/// Returns the funds deposited, None if the player does not carry them.
+pub fn deposit(account: &mut Account, funds: &Funds) -> Option<Funds> {
+    let Account { credits, items, vault, .. } = account;
+    transfer(credits, items, &mut vault.credits, &mut vault.items, funds)
+}
+
+/// Take funds out of the vault of the player
+///
+/// Returns the funds withdrawn, None if the vault does not hold them.
+pub fn withdraw(account: &mut Account, funds: &Funds) -> Option<Funds> {
+    let Account { credits, items, vault, .. } = account;
+    transfer(&mut vault.credits, &mut vault.items, credits, items, funds)
+}
+
+/// Move funds from one purse to another, the item matched ignoring case
+fn transfer(from_credits: &mut u64, from_items: &mut Vec<String>, to_credits: &mut u64, to_items: &mut Vec<String>,
+            funds: &Funds) -> Option<Funds> {
+    match funds {
+        Funds::Credits(amount) => {
+            *from_credits = from_credits.checked_sub(*amount)?;
+            *to_credits += amount;
+            Some(funds.clone())
+        },
+        Funds::Item(item) => {
+            let position = from_items.iter().position(|i| i.eq_ignore_ascii_case(item))?;
+            let item = from_items.remove(position);
+            to_items.push(item.clone());
+            Some(Funds::Item(item))
+        },
+    }
+}
+
+/// Deposit to, withdraw from or show the vault of the player
+pub(super) async fn visit(a: &Action, world: &GameWorld, client_id: ClientId,
+                          players: &mut HashMap<ClientId, Player>) {
+    let banking = match a {
+        Action::Bank(b) => b,
+        _ => return,
+    };
+    let player = match players.get_mut(&client_id) {
+        Some(p) => p,
+        None => return,
+    };
+    info!("Player {} is performing action {}.", player.player_name, a);
+    if !player.location.is_some_and(|l| world.banks.contains(&l)) {
+        player.send(Message::new(Event::Error, "There is no bank here.")).await;
+        return;
+    }
+    let message = match banking {
+        Banking::Balance => {
+            let account = &player.account;
+            let list = |items: &[String]| if items.is_empty() { "nothing else".to_string() } else { items.join(", ") };
+            let text = format!("You carry {} credits and {}.\nYour vault holds {} credits and {}.", account.credits,
+                list(&account.items), account.vault.credits, list(&account.vault.items));
+            Message::new(Event::Response, &text)
+                .with_field("credits", account.credits)
+                .with_field("vault", &account.vault)
+        },
+        Banking::Deposit(funds) => match deposit(&mut player.account, funds) {
+            Some(funds) => {
+                save_account(world, player).await;
+                Message::new(Event::Response, &format!("You deposit {} in your vault.", funds))
+            },
+            None => Message::new(Event::Error, &format!("You do not carry {}.", funds)),
+        },
+        Banking::Withdraw(funds) => match withdraw(&mut player.account, funds) {
+            Some(funds) => {
+                save_account(world, player).await;
+                Message::new(Event::Response, &format!("You withdraw {} from your vault.", funds))
+            },
+            None => Message::new(Event::Error, &format!("Your vault does not hold {}.", funds)),
+        },
+    };
+    player.send(message).await;
+}
@@ -0,0 +1,294 @@
+//! Banners
+//!
+//! Renders text as large ASCII art at runtime (eg. for announcements of the
+//! staff and the titles of zones), so not every banner has to be drawn as a
+//! static screen. Banners are drawn with fonts in the FIGlet format (.flf).
+//! A block font is bundled with the server, more fonts are loaded from the
+//! font directory at startup and known by the name of their file (eg.
+//! "standard" for "standard.flf").
+//!
+//! Characters are fitted together as FIGlet does for its "fitting" layout.
+//! Fonts asking for smushing are fitted as well, so they render slightly
+//! wider than with figlet.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use tracing::{debug, error, info};
+
+use super::errors::Error;
+use super::loader;
+
+/// The font bundled with the server
+const BUNDLED_FONT: (&str, &str) = ("block", include_str!("../../fonts/block.flf"));
+
+/// Codes of the German characters following the ASCII characters in a font
+const GERMAN_CODES: [u32; 7] = [196, 214, 220, 228, 246, 252, 223];
+
+/// Width of a banner if not configured
+const DEFAULT_MAX_WIDTH: usize = 80;
+
+/// A font in the FIGlet format
+#[derive(Debug, Clone)]
+pub struct Font {
+    height: usize,
+    hardblank: char,
+    fitting: bool,
+    glyphs: HashMap<char, Vec<String>>,
+}
+
+impl Font {
+    /// Parse a font from the content of a .flf file
+    pub fn parse(data: &str) -> Result<Font, Error> {
+        let mut lines = data.lines();
+        let header = lines.next().ok_or(Error::InvalidFont)?;
+        let rest = header.strip_prefix("flf2a").ok_or(Error::InvalidFont)?;
+        let mut chars = rest.chars();
+        let hardblank = chars.next().ok_or(Error::InvalidFont)?;
+        let fields: Vec<i64> = chars.as_str()
+            .split_whitespace()
+            .map(|f| f.parse::<i64>().map_err(|_| Error::InvalidFont))
+            .collect::<Result<_, _>>()?;
+        // height, baseline, max length, old layout, comment lines
+        if fields.len() < 5 || fields[0] < 1 || fields[4] < 0 {
+            return Err(Error::InvalidFont);
+        }
+        let height = fields[0] as usize;
+        let fitting = fields[3] >= 0;
+        for _ in 0..fields[4] {
+            lines.next().ok_or(Error::InvalidFont)?;
+        }
+
+        let mut glyphs = HashMap::new();
+        // All printable ASCII characters are required
+        for code in 32..127u32 {
+            let glyph = Font::parse_glyph(&mut lines, height).ok_or(Error::InvalidFont)?;
+            glyphs.insert(char::from_u32(code).ok_or(Error::InvalidFont)?, glyph);
+        }
+        // The German characters and code tagged characters are optional
+        for code in GERMAN_CODES {
+            match (Font::parse_glyph(&mut lines, height), char::from_u32(code)) {
+                (Some(glyph), Some(c)) => { glyphs.insert(c, glyph); },
+                _ => break,
+            }
+        }
+        while let Some(tag) = lines.next() {
+            let code = tag.split_whitespace().next().and_then(parse_code);
+            match (Font::parse_glyph(&mut lines, height), code.and_then(char::from_u32)) {
+                (Some(glyph), Some(c)) => { glyphs.insert(c, glyph); },
+                (Some(_), None) => {},
+                (None, _) => break,
+            }
+        }
+
+        Ok(Font { height, hardblank, fitting, glyphs })
+    }
+
+    /// Parse the lines of the next character
+    ///
+    /// The lines end with the end mark (usually "@"), which is stripped. All
+    /// lines are padded to the same width.
+    fn parse_glyph<'a>(lines: &mut impl Iterator<Item = &'a str>, height: usize) -> Option<Vec<String>> {
+        let mut glyph = Vec::with_capacity(height);
+        for _ in 0..height {
+            let line = lines.next()?.trim_end();
+            let endmark = line.chars().last()?;
+            glyph.push(line.trim_end_matches(endmark).to_string());
+        }
+        let width = glyph.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+        for line in glyph.iter_mut() {
+            let padding = width - line.chars().count();
+            line.extend(std::iter::repeat_n(' ', padding));
+        }
+        Some(glyph)
+    }
+
+    /// Render a line of text
+    ///
+    /// Returns the rows of the banner with the hardblanks still in place.
+    /// Characters missing in the font are left out.
+    fn render_line(&self, text: &str) -> Vec<String> {
+        let mut rows = vec![String::new(); self.height];
+        for c in text.chars() {
+            let glyph = match self.glyphs.get(&c) {
+                Some(g) => g,
+                None => continue,
+            };
+            let overlap = if self.fitting { self.overlap(&rows, glyph) } else { 0 };
+            for (row, line) in rows.iter_mut().zip(glyph) {
+                // Only blanks are dropped: first those at the end of the row,
+                // then those at the start of the character.
+                let trailing = blanks(row.chars().rev());
+                let dropped = overlap.min(trailing);
+                row.truncate(row.trim_end_matches(' ').len() + trailing - dropped);
+                row.extend(line.chars().skip(overlap - dropped));
+            }
+        }
+        rows
+    }
+
+    /// Number of columns a character can be moved to the left until it
+    /// touches the rows rendered so far
+    fn overlap(&self, rows: &[String], glyph: &[String]) -> usize {
+        let width = glyph.first().map_or(0, |l| l.chars().count());
+        rows.iter()
+            .zip(glyph)
+            .map(|(row, line)| blanks(row.chars().rev()) + blanks(line.chars()))
+            .min()
+            .unwrap_or(0)
+            .min(width)
+    }
+
+    /// Width of a line of text in this font
+    fn width(&self, text: &str) -> usize {
+        self.render_line(text).iter().map(|r| r.chars().count()).max().unwrap_or(0)
+    }
+}
+
+/// Number of blanks at the start of the characters
+fn blanks(chars: impl Iterator<Item = char>) -> usize {
+    chars.take_while(|c| *c == ' ').count()
+}
+
+/// Parse the code of a code tagged character (decimal, hex or octal)
+fn parse_code(code: &str) -> Option<u32> {
+    if let Some(hex) = code.strip_prefix("0x").or_else(|| code.strip_prefix("0X")) {
+        u32::from_str_radix(hex, 16).ok()
+    } else if code.len() > 1 && code.starts_with('0') {
+        u32::from_str_radix(&code[1..], 8).ok()
+    } else {
+        code.parse().ok()
+    }
+}
+
+/// The fonts banners are rendered with
+#[derive(Debug)]
+pub struct Banners {
+    fonts: HashMap<String, Font>,
+    default_font: String,
+    color: Option<String>,
+    max_width: usize,
+}
+
+impl Banners {
+    /// Load the fonts in the given directory in addition to the bundled font
+    ///
+    /// Fonts that cannot be loaded are logged and left out.
+    pub async fn load(dir: &Path) -> Banners {
+        let mut banners = Banners::default();
+        let mut entries = match tokio::fs::read_dir(dir).await {
+            Ok(e) => e,
+            Err(e) => {
+                error!("Couldn't read font directory {}: {}", dir.display(), e);
+                return banners;
+            },
+        };
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let path = entry.path();
+            if path.extension().is_none_or(|e| e != "flf") {
+                continue;
+            }
+            let name = match path.file_stem() {
+                Some(n) => n.to_string_lossy().to_lowercase(),
+                None => continue,
+            };
+            let font = loader::load(&path).await
+                .map_err(|e| e.to_string())
+                .and_then(|buf| Font::parse(&String::from_utf8_lossy(&buf)).map_err(|e| e.to_string()));
+            match font {
+                Ok(font) => {
+                    debug!("Loaded font {}.", name);
+                    banners.fonts.insert(name, font);
+                },
+                Err(e) => error!("Couldn't load font {}: {}", path.display(), e),
+            }
+        }
+        info!("Loaded {} fonts for banners.", banners.fonts.len());
+        banners
+    }
+
+    /// Use the given font if none is asked for
+    ///
+    /// The bundled font is kept if there is no font with this name.
+    pub fn with_default_font(mut self, name: &str) -> Banners {
+        let name = name.to_lowercase();
+        if self.fonts.contains_key(&name) {
+            self.default_font = name;
+        } else {
+            error!("Unknown font {}. Using {} for banners.", name, self.default_font);
+        }
+        self
+    }
+
+    /// Color the banners (an ANSI SGR code, eg. "1;36" for bright cyan)
+    pub fn with_color(mut self, color: Option<String>) -> Banners {
+        self.color = color;
+        self
+    }
+
+    /// Wrap banners wider than the given number of columns
+    pub fn with_max_width(mut self, max_width: usize) -> Banners {
+        self.max_width = max_width;
+        self
+    }
+
+    /// Names of the fonts, sorted
+    pub fn fonts(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.fonts.keys().map(|n| n.as_str()).collect();
+        names.sort_unstable();
+        names
+    }
+
+    /// Render text as banner
+    ///
+    /// The text is wrapped at word boundaries to fit the maximum width (words
+    /// too wide on their own are not broken). Lines end with CR LF. Uses the
+    /// default font if no font is given.
+    pub fn render(&self, text: &str, font: Option<&str>) -> Result<String, Error> {
+        let font = match font {
+            Some(name) => self.fonts.get(&name.to_lowercase()).ok_or(Error::UnknownFont)?,
+            None => self.fonts.get(&self.default_font).ok_or(Error::UnknownFont)?,
+        };
+
+        let mut lines: Vec<String> = Vec::new();
+        for word in text.split_whitespace() {
+            match lines.last_mut() {
+                Some(line) if font.width(&format!("{} {}", line, word)) <= self.max_width => {
+                    line.push(' ');
+                    line.push_str(word);
+                },
+                _ => lines.push(word.to_string()),
+            }
+        }
+
+        let mut banner = String::new();
+        for line in lines {
+            for row in font.render_line(&line) {
+                let row = row.trim_end().replace(font.hardblank, " ");
+                match &self.color {
+                    Some(color) => banner.push_str(&format!("\x1b[{}m{}\x1b[0m\r\n", color, row)),
+                    None => banner.push_str(&format!("{}\r\n", row)),
+                }
+            }
+        }
+        Ok(banner)
+    }
+}
+
+impl Default for Banners {
+    /// Only the bundled font, without color
+    fn default() -> Banners {
+        let (name, data) = BUNDLED_FONT;
+        let mut fonts = HashMap::new();
+        match Font::parse(data) {
+            Ok(font) => { fonts.insert(name.to_string(), font); },
+            Err(e) => error!("Couldn't parse the bundled font: {}", e),
+        }
+        Banners {
+            fonts,
+            default_font: name.to_string(),
+            color: None,
+            max_width: DEFAULT_MAX_WIDTH,
+        }
+    }
+}
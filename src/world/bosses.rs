@@ -0,0 +1,321 @@
+//! Bosses
+//!
+//! Bosses are hostile NPCs (eg. the ICE of a corporate mainframe) fought by
+//! groups in raids. A raid is scheduled like other limited-time content: the
+//! spawner of the boss runs during a season (see `seasons`), which admins
+//! force on for a surprise raid with "@flags". The boss of a spawned NPC is
+//! found by its name. A boss fights in phases:
+//!  * every phase starts once the integrity of the boss drops to a share of
+//!    its maximum and replaces the behaviors of the NPC (see `ai`), eg. a
+//!    boss calling reinforcements when it is half broken
+//!  * a phase may have a telegraphed attack: the boss warns the node of the
+//!    attack and the verb countering it (eg. "cloak"), and once the warning
+//!    ran out the attack jams the decks of the players in the node that did
+//!    not counter it meanwhile
+//!
+//! The deckers that hacked the boss share its loot when it is destroyed:
+//! credits are split evenly or by the damage dealt (see `Share`), items go
+//! round the deckers by the damage dealt, the rest of the loot (eg. flags and
+//! titles) goes to all of them.
+
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+
+use generational_arena::Index;
+use tokio::time::Instant;
+use tracing::info;
+
+use crate::connection_manager::ClientId;
+use super::ai::Behavior;
+use super::hooks::Hook;
+use super::messages::{Event, Message};
+use super::npcs::NpcID;
+use super::{combat, run_hook, send_to_node, GameWorld, Player};
+
+/// An attack a boss warns of before it lands
+#[derive(Debug, Clone, PartialEq)]
+pub struct Telegraph {
+    /// The name of the attack (eg. "purge sweep")
+    pub name: String,
+    /// The verb countering the attack (eg. "cloak", see `Action::verb`)
+    pub counter: String,
+    /// Time from the warning to the attack landing
+    pub warning: Duration,
+    /// Time from an attack landing to the next warning
+    pub interval: Duration,
+    /// Time the attack jams the decks of the players not countering it
+    pub jam: Duration,
+}
+
+/// A phase of a boss fight
+#[derive(Debug, Clone, PartialEq)]
+pub struct Phase {
+    /// The phase starts once the integrity of the boss drops to this share
+    /// of its maximum (in percent)
+    pub below: u32,
+    /// Told to the node when the phase starts
+    pub announce: String,
+    /// The behaviors of the boss in the phase
+    pub behaviors: Vec<Behavior>,
+    /// The telegraphed attack of the phase, if any
+    pub telegraph: Option<Telegraph>,
+}
+
+/// How the deckers share the credits of the loot
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Share {
+    /// Everybody gets the same
+    Even,
+    /// In proportion to the damage dealt
+    Damage,
+}
+
+/// The blueprint of a boss fight
+#[derive(Debug, Clone)]
+pub struct Boss {
+    /// The name of the NPCs fought as boss
+    name: String,
+    phases: Vec<Phase>,
+    loot: Vec<Hook>,
+    share: Share,
+}
+
+impl Boss {
+    /// Create a boss fight against the NPCs of the name, with the phase it
+    /// starts in
+    pub fn new(name: &str, first: Phase) -> Boss {
+        Boss { name: name.to_string(), phases: vec![first], loot: Vec::new(), share: Share::Even }
+    }
+
+    /// Add a phase, starting below a lower share of the integrity than the
+    /// phases before
+    pub fn with_phase(mut self, phase: Phase) -> Boss {
+        self.phases.push(phase);
+        self
+    }
+
+    /// Add loot, shared by the deckers that hacked the boss
+    pub fn with_loot(mut self, loot: Hook) -> Boss {
+        self.loot.push(loot);
+        self
+    }
+
+    /// Set how the deckers share the credits of the loot
+    pub fn sharing(mut self, share: Share) -> Boss {
+        self.share = share;
+        self
+    }
+}
+
+/// What a boss does on a tick
+#[derive(Debug, Clone, PartialEq)]
+pub enum Blow {
+    /// Warn of a telegraphed attack
+    Warn(Telegraph),
+    /// A telegraphed attack lands, the players that countered it are spared
+    Land { telegraph: Telegraph, countered: HashSet<String> },
+}
+
+/// A boss fight going on
+#[derive(Debug)]
+struct Encounter {
+    /// The index of the boss
+    boss: usize,
+    phase: usize,
+    /// The damage every decker dealt, in the order of the first hack
+    damage: Vec<(String, u32)>,
+    /// When the boss warns of its next attack
+    next_warning: Option<Instant>,
+    /// When the attack warned of lands and the players that countered it
+    pending: Option<(Instant, HashSet<String>)>,
+}
+
+impl Encounter {
+    /// Start a phase at the given time
+    fn enter(&mut self, phase: usize, telegraph: Option<&Telegraph>, now: Instant) {
+        self.phase = phase;
+        self.next_warning = telegraph.map(|t| now + t.interval);
+        self.pending = None;
+    }
+}
+
+/// The bosses of the world and the fights going on
+#[derive(Debug, Default)]
+pub struct Bosses {
+    bosses: Vec<Boss>,
+    encounters: HashMap<NpcID, Encounter>,
+}
+
+impl Bosses {
+    /// Add a boss
+    pub fn add(&mut self, boss: Boss) {
+        self.bosses.push(boss);
+    }
+
+    /// Start a fight with an NPC spawned, if it is a boss (by its name)
+    ///
+    /// Returns the first phase of the fight.
+    pub fn engage(&mut self, uid: NpcID, name: &str, now: Instant) -> Option<&Phase> {
+        let index = self.bosses.iter().position(|b| b.name.eq_ignore_ascii_case(name))?;
+        let first = &self.bosses[index].phases[0];
+        let mut encounter = Encounter { boss: index, phase: 0, damage: Vec::new(), next_warning: None, pending: None };
+        encounter.enter(0, first.telegraph.as_ref(), now);
+        self.encounters.insert(uid, encounter);
+        Some(first)
+    }
+
+    /// End a fight without loot (eg. the boss despawned)
+    pub fn disengage(&mut self, uid: NpcID) {
+        self.encounters.remove(&uid);
+    }
+
+    /// Note a hack of a boss by a player, with the integrity left
+    ///
+    /// Returns the phase the boss entered, if the hack started one.
+    pub fn hit(&mut self, uid: NpcID, player: &str, damage: u32, integrity: u32, max: u32, now: Instant)
+               -> Option<&Phase> {
+        let encounter = self.encounters.get_mut(&uid)?;
+        match encounter.damage.iter_mut().find(|(p, _)| p == player) {
+            Some((_, dealt)) => *dealt = dealt.saturating_add(damage),
+            None => encounter.damage.push((player.to_string(), damage)),
+        }
+        let share = u64::from(integrity) * 100 / u64::from(max.max(1));
+        let phases = &self.bosses[encounter.boss].phases;
+        let phase = phases.iter().rposition(|p| share <= u64::from(p.below)).unwrap_or(0);
+        if phase <= encounter.phase {
+            return None;
+        }
+        encounter.enter(phase, phases[phase].telegraph.as_ref(), now);
+        Some(&phases[phase])
+    }
+
+    /// Note a player acting in the node of bosses, countering the attacks
+    /// they warned of with the verb
+    pub fn counter(&mut self, uids: impl Iterator<Item = NpcID>, player: &str, verb: &str) {
+        for uid in uids {
+            let encounter = match self.encounters.get_mut(&uid) {
+                Some(e) => e,
+                None => continue,
+            };
+            let telegraph = &self.bosses[encounter.boss].phases[encounter.phase].telegraph;
+            if let (Some(t), Some((_, countered))) = (telegraph, encounter.pending.as_mut()) {
+                if t.counter == verb {
+                    countered.insert(player.to_string());
+                }
+            }
+        }
+    }
+
+    /// Let the bosses warn of their attacks and land them on a tick
+    pub fn tick(&mut self, now: Instant) -> Vec<(NpcID, Blow)> {
+        let mut blows = Vec::new();
+        for (uid, encounter) in self.encounters.iter_mut() {
+            let telegraph = match &self.bosses[encounter.boss].phases[encounter.phase].telegraph {
+                Some(t) => t,
+                None => continue,
+            };
+            match encounter.pending.take() {
+                Some((lands, countered)) if lands <= now => {
+                    encounter.next_warning = Some(now + telegraph.interval);
+                    blows.push((*uid, Blow::Land { telegraph: telegraph.clone(), countered }));
+                },
+                Some(pending) => encounter.pending = Some(pending),
+                None if encounter.next_warning.is_some_and(|t| t <= now) => {
+                    encounter.next_warning = None;
+                    encounter.pending = Some((now + telegraph.warning, HashSet::new()));
+                    blows.push((*uid, Blow::Warn(telegraph.clone())));
+                },
+                None => {},
+            }
+        }
+        blows
+    }
+
+    /// End a fight with the boss destroyed
+    ///
+    /// Returns the loot of the deckers that hacked it (see `share`), None if
+    /// the NPC was no boss.
+    pub fn defeat(&mut self, uid: NpcID) -> Option<Vec<(String, Hook)>> {
+        let encounter = self.encounters.remove(&uid)?;
+        let boss = &self.bosses[encounter.boss];
+        Some(share(&boss.loot, &encounter.damage, boss.share))
+    }
+}
+
+/// Share loot between the deckers by the damage they dealt (in the order
+/// of their first hack)
+///
+/// Credits are split as the share tells, the remainder goes to the decker
+/// that dealt the most damage. Items go round the deckers by the damage
+/// dealt, the rest of the loot goes to every decker.
+pub fn share(loot: &[Hook], damage: &[(String, u32)], share: Share) -> Vec<(String, Hook)> {
+    let mut ranked: Vec<&(String, u32)> = damage.iter().filter(|(_, d)| *d > 0).collect();
+    ranked.sort_by_key(|(_, d)| Reverse(*d));
+    if ranked.is_empty() {
+        return Vec::new();
+    }
+    let total: u64 = ranked.iter().map(|(_, d)| u64::from(*d)).sum();
+    let mut shared = Vec::new();
+    let mut items = 0;
+    for hook in loot {
+        match hook {
+            Hook::Credits(credits) => {
+                let cuts: Vec<u64> = ranked.iter()
+                    .map(|(_, d)| match share {
+                        Share::Even => credits / ranked.len() as u64,
+                        Share::Damage => credits * u64::from(*d) / total,
+                    })
+                    .collect();
+                let remainder = credits - cuts.iter().sum::<u64>();
+                for (i, ((player, _), cut)) in ranked.iter().zip(cuts).enumerate() {
+                    let cut = if i == 0 { cut + remainder } else { cut };
+                    if cut > 0 {
+                        shared.push((player.clone(), Hook::Credits(cut)));
+                    }
+                }
+            },
+            Hook::Item(_) => {
+                shared.push((ranked[items % ranked.len()].0.clone(), hook.clone()));
+                items += 1;
+            },
+            hook => shared.extend(ranked.iter().map(|(p, _)| (p.clone(), hook.clone()))),
+        }
+    }
+    shared
+}
+
+/// Note a hack of a boss
+///
+/// The boss enters the phase the hack started, if any. A boss destroyed
+/// leaves its loot to the deckers that hacked it, those gone meanwhile miss
+/// their share.
+pub(super) async fn hit(event: &combat::CombatEvent, location: Index, world: &mut GameWorld,
+                        players: &mut HashMap<ClientId, Player>, now: Instant) {
+    let (player, uid, damage, integrity, max) = match event {
+        combat::CombatEvent::Hack { player, npc, damage, integrity, max, .. } => {
+            (player, *npc, *damage, *integrity, *max)
+        },
+        _ => return,
+    };
+    let phase = world.bosses.hit(uid, player, damage, integrity, max, now)
+        .filter(|_| integrity > 0)
+        .map(|p| (p.behaviors.clone(), p.announce.clone()));
+    if let Some((behaviors, announce)) = phase {
+        world.npcs.set_behaviors(uid, behaviors);
+        send_to_node(players, location, Message::new(Event::Info, &announce).with_field("npc", uid)).await;
+    }
+    if integrity > 0 {
+        return;
+    }
+    let loot = match world.bosses.defeat(uid) {
+        Some(l) => l,
+        None => return,
+    };
+    info!("Boss #{} was destroyed, sharing {} loot.", uid, loot.len());
+    for (name, hook) in loot {
+        if let Some(p) = players.values_mut().find(|p| p.player_name == name) {
+            run_hook(&hook, world, p).await;
+        }
+    }
+}
@@ -0,0 +1,290 @@
+//! Casino
+//!
+//! Games of chance players wager credits on at the casinos of the world (eg.
+//! the bar in the lobby):
+//!  * dice: the player and the dealer roll two dice, the higher roll wins
+//!  * poker: the player and the dealer are dealt five cards of net-poker,
+//!    the better hand wins
+//!
+//! A win pays the wager, a tie returns it. The games are driven by a random
+//! number generator seeded from the settings, so a seed replays the same
+//! games. The house limits the wager of a game and how much a player may
+//! lose per day, the admins change both with "@casino".
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt;
+
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use tracing::info;
+
+use crate::connection_manager::ClientId;
+use super::actions::Action;
+use super::messages::{Event, Message};
+use super::{save_account, timestamp, GameWorld, Player};
+
+/// Seconds of a day, the period the losses of a player are capped for
+const DAY: u64 = 24 * 60 * 60;
+
+/// Names of the ranks of the cards, from two to ace
+const RANKS: [char; 13] = ['2', '3', '4', '5', '6', '7', '8', '9', 'T', 'J', 'Q', 'K', 'A'];
+
+/// Names of the suits of the cards
+const SUITS: [char; 4] = ['c', 'd', 'h', 's'];
+
+/// Names of the hands of poker, from the lowest
+const HANDS: [&str; 9] = ["high card", "pair", "two pair", "three of a kind", "straight", "flush", "full house",
+    "four of a kind", "straight flush"];
+
+/// A game with its wager
+#[derive(Debug, Clone, PartialEq)]
+pub enum Game {
+    Dice(u64),
+    Poker(u64),
+}
+
+impl Game {
+    /// The credits wagered
+    pub fn wager(&self) -> u64 {
+        match self {
+            Game::Dice(wager) | Game::Poker(wager) => *wager,
+        }
+    }
+}
+
+/// Display a game as typed by the player
+impl fmt::Display for Game {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Game::Dice(wager) => write!(f, "dice {}", wager),
+            Game::Poker(wager) => write!(f, "poker {}", wager),
+        }
+    }
+}
+
+/// The limits of the house
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Limits {
+    /// The most credits wagered on a game
+    pub max_wager: u64,
+    /// The most credits a player may lose per day
+    pub max_loss: u64,
+}
+
+/// Display the limits as set with "@casino"
+impl fmt::Display for Limits {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.max_wager, self.max_loss)
+    }
+}
+
+/// Why the house refuses a game
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Refusal {
+    /// The wager is over the limit of the house
+    Wager(u64),
+    /// The player cannot cover the wager
+    Credits,
+    /// The player could lose more than it may lose today, the credits it
+    /// may still lose
+    Losses(u64),
+}
+
+/// Display a refusal as told to the player
+impl fmt::Display for Refusal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Refusal::Wager(max) => write!(f, "The house takes wagers up to {} credits.", max),
+            Refusal::Credits => write!(f, "You cannot cover that wager."),
+            Refusal::Losses(0) => write!(f, "You reached your loss limit for today."),
+            Refusal::Losses(left) => write!(f, "You may only lose {} more credits today.", left),
+        }
+    }
+}
+
+/// The result of a game
+#[derive(Debug, Clone, PartialEq)]
+pub struct Outcome {
+    /// What happened at the table
+    pub text: String,
+    /// The credits won (positive) or lost (negative)
+    pub net: i64,
+}
+
+/// The tables of the world
+#[derive(Debug)]
+pub struct Casino {
+    rng: StdRng,
+    limits: Limits,
+    /// The credits each player lost on the day (by the number of the day)
+    losses: HashMap<String, (u64, u64)>,
+}
+
+impl Casino {
+    /// Create the casino with a seed for its games and the limits of the
+    /// house
+    pub fn new(seed: u64, limits: Limits) -> Casino {
+        Casino { rng: StdRng::seed_from_u64(seed), limits, losses: HashMap::new() }
+    }
+
+    /// The limits of the house
+    pub fn limits(&self) -> Limits {
+        self.limits
+    }
+
+    /// Change the limits of the house
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    /// Play a game for a player owning the given credits at a time (seconds
+    /// since the epoch)
+    pub fn play(&mut self, player: &str, game: &Game, credits: u64, now: u64) -> Result<Outcome, Refusal> {
+        let wager = game.wager();
+        if wager > self.limits.max_wager {
+            return Err(Refusal::Wager(self.limits.max_wager));
+        }
+        if wager > credits {
+            return Err(Refusal::Credits);
+        }
+        let day = now / DAY;
+        let lost = match self.losses.get(player) {
+            Some((d, lost)) if *d == day => *lost,
+            _ => 0,
+        };
+        let left = self.limits.max_loss.saturating_sub(lost);
+        if wager > left {
+            return Err(Refusal::Losses(left));
+        }
+
+        let (text, result) = match game {
+            Game::Dice(_) => self.dice(),
+            Game::Poker(_) => self.poker(),
+        };
+        let (net, verdict) = match result {
+            Ordering::Greater => (wager as i64, format!("You win {} credits.", wager)),
+            Ordering::Less => (-(wager as i64), format!("You lose {} credits.", wager)),
+            Ordering::Equal => (0, "It is a tie, you keep your wager.".to_string()),
+        };
+        if net < 0 {
+            self.losses.insert(player.to_string(), (day, lost + wager));
+        }
+        Ok(Outcome { text: format!("{} {}", text, verdict), net })
+    }
+
+    /// Roll two dice each, the text and how the player fares
+    fn dice(&mut self) -> (String, Ordering) {
+        let mut roll = || (self.rng.gen_range(1..=6u8), self.rng.gen_range(1..=6u8));
+        let (player, dealer) = (roll(), roll());
+        let text = format!("You roll {} and {}, the dealer rolls {} and {}.", player.0, player.1, dealer.0, dealer.1);
+        (text, (player.0 + player.1).cmp(&(dealer.0 + dealer.1)))
+    }
+
+    /// Deal five cards each from a shuffled deck, the text and how the
+    /// player fares
+    fn poker(&mut self) -> (String, Ordering) {
+        let mut deck: Vec<(usize, usize)> = (0..RANKS.len())
+            .flat_map(|r| (0..SUITS.len()).map(move |s| (r, s)))
+            .collect();
+        deck.shuffle(&mut self.rng);
+        let (player, dealer) = (&deck[..5], &deck[5..10]);
+        let (player_hand, dealer_hand) = (rank(player), rank(dealer));
+        let text = format!("You hold {} ({}), the dealer shows {} ({}).", cards(player), HANDS[player_hand.0],
+            cards(dealer), HANDS[dealer_hand.0]);
+        (text, player_hand.cmp(&dealer_hand))
+    }
+}
+
+/// Rank a poker hand of five cards (rank and suit)
+///
+/// Returns the hand (see `HANDS`) and the ranks breaking ties, so hands
+/// compare by the ranking.
+pub fn rank(cards: &[(usize, usize)]) -> (usize, Vec<usize>) {
+    // Ranks grouped by how often they occur, the most often and highest first
+    let mut counts = [0; 13];
+    for (r, _) in cards {
+        counts[*r] += 1;
+    }
+    let mut groups: Vec<(usize, usize)> = counts.iter().enumerate()
+        .filter(|(_, c)| **c > 0)
+        .map(|(r, c)| (*c, r))
+        .collect();
+    groups.sort_unstable_by(|a, b| b.cmp(a));
+    let mut ranks: Vec<usize> = groups.iter().map(|(_, r)| *r).collect();
+
+    let flush = cards.iter().all(|(_, s)| *s == cards[0].1);
+    // The ace may count low in the wheel (five to ace)
+    if ranks == [12, 3, 2, 1, 0] {
+        ranks = vec![3, 2, 1, 0, 12];
+    }
+    let straight = groups.len() == 5 && (ranks[0] == ranks[4] + 4 || ranks[0] == 3);
+    let hand = match (groups[0].0, groups.get(1).map(|g| g.0)) {
+        _ if straight && flush => 8,
+        (4, _) => 7,
+        (3, Some(2)) => 6,
+        _ if flush => 5,
+        _ if straight => 4,
+        (3, _) => 3,
+        (2, Some(2)) => 2,
+        (2, _) => 1,
+        _ => 0,
+    };
+    (hand, ranks)
+}
+
+/// Cards as shown to the player (eg. "Ah Td 7c")
+fn cards(cards: &[(usize, usize)]) -> String {
+    cards.iter().map(|(r, s)| format!("{}{}", RANKS[*r], SUITS[*s])).collect::<Vec<_>>().join(" ")
+}
+
+/// Play a game of chance at the casino of the node
+pub(super) async fn play(a: &Action, world: &mut GameWorld, client_id: ClientId,
+                         players: &mut HashMap<ClientId, Player>) {
+    let game = match a {
+        Action::Gamble(g) => g,
+        _ => return,
+    };
+    let player = match players.get_mut(&client_id) {
+        Some(p) => p,
+        None => return,
+    };
+    info!("Player {} is performing action {}.", player.player_name, a);
+    if !player.location.is_some_and(|l| world.casinos.contains(&l)) {
+        player.send(Message::new(Event::Error, "There is no casino here.")).await;
+        return;
+    }
+    let message = match world.casino.play(&player.player_name, game, player.account.credits, timestamp()) {
+        Ok(outcome) => {
+            player.account.credits = player.account.credits.saturating_add_signed(outcome.net);
+            if outcome.net > 0 {
+                world.ledger.create("casino", outcome.net.unsigned_abs());
+            } else {
+                world.ledger.destroy("casino", outcome.net.unsigned_abs());
+            }
+            save_account(world, player).await;
+            Message::new(Event::Response, &format!("{}\nYou own {} credits.", outcome.text, player.account.credits))
+                .with_field("net", outcome.net)
+                .with_field("credits", player.account.credits)
+        },
+        Err(refusal) => Message::new(Event::Error, &refusal.to_string()),
+    };
+    player.send(message).await;
+}
+
+/// Show or change the limits of the house for an admin, returns the reply
+pub(super) fn administer(limits: Option<Limits>, world: &mut GameWorld) -> String {
+    match limits {
+        None => {
+            let limits = world.casino.limits();
+            format!("The house takes wagers up to {} credits and lets players lose up to {} credits per day.",
+                limits.max_wager, limits.max_loss)
+        },
+        Some(limits) => {
+            world.casino.set_limits(limits);
+            format!("The house now takes wagers up to {} credits and lets players lose up to {} credits per day.",
+                limits.max_wager, limits.max_loss)
+        },
+    }
+}
@@ -0,0 +1,484 @@
+//! Challenges
+//!
+//! Timed challenge runs. A player starts a run of a challenge and other
+//! players in the same node may join it. The run shares one countdown for
+//! all its members, which is pushed to their sessions while it runs. The
+//! run is won if its members reach the goal before the time is up:
+//!  * reach a node
+//!  * crack a chain of puzzles (eg. the puzzles locking a row of ports)
+//!
+//! The best times of every challenge are kept on the leaderboard, which is
+//! stored as JSON file inside the data directory of the world.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::fs;
+use tokio::time::Instant;
+use tracing::{error, info};
+
+use crate::connection_manager::ClientId;
+use super::actions::Action;
+use super::assets::{AssetID, GameAsset};
+use super::intern::intern;
+use super::messages::{Event, Message};
+use super::{goals, news, timestamp, watchers, GameWorld, Player};
+
+/// Name of the file the leaderboard is stored in (inside the data directory)
+pub const LEADERBOARD_FILE: &str = "leaderboard.json";
+
+/// Number of results kept per challenge
+pub const LEADERBOARD_SIZE: usize = 10;
+
+/// Goals of challenges
+#[derive(Debug, Clone, PartialEq)]
+pub enum Goal {
+    /// Reach the node with the given uid
+    #[allow(dead_code)]
+    Reach(AssetID),
+    /// Solve all puzzles with the given ids, in any order
+    Crack(Vec<String>),
+}
+
+/// A challenge
+#[derive(Debug, Clone)]
+pub struct Challenge {
+    id: Arc<str>,
+    description: Arc<str>,
+    goal: Goal,
+    time_limit: Duration,
+}
+
+impl Challenge {
+    /// Create a new challenge
+    pub fn new(id: &str, description: &str, goal: Goal, time_limit: Duration) -> Challenge {
+        Challenge { id: intern(id), description: intern(description), goal, time_limit }
+    }
+
+    /// The id players start the challenge with
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Describe the challenge as shown to players
+    pub fn describe(&self) -> String {
+        format!("{} ({}): {}", self.id, clock(self.time_limit), self.description)
+    }
+
+    /// The time the members of a run have to reach the goal
+    pub fn time_limit(&self) -> Duration {
+        self.time_limit
+    }
+}
+
+/// A run of a challenge
+#[derive(Debug)]
+pub struct Run {
+    challenge: Arc<str>,
+    /// The players taking part, the player that started the run first
+    pub members: Vec<ClientId>,
+    started: Instant,
+    deadline: Instant,
+    next_update: Instant,
+    cracked: HashSet<String>,
+}
+
+impl Run {
+    /// Start a run of a challenge
+    pub fn new(challenge: &Challenge, leader: ClientId, now: Instant) -> Run {
+        Run {
+            challenge: challenge.id.clone(),
+            members: vec![leader],
+            started: now,
+            deadline: now + challenge.time_limit,
+            next_update: now,
+            cracked: HashSet::new(),
+        }
+    }
+
+    /// The id of the challenge
+    pub fn challenge(&self) -> &str {
+        &self.challenge
+    }
+
+    /// The time since the run started
+    pub fn elapsed(&self, now: Instant) -> Duration {
+        now.saturating_duration_since(self.started)
+    }
+
+    /// The time left to reach the goal
+    pub fn remaining(&self, now: Instant) -> Duration {
+        self.deadline.saturating_duration_since(now)
+    }
+
+    /// Note that a member solved a puzzle
+    pub fn crack(&mut self, puzzle: &str) {
+        self.cracked.insert(puzzle.to_string());
+    }
+
+    /// Check if the members reached the goal of the challenge
+    ///
+    /// Takes the uids of the nodes the members are in.
+    pub fn is_won(&self, challenge: &Challenge, locations: &[AssetID]) -> bool {
+        match &challenge.goal {
+            Goal::Reach(node) => locations.contains(node),
+            Goal::Crack(puzzles) => puzzles.iter().all(|p| self.cracked.contains(p)),
+        }
+    }
+
+    /// The remaining time in whole seconds (rounded up), if the countdown
+    /// is due to be pushed
+    ///
+    /// The countdown is pushed every 30 seconds while more than a minute is
+    /// left, every 10 seconds in the last minute and every second in the
+    /// last 10 seconds.
+    pub fn countdown(&mut self, now: Instant) -> Option<Duration> {
+        if now < self.next_update {
+            return None;
+        }
+        let remaining = self.remaining(now);
+        let secs = remaining.as_secs() + u64::from(remaining.subsec_nanos() > 0);
+        let step = match secs {
+            s if s > 60 => 30,
+            s if s > 10 => 10,
+            _ => 1,
+        };
+        let next = Duration::from_secs(secs.saturating_sub(1) / step * step);
+        self.next_update = self.deadline - next;
+        Some(Duration::from_secs(secs))
+    }
+}
+
+/// A result on the leaderboard
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Record {
+    /// The members of the run
+    pub players: Vec<String>,
+    /// The time the run took in milliseconds
+    pub millis: u64,
+    /// Seconds since the epoch when the run was won
+    pub timestamp: u64,
+}
+
+/// The best results of every challenge, fastest first
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Leaderboard {
+    records: BTreeMap<String, Vec<Record>>,
+}
+
+impl Leaderboard {
+    /// Load the leaderboard stored in the data directory
+    pub async fn load(data_dir: &Path) -> io::Result<Leaderboard> {
+        match fs::read(Leaderboard::path(data_dir)).await {
+            Ok(buf) => Ok(serde_json::from_slice(&buf)?),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Leaderboard::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Save the leaderboard to the data directory
+    pub async fn save(&self, data_dir: &Path) -> io::Result<()> {
+        fs::create_dir_all(data_dir).await?;
+        fs::write(Leaderboard::path(data_dir), serde_json::to_vec_pretty(self)?).await
+    }
+
+    /// Record the result of a won run
+    ///
+    /// Returns the rank of the result (counting from one), if it made it
+    /// onto the leaderboard.
+    pub fn record(&mut self, challenge: &str, players: Vec<String>, time: Duration) -> Option<usize> {
+        let millis = time.as_millis() as u64;
+        let records = self.records.entry(challenge.to_string()).or_default();
+        let rank = records.iter().take_while(|r| r.millis <= millis).count();
+        if rank >= LEADERBOARD_SIZE {
+            return None;
+        }
+        records.insert(rank, Record { players, millis, timestamp: timestamp() });
+        records.truncate(LEADERBOARD_SIZE);
+        Some(rank + 1)
+    }
+
+    /// The best results of a challenge, fastest first
+    pub fn records(&self, challenge: &str) -> &[Record] {
+        self.records.get(challenge).map_or(&[], |r| r.as_slice())
+    }
+
+    /// The results a player took part in, by challenge
+    pub fn records_of(&self, player: &str) -> Vec<(String, Record)> {
+        self.records.iter()
+            .flat_map(|(c, records)| records.iter().map(move |r| (c.clone(), r.clone())))
+            .filter(|(_, r)| r.players.iter().any(|p| p == player))
+            .collect()
+    }
+
+    /// Forget a player
+    ///
+    /// The results of the player are removed, results it shared with other
+    /// players are kept without it. Only the loaded leaderboard is changed,
+    /// see `remove_stored` for the leaderboard file.
+    pub fn remove(&mut self, player: &str) {
+        for records in self.records.values_mut() {
+            for record in records.iter_mut() {
+                record.players.retain(|p| p != player);
+            }
+            records.retain(|r| !r.players.is_empty());
+        }
+    }
+
+    /// Remove a player from the leaderboard file
+    pub async fn remove_stored(data_dir: &Path, player: &str) -> io::Result<()> {
+        let mut leaderboard = Leaderboard::load(data_dir).await?;
+        if leaderboard.records_of(player).is_empty() {
+            return Ok(());
+        }
+        leaderboard.remove(player);
+        leaderboard.save(data_dir).await
+    }
+
+    /// Path of the leaderboard file
+    fn path(data_dir: &Path) -> PathBuf {
+        data_dir.join(LEADERBOARD_FILE)
+    }
+}
+
+/// The challenges of the world, their runs and the leaderboard
+#[derive(Debug, Default)]
+pub struct Challenges {
+    challenges: Vec<Challenge>,
+    /// The runs in progress
+    pub runs: Vec<Run>,
+    /// The best results of the challenges
+    pub leaderboard: Leaderboard,
+}
+
+impl Challenges {
+    /// Add a challenge (a challenge with the same id is replaced)
+    pub fn add(&mut self, challenge: Challenge) {
+        self.challenges.retain(|c| c.id != challenge.id);
+        self.challenges.push(challenge);
+    }
+
+    /// The challenges, in the order they were added
+    pub fn all(&self) -> &[Challenge] {
+        &self.challenges
+    }
+
+    /// The challenge with the given id
+    pub fn get(&self, id: &str) -> Option<&Challenge> {
+        self.challenges.iter().find(|c| c.id.eq_ignore_ascii_case(id))
+    }
+
+    /// The run a player takes part in
+    pub fn run_of(&mut self, client_id: ClientId) -> Option<&mut Run> {
+        self.runs.iter_mut().find(|r| r.members.contains(&client_id))
+    }
+
+    /// The run a player takes part in, if any
+    pub fn joined(&self, client_id: ClientId) -> Option<&Run> {
+        self.runs.iter().find(|r| r.members.contains(&client_id))
+    }
+
+    /// Remove a player from its run (eg. when it hangs up)
+    ///
+    /// Runs without members are dropped.
+    pub fn leave(&mut self, client_id: ClientId) {
+        for run in self.runs.iter_mut() {
+            run.members.retain(|m| *m != client_id);
+        }
+        self.runs.retain(|r| !r.members.is_empty());
+    }
+}
+
+/// Format a duration as countdown clock ("1:05")
+pub fn clock(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    format!("{}:{:02}", secs / 60, secs % 60)
+}
+
+/// Format a duration as run time ("1:05.3")
+pub fn run_time(duration: Duration) -> String {
+    format!("{}.{}", clock(duration), duration.subsec_millis() / 100)
+}
+
+/// Start a challenge run, join one or show the leaderboard
+/// 
+/// A run can only be joined while the player that started it is in the same
+/// node.
+pub(super) async fn compete(a: &Action, world: &mut GameWorld, client_id: ClientId,
+                            players: &mut HashMap<ClientId, Player>, now: Instant) {
+    let player = match players.get(&client_id) {
+        Some(p) => p,
+        None => return,
+    };
+    info!("Player {} is performing action {}.", player.player_name, a);
+    let challenges = &mut world.challenges;
+    let message = match a {
+        Action::Challenge(None) if challenges.all().is_empty() => {
+            Message::new(Event::Response, "There are no challenges.")
+        },
+        Action::Challenge(None) => {
+            let list: Vec<String> = challenges.all().iter().map(|c| c.describe()).collect();
+            Message::new(Event::Response, &format!("Challenges (start one with \"challenge <name>\"):\r\n  {}", 
+                list.join("\r\n  ")))
+                .with_field("challenges", challenges.all().iter().map(|c| c.id()).collect::<Vec<&str>>())
+        },
+        Action::Challenge(Some(_)) | Action::Join(_) if challenges.run_of(client_id).is_some() => {
+            Message::new(Event::Error, "You are already on a challenge run.")
+        },
+        Action::Challenge(Some(id)) => match challenges.get(id) {
+            Some(c) => {
+                let text = format!("Challenge {} started. You have {}. Others here can \"join {}\".", 
+                    c.id(), clock(c.time_limit()), player.player_name);
+                let message = Message::new(Event::Info, &text).with_field("challenge", c.id());
+                challenges.runs.push(Run::new(c, client_id, now));
+                message
+            },
+            None => Message::new(Event::Error, &format!("There is no challenge {}.", id)),
+        },
+        Action::Join(name) => {
+            let leader = players.iter()
+                .find(|(_, p)| p.player_name.eq_ignore_ascii_case(name) && p.location == player.location)
+                .map(|(id, _)| *id);
+            match leader.and_then(|l| challenges.run_of(l)) {
+                Some(run) => {
+                    let text = format!("{} joins your run of {}.", player.player_name, run.challenge());
+                    for member in run.members.iter().filter_map(|m| players.get(m)) {
+                        member.send(Message::new(Event::Info, &text)).await;
+                    }
+                    run.members.push(client_id);
+                    Message::new(Event::Info, &format!("You join the run of {}. {} left.", 
+                        run.challenge(), clock(run.remaining(now))))
+                        .with_field("challenge", run.challenge())
+                },
+                None => Message::new(Event::Error, &format!("{} is not on a challenge run here.", name)),
+            }
+        },
+        Action::Leaderboard(None) => {
+            let mut text = String::from("Leaderboard:");
+            for c in challenges.all() {
+                text += &format!("\r\n{}", c.id());
+                text += &leaderboard(challenges.leaderboard.records(c.id()), 3);
+            }
+            Message::new(Event::Response, &text)
+        },
+        Action::Leaderboard(Some(id)) => match challenges.get(id) {
+            Some(c) => {
+                let records = challenges.leaderboard.records(c.id());
+                Message::new(Event::Response, &format!("Leaderboard of {}:{}", c.id(), 
+                    leaderboard(records, LEADERBOARD_SIZE)))
+                    .with_field("records", records)
+            },
+            None => Message::new(Event::Error, &format!("There is no challenge {}.", id)),
+        },
+        _ => return,
+    };
+    player.send(message.with_field("action", a.to_string())).await;
+}
+
+/// List the best results of a challenge, one per line
+fn leaderboard(records: &[Record], count: usize) -> String {
+    if records.is_empty() {
+        return "\r\n  No results yet.".to_string();
+    }
+    records.iter().take(count).enumerate()
+        .map(|(i, r)| format!("\r\n  {}. {} {}", i + 1, run_time(Duration::from_millis(r.millis)), 
+            r.players.join(", ")))
+        .collect()
+}
+
+/// Process the challenge runs
+/// 
+/// Feeds the puzzles the players solved to their runs and the goals, ends
+/// the runs that were won or ran out of time and pushes the countdown to the
+/// members of the others.
+pub(super) async fn process(world: &mut GameWorld, players: &mut HashMap<ClientId, Player>, now: Instant) {
+    let mut solved = Vec::new();
+    for (client_id, player) in players.iter_mut() {
+        for puzzle in player.solved_puzzles.drain(..) {
+            if let Some(run) = world.challenges.run_of(*client_id) {
+                run.crack(&puzzle);
+            }
+            solved.push(goals::Deed::Solve(puzzle));
+        }
+    }
+    for deed in &solved {
+        goals::accomplish(deed, world, players).await;
+    }
+
+    let mut i = 0;
+    while i < world.challenges.runs.len() {
+        let challenge = match world.challenges.get(world.challenges.runs[i].challenge()) {
+            Some(c) => c.clone(),
+            None => {
+                world.challenges.runs.remove(i);
+                continue;
+            },
+        };
+        let nodes = &world.nodes;
+        let run = &mut world.challenges.runs[i];
+        let members: Vec<&Player> = run.members.iter().filter_map(|m| players.get(m)).collect();
+        let locations: Vec<AssetID> = members.iter()
+            .filter_map(|p| p.location.and_then(|l| nodes.get(l)))
+            .map(|n| n.uid())
+            .collect();
+
+        let names: Vec<String> = members.iter().map(|p| p.player_name.clone()).collect();
+        let (message, feed) = if run.is_won(&challenge, &locations) {
+            let time = run.elapsed(now);
+            info!("{} won challenge {} in {}.", names.join(", "), challenge.id(), run_time(time));
+            let rank = world.challenges.leaderboard.record(challenge.id(), names.clone(), time);
+            if let Err(e) = world.challenges.leaderboard.save(&world.data_dir).await {
+                error!("Could not save the leaderboard: {}", e);
+            }
+            if let Some(r) = rank {
+                let title = format!("#{} on the leaderboard of {}", r, challenge.id());
+                let text = format!("{} won challenge {} in {}.", names.join(", "), challenge.id(), 
+                    run_time(time));
+                world.news.publish(news::Kind::Record, &title, &text, names.clone());
+            }
+            let text = match rank {
+                Some(r) => format!("Challenge {} won in {}! Your run is #{} on the leaderboard.", 
+                    challenge.id(), run_time(time), r),
+                None => format!("Challenge {} won in {}!", challenge.id(), run_time(time)),
+            };
+            let message = Message::new(Event::Info, &text)
+                .with_field("time_ms", time.as_millis() as u64)
+                .with_field("rank", rank);
+            (message, format!("{} won challenge {} in {}.", names.join(", "), challenge.id(), 
+                run_time(time)))
+        } else if run.remaining(now).is_zero() {
+            let message = Message::new(Event::Info, &format!("Time is up! Challenge {} failed.", challenge.id()));
+            (message, format!("{} failed challenge {}.", names.join(", "), challenge.id()))
+        } else {
+            if let Some(remaining) = run.countdown(now) {
+                let text = format!("Challenge {}: {} left.", challenge.id(), clock(remaining));
+                let message = Message::new(Event::Info, &text)
+                    .with_field("challenge", challenge.id())
+                    .with_field("remaining_s", remaining.as_secs());
+                for member in members {
+                    member.send(message.clone()).await;
+                }
+            }
+            i += 1;
+            continue;
+        };
+
+        let message = message.with_field("challenge", challenge.id());
+        let zone = members.first().and_then(|p| p.location);
+        for member in members {
+            member.send(message.clone()).await;
+        }
+        if let Some(zone) = zone {
+            let feed = Message::new(Event::Info, &format!("[{}] {}", world.nodes[zone].name(), feed))
+                .with_field("challenge", challenge.id())
+                .with_field("zone", world.nodes[zone].name());
+            for watcher in watchers(world, players, zone) {
+                watcher.send(feed.clone()).await;
+            }
+        }
+        world.challenges.runs.remove(i);
+    }
+}
@@ -0,0 +1,192 @@
+//! Checkpoints
+//!
+//! The world is built from code or a world file (see `loader`) on every
+//! start, the progress made since lives in memory. The world saves it as
+//! checkpoint to the checkpoint file inside the data directory when the
+//! server shuts down (SIGTERM or ctrl-c) and every autosave interval
+//! (`autosave_s` in the `[world]` section of the settings, 0 for never).
+//! The checkpoint is taken in the world loop and written in the background.
+//! On the next start the checkpoint is applied to the world built:
+//!  * the descriptions of nodes and assets (eg. rewritten by builders, see
+//!    `edits`) and the ACLs of nodes
+//!  * the node every item lies in, items carried by players lie in the node
+//!    they were taken from (they return there when the player leaves)
+//!  * which ports are open (eg. opened by a timer, see `timers`)
+//!  * the node every player was in, the player resumes there on its next
+//!    login if it still may enter the node
+//!
+//! Nodes and assets are matched by their uid. Those missing in the world
+//! built (eg. created by a builder) are skipped, the rest of the world keeps
+//! what it was built with. The accounts of the players are saved on their
+//! own (see `accounts`).
+//!
+//! The format of the checkpoint:
+//! ```ignore
+//!     {
+//!       "timestamp": <seconds since the epoch>,
+//!       "nodes": [{
+//!         "uid": <uid>, "description": <text>, "acl": <rules> | null,
+//!         "assets": [{ "uid": <uid>, "description": <text>, "open": <bool> | null }, ...]
+//!       }, ...],
+//!       "players": { <player>: <node uid>, ... }
+//!     }
+//! ```
+
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use generational_arena::{Arena, Index};
+
+use super::acl::Acl;
+use super::assets::{AssetID, GameAsset, Node};
+
+/// Name of the file the checkpoint is stored in (inside the data directory)
+pub const CHECKPOINT_FILE: &str = "checkpoint.json";
+
+/// The progress of a world
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    /// Seconds since the epoch when the checkpoint was taken
+    pub timestamp: u64,
+    pub nodes: Vec<NodeState>,
+    /// The uid of the node every player was in, by the name of the player
+    /// (in lowercase)
+    #[serde(default)]
+    pub players: BTreeMap<String, AssetID>,
+}
+
+/// A node in a checkpoint
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NodeState {
+    pub uid: AssetID,
+    pub description: String,
+    /// The rules of the ACL of the node (see `acl`)
+    #[serde(default)]
+    pub acl: Option<String>,
+    /// The assets in the node
+    #[serde(default)]
+    pub assets: Vec<AssetState>,
+}
+
+/// An asset in a checkpoint
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AssetState {
+    pub uid: AssetID,
+    pub description: String,
+    /// True if the port is open, None if the asset is no port
+    #[serde(default)]
+    pub open: Option<bool>,
+}
+
+impl AssetState {
+    fn of(asset: &dyn GameAsset) -> AssetState {
+        AssetState { uid: asset.uid(), description: asset.description().to_string(),
+            open: asset.port().map(|p| p.is_open()) }
+    }
+}
+
+impl Checkpoint {
+    /// Take a checkpoint of the nodes, the items the players carry (with the
+    /// nodes they were taken from) and the nodes the players are in
+    pub fn take<'a>(nodes: &Arena<Node>, carried: impl Iterator<Item = (&'a dyn GameAsset, Index)>,
+                    players: impl Iterator<Item = (&'a str, Index)>, timestamp: u64) -> Checkpoint {
+        let mut states: Vec<NodeState> = nodes.iter()
+            .map(|(_, n)| NodeState {
+                uid: n.uid(),
+                description: n.description().to_string(),
+                acl: n.acl().map(|a| a.to_string()),
+                assets: n.assets().map(AssetState::of).collect(),
+            })
+            .collect();
+        for (item, from) in carried {
+            let uid = nodes.get(from).map(|n| n.uid());
+            if let Some(state) = states.iter_mut().find(|s| Some(s.uid) == uid) {
+                state.assets.push(AssetState::of(item));
+            }
+        }
+        let players = players
+            .filter_map(|(p, l)| nodes.get(l).map(|n| (p.to_lowercase(), n.uid())))
+            .collect();
+        Checkpoint { timestamp, nodes: states, players }
+    }
+
+    /// Apply the checkpoint to the nodes of a world
+    ///
+    /// Returns the number of nodes and assets restored.
+    pub fn restore(&self, nodes: &mut Arena<Node>) -> usize {
+        let mut restored = 0;
+        for state in &self.nodes {
+            let index = match nodes.iter().find(|(_, n)| n.uid() == state.uid) {
+                Some((i, _)) => i,
+                None => continue,
+            };
+            for asset in &state.assets {
+                // Items moved to another node go back where they lay
+                let from = nodes.iter().find(|(_, n)| n.assets().any(|a| a.uid() == asset.uid)).map(|(i, _)| i);
+                match from {
+                    Some(from) if from != index => {
+                        if let Some(moved) = nodes.get_mut(from).and_then(|n| n.take_asset(asset.uid)) {
+                            nodes[index].add_asset(moved);
+                        }
+                    },
+                    Some(_) => {},
+                    None => continue,
+                }
+                if let Some(a) = nodes[index].asset_mut(asset.uid) {
+                    a.rewrite(&asset.description);
+                    if let (Some(port), Some(open)) = (a.port_mut(), asset.open) {
+                        port.update_open(open);
+                    }
+                    restored += 1;
+                }
+            }
+            let node = &mut nodes[index];
+            node.rewrite(&state.description);
+            node.update_acl(state.acl.as_deref().and_then(|a| Acl::try_from(a).ok()));
+            restored += 1;
+        }
+        restored
+    }
+
+    /// Load the checkpoint stored in the data directory, None if there is
+    /// none
+    pub async fn load(data_dir: &Path) -> io::Result<Option<Checkpoint>> {
+        match tokio::fs::read(Checkpoint::path(data_dir)).await {
+            Ok(buf) => Ok(Some(serde_json::from_slice(&buf)?)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Save the checkpoint to the data directory
+    ///
+    /// The checkpoint is written next to the file and renamed, so a crash
+    /// while saving keeps the previous checkpoint. Saving blocks, the world
+    /// runs it with `tokio::task::spawn_blocking`.
+    pub fn save(&self, data_dir: &Path) -> io::Result<()> {
+        std::fs::create_dir_all(data_dir)?;
+        let path = Checkpoint::path(data_dir);
+        let partial = path.with_extension("json.partial");
+        std::fs::write(&partial, serde_json::to_vec_pretty(self)?)?;
+        std::fs::rename(partial, path)
+    }
+
+    /// Remove a player from the checkpoint stored in the data directory
+    pub async fn remove_stored(data_dir: &Path, player: &str) -> io::Result<()> {
+        let mut checkpoint = match Checkpoint::load(data_dir).await? {
+            Some(c) => c,
+            None => return Ok(()),
+        };
+        if checkpoint.players.remove(&player.to_lowercase()).is_some() {
+            let data_dir = data_dir.to_path_buf();
+            tokio::task::spawn_blocking(move || checkpoint.save(&data_dir)).await??;
+        }
+        Ok(())
+    }
+
+    fn path(data_dir: &Path) -> PathBuf {
+        data_dir.join(CHECKPOINT_FILE)
+    }
+}
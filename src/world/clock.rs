@@ -0,0 +1,132 @@
+//! Clock
+//!
+//! The time of the world. It runs with the real time unless the staff pauses
+//! it (eg. to debug a scripted event or to freeze the world during a judged
+//! competition). A paused world only moves by single steps of a tick, so
+//! timers like the countdowns of challenge runs and busy players stand still
+//! in between.
+
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::time::Instant;
+
+use crate::connection_manager::ClientId;
+use super::messages::{Event, Message};
+use super::{GameWorld, Player, WORLD_TICK};
+
+/// The time of the world
+#[derive(Debug, Clone)]
+pub struct Clock {
+    /// World time at `base_real`
+    base_game: Instant,
+    /// Real time the world time was last synchronized at
+    base_real: Instant,
+    paused: bool,
+    /// Ticks to perform while paused
+    steps: u32,
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        let now = Instant::now();
+        Clock { base_game: now, base_real: now, paused: false, steps: 0 }
+    }
+}
+
+impl Clock {
+    /// The world time at the given real time
+    pub fn now(&self, real: Instant) -> Instant {
+        if self.paused {
+            self.base_game
+        } else {
+            self.base_game + real.saturating_duration_since(self.base_real)
+        }
+    }
+
+    /// True if the world is paused
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Stop the world time
+    pub fn pause(&mut self, real: Instant) {
+        if !self.paused {
+            self.base_game = self.now(real);
+            self.paused = true;
+        }
+    }
+
+    /// Let the world time run again, from where it was stopped
+    pub fn resume(&mut self, real: Instant) {
+        self.base_real = real;
+        self.paused = false;
+        self.steps = 0;
+    }
+
+    /// Let a paused world perform the given number of ticks
+    pub fn step(&mut self, ticks: u32) {
+        if self.paused {
+            self.steps = self.steps.saturating_add(ticks);
+        }
+    }
+
+    /// Ticks left to perform while paused
+    pub fn steps(&self) -> u32 {
+        self.steps
+    }
+
+    /// Advance the clock on a tick of the world loop
+    ///
+    /// Returns the world time if the world performs the tick. A paused
+    /// world only performs the ticks of steps, each advancing the world time
+    /// by the given interval.
+    pub fn tick(&mut self, real: Instant, interval: Duration) -> Option<Instant> {
+        if !self.paused {
+            return Some(self.now(real));
+        }
+        if self.steps == 0 {
+            return None;
+        }
+        self.steps -= 1;
+        self.base_game += interval;
+        Some(self.base_game)
+    }
+}
+
+/// Pause the world on behalf of an admin and tell the players
+pub(super) async fn pause(world: &mut GameWorld, players: &HashMap<ClientId, Player>) -> String {
+    if world.clock.is_paused() {
+        "The world is already paused.".to_string()
+    } else {
+        world.clock.pause(Instant::now());
+        let message = Message::new(Event::Info, "Time stands still. The world is paused.");
+        for p in players.values() {
+            p.send(message.clone()).await;
+        }
+        "Paused the world. Use \"@step [ticks]\" to advance it and \"@resume\" to let it run.".to_string()
+    }
+}
+
+/// Advance the paused world by ticks on behalf of an admin
+pub(super) fn step(ticks: u32, world: &mut GameWorld) -> String {
+    if world.clock.is_paused() {
+        world.clock.step(ticks);
+        format!("Stepping {} ticks of {}ms ({} pending).", ticks, WORLD_TICK.as_millis(), world.clock.steps())
+    } else {
+        "The world is not paused.".to_string()
+    }
+}
+
+/// Let the paused world run again on behalf of an admin and tell the players
+pub(super) async fn resume(world: &mut GameWorld, players: &HashMap<ClientId, Player>) -> String {
+    if world.clock.is_paused() {
+        world.clock.resume(Instant::now());
+        let message = Message::new(Event::Info, "Time flows again. The world is running.");
+        for p in players.values() {
+            p.send(message.clone()).await;
+        }
+        "Resumed the world.".to_string()
+    } else {
+        "The world is not paused.".to_string()
+    }
+}
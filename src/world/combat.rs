@@ -0,0 +1,162 @@
+//! Combat messages
+//!
+//! Combat output is built from structured events instead of text, so every
+//! player sees it at the verbosity chosen with "combat <verbosity>":
+//!  * full: a sentence per event
+//!  * summary: one short line per event (eg. "trinity > sentinel -10 (5/15)")
+//!  * minimal: only the events involving the player, as summary
+//!
+//! Robots get the event in the "combat" field. Clients with GMCP (see
+//! `media`) also get the vitals mirrored, so badges can render health bars
+//! without parsing text: "Char.Vitals" for the deck of the player,
+//! "Char.Enemy" for the integrity of the NPC fought and "Char.Drone" for
+//! the integrity of the drone of the player (see `drones`).
+
+use std::fmt;
+
+use serde_json::{json, Value};
+
+use super::messages::{Event, Message};
+use super::npcs::NpcID;
+
+/// How much combat output a player sees
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Verbosity {
+    #[default]
+    Full,
+    Summary,
+    Minimal,
+}
+
+/// Display a verbosity by the name used in the "combat" command
+impl fmt::Display for Verbosity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Verbosity::Full => write!(f, "full"),
+            Verbosity::Summary => write!(f, "summary"),
+            Verbosity::Minimal => write!(f, "minimal"),
+        }
+    }
+}
+
+/// Something that happened in a fight
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum CombatEvent {
+    /// A player (or its drone) hacked an NPC, destroying it if no integrity
+    /// is left
+    Hack { player: String, drone: bool, npc: NpcID, name: String, damage: u32, integrity: u32, max: u32 },
+    /// A player taunted an NPC
+    Taunt { player: String, npc: NpcID, name: String },
+    /// An NPC struck a player, jamming its deck for some seconds
+    Strike { player: String, npc: NpcID, name: String, jam: u64 },
+    /// The drone of a player took the strike of an NPC, wrecking it if no
+    /// integrity is left
+    Shield { player: String, npc: NpcID, name: String, integrity: u32 },
+}
+
+impl CombatEvent {
+    /// The player fighting
+    fn player(&self) -> &str {
+        match self {
+            CombatEvent::Hack { player, .. } | CombatEvent::Taunt { player, .. }
+                | CombatEvent::Strike { player, .. } | CombatEvent::Shield { player, .. } => player,
+        }
+    }
+
+    /// The text of the event as seen by a player, None if the verbosity of
+    /// the player hides it
+    pub fn text(&self, viewer: &str, verbosity: Verbosity) -> Option<String> {
+        let own = self.player() == viewer;
+        if verbosity == Verbosity::Minimal && !own {
+            return None;
+        }
+        let who = if own { "you".to_string() } else { self.player().to_string() };
+        let drone = if own { "your drone".to_string() } else { format!("the drone of {}", self.player()) };
+        let text = match (self, verbosity) {
+            (CombatEvent::Hack { drone: false, name, integrity: 0, .. }, Verbosity::Full) => {
+                format!("{} {} the {} to pieces. It shatters.", capitalize(&who), if own { "hack" } else { "hacks" }, name)
+            },
+            (CombatEvent::Hack { drone: true, name, integrity: 0, .. }, Verbosity::Full) => {
+                format!("{} hacks the {} to pieces. It shatters.", capitalize(&drone), name)
+            },
+            (CombatEvent::Hack { drone: false, name, damage, integrity, max, .. }, Verbosity::Full) => {
+                format!("{} {} the {} for {} damage ({}/{} integrity left).", capitalize(&who),
+                    if own { "hack" } else { "hacks" }, name, damage, integrity, max)
+            },
+            (CombatEvent::Hack { drone: true, name, damage, integrity, max, .. }, Verbosity::Full) => {
+                format!("{} hacks the {} for {} damage ({}/{} integrity left).", capitalize(&drone), name, damage,
+                    integrity, max)
+            },
+            (CombatEvent::Hack { drone, name, integrity: 0, .. }, _) => {
+                format!("{} > {} destroyed", if *drone { short_drone(&who) } else { who }, name)
+            },
+            (CombatEvent::Hack { drone, name, damage, integrity, max, .. }, _) => {
+                format!("{} > {} -{} ({}/{})", if *drone { short_drone(&who) } else { who }, name, damage, integrity, max)
+            },
+            (CombatEvent::Taunt { name, .. }, Verbosity::Full) => {
+                format!("{} {} a decoy at the {}. It turns on {}.", capitalize(&who),
+                    if own { "run" } else { "runs" }, name, who)
+            },
+            (CombatEvent::Taunt { name, .. }, _) => format!("{} taunts {}", who, name),
+            (CombatEvent::Strike { name, jam, .. }, Verbosity::Full) if own => {
+                format!("The {} strikes you. Your deck is jammed for {}s.", name, jam)
+            },
+            (CombatEvent::Strike { name, .. }, Verbosity::Full) => format!("The {} strikes {}.", name, who),
+            (CombatEvent::Strike { name, jam, .. }, _) if own => format!("{} > you, jammed {}s", name, jam),
+            (CombatEvent::Strike { name, .. }, _) => format!("{} > {}", name, who),
+            (CombatEvent::Shield { name, integrity: 0, .. }, Verbosity::Full) => {
+                format!("{} takes the strike of the {} and is wrecked.", capitalize(&drone), name)
+            },
+            (CombatEvent::Shield { name, integrity, .. }, Verbosity::Full) => {
+                format!("{} takes the strike of the {} ({} integrity left).", capitalize(&drone), name, integrity)
+            },
+            (CombatEvent::Shield { name, integrity: 0, .. }, _) => format!("{} > {} wrecked", name, short_drone(&who)),
+            (CombatEvent::Shield { name, integrity, .. }, _) => {
+                format!("{} > {} ({} left)", name, short_drone(&who), integrity)
+            },
+        };
+        Some(text)
+    }
+
+    /// The message of the event for a player, None if the verbosity of the
+    /// player hides it
+    pub fn message(&self, viewer: &str, verbosity: Verbosity) -> Option<Message> {
+        let text = self.text(viewer, verbosity)?;
+        Some(Message::new(Event::Info, &text).with_field("combat", self))
+    }
+
+    /// The GMCP vitals mirrored to a player, as package and data
+    pub fn vitals(&self, viewer: &str) -> Option<(&'static str, Value)> {
+        match self {
+            CombatEvent::Hack { npc, name, integrity, max, .. } => {
+                Some(("Char.Enemy", json!({ "npc": npc, "name": name, "integrity": integrity, "max": max })))
+            },
+            CombatEvent::Strike { player, jam, .. } if player == viewer => {
+                Some(("Char.Vitals", json!({ "jammed": jam })))
+            },
+            CombatEvent::Shield { player, integrity, .. } if player == viewer => {
+                Some(("Char.Drone", json!({ "integrity": integrity })))
+            },
+            _ => None,
+        }
+    }
+}
+
+/// The drone of a player in summaries, eg. "neo's drone"
+fn short_drone(who: &str) -> String {
+    if who == "you" { "your drone".to_string() } else { format!("{}'s drone", who) }
+}
+
+/// Capitalize the first letter of "you" and drones at the start of a
+/// sentence, names of players are kept as they are
+fn capitalize(text: &str) -> String {
+    match text.strip_prefix("you") {
+        Some(rest) => format!("You{}", rest),
+        None => match text.strip_prefix("the ") {
+            Some(rest) => format!("The {}", rest),
+            None => text.to_string(),
+        },
+    }
+}
@@ -0,0 +1,116 @@
+//! Consent
+//!
+//! Players decide which interactions other players may start with them,
+//! shown with "prefs" and changed with "prefs <flag> on|off":
+//!  * tells: accept tells from strangers, ie. players not in the same node
+//!    (including players of a linked world)
+//!  * trades: accept trades offered directly by other players
+//!  * duels: accept duels
+//!  * follows: let other players follow them
+//!
+//! The world checks the flags of the player on the receiving end before it
+//! lets an interaction happen, the staff is always let through. The world
+//! has no direct trades, duels or following yet, their flags are kept so
+//! they apply as soon as it does.
+
+use std::convert::TryFrom;
+use std::fmt;
+
+use super::errors::Error;
+
+/// The interactions a player consents to
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Consent {
+    /// Accept tells from strangers
+    pub tells: bool,
+    pub trades: bool,
+    pub duels: bool,
+    pub follows: bool,
+}
+
+impl Default for Consent {
+    fn default() -> Consent {
+        Consent { tells: true, trades: true, duels: false, follows: true }
+    }
+}
+
+/// An interaction another player starts with a player
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Interaction {
+    /// A tell, from a stranger or not
+    Tell { stranger: bool },
+    #[allow(dead_code)]
+    Trade,
+    #[allow(dead_code)]
+    Duel,
+    #[allow(dead_code)]
+    Follow,
+}
+
+/// A flag of the consent, as named in "prefs"
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Pref {
+    Tells,
+    Trades,
+    Duels,
+    Follows,
+}
+
+impl Consent {
+    /// True if the player allows the interaction
+    pub fn allows(&self, interaction: Interaction) -> bool {
+        match interaction {
+            Interaction::Tell { stranger } => !stranger || self.tells,
+            Interaction::Trade => self.trades,
+            Interaction::Duel => self.duels,
+            Interaction::Follow => self.follows,
+        }
+    }
+
+    /// Turn a flag on or off
+    pub fn set(&mut self, pref: Pref, on: bool) {
+        match pref {
+            Pref::Tells => self.tells = on,
+            Pref::Trades => self.trades = on,
+            Pref::Duels => self.duels = on,
+            Pref::Follows => self.follows = on,
+        }
+    }
+}
+
+/// Parse the name of a flag
+impl TryFrom<&str> for Pref {
+    type Error = Error;
+
+    fn try_from(item: &str) -> Result<Self, Error> {
+        match item.to_lowercase().as_str() {
+            "tells" => Ok(Pref::Tells),
+            "trades" => Ok(Pref::Trades),
+            "duels" => Ok(Pref::Duels),
+            "follows" => Ok(Pref::Follows),
+            _ => Err(Error::InvalidCommand),
+        }
+    }
+}
+
+/// Display a flag by its name
+impl fmt::Display for Pref {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Pref::Tells => write!(f, "tells"),
+            Pref::Trades => write!(f, "trades"),
+            Pref::Duels => write!(f, "duels"),
+            Pref::Follows => write!(f, "follows"),
+        }
+    }
+}
+
+/// Display the flags as shown by "prefs"
+impl fmt::Display for Consent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let on = |flag: bool| if flag { "on" } else { "off" };
+        write!(f, "Your preferences:\r\n  tells {} (from players not in your node)\r\n  trades {}\r\n  \
+            duels {}\r\n  follows {}\r\nChange them with \"prefs <flag> on|off\".", on(self.tells), on(self.trades),
+            on(self.duels), on(self.follows))
+    }
+}
@@ -0,0 +1,128 @@
+//! Grammar coverage
+//!
+//! Diagnostics for the grammar: "@coverage" replays the commands recorded in
+//! the transcripts (see `transcripts`) through the parser, "@coverage
+//! <player>" only the transcripts of one player. The analytics (see
+//! `analytics`) keep no text, so the transcripts players opted in to are the
+//! corpus. The report counts the commands parsed and the commands with
+//! invalid arguments, and groups the commands with an unknown verb by their
+//! first token, most frequent first, to prioritize new verbs and synonyms.
+//!
+//! Admin commands and the commands sent to the shell of a terminal are not
+//! replayed, they are not meant for the grammar.
+
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::io;
+use std::path::Path;
+use tokio::fs;
+
+use super::actions::Action;
+use super::errors::Error;
+use super::transcripts::{Transcript, TRANSCRIPT_DIR};
+
+/// Number of first tokens listed in the report
+const MAX_TOKENS: usize = 20;
+
+/// Prefix of the commands in a transcript
+const INPUT_PREFIX: &str = "> ";
+
+/// Output starting a shell session (see `access`)
+const SHELL_START: &str = "You jack into ";
+
+/// The commands of a transcript replayed through the parser
+#[derive(Debug, Clone, Default)]
+pub struct Coverage {
+    /// Commands replayed
+    pub replayed: usize,
+    /// Commands parsed into an action
+    pub parsed: usize,
+    /// Commands with a known verb and invalid arguments
+    pub invalid: usize,
+    /// Commands with an unknown verb by their first token (lowercase)
+    unknown: BTreeMap<String, usize>,
+}
+
+impl Coverage {
+    /// Replay a command
+    pub fn replay(&mut self, command: &str) {
+        let command = command.trim();
+        if command.is_empty() || command.starts_with('@') {
+            return;
+        }
+        self.replayed += 1;
+        match Action::try_from(command) {
+            Ok(_) => self.parsed += 1,
+            Err(Error::VerbUnknownError) => {
+                let token = command.split_whitespace().next().unwrap_or_default().to_lowercase();
+                *self.unknown.entry(token).or_default() += 1;
+            },
+            Err(_) => self.invalid += 1,
+        }
+    }
+
+    /// Replay the commands of a transcript, skipping the shell sessions
+    pub fn replay_transcript(&mut self, transcript: &str) {
+        let mut in_shell = false;
+        for line in transcript.lines() {
+            match line.strip_prefix(INPUT_PREFIX) {
+                Some(command) if in_shell => in_shell = !command.trim().eq_ignore_ascii_case("exit"),
+                Some(command) => self.replay(command),
+                None => in_shell = in_shell || line.starts_with(SHELL_START),
+            }
+        }
+    }
+
+    /// Replay the transcripts of a player, or of all players (None)
+    pub async fn load(data_dir: &Path, player: Option<&str>) -> io::Result<Coverage> {
+        let mut coverage = Coverage::default();
+        let mut dirs = Vec::new();
+        match player {
+            Some(p) => dirs.push(Transcript::dir(data_dir, p)),
+            None => match fs::read_dir(data_dir.join(TRANSCRIPT_DIR)).await {
+                Ok(mut entries) => {
+                    while let Some(entry) = entries.next_entry().await? {
+                        dirs.push(entry.path());
+                    }
+                },
+                Err(e) if e.kind() == io::ErrorKind::NotFound => (),
+                Err(e) => return Err(e),
+            },
+        }
+        for dir in dirs {
+            if !fs::metadata(&dir).await.is_ok_and(|m| m.is_dir()) {
+                continue;
+            }
+            let mut entries = fs::read_dir(dir).await?;
+            while let Some(entry) = entries.next_entry().await? {
+                coverage.replay_transcript(&fs::read_to_string(entry.path()).await?);
+            }
+        }
+        Ok(coverage)
+    }
+
+    /// The first tokens of the commands with an unknown verb and how often
+    /// they were used, most frequent first
+    pub fn unknown(&self) -> Vec<(&str, usize)> {
+        let mut unknown: Vec<(&str, usize)> = self.unknown.iter().map(|(t, n)| (t.as_str(), *n)).collect();
+        unknown.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        unknown
+    }
+}
+
+/// Display the report for the staff
+impl fmt::Display for Coverage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let unknown = self.unknown();
+        write!(f, "Replayed {} commands: {} parsed, {} with invalid arguments, {} with an unknown verb.",
+            self.replayed, self.parsed, self.invalid, unknown.iter().map(|(_, n)| n).sum::<usize>())?;
+        for (token, count) in unknown.iter().take(MAX_TOKENS) {
+            write!(f, "\r\n  {} {}", token, count)?;
+        }
+        if unknown.len() > MAX_TOKENS {
+            write!(f, "\r\n  ({} more)", unknown.len() - MAX_TOKENS)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,94 @@
+//! Delays
+//!
+//! Some actions take effect when they complete rather than when they start
+//! (eg. a scan reveals the cloaked players once the scanner swept the node).
+//! The world starts such an action on its tick and keeps it pending for the
+//! duration of the action, telling the player how it progresses. The player
+//! goes on with other actions meanwhile, but has one pending action at most.
+//! When it completes, the world performs it like any other action.
+//!
+//! A pending action is bound to the node it was started in: moving to
+//! another node (or leaving the world) cancels it.
+
+use std::time::Duration;
+
+use generational_arena::Index;
+use tokio::time::Instant;
+
+use super::actions::Action;
+use crate::connection_manager::ClientId;
+
+/// Time between two progress messages
+pub const PROGRESS_EVERY: Duration = Duration::from_secs(1);
+
+/// An action waiting to complete
+#[derive(Debug)]
+pub struct Delayed {
+    pub client_id: ClientId,
+    pub action: Action,
+    /// The node the action was started in
+    pub location: Option<Index>,
+    /// The action was run by a trigger (see `triggers`)
+    pub triggered: bool,
+    due: Instant,
+    next_progress: Instant,
+}
+
+impl Delayed {
+    /// The message telling the player the action goes on
+    pub fn progress(&self, now: Instant) -> String {
+        let left = self.due.saturating_duration_since(now).as_secs_f64().ceil();
+        match self.action {
+            Action::Scan => format!("Your scanner sweeps the node... ({}s left)", left),
+            _ => format!("You are busy with {}... ({}s left)", self.action.verb(), left),
+        }
+    }
+}
+
+/// The pending actions of the players
+#[derive(Debug, Default)]
+pub struct Delays {
+    pending: Vec<Delayed>,
+}
+
+impl Delays {
+    /// Start an action completing after its duration
+    ///
+    /// A progress message is due right away.
+    pub fn start(&mut self, client_id: ClientId, action: Action, location: Option<Index>, triggered: bool,
+                 now: Instant) {
+        let due = now + action.duration();
+        self.pending.push(Delayed { client_id, action, location, triggered, due, next_progress: now });
+    }
+
+    /// The pending action of a player, if any
+    pub fn pending(&self, client_id: ClientId) -> Option<&Delayed> {
+        self.pending.iter().find(|d| d.client_id == client_id)
+    }
+
+    /// The pending actions a progress message is due for at the given time
+    ///
+    /// The next progress message is due `PROGRESS_EVERY` later. No progress
+    /// message is due for actions completing at the given time.
+    pub fn progress(&mut self, now: Instant) -> Vec<(ClientId, String)> {
+        let mut messages = Vec::new();
+        for delayed in self.pending.iter_mut().filter(|d| d.next_progress <= now && d.due > now) {
+            delayed.next_progress = now + PROGRESS_EVERY;
+            messages.push((delayed.client_id, delayed.progress(now)));
+        }
+        messages
+    }
+
+    /// Take the actions completing at the given time
+    pub fn due(&mut self, now: Instant) -> Vec<Delayed> {
+        let (due, pending) = std::mem::take(&mut self.pending).into_iter().partition(|d| d.due <= now);
+        self.pending = pending;
+        due
+    }
+
+    /// Cancel the pending action of a player, returning it if there was one
+    pub fn cancel(&mut self, client_id: ClientId) -> Option<Delayed> {
+        let position = self.pending.iter().position(|d| d.client_id == client_id)?;
+        Some(self.pending.remove(position))
+    }
+}
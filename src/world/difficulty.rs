@@ -0,0 +1,52 @@
+//! Difficulty
+//!
+//! ICE scales with the deckers it faces, so a solo explorer and a group
+//! during peak con hours both get a fight worth having. On every tick the
+//! world computes the scale of every node with players in it from their
+//! number and their average level (see `safety`), and the hostile NPCs in
+//! the node take it on (see `npcs`):
+//!  * their integrity grows with the scale, keeping the damage taken in
+//!    proportion
+//!  * their strikes jam decks for longer
+//!
+//! A single decker of level 1 faces the ICE as it was built (a scale of
+//! `BASE_SCALE`). Every further decker adds `PLAYER_SCALE`, every level
+//! above 1 of the average `LEVEL_SCALE`, up to `MAX_SCALE`. NPCs keep their
+//! scale while their node is empty.
+
+use std::convert::TryFrom;
+use std::time::Duration;
+
+/// Scale of ICE as it was built, in percent
+pub const BASE_SCALE: u32 = 100;
+
+/// Scale added by every decker beyond the first, in percent
+pub const PLAYER_SCALE: u32 = 50;
+
+/// Scale added by every level above 1 of the average level, in percent
+pub const LEVEL_SCALE: u32 = 10;
+
+/// Highest scale of ICE, in percent
+pub const MAX_SCALE: u32 = 400;
+
+/// The scale of ICE facing deckers of the given levels, in percent
+pub fn scale(levels: &[u64]) -> u32 {
+    if levels.is_empty() {
+        return BASE_SCALE;
+    }
+    let count = u32::try_from(levels.len()).unwrap_or(u32::MAX);
+    let average = levels.iter().sum::<u64>() / u64::from(count);
+    let players = (count - 1).saturating_mul(PLAYER_SCALE);
+    let level = u32::try_from(average.saturating_sub(1)).unwrap_or(u32::MAX).saturating_mul(LEVEL_SCALE);
+    BASE_SCALE.saturating_add(players).saturating_add(level).min(MAX_SCALE)
+}
+
+/// Apply a scale (in percent) to an amount, eg. the integrity of an NPC
+pub fn apply(amount: u32, scale: u32) -> u32 {
+    u32::try_from(u64::from(amount) * u64::from(scale) / u64::from(BASE_SCALE)).unwrap_or(u32::MAX)
+}
+
+/// Apply a scale (in percent) to a duration, eg. the jam of a strike
+pub fn apply_duration(duration: Duration, scale: u32) -> Duration {
+    duration * scale / BASE_SCALE
+}
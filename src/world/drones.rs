@@ -0,0 +1,143 @@
+//! Drones
+//!
+//! Companion drones players acquire as a reward (see `hooks`). A drone is an
+//! item kept in the account of its owner, so it persists between sessions.
+//! Deployed, it follows its owner from node to node and takes orders (eg.
+//! "drone attack sentinel" or "drone scan"). A deployed drone takes the
+//! strikes of ICE meant for its owner. When its integrity runs out, the
+//! drone is wrecked and lost.
+
+use std::fmt;
+use std::collections::HashMap;
+use tokio::time::Instant;
+
+use crate::connection_manager::ClientId;
+use super::actions::Action;
+use super::messages::{Event, Message};
+use super::{scan, threat, GameWorld, Player};
+
+/// Integrity of a new drone
+pub const DRONE_INTEGRITY: u32 = 30;
+
+/// Damage a drone attack deals to an NPC
+pub const DRONE_DAMAGE: u32 = 5;
+
+/// Damage a drone takes from a strike meant for its owner
+pub const STRIKE_DAMAGE: u32 = 10;
+
+/// A companion drone
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Drone {
+    /// The model of the drone (eg. "wasp")
+    pub model: String,
+    /// The damage the drone takes before it is wrecked
+    pub integrity: u32,
+}
+
+impl Drone {
+    /// Create a new drone of a model
+    pub fn new(model: &str) -> Drone {
+        Drone { model: model.to_string(), integrity: DRONE_INTEGRITY }
+    }
+
+    /// Take damage
+    ///
+    /// Returns true if the drone is wrecked.
+    pub fn damage(&mut self, damage: u32) -> bool {
+        self.integrity = self.integrity.saturating_sub(damage);
+        self.integrity == 0
+    }
+}
+
+/// An order to a drone
+#[derive(Debug, Clone, PartialEq)]
+pub enum Order {
+    /// Show the state of the drone
+    Status,
+    /// Launch the drone to follow its owner
+    Deploy,
+    /// Stow the drone away
+    Recall,
+    /// Hack a hostile NPC in the node
+    Attack { target: String, ordinal: Option<usize> },
+    /// Reveal the cloaked players in the node
+    Scan,
+}
+
+/// Display an order as typed by the player
+impl fmt::Display for Order {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Order::Status => write!(f, "drone"),
+            Order::Deploy => write!(f, "drone deploy"),
+            Order::Recall => write!(f, "drone recall"),
+            Order::Attack { target, ordinal: None } => write!(f, "drone attack {}", target),
+            Order::Attack { target, ordinal: Some(n) } => write!(f, "drone attack {} {}", target, n),
+            Order::Scan => write!(f, "drone scan"),
+        }
+    }
+}
+
+/// Give an order to the drone of a player
+/// 
+/// Only deployed drones attack and scan.
+pub(super) async fn order(a: &Action, world: &mut GameWorld, client_id: ClientId,
+                          players: &mut HashMap<ClientId, Player>, now: Instant) {
+    let order = match a {
+        Action::Drone(o) => o,
+        _ => return,
+    };
+    let player = match players.get_mut(&client_id) {
+        Some(p) => p,
+        None => return,
+    };
+    let (model, integrity) = match &player.account.drone {
+        Some(d) => (d.model.clone(), d.integrity),
+        None => {
+            player.send(Message::new(Event::Error, "You have no drone.")).await;
+            return;
+        },
+    };
+    let message = match (order, player.drone_deployed) {
+        (Order::Status, deployed) => {
+            let state = if deployed { "deployed" } else { "stowed" };
+            Message::new(Event::Response, &format!("Your {} drone is {} ({} integrity).", model, state, integrity))
+                .with_field("drone", model)
+                .with_field("deployed", deployed)
+                .with_field("integrity", integrity)
+        },
+        (Order::Deploy, true) => Message::new(Event::Error, "Your drone is deployed already."),
+        (Order::Deploy, false) => {
+            player.drone_deployed = true;
+            Message::new(Event::Response, &format!("Your {} drone whirs to life and follows you.", model))
+                .with_field("deployed", true)
+        },
+        (Order::Recall, false) => Message::new(Event::Error, "Your drone is stowed already."),
+        (Order::Recall, true) => {
+            player.drone_deployed = false;
+            Message::new(Event::Response, "Your drone docks with your deck.").with_field("deployed", false)
+        },
+        (_, false) => Message::new(Event::Error, "Your drone is not deployed."),
+        (Order::Attack { .. }, true) => {
+            threat::fight(a, world, client_id, players, now).await;
+            return;
+        },
+        (Order::Scan, true) => {
+            let location = match player.location {
+                Some(l) => l,
+                None => return,
+            };
+            let by = format!("The drone of {}", player.player_name);
+            let revealed = scan(location, client_id, players, &by).await;
+            let text = if revealed.is_empty() {
+                "Your drone finds nobody hiding.".to_string()
+            } else {
+                format!("Your drone reveals: {}.", revealed.join(", "))
+            };
+            Message::new(Event::Response, &text).with_field("revealed", revealed)
+        },
+    };
+    if let Some(p) = players.get(&client_id) {
+        p.send(message).await;
+    }
+}
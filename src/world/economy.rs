@@ -0,0 +1,334 @@
+//! Economy
+//!
+//! Vendors sell wares to players and buy them back ("wares", "buy <item>",
+//! "sell <item>"). What a vendor offers and asks depends on the player and
+//! on the world:
+//!  * reputation: a vendor belongs to a faction. Players earn reputation
+//!    with factions (see `hooks`), wares may be held back until a player
+//!    reaches a reputation, and the price drops by up to a fifth for trusted
+//!    players (or rises for distrusted ones).
+//!  * supply and demand: the world counts the items bought from and sold to
+//!    vendors recently. Items in demand get more expensive, items flooding
+//!    the vendors cheaper. The counters decay, so prices return to the base
+//!    price when the trade calms down.
+//!
+//! Vendors buy wares they stock at half the price they ask.
+//!
+//! The ledger of the world books the credits the systems create (faucets,
+//! eg. rewards) and destroy (sinks, eg. vendors), so the staff can spot
+//! balance problems during the event ("@economy" and the metrics export).
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tracing::info;
+
+use crate::connection_manager::ClientId;
+use super::actions::Action;
+use super::market;
+use super::messages::{Event, Message};
+use super::{save_account, timestamp, GameWorld, Player};
+
+/// Seconds in which the trade counted for an item halves
+const HALF_LIFE: f64 = 60.0 * 60.0;
+
+/// Change of the price per item traded recently
+const DEMAND_STEP: f64 = 0.1;
+
+/// Reputation at which the full discount (or surcharge) is reached
+const MAX_REPUTATION: i64 = 100;
+
+/// Discount at the full reputation
+const MAX_DISCOUNT: f64 = 0.2;
+
+/// An order to the vendor of the node
+#[derive(Debug, Clone, PartialEq)]
+pub enum Deal {
+    /// Show the wares offered to the player
+    Wares,
+    /// Buy an item
+    Buy(String),
+    /// Sell an item
+    Sell(String),
+}
+
+/// Display a deal as typed by the player
+impl fmt::Display for Deal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Deal::Wares => write!(f, "wares"),
+            Deal::Buy(item) => write!(f, "buy {}", item),
+            Deal::Sell(item) => write!(f, "sell {}", item),
+        }
+    }
+}
+
+/// An item a vendor stocks
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ware {
+    pub item: String,
+    /// The price when nobody traded the item recently
+    pub base_price: u64,
+    /// The reputation with the faction of the vendor a player needs to see
+    /// the ware
+    pub reputation: i64,
+}
+
+/// A vendor of a faction
+#[derive(Debug, Clone, PartialEq)]
+pub struct Vendor {
+    pub name: String,
+    pub faction: String,
+    stock: Vec<Ware>,
+}
+
+impl Vendor {
+    /// Create a vendor of a faction without wares
+    pub fn new(name: &str, faction: &str) -> Vendor {
+        Vendor { name: name.to_string(), faction: faction.to_string(), stock: Vec::new() }
+    }
+
+    /// Stock an item offered to every player
+    pub fn with_ware(self, item: &str, base_price: u64) -> Vendor {
+        self.with_gated_ware(item, base_price, 0)
+    }
+
+    /// Stock an item only offered to players with the given reputation
+    pub fn with_gated_ware(mut self, item: &str, base_price: u64, reputation: i64) -> Vendor {
+        self.stock.push(Ware { item: item.to_string(), base_price, reputation });
+        self
+    }
+
+    /// The wares offered to a player with the reputation
+    pub fn wares(&self, reputation: i64) -> impl Iterator<Item = &Ware> {
+        self.stock.iter().filter(move |w| reputation >= w.reputation)
+    }
+
+    /// The ware of an item (ignoring case) offered to a player with the
+    /// reputation
+    pub fn ware(&self, item: &str, reputation: i64) -> Option<&Ware> {
+        self.wares(reputation).find(|w| w.item.eq_ignore_ascii_case(item))
+    }
+}
+
+/// Items traded recently, decaying over time
+#[derive(Debug, Clone, Copy, Default)]
+struct Counter {
+    /// Items bought minus items sold
+    demand: f64,
+    /// Seconds since the epoch the counter was decayed to
+    updated: u64,
+}
+
+impl Counter {
+    /// Decay the counter to a time
+    fn decay(&mut self, now: u64) {
+        let elapsed = now.saturating_sub(self.updated) as f64;
+        self.demand *= 0.5f64.powf(elapsed / HALF_LIFE);
+        self.updated = now;
+    }
+}
+
+/// The trade of the world with its vendors
+#[derive(Debug, Default)]
+pub struct Economy {
+    counters: HashMap<String, Counter>,
+}
+
+impl Economy {
+    /// The price a vendor asks for a ware from a player with the reputation
+    /// at a time (seconds since the epoch)
+    pub fn price(&self, ware: &Ware, reputation: i64, now: u64) -> u64 {
+        let mut counter = self.counters.get(&ware.item).copied().unwrap_or_default();
+        counter.decay(now);
+        let demand = (1.0 + DEMAND_STEP * counter.demand).clamp(0.5, 2.0);
+        let trust = reputation.clamp(-MAX_REPUTATION, MAX_REPUTATION) as f64 / MAX_REPUTATION as f64;
+        let price = ware.base_price as f64 * demand * (1.0 - MAX_DISCOUNT * trust);
+        (price.round() as u64).max(1)
+    }
+
+    /// The price a vendor pays for a ware, half the price it asks
+    pub fn offer(&self, ware: &Ware, reputation: i64, now: u64) -> u64 {
+        self.price(ware, reputation, now) / 2
+    }
+
+    /// Count an item bought from a vendor
+    pub fn bought(&mut self, item: &str, now: u64) {
+        self.trade(item, 1.0, now);
+    }
+
+    /// Count an item sold to a vendor
+    pub fn sold(&mut self, item: &str, now: u64) {
+        self.trade(item, -1.0, now);
+    }
+
+    fn trade(&mut self, item: &str, amount: f64, now: u64) {
+        let counter = self.counters.entry(item.to_string()).or_default();
+        counter.decay(now);
+        counter.demand += amount;
+    }
+}
+
+/// Whether credits were created or destroyed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Flow {
+    /// Credits paid out by the world (a faucet)
+    Created,
+    /// Credits taken out of the world (a sink)
+    Destroyed,
+}
+
+/// Display a flow by the name used in the metrics
+impl fmt::Display for Flow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Flow::Created => write!(f, "created"),
+            Flow::Destroyed => write!(f, "destroyed"),
+        }
+    }
+}
+
+/// The credits created and destroyed by the systems of the world
+///
+/// Clones share the ledger, so the world can book while the telemetry
+/// exports it.
+#[derive(Debug, Clone)]
+pub struct Ledger {
+    started: Instant,
+    flows: Arc<Mutex<BTreeMap<(Flow, &'static str), u64>>>,
+}
+
+impl Ledger {
+    /// Create an empty ledger
+    pub fn new() -> Ledger {
+        Ledger { started: Instant::now(), flows: Arc::new(Mutex::new(BTreeMap::new())) }
+    }
+
+    /// Book credits a system (eg. "casino") created
+    pub fn create(&self, system: &'static str, credits: u64) {
+        self.book(Flow::Created, system, credits);
+    }
+
+    /// Book credits a system destroyed
+    pub fn destroy(&self, system: &'static str, credits: u64) {
+        self.book(Flow::Destroyed, system, credits);
+    }
+
+    /// The credits booked by flow and system
+    pub fn flows(&self) -> Vec<(Flow, &'static str, u64)> {
+        let flows = self.flows.lock().unwrap_or_else(|e| e.into_inner());
+        flows.iter().map(|((flow, system), credits)| (*flow, *system, *credits)).collect()
+    }
+
+    /// All credits booked for a flow
+    pub fn total(&self, flow: Flow) -> u64 {
+        self.flows().iter().filter(|(f, _, _)| *f == flow).map(|(_, _, c)| c).sum()
+    }
+
+    fn book(&self, flow: Flow, system: &'static str, credits: u64) {
+        if credits == 0 {
+            return;
+        }
+        let mut flows = self.flows.lock().unwrap_or_else(|e| e.into_inner());
+        *flows.entry((flow, system)).or_default() += credits;
+    }
+}
+
+impl Default for Ledger {
+    fn default() -> Ledger {
+        Ledger::new()
+    }
+}
+
+/// Display the ledger as report for the staff, with the flows per hour
+/// since the server started
+impl fmt::Display for Ledger {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let hours = (self.started.elapsed().as_secs_f64() / 3600.0).max(1.0 / 60.0);
+        let (created, destroyed) = (self.total(Flow::Created), self.total(Flow::Destroyed));
+        write!(f, "Credits since the start ({:.1}h): created {} ({:.0}/h), destroyed {} ({:.0}/h), net {:+}",
+            hours, created, created as f64 / hours, destroyed, destroyed as f64 / hours,
+            created as i64 - destroyed as i64)?;
+        for (flow, system, credits) in self.flows() {
+            write!(f, "\r\n  {} {} {} ({:.0}/h)", system, flow, credits, credits as f64 / hours)?;
+        }
+        Ok(())
+    }
+}
+
+/// Trade with the vendor of the node
+/// 
+/// The wares offered and their prices depend on the reputation of the
+/// player with the faction of the vendor and on the recent trade of the
+/// world.
+pub(super) async fn deal(a: &Action, world: &mut GameWorld, client_id: ClientId,
+                         players: &mut HashMap<ClientId, Player>) {
+    let deal = match a {
+        Action::Vendor(d) => d,
+        _ => return,
+    };
+    let player = match players.get_mut(&client_id) {
+        Some(p) => p,
+        None => return,
+    };
+    info!("Player {} is performing action {}.", player.player_name, a);
+    let vendor = match player.location.and_then(|l| world.vendors.get(&l)) {
+        Some(v) => v,
+        None => {
+            player.send(Message::new(Event::Error, "There is no vendor here.")).await;
+            return;
+        },
+    };
+    let reputation = player.account.reputation(&vendor.faction);
+    let now = timestamp();
+    let message = match deal {
+        Deal::Wares => {
+            let wares: Vec<(String, u64)> = vendor.wares(reputation)
+                .map(|w| (w.item.clone(), world.economy.price(w, reputation, now)))
+                .collect();
+            let lines: Vec<String> = wares.iter().map(|(item, price)| format!("  {} for {} credits", item, price)).collect();
+            let text = format!("The {} of the {} offers you (reputation {}):\n{}\nYou own {} credits.", vendor.name,
+                vendor.faction, reputation, lines.join("\n"), player.account.credits);
+            Message::new(Event::Response, &text)
+                .with_field("wares", &wares)
+                .with_field("reputation", reputation)
+        },
+        Deal::Buy(item) => match vendor.ware(item, reputation) {
+            None => Message::new(Event::Error, &format!("The {} offers you no {}.", vendor.name, item)),
+            Some(ware) => {
+                let price = world.economy.price(ware, reputation, now);
+                if price > player.account.credits {
+                    Message::new(Event::Error, &format!("You cannot afford {} for {} credits.", ware.item, price))
+                } else {
+                    let item = ware.item.clone();
+                    player.account.credits -= price;
+                    player.account.items.push(item.clone());
+                    world.economy.bought(&item, now);
+                    world.ledger.destroy("vendors", price);
+                    save_account(world, player).await;
+                    Message::new(Event::Response, &format!("You buy {} for {} credits.", item, price))
+                        .with_field("credits", player.account.credits)
+                }
+            },
+        },
+        Deal::Sell(item) => {
+            let ware = vendor.ware(item, reputation);
+            match (ware, player.account.items.iter().position(|i| i.eq_ignore_ascii_case(item))) {
+                (_, None) => market::untradeable(player, item),
+                (None, _) => Message::new(Event::Error, &format!("The {} does not deal in {}.", vendor.name, item)),
+                (Some(ware), Some(position)) => {
+                    let offer = world.economy.offer(ware, reputation, now);
+                    let item = player.account.items.remove(position);
+                    player.account.credits += offer;
+                    world.economy.sold(&item, now);
+                    world.ledger.create("vendors", offer);
+                    save_account(world, player).await;
+                    Message::new(Event::Response, &format!("You sell {} for {} credits.", item, offer))
+                        .with_field("credits", player.account.credits)
+                },
+            }
+        },
+    };
+    player.send(message).await;
+}
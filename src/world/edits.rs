@@ -0,0 +1,384 @@
+//! Edits
+//!
+//! Changes builders make to the running world (eg. "@describe 2 A port
+//! humming with static."). Every edit applied yields the edit undoing it,
+//! which goes on the undo stack of the builder ("@undo"). The admins see all
+//! changes in the change log ("@changes").
+//!
+//! Builders may stage their edits in a draft ("@draft") instead, so players
+//! do not see half-finished edits. "@preview" applies the staged edits to a
+//! shadow copy of the affected nodes and shows the result, "@publish"
+//! applies them to the world at once (or none, if one cannot be applied).
+//!
+//! The links between nodes come from the world file (see `loader`), they
+//! cannot be edited yet.
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+
+use generational_arena::Arena;
+use tracing::{error, info};
+
+use super::acl::Acl;
+use super::assets::{AssetID, GameAsset, Node, Port};
+use super::terminals::Terminal;
+use super::{inspect, GameWorld};
+
+/// Number of edits a builder can undo
+const MAX_UNDO: usize = 20;
+
+/// Number of changes kept in the change log
+const MAX_CHANGES: usize = 100;
+
+/// Kinds of assets builders can create
+#[derive(Debug, Clone, PartialEq)]
+pub enum AssetKind {
+    Port,
+    /// A terminal with the given hostname and an empty filesystem
+    Terminal(String),
+}
+
+/// A change to the world
+#[derive(Debug, Clone, PartialEq)]
+pub enum Edit {
+    /// Replace the description of a node or asset
+    Describe { uid: AssetID, description: String },
+    /// Create an asset in a node
+    Create { node: AssetID, kind: AssetKind, description: String },
+    /// Remove an asset from a node (undoes creating it)
+    Remove { node: AssetID, uid: AssetID },
+    /// Restrict who may enter a node (None lets everybody enter)
+    Restrict { node: AssetID, acl: Option<Acl> },
+}
+
+/// Display an edit as shown in the change log
+impl fmt::Display for Edit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Edit::Describe { uid, description } => write!(f, "describe #{} \"{}\"", uid, description),
+            Edit::Create { node, kind: AssetKind::Port, description } => {
+                write!(f, "create port in #{} \"{}\"", node, description)
+            },
+            Edit::Create { node, kind: AssetKind::Terminal(hostname), description } => {
+                write!(f, "create terminal {} in #{} \"{}\"", hostname, node, description)
+            },
+            Edit::Remove { node, uid } => write!(f, "remove #{} from #{}", uid, node),
+            Edit::Restrict { node, acl: Some(acl) } => write!(f, "restrict #{} to {}", node, acl),
+            Edit::Restrict { node, acl: None } => write!(f, "open #{}", node),
+        }
+    }
+}
+
+/// Apply an edit to the nodes of the world
+///
+/// Returns the edit undoing it, or why the edit cannot be applied.
+pub fn apply(nodes: &mut Arena<Node>, edit: &Edit) -> Result<Edit, String> {
+    match edit {
+        Edit::Describe { uid, description } => {
+            let asset: &mut dyn GameAsset = match nodes.iter_mut().map(|(_, n)| n).find(|n| n.uid() == *uid) {
+                Some(node) => node,
+                None => nodes.iter_mut()
+                    .find_map(|(_, n)| n.asset_mut(*uid))
+                    .ok_or_else(|| format!("There is no asset #{}.", uid))?,
+            };
+            let previous = asset.description().to_string();
+            asset.rewrite(description);
+            Ok(Edit::Describe { uid: *uid, description: previous })
+        },
+        Edit::Create { node, kind, description } => {
+            let uid = next_uid(nodes);
+            let node = find_node(nodes, *node)?;
+            let mut asset: Box<dyn GameAsset> = match kind {
+                AssetKind::Port => Box::new(Port::new(uid)),
+                AssetKind::Terminal(hostname) => Box::new(Terminal::new(uid, hostname)),
+            };
+            asset.rewrite(description);
+            node.add_asset(asset);
+            Ok(Edit::Remove { node: node.uid(), uid })
+        },
+        Edit::Remove { node, uid } => {
+            let node = find_node(nodes, *node)?;
+            if node.asset_mut(*uid).is_none() {
+                return Err(format!("There is no asset #{} in node #{}.", uid, node.uid()));
+            }
+            node.remove_asset(*uid);
+            // Removed assets cannot be restored
+            Ok(Edit::Remove { node: node.uid(), uid: *uid })
+        },
+        Edit::Restrict { node, acl } => {
+            let node = find_node(nodes, *node)?;
+            let previous = node.acl().cloned();
+            node.update_acl(acl.clone());
+            Ok(Edit::Restrict { node: node.uid(), acl: previous })
+        },
+    }
+}
+
+/// Find a node by its uid, to change it
+fn find_node(nodes: &mut Arena<Node>, uid: AssetID) -> Result<&mut Node, String> {
+    nodes.iter_mut()
+        .map(|(_, n)| n)
+        .find(|n| n.uid() == uid)
+        .ok_or_else(|| format!("There is no node #{}.", uid))
+}
+
+/// The uid of the node an edit changes, if it exists
+pub fn affected(nodes: &Arena<Node>, edit: &Edit) -> Option<AssetID> {
+    match edit {
+        Edit::Describe { uid, .. } => nodes.iter()
+            .map(|(_, n)| n)
+            .find(|n| n.uid() == *uid || n.assets().any(|a| a.uid() == *uid))
+            .map(|n| n.uid()),
+        Edit::Create { node, .. } | Edit::Remove { node, .. } | Edit::Restrict { node, .. } => Some(*node),
+    }
+}
+
+/// Apply edits to a shadow copy of the nodes they change
+///
+/// Returns the changed copies, or why an edit cannot be applied. Assets
+/// created get their final uids when the edits are published.
+pub fn preview(nodes: &Arena<Node>, edits: &[Edit]) -> Result<Arena<Node>, String> {
+    let affected: Vec<AssetID> = edits.iter().filter_map(|e| affected(nodes, e)).collect();
+    let mut shadow = Arena::new();
+    for (_, node) in nodes.iter().filter(|(_, n)| affected.contains(&n.uid())) {
+        shadow.insert(node.clone());
+    }
+    for edit in edits {
+        apply(&mut shadow, edit)?;
+    }
+    Ok(shadow)
+}
+
+/// The uid for a new asset (one above the highest uid in the world)
+pub fn next_uid(nodes: &Arena<Node>) -> AssetID {
+    nodes.iter()
+        .flat_map(|(_, n)| std::iter::once(n.uid()).chain(n.assets().map(|a| a.uid())))
+        .max()
+        .map_or(0, |uid| uid + 1)
+}
+
+/// The drafts of the builders, the edits staged by builder
+#[derive(Debug, Default)]
+pub struct Drafts {
+    drafts: HashMap<String, Vec<Edit>>,
+}
+
+impl Drafts {
+    /// Start a draft for a builder
+    ///
+    /// Returns false if the builder has a draft already.
+    pub fn open(&mut self, builder: &str) -> bool {
+        let key = builder.to_lowercase();
+        if self.drafts.contains_key(&key) {
+            return false;
+        }
+        self.drafts.insert(key, Vec::new());
+        true
+    }
+
+    /// The edits staged by a builder, if the builder has a draft
+    pub fn get(&self, builder: &str) -> Option<&[Edit]> {
+        self.drafts.get(&builder.to_lowercase()).map(|d| d.as_slice())
+    }
+
+    /// Stage an edit in the draft of a builder
+    ///
+    /// Returns the number of edits staged, or None if the builder has no
+    /// draft.
+    pub fn stage(&mut self, builder: &str, edit: Edit) -> Option<usize> {
+        let draft = self.drafts.get_mut(&builder.to_lowercase())?;
+        draft.push(edit);
+        Some(draft.len())
+    }
+
+    /// Drop the last edit staged by a builder
+    pub fn unstage(&mut self, builder: &str) -> Option<Edit> {
+        self.drafts.get_mut(&builder.to_lowercase())?.pop()
+    }
+
+    /// Close the draft of a builder, returning its edits
+    pub fn close(&mut self, builder: &str) -> Option<Vec<Edit>> {
+        self.drafts.remove(&builder.to_lowercase())
+    }
+}
+
+/// A change in the change log
+#[derive(Debug, Clone)]
+pub struct Change {
+    pub builder: String,
+    pub timestamp: u64,
+    pub edit: Edit,
+    /// The change undid an earlier change
+    pub undo: bool,
+}
+
+/// The undo stacks of the builders and the change log
+#[derive(Debug, Default)]
+pub struct History {
+    stacks: HashMap<String, Vec<Edit>>,
+    log: VecDeque<Change>,
+}
+
+impl History {
+    /// Record an edit of a builder and the edit undoing it
+    pub fn record(&mut self, builder: &str, edit: Edit, undo: Edit) {
+        let stack = self.stacks.entry(builder.to_lowercase()).or_default();
+        if stack.len() == MAX_UNDO {
+            stack.remove(0);
+        }
+        stack.push(undo);
+        self.log(builder, edit, false);
+    }
+
+    /// Take the edit undoing the last edit of a builder off its stack
+    pub fn pop(&mut self, builder: &str) -> Option<Edit> {
+        self.stacks.get_mut(&builder.to_lowercase())?.pop()
+    }
+
+    /// Record that a builder undid an edit
+    pub fn record_undo(&mut self, builder: &str, undo: Edit) {
+        self.log(builder, undo, true);
+    }
+
+    /// The latest changes, newest first
+    pub fn changes(&self, count: usize) -> impl Iterator<Item = &Change> {
+        self.log.iter().rev().take(count)
+    }
+
+    fn log(&mut self, builder: &str, edit: Edit, undo: bool) {
+        if self.log.len() == MAX_CHANGES {
+            self.log.pop_front();
+        }
+        self.log.push_back(Change { builder: builder.to_string(), timestamp: super::timestamp(), edit, undo });
+    }
+}
+
+/// Apply an edit of a builder to the world and record it in its history
+pub(super) fn edit(edit: Edit, builder: &str, world: &mut GameWorld) -> String {
+    match apply(&mut world.nodes, &edit) {
+        Ok(undo) => {
+            info!("Builder {} changed the world: {}.", builder, edit);
+            let reply = match &undo {
+                Edit::Remove { uid, .. } => format!("Created asset #{}.", uid),
+                _ => "Done.".to_string(),
+            };
+            world.history.record(builder, edit, undo);
+            reply
+        },
+        Err(e) => e,
+    }
+}
+
+/// Undo the latest edit of a builder
+pub(super) fn undo(builder: &str, world: &mut GameWorld) -> String {
+    match world.history.pop(builder) {
+        Some(undo) => match apply(&mut world.nodes, &undo) {
+            Ok(_) => {
+                info!("Builder {} undid a change: {}.", builder, undo);
+                let reply = format!("Undone: {}.", undo);
+                world.history.record_undo(builder, undo);
+                reply
+            },
+            Err(e) => format!("Cannot undo ({}).", e),
+        },
+        None => "There is nothing to undo.".to_string(),
+    }
+}
+
+/// Describe the latest changes of all builders
+pub(super) fn changes(count: usize, world: &GameWorld) -> String {
+    let changes: Vec<String> = world.history.changes(count)
+        .map(|c| format!("  {} {} {}{}", crate::feed::rfc3339(c.timestamp), c.builder, 
+            if c.undo { "undid: " } else { "" }, c.edit))
+        .collect();
+    if changes.is_empty() {
+        "No changes yet.".to_string()
+    } else {
+        format!("Latest changes:\r\n{}", changes.join("\r\n"))
+    }
+}
+
+/// Stage an edit of a builder in its draft
+pub(super) fn stage(edit: Edit, builder: &str, world: &mut GameWorld) -> String {
+    let text = edit.to_string();
+    match world.drafts.stage(builder, edit) {
+        Some(count) => format!("Staged {} ({} edits in the draft).", text, count),
+        None => "You have no draft.".to_string(),
+    }
+}
+
+/// Drop the latest edit staged in the draft of a builder
+pub(super) fn unstage(builder: &str, world: &mut GameWorld) -> String {
+    match world.drafts.unstage(builder) {
+        Some(edit) => format!("Dropped {} from the draft.", edit),
+        None => "The draft is empty.".to_string(),
+    }
+}
+
+/// Open a draft for a builder
+pub(super) fn open_draft(builder: &str, world: &mut GameWorld) -> String {
+    if world.drafts.open(builder) {
+        "Your edits are staged from now on. Use \"@preview\", then \"@publish\" or \"@discard\".".to_string()
+    } else {
+        "You have a draft already.".to_string()
+    }
+}
+
+/// Show the nodes changed by the draft of a builder as if it was published
+pub(super) fn preview_draft(builder: &str, world: &GameWorld) -> String {
+    match world.drafts.get(builder) {
+        None => "You have no draft.".to_string(),
+        Some([]) => "The draft is empty.".to_string(),
+        Some(staged) => match preview(&world.nodes, staged) {
+            Ok(shadow) => {
+                let mut dumps = vec![format!("Preview of {} edits:", staged.len())];
+                for (_, node) in shadow.iter() {
+                    dumps.push(inspect::dump(node, None));
+                    dumps.extend(node.assets().map(|a| inspect::dump(a, Some(node))));
+                }
+                dumps.join("\r\n")
+            },
+            Err(e) => format!("The draft cannot be published: {}", e),
+        },
+    }
+}
+
+/// Apply the draft of a builder to the world at once, or keep it if one of
+/// its edits cannot be applied
+pub(super) fn publish_draft(builder: &str, world: &mut GameWorld) -> String {
+    match world.drafts.close(builder) {
+        None => "You have no draft.".to_string(),
+        Some(staged) => match preview(&world.nodes, &staged) {
+            Ok(_) => {
+                let mut published = 0;
+                for edit in staged {
+                    match apply(&mut world.nodes, &edit) {
+                        Ok(undo) => {
+                            info!("Builder {} changed the world: {}.", builder, edit);
+                            world.history.record(builder, edit, undo);
+                            published += 1;
+                        },
+                        Err(e) => error!("Could not publish {} of {}: {}", edit, builder, e),
+                    }
+                }
+                format!("Published {} edits.", published)
+            },
+            Err(e) => {
+                let reply = format!("Nothing published, the draft cannot be applied: {}", e);
+                world.drafts.open(builder);
+                for edit in staged {
+                    world.drafts.stage(builder, edit);
+                }
+                reply
+            },
+        },
+    }
+}
+
+/// Throw away the draft of a builder
+pub(super) fn discard_draft(builder: &str, world: &mut GameWorld) -> String {
+    match world.drafts.close(builder) {
+        Some(staged) => format!("Discarded {} edits.", staged.len()),
+        None => "You have no draft.".to_string(),
+    }
+}
@@ -9,9 +9,11 @@ use std::fmt;
 ///
 /// This type exists to avoid writing out `crate::errors`, and is
 /// otherwise a direct mapping to `Result`.
+#[allow(dead_code)]
 pub type GameWorldResult<T> = Result<T, Error>;
 
 /// Error type for auth errors
+#[allow(dead_code, clippy::enum_variant_names)]
 #[derive(Debug, Clone)]
 pub enum Error {
     /// Command is not valid
@@ -28,6 +30,20 @@ pub enum Error {
     VerbEncodingError,
     /// Conversion into property failed
     PropertyConversionFailed,
+    /// A name or text is too short or too long
+    InvalidLength,
+    /// A name or text contains characters that are not allowed
+    InvalidCharacters,
+    /// A name is reserved and may not be used by players
+    ReservedName,
+    /// A name or text contains profanity
+    ProfaneContent,
+    /// The player is not allowed to use the command
+    PermissionDenied,
+    /// A font is not in the FIGlet format
+    InvalidFont,
+    /// There is no font with the given name
+    UnknownFont,
     /// Unknown error - typically used to map errors from other libraries
     /// that do not fit.
     UnknownError,
@@ -47,6 +63,13 @@ impl fmt::Display for Error {
             Error::VerbUnknownError => write!(f,"unknown verb"),
             Error::VerbEncodingError => write!(f,"unknown verb encoding"),
             Error::PropertyConversionFailed => write!(f, "property conversion failed"),
+            Error::InvalidLength => write!(f, "invalid length"),
+            Error::InvalidCharacters => write!(f, "invalid characters"),
+            Error::ReservedName => write!(f, "reserved name"),
+            Error::ProfaneContent => write!(f, "profane content"),
+            Error::PermissionDenied => write!(f, "permission denied"),
+            Error::InvalidFont => write!(f, "invalid font"),
+            Error::UnknownFont => write!(f, "unknown font"),
             Error::UnknownError => write!(f, "unknown error"),
         }
 
@@ -64,15 +87,20 @@ impl StdError for Error {
 /// necessarily the same kind of error.
 impl PartialEq for Error {
     fn eq(&self, other: &Error) -> bool {
-        match (self, other) {
-            (&Error::InvalidCommand, &Error::InvalidCommand) => true,
-            (&Error::InvalidDataMessage, &Error::InvalidDataMessage) => true,
-            (&Error::PlayerDoesNotExist, &Error::PlayerDoesNotExist) => true,
-            (&Error::NoSpawnpointFound, &Error::NoSpawnpointFound) => true,
-            (&Error::VerbUnknownError, &Error::VerbUnknownError) => true,
-            (&Error::VerbEncodingError, &Error::VerbEncodingError) => true,
-            (&Error::PropertyConversionFailed, &Error::PropertyConversionFailed) => true,
-            _ => false,
-        }
+        matches!((self, other),
+            (&Error::InvalidCommand, &Error::InvalidCommand)
+            | (&Error::InvalidDataMessage, &Error::InvalidDataMessage)
+            | (&Error::PlayerDoesNotExist, &Error::PlayerDoesNotExist)
+            | (&Error::NoSpawnpointFound, &Error::NoSpawnpointFound)
+            | (&Error::VerbUnknownError, &Error::VerbUnknownError)
+            | (&Error::VerbEncodingError, &Error::VerbEncodingError)
+            | (&Error::PropertyConversionFailed, &Error::PropertyConversionFailed)
+            | (&Error::InvalidLength, &Error::InvalidLength)
+            | (&Error::InvalidCharacters, &Error::InvalidCharacters)
+            | (&Error::ReservedName, &Error::ReservedName)
+            | (&Error::ProfaneContent, &Error::ProfaneContent)
+            | (&Error::PermissionDenied, &Error::PermissionDenied)
+            | (&Error::InvalidFont, &Error::InvalidFont)
+            | (&Error::UnknownFont, &Error::UnknownFont))
     }
 }
@@ -0,0 +1,249 @@
+//! Filesystem
+//!
+//! The virtual filesystem of a terminal: directories holding files and
+//! further directories. Paths are written as on unix ("/logs/access.log"),
+//! relative paths are resolved against the working directory of the shell.
+//!
+//! Entries can be
+//!  * locked by ICE of a strength. Locked entries cannot be read until the
+//!    ICE is cracked by a program at least as strong.
+//!  * hidden, so they are only listed with "ls -a".
+//!  * programs players can run (eg. an icebreaker cracking ICE).
+//!  * hooked, so reading them changes the state of the player (eg. noting a
+//!    port code in the journal or advancing a quest).
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use super::hooks::Hook;
+use super::intern::intern;
+
+/// A path, as its components (the root directory is the empty path)
+pub type Path = Vec<String>;
+
+/// An entry of a directory
+#[derive(Debug, Clone)]
+pub struct Entry {
+    kind: Kind,
+    /// Strength of the ICE locking the entry (0 if it is not locked)
+    ice: u8,
+    hidden: bool,
+}
+
+/// Kinds of entries
+#[derive(Debug, Clone)]
+pub enum Kind {
+    Directory(Directory),
+    File(File),
+}
+
+impl Entry {
+    /// The kind of the entry
+    pub fn kind(&self) -> &Kind {
+        &self.kind
+    }
+
+    /// True if the entry is only listed with "ls -a"
+    pub fn is_hidden(&self) -> bool {
+        self.hidden
+    }
+
+    /// The entry as file, if it is one
+    pub fn file(&self) -> Option<&File> {
+        match &self.kind {
+            Kind::File(f) => Some(f),
+            Kind::Directory(_) => None,
+        }
+    }
+
+    /// The entry as directory, if it is one
+    pub fn directory(&self) -> Option<&Directory> {
+        match &self.kind {
+            Kind::Directory(d) => Some(d),
+            Kind::File(_) => None,
+        }
+    }
+}
+
+/// A directory
+#[derive(Debug, Clone, Default)]
+pub struct Directory {
+    entries: BTreeMap<String, Entry>,
+}
+
+impl Directory {
+    /// The entries of the directory, sorted by name
+    pub fn entries(&self) -> impl Iterator<Item = (&String, &Entry)> {
+        self.entries.iter()
+    }
+}
+
+/// A file
+#[derive(Debug, Clone)]
+pub struct File {
+    contents: Arc<str>,
+    program: Option<u8>,
+    hook: Option<Hook>,
+}
+
+impl File {
+    /// The contents of the file
+    pub fn contents(&self) -> &str {
+        &self.contents
+    }
+
+    /// Strength of the ICE the file cracks, if it is a program
+    pub fn program(&self) -> Option<u8> {
+        self.program
+    }
+
+    /// What happens when the file is read
+    pub fn hook(&self) -> Option<&Hook> {
+        self.hook.as_ref()
+    }
+}
+
+/// A virtual filesystem
+#[derive(Debug, Clone, Default)]
+pub struct FileSystem {
+    root: Directory,
+}
+
+impl FileSystem {
+    /// Create an empty filesystem
+    pub fn new() -> FileSystem {
+        FileSystem::default()
+    }
+
+    /// Add a file, creating the directories leading to it
+    ///
+    /// An existing file is replaced. Nothing is added if a file is in the way
+    /// of a directory on the path.
+    pub fn add_file(&mut self, path: &str, contents: &str) {
+        let file = File { contents: intern(contents), program: None, hook: None };
+        self.insert(path, Kind::File(file));
+    }
+
+    /// Add a program cracking ICE up to the given strength
+    ///
+    /// The contents of the file are shown when it is read.
+    pub fn add_program(&mut self, path: &str, contents: &str, strength: u8) {
+        let file = File { contents: intern(contents), program: Some(strength), hook: None };
+        self.insert(path, Kind::File(file));
+    }
+
+    /// Add a directory, creating the directories leading to it
+    pub fn add_dir(&mut self, path: &str) {
+        self.make_dirs(&resolve(&[], path));
+    }
+
+    /// Lock an entry with ICE of the given strength
+    pub fn lock(&mut self, path: &str, strength: u8) {
+        if let Some(entry) = self.get_mut(&resolve(&[], path)) {
+            entry.ice = strength;
+        }
+    }
+
+    /// Hide an entry, so it is only listed with "ls -a"
+    pub fn hide(&mut self, path: &str) {
+        if let Some(entry) = self.get_mut(&resolve(&[], path)) {
+            entry.hidden = true;
+        }
+    }
+
+    /// Run a hook whenever the file is read
+    pub fn on_read(&mut self, path: &str, hook: Hook) {
+        if let Some(Entry { kind: Kind::File(file), .. }) = self.get_mut(&resolve(&[], path)) {
+            file.hook = Some(hook);
+        }
+    }
+
+    /// The entry at the given path
+    pub fn get(&self, path: &[String]) -> Option<&Entry> {
+        let mut directory = &self.root;
+        let (name, parents) = path.split_last()?;
+        for component in parents {
+            directory = directory.entries.get(component)?.directory()?;
+        }
+        directory.entries.get(name)
+    }
+
+    /// The directory at the given path
+    pub fn dir(&self, path: &[String]) -> Option<&Directory> {
+        if path.is_empty() {
+            return Some(&self.root);
+        }
+        self.get(path)?.directory()
+    }
+
+    /// The ICE on the path to an entry (including the entry)
+    ///
+    /// Returns the locked path and the strength of its ICE for every locked
+    /// entry on the way.
+    pub fn ice_on(&self, path: &[String]) -> Vec<(Path, u8)> {
+        (1..=path.len())
+            .filter_map(|i| self.get(&path[..i]).map(|e| (path[..i].to_vec(), e.ice)))
+            .filter(|(_, ice)| *ice > 0)
+            .collect()
+    }
+
+    /// Insert an entry, creating the directories leading to it
+    fn insert(&mut self, path: &str, kind: Kind) {
+        let mut components = resolve(&[], path);
+        let name = match components.pop() {
+            Some(n) => n,
+            None => return,
+        };
+        if let Some(directory) = self.make_dirs(&components) {
+            directory.entries.insert(name, Entry { kind, ice: 0, hidden: false });
+        }
+    }
+
+    /// The entry at the given path, to change it
+    fn get_mut(&mut self, path: &[String]) -> Option<&mut Entry> {
+        let mut directory = &mut self.root;
+        let (name, parents) = path.split_last()?;
+        for component in parents {
+            directory = match directory.entries.get_mut(component)? {
+                Entry { kind: Kind::Directory(d), .. } => d,
+                _ => return None,
+            };
+        }
+        directory.entries.get_mut(name)
+    }
+
+    /// Get the directory at the given path, creating missing directories
+    fn make_dirs(&mut self, path: &[String]) -> Option<&mut Directory> {
+        let mut directory = &mut self.root;
+        for component in path {
+            let entry = directory.entries.entry(component.clone())
+                .or_insert_with(|| Entry { kind: Kind::Directory(Directory::default()), ice: 0, hidden: false });
+            directory = match entry {
+                Entry { kind: Kind::Directory(d), .. } => d,
+                _ => return None,
+            };
+        }
+        Some(directory)
+    }
+}
+
+/// Resolve a path against a working directory
+///
+/// Handles absolute paths, "." and "..". Going up from the root directory
+/// stays in the root directory.
+pub fn resolve(cwd: &[String], path: &str) -> Path {
+    let mut resolved: Path = if path.starts_with('/') { Vec::new() } else { cwd.to_vec() };
+    for component in path.split('/') {
+        match component {
+            "" | "." => {},
+            ".." => { resolved.pop(); },
+            c => resolved.push(c.to_string()),
+        }
+    }
+    resolved
+}
+
+/// Display a path as on unix ("/" for the root directory)
+pub fn display(path: &[String]) -> String {
+    format!("/{}", path.join("/"))
+}
@@ -0,0 +1,58 @@
+//! Content filter
+//!
+//! An optional filter over text that players send to each other (eg. say).
+//! Words on the configured word list are masked on the receiving side, so
+//! players can opt out of the filter for the text they receive.
+
+/// Filter masking profane words in public text
+#[derive(Debug, Clone, Default)]
+pub struct ContentFilter {
+    /// The filter is only applied if enabled
+    pub enabled: bool,
+    /// Words that are masked (ignoring case)
+    pub words: Vec<String>,
+}
+
+impl ContentFilter {
+    /// Create a new content filter
+    pub fn new(enabled: bool, words: Vec<String>) -> ContentFilter {
+        ContentFilter {
+            enabled,
+            words: words.iter().map(|w| w.to_lowercase()).collect(),
+        }
+    }
+
+    /// Apply the filter to a text
+    ///
+    /// Every word containing one of the words on the word list is replaced by
+    /// asterisks of the same length. Anything else (including whitespace and
+    /// punctuation) is kept as is. If the filter is disabled, the text is
+    /// returned unchanged.
+    pub fn apply(&self, text: &str) -> String {
+        if !self.enabled || self.words.is_empty() {
+            return text.to_string();
+        }
+
+        let mut filtered = String::with_capacity(text.len());
+        let mut word = String::new();
+        for c in text.chars().chain(std::iter::once(' ')) {
+            if c.is_alphanumeric() {
+                word.push(c);
+                continue;
+            }
+            if !word.is_empty() {
+                let lower = word.to_lowercase();
+                if self.words.iter().any(|w| lower.contains(w.as_str())) {
+                    filtered.push_str(&"*".repeat(word.chars().count()));
+                } else {
+                    filtered.push_str(&word);
+                }
+                word.clear();
+            }
+            filtered.push(c);
+        }
+        // Remove the space added to flush the last word
+        filtered.pop();
+        filtered
+    }
+}
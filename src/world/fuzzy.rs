@@ -0,0 +1,98 @@
+//! Fuzzy nouns
+//!
+//! Players mistype and pluralize ("prot", "ports"), so nouns are resolved
+//! against the names of the assets in two steps:
+//!  * stemming: the plural endings are dropped on both sides, "ports" is the
+//!    port
+//!  * edit distance: the edits (insertions, deletions, substitutions and
+//!    swaps of neighbouring letters) turning the noun into a name, divided by
+//!    the length of the longer word, give the confidence of the match
+//!
+//! A name matched with confidence is taken. If the closest name is only
+//! plausible, the player is asked if it meant that name. The matching is a
+//! plain algorithm, no language model is involved.
+
+/// Lowest confidence taking a name for the noun
+pub const CONFIDENT: f64 = 0.75;
+
+/// Lowest confidence asking the player if it meant the name
+pub const PLAUSIBLE: f64 = 0.6;
+
+/// The stem of a noun: lowercase and without plural ending (eg. "ports" is
+/// "port", "proxies" is "proxy")
+pub fn stem(noun: &str) -> String {
+    let noun = noun.to_lowercase();
+    if let Some(s) = noun.strip_suffix("ies").filter(|s| s.len() > 1) {
+        return format!("{}y", s);
+    }
+    if let Some(s) = noun.strip_suffix("es").filter(|s| ["s", "x", "z", "ch", "sh"].iter().any(|e| s.ends_with(e))) {
+        return s.to_string();
+    }
+    match noun.strip_suffix('s') {
+        Some(s) if !s.is_empty() && !s.ends_with('s') => s.to_string(),
+        _ => noun,
+    }
+}
+
+/// Number of edits turning one word into the other, a swap of neighbouring
+/// letters counting as one edit
+pub fn distance(a: &str, b: &str) -> usize {
+    let (a, b): (Vec<char>, Vec<char>) = (a.chars().collect(), b.chars().collect());
+    // Three rows of the matrix are enough: the swaps look two rows back
+    let mut before: Vec<usize> = Vec::new();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for i in 1..=a.len() {
+        let mut current = vec![i; b.len() + 1];
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            current[j] = (previous[j] + 1).min(current[j - 1] + 1).min(previous[j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                current[j] = current[j].min(before[j - 2] + 1);
+            }
+        }
+        before = std::mem::replace(&mut previous, current);
+    }
+    previous[b.len()]
+}
+
+/// How sure it is that a noun means a name, from 0 (not at all) to 1 (the
+/// same stem)
+pub fn confidence(noun: &str, name: &str) -> f64 {
+    let (noun, name) = (stem(noun), stem(name));
+    let length = noun.chars().count().max(name.chars().count());
+    if length == 0 {
+        return 0.0;
+    }
+    1.0 - distance(&noun, &name) as f64 / length as f64
+}
+
+/// Resolve a noun against names
+///
+/// Returns the closest name if it is matched with confidence, None if no
+/// name is even plausible, or the names to ask the player about (see
+/// `question`) if the closest name is only plausible or several names are
+/// as close.
+pub fn resolve<'a>(noun: &str, names: impl IntoIterator<Item = &'a str>) -> Result<Option<&'a str>, Vec<&'a str>> {
+    let mut closest: Vec<&str> = Vec::new();
+    let mut best = PLAUSIBLE;
+    for name in names {
+        let c = confidence(noun, name);
+        if c > best {
+            best = c;
+            closest.clear();
+        }
+        if c >= best && !closest.iter().any(|n| n.eq_ignore_ascii_case(name)) {
+            closest.push(name);
+        }
+    }
+    match closest.as_slice() {
+        [] => Ok(None),
+        [name] if best >= CONFIDENT => Ok(Some(name)),
+        names => Err(names.to_vec()),
+    }
+}
+
+/// The question asking the player which of the names it meant
+pub fn question(names: &[&str]) -> String {
+    format!("Did you mean the {}?", names.join(" or the "))
+}
@@ -0,0 +1,210 @@
+//! Goals
+//!
+//! Collective goals give the community shared aims, eg. liberating subnet-7
+//! during the con. A goal counts a deed of every player (eg. destroying the
+//! warden of the mainframe) and sets its world flag once the count reaches
+//! its target. The flag changes the world for everybody:
+//!  * it rewrites the descriptions of nodes and assets
+//!  * it opens ports (eg. into a zone liberated)
+//!  * it changes what NPCs say
+//!
+//! "goals" shows the players how far the goals are. Flags are never
+//! cleared. The counts and the flags set are stored in the goal file inside
+//! the data directory whenever they change, the world applies the changes of
+//! the flags set again on startup.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::io;
+use std::path::{Path, PathBuf};
+use tokio::fs;
+use tracing::{error, info};
+
+use crate::connection_manager::ClientId;
+use super::assets::AssetID;
+use super::messages::{Event, Message};
+use super::{GameWorld, Player};
+
+/// Name of the file the progress of the goals is stored in (inside the data
+/// directory)
+pub const GOAL_FILE: &str = "goals.json";
+
+/// What players do for a goal
+#[derive(Debug, Clone, PartialEq)]
+pub enum Deed {
+    /// Destroy an NPC of the name (eg. "warden")
+    Destroy(String),
+    /// Break the ICE of the uid (see `ice`)
+    Break(AssetID),
+    /// Solve the puzzle of the id (see `puzzles`)
+    Solve(String),
+}
+
+/// How a world flag changes the world
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    /// Rewrite the description of a node or an asset (by uid)
+    Describe { uid: AssetID, text: String },
+    /// Open a port (by uid) to everybody
+    Open { port: AssetID },
+    /// Change what the NPCs of the name say
+    Say { npc: String, line: String },
+}
+
+/// A collective goal
+#[derive(Debug, Clone)]
+pub struct Goal {
+    /// The world flag the goal sets (eg. "subnet-7-liberated")
+    flag: String,
+    /// What to do, shown to the players
+    description: String,
+    deed: Deed,
+    target: u64,
+    /// Told to everybody when the goal is reached
+    announce: Option<String>,
+    changes: Vec<Change>,
+}
+
+impl Goal {
+    /// Create a goal setting the flag once the deed was done the target
+    /// number of times
+    pub fn new(flag: &str, description: &str, deed: Deed, target: u64) -> Goal {
+        Goal { flag: flag.to_string(), description: description.to_string(), deed, target: target.max(1),
+            announce: None, changes: Vec::new() }
+    }
+
+    /// Tell everybody the text when the goal is reached
+    pub fn announcing(mut self, text: &str) -> Goal {
+        self.announce = Some(text.to_string());
+        self
+    }
+
+    /// Add a change of the world the flag of the goal makes
+    pub fn with_change(mut self, change: Change) -> Goal {
+        self.changes.push(change);
+        self
+    }
+
+    /// The world flag the goal sets
+    pub fn flag(&self) -> &str {
+        &self.flag
+    }
+
+    /// The text told to everybody when the goal is reached, if any
+    pub fn announce(&self) -> Option<&str> {
+        self.announce.as_deref()
+    }
+
+    /// The changes of the world the flag of the goal makes
+    pub fn changes(&self) -> &[Change] {
+        &self.changes
+    }
+}
+
+/// The counts of the deeds and the flags set
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+struct Progress {
+    /// The count of every goal, by its flag
+    counts: BTreeMap<String, u64>,
+    flags: BTreeSet<String>,
+}
+
+/// The goals of the world and their progress
+#[derive(Debug, Default)]
+pub struct Goals {
+    goals: Vec<Goal>,
+    progress: Progress,
+}
+
+impl Goals {
+    /// Load the progress stored in the data directory, without goals
+    pub async fn load(data_dir: &Path) -> io::Result<Goals> {
+        let progress = match fs::read(Goals::path(data_dir)).await {
+            Ok(buf) => serde_json::from_slice(&buf)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Progress::default(),
+            Err(e) => return Err(e),
+        };
+        Ok(Goals { goals: Vec::new(), progress })
+    }
+
+    /// Add a goal
+    pub fn add(&mut self, goal: Goal) {
+        self.goals.push(goal);
+    }
+
+    /// Count a deed of a player for the goals not reached yet
+    ///
+    /// Returns the goals the deed reached, None if no goal counts it.
+    pub fn record(&mut self, deed: &Deed) -> Option<Vec<Goal>> {
+        let mut counted = false;
+        let mut reached = Vec::new();
+        for goal in &self.goals {
+            if &goal.deed != deed || self.progress.flags.contains(&goal.flag) {
+                continue;
+            }
+            counted = true;
+            let count = self.progress.counts.entry(goal.flag.clone()).or_default();
+            *count += 1;
+            if *count >= goal.target {
+                self.progress.flags.insert(goal.flag.clone());
+                reached.push(goal.clone());
+            }
+        }
+        Some(reached).filter(|_| counted)
+    }
+
+    /// The goals whose flags are set
+    pub fn reached(&self) -> impl Iterator<Item = &Goal> {
+        self.goals.iter().filter(move |g| self.progress.flags.contains(&g.flag))
+    }
+
+    /// Describe the goals and how far they are for the players
+    pub fn describe(&self) -> String {
+        if self.goals.is_empty() {
+            return "There are no goals. The grid is quiet.".to_string();
+        }
+        let lines: Vec<String> = self.goals.iter()
+            .map(|g| match self.progress.flags.contains(&g.flag) {
+                true => format!("  {} (reached, {})", g.description, g.flag),
+                false => format!("  {} ({}/{})", g.description, self.progress.counts.get(&g.flag).unwrap_or(&0),
+                    g.target),
+            })
+            .collect();
+        format!("Goals of the community:\r\n{}", lines.join("\r\n"))
+    }
+
+    /// Save the progress to the data directory
+    pub async fn save(&self, data_dir: &Path) -> io::Result<()> {
+        fs::create_dir_all(data_dir).await?;
+        fs::write(Goals::path(data_dir), serde_json::to_vec_pretty(&self.progress)?).await
+    }
+
+    fn path(data_dir: &Path) -> PathBuf {
+        data_dir.join(GOAL_FILE)
+    }
+}
+
+/// Count a deed of a player for the goals
+///
+/// The flags of the goals reached change the world and are announced to
+/// everybody.
+pub(super) async fn accomplish(deed: &Deed, world: &mut GameWorld, players: &HashMap<ClientId, Player>) {
+    let reached = match world.goals.record(deed) {
+        Some(r) => r,
+        None => return,
+    };
+    if let Err(e) = world.goals.save(&world.data_dir).await {
+        error!("Could not save the goals: {}", e);
+    }
+    for goal in reached {
+        info!("Goal {} was reached.", goal.flag());
+        for change in goal.changes() {
+            world.apply_change(change);
+        }
+        if let Some(text) = goal.announce() {
+            let message = Message::new(Event::Info, text).with_field("flag", goal.flag());
+            for p in players.values() {
+                p.send(message.clone()).await;
+            }
+        }
+    }
+}
@@ -5,16 +5,54 @@
 //! 
 //! The grammar supported is:
 //! ```ignore
-//!     <sentence> ::= <action> | <command>
-//!     <action> ::= <verb> <blank> <adverblist> <blank> <object> ("." | E)
-//!     <command> ::= "help" (<blank> <topic> | E) | "inventory"
+//!     <sentence> ::= ("please" <blank> | E) (<action> | <access> | <puzzle> | <combat> | <carry>
+//!         | <command> | <plugin>)
+//!     <action> ::= <verb> <blank> <adverblist> <blank> (<preposition> <blank> | E) <object> ("." | E)
+//!     <command> ::= "help" (<blank> <topic> (<blank> <number> | E) | E) | "inventory" | "stop" | "quit"
+//!         | "robot" (<blank> ("on" | "off") | E) | "bug" <blank> <text>
+//!         | "note" <blank> <text> | "journal" (<blank> <text> | E)
+//!         | "title" (<blank> <text> | E) | "who" | "score" | "goals" | "say" <blank> <text>
+//!         | "vote" (<blank> <option> | E)
+//!         | "schedule" (<blank> ("remind" | "forget") <blank> <number> | E)
+//!         | "shout" <blank> <text>
+//!         | "describe" <blank> "me" (<blank> <text> | E)
+//!         | "ignore" (<blank> <player> | E) | "unignore" <blank> <player>
+//!         | "prefs" (<blank> ("tells" | "trades" | "duels" | "follows") <blank> ("on" | "off") | E)
+//!         | "filter" <blank> ("on" | "off")
+//!         | "transcript" (<blank> ("on" | "off" | "mail me") | E)
+//!         | "trigger" (<blank> "\"" <text> "\"" <blank> <sentence> | E)
+//!         | "untrigger" <blank> <ordinal>
+//!         | "bind" (<blank> <key> (<blank> <sentence> | E) | E)
+//!         | "animations" (<blank> ("on" | "off") | E)
+//!         | "media" <blank> ("msp" | "gmcp" | "off") | "mute" (<blank> ("on" | "off") | E)
+//!         | "challenge" (<blank> <text> | E) | "join" <blank> <text>
+//!         | "leaderboard" (<blank> <text> | E) | "watch" (<blank> <zone> | E)
+//!         | "tell" <blank> <player> ("@" <world> | E) <blank> <text>
+//!         | "combat" (<blank> ("full" | "summary" | "minimal") | E)
+//!         | "cloak" (<blank> ("on" | "off") | E) | "scan"
+//!         | "drone" (<blank> ("deploy" | "recall" | "scan" | "attack" <blank> <object>) | E)
+//!         | "market" (<blank> <trade> | E) | "mail"
+//!         | "balance" | ("deposit" | "withdraw") <blank> <funds>
+//!         | ("dice" | "poker") <blank> <number>
+//!         | "wares" | ("buy" | "sell") <blank> <text> | "bribe"
+//!     <trade> ::= "search" <blank> <text> | "sell" <blank> <text> <blank> <number>
+//!         | ("buy" | "cancel") <blank> <number>
+//!     <funds> ::= <number> (<blank> "credits" | E) | <text>
 //!     <adverblist> ::= <adverb> | <adverb> (","+ <blank>* | <blank>+) <adverblist> | E
 //!     <adverb> ::= "quickly" | "slowly"
 //!     <do> ::= "do"
 //!     <verb> ::= "look" | "read" | "enter" | "connect" | "access" | "open"
-//!     <object> ::= <article> ("port" | "ram bank" | "quickhack")
-//!     <article> ::= ("the" <blank> | E)
-//!     <topic> ::= "verbs" | "inventory" | "combat" 
+//!     <access> ::= ("access" | "enter" | "connect") (<blank> <adverblist> | E) (<blank> <object> | E)
+//!     <puzzle> ::= "solve" <blank> <object> <blank> <text> | "hint" <blank> <object>
+//!     <combat> ::= ("hack" | "taunt" | "attack" | "crack") <blank> <object>
+//!     <carry> ::= ("take" | "steal" | "drop") <blank> <object>
+//!     <plugin> ::= <registered verb> (<blank> <text> | E)
+//!     <object> ::= (<filler> <blank>)* (<ordinal> "." | E) <noun> (<blank> <ordinal> | E) (<blank> <filler>)*
+//!     <noun> ::= "port" | "terminal" | "ram bank" | "quickhack"
+//!     <ordinal> ::= ("1" | "2" | ... | "9")+
+//!     <filler> ::= "the" | "a" | "an" | "please" | "at" | "to"
+//!     <preposition> ::= "at" | "in" | "into" | "on" | "under" | "behind" | "through"
+//!     <topic> ::= "verbs" | "inventory" | "combat" | ... (see `help`)
 //!     <blank> ::= " "+
 //! ```
 //! 
@@ -22,14 +60,25 @@
 //! - [ ] Maybe use lexxer / parser
 //! - [ ] Define sentence structures
 //! - [ ] Clean up traits identifiable, observable, interactable or should we
-//!         use a generic interacable trait that then reacts upon the action enum?
+//!   use a generic interacable trait that then reacts upon the action enum?
 //! - [ ] Ensure grammar is up to date
 
 use std::convert::TryFrom;
-use tracing::{debug, info, error};
+use tracing::{debug, info};
 
 use crate::world::errors::Error;
 use super::actions::Action;
+use super::media::Protocol;
+use super::combat::Verbosity;
+use super::drones::Order;
+use super::market::Trade;
+use super::bank::{Banking, Funds};
+use super::casino::Game;
+use super::economy::Deal;
+use super::consent::Pref;
+use super::schedule::Scheduling;
+use super::adverbs;
+use super::verbs;
 
 use regex::Regex;
 use lazy_static::lazy_static;
@@ -53,7 +102,15 @@ impl TryFrom<&str> for Action {
         lazy_static! {
             static ref CMD_RE: Regex = Regex::new(r"^([\w\-]+)").unwrap();
         }
-        let mat = CMD_RE.find(item).unwrap();
+        // A polite "please" before the verb is dropped
+        lazy_static! {
+            static ref PLEASE_RE: Regex = Regex::new(r"(?i)^\s*please\s+").unwrap();
+        }
+        let item = PLEASE_RE.find(item).map_or(item, |m| &item[m.end()..]);
+        let mat = match CMD_RE.find(item) {
+            Some(m) => m,
+            None => return Err(Error::VerbUnknownError),
+        };
         let command = &item[mat.start()..mat.end()];
 
         // Check if the first word is a legitimate command and then depending
@@ -61,96 +118,478 @@ impl TryFrom<&str> for Action {
         for i in synonyms(command) {
             match i.to_lowercase().as_str() {
                 "look" => {
-                    if mat.end() == item.len() {
-                        // No more remaining characters. We have a simple "look" command.
-                        debug!("Found simple look command: \"{}\"", command);
-                        return Ok(Action::Look {target: None, preposition: None, properties: None});
-                    } else {
-                        debug!("Found command \"{}\". Rest of data message is \"{}\"", command, &item[mat.end()+1..]);
-                    
-                        // Try to match either a simple look command or a complex look command
-                        // For a simple look command only whitespaces and an optional dot may follow.
+                    // The adverbs come first, eg. "look slowly at the port"
+                    let (manner, rest) = adverbs::split(&item[mat.end()..])?;
+                    // The preposition is optional, the articles and fillers
+                    // are dropped, eg. "look at the purple port please"
+                    lazy_static! {
+                        static ref PREPOSITION_RE: Regex 
+                            = Regex::new(r"(?i)^(at|in|into|on|under|behind|through)\b").unwrap();
+                    }
+                    let preposition = PREPOSITION_RE.find(rest).map(|m| m.as_str().to_lowercase());
+                    let rest = strip_fillers(&rest[preposition.as_ref().map_or(0, |p| p.len())..]);
+                    match (rest.is_empty(), &preposition) {
+                        (true, None) => {
+                            // Nothing but fillers. We have a simple "look" command.
+                            debug!("Found simple look command: \"{}\"", command);
+                            return Ok(Action::Look {target: None, ordinal: None, preposition: None, properties: None,
+                                manner});
+                        },
+                        (true, Some(_)) => {
+                            info!("Preposition without an object.");
+                            return Err(Error::VerbEncodingError);
+                        },
+                        (false, _) => {},
+                    }
+                    debug!("Found command \"{}\". Rest of data message is \"{}\"", command, rest);
+
+                    // For a complex look command we need zero or more
+                    // adjectives and a noun. The noun may carry an ordinal
+                    // either as prefix ("2.port") or as suffix ("port 2").
+                    lazy_static! {
+                        static ref COMPLEX_LOOK_RE: Regex 
+                            = Regex::new(r"^((?:\b(?:\p{L}+)\b(?:\s*,\s*|\s+))*)(?:(\d+)\.)?\b(\p{L}+)(?:\s+(\d+))?$").unwrap();
+                    }
+                    let caps = match COMPLEX_LOOK_RE.captures(&rest) {
+                        Some(caps) => caps,
+                        None => {
+                            info!("No complex command found.");
+                            return Err(Error::VerbEncodingError);
+                        },
+                    };
+                    info!("Complex command found: {:?}", caps);
+
+                    // Extract all the properties.
+                    let properties = caps.get(1).filter(|m| !m.as_str().is_empty()).map(|m| {
                         lazy_static! {
-                            static ref LOOK_RE: Regex = Regex::new(r"^\s*\.?\s*$").unwrap();
+                            static ref PROP_RE: Regex = Regex::new(r"([\s*\p{L}]+?)(?:\s*,\s*|\s+|$)").unwrap();
                         }
-                        let look_command = LOOK_RE.find(&item[mat.end()..]);
-
-                        match look_command {
-                            Some(m) => {
-                                // There are only whitespaces and an optional dot. 
-                                // It is a simple look command. Return without target.
-                                return Ok(Action::Look 
-                                    {
-                                        target: None, 
-                                        preposition: None, 
-                                        properties: None
-                                    }
-                                );
-                            },
-                            None => {
-                                // For a complex look command we need an adverb, 
-                                // zero or more adjectives and a noun.
-                                // TODO - maybe we could extract adjectives in 
-                                // one run by adjusting first reges
-                                lazy_static! {
-                                    static ref COMPLEX_LOOK_RE: Regex 
-                                        = Regex::new(r"^\s*\b(\p{L}+)\s+((?:\b(?:\p{L}+)\b(?:\s*,\s*|\s+))*)\b(\p{L}+)\s*\.?\s*$").unwrap();
-                                }
-                                let cap = COMPLEX_LOOK_RE.captures(&item[mat.end()..]);
-                                //match COMPLEX_LOOK_RE.find(&item[mat.end()..]) {
-                                match cap {
-                                    Some(caps) => {
-                                        info!("Complex command found: {:?}", caps);
-                                        // Our capture must match 4 groups (the full match and the groupd)
-                                        // Otherwise something went wrong
-                                        if caps.len() != 4 {
-                                            error!("Invalid complex \"look\" command structure ok.");
-                                            return Err(Error::VerbEncodingError);
-                                        }
-                                        
-                                        // Extract all the properties.
-                                        let properties = caps.get(2).map_or(None, |m| {
-                                            let mut p = Vec::new();
-
-                                            lazy_static! {
-                                                static ref PROP_RE: Regex = Regex::new(r"([\s*\p{L}]+?)(?:\s*,\s*|\s+|$)").unwrap();
-                                            }
-                                            // TODO map string on properties
-                                            // TODO error handling
-                                            for cap in PROP_RE.captures_iter(m.as_str()) {
-                                                let property_str = cap.get(1).map_or("", |m| m.as_str());
-
-                                                // Try to build a property
-                                                p.push(Property::from(property_str));
-                                            }
-                                            Some(p)
-                                        });
-                                
-                                        // TODO set properties
-                                        return Ok(Action::Look {
-                                            target: caps.get(3).map_or(None, |m| Some(m.as_str().to_string())), 
-                                            preposition: caps.get(1).map_or(None, |m| Some(m.as_str().to_string())), 
-                                            properties
-                                        });
-                                    },
-                                    None => {
-                                        info!("No complex command found.");
-                                    },
-                                }
+                        PROP_RE.captures_iter(m.as_str())
+                            .map(|cap| Property::from(cap.get(1).map_or("", |m| m.as_str())))
+                            .collect()
+                    });
+
+                    return Ok(Action::Look {
+                        target: caps.get(3).map(|m| m.as_str().to_string()), 
+                        ordinal: parse_ordinal(caps.get(2), caps.get(4))?,
+                        preposition, 
+                        properties,
+                        manner,
+                    });
+                },
+                "read" => return Ok(Action::Read),
+                "enter" => {
+                    // Like access, but a portal is entered by default
+                    let (manner, arguments) = adverbs::split(&item[mat.end()..])?;
+                    let arguments = arguments.trim();
+                    if arguments.is_empty() {
+                        return Ok(Action::Enter { target: None, ordinal: None, manner });
+                    }
+                    let (target, ordinal) = parse_object(arguments)?;
+                    return Ok(Action::Enter { target: Some(target), ordinal, manner });
+                },
+                "connect" => {
+                    // Like enter, but only through ports to other nodes
+                    let (manner, arguments) = adverbs::split(&item[mat.end()..])?;
+                    let arguments = arguments.trim();
+                    if arguments.is_empty() {
+                        return Ok(Action::Connect { target: None, ordinal: None, manner });
+                    }
+                    let (target, ordinal) = parse_object(arguments)?;
+                    return Ok(Action::Connect { target: Some(target), ordinal, manner });
+                },
+                "access" => {
+                    // The target is optional and may carry an ordinal like
+                    // the target of "look"
+                    let (manner, arguments) = adverbs::split(&item[mat.end()..])?;
+                    let arguments = arguments.trim();
+                    if arguments.is_empty() {
+                        return Ok(Action::Access { target: None, ordinal: None, manner });
+                    }
+                    let (target, ordinal) = parse_object(arguments)?;
+                    return Ok(Action::Access { target: Some(target), ordinal, manner });
+                },
+                "solve" => {
+                    // The answer is everything after the target, kept as
+                    // typed by the player. Only the fillers before the
+                    // target are dropped.
+                    lazy_static! {
+                        static ref SOLVE_RE: Regex = Regex::new(
+                            r"^(?i:(?:the|an?|please|at|to)\s+)*(?:(\d+)\.)?(\p{L}+)(?:\s+(\d+))?\s+(.+)$").unwrap();
+                    }
+                    let caps = match SOLVE_RE.captures(item[mat.end()..].trim()) {
+                        Some(c) => c,
+                        None => return Err(Error::VerbEncodingError),
+                    };
+                    return Ok(Action::Solve { 
+                        target: caps[2].to_lowercase(), 
+                        ordinal: parse_ordinal(caps.get(1), caps.get(3))?,
+                        answer: caps[4].trim().to_string(),
+                    });
+                },
+                "hint" => {
+                    let (target, ordinal) = parse_object(item[mat.end()..].trim())?;
+                    return Ok(Action::Hint { target, ordinal });
+                },
+                "take" => {
+                    let (target, ordinal) = parse_object(item[mat.end()..].trim())?;
+                    return Ok(Action::Take { target, ordinal });
+                },
+                "steal" => {
+                    let (target, ordinal) = parse_object(item[mat.end()..].trim())?;
+                    return Ok(Action::Steal { target, ordinal });
+                },
+                "drop" => {
+                    let (target, ordinal) = parse_object(item[mat.end()..].trim())?;
+                    return Ok(Action::Drop { target, ordinal });
+                },
+                "inventory" => return Ok(Action::Inventory),
+                "help" => {
+                    // A trailing number is the page of the topic
+                    let arguments = item[mat.end()..].trim();
+                    let (topic, page) = match arguments.rsplit_once(char::is_whitespace) {
+                        Some((topic, page)) => match page.parse::<usize>() {
+                            Ok(page) => (topic.trim(), Some(page)),
+                            Err(_) => (arguments, None),
+                        },
+                        None => (arguments, None),
+                    };
+                    if topic.is_empty() {
+                        return Ok(Action::Help { topic: None, page: None });
+                    }
+                    return Ok(Action::Help { topic: Some(topic.to_lowercase()), page });
+                },
+                "hack" => {
+                    // The target is an NPC, referenced like an asset
+                    let (target, ordinal) = parse_object(item[mat.end()..].trim())?;
+                    return Ok(Action::Hack { target, ordinal });
+                },
+                "taunt" => {
+                    let (target, ordinal) = parse_object(item[mat.end()..].trim())?;
+                    return Ok(Action::Taunt { target, ordinal });
+                },
+                "attack" => {
+                    let (target, ordinal) = parse_object(item[mat.end()..].trim())?;
+                    return Ok(Action::Attack { target, ordinal });
+                },
+                "crack" => {
+                    let (target, ordinal) = parse_object(item[mat.end()..].trim())?;
+                    return Ok(Action::Crack { target, ordinal });
+                },
+                "open" => return Ok(Action::Open),
+                "stop" => return Ok(Action::Stop),
+                "quit" => return Ok(Action::Quit),
+                "bug" => {
+                    // The description is kept as typed by the player
+                    return Ok(Action::Bug(item[mat.end()..].trim().to_string()));
+                },
+                "note" => {
+                    // The note is kept as typed by the player
+                    return Ok(Action::Note(item[mat.end()..].trim().to_string()));
+                },
+                "journal" => {
+                    let search = item[mat.end()..].trim();
+                    if search.is_empty() {
+                        return Ok(Action::Journal(None));
+                    } else {
+                        return Ok(Action::Journal(Some(search.to_string())));
+                    }
+                },
+                "title" => {
+                    let title = item[mat.end()..].trim();
+                    if title.is_empty() {
+                        return Ok(Action::Title(None));
+                    } else {
+                        return Ok(Action::Title(Some(title.to_string())));
+                    }
+                },
+                "describe" => {
+                    let rest = item[mat.end()..].trim();
+                    let description = match rest.split_once(char::is_whitespace) {
+                        Some((me, text)) if me.eq_ignore_ascii_case("me") => Some(text.trim().to_string()),
+                        None if rest.eq_ignore_ascii_case("me") => None,
+                        _ => return Err(Error::VerbEncodingError),
+                    };
+                    return Ok(Action::Describe(description));
+                },
+                "ignore" => {
+                    let player = item[mat.end()..].trim();
+                    if player.is_empty() {
+                        return Ok(Action::Ignore(None));
+                    } else if player.contains(char::is_whitespace) {
+                        return Err(Error::VerbEncodingError);
+                    }
+                    return Ok(Action::Ignore(Some(player.to_string())));
+                },
+                "unignore" => {
+                    let player = item[mat.end()..].trim();
+                    if player.is_empty() || player.contains(char::is_whitespace) {
+                        return Err(Error::VerbEncodingError);
+                    }
+                    return Ok(Action::Unignore(player.to_string()));
+                },
+                "prefs" => {
+                    let arguments = item[mat.end()..].trim();
+                    if arguments.is_empty() {
+                        return Ok(Action::Prefs(None));
+                    }
+                    let (pref, on) = match arguments.split_once(char::is_whitespace) {
+                        Some((pref, on)) => (Pref::try_from(pref).map_err(|_| Error::VerbEncodingError)?, on.trim()),
+                        None => return Err(Error::VerbEncodingError),
+                    };
+                    match on.to_lowercase().as_str() {
+                        "on" => return Ok(Action::Prefs(Some((pref, true)))),
+                        "off" => return Ok(Action::Prefs(Some((pref, false)))),
+                        _ => return Err(Error::VerbEncodingError),
+                    }
+                },
+                "score" => return Ok(Action::Score),
+                "goals" => return Ok(Action::Goals),
+                "schedule" => {
+                    let arguments: Vec<&str> = item[mat.end()..].split_whitespace().collect();
+                    let id = |id: &str| id.trim_start_matches('#').parse::<u64>().map_err(|_| Error::VerbEncodingError);
+                    let scheduling = match arguments.as_slice() {
+                        [] => Scheduling::List,
+                        [remind, n] if remind.eq_ignore_ascii_case("remind") => Scheduling::Remind(id(n)?),
+                        [forget, n] if forget.eq_ignore_ascii_case("forget") => Scheduling::Forget(id(n)?),
+                        _ => return Err(Error::VerbEncodingError),
+                    };
+                    return Ok(Action::Schedule(scheduling));
+                },
+                "vote" => {
+                    let choice = item[mat.end()..].trim();
+                    return Ok(Action::Vote(Some(choice.to_string()).filter(|c| !c.is_empty())));
+                },
+                "who" => return Ok(Action::Who),
+                "say" => {
+                    let text = item[mat.end()..].trim();
+                    if text.is_empty() {
+                        return Err(Error::VerbEncodingError);
+                    }
+                    return Ok(Action::Say(text.to_string()));
+                },
+                "shout" => {
+                    let text = item[mat.end()..].trim();
+                    if text.is_empty() {
+                        return Err(Error::VerbEncodingError);
+                    }
+                    return Ok(Action::Shout(text.to_string()));
+                },
+                "filter" => {
+                    match item[mat.end()..].trim().to_lowercase().as_str() {
+                        "on" => return Ok(Action::Filter(true)),
+                        "off" => return Ok(Action::Filter(false)),
+                        _ => return Err(Error::VerbEncodingError),
+                    }
+                },
+                "transcript" => {
+                    match item[mat.end()..].trim().to_lowercase().as_str() {
+                        "" => return Ok(Action::Transcript(None)),
+                        "on" => return Ok(Action::Transcript(Some(true))),
+                        "off" => return Ok(Action::Transcript(Some(false))),
+                        "mail me" => return Ok(Action::MailTranscript),
+                        _ => return Err(Error::VerbEncodingError),
+                    }
+                },
+                "trigger" => {
+                    // The text to wait for is quoted, the command follows it
+                    let arguments = item[mat.end()..].trim();
+                    if arguments.is_empty() {
+                        return Ok(Action::Trigger(None));
+                    }
+                    let quoted = match arguments.strip_prefix('"') {
+                        Some(q) => q,
+                        None => return Err(Error::VerbEncodingError),
+                    };
+                    return match quoted.split_once('"') {
+                        Some((pattern, command)) => {
+                            Ok(Action::Trigger(Some((pattern.to_string(), command.trim().to_string()))))
+                        },
+                        None => Err(Error::VerbEncodingError),
+                    };
+                },
+                "bind" => {
+                    // The key is the first word, the command the rest
+                    let arguments = item[mat.end()..].trim();
+                    let (key, command) = match arguments.split_once(char::is_whitespace) {
+                        Some((k, c)) => (Some(k.to_string()), Some(c.trim().to_string())),
+                        None if arguments.is_empty() => (None, None),
+                        None => (Some(arguments.to_string()), None),
+                    };
+                    return Ok(Action::Bind { key, command });
+                },
+                "untrigger" => {
+                    match item[mat.end()..].trim().parse::<usize>() {
+                        Ok(n) => return Ok(Action::Untrigger(n)),
+                        Err(_) => return Err(Error::VerbEncodingError),
+                    }
+                },
+                "animations" => {
+                    match item[mat.end()..].trim().to_lowercase().as_str() {
+                        "" => return Ok(Action::Animations(None)),
+                        "on" => return Ok(Action::Animations(Some(true))),
+                        "off" => return Ok(Action::Animations(Some(false))),
+                        _ => return Err(Error::VerbEncodingError),
+                    }
+                },
+                "media" => {
+                    match item[mat.end()..].trim().to_lowercase().as_str() {
+                        "msp" => return Ok(Action::Media(Some(Protocol::Msp))),
+                        "gmcp" => return Ok(Action::Media(Some(Protocol::Gmcp))),
+                        "off" => return Ok(Action::Media(None)),
+                        _ => return Err(Error::VerbEncodingError),
+                    }
+                },
+                "combat" => {
+                    match item[mat.end()..].trim().to_lowercase().as_str() {
+                        "" => return Ok(Action::Combat(None)),
+                        "full" => return Ok(Action::Combat(Some(Verbosity::Full))),
+                        "summary" => return Ok(Action::Combat(Some(Verbosity::Summary))),
+                        "minimal" => return Ok(Action::Combat(Some(Verbosity::Minimal))),
+                        _ => return Err(Error::VerbEncodingError),
+                    }
+                },
+                "cloak" => {
+                    match item[mat.end()..].trim().to_lowercase().as_str() {
+                        "" | "on" => return Ok(Action::Cloak(true)),
+                        "off" => return Ok(Action::Cloak(false)),
+                        _ => return Err(Error::VerbEncodingError),
+                    }
+                },
+                "scan" => return Ok(Action::Scan),
+                "drone" => {
+                    let arguments = item[mat.end()..].trim();
+                    let (order, rest) = arguments.split_once(char::is_whitespace).unwrap_or((arguments, ""));
+                    return match order.to_lowercase().as_str() {
+                        "" => Ok(Action::Drone(Order::Status)),
+                        "deploy" => Ok(Action::Drone(Order::Deploy)),
+                        "recall" => Ok(Action::Drone(Order::Recall)),
+                        "scan" => Ok(Action::Drone(Order::Scan)),
+                        "attack" => {
+                            let (target, ordinal) = parse_object(rest)?;
+                            Ok(Action::Drone(Order::Attack { target, ordinal }))
+                        },
+                        _ => Err(Error::VerbEncodingError),
+                    };
+                },
+                "market" => {
+                    // The item sold may have several words, the price is the
+                    // last word ("market sell purple keycard 50")
+                    let arguments = item[mat.end()..].trim();
+                    let (trade, rest) = arguments.split_once(char::is_whitespace).unwrap_or((arguments, ""));
+                    let rest = rest.trim();
+                    return match (trade.to_lowercase().as_str(), rest) {
+                        ("", _) => Ok(Action::Market(Trade::Browse(None))),
+                        ("search", "") => Err(Error::VerbEncodingError),
+                        ("search", term) => Ok(Action::Market(Trade::Browse(Some(term.to_string())))),
+                        ("sell", _) => match rest.rsplit_once(char::is_whitespace) {
+                            Some((item, price)) => match price.parse::<u64>() {
+                                Ok(price) if price > 0 => {
+                                    Ok(Action::Market(Trade::Sell { item: item.trim().to_lowercase(), price }))
+                                },
+                                _ => Err(Error::VerbEncodingError),
                             },
-                        }
+                            None => Err(Error::VerbEncodingError),
+                        },
+                        ("buy", id) => id.trim_start_matches('#').parse::<u64>()
+                            .map(|id| Action::Market(Trade::Buy(id)))
+                            .map_err(|_| Error::VerbEncodingError),
+                        ("cancel", id) => id.trim_start_matches('#').parse::<u64>()
+                            .map(|id| Action::Market(Trade::Cancel(id)))
+                            .map_err(|_| Error::VerbEncodingError),
+                        _ => Err(Error::VerbEncodingError),
+                    };
+                },
+                "mail" => return Ok(Action::Mail),
+                "dice" | "poker" => {
+                    let wager = match item[mat.end()..].trim().parse::<u64>() {
+                        Ok(w) if w > 0 => w,
+                        _ => return Err(Error::VerbEncodingError),
+                    };
+                    if i.eq_ignore_ascii_case("dice") {
+                        return Ok(Action::Gamble(Game::Dice(wager)));
+                    }
+                    return Ok(Action::Gamble(Game::Poker(wager)));
+                },
+                "wares" => return Ok(Action::Vendor(Deal::Wares)),
+                "buy" | "sell" => {
+                    let item = item[mat.end()..].trim().to_lowercase();
+                    if item.is_empty() {
+                        return Err(Error::VerbEncodingError);
+                    }
+                    if i.eq_ignore_ascii_case("buy") {
+                        return Ok(Action::Vendor(Deal::Buy(item)));
+                    }
+                    return Ok(Action::Vendor(Deal::Sell(item)));
+                },
+                "bribe" => return Ok(Action::Bribe),
+                "balance" => return Ok(Action::Bank(Banking::Balance)),
+                "deposit" => return Ok(Action::Bank(Banking::Deposit(parse_funds(&item[mat.end()..])?))),
+                "withdraw" => return Ok(Action::Bank(Banking::Withdraw(parse_funds(&item[mat.end()..])?))),
+                "mute" => {
+                    match item[mat.end()..].trim().to_lowercase().as_str() {
+                        "" => return Ok(Action::Mute(None)),
+                        "on" => return Ok(Action::Mute(Some(true))),
+                        "off" => return Ok(Action::Mute(Some(false))),
+                        _ => return Err(Error::VerbEncodingError),
+                    }
+                },
+                "challenge" => {
+                    let challenge = item[mat.end()..].trim();
+                    if challenge.is_empty() {
+                        return Ok(Action::Challenge(None));
+                    }
+                    return Ok(Action::Challenge(Some(challenge.to_lowercase())));
+                },
+                "join" => {
+                    let player = item[mat.end()..].trim();
+                    if player.is_empty() {
+                        return Err(Error::VerbEncodingError);
+                    }
+                    return Ok(Action::Join(player.to_string()));
+                },
+                "leaderboard" => {
+                    let challenge = item[mat.end()..].trim();
+                    if challenge.is_empty() {
+                        return Ok(Action::Leaderboard(None));
+                    }
+                    return Ok(Action::Leaderboard(Some(challenge.to_lowercase())));
+                },
+                "tell" => {
+                    // The player may be in a linked world ("neo@hackerspace")
+                    let (to, text) = match item[mat.end()..].trim().split_once(char::is_whitespace) {
+                        Some((to, text)) if !text.trim().is_empty() => (to, text.trim()),
+                        _ => return Err(Error::VerbEncodingError),
+                    };
+                    let (to, world) = match to.split_once('@') {
+                        Some((to, world)) if !to.is_empty() && !world.is_empty() => (to, Some(world.to_string())),
+                        Some(_) => return Err(Error::VerbEncodingError),
+                        None => (to, None),
+                    };
+                    return Ok(Action::Tell { to: to.to_string(), world, text: text.to_string() });
+                },
+                "watch" => {
+                    let zone = item[mat.end()..].trim();
+                    if zone.is_empty() {
+                        return Ok(Action::Watch(None));
+                    }
+                    return Ok(Action::Watch(Some(zone.to_string())));
+                },
+                "robot" => {
+                    // Robot mode can be switched on, off or toggled
+                    match item[mat.end()..].trim().to_lowercase().as_str() {
+                        "" => return Ok(Action::Robot(None)),
+                        "on" => return Ok(Action::Robot(Some(true))),
+                        "off" => return Ok(Action::Robot(Some(false))),
+                        _ => return Err(Error::VerbEncodingError),
                     }
                 },
-                "read" => return Ok(Action::Read),
-                "enter" => return Ok(Action::Enter),
-                "connect" => return Ok(Action::Connect),
-                "Access" => return Ok(Action::Access),
-                "Open" => return Ok(Action::Open),
                 _ => {},
             }
         };
 
-        Err(Error::VerbUnknownError)
+        // Verbs registered at runtime come last, they never shadow built-in verbs
+        verbs::parse(command, &item[mat.end()..]).unwrap_or(Err(Error::VerbUnknownError))
     }
 }
 
@@ -177,6 +616,64 @@ impl TryFrom<Vec<u8>> for Action {
     }
 }
 
+/// Words dropped from references to objects, eg. "enter the port please"
+const FILLERS: [&str; 6] = ["the", "a", "an", "please", "at", "to"];
+
+/// Drop the articles and filler words (see `FILLERS`) and a final dot from
+/// a reference to an object, separating the remaining words by one blank
+fn strip_fillers(arguments: &str) -> String {
+    arguments.trim().trim_end_matches('.')
+        .split_whitespace()
+        .filter(|w| !FILLERS.iter().any(|f| w.eq_ignore_ascii_case(f)))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Parse a reference to an object (eg. "the port 2" or "2.port") into
+/// its noun and ordinal
+fn parse_object(arguments: &str) -> Result<(String, Option<usize>), Error> {
+    lazy_static! {
+        static ref OBJECT_RE: Regex = Regex::new(r"^(?:(\d+)\.)?(\p{L}+)(?:\s+(\d+))?$").unwrap();
+    }
+    let arguments = strip_fillers(arguments);
+    let caps = match OBJECT_RE.captures(&arguments) {
+        Some(c) => c,
+        None => return Err(Error::VerbEncodingError),
+    };
+    Ok((caps[2].to_lowercase(), parse_ordinal(caps.get(1), caps.get(3))?))
+}
+
+/// Parse credits (eg. "50" or "50 credits") or the name of an item moved
+/// to or from the bank
+fn parse_funds(arguments: &str) -> Result<Funds, Error> {
+    lazy_static! {
+        static ref CREDITS_RE: Regex = Regex::new(r"^(\d+)(?:\s+credits?)?$").unwrap();
+    }
+    let arguments = arguments.trim().to_lowercase();
+    if let Some(caps) = CREDITS_RE.captures(&arguments) {
+        return match caps[1].parse::<u64>() {
+            Ok(credits) if credits > 0 => Ok(Funds::Credits(credits)),
+            _ => Err(Error::VerbEncodingError),
+        };
+    }
+    if arguments.is_empty() {
+        return Err(Error::VerbEncodingError);
+    }
+    Ok(Funds::Item(arguments))
+}
+
+/// Parse the ordinal of an object given either before ("2.port") or after
+/// ("port 2") the noun, but not both
+fn parse_ordinal(before: Option<regex::Match<'_>>, after: Option<regex::Match<'_>>) -> Result<Option<usize>, Error> {
+    match (before, after) {
+        (Some(_), Some(_)) => Err(Error::VerbEncodingError),
+        (Some(m), None) | (None, Some(m)) => {
+            Ok(Some(m.as_str().parse::<usize>().map_err(|_| Error::VerbEncodingError)?))
+        },
+        (None, None) => Ok(None),
+    }
+}
+
 /// Helper function to give a list of synonymous words. Returns a vector only
 /// containing the looked up word itself if no synonyms are available (every
 /// word is synonymous to istself) and a vector of more sysnonyms otherwise also
@@ -185,7 +682,5 @@ impl TryFrom<Vec<u8>> for Action {
 /// TODO:
 /// - [ ] Implement it - currently just returns the word itself.
 fn synonyms(word: &str) -> Vec<&str> {
-    let mut synonyms = Vec::new();
-    synonyms.push(word);
-    synonyms
+    vec![word]
 }
\ No newline at end of file
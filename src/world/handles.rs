@@ -0,0 +1,160 @@
+//! Handles
+//!
+//! Rules for the names players are known by in the world. The handle of a
+//! player is the user name it connects with and it is validated when the
+//! player registers. Players can add a short title that is shown after their
+//! handle (eg. "neo the One") and describe their avatar (see `appearance`).
+
+use std::collections::HashMap;
+use tracing::error;
+
+use crate::connection_manager::ClientId;
+use crate::world::accounts::Account;
+use crate::world::errors::Error;
+use crate::world::messages::{Event, Message};
+use crate::world::{GameWorld, Player};
+
+/// Rules handles and titles must follow
+#[derive(Debug, Clone)]
+pub struct HandleRules {
+    /// Minimum number of characters of a handle
+    pub min_length: usize,
+    /// Maximum number of characters of a handle
+    pub max_length: usize,
+    /// Maximum number of characters of a title
+    pub max_title_length: usize,
+    /// Maximum number of characters of the description of an avatar
+    pub max_description_length: usize,
+    /// Handles that may not be used by players (eg. "admin")
+    pub reserved: Vec<String>,
+    /// Words that may not appear in handles or titles
+    pub profanity: Vec<String>,
+}
+
+impl Default for HandleRules {
+    fn default() -> HandleRules {
+        HandleRules {
+            min_length: 3,
+            max_length: 16,
+            max_title_length: 24,
+            max_description_length: 240,
+            reserved: Vec::new(),
+            profanity: Vec::new(),
+        }
+    }
+}
+
+impl HandleRules {
+    /// Validate a handle
+    ///
+    /// A handle must start with a letter and may only contain ASCII letters,
+    /// digits, dashes and underscores.
+    pub fn validate_handle(&self, handle: &str) -> Result<(), Error> {
+        let length = handle.chars().count();
+        if length < self.min_length || length > self.max_length {
+            return Err(Error::InvalidLength);
+        }
+        if !handle.starts_with(|c: char| c.is_ascii_alphabetic())
+            || !handle.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+            return Err(Error::InvalidCharacters);
+        }
+        if self.reserved.iter().any(|r| r.eq_ignore_ascii_case(handle)) {
+            return Err(Error::ReservedName);
+        }
+        if self.is_profane(handle) {
+            return Err(Error::ProfaneContent);
+        }
+        Ok(())
+    }
+
+    /// Validate a title
+    ///
+    /// A title may contain any printable characters.
+    pub fn validate_title(&self, title: &str) -> Result<(), Error> {
+        if title.chars().count() > self.max_title_length {
+            return Err(Error::InvalidLength);
+        }
+        if title.chars().any(|c| c.is_control()) {
+            return Err(Error::InvalidCharacters);
+        }
+        if self.is_profane(title) {
+            return Err(Error::ProfaneContent);
+        }
+        Ok(())
+    }
+
+    /// Validate the description of an avatar
+    ///
+    /// Like a title, a description may contain any printable characters.
+    pub fn validate_description(&self, description: &str) -> Result<(), Error> {
+        if description.is_empty() || description.chars().count() > self.max_description_length {
+            return Err(Error::InvalidLength);
+        }
+        if description.chars().any(|c| c.is_control()) {
+            return Err(Error::InvalidCharacters);
+        }
+        if self.is_profane(description) {
+            return Err(Error::ProfaneContent);
+        }
+        Ok(())
+    }
+
+    /// Check if the text contains any of the profane words (ignoring case)
+    fn is_profane(&self, text: &str) -> bool {
+        let text = text.to_lowercase();
+        self.profanity.iter().any(|w| text.contains(&w.to_lowercase()))
+    }
+}
+
+/// Set or clear the title of a player
+pub(super) async fn title(title: Option<String>, world: &GameWorld, player: &mut Player) {
+    let result = match &title {
+        Some(t) => world.handle_rules.validate_title(t),
+        None => Ok(()),
+    };
+    match result {
+        Ok(_) => {
+            player.account.title = title;
+            if let Err(e) = player.account.save(&world.data_dir).await {
+                error!("Could not save account of {}: {}", player.player_name, e);
+            }
+            let message = format!("You are now known as {}.", player.display_name());
+            player.send(Message::new(Event::Info, &message)
+                .with_field("title", player.account.title.clone())).await;
+        },
+        Err(e) => {
+            let message = format!("The matrix does not accept this title ({}). \
+                Titles are at most {} characters.", e, world.handle_rules.max_title_length);
+            player.send(Message::new(Event::Error, &message)).await;
+        },
+    }
+}
+
+/// Set or clear the title of a player on behalf of an admin, connected or
+/// not, and describe the outcome
+pub(super) async fn grant(player: &str, title: Option<String>, world: &GameWorld,
+                          players: &mut HashMap<ClientId, Player>) -> String {
+    // The admin override skips the validation of the title
+    match players.values_mut().find(|p| p.player_name == player) {
+        Some(p) => {
+            p.account.title = title;
+            match p.account.save(&world.data_dir).await {
+                Ok(_) => format!("{} is now known as {}.", player, p.display_name()),
+                Err(e) => format!("Could not save the account of {}: {}", player, e),
+            }
+        },
+        None => {
+            // The player is not connected, update the stored account
+            match Account::load(&world.data_dir, player).await {
+                Ok(mut account) => {
+                    account.title = title;
+                    match account.save(&world.data_dir).await {
+                        Ok(_) => format!("Updated the title of {} (not connected).", player),
+                        Err(e) => format!("Could not save the account of {}: {}", player, e),
+                    }
+                },
+                Err(e) => format!("Could not load the account of {}: {}", player, e),
+            }
+        },
+    }
+}
@@ -0,0 +1,112 @@
+//! Help
+//!
+//! "help" lists the topics players can read about, "help <topic>" shows a
+//! topic (eg. "help combat"). The topics are text files in the help
+//! directory (`help_dir` in the `[world]` section of the settings), loaded
+//! at startup and known by the name of their file (eg. "combat" for
+//! "combat.txt"), so the staff writes them without recompiling.
+//!
+//! Long topics are shown in pages of `PAGE_LINES` lines, "help <topic> 2"
+//! shows the second page. Every page ends with where the player is in the
+//! topic, so clients without a scrollback still get through it.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use tracing::{debug, error, info};
+
+use super::fuzzy;
+use super::loader;
+
+/// Number of lines of a page
+pub const PAGE_LINES: usize = 20;
+
+/// The help topics
+#[derive(Debug, Default)]
+pub struct Help {
+    /// The lines of the topics, by name
+    topics: BTreeMap<String, Vec<String>>,
+}
+
+impl Help {
+    /// Load the topics from the text files (.txt) of a directory
+    pub async fn load(dir: &Path) -> Help {
+        let mut help = Help::default();
+        let mut entries = match tokio::fs::read_dir(dir).await {
+            Ok(e) => e,
+            Err(e) => {
+                error!("Couldn't read help directory {}: {}", dir.display(), e);
+                return help;
+            },
+        };
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let path = entry.path();
+            if path.extension().is_none_or(|e| e != "txt") {
+                continue;
+            }
+            let name = match path.file_stem() {
+                Some(n) => n.to_string_lossy().to_lowercase(),
+                None => continue,
+            };
+            match loader::load(&path).await {
+                Ok(buf) => {
+                    debug!("Loaded help topic {}.", name);
+                    help.add_topic(&name, &String::from_utf8_lossy(&buf));
+                },
+                Err(e) => error!("Couldn't load help topic {}: {}", path.display(), e),
+            }
+        }
+        info!("Loaded {} help topics.", help.topics.len());
+        help
+    }
+
+    /// Add a topic, replacing the topic of the same name
+    pub fn add_topic(&mut self, name: &str, text: &str) {
+        let lines = text.trim_end().lines().map(|l| l.trim_end().to_string()).collect();
+        self.topics.insert(name.to_lowercase(), lines);
+    }
+
+    /// The list of the topics
+    pub fn index(&self) -> String {
+        if self.topics.is_empty() {
+            return "There are no help topics. Ask the staff.".to_string();
+        }
+        let names: Vec<&str> = self.topics.keys().map(String::as_str).collect();
+        format!("Help topics: {}.\r\nType \"help <topic>\" to read one (eg. \"help {}\").", names.join(", "),
+            names[0])
+    }
+
+    /// A page of a topic (counting from one)
+    ///
+    /// A mistyped topic is resolved to the closest name (see `fuzzy`).
+    /// Returns the page, or why there is no such page.
+    pub fn page(&self, topic: &str, page: usize) -> Result<String, String> {
+        let topic = topic.to_lowercase();
+        let name = match self.topics.get_key_value(&topic) {
+            Some((name, _)) => name.as_str(),
+            None => match fuzzy::resolve(&topic, self.topics.keys().map(String::as_str)) {
+                Ok(Some(name)) => name,
+                Ok(None) => return Err(format!("There is no help on {}. Type \"help\" for the topics.", topic)),
+                Err(names) => {
+                    return Err(format!("There is no help on {}. Did you mean {}?", topic, names.join(" or ")))
+                },
+            },
+        };
+        let lines = &self.topics[name];
+        let pages = lines.len().div_ceil(PAGE_LINES).max(1);
+        if page == 0 || page > pages {
+            return Err(format!("The help on {} has {} pages.", name, pages));
+        }
+        let mut text = lines.iter().skip((page - 1) * PAGE_LINES).take(PAGE_LINES).cloned()
+            .collect::<Vec<String>>()
+            .join("\r\n");
+        if pages > 1 {
+            text += &format!("\r\n-- Page {} of {}", page, pages);
+            if page < pages {
+                text += &format!(", \"help {} {}\" shows the next", name, page + 1);
+            }
+            text += " --";
+        }
+        Ok(text)
+    }
+}
@@ -0,0 +1,34 @@
+//! Hooks
+//!
+//! Hooks change the state of a player when something happens in the world,
+//! eg. when the player reads a file on a terminal or solves a puzzle. They
+//! are how the world notes discoveries in the journal of the player and
+//! feeds quests and achievements. A hook may also change the world later,
+//! by starting a timer (see `timers`).
+
+use std::time::Duration;
+
+use super::timers::Effect;
+
+/// A change of the state of a player
+#[derive(Debug, Clone, PartialEq)]
+pub enum Hook {
+    /// Add a note to the journal of the player (eg. a port code)
+    Note(String),
+    /// Set a quest or achievement flag in the account of the player
+    Flag(String),
+    /// Give the player a drone of the model, unless it has one (see
+    /// `drones`)
+    Drone(String),
+    /// Pay the player credits
+    Credits(u64),
+    /// Give the player an item
+    Item(String),
+    /// Change the reputation of the player with a faction (see `economy`)
+    Reputation { faction: String, amount: i64 },
+    /// Give the player a title (see `streaks`)
+    Title(String),
+    /// Apply an effect to the world after the given time (eg. close the port
+    /// behind the player)
+    Timer { after: Duration, effect: Effect },
+}
@@ -0,0 +1,395 @@
+//! ICE
+//!
+//! Intrusion countermeasures guard closed ports. An `Ice` asset in a node
+//! protects one of its ports: while the ICE stands, nobody passes the port,
+//! not even the players that solved its puzzle. Deckers break the ICE in
+//! turns, every turn an exchange:
+//!  * the decker runs a program at the ICE, "attack ice" for a brute force
+//!    hit or "crack ice" for a slower, quieter one the ICE traces at half the
+//!    rate
+//!  * the ICE takes the damage and traces the decker back, raising the trace
+//!    level of the decker by its trace rate
+//!
+//! Once the integrity of the ICE runs out, it is broken: the port it
+//! protects lets everybody pass (still asking for its puzzle, if any) until
+//! the ICE reboots after `REBOOT`. Once the trace level of a decker reaches
+//! `TRACE_MAX`, the ICE purges the intrusion: it restores its integrity and
+//! jams the deck of the decker for `TRACE_JAM`.
+//!
+//! The ICE is built with the world, the intrusions are kept in memory only.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::time::Instant;
+use tracing::{debug, info};
+
+use crate::connection_manager::ClientId;
+use super::actions::Action;
+use super::assets::{Actor, AssetID, AssetResponse, GameAsset, Port};
+use super::intern::intern;
+use super::messages::{Event, Message};
+use super::properties::Property;
+use super::puzzles::Puzzle;
+use super::terminals::Terminal;
+use super::{goals, security, send_to_node, GameWorld, Player};
+
+/// Integrity of ICE unless built with another
+pub const DEFAULT_STRENGTH: u32 = 30;
+
+/// Trace rate of ICE unless built with another, in percent per exchange
+pub const DEFAULT_TRACE: u32 = 20;
+
+/// Damage of an attack
+pub const ATTACK_DAMAGE: u32 = 10;
+
+/// Damage of a crack
+pub const CRACK_DAMAGE: u32 = 5;
+
+/// Trace level at which the ICE purges the intrusion of a decker, in
+/// percent
+pub const TRACE_MAX: u32 = 100;
+
+/// Time the deck of a decker traced is jammed for
+pub const TRACE_JAM: Duration = Duration::from_secs(10);
+
+/// Time broken ICE takes to reboot
+pub const REBOOT: Duration = Duration::from_secs(5 * 60);
+
+/// ICE guarding a port
+#[derive(Debug, Clone)]
+pub struct Ice {
+    id: AssetID,
+    description: Arc<str>,
+    /// The uid of the port the ICE protects
+    protects: AssetID,
+    strength: u32,
+    trace: u32,
+}
+
+impl Ice {
+    /// Create ICE protecting a port of its node
+    pub fn new(id: AssetID, protects: AssetID) -> Ice {
+        Ice {
+            id,
+            description: intern(""),
+            protects,
+            strength: DEFAULT_STRENGTH,
+            trace: DEFAULT_TRACE,
+        }
+    }
+
+    /// Describe the ICE
+    pub fn update_description(&mut self, description: &str) {
+        self.description = intern(description);
+    }
+
+    /// Set the integrity of the ICE
+    pub fn update_strength(&mut self, strength: u32) {
+        self.strength = strength.max(1);
+    }
+
+    /// Set the trace level the ICE raises per exchange, in percent
+    pub fn update_trace(&mut self, trace: u32) {
+        self.trace = trace;
+    }
+
+    /// The uid of the port the ICE protects
+    pub fn protects(&self) -> AssetID {
+        self.protects
+    }
+}
+
+impl GameAsset for Ice {
+    /// Return the uid of the ICE
+    fn uid(&self) -> AssetID {
+        self.id
+    }
+
+    /// ICE is referenced as "ice"
+    fn name(&self) -> &str {
+        "ice"
+    }
+
+    /// ICE has no properties yet
+    fn properties(&self) -> Option<&Vec<Property>> {
+        None
+    }
+
+    /// Describe the ICE
+    fn describe(&self) -> String {
+        format!("{} It guards a port. Break it with \"attack ice\" or \"crack ice\".", self.description)
+    }
+
+    /// Returns the description of the ICE
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    /// Replace the description of the ICE
+    fn rewrite(&mut self, description: &str) {
+        self.update_description(description);
+    }
+
+    /// React to
+    ///
+    /// Breaking the ICE is handled by the world, as it changes the intrusions
+    /// and the player.
+    fn react_to(&mut self, _actor: &Actor<'_>, a: &Action) -> AssetResponse {
+        match a {
+            Action::Look { .. } => self.describe().into(),
+            _ => AssetResponse::default(),
+        }
+    }
+
+    /// ICE makes no sound
+    fn sound(&self) -> Option<&str> {
+        None
+    }
+
+    /// ICE is not a terminal
+    fn terminal(&self) -> Option<&Terminal> {
+        None
+    }
+
+    /// ICE is not a port
+    fn port(&self) -> Option<&Port> {
+        None
+    }
+
+    /// ICE is not a port
+    fn port_mut(&mut self) -> Option<&mut Port> {
+        None
+    }
+
+    /// ICE carries no puzzles
+    fn puzzle(&self) -> Option<&Puzzle> {
+        None
+    }
+
+    /// ICE does not lead anywhere
+    fn portal(&self) -> Option<&str> {
+        None
+    }
+
+    /// ICE cannot be carried
+    fn is_takeable(&self) -> bool {
+        false
+    }
+
+    /// ICE is ICE
+    fn ice(&self) -> Option<&Ice> {
+        Some(self)
+    }
+
+    /// ICE is attacked and cracked
+    fn verbs(&self) -> Vec<&'static str> {
+        vec!["look", "attack", "crack"]
+    }
+
+    /// Returns a copy of the ICE
+    fn clone_box(&self) -> Box<dyn GameAsset> {
+        Box::new(self.clone())
+    }
+
+    /// Gives the ICE another uid
+    fn renumber(&mut self, uid: AssetID) {
+        self.id = uid;
+    }
+}
+
+/// The program a decker runs at ICE
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Move {
+    /// A brute force hit
+    Attack,
+    /// A slower, quieter hit, traced at half the rate
+    Crack,
+}
+
+impl Move {
+    /// The damage the move deals
+    pub fn damage(self) -> u32 {
+        match self {
+            Move::Attack => ATTACK_DAMAGE,
+            Move::Crack => CRACK_DAMAGE,
+        }
+    }
+
+    /// The trace level the ICE of the trace rate raises in answer
+    fn trace(self, rate: u32) -> u32 {
+        match self {
+            Move::Attack => rate,
+            Move::Crack => rate.div_ceil(2),
+        }
+    }
+}
+
+/// The outcome of an exchange with ICE
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Exchange {
+    /// The ICE held and traced the decker back
+    Held { damage: u32, integrity: u32, max: u32, trace: u32 },
+    /// The ICE broke, the port it protects lets everybody pass until it
+    /// reboots
+    Broken { damage: u32 },
+    /// The trace of the decker completed, the ICE purged the intrusion and
+    /// jammed the deck of the decker
+    Traced { damage: u32, jam: Duration },
+    /// The ICE is broken already
+    Down,
+}
+
+/// An intrusion into ICE
+#[derive(Debug)]
+struct Intrusion {
+    integrity: u32,
+    /// The trace level of every decker, in percent
+    traces: HashMap<String, u32>,
+    /// When the ICE reboots, if it is broken
+    reboots: Option<Instant>,
+}
+
+/// The intrusions into the ICE of the world
+#[derive(Debug, Default)]
+pub struct Intrusions {
+    intrusions: HashMap<AssetID, Intrusion>,
+}
+
+impl Intrusions {
+    /// Resolve an exchange of a decker with ICE
+    pub fn exchange(&mut self, ice: &Ice, player: &str, mv: Move, now: Instant) -> Exchange {
+        let intrusion = self.intrusions.entry(ice.id)
+            .or_insert_with(|| Intrusion { integrity: ice.strength, traces: HashMap::new(), reboots: None });
+        if intrusion.reboots.is_some() {
+            return Exchange::Down;
+        }
+        let damage = mv.damage();
+        intrusion.integrity = intrusion.integrity.saturating_sub(damage);
+        if intrusion.integrity == 0 {
+            intrusion.traces.clear();
+            intrusion.reboots = Some(now + REBOOT);
+            return Exchange::Broken { damage };
+        }
+        let trace = intrusion.traces.entry(player.to_string()).or_default();
+        *trace = trace.saturating_add(mv.trace(ice.trace)).min(TRACE_MAX);
+        if *trace < TRACE_MAX {
+            return Exchange::Held { damage, integrity: intrusion.integrity, max: ice.strength, trace: *trace };
+        }
+        intrusion.traces.remove(player);
+        intrusion.integrity = ice.strength;
+        Exchange::Traced { damage, jam: TRACE_JAM }
+    }
+
+    /// True if the ICE is broken
+    pub fn is_broken(&self, ice: AssetID) -> bool {
+        self.intrusions.get(&ice).is_some_and(|i| i.reboots.is_some())
+    }
+
+    /// Reboot the broken ICE due at the given time, forgetting the
+    /// intrusions
+    ///
+    /// Returns the uids of the ICE rebooted.
+    pub fn reboot(&mut self, now: Instant) -> Vec<AssetID> {
+        let due: Vec<AssetID> = self.intrusions.iter()
+            .filter(|(_, i)| i.reboots.is_some_and(|t| t <= now))
+            .map(|(uid, _)| *uid)
+            .collect();
+        for uid in &due {
+            self.intrusions.remove(uid);
+        }
+        due
+    }
+}
+
+/// Run a program at the ICE guarding a port
+/// 
+/// Every attack or crack is an exchange: the ICE takes the damage and traces
+/// the player back. Breaking the ICE is a crime in secured zones, the
+/// players in the node see the exchange.
+pub(super) async fn intrude(a: &Action, world: &mut GameWorld, client_id: ClientId,
+                            players: &mut HashMap<ClientId, Player>, now: Instant) {
+    let (target, ordinal, mv) = match a {
+        Action::Attack { target, ordinal } => (target, *ordinal, Move::Attack),
+        Action::Crack { target, ordinal } => (target, *ordinal, Move::Crack),
+        _ => return,
+    };
+    let player = match players.get_mut(&client_id) {
+        Some(p) => p,
+        None => return,
+    };
+    let (location, node) = match player.location.and_then(|l| world.nodes.get(l).map(|n| (l, n))) {
+        Some(found) => found,
+        None => return,
+    };
+    let ice = match node.find_asset(target, ordinal) {
+        Ok(asset) => match asset.ice() {
+            Some(ice) => ice.clone(),
+            None => return,
+        },
+        Err(reason) => {
+            player.send(Message::new(Event::Error, &reason)).await;
+            return;
+        },
+    };
+    player.touched.push(ice.protects());
+    let (verb, name) = (a.verb(), player.display_name());
+    let mut broken = false;
+    let others = match world.intrusions.exchange(&ice, &player.player_name, mv, now) {
+        Exchange::Held { damage, integrity, max, trace } => {
+            let text = format!("You {} the ICE for {} damage ({}/{} integrity left). It traces you back, you are \
+                {}% traced.", verb, damage, integrity, max, trace);
+            player.send(Message::new(Event::Response, &text)
+                .with_field("ice", ice.uid())
+                .with_field("integrity", integrity)
+                .with_field("trace", trace)).await;
+            format!("{} {}s the ICE ({}/{} integrity left).", name, verb, integrity, max)
+        },
+        Exchange::Broken { damage } => {
+            player.crimes.push(security::Crime::Cracking);
+            broken = true;
+            info!("Player {} broke ICE #{}.", player.player_name, ice.uid());
+            let text = format!("You {} the ICE for {} damage. It shatters, the port it guards lies open until it \
+                reboots.", verb, damage);
+            player.send(Message::new(Event::Response, &text)
+                .with_field("ice", ice.uid())
+                .with_field("integrity", 0)).await;
+            format!("{} breaks the ICE. The port it guards lies open.", name)
+        },
+        Exchange::Traced { damage, jam } => {
+            player.busy_until = Some(player.busy_until.map_or(now, |t| t.max(now)) + jam);
+            info!("ICE #{} traced player {}.", ice.uid(), player.player_name);
+            let text = format!("You {} the ICE for {} damage, but its trace completes. It purges your intrusion \
+                and jams your deck for {}s.", verb, damage, jam.as_secs());
+            player.send(Message::new(Event::Response, &text)
+                .with_field("ice", ice.uid())
+                .with_field("trace", TRACE_MAX)).await;
+            format!("The ICE traces {} and purges the intrusion.", name)
+        },
+        Exchange::Down => {
+            player.send(Message::new(Event::Error, "The ICE is broken already. The port it guards lies open."))
+                .await;
+            return;
+        },
+    };
+    let message = Message::new(Event::Info, &others);
+    for (_, p) in players.iter().filter(|(id, p)| **id != client_id && p.location == Some(location)) {
+        p.send(message.clone()).await;
+    }
+    if broken {
+        goals::accomplish(&goals::Deed::Break(ice.uid()), world, players).await;
+    }
+}
+
+/// Reboot the broken ICE due
+pub(super) async fn process(world: &mut GameWorld, players: &HashMap<ClientId, Player>, now: Instant) {
+    for uid in world.intrusions.reboot(now) {
+        let node = world.nodes.iter().find(|(_, n)| n.assets().any(|a| a.uid() == uid)).map(|(i, _)| i);
+        debug!("ICE #{} rebooted.", uid);
+        if let Some(index) = node {
+            send_to_node(players, index, Message::new(Event::Info, "The ICE reboots and guards its port again."))
+                .await;
+        }
+    }
+}
@@ -0,0 +1,106 @@
+//! Idle progression
+//!
+//! Attendees often leave their badge connected while they walk the con. A
+//! connected player that sent nothing for a while is idle, and its deck keeps
+//! working in the background. For every cycle of idle time:
+//!  * the deck recompiles programs, restoring the integrity of the drone of
+//!    the player (if it has one)
+//!  * the scanners gather intel, sold for credits (booked as "idle" in the
+//!    ledger, see `economy`)
+//!
+//! The cycles are capped per session, so idling all day earns less than
+//! playing. When the player returns (sends its next input), it is told what
+//! accrued while it was away.
+
+use std::fmt;
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+use super::accounts::Account;
+use super::drones::DRONE_INTEGRITY;
+
+/// Time without input after which a player is idle
+pub const IDLE_AFTER: Duration = Duration::from_secs(5 * 60);
+
+/// Idle time of a cycle of progression
+pub const CYCLE: Duration = Duration::from_secs(10 * 60);
+
+/// Most cycles per session
+pub const MAX_CYCLES: u32 = 12;
+
+/// Credits the intel of a cycle sells for
+pub const INTEL_CREDITS: u64 = 5;
+
+/// Integrity a cycle restores of the drone of a player
+pub const RECOMPILE_REPAIR: u32 = 5;
+
+/// What accrued while a player was away
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Report {
+    /// Cycles completed
+    pub cycles: u32,
+    /// Credits earned with intel
+    pub credits: u64,
+    /// Integrity restored of the drone
+    pub repaired: u32,
+}
+
+/// The idle time of a player in a session
+#[derive(Debug, Clone)]
+pub struct Idle {
+    /// When the player last sent input
+    last_input: Instant,
+    /// Cycles completed in the session
+    cycles: u32,
+    /// What accrued since the last input
+    away: Report,
+}
+
+impl Idle {
+    /// Start counting the idle time of a session
+    pub fn new(now: Instant) -> Idle {
+        Idle { last_input: now, cycles: 0, away: Report::default() }
+    }
+
+    /// The player sent input
+    ///
+    /// Returns what accrued while the player was away, if anything.
+    pub fn input(&mut self, now: Instant) -> Option<Report> {
+        self.last_input = now;
+        let away = std::mem::take(&mut self.away);
+        (away.cycles > 0).then_some(away)
+    }
+
+    /// Complete the next cycle if it is due, progressing the account
+    ///
+    /// Returns true if a cycle was completed.
+    pub fn tick(&mut self, account: &mut Account, now: Instant) -> bool {
+        let due = self.last_input + IDLE_AFTER + CYCLE * (self.away.cycles + 1);
+        if self.cycles >= MAX_CYCLES || now < due {
+            return false;
+        }
+        self.cycles += 1;
+        self.away.cycles += 1;
+        account.credits += INTEL_CREDITS;
+        self.away.credits += INTEL_CREDITS;
+        if let Some(drone) = account.drone.as_mut() {
+            let repaired = RECOMPILE_REPAIR.min(DRONE_INTEGRITY.saturating_sub(drone.integrity));
+            drone.integrity += repaired;
+            self.away.repaired += repaired;
+        }
+        true
+    }
+}
+
+/// Display a report as told to the returning player
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let minutes = (IDLE_AFTER + CYCLE * self.cycles).as_secs() / 60;
+        write!(f, "While you were away for {}m, your scanners gathered intel worth {} credits", minutes, self.credits)?;
+        if self.repaired > 0 {
+            write!(f, " and your deck recompiled your drone (+{} integrity)", self.repaired)?;
+        }
+        write!(f, ".")
+    }
+}
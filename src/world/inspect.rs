@@ -0,0 +1,146 @@
+//! Inspection
+//!
+//! Lets the staff building the world search and inspect its assets. Assets
+//! are dumped as their fields, one "key: value" per line, eg.
+//! ```ignore
+//!     port #2 in Lobby (node #0)
+//!       description: A port that has a slight purple shimmering edge.
+//!       color: purple
+//!       puzzle: lobby/purple-port
+//! ```
+//! Searches filter on the same fields ("color=purple" matches the port
+//! above), so what a dump shows is what builders can search for.
+
+use generational_arena::Arena;
+use std::collections::HashMap;
+
+use crate::connection_manager::ClientId;
+use super::admin::Target;
+use super::assets::{AssetID, GameAsset, Node};
+use super::{GameWorld, Player};
+
+/// The fields of an asset, in the order they are dumped
+pub fn fields(asset: &dyn GameAsset) -> Vec<(String, String)> {
+    let mut fields = Vec::new();
+    if let Some(terminal) = asset.terminal() {
+        fields.push(("hostname".to_string(), terminal.hostname().to_string()));
+    }
+    fields.push(("description".to_string(), asset.describe()));
+    for property in asset.properties().into_iter().flatten() {
+        fields.push((property.key().to_string(), property.value()));
+    }
+    if let Some(sound) = asset.sound() {
+        fields.push(("sound".to_string(), sound.to_string()));
+    }
+    if let Some(puzzle) = asset.puzzle() {
+        fields.push(("puzzle".to_string(), puzzle.id().to_string()));
+    }
+    if let Some(portal) = asset.portal() {
+        fields.push(("portal".to_string(), portal.to_string()));
+    }
+    fields
+}
+
+/// True if the asset has a field for every filter whose value contains the
+/// value of the filter (ignoring case)
+pub fn matches(asset: &dyn GameAsset, filters: &[(String, String)]) -> bool {
+    let fields = fields(asset);
+    filters.iter().all(|(key, value)| {
+        let value = value.to_lowercase();
+        fields.iter().any(|(k, v)| k == key && v.to_lowercase().contains(&value))
+    })
+}
+
+/// Find the assets referenced by a noun ("node", "asset" for any asset in a
+/// node or the name of assets, eg. "port") that match the filters
+///
+/// Returns one line per asset found.
+pub fn find(nodes: &Arena<Node>, noun: &str, filters: &[(String, String)]) -> Vec<String> {
+    let mut found = Vec::new();
+    for (_, node) in nodes.iter() {
+        if noun == "node" {
+            if matches(node, filters) {
+                found.push(heading(node, None));
+            }
+            continue;
+        }
+        for asset in node.assets() {
+            if (noun == "asset" || asset.name() == noun) && matches(asset, filters) {
+                found.push(heading(asset, Some(node)));
+            }
+        }
+    }
+    found
+}
+
+/// Find a node by its uid
+pub fn node(nodes: &Arena<Node>, uid: AssetID) -> Option<&Node> {
+    nodes.iter().map(|(_, n)| n).find(|n| n.uid() == uid)
+}
+
+/// Find an asset in a node by its uid, and the node it is in
+pub fn locate(nodes: &Arena<Node>, uid: AssetID) -> Option<(&Node, &dyn GameAsset)> {
+    nodes.iter()
+        .flat_map(|(_, n)| n.assets().map(move |a| (n, a)))
+        .find(|(_, a)| a.uid() == uid)
+}
+
+/// Dump an asset (and the node it is in, None for nodes)
+pub fn dump(asset: &dyn GameAsset, node: Option<&Node>) -> String {
+    let mut lines = vec![heading(asset, node)];
+    lines.extend(fields(asset).into_iter().map(|(k, v)| format!("  {}: {}", k, v)));
+    lines.join("\r\n")
+}
+
+/// Dump a node and list its assets
+pub fn dump_node(node: &Node) -> String {
+    let mut dump = dump(node, None);
+    if let Some(acl) = node.acl() {
+        dump += &format!("\r\n  acl: {}", acl);
+    }
+    for asset in node.assets() {
+        dump += &format!("\r\n  asset: {} #{}", asset.name(), asset.uid());
+    }
+    dump
+}
+
+/// The line naming an asset, eg. "port #2 in Lobby (node #0)"
+pub fn heading(asset: &dyn GameAsset, node: Option<&Node>) -> String {
+    match node {
+        Some(node) => format!("{} #{} in {} (node #{})", asset.name(), asset.uid(), node_name(node), node.uid()),
+        None => format!("node #{} {}", asset.uid(), node_name(asset)),
+    }
+}
+
+/// The name of a node, or "(unnamed)"
+fn node_name(node: &dyn GameAsset) -> &str {
+    if node.name().is_empty() { "(unnamed)" } else { node.name() }
+}
+
+/// Dump a node (with the players in it) or an asset for the staff
+pub(super) fn show(target: Target, world: &GameWorld, players: &HashMap<ClientId, Player>) -> String {
+    match target {
+        Target::Node(uid) => {
+            match world.nodes.iter().find(|(_, n)| n.uid() == uid) {
+                Some((index, node)) => {
+                    let mut dump = dump_node(node);
+                    if world.spawn_nodes.contains(&index) {
+                        dump += "\r\n  spawn: yes";
+                    }
+                    for p in players.values().filter(|p| p.location == Some(index)) {
+                        dump += &format!("\r\n  player: {}", p.player_name);
+                    }
+                    dump
+                },
+                None => format!("There is no node #{}.", uid),
+            }
+        },
+        Target::Asset(uid) => match locate(&world.nodes, uid) {
+            Some((node, asset)) => dump(asset, Some(node)),
+            None => match node(&world.nodes, uid) {
+                Some(node) => dump_node(node),
+                None => format!("There is no asset #{}.", uid),
+            },
+        },
+    }
+}
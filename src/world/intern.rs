@@ -0,0 +1,56 @@
+//! String interning
+//!
+//! Names and descriptions of assets are sent to players over and over again
+//! and many assets share them (eg. every port is called "port"). Interned
+//! strings are stored once and shared by reference counting, so responses can
+//! use them without copying.
+//!
+//! Strings nobody holds anymore (eg. descriptions rewritten by builders) are
+//! evicted whenever the number of interned strings doubled, so the interned
+//! strings do not grow with every rewrite.
+
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use lazy_static::lazy_static;
+
+/// Least number of interned strings before strings are evicted
+const MIN_EVICTION: usize = 1_024;
+
+/// The interned strings
+#[derive(Debug)]
+struct Strings {
+    strings: HashSet<Arc<str>>,
+    /// Number of strings at which strings nobody holds are evicted
+    evict_at: usize,
+}
+
+lazy_static! {
+    static ref STRINGS: Mutex<Strings> = Mutex::new(Strings { strings: HashSet::new(), evict_at: MIN_EVICTION });
+}
+
+/// Intern a string
+///
+/// Returns the shared copy of the string, which is created if the string was
+/// not interned before.
+pub fn intern(s: &str) -> Arc<str> {
+    let mut strings = match STRINGS.lock() {
+        Ok(s) => s,
+        Err(_) => return Arc::from(s),
+    };
+    if let Some(interned) = strings.strings.get(s) {
+        return interned.clone();
+    }
+    if strings.strings.len() >= strings.evict_at {
+        // Only the interned copy is left of strings nobody holds
+        strings.strings.retain(|s| Arc::strong_count(s) > 1);
+        strings.evict_at = (strings.strings.len() * 2).max(MIN_EVICTION);
+    }
+    let interned: Arc<str> = Arc::from(s);
+    strings.strings.insert(interned.clone());
+    interned
+}
+
+/// Number of strings interned
+pub fn count() -> usize {
+    STRINGS.lock().map_or(0, |s| s.strings.len())
+}
@@ -0,0 +1,250 @@
+//! Inventory
+//!
+//! Some assets are items players can carry (eg. a quickhack lying in a
+//! node). "take <item>" picks an item up from the node, "drop <item>" puts
+//! it down in the node the player is in and "inventory" lists what the
+//! player carries: the items picked up and the items of the account (eg.
+//! bought from a vendor, see `economy`).
+//!
+//! Items picked up are assets of the world, they are not saved with the
+//! account. When a player leaves the world, they return to the nodes they
+//! were taken from, so they stay in the world for the others.
+//!
+//! Items may belong to a player, only the owner takes them (see
+//! `ownership`).
+
+use std::sync::Arc;
+
+use generational_arena::Index;
+
+use super::actions::Action;
+use super::assets::{Actor, AssetID, AssetResponse, GameAsset, Port};
+use super::fuzzy;
+use super::intern::intern;
+use super::ownership::Ownership;
+use super::properties::Property;
+use super::puzzles::Puzzle;
+use super::terminals::Terminal;
+
+/// Number of items a player can carry
+pub const CAPACITY: usize = 8;
+
+/// An item players can take and carry
+#[derive(Debug, Clone)]
+pub struct Item {
+    id: AssetID,
+    /// The noun the item is referenced by (eg. "quickhack")
+    name: Arc<str>,
+    description: Arc<str>,
+    ownership: Option<Ownership>,
+}
+
+impl Item {
+    /// Create a new item
+    pub fn new(id: AssetID, name: &str) -> Item {
+        Item {
+            id,
+            name: intern(&name.to_lowercase()),
+            description: intern(""),
+            ownership: None,
+        }
+    }
+
+    /// Describe an item
+    pub fn update_description(&mut self, description: &str) {
+        self.description = intern(description);
+    }
+
+    /// Give the item to an account, or to nobody if None
+    pub fn update_ownership(&mut self, ownership: Option<Ownership>) {
+        self.ownership = ownership;
+    }
+}
+
+impl GameAsset for Item {
+    /// Return the uid of the item
+    fn uid(&self) -> AssetID {
+        self.id
+    }
+
+    /// Returns the noun the item is referenced by
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Items have no properties yet
+    fn properties(&self) -> Option<&Vec<Property>> {
+        None
+    }
+
+    /// Describe the item
+    fn describe(&self) -> String {
+        match &self.ownership {
+            Some(o) => format!("{} The {} belongs to {}.", self.description, self.name, o.owner),
+            None => format!("{} You could take the {}.", self.description, self.name),
+        }
+    }
+
+    /// Returns the description of the item
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    /// Replace the description of the item
+    fn rewrite(&mut self, description: &str) {
+        self.update_description(description);
+    }
+
+    /// React to
+    ///
+    /// Taking and dropping the item is handled by the world, as it changes
+    /// the node and the player.
+    fn react_to(&mut self, _actor: &Actor<'_>, a: &Action) -> AssetResponse {
+        match a {
+            Action::Look { .. } => self.description.to_string().into(),
+            _ => AssetResponse::default(),
+        }
+    }
+
+    /// Items make no sound
+    fn sound(&self) -> Option<&str> {
+        None
+    }
+
+    /// An item is not a terminal
+    fn terminal(&self) -> Option<&Terminal> {
+        None
+    }
+
+    /// An item is not a port
+    fn port(&self) -> Option<&Port> {
+        None
+    }
+
+    /// An item is not a port
+    fn port_mut(&mut self) -> Option<&mut Port> {
+        None
+    }
+
+    /// Items carry no puzzles
+    fn puzzle(&self) -> Option<&Puzzle> {
+        None
+    }
+
+    /// Items do not lead anywhere
+    fn portal(&self) -> Option<&str> {
+        None
+    }
+
+    /// Items can be taken
+    fn is_takeable(&self) -> bool {
+        true
+    }
+
+    /// Returns the account the item belongs to, if any
+    fn ownership(&self) -> Option<&Ownership> {
+        self.ownership.as_ref()
+    }
+
+    /// An item is taken, or stolen if it is lootable
+    fn verbs(&self) -> Vec<&'static str> {
+        match &self.ownership {
+            Some(o) if o.lootable => vec!["look", "take", "steal"],
+            _ => vec!["look", "take"],
+        }
+    }
+
+    /// Returns a copy of the item
+    fn clone_box(&self) -> Box<dyn GameAsset> {
+        Box::new(self.clone())
+    }
+
+    /// Gives the item another uid
+    fn renumber(&mut self, uid: AssetID) {
+        self.id = uid;
+    }
+}
+
+/// The items a player picked up
+#[derive(Debug, Default)]
+pub struct Inventory {
+    /// The items and the nodes they were taken from
+    items: Vec<(Box<dyn GameAsset>, Index)>,
+}
+
+impl Inventory {
+    /// True if the player carries as many items as it can
+    pub fn is_full(&self) -> bool {
+        self.items.len() >= CAPACITY
+    }
+
+    /// Carry an item taken from a node
+    ///
+    /// Returns the item back if the player carries too much.
+    pub fn carry(&mut self, item: Box<dyn GameAsset>, from: Index) -> Result<(), Box<dyn GameAsset>> {
+        if self.is_full() {
+            return Err(item);
+        }
+        self.items.push((item, from));
+        Ok(())
+    }
+
+    /// Put down the item referenced by a noun and ordinal (counting from
+    /// one, the only one if None)
+    ///
+    /// A mistyped noun is resolved to the closest item (see `fuzzy`).
+    /// Returns the item, or why there is no such item.
+    pub fn put_down(&mut self, noun: &str, ordinal: Option<usize>) -> Result<Box<dyn GameAsset>, String> {
+        let mut matches = self.positions(noun);
+        let resolved;
+        let mut noun = noun;
+        if matches.is_empty() {
+            resolved = fuzzy::resolve(noun, self.items.iter().map(|(i, _)| i.name()))
+                .map_err(|names| format!("You carry no {}. {}", noun, fuzzy::question(&names)))?
+                .map(str::to_string);
+            if let Some(name) = &resolved {
+                matches = self.positions(name);
+                noun = name;
+            }
+        }
+        let position = match (ordinal, matches.len()) {
+            (Some(n), _) => n.checked_sub(1).and_then(|i| matches.get(i).copied())
+                .ok_or_else(|| format!("You carry no {} {}.", noun, n))?,
+            (None, 0) => return Err(format!("You carry no {}.", noun)),
+            (None, 1) => matches[0],
+            (None, _) => return Err(format!("You carry more than one {}. Which one do you mean \
+                (eg. \"{} 2\")?", noun, noun)),
+        };
+        Ok(self.items.remove(position).0)
+    }
+
+    /// The positions of the items referenced by a noun (or its plural)
+    fn positions(&self, noun: &str) -> Vec<usize> {
+        let stem = fuzzy::stem(noun);
+        self.items.iter()
+            .enumerate()
+            .filter(|(_, (i, _))| fuzzy::stem(i.name()) == stem)
+            .map(|(position, _)| position)
+            .collect()
+    }
+
+    /// The items, with the nodes they were taken from
+    pub fn items(&self) -> impl Iterator<Item = (&dyn GameAsset, Index)> {
+        self.items.iter().map(|(i, from)| (i.as_ref(), *from))
+    }
+
+    /// Put down all items, with the nodes they were taken from
+    pub fn empty(&mut self) -> Vec<(Box<dyn GameAsset>, Index)> {
+        std::mem::take(&mut self.items)
+    }
+
+    /// Who a carried item referenced by a noun belongs to, if anybody
+    pub fn ownership(&self, noun: &str) -> Option<&Ownership> {
+        self.positions(noun).into_iter().find_map(|p| self.items[p].0.ownership())
+    }
+
+    /// The names of the items, in the order they were picked up
+    pub fn names(&self) -> Vec<&str> {
+        self.items.iter().map(|(i, _)| i.name()).collect()
+    }
+}
@@ -0,0 +1,134 @@
+//! Keys
+//!
+//! The buttons of the badge send short control sequences instead of text
+//! (eg. "ESC[A" for up). Players bind commands to keys with the "bind"
+//! command, the bindings are stored in their account. Keys are known by
+//! their names:
+//!  * "up", "down", "right", "left" (ESC[A to ESC[D, also ESC O A to ESC O D)
+//!  * "f1" to "f4" (ESC O P to ESC O S)
+//!  * "ctrl-a" to "ctrl-z" (single control bytes, except ctrl-j and ctrl-m
+//!    that end a line)
+//!  * any other escape sequence by its bytes (eg. "ESC[1;5A")
+//!
+//! Ssh sessions with echo on keep "up" and "down" for the command history
+//! (see `line_editor`), they are no keys there.
+
+use tracing::error;
+
+use crate::world::messages::{Event, Message};
+use crate::world::{GameWorld, Player};
+
+/// Escape, the start of most key sequences
+const ESC: u8 = 0x1b;
+
+/// Named escape sequences (without the leading escape)
+const SEQUENCES: &[(&str, &[u8])] = &[
+    ("up", b"[A"),
+    ("down", b"[B"),
+    ("right", b"[C"),
+    ("left", b"[D"),
+    ("up", b"OA"),
+    ("down", b"OB"),
+    ("right", b"OC"),
+    ("left", b"OD"),
+    ("f1", b"OP"),
+    ("f2", b"OQ"),
+    ("f3", b"OR"),
+    ("f4", b"OS"),
+];
+
+/// Maximum number of keys a player can bind
+pub const MAX_BINDINGS: usize = 32;
+
+/// Longest escape sequence accepted as a key
+const MAX_SEQUENCE_LENGTH: usize = 16;
+
+/// Name of the key a sequence of bytes was sent by
+///
+/// Returns None if the bytes are not a key but text (or a line ending).
+pub fn name_of(sequence: &[u8]) -> Option<String> {
+    match sequence {
+        [ESC, rest @ ..] if !rest.is_empty() && sequence.len() <= MAX_SEQUENCE_LENGTH => {
+            if let Some((name, _)) = SEQUENCES.iter().find(|(_, s)| *s == rest) {
+                return Some(name.to_string());
+            }
+            if rest.iter().all(|b| b.is_ascii_graphic()) {
+                Some(format!("ESC{}", String::from_utf8_lossy(rest).to_uppercase()))
+            } else {
+                None
+            }
+        },
+        [b] if *b < 0x20 && *b != b'\r' && *b != b'\n' && *b != ESC => {
+            Some(format!("ctrl-{}", (b'a' + b - 1) as char))
+        },
+        _ => None,
+    }
+}
+
+/// Name of a key as given by a player
+///
+/// Accepts the names of the keys ignoring case, escape sequences written as
+/// "ESC..." (eg. "ESC[A" is "up") and "^A" for "ctrl-a". Returns None if
+/// there is no such key.
+pub fn parse_name(name: &str) -> Option<String> {
+    let lower = name.trim().to_lowercase();
+    if let Some(rest) = lower.strip_prefix("esc") {
+        let mut sequence = vec![ESC];
+        sequence.extend_from_slice(rest.to_uppercase().as_bytes());
+        return name_of(&sequence);
+    }
+    let letter = lower.strip_prefix("ctrl-").or_else(|| lower.strip_prefix('^'));
+    if let Some(letter) = letter {
+        return match letter.as_bytes() {
+            [l @ b'a'..=b'z'] => name_of(&[l - b'a' + 1]),
+            _ => None,
+        };
+    }
+    SEQUENCES.iter()
+        .find(|(n, _)| *n == lower)
+        .map(|(n, _)| n.to_string())
+}
+
+/// List the keys bound by a player, bind a command to a key or unbind it
+pub(super) async fn bind(key: Option<String>, command: Option<String>, world: &GameWorld, player: &mut Player) {
+    match key {
+        None => {
+            let keymap = &player.account.keymap;
+            let mut text = if keymap.is_empty() {
+                "No keys are bound. Eg. \"bind up look\" looks around when you press up.".to_string()
+            } else {
+                "Your keys:".to_string()
+            };
+            for (key, command) in keymap.iter() {
+                text += format!("\r\n {:>8} -> {}", key, command).as_str();
+            }
+            player.send(Message::new(Event::Info, &text)
+                .with_field("keymap", keymap)).await;
+        },
+        Some(key) => {
+            let message = match (parse_name(&key), command) {
+                (None, _) => format!("There is no key {}. Keys are up, down, left, right, \
+                    f1 to f4, ctrl-a to ctrl-z or escape sequences like ESC[A.", key),
+                (Some(key), Some(_)) if !player.account.keymap.contains_key(&key)
+                    && player.account.keymap.len() >= MAX_BINDINGS => {
+                    format!("You cannot bind more than {} keys.", MAX_BINDINGS)
+                },
+                (Some(key), Some(command)) => {
+                    let message = format!("Pressing {} runs \"{}\".", key, command);
+                    player.account.keymap.insert(key, command);
+                    message
+                },
+                (Some(key), None) => {
+                    match player.account.keymap.remove(&key) {
+                        Some(_) => format!("Nothing is bound to {} anymore.", key),
+                        None => format!("Nothing is bound to {}.", key),
+                    }
+                },
+            };
+            if let Err(e) = player.account.save(&world.data_dir).await {
+                error!("Could not save account of {}: {}", player.player_name, e);
+            }
+            player.send(Message::new(Event::Info, &message)).await;
+        },
+    }
+}
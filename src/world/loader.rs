@@ -0,0 +1,300 @@
+//! Loader
+//!
+//! Loads the files the world is built from (eg. screens) without blocking the
+//! async runtime. Files are loaded once at startup and kept in memory, so the
+//! world loop never has to wait for the disk.
+//!
+//! The world itself may be loaded from a world file (`file` in the `[world]`
+//! section of the settings), so builders change the content without
+//! recompiling. The file is written in TOML (".toml") or RON (".ron") and
+//! lists the nodes with their assets:
+//! ```ignore
+//!     [[nodes]]
+//!     uid = 0
+//!     name = "Lobby"
+//!     description = "Around you its dark."
+//!     spawn = true                       # players spawn here (optional)
+//!     sound = "ultraviolet_hum.wav"      # optional
+//!     season = "con"                     # optional
+//!     safe = false                       # optional
+//!     min_level = 2                      # optional
+//!     security = 1                       # optional, 0 for none
+//!
+//!     [[nodes.ports]]
+//!     uid = 1
+//!     description = "A port rimmed with static."
+//!     portal = "hackerspace"             # the world it leads to (optional)
+//!
+//!     [[nodes.ports]]
+//!     uid = 3
+//!     to = 4                             # the node it leads to (optional)
+//!     open = true                        # optional, else solve its puzzle
+//!
+//!     [[nodes.terminals]]
+//!     uid = 2
+//!     hostname = "ono-sendai"
+//!     description = "A battered terminal."
+//!     files = { "/readme.txt" = "Authorized personnel only." }
+//!
+//!     [[nodes.items]]
+//!     uid = 5
+//!     name = "quickhack"                 # players take it by this noun
+//!     description = "A quickhack on a data shard."
+//!     owner = "zero"                     # the account it belongs to (optional)
+//!     lootable = true                    # optional, else nobody steals it
+//!
+//!     [[nodes.ice]]
+//!     uid = 6
+//!     protects = 3                       # the port of the node it guards
+//!     description = "A wall of black ICE."
+//!     strength = 40                      # optional, its integrity
+//!     trace = 25                         # optional, trace per exchange
+//! ```
+//! The uids of nodes and assets must be unique, ports must lead to nodes of
+//! the file, ICE must protect a port of its node and at least one node must
+//! be a spawn node.
+
+use std::collections::{BTreeMap, HashSet};
+use std::io;
+use std::path::Path;
+
+use generational_arena::Arena;
+
+use super::assets::{AssetID, GameAsset, Node, Port};
+use super::ice::{Ice, DEFAULT_STRENGTH, DEFAULT_TRACE};
+use super::inventory::Item;
+use super::ownership::Ownership;
+use super::safety::Safety;
+use super::terminals::Terminal;
+
+/// Load a file
+pub async fn load(path: &Path) -> io::Result<Vec<u8>> {
+    tokio::fs::read(path).await
+}
+
+/// The content of a world file
+#[derive(Debug, Clone, Deserialize)]
+pub struct WorldFile {
+    pub nodes: Vec<NodeFile>,
+}
+
+/// A node in a world file
+#[derive(Debug, Clone, Deserialize)]
+pub struct NodeFile {
+    pub uid: AssetID,
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// Players spawn in the node
+    #[serde(default)]
+    pub spawn: bool,
+    #[serde(default)]
+    pub sound: Option<String>,
+    #[serde(default)]
+    pub season: Option<String>,
+    #[serde(default)]
+    pub safe: bool,
+    #[serde(default)]
+    pub min_level: u64,
+    /// The security level of the node (see `security`)
+    #[serde(default)]
+    pub security: u32,
+    #[serde(default)]
+    pub ports: Vec<PortFile>,
+    #[serde(default)]
+    pub terminals: Vec<TerminalFile>,
+    #[serde(default)]
+    pub items: Vec<ItemFile>,
+    #[serde(default)]
+    pub ice: Vec<IceFile>,
+}
+
+/// A port in a world file
+#[derive(Debug, Clone, Deserialize)]
+pub struct PortFile {
+    pub uid: AssetID,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub sound: Option<String>,
+    /// The world the port leads to
+    #[serde(default)]
+    pub portal: Option<String>,
+    /// The uid of the node the port leads to
+    #[serde(default)]
+    pub to: Option<AssetID>,
+    #[serde(default)]
+    pub open: bool,
+}
+
+/// A terminal in a world file
+#[derive(Debug, Clone, Deserialize)]
+pub struct TerminalFile {
+    pub uid: AssetID,
+    pub hostname: String,
+    #[serde(default)]
+    pub description: String,
+    /// The contents of the files by their path
+    #[serde(default)]
+    pub files: BTreeMap<String, String>,
+}
+
+/// An item players can take in a world file
+#[derive(Debug, Clone, Deserialize)]
+pub struct ItemFile {
+    pub uid: AssetID,
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// The account the item belongs to
+    #[serde(default)]
+    pub owner: Option<String>,
+    #[serde(default)]
+    pub lootable: bool,
+}
+
+/// ICE guarding a port in a world file (see `ice`)
+#[derive(Debug, Clone, Deserialize)]
+pub struct IceFile {
+    pub uid: AssetID,
+    /// The uid of the port of the node the ICE protects
+    pub protects: AssetID,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub strength: Option<u32>,
+    #[serde(default)]
+    pub trace: Option<u32>,
+}
+
+impl WorldFile {
+    /// Parse a world file, in RON if its name ends in ".ron" and in TOML
+    /// otherwise
+    pub fn parse(path: &Path, data: &[u8]) -> io::Result<WorldFile> {
+        let text = std::str::from_utf8(data).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let parsed = if path.extension().is_some_and(|e| e.eq_ignore_ascii_case("ron")) {
+            ron::from_str(text).map_err(|e| e.to_string())
+        } else {
+            toml::from_str(text).map_err(|e| e.to_string())
+        };
+        parsed.map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Build the nodes of the world
+    ///
+    /// Returns the nodes and if players spawn there, in the order of the
+    /// file, or why the world cannot be built.
+    pub fn build(&self) -> Result<Vec<(Node, bool)>, String> {
+        let mut uids = HashSet::new();
+        let assets = self.nodes.iter().flat_map(|n| {
+            n.ports.iter().map(|p| p.uid)
+                .chain(n.terminals.iter().map(|t| t.uid))
+                .chain(n.items.iter().map(|i| i.uid))
+                .chain(n.ice.iter().map(|i| i.uid))
+        });
+        for uid in self.nodes.iter().map(|n| n.uid).chain(assets) {
+            if !uids.insert(uid) {
+                return Err(format!("The uid {} is used more than once.", uid));
+            }
+        }
+        if !self.nodes.iter().any(|n| n.spawn) {
+            return Err("There is no spawn node.".to_string());
+        }
+        for (node, ice) in self.nodes.iter().flat_map(|n| n.ice.iter().map(move |i| (n, i))) {
+            if !node.ports.iter().any(|p| p.uid == ice.protects) {
+                return Err(format!("ICE {} protects port {}, which is not in its node.", ice.uid, ice.protects));
+            }
+        }
+        for (port, to) in self.links() {
+            if !self.nodes.iter().any(|n| n.uid == to) {
+                return Err(format!("Port {} leads to node {}, which does not exist.", port, to));
+            }
+        }
+        Ok(self.nodes.iter().map(|n| (n.build(), n.spawn)).collect())
+    }
+
+    /// The uids of the ports leading to other nodes and of the nodes they
+    /// lead to, to connect them once the nodes are in the world (see
+    /// `GameWorld::connect`)
+    pub fn links(&self) -> Vec<(AssetID, AssetID)> {
+        self.nodes.iter()
+            .flat_map(|n| n.ports.iter().filter_map(|p| p.to.map(|to| (p.uid, to))))
+            .collect()
+    }
+
+    /// The highest uid of the file
+    pub fn max_uid(&self) -> AssetID {
+        self.nodes.iter()
+            .flat_map(|n| std::iter::once(n.uid)
+                .chain(n.ports.iter().map(|p| p.uid))
+                .chain(n.terminals.iter().map(|t| t.uid))
+                .chain(n.items.iter().map(|i| i.uid))
+                .chain(n.ice.iter().map(|i| i.uid)))
+            .max()
+            .unwrap_or_default()
+    }
+}
+
+impl NodeFile {
+    fn build(&self) -> Node {
+        let mut node = Node::new(self.uid);
+        node.update_name(&self.name);
+        node.update_description(&self.description);
+        node.update_sound(self.sound.as_deref());
+        node.update_season(self.season.as_deref());
+        node.update_safety(Safety { safe: self.safe, min_level: self.min_level });
+        node.update_security(self.security);
+        for p in &self.ports {
+            let mut port = Port::new(p.uid);
+            port.update_description(&p.description);
+            port.update_sound(p.sound.as_deref());
+            port.update_portal(p.portal.as_deref());
+            port.update_open(p.open);
+            node.add_asset(Box::new(port));
+        }
+        for t in &self.terminals {
+            let mut terminal = Terminal::new(t.uid, &t.hostname);
+            terminal.update_description(&t.description);
+            for (path, contents) in &t.files {
+                terminal.fs_mut().add_file(path, contents);
+            }
+            node.add_asset(Box::new(terminal));
+        }
+        for i in &self.items {
+            let mut item = Item::new(i.uid, &i.name);
+            item.update_description(&i.description);
+            item.update_ownership(i.owner.clone().map(|owner| Ownership { owner, lootable: i.lootable }));
+            node.add_asset(Box::new(item));
+        }
+        for i in &self.ice {
+            let mut ice = Ice::new(i.uid, i.protects);
+            ice.update_description(&i.description);
+            ice.update_strength(i.strength.unwrap_or(DEFAULT_STRENGTH));
+            ice.update_trace(i.trace.unwrap_or(DEFAULT_TRACE));
+            node.add_asset(Box::new(ice));
+        }
+        node
+    }
+}
+
+/// Connect a port to a node, both given by their uid
+///
+/// Returns false if there is no such port or node.
+pub fn connect(nodes: &mut Arena<Node>, port: AssetID, node: AssetID) -> bool {
+    let to = match nodes.iter().find(|(_, n)| n.uid() == node) {
+        Some((index, _)) => index,
+        None => return false,
+    };
+    match nodes.iter_mut().find_map(|(_, n)| n.asset_mut(port)).and_then(|a| a.port_mut()) {
+        Some(p) => {
+            p.connect(Some(to));
+            true
+        },
+        None => false,
+    }
+}
+
+/// Load a world file
+pub async fn load_world(path: &Path) -> io::Result<WorldFile> {
+    WorldFile::parse(path, &load(path).await?)
+}
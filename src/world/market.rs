@@ -0,0 +1,392 @@
+//! Market
+//!
+//! Players trade items for credits in the market nodes of the world. A
+//! seller lists an item for a price ("market sell <item> <price>") and the
+//! world holds the item in escrow until a buyer pays the price ("market buy
+//! <listing>") or the listing expires. Buyers and sellers never deal with
+//! each other directly, so neither side can take the goods without giving
+//! its part.
+//!
+//! Whatever the market hands out goes to the mailbox of the player (the item
+//! bought, the credits of a sale, the item of an expired or cancelled
+//! listing), so trades go through while the other side is offline. Players
+//! collect their mail with "mail" anywhere in the world. Listings and mail
+//! are stored as JSON file inside the data directory of the world.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use tokio::fs;
+use tracing::{error, info};
+
+use crate::connection_manager::ClientId;
+use super::actions::Action;
+use super::messages::{Event, Message};
+use super::{timestamp, GameWorld, Player};
+
+/// Name of the file the market is stored in (inside the data directory)
+pub const MARKET_FILE: &str = "market.json";
+
+/// Seconds until a listing expires
+pub const LISTING_TIME: u64 = 24 * 60 * 60;
+
+/// An order to the market
+#[derive(Debug, Clone, PartialEq)]
+pub enum Trade {
+    /// Show the listings, optionally only the items matching a search term
+    Browse(Option<String>),
+    /// List an item for a price
+    Sell { item: String, price: u64 },
+    /// Buy the listing with the given number
+    Buy(u64),
+    /// Cancel the listing with the given number
+    Cancel(u64),
+}
+
+/// Display a trade as typed by the player
+impl fmt::Display for Trade {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Trade::Browse(None) => write!(f, "market"),
+            Trade::Browse(Some(term)) => write!(f, "market search {}", term),
+            Trade::Sell { item, price } => write!(f, "market sell {} {}", item, price),
+            Trade::Buy(id) => write!(f, "market buy {}", id),
+            Trade::Cancel(id) => write!(f, "market cancel {}", id),
+        }
+    }
+}
+
+/// An item listed for sale
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Listing {
+    /// Number of the listing buyers refer to
+    pub id: u64,
+    /// The player selling the item
+    pub seller: String,
+    /// The item held in escrow
+    pub item: String,
+    /// The price in credits
+    pub price: u64,
+    /// Seconds since the epoch when the listing expires
+    pub expires: u64,
+}
+
+/// A delivery waiting in the mailbox of a player
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Parcel {
+    /// What the delivery is about (eg. "Sold purple keycard to neo.")
+    pub text: String,
+    /// The item delivered
+    pub item: Option<String>,
+    /// The credits delivered
+    pub credits: u64,
+}
+
+/// The listings of the market and the mailboxes of the players
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Market {
+    listings: Vec<Listing>,
+    mail: BTreeMap<String, Vec<Parcel>>,
+    next_id: u64,
+}
+
+impl Market {
+    /// Load the market stored in the data directory
+    pub async fn load(data_dir: &Path) -> io::Result<Market> {
+        match fs::read(Market::path(data_dir)).await {
+            Ok(buf) => Ok(serde_json::from_slice(&buf)?),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Market::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Save the market to the data directory
+    pub async fn save(&self, data_dir: &Path) -> io::Result<()> {
+        fs::create_dir_all(data_dir).await?;
+        fs::write(Market::path(data_dir), serde_json::to_vec_pretty(self)?).await
+    }
+
+    /// List an item taken into escrow for a price
+    ///
+    /// Returns the listing.
+    pub fn list(&mut self, seller: &str, item: &str, price: u64, now: u64) -> &Listing {
+        self.next_id += 1;
+        self.listings.push(Listing {
+            id: self.next_id,
+            seller: seller.to_string(),
+            item: item.to_string(),
+            price,
+            expires: now + LISTING_TIME,
+        });
+        &self.listings[self.listings.len() - 1]
+    }
+
+    /// The listing with the given number
+    pub fn get(&self, id: u64) -> Option<&Listing> {
+        self.listings.iter().find(|l| l.id == id)
+    }
+
+    /// The listings of items containing the search term (ignoring case),
+    /// all listings without a term
+    pub fn search(&self, term: Option<&str>) -> Vec<&Listing> {
+        let term = term.map(|t| t.to_lowercase());
+        self.listings.iter()
+            .filter(|l| term.as_deref().is_none_or(|t| l.item.to_lowercase().contains(t)))
+            .collect()
+    }
+
+    /// Complete the sale of a listing the buyer paid for
+    ///
+    /// The item is mailed to the buyer and the price to the seller. Returns
+    /// the listing sold, None if there is no such listing.
+    pub fn buy(&mut self, id: u64, buyer: &str) -> Option<Listing> {
+        let position = self.listings.iter().position(|l| l.id == id)?;
+        let listing = self.listings.remove(position);
+        self.send(buyer, Parcel {
+            text: format!("Bought {} from {}.", listing.item, listing.seller),
+            item: Some(listing.item.clone()),
+            credits: 0,
+        });
+        self.send(&listing.seller, Parcel {
+            text: format!("Sold {} to {}.", listing.item, buyer),
+            item: None,
+            credits: listing.price,
+        });
+        Some(listing)
+    }
+
+    /// Cancel a listing of the seller (ignoring case), mailing the item back
+    ///
+    /// Returns the listing cancelled, None if the seller has no such
+    /// listing.
+    pub fn cancel(&mut self, id: u64, seller: &str) -> Option<Listing> {
+        let position = self.listings.iter().position(|l| l.id == id && l.seller.eq_ignore_ascii_case(seller))?;
+        let listing = self.listings.remove(position);
+        self.send(&listing.seller, Parcel {
+            text: format!("Cancelled the listing of {}.", listing.item),
+            item: Some(listing.item.clone()),
+            credits: 0,
+        });
+        Some(listing)
+    }
+
+    /// Remove the expired listings, mailing the items back to the sellers
+    ///
+    /// Returns the expired listings.
+    pub fn expire(&mut self, now: u64) -> Vec<Listing> {
+        let (expired, listings) = std::mem::take(&mut self.listings).into_iter().partition(|l| l.expires <= now);
+        self.listings = listings;
+        for listing in &expired {
+            self.send(&listing.seller, Parcel {
+                text: format!("Your listing of {} expired unsold.", listing.item),
+                item: Some(listing.item.clone()),
+                credits: 0,
+            });
+        }
+        expired
+    }
+
+    /// Put a parcel into the mailbox of a player
+    pub fn send(&mut self, player: &str, parcel: Parcel) {
+        self.mail.entry(player.to_string()).or_default().push(parcel);
+    }
+
+    /// Number of parcels waiting for a player
+    pub fn mail_count(&self, player: &str) -> usize {
+        self.mail.get(player).map_or(0, |m| m.len())
+    }
+
+    /// Take the parcels waiting for a player out of its mailbox
+    pub fn collect(&mut self, player: &str) -> Vec<Parcel> {
+        self.mail.remove(player).unwrap_or_default()
+    }
+
+    /// The listings of a player (ignoring case)
+    pub fn listings_of(&self, player: &str) -> Vec<&Listing> {
+        self.listings.iter().filter(|l| l.seller.eq_ignore_ascii_case(player)).collect()
+    }
+
+    /// The parcels waiting for a player
+    pub fn mail_of(&self, player: &str) -> &[Parcel] {
+        self.mail.get(player).map_or(&[], |m| m.as_slice())
+    }
+
+    /// Rewrite the texts of the parcels of all players, keeping those the
+    /// rewrite returns None for (eg. to anonymize a player)
+    pub fn rewrite_mail(&mut self, rewrite: impl Fn(&str) -> Option<String>) {
+        for parcel in self.mail.values_mut().flatten() {
+            if let Some(text) = rewrite(&parcel.text) {
+                parcel.text = text;
+            }
+        }
+    }
+
+    /// Remove the listings and the mail of a player (eg. when the player is
+    /// forgotten)
+    pub fn remove(&mut self, player: &str) {
+        self.listings.retain(|l| !l.seller.eq_ignore_ascii_case(player));
+        self.mail.remove(player);
+    }
+
+    fn path(data_dir: &Path) -> PathBuf {
+        data_dir.join(MARKET_FILE)
+    }
+}
+
+/// Trade at the market of the node or collect the mail
+/// 
+/// The world holds the items listed in escrow and delivers to the mail of
+/// the players, so neither side of a trade needs to be online.
+pub(super) async fn trade(a: &Action, world: &mut GameWorld, client_id: ClientId,
+                          players: &mut HashMap<ClientId, Player>) {
+    let player = match players.get_mut(&client_id) {
+        Some(p) => p,
+        None => return,
+    };
+    info!("Player {} is performing action {}.", player.player_name, a);
+    let trade = match a {
+        Action::Market(t) => t,
+        _ => {
+            let parcels = world.market.collect(&player.player_name);
+            let message = if parcels.is_empty() {
+                Message::new(Event::Response, "You have no mail.")
+            } else {
+                let mut lines = Vec::new();
+                for parcel in &parcels {
+                    player.account.credits += parcel.credits;
+                    match (&parcel.item, parcel.credits) {
+                        (Some(item), _) => {
+                            player.account.items.push(item.clone());
+                            lines.push(format!("  {} You receive {}.", parcel.text, item));
+                        },
+                        (None, 0) => lines.push(format!("  {}", parcel.text)),
+                        (None, credits) => lines.push(format!("  {} You receive {} credits.", parcel.text, credits)),
+                    }
+                }
+                save_trade(world, player).await;
+                Message::new(Event::Response, &format!("You collect your mail:\n{}\nYou own {} credits.", 
+                        lines.join("\n"), player.account.credits))
+                    .with_field("mail", &parcels)
+                    .with_field("credits", player.account.credits)
+            };
+            player.send(message).await;
+            return;
+        },
+    };
+    if !player.location.is_some_and(|l| world.markets.contains(&l)) {
+        player.send(Message::new(Event::Error, "There is no market here.")).await;
+        return;
+    }
+    let name = player.player_name.clone();
+    let message = match trade {
+        Trade::Browse(term) => {
+            let listings = world.market.search(term.as_deref());
+            let mut text = if listings.is_empty() {
+                "There is nothing for sale.".to_string()
+            } else {
+                let lines: Vec<String> = listings.iter()
+                    .map(|l| format!("  #{} {} for {} credits (sold by {})", l.id, l.item, l.price, l.seller))
+                    .collect();
+                format!("For sale:\n{}", lines.join("\n"))
+            };
+            text.push_str(&format!("\nYou own {} credits", player.account.credits));
+            if player.account.items.is_empty() {
+                text.push('.');
+            } else {
+                text.push_str(&format!(" and carry {}.", player.account.items.join(", ")));
+            }
+            Message::new(Event::Response, &text)
+                .with_field("listings", &listings)
+                .with_field("credits", player.account.credits)
+        },
+        Trade::Sell { item, price } => {
+            match player.account.items.iter().position(|i| i.eq_ignore_ascii_case(item)) {
+                None => untradeable(player, item),
+                Some(position) => {
+                    let item = player.account.items.remove(position);
+                    let listing = world.market.list(&name, &item, *price, timestamp()).clone();
+                    save_trade(world, player).await;
+                    Message::new(Event::Response, &format!("You list {} for {} credits as #{}. Unless sold, it \
+                            returns to your mail in {} hours.", listing.item, listing.price, listing.id, 
+                            LISTING_TIME / 3600))
+                        .with_field("listing", &listing)
+                },
+            }
+        },
+        Trade::Buy(id) => match world.market.get(*id) {
+            None => Message::new(Event::Error, &format!("There is no listing #{}.", id)),
+            Some(l) if l.seller.eq_ignore_ascii_case(&name) => Message::new(Event::Error, "You cannot buy your own listing."),
+            Some(l) if l.price > player.account.credits => {
+                Message::new(Event::Error, &format!("You cannot afford {} for {} credits.", l.item, l.price))
+            },
+            Some(_) => {
+                let listing = match world.market.buy(*id, &name) {
+                    Some(l) => l,
+                    None => return,
+                };
+                player.account.credits -= listing.price;
+                save_trade(world, player).await;
+                let sold = Message::new(Event::Info, &format!("{} bought your {} for {} credits. Collect them \
+                        with \"mail\".", name, listing.item, listing.price));
+                if let Some(seller) = players.values().find(|p| p.player_name.eq_ignore_ascii_case(&listing.seller)) {
+                    seller.send(sold).await;
+                }
+                Message::new(Event::Response, &format!("You buy {} for {} credits. Collect it with \"mail\".", 
+                        listing.item, listing.price))
+                    .with_field("listing", &listing)
+            },
+        },
+        Trade::Cancel(id) => match world.market.cancel(*id, &name) {
+            None => Message::new(Event::Error, &format!("You have no listing #{}.", id)),
+            Some(listing) => {
+                save_trade(world, player).await;
+                Message::new(Event::Response, &format!("You cancel the listing of {}. Collect it with \"mail\".", 
+                    listing.item))
+            },
+        },
+    };
+    if let Some(p) = players.get(&client_id) {
+        p.send(message).await;
+    }
+}
+
+/// Save the account of a trading player and the market, so neither can get
+/// ahead of the other on a crash
+async fn save_trade(world: &GameWorld, player: &Player) {
+    if let Err(e) = player.account.save(&world.data_dir).await {
+        error!("Could not save account of {}: {}", player.player_name, e);
+    }
+    if let Err(e) = world.market.save(&world.data_dir).await {
+        error!("Could not save the market: {}", e);
+    }
+}
+
+/// Why a player cannot sell an item missing from its account: it carries
+/// no such item, or the item it carries belongs to somebody else (see
+/// `ownership`)
+pub(super) fn untradeable(player: &Player, item: &str) -> Message {
+    match player.inventory.ownership(item).filter(|o| !o.is_owner(&player.player_name)) {
+        Some(o) => {
+            Message::new(Event::Error, &format!("The {} belongs to {}, it is not yours to sell.", item, o.owner))
+        },
+        None => Message::new(Event::Error, &format!("You carry no {}.", item)),
+    }
+}
+
+/// Return the expired listings of the market to the mail of the sellers
+pub(super) async fn process(world: &mut GameWorld, players: &HashMap<ClientId, Player>) {
+    let expired = world.market.expire(timestamp());
+    if expired.is_empty() {
+        return;
+    }
+    if let Err(e) = world.market.save(&world.data_dir).await {
+        error!("Could not save the market: {}", e);
+    }
+    for listing in expired {
+        if let Some(p) = players.values().find(|p| p.player_name.eq_ignore_ascii_case(&listing.seller)) {
+            let text = format!("Your listing of {} expired unsold. Collect it with \"mail\".", listing.item);
+            p.send(Message::new(Event::Info, &text)).await;
+        }
+    }
+}
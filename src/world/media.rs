@@ -0,0 +1,76 @@
+//! Media
+//!
+//! Sound triggers for clients that can play media. Over SSH there is no
+//! telnet negotiation, so a client announces the protocol it understands
+//! with the "media" command (usually sent by a script of the client on
+//! connect):
+//!  * MSP, the MUD Sound Protocol ("!!SOUND(door.wav U=...)" on its own line)
+//!  * GMCP Client.Media (a telnet subnegotiation carrying JSON)
+//!
+//! Sounds are named by their file (eg. "door_open.wav"). Clients fetch the
+//! files from the media URL of the world, if one is configured. Clients
+//! announcing GMCP get other packages, too (eg. vitals, see `combat`).
+
+use std::fmt;
+
+use serde_json::{json, Value};
+
+/// Telnet: interpret as command
+const IAC: u8 = 255;
+/// Telnet: subnegotiation begin
+const SB: u8 = 250;
+/// Telnet: subnegotiation end
+const SE: u8 = 240;
+/// Telnet option of GMCP
+const GMCP: u8 = 201;
+
+/// The protocols clients can play sounds with
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Protocol {
+    /// MUD Sound Protocol
+    Msp,
+    /// GMCP Client.Media
+    Gmcp,
+}
+
+impl Protocol {
+    /// The packet making a client play a sound
+    ///
+    /// The URL the client fetches the sound files from is left out if empty.
+    pub fn packet(&self, sound: &str, url: &str) -> Vec<u8> {
+        match self {
+            Protocol::Msp => {
+                if url.is_empty() {
+                    format!("!!SOUND({})\r\n", sound).into_bytes()
+                } else {
+                    format!("!!SOUND({} U={})\r\n", sound, url).into_bytes()
+                }
+            },
+            Protocol::Gmcp => {
+                let mut play = json!({ "name": sound, "type": "sound" });
+                if !url.is_empty() {
+                    play["url"] = json!(url);
+                }
+                gmcp("Client.Media.Play", &play)
+            },
+        }
+    }
+}
+
+/// A GMCP packet of a package (eg. "Char.Vitals") carrying JSON data
+pub fn gmcp(package: &str, data: &Value) -> Vec<u8> {
+    let mut packet = vec![IAC, SB, GMCP];
+    packet.extend_from_slice(format!("{} {}", package, data).as_bytes());
+    packet.extend_from_slice(&[IAC, SE]);
+    packet
+}
+
+/// Display a protocol by the name used in the "media" command
+impl fmt::Display for Protocol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Protocol::Msp => write!(f, "msp"),
+            Protocol::Gmcp => write!(f, "gmcp"),
+        }
+    }
+}
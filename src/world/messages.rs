@@ -0,0 +1,112 @@
+//! Messages
+//!
+//! Contains the messages the world sends to the players and how they are
+//! rendered for the different output modes of a session:
+//!  * Text (human readable text for terminals)
+//!  * Robot (line-delimited JSON for the badge firmware and bots)
+
+use std::collections::BTreeMap;
+use serde::Serialize;
+use serde_json::Value;
+use tracing::error;
+
+use crate::connection_manager::Output;
+
+/// Output modes of a player session
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputMode {
+    /// Plain text, as seen on a terminal
+    Text,
+    /// One JSON object per line
+    Robot,
+}
+
+/// Types of events a message can report
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Event {
+    /// Response of the world to an action of the player
+    Response,
+    /// Information about the session or the state of the player
+    Info,
+    /// The player did something the world did not understand
+    Error,
+    /// Text another player sent
+    Chat,
+}
+
+/// A message from the world to a player
+#[derive(Debug, Clone, Serialize)]
+pub struct Message {
+    event: Event,
+    #[serde(serialize_with = "serialize_trimmed")]
+    text: String,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    fields: BTreeMap<String, Value>,
+}
+
+impl Message {
+    /// Create a new message without structured fields
+    pub fn new(event: Event, text: &str) -> Message {
+        Message {
+            event,
+            text: String::from(text),
+            fields: BTreeMap::new(),
+        }
+    }
+
+    /// Add a structured field to the message
+    ///
+    /// Fields are only rendered in robot mode. They carry the information
+    /// that is otherwise hidden in the text.
+    pub fn with_field<T: Serialize>(mut self, key: &str, value: T) -> Message {
+        let value = serde_json::to_value(value).unwrap_or(Value::Null);
+        self.fields.insert(key.to_string(), value);
+        self
+    }
+
+    /// The event the message reports
+    pub fn event(&self) -> Event {
+        self.event
+    }
+
+    /// The text of the message
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Render the message for the given output mode
+    ///
+    /// In text mode the text is terminated by CR/LF. In robot mode the
+    /// message is rendered as a single line of JSON.
+    pub fn render(&self, mode: OutputMode) -> String {
+        let mut buf = Output::new();
+        self.render_into(mode, &mut buf);
+        String::from_utf8(buf).unwrap_or_default()
+    }
+
+    /// Render the message for the given output mode into a buffer
+    ///
+    /// The rendered message is appended to the buffer. Nothing is appended
+    /// if the message cannot be rendered.
+    pub fn render_into(&self, mode: OutputMode, buf: &mut Output) {
+        match mode {
+            OutputMode::Text => buf.extend_from_slice(self.text.as_bytes()),
+            OutputMode::Robot => {
+                let len = buf.len();
+                if let Err(e) = serde_json::to_writer(&mut *buf, self) {
+                    error!("Could not render message as JSON: {}", e);
+                    buf.truncate(len);
+                    return;
+                }
+            },
+        }
+        buf.extend_from_slice(b"\r\n");
+    }
+}
+
+/// Serialize text without trailing whitespace (the line ends are added when
+/// rendering)
+fn serialize_trimmed<S: serde::Serializer>(text: &str, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(text.trim_end())
+}
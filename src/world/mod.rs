@@ -2,161 +2,2791 @@
 //!
 //! This module encapsulates all the information about the game wold and the 
 //! objects (including players) and interactions in it.
+//!
+//! The stores in the data directory (accounts, market, schedule, ...) are
+//! read and written with `tokio::fs`, so the world loop does not block the
+//! runtime while it waits for the disk.
 pub mod states;
+pub mod loader;
 pub mod assets;
 pub mod grammar;
 pub mod errors;
 pub mod properties;
 pub mod actions;
+pub mod messages;
+pub mod reports;
+pub mod accounts;
+pub mod handles;
+pub mod admin;
+pub mod filter;
+pub mod privacy;
+pub mod transcripts;
+pub mod stats;
+pub mod intern;
+pub mod triggers;
+pub mod keys;
+pub mod visitors;
+pub mod banners;
+pub mod media;
+pub mod filesystem;
+pub mod terminals;
+pub mod hooks;
+pub mod puzzles;
+pub mod challenges;
+pub mod news;
+pub mod qr;
+pub mod clock;
+pub mod resets;
+pub mod inspect;
+pub mod edits;
+pub mod acl;
+pub mod npcs;
+pub mod ai;
+pub mod threat;
+pub mod combat;
+pub mod stealth;
+pub mod drones;
+pub mod market;
+pub mod bank;
+pub mod casino;
+pub mod economy;
+pub mod seasons;
+pub mod safety;
+pub mod idle;
+pub mod streaks;
+pub mod appearance;
+pub mod consent;
+pub mod archive;
+pub mod sandboxes;
+pub mod analytics;
+pub mod coverage;
+pub mod adverbs;
+pub mod inventory;
+pub mod fuzzy;
+pub mod verbs;
+pub mod delays;
+pub mod timers;
+pub mod propagation;
+pub mod ownership;
+pub mod security;
+pub mod help;
+pub mod difficulty;
+pub mod checkpoint;
+pub mod bosses;
+pub mod ice;
+pub mod goals;
+pub mod polls;
+pub mod schedule;
 
-use std::collections::HashMap;
-use tokio::sync::mpsc::Receiver;
-use crate::{connection_manager::{Command, DataMessage, ClientId}, world::states::ScreenType};
-
-use thrussh::CryptoVec;
-use tracing::{info, error, instrument, debug, warn};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::time::Instant;
+use crate::{connection_manager::{ClientConnection, Command, DataMessage, ClientId, Output, Push},
+    world::states::ScreenType};
+use crate::connection_manager::channels::{MeteredReceiver, Metrics, SendError};
+use crate::connection_manager::buffers;
+use crate::telemetry::LogFilter;
+use crate::federation;
+use crate::matrix;
+use crate::worlds;
+use crate::connection_manager::keyring::Keyring;
+use crate::connection_manager::tokens::LoginTokens;
+use tracing::{info, error, instrument, debug, warn, info_span, field};
 
 use assets::GameAsset;
 use actions::Action;
+use messages::{Event, Message, OutputMode};
 use std::convert::TryFrom;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use generational_arena::{Arena, Index};
+use rand::Rng;
+
+/// Interval in which the world processes the action queues of the players
+const WORLD_TICK: Duration = Duration::from_millis(100);
+
+/// Maximum number of actions a player can queue
+pub(crate) const MAX_QUEUED_ACTIONS: usize = 16;
+
+/// Number of recent commands remembered per player (eg. for bug reports)
+const HISTORY_LENGTH: usize = 10;
+
+/// Maximum number of players a player may ignore
+const MAX_IGNORED: usize = 100;
 
 /// Run
 /// 
 /// Run the world and accept commands from the connection manager for users to manipulate
-/// the world.
+/// the world. The world saves a checkpoint every autosave interval and stops
+/// once the server shuts down, saving a last one (see `checkpoint`).
 #[instrument]
-pub async fn run(mut command_rx: Receiver<Command>, mut data_rx: Receiver<DataMessage>, world: GameWorld) {
+pub async fn run(mut command_rx: MeteredReceiver<Command>, mut data_rx: MeteredReceiver<DataMessage>, mut world: GameWorld,
+                 mut shutdown: watch::Receiver<bool>) {
     
     let mut players : HashMap<ClientId, Player>= HashMap::new();
+    let mut tick = tokio::time::interval(WORLD_TICK);
+    let period = world.autosave.max(WORLD_TICK);
+    let mut autosave = tokio::time::interval_at(Instant::now() + period, period);
     loop {
         tokio::select! {
+            // Check the branches in order, so the shutdown is handled first,
+            // commands are always processed before data and data before the
+            // tick.
+            biased;
+
+            // The server shuts down. Save the progress and stop.
+            Ok(()) = shutdown.changed() => {
+                save_checkpoint(&mut world, &players).await;
+                finish_checkpoint(&mut world).await;
+                let message = Message::new(Event::Info, "The server is going down. Your progress is saved.");
+                for p in players.values() {
+                    p.send(message.clone()).await;
+                }
+                info!("World {} stopped.", world.name);
+                return;
+            }
+
             // A game command was received. Process the command.
             Some(command) = command_rx.recv() => {
                 debug!("Received command. Processing... (BLOCKING)");
-                process_command(command, &world, &mut players).await;
+                let span = info_span!("process_command", elapsed_us = field::Empty);
+                let (_, elapsed) = stats::timed(span, 
+                    process_command(command, &mut world, &mut players)).await;
+                world.loop_stats.commands.record(elapsed);
+                warn_if_slow("Processing a command", elapsed);
+            }
+
+            // A player performed an interaction with the game world (data command). Process it.
+            Some(data_message) = data_rx.recv() => {
+                debug!("Received data. Processing: {:?} from data_tx of client {}", data_message.data, data_message.client_id);
+                let span = info_span!("process_data", client_id = data_message.client_id, elapsed_us = field::Empty);
+                let (_, elapsed) = stats::timed(span, 
+                    process_data(data_message, &mut world, &mut players)).await;
+                world.loop_stats.data.record(elapsed);
+                warn_if_slow("Processing data", elapsed);
+            }
+
+            // The world ticks. Perform the queued actions of the players,
+            // unless the world is paused.
+            _ = tick.tick() => {
+                if let Some(now) = world.clock.tick(Instant::now(), WORLD_TICK) {
+                    let span = info_span!("process_queues", elapsed_us = field::Empty);
+                    let (_, elapsed) = stats::timed(span, process_queues(&mut world, &mut players, now)).await;
+                    world.loop_stats.ticks.record(elapsed);
+                    warn_if_slow("Processing the action queues", elapsed);
+                }
+                process_maintenance(&mut world, &players, Instant::now()).await;
+                if let Some(summary) = world.analytics.summary(Instant::now()) {
+                    info!("Analytics: {}", summary);
+                }
+                world.heartbeat.beat();
+            }
+
+            _ = autosave.tick(), if !world.autosave.is_zero() => save_checkpoint(&mut world, &players).await,
+            else => {
+                error!("Both channels closed");
+            }
+        }
+    } 
+}
+
+/// Save a checkpoint of the world to its data directory
+///
+/// The checkpoint is taken at once, but serialized and written in the
+/// background, so the clients do not wait for the disk. The players that did
+/// not resume since the last checkpoint keep their nodes.
+async fn save_checkpoint(world: &mut GameWorld, players: &HashMap<ClientId, Player>) {
+    finish_checkpoint(world).await;
+    let carried = players.values().flat_map(|p| p.inventory.items());
+    let located = players.values().filter_map(|p| p.location.map(|l| (p.player_name.as_str(), l)));
+    let mut checkpoint = checkpoint::Checkpoint::take(&world.nodes, carried, located, timestamp());
+    for (player, node) in &world.resumes {
+        checkpoint.players.entry(player.clone()).or_insert(*node);
+    }
+    let (name, data_dir) = (world.name.clone(), world.data_dir.clone());
+    world.checkpointing = Some(tokio::task::spawn_blocking(move || match checkpoint.save(&data_dir) {
+        Ok(()) => info!("Saved a checkpoint of world {} ({} nodes, {} players).", name, checkpoint.nodes.len(),
+            checkpoint.players.len()),
+        Err(e) => error!("Could not save a checkpoint of world {}: {}", name, e),
+    }));
+}
+
+/// Wait until the checkpoint written in the background, if any, is saved
+async fn finish_checkpoint(world: &mut GameWorld) {
+    if let Some(checkpointing) = world.checkpointing.take() {
+        if let Err(e) = checkpointing.await {
+            error!("Could not save a checkpoint of world {}: {}", world.name, e);
+        }
+    }
+}
+
+/// Warn if the world was blocked for longer than a tick
+fn warn_if_slow(what: &str, elapsed: Duration) {
+    if elapsed > WORLD_TICK {
+        warn!("{} blocked the world for {}ms.", what, elapsed.as_millis());
+    }
+}
+
+/// Handle commands
+/// 
+/// This function processes commands to the game engine. Commands are usually
+/// issued by a client.
+async fn process_command(command: Command, world: &mut GameWorld, players : &mut HashMap<ClientId, Player>) {
+    match command {
+        // Register a new player to the game
+        Command::Register(client_id, username, connection) => {
+            // Staff may use any name, everybody else must follow the rules for
+            // handles. A client gets the role of its handle only if it proved
+            // the handle with its key.
+            let role = if connection.info().verified { world.role_of(&username) } else { Role::Player };
+            if role == Role::Player && world.maintenance {
+                info!("Turning away player {} during maintenance.", username);
+                let mut message = world.screens.get(ScreenType::Maintenance)
+                    .map(|s| s.final_frame().to_vec())
+                    .unwrap_or_default();
+                message.extend(world.maintenance_notice(Instant::now()).into_bytes());
+                if connection.send(Push::Data(buffers::from_slice(&message))).await.is_err()
+                    || connection.close().await.is_err() {
+                    error!("Could not turn away player {}.", username);
+                }
+                return;
+            }
+            if role == Role::Player {
+                if let Err(e) = world.handle_rules.validate_handle(&username) {
+                    info!("Rejecting player with invalid handle {}: {}", username, e);
+                    let message = format!("The matrix does not accept \"{}\" as your handle ({}). \
+                        Handles are {} to {} letters, digits, dashes or underscores, starting with a letter.\r\n", 
+                        username, e, world.handle_rules.min_length, world.handle_rules.max_length);
+                    if connection.send(Push::Data(buffers::from_slice(message.as_bytes()))).await.is_err()
+                        || connection.close().await.is_err() {
+                        error!("Could not reject player with invalid handle {}.", username);
+                    }
+                    return;
+                }
+            }
+            // A handle plays one session at a time, two sessions would save
+            // the same account over each other (eg. duplicating credits).
+            // The new session replaces the old one (eg. after a dropped
+            // connection) if it proved the handle or logged in as the same
+            // account. Anybody else is refused, or typing the handle in
+            // another case would kick the player.
+            let replaced = players.iter()
+                .find(|(_, p)| p.player_name.eq_ignore_ascii_case(&username))
+                .map(|(id, p)| (*id, p.player_name == username));
+            if replaced.is_some_and(|(_, same)| !same) && !connection.info().verified {
+                info!("Refusing player {}, the handle is in use.", username);
+                let message = format!("The handle \"{}\" is in use. Connect with the key of the player or pick \
+                    another handle.\r\n", username);
+                if connection.send(Push::Data(buffers::from_slice(message.as_bytes()))).await.is_err()
+                    || connection.close().await.is_err() {
+                    error!("Could not refuse player {}.", username);
+                }
+                return;
+            }
+            if let Some((old, _)) = replaced {
+                info!("Player {} jacked in again, replacing the session of client {}.", username, old);
+                if let Some(p) = players.get(&old) {
+                    p.send(Message::new(Event::Info, "You jacked in from another session.")).await;
+                }
+                hang_up(old, world, players).await;
+            }
+            info!("Player {} jacks in by {}.", username, connection.info().transport);
+            let mut player = Player::new(username, connection);
+            player.role = role;
+            player.idle = idle::Idle::new(world.clock.now(Instant::now()));
+            player.account = match accounts::Account::load(&world.data_dir, &player.player_name).await {
+                Ok(a) => a,
+                Err(e) => {
+                    error!("Could not load account of {}: {}", player.player_name, e);
+                    accounts::Account::new(&player.player_name)
+                },
+            };
+            let traveler = world.arrivals.arrive(&player.player_name, Instant::now());
+            if let Some(traveler) = &traveler {
+                arrive(&mut player.account, traveler);
+                if let Err(e) = player.account.save(&world.data_dir).await {
+                    error!("Could not save account of {}: {}", player.player_name, e);
+                }
+            }
+            match world.spawn(&mut player).map(|i| resume(world, &mut player).unwrap_or(i)) {
+                Ok(index) => {
+                    // Display the welcome screen. If the screen could not be loaded at
+                    // startup, nothing is sent to the client.
+                    if let Some(screen) = world.screens.get(ScreenType::Welcome) {
+                        player.show(screen).await;
+                    }
+                    if let Some(node) = world.nodes.get(index) {
+                        player.session.explored.insert(node.uid());
+                    }
+
+                    // Count the player as visitor of the event and tell it how
+                    // many deckers jacked in so far
+                    let first_visit = match world.visitors.visit(&world.data_dir, &player.player_name).await {
+                        Ok(first) => first,
+                        Err(e) => {
+                            error!("Could not record visit of {}: {}", player.player_name, e);
+                            false
+                        },
+                    };
+                    if let Some(screen) = world.screens.get(ScreenType::Login) {
+                        player.show(screen).await;
+                    }
+                    if let Some(title) = world.zone_title(index) {
+                        player.push(buffers::from_slice(title.as_bytes())).await;
+                    }
+                    let visitors = world.visitors.count();
+                    let message = if first_visit {
+                        format!("Welcome, {}. You are decker #{} to jack in at the con.", player.player_name, visitors)
+                    } else {
+                        format!("Welcome back, {}. {} deckers have jacked in at the con so far.", 
+                            player.player_name, visitors)
+                    };
+                    player.send(Message::new(Event::Info, &message)
+                        .with_field("visitors", visitors)
+                        .with_field("first_visit", first_visit)).await;
+                    if let (Some(_), Some(link)) = (traveler, &world.federation) {
+                        let message = format!("You arrive from {}. Your title, score and quest flags came \
+                            with you.", link.peer_name);
+                        player.send(Message::new(Event::Info, &message)
+                            .with_field("from_world", link.peer_name.clone())).await;
+                    }
+                    let mail = world.market.mail_count(&player.player_name);
+                    if mail > 0 {
+                        let message = format!("You have mail ({} waiting). Collect it with \"mail\".", mail);
+                        player.send(Message::new(Event::Info, &message).with_field("mail", mail)).await;
+                    }
+
+                    // The first login of a day continues the streak of the
+                    // player and grants the rewards of the day
+                    if let Some(day) = player.account.streak.login(timestamp()) {
+                        info!("Player {} logged in on day {} of its streak.", player.player_name, day);
+                        let message = format!("Day {} of your login streak (best {}).", day, 
+                            player.account.streak.best);
+                        player.send(Message::new(Event::Info, &message).with_field("streak", day)).await;
+                        for hook in world.rewards.for_day(day) {
+                            run_hook(hook, world, &mut player).await;
+                        }
+                        save_account(world, &player).await;
+                        if world.rewards.is_milestone(day) {
+                            let message = Message::new(Event::Info, 
+                                    &format!("{} jacked in {} days in a row!", player.player_name, day))
+                                .with_field("player", player.player_name.clone())
+                                .with_field("streak", day);
+                            for p in players.values() {
+                                p.send(message.clone()).await;
+                            }
+                        }
+                    }
+
+                    players.insert(client_id, player);
+                },
+                Err(e) => {
+                    warn!("Could not spawn player {}: {}", player.player_name, e);
+                    player.send(Message::new(Event::Error, 
+                        "The matrix has no node you may enter. Ask the staff for help.")).await;
+                    if player.connection.close().await.is_err() {
+                        debug!("Client of player {} is already gone.", player.player_name);
+                    }
+                },
+            };
+        },
+        Command::Hangup(client_id) => hang_up(client_id, world, players).await,
+        Command::Admin(data_message) => {
+            if let Some(player) = players.get(&data_message.client_id) {
+                if let Some(transcript) = &player.transcript {
+                    if let Err(e) = transcript.record_input(&String::from_utf8_lossy(&data_message.data)).await {
+                        error!("Could not write transcript of {}: {}", player.player_name, e);
+                    }
+                }
+            }
+            process_admin_command(data_message, world, players).await;
+        },
+        Command::Federation(incoming) => process_federation(incoming, world, players).await,
+        Command::Matrix(message) => process_matrix(message, world, players).await,
+        Command::Arrival(traveler) => {
+            info!("{} moves here from another world.", traveler.name);
+            world.arrivals.expect(traveler, Instant::now());
+        },
+    };
+}
+
+/// End the session of a player
+///
+/// The player hung up or quit. It leaves the world: its account is saved,
+/// the others in its node see it jack out (unless it is cloaked) and its
+/// client is closed after the goodbye screen.
+async fn hang_up(client_id: ClientId, world: &mut GameWorld, players: &mut HashMap<ClientId, Player>) {
+    world.challenges.leave(client_id);
+    world.delays.cancel(client_id);
+    let mut player = match players.remove(&client_id) {
+        Some(p) => p,
+        None => return,
+    };
+    info!("Player {} hung up: {}.", player.player_name, player.session);
+    return_items(world, &mut player);
+    world.sandboxes.close(&mut world.nodes, &player.player_name);
+    player.account.activity.record(&player.session);
+    if let Err(e) = player.account.save(&world.data_dir).await {
+        error!("Could not save account of {}: {}", player.player_name, e);
+    }
+    if let (Some(location), None) = (player.location, &player.cloak) {
+        let message = format!("{} jacks out.", player.display_name());
+        send_to_node(players, location, Message::new(Event::Info, &message)).await;
+    }
+    if let Some(screen) = world.screens.get(ScreenType::Goodbye) {
+        player.show(screen).await;
+    }
+    let session = &player.session;
+    player.send(Message::new(Event::Info, &format!("Session: {}.", session))
+        .with_field("played_s", session.played().as_secs())
+        .with_field("explored", session.explored.len())
+        .with_field("actions", session.actions)).await;
+    if player.connection.close().await.is_err() {
+        debug!("Client of player {} is already gone.", player.player_name);
+    }
+}
+
+/// Handle news from the link to the peer world
+async fn process_federation(incoming: federation::Incoming, world: &mut GameWorld, 
+                            players: &mut HashMap<ClientId, Player>) {
+    let link = match &mut world.federation {
+        Some(l) => l,
+        None => return,
+    };
+    match incoming {
+        federation::Incoming::Linked { world: name, ssh } => {
+            info!("The world is linked to {}.", name);
+            link.peer_ssh = Some(ssh);
+        },
+        federation::Incoming::Unlinked => {
+            info!("The world is no longer linked to {}.", link.peer_name);
+            link.peer_ssh = None;
+        },
+        federation::Incoming::Received(federation::Message::Transfer { mut traveler }) => {
+            // The peer is trusted with the state of its players, but not with
+            // what the players of this world see
+            let rules = &world.handle_rules;
+            traveler.title = traveler.title.map(|t| matrix::sanitize(&t)).filter(|t| rules.validate_title(t).is_ok());
+            info!("{} travels here from {}.", traveler.name, link.peer_name);
+            world.arrivals.expect(traveler, Instant::now());
+        },
+        federation::Incoming::Received(federation::Message::Tell { from, to, text }) => {
+            match players.values().find(|p| p.player_name.eq_ignore_ascii_case(&to)) {
+                Some(p) => {
+                    let text = matrix::sanitize(&text);
+                    let text = if p.account.unfiltered { text } else { world.content_filter.apply(&text) };
+                    let from = format!("{}@{}", matrix::sanitize(&from), link.peer_name);
+                    let stranger = consent::Interaction::Tell { stranger: true };
+                    if p.account.ignores(&from) || !p.account.consent.allows(stranger) {
+                        debug!("{} does not see the tell of {}.", p.player_name, from);
+                        return;
+                    }
+                    p.send(Message::new(Event::Chat, &format!("{} tells you: {}", from, text))
+                        .with_field("from", from)).await;
+                },
+                None => {
+                    if !link.send(federation::Message::Undeliverable { from, to }) {
+                        warn!("Could not tell {} that a tell was undeliverable.", link.peer_name);
+                    }
+                },
+            }
+        },
+        federation::Incoming::Received(federation::Message::Undeliverable { from, to }) => {
+            if let Some(p) = players.values().find(|p| p.player_name.eq_ignore_ascii_case(&from)) {
+                let message = format!("{}@{} is not jacked in.", matrix::sanitize(&to), link.peer_name);
+                p.send(Message::new(Event::Error, &message)).await;
+            }
+        },
+        federation::Incoming::Received(message) => {
+            debug!("Ignoring unexpected message from {}: {:?}", link.peer_name, message);
+        },
+    }
+}
+
+/// Show a message written in the Matrix room in the mirrored zones
+async fn process_matrix(message: matrix::Incoming, world: &GameWorld, players: &HashMap<ClientId, Player>) {
+    let bridge = match &world.matrix {
+        Some(b) => b,
+        None => return,
+    };
+    let mirrored = |p: &&Player| p.location
+        .and_then(|l| world.nodes.get(l))
+        .is_some_and(|n| bridge.mirrors(n.name()));
+    for player in players.values().filter(mirrored) {
+        let text = if player.account.unfiltered { message.body.clone() } else { world.content_filter.apply(&message.body) };
+        player.send_with_triggers(Message::new(Event::Chat, &format!("[Matrix] {}: {}", message.author(), text))
+            .with_field("from", message.sender.clone())
+            .with_field("matrix", true), false).await;
+    }
+}
+
+/// Apply the state a player brought from the peer world
+/// 
+/// The player keeps its own title, if it has one. The score is the higher
+/// one of both worlds.
+fn arrive(account: &mut accounts::Account, traveler: &federation::Traveler) {
+    if account.title.is_none() {
+        account.title = traveler.title.clone();
+    }
+    account.score = account.score.max(traveler.score);
+    account.flags.extend(traveler.flags.iter().cloned());
+}
+
+/// Handle data messages
+/// 
+/// A data message usually is a player action. This function tries to decode
+/// the data message and then queues the action for the player. Queued actions
+/// are performed on the world tick (see `process_queues`). Actions that 
+/// concern the session rather than the world (eg. "stop" which flushes the 
+/// queue of the player) are handled immediately.
+async fn process_data(data_message: DataMessage, world: &mut GameWorld, players: &mut HashMap<ClientId, Player>) {
+    // Check if the data message can be matched on an active player. If no
+    // active player is known then the data message gets discarded.
+    let player_info = match players.get_mut(&data_message.client_id) {
+        Some(p) => p,
+        None => {
+            error!("Received data message but no active player found for the client that sent the message.");
+            return;
+        },
+    };
+
+    player_info.session.commands += 1;
+
+    // A returning player hears what its deck did while it was away
+    if let Some(report) = player_info.idle.input(world.clock.now(Instant::now())) {
+        player_info.send(Message::new(Event::Info, &report.to_string())
+            .with_field("idle_cycles", report.cycles)
+            .with_field("idle_credits", report.credits)).await;
+    }
+
+    // A key (eg. a button of the badge) runs the command bound to it
+    let data = match keys::name_of(&data_message.data) {
+        Some(key) => match player_info.account.keymap.get(&key) {
+            Some(command) => command.clone().into_bytes(),
+            None => {
+                let message = format!("Nothing is bound to {}. Use \"bind {} <command>\" to bind a command.", key, key);
+                player_info.send(Message::new(Event::Error, &message)
+                    .with_field("key", key)).await;
+                return;
+            },
+        },
+        None => data_message.data,
+    };
+
+    if let Some(transcript) = &player_info.transcript {
+        if let Err(e) = transcript.record_input(&String::from_utf8_lossy(&data)).await {
+            error!("Could not write transcript of {}: {}", player_info.player_name, e);
+        }
+    }
+
+    // A player that accessed a terminal talks to its shell
+    if let Some(shell) = player_info.shell.take() {
+        let line = String::from_utf8_lossy(&data).to_string();
+        run_shell(shell, &line, world, player_info).await;
+        player_info.remember(line);
+        return;
+    }
+
+    // Notification for the admins, sent once we are done with the player
+    let mut admin_notification = None;
+
+    // Check if the player did a proper action
+    match Action::try_from(data.clone()) {
+        Ok(Action::Stop) => {
+            // The action in progress is cancelled as well as the queued ones
+            let delayed = world.delays.cancel(data_message.client_id).is_some();
+            let cancelled = player_info.queue.len() + usize::from(delayed);
+            let message = if cancelled == 0 {
+                "You are not doing anything.".to_string()
+            } else {
+                format!("You stop what you are doing ({} pending actions cancelled).", cancelled)
+            };
+            player_info.queue.clear();
+            player_info.send(Message::new(Event::Info, &message)
+                .with_field("cancelled", cancelled)).await;
+        },
+        Ok(Action::Robot(enabled)) => {
+            // Switching the output mode is a session setting and
+            // takes effect immediately.
+            let enabled = enabled.unwrap_or(player_info.output_mode != OutputMode::Robot);
+            player_info.output_mode = if enabled { OutputMode::Robot } else { OutputMode::Text };
+            let message = if enabled { "Robot mode on." } else { "Robot mode off." };
+            player_info.send(Message::new(Event::Info, message)
+                .with_field("robot", enabled)).await;
+        },
+        Ok(Action::Bug(description)) => {
+            admin_notification = reports::file(&description, world, player_info).await;
+        },
+        Ok(Action::Note(text)) => accounts::note(&text, world, player_info).await,
+        Ok(Action::Journal(search)) => accounts::journal(search.as_deref(), player_info).await,
+        Ok(Action::Filter(enabled)) => {
+            player_info.account.unfiltered = !enabled;
+            if let Err(e) = player_info.account.save(&world.data_dir).await {
+                error!("Could not save account of {}: {}", player_info.player_name, e);
+            }
+            let message = if enabled {
+                "Text from other players is filtered for you."
+            } else {
+                "Text from other players is no longer filtered for you."
+            };
+            player_info.send(Message::new(Event::Info, message)
+                .with_field("filter", enabled)).await;
+        },
+        Ok(Action::Animations(enabled)) => {
+            let enabled = enabled.unwrap_or(player_info.account.still_screens);
+            player_info.account.still_screens = !enabled;
+            if let Err(e) = player_info.account.save(&world.data_dir).await {
+                error!("Could not save account of {}: {}", player_info.player_name, e);
+            }
+            let message = if enabled {
+                "Screens are animated for you."
+            } else {
+                "Screens are no longer animated for you."
+            };
+            player_info.send(Message::new(Event::Info, message)
+                .with_field("animations", enabled)).await;
+        },
+        Ok(Action::Media(protocol)) => {
+            // The client tells which protocol it plays sounds with
+            player_info.media = protocol;
+            let message = match protocol {
+                Some(p) => format!("Sounds are sent to your client ({}).", p),
+                None => "No sounds are sent to your client.".to_string(),
+            };
+            player_info.send(Message::new(Event::Info, &message)
+                .with_field("media", protocol.map(|p| p.to_string()))).await;
+        },
+        Ok(Action::Mute(muted)) => {
+            let muted = muted.unwrap_or(!player_info.account.muted);
+            player_info.account.muted = muted;
+            if let Err(e) = player_info.account.save(&world.data_dir).await {
+                error!("Could not save account of {}: {}", player_info.player_name, e);
+            }
+            let message = if muted { "Sounds are muted." } else { "Sounds are no longer muted." };
+            player_info.send(Message::new(Event::Info, message)
+                .with_field("muted", muted)).await;
+        },
+        Ok(Action::Combat(None)) => {
+            let verbosity = player_info.account.combat;
+            player_info.send(Message::new(Event::Info, &format!("Combat output is {}.", verbosity))
+                .with_field("combat", verbosity)).await;
+        },
+        Ok(Action::Combat(Some(verbosity))) => {
+            player_info.account.combat = verbosity;
+            if let Err(e) = player_info.account.save(&world.data_dir).await {
+                error!("Could not save account of {}: {}", player_info.player_name, e);
+            }
+            player_info.send(Message::new(Event::Info, &format!("Combat output is now {}.", verbosity))
+                .with_field("combat", verbosity)).await;
+        },
+        Ok(Action::Watch(zone)) => {
+            let message = match zone {
+                None => {
+                    player_info.watching = None;
+                    Message::new(Event::Info, "You stop watching.")
+                },
+                Some(name) => match world.find_zone(&name) {
+                    Some(index) if world.spectated.contains(&index) => {
+                        player_info.watching = Some(index);
+                        let name = world.nodes.get(index).map(|n| n.name()).unwrap_or_default();
+                        Message::new(Event::Info, &format!("You are watching {}. Use \"watch\" to stop.", name))
+                            .with_field("zone", name)
+                    },
+                    _ => Message::new(Event::Error, &format!("{} cannot be watched.", name)),
+                },
+            };
+            player_info.send(message).await;
+        },
+        Ok(Action::Transcript(enabled)) => transcripts::record(enabled, world, player_info).await,
+        Ok(Action::Trigger(trigger)) => triggers::define(trigger, world, player_info).await,
+        Ok(Action::Untrigger(number)) => triggers::remove(number, world, player_info).await,
+        Ok(Action::Ignore(None)) => {
+            let ignored: Vec<&String> = player_info.account.ignored.iter().collect();
+            let message = if ignored.is_empty() {
+                "You ignore nobody. Use \"ignore <player>\" to stop seeing what a player says and tells you."
+                    .to_string()
+            } else {
+                format!("You ignore: {}", ignored.iter().map(|s| s.as_str()).collect::<Vec<_>>().join(", "))
+            };
+            player_info.send(Message::new(Event::Info, &message).with_field("ignored", ignored)).await;
+        },
+        Ok(Action::Ignore(Some(name))) => {
+            let account = &mut player_info.account;
+            let message = if name.eq_ignore_ascii_case(&player_info.player_name) {
+                "You cannot ignore yourself.".to_string()
+            } else if !account.ignores(&name) && account.ignored.len() >= MAX_IGNORED {
+                format!("You cannot ignore more than {} players. Use \"unignore\" to remove one.", MAX_IGNORED)
+            } else {
+                account.ignored.insert(name.to_lowercase());
+                format!("You no longer see what {} says and tells you.", name)
+            };
+            if let Err(e) = player_info.account.save(&world.data_dir).await {
+                error!("Could not save account of {}: {}", player_info.player_name, e);
+            }
+            player_info.send(Message::new(Event::Info, &message)
+                .with_field("ignored", player_info.account.ignored.len())).await;
+        },
+        Ok(Action::Unignore(name)) => {
+            let message = if player_info.account.ignored.remove(&name.to_lowercase()) {
+                format!("You see what {} says and tells you again.", name)
+            } else {
+                format!("You do not ignore {}.", name)
+            };
+            if let Err(e) = player_info.account.save(&world.data_dir).await {
+                error!("Could not save account of {}: {}", player_info.player_name, e);
+            }
+            player_info.send(Message::new(Event::Info, &message)
+                .with_field("ignored", player_info.account.ignored.len())).await;
+        },
+        Ok(Action::Prefs(None)) => {
+            player_info.send(Message::new(Event::Info, &player_info.account.consent.to_string())
+                .with_field("consent", player_info.account.consent)).await;
+        },
+        Ok(Action::Prefs(Some((pref, on)))) => {
+            player_info.account.consent.set(pref, on);
+            if let Err(e) = player_info.account.save(&world.data_dir).await {
+                error!("Could not save account of {}: {}", player_info.player_name, e);
+            }
+            let message = format!("You turned {} {}.", pref, if on { "on" } else { "off" });
+            player_info.send(Message::new(Event::Info, &message)
+                .with_field("consent", player_info.account.consent)).await;
+        },
+        Ok(Action::Score) => {
+            let account = &player_info.account;
+            let level = safety::level(account.score);
+            let activity = account.activity.with(&player_info.session);
+            let message = format!("Score: {} points, level {} ({} points to level {}).\r\n\
+                Played {} in {} sessions, {} commands sent, {} nodes visited.\r\n\
+                Login streak: {} days, best {}.", account.score, level,
+                level * safety::LEVEL_POINTS - account.score, level + 1, stats::duration(activity.played_s),
+                activity.sessions, activity.commands, activity.nodes.len(), account.streak.days, account.streak.best);
+            player_info.send(Message::new(Event::Info, &message)
+                .with_field("score", account.score)
+                .with_field("level", level)
+                .with_field("activity", activity)).await;
+        },
+        Ok(Action::Goals) => {
+            let flags: Vec<&str> = world.goals.reached().map(|g| g.flag()).collect();
+            player_info.send(Message::new(Event::Info, &world.goals.describe()).with_field("flags", flags)).await;
+        },
+        Ok(Action::Help { topic: None, .. }) => {
+            player_info.send(Message::new(Event::Info, &world.help.index())).await;
+        },
+        Ok(Action::Help { topic: Some(topic), page }) => {
+            match world.help.page(&topic, page.unwrap_or(1)) {
+                Ok(text) => player_info.send(Message::new(Event::Info, &text)).await,
+                Err(e) => player_info.send(Message::new(Event::Error, &e)).await,
+            }
+        },
+        Ok(Action::Inventory) => {
+            let carried = player_info.inventory.names();
+            let owned = &player_info.account.items;
+            let mut text = if carried.is_empty() && owned.is_empty() {
+                "You carry nothing. Eg. \"take quickhack\" picks up a quickhack lying in the node.".to_string()
+            } else {
+                format!("You carry ({} of {} picked up):", carried.len(), inventory::CAPACITY)
+            };
+            for name in carried.iter().copied().chain(owned.iter().map(String::as_str)) {
+                text += format!("\r\n  {}", name).as_str();
+            }
+            player_info.send(Message::new(Event::Info, &text)
+                .with_field("carried", &carried)
+                .with_field("items", owned)).await;
+        },
+        Ok(Action::Bind { key, command }) => keys::bind(key, command, world, player_info).await,
+        Ok(Action::Title(title)) => handles::title(title, world, player_info).await,
+        Ok(Action::Describe(description)) => {
+            appearance::describe(description, world, data_message.client_id, player_info).await
+        },
+        Ok(a) => {
+            if player_info.queue.len() >= MAX_QUEUED_ACTIONS {
+                debug!("Action queue of player {} is full. Discarding action {}.", player_info.player_name, a);
+                let message = "You cannot plan that far ahead. Use \"stop\" to cancel pending actions.";
+                player_info.send(Message::new(Event::Error, message)
+                    .with_field("queued", player_info.queue.len())).await;
+            } else {
+                debug!("Player {} queued action {}.", player_info.player_name, a);
+                player_info.queue.push_back(QueuedAction { action: a, triggered: false });
+            }
+        },
+        Err(e) => {
+            // Not a valid aciton, tell the player
+            debug!("User used unkown command: {}", e);
+            world.analytics.misunderstood(&e);
+            let message = "Error 23: Command not found.";
+            player_info.send(Message::new(Event::Error, message)
+                .with_field("code", 23)).await;
+        },
+    }
+
+    // Remember the command for bug reports
+    player_info.remember(String::from_utf8_lossy(&data).to_string());
+
+    if let Some(message) = admin_notification {
+        for admin in players.values().filter(|p| p.role == Role::Admin) {
+            admin.send(message.clone()).await;
+        }
+    }
+}
+
+/// Run a command line in the shell of a player
+/// 
+/// The shell is given back to the player, unless the player left it or the
+/// terminal is gone.
+async fn run_shell(mut shell: terminals::Shell, line: &str, world: &GameWorld, player: &mut Player) {
+    let terminal = match world.nodes.get(shell.node).and_then(|n| n.find_terminal(shell.terminal)) {
+        Some(t) => t,
+        None => {
+            player.send(Message::new(Event::Error, "The terminal goes dark. You are disconnected.")).await;
+            return;
+        },
+    };
+    let (output, hook) = match shell.run(terminal, line) {
+        terminals::Reply::Exit => {
+            let message = format!("Connection to {} closed.", terminal.hostname());
+            player.send(Message::new(Event::Info, &message)
+                .with_field("terminal", Option::<String>::None)).await;
+            return;
+        },
+        terminals::Reply::Output(output) => (output, None),
+        terminals::Reply::Hooked(output, hook) => (output, Some(hook)),
+    };
+
+    // Robots get every reply, so they learn the working directory
+    if !output.is_empty() || player.output_mode == OutputMode::Robot {
+        player.send(Message::new(Event::Response, &output)
+            .with_field("terminal", terminal.hostname())
+            .with_field("cwd", shell.cwd())).await;
+    }
+    if let Some(hook) = hook {
+        run_hook(&hook, world, player).await;
+    }
+    player.prompt(&shell.prompt(terminal, &player.player_name)).await;
+    player.shell = Some(shell);
+}
+
+/// Run a hook for a player (eg. of a file the player read)
+/// 
+/// Hooks only change the account of the player the first time they run.
+/// In a sandbox, they do not run at all.
+async fn run_hook(hook: &hooks::Hook, world: &GameWorld, player: &mut Player) {
+    if world.sandboxes.contains(player.location) {
+        let text = format!("Sandbox: the hook {:?} would run now.", hook);
+        player.send(Message::new(Event::Info, &text)).await;
+        return;
+    }
+    let message = match hook {
+        hooks::Hook::Note(text) => {
+            if player.account.notes.iter().any(|n| &n.text == text) {
+                return;
+            }
+            player.account.add_note(text);
+            Some(Message::new(Event::Info, &format!("You note in your journal: {}", text))
+                .with_field("note", player.account.notes.len()))
+        },
+        hooks::Hook::Flag(flag) => {
+            if !player.account.flags.insert(flag.clone()) {
+                return;
+            }
+            info!("Player {} reached {}.", player.player_name, flag);
+            None
+        },
+        hooks::Hook::Drone(model) => {
+            if player.account.drone.is_some() {
+                return;
+            }
+            player.account.drone = Some(drones::Drone::new(model));
+            Some(Message::new(Event::Info, &format!("You acquire a {} drone. Launch it with \"drone deploy\".", model))
+                .with_field("drone", model))
+        },
+        hooks::Hook::Credits(credits) => {
+            player.account.credits += credits;
+            world.ledger.create("rewards", *credits);
+            Some(Message::new(Event::Info, &format!("You receive {} credits.", credits))
+                .with_field("credits", player.account.credits))
+        },
+        hooks::Hook::Item(item) => {
+            player.account.items.push(item.clone());
+            Some(Message::new(Event::Info, &format!("You obtain {}.", item)).with_field("item", item))
+        },
+        hooks::Hook::Reputation { faction, amount } => {
+            let reputation = player.account.reputation.entry(faction.clone()).or_default();
+            *reputation += amount;
+            let change = if *amount >= 0 { "rises" } else { "drops" };
+            Some(Message::new(Event::Info, &format!("Your reputation with the {} {} to {}.", faction, change, 
+                    reputation))
+                .with_field("faction", faction)
+                .with_field("reputation", *reputation))
+        },
+        hooks::Hook::Title(title) => {
+            if player.account.title.as_ref() == Some(title) {
+                return;
+            }
+            player.account.title = Some(title.clone());
+            Some(Message::new(Event::Info, &format!("You earn the title \"{}\".", title)).with_field("title", title))
+        },
+        hooks::Hook::Timer { after, effect } => {
+            let id = world.timers.start(*after, effect.clone(), timestamp());
+            debug!("Player {} started timer {} ({:?}).", player.player_name, id, effect);
+            if let Err(e) = world.timers.save(&world.data_dir).await {
+                error!("Could not save the timers: {}", e);
+            }
+            return;
+        },
+    };
+    if let Err(e) = player.account.save(&world.data_dir).await {
+        error!("Could not save account of {}: {}", player.player_name, e);
+    }
+    if let Some(message) = message {
+        player.send(message).await;
+    }
+}
+
+/// Access an asset
+/// 
+/// Accessing a terminal switches the session of the player into the shell
+/// of the terminal.
+async fn access(target: Option<&str>, ordinal: Option<usize>, node: &assets::Node, location: Index, 
+                client_id: ClientId, players: &mut HashMap<ClientId, Player>) {
+    let player = match players.get_mut(&client_id) {
+        Some(p) => p,
+        None => return,
+    };
+    let reason = match node.find_asset(target.unwrap_or("terminal"), ordinal) {
+        Ok(asset) => match asset.terminal() {
+            Some(terminal) => {
+                player.touched.push(asset.uid());
+                let shell = terminals::Shell::new(location, terminal.uid());
+                let message = format!("You jack into {}. Type \"help\" for the commands, \"exit\" to jack out.", 
+                    terminal.hostname());
+                player.send(Message::new(Event::Response, &message)
+                    .with_field("terminal", terminal.hostname())
+                    .with_field("cwd", shell.cwd())).await;
+                player.prompt(&shell.prompt(terminal, &player.player_name)).await;
+                player.shell = Some(shell);
+                return;
+            },
+            None => format!("You cannot access the {}.", asset.name()),
+        },
+        Err(reason) => reason,
+    };
+    player.send(Message::new(Event::Error, &reason)).await;
+}
+
+/// Solve the puzzle on an asset or buy a hint for it
+/// 
+/// The progress of the player is saved in the account. Solving the puzzle
+/// adds its points to the score of the player and runs its hooks. In a
+/// sandbox, the answer is only checked.
+async fn puzzle(a: &Action, asset: &dyn GameAsset, world: &GameWorld, player: &mut Player) {
+    let puzzle = match asset.puzzle() {
+        Some(p) => p,
+        None => {
+            let reason = format!("There is no puzzle on the {}.", asset.name());
+            player.send(Message::new(Event::Error, &reason)).await;
+            return;
+        },
+    };
+    if !world.seasons.allows(puzzle.season(), timestamp()) {
+        player.send(Message::new(Event::Error, "The puzzle is dormant. Its season is not running.")).await;
+        return;
+    }
+    player.touched.push(asset.uid());
+    if world.sandboxes.contains(player.location) {
+        let message = match a {
+            Action::Solve { answer, .. } if puzzle.check(answer) => {
+                Message::new(Event::Response, "Sandbox: the answer is right. Nothing is recorded.")
+            },
+            Action::Solve { .. } => Message::new(Event::Response, "Sandbox: the answer is wrong. Nothing is recorded."),
+            _ => Message::new(Event::Error, "Sandbox: there are no hints in a sandbox."),
+        };
+        player.send(message.with_field("puzzle", puzzle.id())).await;
+        return;
+    }
+    let progress = player.account.puzzles.entry(puzzle.id().to_string()).or_default();
+    let mut solved = false;
+    let message = match a {
+        Action::Solve { answer, .. } => match puzzle.attempt(progress, answer) {
+            puzzles::Outcome::Solved { points } => {
+                player.account.score += u64::from(points);
+                player.solved_puzzles.push(puzzle.id().to_string());
+                solved = true;
+                // Cracking a port is a crime in secured zones
+                if asset.port().is_some() {
+                    player.crimes.push(security::Crime::Cracking);
+                }
+                info!("Player {} solved puzzle {}.", player.player_name, puzzle.id());
+                Message::new(Event::Response, &format!("Solved! You earn {} points.", points))
+                    .with_field("score", player.account.score)
+            },
+            puzzles::Outcome::Wrong { attempts_left: Some(n) } => {
+                Message::new(Event::Response, &format!("That is not right. {} attempts left.", n))
+            },
+            puzzles::Outcome::Wrong { attempts_left: None } => Message::new(Event::Response, "That is not right."),
+            puzzles::Outcome::Locked => {
+                Message::new(Event::Error, "The puzzle is locked. You used up all your attempts.")
+            },
+            // Solving the puzzle again still counts for challenge runs
+            puzzles::Outcome::AlreadySolved if puzzle.check(answer) => {
+                player.solved_puzzles.push(puzzle.id().to_string());
+                Message::new(Event::Response, "Solved again! You earned the points for this puzzle before.")
+            },
+            puzzles::Outcome::AlreadySolved => Message::new(Event::Error, "You already solved this puzzle."),
+        },
+        _ => match (progress.solved, puzzle.hint(progress)) {
+            (true, _) => Message::new(Event::Error, "You already solved this puzzle."),
+            (false, Some(hint)) => {
+                Message::new(Event::Response, &format!("Hint (costs {} points): {}", hint.cost, hint.text))
+            },
+            (false, None) => Message::new(Event::Error, "There are no more hints."),
+        },
+    };
+    if let Err(e) = player.account.save(&world.data_dir).await {
+        error!("Could not save account of {}: {}", player.player_name, e);
+    }
+    player.send(message.with_field("puzzle", puzzle.id())).await;
+    if solved {
+        for hook in puzzle.hooks() {
+            run_hook(hook, world, player).await;
+        }
+    }
+}
+
+/// Handle admin commands
+/// 
+/// Admin commands are only executed for players with the admin role (or 
+/// the builder role for the commands for builders). They are not queued but
+/// executed immediately.
+async fn process_admin_command(data_message: DataMessage, world: &mut GameWorld, players: &mut HashMap<ClientId, Player>) {
+    let admin = match players.get(&data_message.client_id) {
+        Some(p) => p,
+        None => return,
+    };
+    if admin.role == Role::Player {
+        info!("Player {} tried to use an admin command.", admin.player_name);
+        admin.send(Message::new(Event::Error, "Error 23: Command not found.")
+            .with_field("code", 23)).await;
+        return;
+    }
+    let admin_name = admin.player_name.clone();
+
+    let command = match std::str::from_utf8(&data_message.data).map(admin::AdminCommand::try_from) {
+        Ok(Ok(c)) => c,
+        _ => {
+            admin.send(Message::new(Event::Error, "Unknown admin command or invalid arguments.")).await;
+            return;
+        },
+    };
+    if admin.role == Role::Builder && !command.is_for_builders() {
+        info!("Builder {} tried to use {}.", admin_name, command);
+        admin.send(Message::new(Event::Error, "This command is reserved to admins.")).await;
+        return;
+    }
+    info!("Admin {} issued {}.", admin_name, command);
+
+    let reply = match command {
+        admin::AdminCommand::Title { player, title } => handles::grant(&player, title, world, players).await,
+        admin::AdminCommand::Export(player) => privacy::administer_export(&player, world).await,
+        admin::AdminCommand::Forget(player) => privacy::administer_forget(&player, world, players).await,
+        admin::AdminCommand::Token(player) => match (&world.login_tokens, world.handle_rules.validate_handle(&player)) {
+            (None, _) => "Login tokens are disabled.".to_string(),
+            (Some(_), Err(e)) => format!("{} is no valid handle: {}.", player, e),
+            (Some(tokens), Ok(_)) => {
+                let user = tokens.issue(&player);
+                let (host, port) = &world.ssh_address;
+                info!("Issued a login token for {}.", player);
+                let mut text = format!("Login token for {} (valid once, for {} minutes):\r\n  ssh {}@{} -p {}", 
+                    player, tokens.ttl().as_secs() / 60, user, host, port);
+                if let Some(code) = qr::render(&format!("ssh://{}@{}:{}", user, host, port)) {
+                    text += "\r\n";
+                    text += &code;
+                }
+                text
+            },
+        },
+        admin::AdminCommand::Maintenance { enabled: true, minutes } => {
+            let now = Instant::now();
+            world.maintenance = true;
+            world.reopens = minutes.map(|m| now + Duration::from_secs(m * 60));
+            let notice = world.maintenance_notice(now);
+            let turned_away: Vec<ClientId> = players.iter()
+                .filter(|(_, p)| p.role == Role::Player)
+                .map(|(c, _)| *c)
+                .collect();
+            for client_id in &turned_away {
+                world.challenges.leave(*client_id);
+                if let Some(mut player) = players.remove(client_id) {
+                    return_items(world, &mut player);
+                    if let Some(screen) = world.screens.get(ScreenType::Maintenance) {
+                        player.show(screen).await;
+                    }
+                    player.push(buffers::from_slice(notice.as_bytes())).await;
+                    if player.connection.close().await.is_err() {
+                        debug!("Client of player {} is already gone.", player.player_name);
+                    }
+                }
+            }
+            match minutes {
+                Some(m) => format!("Maintenance is on, {} players disconnected. The world reopens in {} minutes.", 
+                    turned_away.len(), m),
+                None => format!("Maintenance is on, {} players disconnected. Reopen the world with \"@maintenance off\".", 
+                    turned_away.len()),
+            }
+        },
+        admin::AdminCommand::Maintenance { enabled: false, .. } => {
+            world.maintenance = false;
+            world.reopens = None;
+            "Maintenance is off, the world is open again.".to_string()
+        },
+        admin::AdminCommand::Pause => clock::pause(world, players).await,
+        admin::AdminCommand::Step(ticks) => clock::step(ticks, world),
+        admin::AdminCommand::Resume => clock::resume(world, players).await,
+        admin::AdminCommand::Reset(zone) => match world.resets.get(&zone) {
+            Some(reset) => resets::reset(&reset.clone(), world, players).await,
+            None => format!("There is no reset for {}.", zone),
+        },
+        admin::AdminCommand::Timers(None) => {
+            let pending = world.timers.pending();
+            if pending.is_empty() {
+                "No timers are pending.".to_string()
+            } else {
+                let now = timestamp();
+                let lines: Vec<String> = pending.iter()
+                    .map(|t| format!("{}: in {}s, {:?}", t.id, t.due.saturating_sub(now), t.effect))
+                    .collect();
+                format!("Pending timers:\r\n{}", lines.join("\r\n"))
+            }
+        },
+        admin::AdminCommand::Timers(Some(id)) => match world.timers.cancel(id) {
+            Some(timer) => {
+                if let Err(e) = world.timers.save(&world.data_dir).await {
+                    error!("Could not save the timers: {}", e);
+                }
+                format!("Cancelled timer {} ({:?}).", timer.id, timer.effect)
+            },
+            None => format!("There is no timer {}.", id),
+        },
+        admin::AdminCommand::Find { noun, filters } => {
+            let found = inspect::find(&world.nodes, &noun, &filters);
+            if found.is_empty() {
+                "Nothing found.".to_string()
+            } else {
+                format!("Found {}:\r\n{}", found.len(), found.join("\r\n"))
+            }
+        },
+        admin::AdminCommand::Show(target) => inspect::show(target, world, players),
+        admin::AdminCommand::Where(uid) => match inspect::locate(&world.nodes, uid) {
+            Some((node, asset)) => inspect::heading(asset, Some(node)),
+            None => format!("There is no asset #{} in any node.", uid),
+        },
+        admin::AdminCommand::Edit(edit) if world.sandboxes.get(&admin_name).is_some() => {
+            sandboxes::edit(edit, &admin_name, world)
+        },
+        admin::AdminCommand::Edit(edit) if world.drafts.get(&admin_name).is_some() => {
+            edits::stage(edit, &admin_name, world)
+        },
+        admin::AdminCommand::Edit(edit) => edits::edit(edit, &admin_name, world),
+        admin::AdminCommand::Undo if world.sandboxes.get(&admin_name).is_some() => sandboxes::undo(&admin_name, world),
+        admin::AdminCommand::Undo if world.drafts.get(&admin_name).is_some() => edits::unstage(&admin_name, world),
+        admin::AdminCommand::Undo => edits::undo(&admin_name, world),
+        admin::AdminCommand::Changes(count) => edits::changes(count, world),
+        admin::AdminCommand::Draft => edits::open_draft(&admin_name, world),
+        admin::AdminCommand::Preview => edits::preview_draft(&admin_name, world),
+        admin::AdminCommand::Publish => edits::publish_draft(&admin_name, world),
+        admin::AdminCommand::Discard => edits::discard_draft(&admin_name, world),
+        admin::AdminCommand::Sandbox(step) => {
+            sandboxes::administer(step, &admin_name, data_message.client_id, world, players)
+        },
+        admin::AdminCommand::Slay(uid) => match world.npcs.kill(uid, world.clock.now(Instant::now())) {
+            Some(npc) => {
+                let message = Message::new(Event::Info, &format!("The {} is purged by the sysop.", npc.name()))
+                    .with_field("npc", npc.uid);
+                for p in players.values().filter(|p| p.location == Some(npc.location)) {
+                    p.send(message.clone()).await;
+                }
+                format!("Slew {} #{}.", npc.name(), npc.uid)
+            },
+            None => format!("There is no NPC #{}.", uid),
+        },
+        admin::AdminCommand::Poll(step) => polls::administer(step, world, players).await,
+        admin::AdminCommand::Schedule(step) => schedule::administer(step, world).await,
+        admin::AdminCommand::Flags(None) => world.seasons.describe(timestamp()),
+        admin::AdminCommand::Flags(Some((season, forced))) => {
+            if world.seasons.set_override(&season, forced) {
+                let state = match forced {
+                    Some(true) => "forced on",
+                    Some(false) => "forced off",
+                    None => "following its dates",
+                };
+                format!("Season {} is {}.", season, state)
+            } else {
+                format!("There is no season {}.", season)
+            }
+        },
+        admin::AdminCommand::Casino(limits) => casino::administer(limits, world),
+        admin::AdminCommand::Channels => {
+            let metrics = &world.channel_metrics;
+            format!("Channels:\r\n {}\r\n {}\r\n {}", metrics.command, metrics.data, metrics.push)
+        },
+        admin::AdminCommand::Stats => format!("World loop:\r\n{}\r\nOutput: {}, {} strings interned", world.loop_stats,
+            buffers::usage(), intern::count()),
+        admin::AdminCommand::Economy => {
+            let carried: u64 = players.values().map(|p| p.account.credits).sum();
+            let vaults: u64 = players.values().map(|p| p.account.vault.credits).sum();
+            format!("{}\r\nThe {} players online carry {} credits and keep {} in their vaults.", world.ledger,
+                players.len(), carried, vaults)
+        },
+        admin::AdminCommand::Totals { announce } => {
+            match activity_totals(world, players).await {
+                Ok(totals) => {
+                    let text = totals.to_string();
+                    if announce {
+                        for p in players.values() {
+                            p.send(Message::new(Event::Info, &text)
+                                .with_field("players", totals.players)
+                                .with_field("played_s", totals.played_s)
+                                .with_field("nodes", totals.nodes())).await;
+                        }
+                        format!("Announced the totals to the {} players online.", players.len())
+                    } else {
+                        text
+                    }
+                },
+                Err(e) => format!("Could not load the accounts: {}", e),
+            }
+        },
+        admin::AdminCommand::Archive => {
+            let archive = match activity_totals(world, players).await {
+                Ok(totals) => {
+                    archive::Archive::new(&world.nodes, &world.challenges, &world.news, &totals, world.visitors.count())
+                        .save(&world.data_dir).await
+                },
+                Err(e) => Err(e),
+            };
+            match archive {
+                Ok(path) => format!("Archived the world to {}.", path.display()),
+                Err(e) => format!("Could not archive the world: {}", e),
+            }
+        },
+        admin::AdminCommand::Coverage(player) => {
+            match coverage::Coverage::load(&world.data_dir, player.as_deref()).await {
+                Ok(coverage) if coverage.replayed == 0 => "There are no commands in the transcripts.".to_string(),
+                Ok(coverage) => coverage.to_string(),
+                Err(e) => format!("Could not replay the transcripts: {}", e),
+            }
+        },
+        admin::AdminCommand::LogLevel(directives) => {
+            if directives.is_empty() {
+                format!("Log levels: {}", world.log_filter)
+            } else {
+                match world.log_filter.set(&directives) {
+                    Ok(_) => format!("Log levels: {}", world.log_filter),
+                    Err(e) => format!("Could not change the log levels: {}", e),
+                }
+            }
+        },
+        admin::AdminCommand::Banner { font, text } => {
+            match world.banners.render(&text, font.as_deref()) {
+                Ok(banner) => {
+                    // Robots get the text only
+                    for p in players.values() {
+                        if p.output_mode == OutputMode::Robot {
+                            p.send(Message::new(Event::Info, &text)
+                                .with_field("banner", true)).await;
+                        } else {
+                            p.push(buffers::from_slice(banner.as_bytes())).await;
+                        }
+                    }
+                    world.news.publish(news::Kind::Announcement, "Announcement", &text, Vec::new());
+                    format!("Announced to {} players.", players.len())
+                },
+                Err(e) => format!("Could not render the banner ({}). Fonts: {}", e, world.banners.fonts().join(", ")),
+            }
+        },
+        admin::AdminCommand::Spectate { zone, enabled } => {
+            match world.find_zone(&zone) {
+                Some(index) if enabled => {
+                    world.spectated.insert(index);
+                    format!("{} can be watched now.", world.nodes[index].name())
+                },
+                Some(index) => {
+                    world.spectated.remove(&index);
+                    let name = world.nodes[index].name();
+                    let message = Message::new(Event::Info, &format!("The feed of {} ends.", name));
+                    for p in players.values_mut().filter(|p| p.watching == Some(index)) {
+                        p.watching = None;
+                        p.send(message.clone()).await;
+                    }
+                    format!("{} can no longer be watched.", name)
+                },
+                None => format!("There is no zone {}.", zone),
+            }
+        },
+        admin::AdminCommand::Filter(enabled) => {
+            world.content_filter.enabled = enabled;
+            if enabled {
+                "The content filter is on.".to_string()
+            } else {
+                "The content filter is off.".to_string()
+            }
+        },
+    };
+
+    if let Some(admin) = players.get(&data_message.client_id) {
+        admin.send(Message::new(Event::Info, &reply)).await;
+    }
+}
+
+/// Process action queues
+/// 
+/// Called on every world tick. Every player that is not busy with a previous 
+/// action performs the next action of its queue. The player then stays busy
+/// for the duration of the action, so that queued actions are executed 
+/// sequentially and with the delay each action takes. `now` is the time of
+/// the world (see `clock`).
+async fn process_queues(world: &mut GameWorld, players: &mut HashMap<ClientId, Player>, now: Instant) {
+    let mut due = Vec::new();
+    let mut throttled = Vec::new();
+    for (client_id, player_info) in players.iter_mut() {
+        if player_info.queue_triggered(now) > 0 {
+            throttled.push(*client_id);
+        }
+        if player_info.busy_until.is_some_and(|t| t > now) {
+            continue;
+        }
+        if let Some(q) = player_info.queue.pop_front() {
+            // Delayed actions run in the background, the player goes on
+            if !q.action.is_delayed() {
+                player_info.busy_until = Some(now + q.action.duration());
+            }
+            player_info.session.actions += 1;
+            due.push((*client_id, q));
+        }
+    }
+
+    for client_id in throttled {
+        if let Some(p) = players.get(&client_id) {
+            let message = Message::new(Event::Error, "Your triggers fire too fast. Some were skipped.");
+            p.send_with_triggers(message, false).await;
+        }
+    }
+
+    for (client_id, q) in due {
+        // The zone of the player may refuse the action
+        if let Some(p) = players.get(&client_id) {
+            let safety = p.location.and_then(|l| world.nodes.get(l)).map(|n| n.safety()).unwrap_or_default();
+            if let Err(refusal) = safety.check(&q.action, safety::level(p.account.score)) {
+                p.send(Message::new(Event::Error, &refusal.to_string())).await;
+                continue;
+            }
+            // So does the asset the action is aimed at
+            if let Some(refusal) = p.location.and_then(|l| world.nodes.get(l)).and_then(|n| n.refusal(&q.action)) {
+                p.send(Message::new(Event::Error, &refusal)).await;
+                continue;
+            }
+        }
+        let location = match players.get_mut(&client_id) {
+            Some(p) => {
+                // Noisy actions give cloaked players away, hasty actions leave a trace
+                if q.action.is_noisy() && p.cloak.take().is_some() {
+                    p.send(Message::new(Event::Info, "Your cloak drops.").with_field("cloaked", false)).await;
+                }
+                p.hurried |= q.action.manner() == Some(adverbs::Adverb::Quickly);
+                p.location
+            },
+            None => continue,
+        };
+        if let (true, Some(pending)) = (q.action.is_delayed(), world.delays.pending(client_id)) {
+            let message = format!("Your {} is still running.", pending.action.verb());
+            if let Some(p) = players.get(&client_id) {
+                p.send(Message::new(Event::Error, &message)).await;
+            }
+            continue;
+        }
+        if q.action.is_delayed() {
+            world.delays.start(client_id, q.action, location, q.triggered, now);
+            continue;
+        }
+        perform(q.action, q.triggered, world, client_id, players, now).await;
+    }
+
+    process_delays(world, players, now).await;
+    challenges::process(world, players, now).await;
+    for reset in world.resets.due(now) {
+        resets::reset(&reset, world, players).await;
+    }
+    process_timers(world, players).await;
+    ice::process(world, players, now).await;
+    process_npcs(world, players, now).await;
+    market::process(world, players).await;
+    process_seasons(world, players).await;
+    schedule::process(world, players).await;
+    process_idle(world, players, now).await;
+}
+
+/// Perform an action of a player
+/// 
+/// The action is dispatched to the part of the world it changes, timed and
+/// counted by the analytics.
+async fn perform(a: Action, triggered: bool, world: &mut GameWorld, client_id: ClientId,
+                 players: &mut HashMap<ClientId, Player>, now: Instant) {
+    // Output caused by a triggered action does not fire triggers
+    if let Some(p) = players.get_mut(&client_id) {
+        p.performing_triggered = triggered;
+    }
+    let before = players.get(&client_id).map(|p| {
+        (p.errors.load(Ordering::Relaxed), p.location.and_then(|l| world.nodes.get(l)).map(|n| n.uid()))
+    });
+    let span = info_span!("perform_action", client_id, action = %a, elapsed_us = field::Empty);
+    let (_, elapsed) = match a {
+        // Challenges change the runs of the world
+        Action::Challenge(_) | Action::Join(_) | Action::Leaderboard(_) => {
+            stats::timed(span, challenges::compete(&a, world, client_id, players, now)).await
+        },
+        Action::Enter { .. } | Action::Connect { .. } => {
+            stats::timed(span, enter(&a, world, client_id, players)).await
+        },
+        Action::Plugin { .. } => stats::timed(span, plugin(&a, world, client_id, players)).await,
+        Action::Quit => stats::timed(span, hang_up(client_id, world, players)).await,
+        // Carrying items changes the nodes of the world
+        Action::Take { .. } | Action::Steal { .. } | Action::Drop { .. } => {
+            stats::timed(span, carry(&a, world, client_id, players)).await
+        },
+        // Combat changes the NPCs of the world
+        Action::Hack { .. } | Action::Taunt { .. } => {
+            stats::timed(span, threat::fight(&a, world, client_id, players, now)).await
+        },
+        Action::Drone(_) => stats::timed(span, drones::order(&a, world, client_id, players, now)).await,
+        // Breaking ICE changes the intrusions of the world
+        Action::Attack { .. } | Action::Crack { .. } => {
+            stats::timed(span, ice::intrude(&a, world, client_id, players, now)).await
+        },
+        // Trades change the market of the world
+        Action::Market(_) | Action::Mail => stats::timed(span, market::trade(&a, world, client_id, players)).await,
+        Action::MailTranscript => stats::timed(span, transcripts::mail(world, client_id, players)).await,
+        Action::Bank(_) => stats::timed(span, bank::visit(&a, world, client_id, players)).await,
+        Action::Gamble(_) => stats::timed(span, casino::play(&a, world, client_id, players)).await,
+        Action::Vendor(_) => stats::timed(span, economy::deal(&a, world, client_id, players)).await,
+        // Bribes change the heat of security
+        Action::Bribe => stats::timed(span, bribe(world, client_id, players, now)).await,
+        // Votes change the poll of the world
+        Action::Vote(_) => stats::timed(span, polls::vote(&a, world, client_id, players)).await,
+        // Reminders change the schedule of the world
+        Action::Schedule(_) => stats::timed(span, schedule::consult(&a, world, client_id, players)).await,
+        _ => stats::timed(span, perform_action(&a, world, client_id, players)).await,
+    };
+    world.loop_stats.actions.entry(a.verb()).or_default().record(elapsed);
+    if let Some((errors, node)) = before {
+        // A player gone during the action (eg. to another world) did not fail
+        let failed = players.get(&client_id).is_some_and(|p| p.errors.load(Ordering::Relaxed) > errors);
+        world.analytics.action(a.verb(), node, failed);
+        // Acting as a boss telegraphed counters its attack (see `bosses`)
+        if let (false, Some(p)) = (failed, players.get(&client_id)) {
+            if let Some(location) = p.location {
+                world.bosses.counter(world.npcs.in_node(location).map(|n| n.uid), &p.player_name, a.verb());
+            }
+        }
+    }
+    if let Some(p) = players.get_mut(&client_id) {
+        p.performing_triggered = false;
+    }
+}
+
+/// Tell the players how their pending actions progress and perform the
+/// actions completing (see `delays`)
+/// 
+/// An action started in another node than the player is in now is
+/// cancelled.
+async fn process_delays(world: &mut GameWorld, players: &mut HashMap<ClientId, Player>, now: Instant) {
+    for delayed in world.delays.due(now) {
+        match players.get(&delayed.client_id) {
+            Some(p) if p.location == delayed.location => {
+                perform(delayed.action, delayed.triggered, world, delayed.client_id, players, now).await;
+            },
+            Some(p) => p.send(Message::new(Event::Error, &format!("You moved. Your {} is cancelled.",
+                delayed.action.verb()))).await,
+            None => {},
+        }
+    }
+    for (client_id, progress) in world.delays.progress(now) {
+        if let Some(p) = players.get(&client_id) {
+            p.send(Message::new(Event::Info, &progress)).await;
+        }
+    }
+}
+
+/// Buy off the heat of security (see `security`)
+///
+/// The ICE dispatched to the player stands down.
+async fn bribe(world: &mut GameWorld, client_id: ClientId, players: &mut HashMap<ClientId, Player>, now: Instant) {
+    let player = match players.get_mut(&client_id) {
+        Some(p) => p,
+        None => return,
+    };
+    info!("Player {} is bribing security.", player.player_name);
+    let cost = match world.security.bribe(&player.player_name, now) {
+        Some(cost) => cost,
+        None => {
+            player.send(Message::new(Event::Error, "Security has nothing on you.")).await;
+            return;
+        },
+    };
+    if cost > player.account.credits {
+        let text = format!("Buying off security costs {} credits, you carry {}.", cost, player.account.credits);
+        player.send(Message::new(Event::Error, &text)).await;
+        return;
+    }
+    player.account.credits -= cost;
+    world.ledger.destroy("security", cost);
+    save_account(world, player).await;
+    player.send(Message::new(Event::Response, &format!("You pay {} credits. Security looks the other way.", cost))
+        .with_field("credits", player.account.credits)).await;
+    let responders = world.security.clear(&players[&client_id].player_name).unwrap_or_default();
+    stand_down(responders, world, players, now).await;
+}
+
+/// Remove the ICE security dispatched to a player, the heat being gone
+async fn stand_down(responders: Vec<npcs::NpcID>, world: &mut GameWorld, players: &HashMap<ClientId, Player>,
+                    now: Instant) {
+    for uid in responders {
+        if let Some(npc) = world.npcs.kill(uid, now) {
+            let message = Message::new(Event::Info, &format!("The {} stands down and logs off.", npc.name()))
+                .with_field("npc", npc.uid);
+            send_to_node(players, npc.location, message).await;
+        }
+    }
+}
+
+/// Save the account of a player, logging failures
+async fn save_account(world: &GameWorld, player: &Player) {
+    if let Err(e) = player.account.save(&world.data_dir).await {
+        error!("Could not save account of {}: {}", player.player_name, e);
+    }
+}
+
+/// The totals of the activity of all accounts
+///
+/// Players online count with their current session.
+async fn activity_totals(world: &GameWorld, players: &HashMap<ClientId, Player>) -> std::io::Result<stats::Totals> {
+    let mut totals = stats::Totals::default();
+    for p in players.values() {
+        totals.add(&p.account.activity.with(&p.session));
+    }
+    for account in accounts::Account::load_all(&world.data_dir).await? {
+        if !players.values().any(|p| p.account.name == account.name) {
+            totals.add(&account.activity);
+        }
+    }
+    Ok(totals)
+}
+
+/// Tell the admins about seasons starting and ending and move the players
+/// out of the nodes whose season ended
+async fn process_seasons(world: &mut GameWorld, players: &mut HashMap<ClientId, Player>) {
+    let now = timestamp();
+    let active: Vec<String> = world.seasons.active(now).into_iter().map(String::from).collect();
+    if active == world.active_seasons {
+        return;
+    }
+    for season in active.iter().filter(|s| !world.active_seasons.contains(s)) {
+        info!("Season {} started.", season);
+        send_to_admins(players, &format!("Season {} started.", season)).await;
+    }
+    for season in world.active_seasons.iter().filter(|s| !active.contains(s)) {
+        info!("Season {} ended.", season);
+        send_to_admins(players, &format!("Season {} ended.", season)).await;
+    }
+    world.active_seasons = active;
+
+    for p in players.values_mut() {
+        let closed = p.location
+            .and_then(|l| world.nodes.get(l))
+            .is_some_and(|n| !world.seasons.allows(n.season(), now));
+        if !closed {
+            continue;
+        }
+        match world.spawn(p) {
+            Ok(index) => {
+                let node = world.nodes.get(index);
+                if let Some(node) = node {
+                    p.session.explored.insert(node.uid());
+                }
+                let name = node.map(|n| n.name().to_string()).unwrap_or_default();
+                let text = format!("The node fades as its season ends. You find yourself in the {}.", name);
+                p.send(Message::new(Event::Info, &text)).await;
+            },
+            Err(e) => warn!("Could not move {} out of a node out of season: {}", p.player_name, e),
+        }
+    }
+}
+
+/// Let the decks of idle players work in the background (see `idle`)
+async fn process_idle(world: &GameWorld, players: &mut HashMap<ClientId, Player>, now: Instant) {
+    for p in players.values_mut() {
+        if p.idle.tick(&mut p.account, now) {
+            debug!("The deck of idle player {} completed a cycle.", p.player_name);
+            world.ledger.create("idle", idle::INTEL_CREDITS);
+            save_account(world, p).await;
+        }
+    }
+}
+
+/// Send an info to the admins online
+async fn send_to_admins(players: &HashMap<ClientId, Player>, text: &str) {
+    let message = Message::new(Event::Info, text);
+    for admin in players.values().filter(|p| p.role == Role::Admin) {
+        admin.send(message.clone()).await;
+    }
+}
+
+/// Send a combat event to the players in a node, at the verbosity each
+/// chose, and mirror the vitals to clients with GMCP
+async fn send_combat(players: &HashMap<ClientId, Player>, node: Index, event: &combat::CombatEvent) {
+    for p in players.values().filter(|p| p.location == Some(node)) {
+        if let Some(message) = event.message(&p.player_name, p.account.combat) {
+            p.send(message).await;
+        }
+        if let Some((package, data)) = event.vitals(&p.player_name) {
+            p.mirror(package, &data).await;
+        }
+    }
+}
+
+/// Keep the population of NPCs in the nodes with players and let them act
+/// 
+/// The players in a node see the NPCs spawned and moving, players traced by
+/// an NPC are told.
+async fn process_npcs(world: &mut GameWorld, players: &mut HashMap<ClientId, Player>, now: Instant) {
+    let occupied: HashSet<Index> = players.values().filter_map(|p| p.location).collect();
+    let (seasons, time) = (&world.seasons, timestamp());
+    for change in world.npcs.tick(|node| occupied.contains(&node), |s| seasons.allows(s, time), now) {
+        match change {
+            npcs::Change::Spawned(npc) => {
+                let message = Message::new(Event::Info, &format!("A {} materializes.", npc.name()))
+                    .with_field("npc", npc.uid);
+                send_to_node(players, npc.location, message).await;
+                let phase = world.bosses.engage(npc.uid, npc.name(), now)
+                    .map(|p| (p.behaviors.clone(), p.announce.clone()));
+                if let Some((behaviors, announce)) = phase {
+                    world.npcs.set_behaviors(npc.uid, behaviors);
+                    let message = Message::new(Event::Info, &announce).with_field("npc", npc.uid);
+                    send_to_node(players, npc.location, message).await;
+                }
+            },
+            npcs::Change::Despawned(npc) => {
+                debug!("Despawned {} #{} in an empty node.", npc.name(), npc.uid);
+                world.bosses.disengage(npc.uid);
+            },
+        }
+    }
+    // ICE scales with the number and the level of the players in its node
+    let mut levels: HashMap<Index, Vec<u64>> = HashMap::new();
+    for p in players.values() {
+        if let Some(location) = p.location {
+            levels.entry(location).or_default().push(safety::level(p.account.score));
+        }
+    }
+    world.npcs.scale(|node| levels.get(&node).map(|l| difficulty::scale(l)));
+
+    // NPCs do not perceive the players in safe zones
+    let mut view = ai::View::default();
+    let mut crimes = Vec::new();
+    for p in players.values_mut() {
+        let touched = std::mem::take(&mut p.touched);
+        let hurried = std::mem::take(&mut p.hurried);
+        let shouted = std::mem::take(&mut p.shouted);
+        let committed = std::mem::take(&mut p.crimes);
+        if p.location.and_then(|l| world.nodes.get(l)).is_some_and(|n| n.safety().safe) {
+            continue;
+        }
+        match (p.location, p.cloak.is_some()) {
+            (Some(location), true) => view.cloaked.push(location),
+            (Some(location), false) => view.players.push((p.player_name.clone(), location)),
+            (None, _) => {},
+        }
+        if let Some(location) = p.location {
+            view.touched.extend(touched.into_iter().map(|a| (location, a, p.player_name.clone())));
+            if hurried {
+                view.hurried.push((location, p.player_name.clone()));
+            }
+            if shouted {
+                let heard = world.propagate(location, propagation::Noise::Shout);
+                view.heard.extend(heard.into_iter().map(|(n, _)| (n, p.player_name.clone())));
+            }
+            if committed.contains(&security::Crime::Theft) {
+                view.caught.push((location, p.player_name.clone()));
+            }
+            crimes.extend(committed.into_iter().map(|c| (p.player_name.clone(), location, c)));
+        }
+    }
+    // Crimes heat the offenders up, security dispatches its ICE to the hot
+    // ones and guards trace everybody with heat
+    for (player, location, crime) in crimes {
+        let level = world.nodes.get(location).map_or(0, |n| n.security());
+        let response = match world.security.commit(&player, crime, level, now) {
+            Some(r) => r,
+            None => continue,
+        };
+        let offender = players.values().find(|p| p.player_name == player);
+        if let Some(p) = offender {
+            let text = format!("Security logs your {}. Heat: {}.", crime, response.heat);
+            p.send(Message::new(Event::Info, &text).with_field("heat", response.heat)).await;
+        }
+        let npc = match (response.dispatch, world.security.responder()) {
+            (true, Some(responder)) => world.npcs.dispatch(&responder.clone(), location, &player),
+            _ => continue,
+        };
+        world.security.dispatched(&player, npc.uid);
+        info!("Security dispatched {} #{} to {}.", npc.name(), npc.uid, player);
+        let message = Message::new(Event::Info, &format!("Security dispatches a {}. It traces {}.", npc.name(),
+                offender.map_or(player.clone(), |p| p.display_name())))
+            .with_field("npc", npc.uid);
+        send_to_node(players, location, message).await;
+    }
+    for (player, responders) in world.security.cool_down(now) {
+        if let Some(p) = players.values().find(|p| p.player_name == player) {
+            p.send(Message::new(Event::Info, "The heat is off. Security lost interest in you.")
+                .with_field("heat", 0)).await;
+        }
+        stand_down(responders, world, players, now).await;
+    }
+    view.wanted = world.security.wanted(now);
+
+    for (uid, act) in world.npcs.think(&view, now) {
+        match act {
+            ai::Act::Move(to) => {
+                let from = match world.npcs.get(uid).map(|n| n.location) {
+                    Some(l) => l,
+                    None => continue,
+                };
+                let name = match world.npcs.relocate(uid, to) {
+                    Some(npc) => npc.name().to_string(),
+                    None => continue,
+                };
+                let message = Message::new(Event::Info, &format!("The {} slips away.", name)).with_field("npc", uid);
+                send_to_node(players, from, message).await;
+                let message = Message::new(Event::Info, &format!("A {} slips in.", name)).with_field("npc", uid);
+                send_to_node(players, to, message).await;
+            },
+            ai::Act::Trace(target) => {
+                let name = world.npcs.get(uid).map(|n| n.name().to_string()).unwrap_or_default();
+                info!("The {} #{} traces {}.", name, uid, target);
+                if let Some(p) = players.values().find(|p| p.player_name == target) {
+                    p.send(Message::new(Event::Info, &format!("The {} locks onto your trace.", name))
+                        .with_field("traced_by", uid)).await;
+                }
+            },
+            ai::Act::Lose(target) => debug!("NPC #{} lost the trace of {}.", uid, target),
+            ai::Act::Strike { player, jam } => strike(uid, player, jam, world, players, now).await,
+            ai::Act::Perceive(perception) => {
+                let (name, location) = match world.npcs.get(uid) {
+                    Some(n) => (n.name().to_string(), n.location),
+                    None => continue,
+                };
+                for p in players.values_mut().filter(|p| p.location == Some(location)) {
+                    if p.cloak.as_mut().is_some_and(|c| c.wear(perception)) {
+                        p.cloak = None;
+                        info!("The {} #{} spotted {}.", name, uid, p.player_name);
+                        p.send(Message::new(Event::Info, &format!("The {} spots you. Your cloak fails.", name))
+                            .with_field("cloaked", false)).await;
+                    }
+                }
+            },
+            ai::Act::Reinforce(count) => {
+                let spawned = world.npcs.reinforce(uid, count);
+                if let Some(npc) = spawned.first() {
+                    let message = Message::new(Event::Info, &format!("The {} calls reinforcements, {} more appear.",
+                        npc.name(), spawned.len())).with_field("npc", uid);
+                    send_to_node(players, npc.location, message).await;
+                }
+            },
+        }
+    }
+    // Bosses warn of their telegraphed attacks and land them on the players
+    // in the node not countering them
+    for (uid, blow) in world.bosses.tick(now) {
+        let (name, location, scale) = match world.npcs.get(uid) {
+            Some(n) => (n.name().to_string(), n.location, n.scale()),
+            None => {
+                world.bosses.disengage(uid);
+                continue;
+            },
+        };
+        match blow {
+            bosses::Blow::Warn(telegraph) => {
+                let text = format!("The {} charges a {}! Counter it with \"{}\".", name, telegraph.name,
+                    telegraph.counter);
+                send_to_node(players, location, Message::new(Event::Info, &text).with_field("npc", uid)).await;
+            },
+            bosses::Blow::Land { telegraph, countered } => {
+                let text = format!("The {} unleashes its {}.", name, telegraph.name);
+                send_to_node(players, location, Message::new(Event::Info, &text).with_field("npc", uid)).await;
+                let struck: Vec<String> = players.values()
+                    .filter(|p| p.location == Some(location) && !countered.contains(&p.player_name))
+                    .map(|p| p.player_name.clone())
+                    .collect();
+                let jam = difficulty::apply_duration(telegraph.jam, scale);
+                for player in struck {
+                    strike(uid, player, jam, world, players, now).await;
+                }
+            },
+        }
+    }
+}
+
+/// Let an NPC strike a player, jamming its deck
+///
+/// A deployed drone takes the strike for its owner.
+async fn strike(uid: npcs::NpcID, player: String, jam: Duration, world: &mut GameWorld,
+                players: &mut HashMap<ClientId, Player>, now: Instant) {
+    let (name, location) = match world.npcs.get(uid) {
+        Some(n) => (n.name().to_string(), n.location),
+        None => return,
+    };
+    let mut event = combat::CombatEvent::Strike { player: player.clone(), npc: uid, name: name.clone(),
+        jam: jam.as_secs() };
+    if let Some(p) = players.values_mut().find(|p| p.player_name == player) {
+        match (p.drone_deployed, p.account.drone.as_mut()) {
+            (true, Some(drone)) => {
+                if drone.damage(drones::STRIKE_DAMAGE) {
+                    p.account.drone = None;
+                    p.drone_deployed = false;
+                    info!("The drone of {} was wrecked.", p.player_name);
+                }
+                if let Err(e) = p.account.save(&world.data_dir).await {
+                    error!("Could not save account of {}: {}", p.player_name, e);
+                }
+                let integrity = p.account.drone.as_ref().map_or(0, |d| d.integrity);
+                event = combat::CombatEvent::Shield { player, npc: uid, name, integrity };
+            },
+            _ => p.busy_until = Some(p.busy_until.map_or(now, |t| t.max(now)) + jam),
+        }
+    }
+    send_combat(players, location, &event).await;
+}
+
+/// Send a message to the players in a node
+async fn send_to_node(players: &HashMap<ClientId, Player>, node: Index, message: Message) {
+    for p in players.values().filter(|p| p.location == Some(node)) {
+        p.send(message.clone()).await;
+    }
+}
+
+/// Apply the effects of the timers due (see `timers`)
+async fn process_timers(world: &mut GameWorld, players: &mut HashMap<ClientId, Player>) {
+    let due = world.timers.due(timestamp());
+    if due.is_empty() {
+        return;
+    }
+    if let Err(e) = world.timers.save(&world.data_dir).await {
+        error!("Could not save the timers: {}", e);
+    }
+    for timer in due {
+        debug!("Timer {} is due ({:?}).", timer.id, timer.effect);
+        match timer.effect {
+            timers::Effect::Open { port } | timers::Effect::Close { port } => {
+                let open = matches!(timer.effect, timers::Effect::Open { .. });
+                let changed = world.nodes.iter_mut().find_map(|(index, node)| {
+                    let port = node.asset_mut(port)?.port_mut()?;
+                    port.update_open(open);
+                    Some((index, port.name().to_string()))
+                });
+                match changed {
+                    Some((index, name)) => {
+                        let text = format!("The {} {}.", name, if open { "opens" } else { "closes" });
+                        send_to_node(players, index, Message::new(Event::Info, &text)).await;
+                    },
+                    None => warn!("Timer {} cannot change port {}, it does not exist.", timer.id, port),
+                }
+            },
+            timers::Effect::Reset { zone } => match world.resets.get(&zone) {
+                Some(reset) => {
+                    resets::reset(&reset.clone(), world, players).await;
+                },
+                None => warn!("Timer {} cannot reset {}, it has no reset.", timer.id, zone),
+            },
+            timers::Effect::Announce { zone, text } => match world.find_zone(&zone) {
+                Some(index) => send_to_node(players, index, Message::new(Event::Info, &text)).await,
+                None => warn!("Timer {} cannot announce in zone {}, it does not exist.", timer.id, zone),
+            },
+            timers::Effect::Alarm { zone } => match world.find_zone(&zone) {
+                Some(index) => sound_alarm(index, world, players).await,
+                None => warn!("Timer {} cannot sound an alarm in zone {}, it does not exist.", timer.id, zone),
+            },
+        }
+    }
+}
+
+/// Sound an alarm in a zone, heard in the nodes around it (see
+/// `propagation`)
+async fn sound_alarm(zone: Index, world: &GameWorld, players: &HashMap<ClientId, Player>) {
+    let name = world.nodes[zone].name();
+    for (node, level) in world.propagate(zone, propagation::Noise::Alarm) {
+        let text = match level {
+            propagation::Level::Clear => "An alarm blares! Security is on its way.".to_string(),
+            propagation::Level::Muffled if name.is_empty() => "An alarm blares nearby.".to_string(),
+            propagation::Level::Muffled => format!("An alarm blares in the {}.", name),
+            propagation::Level::Faint => "You hear an alarm in the distance.".to_string(),
+        };
+        send_to_node(players, node, Message::new(Event::Info, &text)).await;
+    }
+}
+
+/// Reopen the world when the scheduled maintenance is over
+async fn process_maintenance(world: &mut GameWorld, players: &HashMap<ClientId, Player>, now: Instant) {
+    if !world.maintenance || world.reopens.is_none_or(|r| r > now) {
+        return;
+    }
+    world.maintenance = false;
+    world.reopens = None;
+    info!("Maintenance is over, the world is open again.");
+    let message = Message::new(Event::Info, "Maintenance is over, the world is open again.");
+    for admin in players.values().filter(|p| p.role == Role::Admin) {
+        admin.send(message.clone()).await;
+    }
+}
+
+/// Enter an asset
+/// 
+/// Entering a port connected to another node moves the player there (see
+/// `traverse`), connecting only follows such ports. Entering a portal sends
+/// the player to the world it leads to. A player moving to another world of
+/// the server stays connected, a player traveling to the linked world leaves
+/// this world and connects to the other one.
+async fn enter(a: &Action, world: &mut GameWorld, client_id: ClientId, players: &mut HashMap<ClientId, Player>) {
+    let (target, ordinal, connect) = match a {
+        Action::Enter { target, ordinal, .. } => (target.as_deref(), *ordinal, false),
+        Action::Connect { target, ordinal, .. } => (target.as_deref(), *ordinal, true),
+        _ => return,
+    };
+    let player = match players.get(&client_id) {
+        Some(p) => p,
+        None => return,
+    };
+    info!("Player {} is entering {}.", player.player_name, target.unwrap_or("a port"));
+    let node = match player.location.and_then(|l| world.nodes.get(l)) {
+        Some(n) => n,
+        None => {
+            player.send(Message::new(Event::Error, "In limbo there is nothing to enter.")).await;
+            return;
+        },
+    };
+    let asset = match (target, connect) {
+        (Some(t), _) => node.find_asset(t, ordinal),
+        (None, true) => node.find_exit().ok_or_else(|| "Connect to which port?".to_string()),
+        (None, false) => node.find_portal().or_else(|| node.find_exit())
+            .ok_or_else(|| "Enter which port?".to_string()),
+    };
+    let asset = match asset {
+        Ok(asset) => asset,
+        Err(reason) => {
+            player.send(Message::new(Event::Error, &reason)).await;
+            return;
+        },
+    };
+    if let Some(port) = asset.port().filter(|p| p.destination().is_some()) {
+        // A closed port lets the players pass that solved its puzzle, once
+        // the ICE guarding it is broken (see `ice`). Breaking the ICE of a
+        // port without puzzle is enough.
+        let solved = port.puzzle().is_some_and(|p| player.account.puzzles.get(p.id()).is_some_and(|p| p.solved));
+        let ice = node.assets().filter_map(|a| a.ice()).find(|i| i.protects() == asset.uid());
+        let (uid, to) = (asset.uid(), port.destination());
+        let open = match ice.map(|i| world.intrusions.is_broken(i.uid())) {
+            _ if port.is_open() => true,
+            Some(false) => {
+                let reason = "ICE guards the port. Break it first (\"attack ice\" or \"crack ice\").";
+                player.send(Message::new(Event::Error, reason)).await;
+                return;
+            },
+            Some(true) => solved || port.puzzle().is_none(),
+            None => solved,
+        };
+        match to {
+            Some(to) if open => traverse(uid, to, world, client_id, players).await,
+            _ => player.send(Message::new(Event::Error, "The port is closed.")).await,
+        }
+        return;
+    }
+    let destination = match asset.portal() {
+        Some(d) if !connect => d.to_string(),
+        _ => {
+            let reason = format!("The {} does not lead anywhere.", asset.name());
+            player.send(Message::new(Event::Error, &reason)).await;
+            return;
+        },
+    };
+    let traveler = federation::Traveler {
+        name: player.player_name.clone(),
+        title: player.account.title.clone(),
+        score: player.account.score,
+        flags: player.account.flags.clone(),
+    };
+    let gate = world.gate.as_ref().and_then(|g| g.leads_to(&destination).map(|w| (g.clone(), w.to_string())));
+    if let Some((gate, destination)) = gate {
+        if !gate.has_room() {
+            let reason = format!("The portal flickers. {} is out of reach.", destination);
+            player.send(Message::new(Event::Error, &reason)).await;
+            return;
+        }
+        info!("Player {} moves to {}.", player.player_name, destination);
+        let message = format!("The portal pulls you into {}.", destination);
+        player.send(Message::new(Event::Info, &message)
+            .with_field("world", destination.as_str())).await;
+        world.challenges.leave(client_id);
+        if let Some(mut player) = players.remove(&client_id) {
+            return_items(world, &mut player);
+            world.sandboxes.close(&mut world.nodes, &player.player_name);
+            player.account.activity.record(&player.session);
+            save_account(world, &player).await;
+            // The client goes along to the other world
+            let transfer = worlds::Transfer { client_id, world: destination, connection: player.connection, traveler };
+            if let Err(transfer) = gate.send(transfer) {
+                error!("Could not move player {} to {}, closing its client.", player.player_name, transfer.world);
+                if transfer.connection.close().await.is_err() {
+                    debug!("Client of player {} is already gone.", player.player_name);
+                }
+            }
+        }
+        return;
+    }
+    let link = match &world.federation {
+        Some(l) if l.is_peer(&destination) && l.send(federation::Message::Transfer { traveler }) => l,
+        _ => {
+            let reason = format!("The portal flickers. {} is out of reach.", destination);
+            player.send(Message::new(Event::Error, &reason)).await;
+            return;
+        },
+    };
+
+    info!("Player {} travels to {}.", player.player_name, destination);
+    let ssh = link.peer_ssh.clone().unwrap_or_default();
+    let message = format!("The portal pulls you into {}. Connect to {} as {} to continue your journey.", 
+        destination, ssh, player.player_name);
+    player.send(Message::new(Event::Info, &message)
+        .with_field("world", destination)
+        .with_field("ssh", ssh)).await;
+    if player.connection.close().await.is_err() {
+        debug!("Client of player {} is already gone.", player.player_name);
+    }
+    world.challenges.leave(client_id);
+    if let Some(mut player) = players.remove(&client_id) {
+        return_items(world, &mut player);
+        world.sandboxes.close(&mut world.nodes, &player.player_name);
+    }
+}
+
+/// Move a player through a port to the node it leads to
+/// 
+/// The player may only pass into a node its ACL lets it enter and whose
+/// season runs, and cannot leave a sandbox this way. The players in both
+/// nodes see the player leave and arrive, unless it is cloaked. The player
+/// then looks around the new node.
+async fn traverse(port: assets::AssetID, to: Index, world: &mut GameWorld, client_id: ClientId,
+                  players: &mut HashMap<ClientId, Player>) {
+    let player = match players.get_mut(&client_id) {
+        Some(p) => p,
+        None => return,
+    };
+    let from = player.location;
+    let refusal = match world.nodes.get(to) {
+        _ if world.sandboxes.contains(from) => {
+            Some("You are in a sandbox. Use \"@sandbox discard\" or \"@sandbox promote\" to leave it.")
+        },
+        None => Some("The port leads nowhere. The node behind it is gone."),
+        Some(node) if !player.may_enter(node) => Some("The port refuses you passage."),
+        Some(node) if !world.seasons.allows(node.season(), timestamp()) => {
+            Some("The port is dormant. The season of the node behind it is not running.")
+        },
+        Some(_) => None,
+    };
+    if let Some(reason) = refusal {
+        player.send(Message::new(Event::Error, reason)).await;
+        return;
+    }
+    info!("Player {} passes port #{} to node {:?}.", player.player_name, port, to);
+    if let Some(delayed) = world.delays.cancel(client_id) {
+        let message = format!("You move on. Your {} is cancelled.", delayed.action.verb());
+        player.send(Message::new(Event::Error, &message)).await;
+    }
+    player.touched.push(port);
+    player.location = Some(to);
+    let (name, cloaked) = (player.display_name(), player.cloak.is_some());
+    player.send(Message::new(Event::Info, "You pass through the port.").with_field("port", port)).await;
+    if !cloaked {
+        if let Some(from) = from {
+            send_to_node(players, from, Message::new(Event::Info, &format!("{} leaves through a port.", name))).await;
+        }
+        let message = Message::new(Event::Info, &format!("{} arrives through a port.", name));
+        for (_, p) in players.iter().filter(|(id, p)| **id != client_id && p.location == Some(to)) {
+            p.send(message.clone()).await;
+        }
+    }
+    perform_action(&Action::Look { target: None, ordinal: None, preposition: None, properties: None, manner: None },
+        world, client_id, players).await;
+}
+
+/// Take an item from the node of the player or drop an item it carries
+/// 
+/// The players in the node see the item taken or dropped, unless the player
+/// is cloaked.
+async fn carry(a: &Action, world: &mut GameWorld, client_id: ClientId, players: &mut HashMap<ClientId, Player>) {
+    let player = match players.get_mut(&client_id) {
+        Some(p) => p,
+        None => return,
+    };
+    let (location, node) = match player.location.and_then(|l| world.nodes.get_mut(l).map(|n| (l, n))) {
+        Some(n) => n,
+        None => {
+            player.send(Message::new(Event::Error, "In limbo there is nothing to carry.")).await;
+            return;
+        },
+    };
+    let (name, cloaked) = (player.display_name(), player.cloak.is_some());
+    let message = match a {
+        Action::Take { target, ordinal } => {
+            let uid = match node.find_asset(target, *ordinal) {
+                Ok(asset) if !asset.is_takeable() => Err(format!("You cannot take the {}.", asset.name())),
+                Ok(asset) => match asset.ownership().filter(|o| !o.is_owner(&player.player_name)) {
+                    Some(o) if o.lootable => {
+                        Err(format!("The {} belongs to {}. You would have to steal it.", asset.name(), o.owner))
+                    },
+                    Some(o) => Err(format!("The {} belongs to {}.", asset.name(), o.owner)),
+                    None if player.inventory.is_full() => {
+                        Err(format!("You cannot carry more than {} items.", inventory::CAPACITY))
+                    },
+                    None => Ok(asset.uid()),
+                },
+                Err(reason) => Err(reason),
+            };
+            let item = match uid.map(|uid| node.take_asset(uid)) {
+                Ok(Some(item)) => item,
+                Ok(None) => return,
+                Err(reason) => {
+                    player.send(Message::new(Event::Error, &reason)).await;
+                    return;
+                },
+            };
+            let item_name = item.name().to_string();
+            if let Err(item) = player.inventory.carry(item, location) {
+                node.add_asset(item);
+                return;
+            }
+            player.send(Message::new(Event::Info, &format!("You take the {}.", item_name))
+                .with_field("item", &item_name)).await;
+            format!("{} takes the {}.", name, item_name)
+        },
+        Action::Steal { target, ordinal } => {
+            let found = match node.find_asset(target, *ordinal) {
+                Ok(asset) => match asset.ownership() {
+                    Some(o) if o.is_owner(&player.player_name) => {
+                        Err(format!("The {} is yours, just take it.", asset.name()))
+                    },
+                    Some(o) if o.lootable && asset.is_takeable() => {
+                        Ok((asset.uid(), asset.name().to_string(), o.owner.clone()))
+                    },
+                    _ => Err(format!("You cannot steal the {}.", asset.name())),
+                },
+                Err(reason) => Err(reason),
+            };
+            let found = match (found, &mut player.cloak) {
+                (Err(reason), _) => Err(reason),
+                (Ok(_), _) if player.inventory.is_full() => {
+                    Err(format!("You cannot carry more than {} items.", inventory::CAPACITY))
+                },
+                (Ok(_), None) => Err("You cannot steal in plain sight. Cloak first.".to_string()),
+                (Ok(found), Some(cloak)) => {
+                    let roll = rand::thread_rng().gen_range(0..stealth::CLOAK_STRENGTH);
+                    Ok((found, ownership::steal(cloak, roll)))
+                },
+            };
+            let ((uid, item_name, owner), unnoticed) = match found {
+                Ok(found) => found,
+                Err(reason) => {
+                    player.send(Message::new(Event::Error, &reason)).await;
+                    return;
+                },
+            };
+            info!("Player {}: {} ({}).", player.player_name, a, if unnoticed { "unnoticed" } else { "caught" });
+            if !unnoticed {
+                // Caught in the act: the cloak drops and the ICE of the node traces the thief
+                player.cloak = None;
+                player.crimes.push(security::Crime::Theft);
+                player.send(Message::new(Event::Error, &format!("You are caught stealing the {}!", item_name))).await;
+                player.send(Message::new(Event::Info, "Your cloak drops.").with_field("cloaked", false)).await;
+                let others = Message::new(Event::Info, &format!("{} is caught stealing the {}!", name, item_name));
+                for (_, p) in players.iter().filter(|(id, p)| **id != client_id && p.location == Some(location)) {
+                    p.send(others.clone()).await;
+                }
+                let message = format!("{} tried to steal your {}.", name, item_name);
+                tell_owner(players, &owner, Message::new(Event::Info, &message)).await;
+                return;
+            }
+            let item = match node.take_asset(uid) {
+                Some(item) => item,
+                None => return,
+            };
+            if let Err(item) = player.inventory.carry(item, location) {
+                node.add_asset(item);
+                return;
+            }
+            player.send(Message::new(Event::Info, &format!("You steal the {}.", item_name))
+                .with_field("item", &item_name)).await;
+            let message = format!("Somebody stole your {}.", item_name);
+            tell_owner(players, &owner, Message::new(Event::Info, &message)).await;
+            return;
+        },
+        Action::Drop { target, ordinal } => {
+            let item = match player.inventory.put_down(target, *ordinal) {
+                Ok(item) => item,
+                Err(reason) => {
+                    player.send(Message::new(Event::Error, &reason)).await;
+                    return;
+                },
+            };
+            let item_name = item.name().to_string();
+            node.add_asset(item);
+            player.send(Message::new(Event::Info, &format!("You drop the {}.", item_name))
+                .with_field("item", &item_name)).await;
+            format!("{} drops the {}.", name, item_name)
+        },
+        _ => return,
+    };
+    info!("Player {}: {}.", player.player_name, a);
+    if !cloaked {
+        let message = Message::new(Event::Info, &message);
+        for (_, p) in players.iter().filter(|(id, p)| **id != client_id && p.location == Some(location)) {
+            p.send(message.clone()).await;
+        }
+    }
+}
+
+/// Tell the owner of an item, if the owner is in the world
+async fn tell_owner(players: &HashMap<ClientId, Player>, owner: &str, message: Message) {
+    if let Some(p) = players.values().find(|p| p.player_name.eq_ignore_ascii_case(owner)) {
+        p.send(message).await;
+    }
+}
+
+/// Perform a verb registered at runtime (see `verbs`)
+/// 
+/// The handler replies to the player. The other players in the node see its
+/// reply to them, unless they ignore the player or the player is cloaked.
+async fn plugin(a: &Action, world: &GameWorld, client_id: ClientId, players: &HashMap<ClientId, Player>) {
+    let (verb, arguments) = match a {
+        Action::Plugin { verb, arguments } => (*verb, arguments),
+        _ => return,
+    };
+    let player = match players.get(&client_id) {
+        Some(p) => p,
+        None => return,
+    };
+    let name = player.display_name();
+    let call = verbs::Call { player: &name, node: player.location.and_then(|l| world.nodes.get(l)), arguments };
+    let reply = match verbs::perform(verb, &call) {
+        Ok(reply) => reply,
+        Err(reason) => {
+            player.send(Message::new(Event::Error, &reason)).await;
+            return;
+        },
+    };
+    player.send(Message::new(Event::Response, &reply.text).with_field("action", a.to_string())).await;
+    if let (Some(text), Some(location), None) = (reply.node, player.location, &player.cloak) {
+        let message = Message::new(Event::Info, &text);
+        let others = players.iter().filter(|(id, p)| **id != client_id && p.location == Some(location)
+            && !p.account.ignores(&player.player_name));
+        for (_, p) in others {
+            p.send(message.clone()).await;
+        }
+    }
+}
+
+/// Move a player registering to the node it was in at the last checkpoint,
+/// if it still may enter it (see `checkpoint`)
+///
+/// Returns the node, None if the player spawns.
+fn resume(world: &mut GameWorld, player: &mut Player) -> Option<Index> {
+    let uid = world.resumes.remove(&player.player_name.to_lowercase())?;
+    let (index, node) = world.nodes.iter().find(|(_, n)| n.uid() == uid)?;
+    if !player.may_enter(node) || !world.seasons.allows(node.season(), timestamp()) {
+        return None;
+    }
+    info!("Player {} resumes in node {}.", player.player_name, uid);
+    player.set_spawn_point_index(index);
+    Some(index)
+}
+
+/// Return the items a player leaving the world carries to the nodes they
+/// were taken from
+fn return_items(world: &mut GameWorld, player: &mut Player) {
+    for (item, from) in player.inventory.empty() {
+        if let Some(node) = world.nodes.get_mut(from) {
+            node.add_asset(item);
+        }
+    }
+}
+
+/// Perform an action
+/// 
+/// Performs the action of a player in the world and sends the response back
+/// to the player.
+async fn perform_action(a: &Action, world: &mut GameWorld, client_id: ClientId,
+                        players: &mut HashMap<ClientId, Player>) {
+    let player_info = match players.get(&client_id) {
+        Some(p) => p,
+        None => return,
+    };
+    info!("Player {} is performing action {}.", player_info.player_name, a);
+
+    // Actions that do not depend on the location of the player
+    if let Action::Who = a {
+        let mut names: Vec<String> = players.values().map(|p| p.display_name()).collect();
+        names.sort();
+        let mut text = format!("{} deckers are jacked in:", names.len());
+        for name in names.iter() {
+            text += format!("\r\n  {}", name).as_str();
+        }
+        player_info.send(Message::new(Event::Response, &text)
+            .with_field("action", a.to_string())
+            .with_field("players", names)).await;
+        return;
+    }
+    if let Action::Tell { to, world: destination, text } = a {
+        tell(to, destination.as_deref(), text, world, client_id, players).await;
+        return;
+    }
+
+    // Currently all our actions are location specific, so get the location of the player
+    match player_info.location {
+        Some(l) => {
+            // Currently all locations are nodes. So we only need to check if the node exists.
+            // If the node does not exist, we have some inconsistency.
+            match world.nodes.get(l) {
+                Some(node) => {
+                    if let Action::Say(text) = a {
+                        say(text, world, client_id, players).await;
+                        return;
+                    }
+                    if let Action::Shout(text) = a {
+                        // Hostile NPCs hear the shout on the next tick
+                        if let Some(p) = players.get_mut(&client_id) {
+                            p.shouted = true;
+                        }
+                        shout(text, l, world, client_id, players).await;
+                        return;
+                    }
+                    if let Action::Access { target, ordinal, .. } = a {
+                        access(target.as_deref(), *ordinal, node, l, client_id, players).await;
+                        return;
+                    }
+                    if let Action::Solve { target, ordinal, .. } | Action::Hint { target, ordinal } = a {
+                        if let Some(player) = players.get_mut(&client_id) {
+                            match node.find_asset(target, *ordinal) {
+                                Ok(asset) => puzzle(a, asset, world, player).await,
+                                Err(reason) => player.send(Message::new(Event::Error, &reason)).await,
+                            }
+                        }
+                        return;
+                    }
+
+                    if let Action::Cloak(_) | Action::Scan = a {
+                        cloak(a, l, client_id, players).await;
+                        return;
+                    }
+
+                    // Looking through a port shows the node it leads to
+                    if let Action::Look { target: Some(t), ordinal, preposition: Some(p), .. } = a {
+                        if p == "through" {
+                            look_through(a, t, *ordinal, node, world, player_info, players).await;
+                            return;
+                        }
+                    }
+
+                    // Neither are players, looking at them (or at "me") shows
+                    // their appearance
+                    if let Action::Look { target: Some(t), .. } = a {
+                        let other = players.iter().find(|(id, p)| match t.eq_ignore_ascii_case("me") {
+                            true => **id == client_id,
+                            false => p.location == Some(l) && p.cloak.is_none()
+                                && p.player_name.eq_ignore_ascii_case(t),
+                        });
+                        if let (true, Some((id, other))) = (node.find_assets(t).is_empty(), other) {
+                            let run = world.challenges.joined(*id).map(|r| r.challenge());
+                            let appearance = world.wardrobe.appearance(&other.profile(run));
+                            player_info.send(Message::new(Event::Response, &appearance)
+                                .with_field("action", a.to_string())
+                                .with_field("player", other.player_name.clone())).await;
+                            return;
+                        }
+                    }
+
+                    // NPCs are not assets of the node, look at them directly
+                    if let Action::Look { target: Some(t), ordinal, .. } = a {
+                        if let (true, Some(npc)) = (node.find_assets(t).is_empty(), world.npcs.find(l, t, *ordinal)) {
+                            let mut description = npc.description().to_string();
+                            if npc.scale() > difficulty::BASE_SCALE {
+                                description += &format!(" It is hardened to {}% against the deckers here.",
+                                    npc.scale());
+                            }
+                            if let Some(line) = npc.line() {
+                                description += &format!(" The {} says: \"{}\"", npc.name(), line);
+                            }
+                            if let Some(player) = players.get(&client_id) {
+                                player.send(Message::new(Event::Response, &description)
+                                    .with_field("action", a.to_string())
+                                    .with_field("npc", npc.uid)
+                                    .with_field("scale", npc.scale())).await;
+                            }
+                            return;
+                        }
+                    }
+
+                    // Send the action to the node. The node itself will take care to
+                    // relay the action to the necessary contents of itself.
+                    //
+                    // TODO - this mechanism currently limits action radius to one node
+                    //          we may want to implement either other nodes receiveing as well
+                    //          or even a generic listener that sends it to all assets?
+                    let span = info_span!("react_to", node = node.uid(), elapsed_us = field::Empty);
+                    let start = Instant::now();
+                    let actor = assets::Actor::new(&player_info.player_name, &player_info.account.puzzles);
+                    let response = span.in_scope(|| world.nodes[l].react_to(&actor, a));
+                    span.record("elapsed_us", start.elapsed().as_micros() as u64);
+                    let node = &world.nodes[l];
+                    let mut response_message = response.text;
+
+                    // When looking around, the player also sees who else is in the node
+                    let occupants: Vec<String> = players.iter()
+                        .filter(|(id, p)| **id != client_id && p.location == Some(l) && p.cloak.is_none())
+                        .map(|(_, p)| match (p.drone_deployed, &p.account.drone) {
+                            (true, Some(drone)) => format!("{} (with a {} drone)", p.display_name(), drone.model),
+                            _ => p.display_name(),
+                        })
+                        .collect();
+                    let npcs: Vec<String> = world.npcs.in_node(l)
+                        .map(|n| match (n.is_hostile(), n.target() == Some(&player_info.player_name)) {
+                            (true, true) => format!("a {} (hostile, tracing you)", n.name()),
+                            (true, false) => format!("a {} (hostile)", n.name()),
+                            _ => format!("a {}", n.name()),
+                        })
+                        .collect();
+                    let present: Vec<String> = occupants.iter().cloned().chain(npcs.iter().cloned()).collect();
+                    if let (Action::Look { target: None, .. }, false) = (a, present.is_empty()) {
+                        response_message += format!("Also here: {}.\r\n", present.join(", ")).as_str();
+                    }
+
+                    player_info.send(Message::new(Event::Response, &response_message)
+                        .with_field("action", a.to_string())
+                        .with_field("node", node.uid())
+                        .with_field("occupants", occupants)
+                        .with_field("npcs", npcs)).await;
+                    if let Some(sound) = node.sound_for(a) {
+                        player_info.play(sound, &world.media_url).await;
+                    }
+                    if player_info.cloak.is_none() {
+                        for event in &response.events {
+                            let message = Message::new(Event::Info, event);
+                            for (_, p) in players.iter().filter(|(id, p)| **id != client_id && p.location == Some(l)) {
+                                p.send(message.clone()).await;
+                            }
+                        }
+                    }
+                    for change in &response.changes {
+                        changed(change, world, players).await;
+                    }
+                    if let Some(player) = players.get_mut(&client_id) {
+                        for effect in &response.effects {
+                            run_hook(effect, world, player).await;
+                        }
+                    }
+                },
+                None => {
+                    error!("Location index cannot be mapped to node: {:?}", l);
+                    player_info.send(Message::new(Event::Error, "A glitch in the matrix occured.")).await;
+                },
             }
+        },
+        None => {
+            // Check if this action is location independent - TODO currently no actions are location independen
+            warn!("User does not have a location. Command ignored.");
+            let message = "In limbo everything is possible. And nothing. Makes you wonder...";
+            player_info.send(Message::new(Event::Error, message)).await;
+        },
+    }
+}
 
-            // A player performed an interaction with the game world (data command). Process it.
-            Some(data_message) = data_rx.recv() => {
-                debug!("Received data. Processing: {:?} from data_tx of client {}", data_message.data, data_message.client_id);
-                process_data(data_message, &world, &players).await;   
-            }
-            else => {
-                error!("Both channels closed");
+/// Tell the world how an asset changed reacting to an action (see
+/// `assets::AssetResponse`)
+///
+/// The players on the other side of a port opened see it open.
+async fn changed(change: &assets::StateChange, world: &GameWorld, players: &HashMap<ClientId, Player>) {
+    match change {
+        assets::StateChange::Opened(uid) => {
+            info!("Port {} was opened.", uid);
+            let destination = world.nodes.iter()
+                .find_map(|(_, n)| n.assets().find(|a| a.uid() == *uid)?.port()?.destination());
+            if let Some(index) = destination {
+                send_to_node(players, index, Message::new(Event::Info, "A port leading here opens.")).await;
             }
-        }
-    } 
+        },
+    }
 }
 
-/// Handle commands
+/// Look through a port at the node it leads to
 /// 
-/// This function processes commands to the game engine. Commands are usually
-/// issued by a client.
-async fn process_command(command: Command, world: &GameWorld, players : &mut HashMap<ClientId, Player>) {
-    match command {
-        // Register a new player to the game
-        Command::Register(client_id, username, channel_id, mut handle) => {
-            // TODO - check if player is alread registered and using another session
-            let mut player = Player::new(username, (channel_id, handle.clone()));
-            match world.spawn(&mut player) {
-                Ok(_) => {
-                    players.insert(client_id, player);
-
-                    // Display the welcome screen
-                    // Open the file for the welcome screen and display it. If the file is not found
-                    // (an error is sent to stderr and nothing is sent back to the client.)
-                    match ScreenType::Welcome.display_ansi() {
-                        // If we receive a valid screen, we send it on the channel. Otherwise we send nothing
-                        // and write an error message to stderr
-                        Ok(buf) => {
-                            //session.data(channel, None, buf.as_ref());
-                            handle.data(channel_id, CryptoVec::from_slice(
-                                buf.as_ref()))
-                                .await.expect("Could not send registration msg.");
-                        },
-                        Err(e) => error!("Error sending welcome screen to client: {}", e),
-                    };
-                },
-                Err(_) => todo!(), // TODO - Send error screen and kill the conneciton
-            };
+/// Only open ports can be seen through. The player sees a glimpse of the
+/// node (see `Node::glimpse`) and who is there, but no details.
+async fn look_through(a: &Action, target: &str, ordinal: Option<usize>, node: &assets::Node, world: &GameWorld,
+                      player: &Player, players: &HashMap<ClientId, Player>) {
+    let port = match node.find_asset(target, ordinal) {
+        Ok(asset) => asset,
+        Err(reason) => {
+            player.send(Message::new(Event::Error, &reason)).await;
+            return;
+        },
+    };
+    let name = port.name();
+    let destination = match port.port() {
+        None => Err(format!("You cannot look through the {}.", name)),
+        Some(p) if p.portal().is_some() => Err(format!("The {} leads to another world, it is all static.", name)),
+        Some(p) if !p.is_open() => Err(format!("The {} is closed, you cannot see through it.", name)),
+        Some(p) => p.destination().and_then(|d| world.nodes.get(d).map(|n| (d, n)))
+            .ok_or_else(|| format!("The {} leads nowhere.", name)),
+    };
+    let (index, other) = match destination {
+        Ok(d) => d,
+        Err(reason) => {
+            player.send(Message::new(Event::Error, &reason)).await;
+            return;
         },
-        Command::Hangup(_) => todo!(),
     };
+    let mut seen: Vec<String> = players.values()
+        .filter(|p| p.location == Some(index) && p.cloak.is_none())
+        .map(|p| p.display_name())
+        .collect();
+    seen.extend(world.npcs.in_node(index).map(|n| match n.is_hostile() {
+        true => format!("a {} (hostile)", n.name()),
+        false => format!("a {}", n.name()),
+    }));
+    let mut text = format!("Through the {} you see:\r\n{}", name, other.glimpse());
+    if !seen.is_empty() {
+        text += &format!("\r\nOver there: {}.", seen.join(", "));
+    }
+    player.send(Message::new(Event::Response, &text)
+        .with_field("action", a.to_string())
+        .with_field("node", other.uid())).await;
 }
 
-/// Handle data messages
+/// Engage or drop the cloak of a player, or scan the node for cloaked
+/// players
 /// 
-/// A data message usually is a player action. This function tries to decode
-/// the data message and then act accordingly.
-async fn process_data(data_message: DataMessage, world: &GameWorld, players: &HashMap<ClientId, Player>) {
-    // Check if the data message can be matched on an active player. If no
-    // active player is known then the data message gets discarded.
-    match players.get(&data_message.client_id) {
-        Some(player_info) => {
-
-            // Check if the player did a proper action
-            match Action::try_from(data_message.data.clone()) {
-                Ok(a) => {
-                    info!("Player {} is performing action {}.", player_info.player_name, a);
-
-                    // Currently all our actions are location specific, so get the location of the player
-                    match player_info.location {
-                        Some(l) => {
-                            // Currently all locations are nodes. So we only need to check if the node exists.
-                            // If the node does not exist, we have some inconsistency.
-                            match world.nodes.get(l) {
-                                Some(node) => {
-                                    // Send the action to the node. The node itself will take care to
-                                    // relay the action to the necessary contents of itself.
-                                    //
-                                    // TODO - this mechanism currently limits action radius to one node
-                                    //          we may want to implement either other nodes receiveing as well
-                                    //          or even a generic listener that sends it to all assets?
-                                    let response_message = node.react_to(&a);
-
-                                    player_info.active_session.1.clone().data(player_info.active_session.0, 
-                                        CryptoVec::from_slice(format!("{}\r\n",response_message).as_ref()))
-                                        .await.expect("Could not send data message to client.".as_ref());
-                                },
-                                None => {
-                                    error!("Location index cannot be mapped to node: {:?}", l);
-                                    player_info.active_session.1.clone().data(player_info.active_session.0, 
-                                        CryptoVec::from_slice("A glitch in the matrix occured.\r\n".as_ref()))
-                                        .await.expect("Could not send data message to client.".as_ref());
-                                },
-                            }
-                        },
-                        None => {
-                            // Check if this action is location independent - TODO currently no actions are location independen
-                            warn!("User does not have a location. Command ignored.");
-                            let message = "In limbo everything is possible. And nothing. Makes you wonder...\r\n";
-                            player_info.active_session.1.clone().data(player_info.active_session.0, 
-                                CryptoVec::from_slice(message.as_ref()))
-                                .await.expect("Could not send data message to client.".as_ref());
-                        },
-                    }
+/// The players revealed by a scan lose their cloak (see `stealth`).
+async fn cloak(a: &Action, location: Index, client_id: ClientId, players: &mut HashMap<ClientId, Player>) {
+    if let Action::Cloak(engage) = a {
+        let player = match players.get_mut(&client_id) {
+            Some(p) => p,
+            None => return,
+        };
+        let message = match (engage, player.cloak.is_some()) {
+            (true, true) => Message::new(Event::Error, "You are cloaked already."),
+            (true, false) => {
+                player.cloak = Some(stealth::Cloak::new());
+                Message::new(Event::Response, "You engage your cloak. Others no longer see you.")
+            },
+            (false, true) => {
+                player.cloak = None;
+                Message::new(Event::Response, "You drop your cloak.")
+            },
+            (false, false) => Message::new(Event::Error, "You are not cloaked."),
+        };
+        player.send(message.with_field("cloaked", player.cloak.is_some())).await;
+        return;
+    }
 
-                },
-                Err(e) => {
-                    // Not a valid aciton, tell the player
-                    debug!("User used unkown command: {}", e);
-                    let message = "Error 23: Command not found.\r\n";
-                            player_info.active_session.1.clone().data(player_info.active_session.0, 
-                                CryptoVec::from_slice(message.as_ref()))
-                                .await.expect("Could not send data message to client.".as_ref());
+    let by = match players.get(&client_id) {
+        Some(p) => format!("The scanner of {}", p.player_name),
+        None => return,
+    };
+    let revealed = scan(location, client_id, players, &by).await;
+    let text = if revealed.is_empty() {
+        "Your scanner finds nobody hiding.".to_string()
+    } else {
+        format!("Your scanner reveals: {}.", revealed.join(", "))
+    };
+    if let Some(p) = players.get(&client_id) {
+        p.send(Message::new(Event::Response, &text).with_field("revealed", revealed)).await;
+    }
+}
 
-                },
+/// Reveal the cloaked players in a node to a player scanning it
+/// 
+/// The players revealed are told what revealed them ("The scanner of neo").
+/// Returns the names of the players revealed.
+async fn scan(location: Index, client_id: ClientId, players: &mut HashMap<ClientId, Player>, by: &str) -> Vec<String> {
+    let mut revealed = Vec::new();
+    for (_, p) in players.iter_mut().filter(|(id, p)| **id != client_id && p.location == Some(location)) {
+        if p.cloak.take().is_some() {
+            p.send(Message::new(Event::Info, &format!("{} reveals you.", by)).with_field("cloaked", false)).await;
+            revealed.push(p.display_name());
+        }
+    }
+    revealed
+}
+
+/// Say something
+/// 
+/// Everybody in the same node as the speaking player hears what it says. The
+/// content filter is applied for every listener that did not opt out.
+async fn say(text: &str, world: &GameWorld, client_id: ClientId, players: &HashMap<ClientId, Player>) {
+    let speaker = match players.get(&client_id) {
+        Some(p) => p,
+        None => return,
+    };
+    let speaker_name = speaker.display_name();
+    let listeners = players.iter()
+        .filter(|(_, p)| p.location == speaker.location && !p.account.ignores(&speaker.player_name));
+    for (id, listener) in listeners {
+        let message = if *id == client_id {
+            format!("You say: {}", text)
+        } else if listener.account.unfiltered {
+            format!("{} says: {}", speaker_name, text)
+        } else {
+            format!("{} says: {}", speaker_name, world.content_filter.apply(text))
+        };
+        // Chat caused by a trigger does not fire the triggers of the listeners
+        listener.send_with_triggers(Message::new(Event::Chat, &message)
+            .with_field("from", speaker.player_name.clone()), !speaker.performing_triggered).await;
+    }
+    if let (Some(zone), Some(bridge)) = (speaker.location, &world.matrix) {
+        if bridge.mirrors(world.nodes[zone].name()) {
+            bridge.say(world.nodes[zone].name(), &speaker_name, &world.content_filter.apply(text));
+        }
+    }
+    if let Some(zone) = speaker.location {
+        for watcher in watchers(world, players, zone).filter(|w| !w.account.ignores(&speaker.player_name)) {
+            let text = if watcher.account.unfiltered { text.to_string() } else { world.content_filter.apply(text) };
+            let message = format!("[{}] {} says: {}", world.nodes[zone].name(), speaker_name, text);
+            watcher.send_with_triggers(Message::new(Event::Chat, &message)
+                .with_field("from", speaker.player_name.clone())
+                .with_field("zone", world.nodes[zone].name()), false).await;
+        }
+    }
+}
+
+/// Shout something
+///
+/// Heard by everybody in the node of the shouting player and in the nodes
+/// next to it (see `GameWorld::within_earshot`), the listeners elsewhere are
+/// told where the shout came from.
+async fn shout(text: &str, zone: Index, world: &GameWorld, client_id: ClientId, players: &HashMap<ClientId, Player>) {
+    let speaker = match players.get(&client_id) {
+        Some(p) => p,
+        None => return,
+    };
+    let speaker_name = speaker.display_name();
+    let heard = world.propagate(zone, propagation::Noise::Shout);
+    for (id, listener) in players.iter().filter(|(_, p)| !p.account.ignores(&speaker.player_name)) {
+        let level = match listener.location.and_then(|l| heard.iter().find(|(n, _)| *n == l)) {
+            Some((_, level)) => *level,
+            None => continue,
+        };
+        let filtered = if listener.account.unfiltered { text.to_string() } else { world.content_filter.apply(text) };
+        let text = if *id == client_id { text.to_string() } else { filtered };
+        let message = match level {
+            _ if *id == client_id => format!("You shout: {}", text),
+            propagation::Level::Clear => format!("{} shouts: {}", speaker_name, text),
+            propagation::Level::Muffled if world.nodes[zone].name().is_empty() => {
+                format!("{} shouts from nearby: {}", speaker_name, text)
+            },
+            propagation::Level::Muffled => {
+                format!("{} shouts from the {}: {}", speaker_name, world.nodes[zone].name(), text)
+            },
+            // Too far to make out the words or who shouts
+            propagation::Level::Faint => {
+                listener.send(Message::new(Event::Info, "You hear a faint shout in the distance.")).await;
+                continue;
+            },
+        };
+        // Chat caused by a trigger does not fire the triggers of the listeners
+        listener.send_with_triggers(Message::new(Event::Chat, &message)
+            .with_field("from", speaker.player_name.clone()), !speaker.performing_triggered).await;
+    }
+}
+
+/// Tell a player something
+///
+/// Tells to a player of the linked world are passed over the link. The
+/// peer answers if the player is not connected there.
+async fn tell(to: &str, destination: Option<&str>, text: &str, world: &GameWorld, client_id: ClientId,
+              players: &HashMap<ClientId, Player>) {
+    let speaker = match players.get(&client_id) {
+        Some(p) => p,
+        None => return,
+    };
+    let local = match (destination, &world.federation) {
+        (None, _) => true,
+        (Some(d), Some(link)) if link.name.eq_ignore_ascii_case(d) => true,
+        (Some(d), Some(link)) if link.is_peer(d) => {
+            let message = federation::Message::Tell { from: speaker.player_name.clone(), to: to.to_string(),
+                text: text.to_string() };
+            if link.send(message) {
+                speaker.send(Message::new(Event::Chat, &format!("You tell {}@{}: {}", to, link.peer_name, text))
+                    .with_field("to", format!("{}@{}", to, link.peer_name))).await;
+            } else {
+                let reason = format!("The link to {} is down.", link.peer_name);
+                speaker.send(Message::new(Event::Error, &reason)).await;
             }
+            false
+        },
+        (Some(d), _) => {
+            speaker.send(Message::new(Event::Error, &format!("There is no link to {}.", d))).await;
+            false
         },
-        None => error!("Received data message but no active player found for the client that sent the message."),
     };
+    if !local {
+        return;
+    }
+
+    match players.values().find(|p| p.player_name.eq_ignore_ascii_case(to)) {
+        Some(listener) if speaker.role == Role::Player && !listener.account.consent
+                .allows(consent::Interaction::Tell { stranger: listener.location != speaker.location }) => {
+            let message = format!("{} does not accept tells from strangers. Find them in the matrix.", 
+                listener.player_name);
+            speaker.send(Message::new(Event::Error, &message)).await;
+        },
+        Some(listener) => {
+            speaker.send(Message::new(Event::Chat, &format!("You tell {}: {}", listener.player_name, text))
+                .with_field("to", listener.player_name.clone())).await;
+            // The speaker is not told it is ignored
+            if listener.account.ignores(&speaker.player_name) {
+                return;
+            }
+            let text = if listener.account.unfiltered { text.to_string() } else { world.content_filter.apply(text) };
+            listener.send_with_triggers(Message::new(Event::Chat,
+                    &format!("{} tells you: {}", speaker.display_name(), text))
+                .with_field("from", speaker.player_name.clone()), !speaker.performing_triggered).await;
+        },
+        None => {
+            speaker.send(Message::new(Event::Error, &format!("{} is not jacked in.", to))).await;
+        },
+    }
+}
+
+/// The players watching a zone from elsewhere
+/// 
+/// Nobody watches a zone that is not spectated.
+fn watchers<'a>(world: &GameWorld, players: &'a HashMap<ClientId, Player>, zone: Index) 
+        -> impl Iterator<Item = &'a Player> {
+    let spectated = world.spectated.contains(&zone);
+    players.values().filter(move |p| spectated && p.watching == Some(zone) && p.location != Some(zone))
 }
 
 /// GameWorld
 /// 
 /// The structure describing the game world.
+#[allow(dead_code)]
 #[derive(Debug)]
 pub struct GameWorld {
     name: String,
@@ -164,6 +2794,81 @@ pub struct GameWorld {
     spawn_nodes: Vec<Index>, 
     nodes: Arena<assets::Node>,
     players: Vec<Player>, // Not sure we should include the players in the world? TODO replace with arena
+    data_dir: PathBuf,
+    roles: HashMap<String, Role>,
+    handle_rules: handles::HandleRules,
+    content_filter: filter::ContentFilter,
+    channel_metrics: Metrics,
+    loop_stats: stats::LoopStats,
+    heartbeat: stats::Heartbeat,
+    log_filter: LogFilter,
+    visitors: visitors::Visitors,
+    screens: states::Screens,
+    banners: banners::Banners,
+    help: help::Help,
+    media_url: String,
+    challenges: challenges::Challenges,
+    spectated: HashSet<Index>,
+    federation: Option<federation::Link>,
+    gate: Option<worlds::Gate>,
+    arrivals: federation::Arrivals,
+    matrix: Option<matrix::Bridge>,
+    news: news::News,
+    login_tokens: Option<LoginTokens>,
+    /// The keys allowed to connect (shared with the ssh server), to forget
+    /// the keys bound to a player
+    keyring: Keyring,
+    ssh_address: (String, u32),
+    /// Only staff may log in
+    maintenance: bool,
+    /// When the world reopens after the maintenance, if scheduled
+    reopens: Option<Instant>,
+    clock: clock::Clock,
+    resets: resets::Resets,
+    delays: delays::Delays,
+    timers: timers::Timers,
+    /// Interval the world saves a checkpoint in, zero for never (see
+    /// `checkpoint`)
+    autosave: Duration,
+    /// The nodes the players were in at the last checkpoint, by name, until
+    /// they resume there
+    resumes: BTreeMap<String, assets::AssetID>,
+    /// The checkpoint being written in the background, if any
+    checkpointing: Option<tokio::task::JoinHandle<()>>,
+    history: edits::History,
+    drafts: edits::Drafts,
+    sandboxes: sandboxes::Sandboxes,
+    npcs: npcs::Npcs,
+    market: market::Market,
+    /// The heat of the players and the ICE security dispatches (see
+    /// `security`)
+    security: security::Security,
+    /// The bosses and the fights with them (see `bosses`)
+    bosses: bosses::Bosses,
+    /// The intrusions into the ICE guarding ports (see `ice`)
+    intrusions: ice::Intrusions,
+    /// The collective goals and the world flags they set (see `goals`)
+    goals: goals::Goals,
+    /// The poll running, if any (see `polls`)
+    poll: Option<polls::Poll>,
+    /// The events of the con (see `schedule`)
+    schedule: schedule::Schedule,
+    /// The nodes players trade at the market in
+    markets: HashSet<Index>,
+    /// The nodes players reach their vaults in
+    banks: HashSet<Index>,
+    casino: casino::Casino,
+    /// The nodes players play at the casino in
+    casinos: HashSet<Index>,
+    vendors: HashMap<Index, economy::Vendor>,
+    economy: economy::Economy,
+    ledger: economy::Ledger,
+    analytics: analytics::Analytics,
+    seasons: seasons::Seasons,
+    /// The seasons active on the last tick
+    active_seasons: Vec<String>,
+    rewards: streaks::Rewards,
+    wardrobe: appearance::Wardrobe,
 }
 
 impl GameWorld {
@@ -175,9 +2880,325 @@ impl GameWorld {
             spawn_nodes: Vec::new(),
             nodes: Arena::new(),
             players: Vec::new(),
+            data_dir: PathBuf::from("data"),
+            roles: HashMap::new(),
+            handle_rules: handles::HandleRules::default(),
+            content_filter: filter::ContentFilter::default(),
+            channel_metrics: Metrics::default(),
+            loop_stats: stats::LoopStats::default(),
+            heartbeat: stats::Heartbeat::default(),
+            log_filter: LogFilter::default(),
+            visitors: visitors::Visitors::default(),
+            screens: states::Screens::default(),
+            banners: banners::Banners::default(),
+            help: help::Help::default(),
+            media_url: String::new(),
+            challenges: challenges::Challenges::default(),
+            spectated: HashSet::new(),
+            federation: None,
+            gate: None,
+            arrivals: federation::Arrivals::default(),
+            matrix: None,
+            news: news::News::new(),
+            login_tokens: None,
+            keyring: Keyring::default(),
+            ssh_address: ("localhost".to_string(), 2222),
+            maintenance: false,
+            reopens: None,
+            clock: clock::Clock::default(),
+            resets: resets::Resets::default(),
+            delays: delays::Delays::default(),
+            timers: timers::Timers::default(),
+            autosave: Duration::ZERO,
+            resumes: BTreeMap::new(),
+            checkpointing: None,
+            history: edits::History::default(),
+            drafts: edits::Drafts::default(),
+            sandboxes: sandboxes::Sandboxes::default(),
+            npcs: npcs::Npcs::default(),
+            market: market::Market::default(),
+            security: security::Security::default(),
+            bosses: bosses::Bosses::default(),
+            intrusions: ice::Intrusions::default(),
+            goals: goals::Goals::default(),
+            poll: None,
+            schedule: schedule::Schedule::default(),
+            markets: HashSet::new(),
+            banks: HashSet::new(),
+            casino: casino::Casino::new(0, casino::Limits { max_wager: 0, max_loss: 0 }),
+            casinos: HashSet::new(),
+            vendors: HashMap::new(),
+            economy: economy::Economy::default(),
+            ledger: economy::Ledger::default(),
+            analytics: analytics::Analytics::default(),
+            seasons: seasons::Seasons::default(),
+            active_seasons: Vec::new(),
+            rewards: streaks::Rewards::default(),
+            wardrobe: appearance::Wardrobe::default(),
+        }
+    }
+
+    /// Set the screens shown to the players
+    pub fn set_screens(&mut self, screens: states::Screens) {
+        self.screens = screens;
+    }
+
+    /// Set the help topics
+    pub fn set_help(&mut self, help: help::Help) {
+        self.help = help;
+    }
+
+    /// Set the fonts banners are rendered with
+    pub fn set_banners(&mut self, banners: banners::Banners) {
+        self.banners = banners;
+    }
+
+    /// Set the URL clients fetch sound files from (empty if the clients
+    /// have the files)
+    pub fn set_media_url(&mut self, media_url: String) {
+        self.media_url = media_url;
+    }
+
+    /// Set the statistics of the channels to the world (shown to admins)
+    pub fn set_channel_metrics(&mut self, channel_metrics: Metrics) {
+        self.channel_metrics = channel_metrics;
+    }
+
+    /// Set the heartbeat the world beats on every tick (read by the health
+    /// check)
+    pub fn set_heartbeat(&mut self, heartbeat: stats::Heartbeat) {
+        self.heartbeat = heartbeat;
+    }
+
+    /// Add a challenge players can start runs of
+    pub fn add_challenge(&mut self, challenge: challenges::Challenge) {
+        self.challenges.add(challenge);
+    }
+
+    /// Add a spawner of NPCs
+    pub fn add_spawner(&mut self, spawner: npcs::Spawner) {
+        self.npcs.add_spawner(spawner);
+    }
+
+    /// Set the prototype of the ICE security dispatches to offenders
+    pub fn set_responder(&mut self, responder: npcs::Prototype) {
+        self.security.set_responder(responder);
+    }
+
+    /// Add a boss fought in raids
+    pub fn add_boss(&mut self, boss: bosses::Boss) {
+        self.bosses.add(boss);
+    }
+
+    /// Set the pending timers (eg. loaded from the data directory)
+    pub fn set_timers(&mut self, timers: timers::Timers) {
+        self.timers = timers;
+    }
+
+    /// Set the schedule of the con (eg. loaded from the data directory)
+    pub fn set_schedule(&mut self, schedule: schedule::Schedule) {
+        self.schedule = schedule;
+    }
+
+    /// Set the goals (eg. with their progress loaded from the data
+    /// directory) and apply the changes of the flags set
+    pub fn set_goals(&mut self, goals: goals::Goals) {
+        let changes: Vec<goals::Change> = goals.reached().flat_map(|g| g.changes().to_vec()).collect();
+        self.goals = goals;
+        for change in &changes {
+            self.apply_change(change);
+        }
+    }
+
+    /// Apply a change of the world made by a flag (see `goals`)
+    fn apply_change(&mut self, change: &goals::Change) {
+        match change {
+            goals::Change::Describe { uid, text } => {
+                for (_, node) in self.nodes.iter_mut() {
+                    if node.uid() == *uid {
+                        node.rewrite(text);
+                    } else if let Some(asset) = node.asset_mut(*uid) {
+                        asset.rewrite(text);
+                    }
+                }
+            },
+            goals::Change::Open { port } => {
+                let opened = self.nodes.iter_mut().find_map(|(_, n)| n.asset_mut(*port)?.port_mut());
+                match opened {
+                    Some(p) => p.update_open(true),
+                    None => warn!("A goal cannot open port {}, it does not exist.", port),
+                }
+            },
+            goals::Change::Say { npc, line } => self.npcs.set_line(npc, line),
+        }
+    }
+
+    /// Set the interval the world saves a checkpoint in, zero for never
+    pub fn set_autosave(&mut self, autosave: Duration) {
+        self.autosave = autosave;
+    }
+
+    /// Apply a checkpoint (eg. loaded from the data directory) to the world
+    /// built
+    ///
+    /// Returns the number of nodes and assets restored.
+    pub fn restore(&mut self, checkpoint: checkpoint::Checkpoint) -> usize {
+        let restored = checkpoint.restore(&mut self.nodes);
+        self.resumes = checkpoint.players;
+        restored
+    }
+
+    /// Set the listings of the market and the mail of the players
+    pub fn set_market(&mut self, market: market::Market) {
+        self.market = market;
+    }
+
+    /// Open the market in a node
+    pub fn add_market(&mut self, node: Index) {
+        self.markets.insert(node);
+    }
+
+    /// Open a bank in a node
+    pub fn add_bank(&mut self, node: Index) {
+        self.banks.insert(node);
+    }
+
+    /// Set the casino with the seed of its games and the limits of the
+    /// house
+    pub fn set_casino(&mut self, casino: casino::Casino) {
+        self.casino = casino;
+    }
+
+    /// Open the casino in a node
+    pub fn add_casino(&mut self, node: Index) {
+        self.casinos.insert(node);
+    }
+
+    /// Set the ledger the credits created and destroyed are booked in
+    /// (shared with the telemetry)
+    pub fn set_ledger(&mut self, ledger: economy::Ledger) {
+        self.ledger = ledger;
+    }
+
+    /// Set the analytics counting what the players do (shared with the
+    /// telemetry)
+    pub fn set_analytics(&mut self, analytics: analytics::Analytics) {
+        self.analytics = analytics;
+    }
+
+    /// Set the rewards of the login streaks
+    pub fn set_rewards(&mut self, rewards: streaks::Rewards) {
+        self.rewards = rewards;
+    }
+
+    /// Set the cosmetic items of the world
+    pub fn set_wardrobe(&mut self, wardrobe: appearance::Wardrobe) {
+        self.wardrobe = wardrobe;
+    }
+
+    /// Add a season limited-time content is tagged with
+    pub fn add_season(&mut self, name: &str, season: seasons::Season) {
+        self.seasons.add(name, season);
+    }
+
+    /// Open the shop of a vendor in a node
+    pub fn add_vendor(&mut self, node: Index, vendor: economy::Vendor) {
+        self.vendors.insert(node, vendor);
+    }
+
+    /// Add the reset of a zone
+    pub fn add_reset(&mut self, reset: resets::ZoneReset) {
+        self.resets.add(reset);
+    }
+
+    /// Set the best results of the challenges so far
+    pub fn set_leaderboard(&mut self, leaderboard: challenges::Leaderboard) {
+        self.challenges.leaderboard = leaderboard;
+    }
+
+    /// The name of the world
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Set the gate to the other worlds of the server
+    pub fn set_gate(&mut self, gate: worlds::Gate) {
+        self.gate = Some(gate);
+    }
+
+    /// Set the link to the peer world
+    pub fn set_federation(&mut self, link: federation::Link) {
+        self.federation = Some(link);
+    }
+
+    /// Set the login tokens (shared with the ssh server) and the address
+    /// players connect to with them
+    pub fn set_login_tokens(&mut self, login_tokens: LoginTokens, ssh_host: &str, ssh_port: u32) {
+        self.login_tokens = Some(login_tokens);
+        self.ssh_address = (ssh_host.to_string(), ssh_port);
+    }
+
+    /// Set the keys allowed to connect (shared with the ssh server)
+    pub fn set_keyring(&mut self, keyring: Keyring) {
+        self.keyring = keyring;
+    }
+
+    /// Set the news of the world (shared with the feed)
+    pub fn set_news(&mut self, news: news::News) {
+        self.news = news;
+    }
+
+    /// Set the bridge to the Matrix room
+    pub fn set_matrix(&mut self, bridge: matrix::Bridge) {
+        self.matrix = Some(bridge);
+    }
+
+    /// Set the unique visitors of the event so far
+    pub fn set_visitors(&mut self, visitors: visitors::Visitors) {
+        self.visitors = visitors;
+    }
+
+    /// Set the filter of the log (changed by the admins)
+    pub fn set_log_filter(&mut self, log_filter: LogFilter) {
+        self.log_filter = log_filter;
+    }
+
+    /// Set the filter applied to text players send to each other
+    pub fn set_content_filter(&mut self, content_filter: filter::ContentFilter) {
+        self.content_filter = content_filter;
+    }
+
+    /// Set the rules handles and titles of players must follow
+    pub fn set_handle_rules(&mut self, handle_rules: handles::HandleRules) {
+        self.handle_rules = handle_rules;
+    }
+
+    /// Set the directory the world stores its data in (eg. bug reports)
+    pub fn set_data_dir(&mut self, data_dir: PathBuf) {
+        self.data_dir = data_dir;
+    }
+
+    /// Grant a role to the player with the given name
+    /// 
+    /// The role is applied when the player registers with the world.
+    pub fn set_role(&mut self, player_name: &str, role: Role) {
+        self.roles.insert(player_name.to_lowercase(), role);
+    }
+
+    /// Text shown to players turned away during the maintenance
+    fn maintenance_notice(&self, now: Instant) -> String {
+        match self.reopens {
+            Some(r) => format!("\r\nThe matrix is down for maintenance. It reopens in about {} minutes.\r\n", 
+                r.saturating_duration_since(now).as_secs().div_ceil(60)),
+            None => "\r\nThe matrix is down for maintenance. Please come back later.\r\n".to_string(),
         }
     }
 
+    /// Get the role of the player with the given name (ignoring case)
+    pub fn role_of(&self, player_name: &str) -> Role {
+        self.roles.get(&player_name.to_lowercase()).copied().unwrap_or(Role::Player)
+    }
+
     /// Add a node to the game world and marks it as a spawn node
     /// 
     /// If the world did not have this node present, None is returned.
@@ -201,9 +3222,59 @@ impl GameWorld {
         Some(self.nodes.insert(node))
     }
 
+    /// Connect a port to a node, both given by their uid
+    /// 
+    /// Returns false if there is no such port or node.
+    pub fn connect(&mut self, port: assets::AssetID, node: assets::AssetID) -> bool {
+        loader::connect(&mut self.nodes, port, node)
+    }
+
+    /// The name of a node rendered as banner
+    /// 
+    /// Returns None for nodes without a name.
+    pub fn zone_title(&self, index: Index) -> Option<String> {
+        let node = self.nodes.get(index)?;
+        if node.name().is_empty() {
+            return None;
+        }
+        match self.banners.render(node.name(), None) {
+            Ok(title) => Some(title),
+            Err(e) => {
+                error!("Could not render the title of node {}: {}", node.uid(), e);
+                None
+            },
+        }
+    }
+
+    /// Find a zone by its name (ignoring case)
+    pub fn find_zone(&self, name: &str) -> Option<Index> {
+        self.nodes.iter()
+            .find(|(_, n)| !n.name().is_empty() && n.name().eq_ignore_ascii_case(name))
+            .map(|(i, _)| i)
+    }
+
+    /// The nodes next to a node: the node itself, the nodes its ports lead
+    /// to and the nodes with ports leading to it
+    pub fn within_earshot(&self, node: Index) -> Vec<Index> {
+        let mut nodes = vec![node];
+        if let Some(n) = self.nodes.get(node) {
+            nodes.extend(n.exits());
+        }
+        nodes.extend(self.nodes.iter().filter(|(_, n)| n.exits().any(|e| e == node)).map(|(i, _)| i));
+        let mut seen = HashSet::new();
+        nodes.retain(|i| seen.insert(*i));
+        nodes
+    }
+
+    /// The nodes a noise in a node is heard in and how (see `propagation`)
+    pub fn propagate(&self, node: Index, noise: propagation::Noise) -> Vec<(Index, propagation::Level)> {
+        propagation::spread(node, noise, |n| self.within_earshot(n))
+    }
+
     /// Automatically choose a spawn node
     /// 
-    /// Automatically chooses a spawn node for the given asset.
+    /// Automatically chooses a spawn node for the given asset, the first one
+    /// the asset may enter.
     /// TODO - Fix seleciton algorithm, currently we just take the first.
     /// TODO - Create specific spwan errors
     pub fn spawn<T>(&self, asset: &mut T) -> Result<Index, errors::Error> 
@@ -211,16 +3282,25 @@ impl GameWorld {
         
         // TODO - choose better spawn point.
 
-        if self.spawn_nodes.is_empty() {
-            return Err(errors::Error::NoSpawnpointFound);
-        } else {
-            asset.set_spawn_point_index(self.spawn_nodes[0]);
-            return Ok(self.spawn_nodes[0]);
-        }
-        
+        let index = self.spawn_nodes.iter()
+            .copied()
+            .find(|i| self.nodes.get(*i).is_some_and(|n| asset.may_enter(n) 
+                && self.seasons.allows(n.season(), timestamp())))
+            .ok_or(errors::Error::NoSpawnpointFound)?;
+        asset.set_spawn_point_index(index);
+        Ok(index)
     }
 }
 
+/// Seconds since the epoch
+/// 
+/// Used to timestamp everything the world stores on disk.
+pub(crate) fn timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
 /// A trait for spawnable objects
 /// 
 /// An object that can be spawned in different locations needs to implement
@@ -228,6 +3308,11 @@ impl GameWorld {
 pub trait Spawnable {
     /// Add the object at index as a potential spawn point
     fn set_spawn_point_index(&mut self, index: Index);
+
+    /// True if the object may enter the node (see `acl`)
+    fn may_enter(&self, _node: &assets::Node) -> bool {
+        true
+    }
 }
 
 /// A trait for assets that can be identified and referenced by other objects
@@ -251,10 +3336,23 @@ pub trait Spawnable {
 /// and "port" as a design choice to remove irrelevant attributes)
 pub trait Identifiable {
     /// Returns true if the object can be identified by a given property
-    fn has_property() -> bool;
+    fn has_property(&self, property: &properties::Property) -> bool;
+
+    /// Returns true if the asset can be identified as an object (by a noun or
+    /// its plural, see `fuzzy`)
+    fn is_object(&self, noun: &str) -> bool;
+}
+
+/// Game assets are identified by their name and their properties
+impl<T: GameAsset + ?Sized> Identifiable for T {
+    fn has_property(&self, property: &properties::Property) -> bool {
+        self.properties().is_some_and(|properties| properties.iter()
+            .any(|p| p.key() == property.key() && p.value().eq_ignore_ascii_case(&property.value())))
+    }
 
-    /// Returns true if the asset can be identified as an object
-    fn is_object() -> bool;
+    fn is_object(&self, noun: &str) -> bool {
+        fuzzy::stem(self.name()) == fuzzy::stem(noun)
+    }
 }
 
 /// A trait for assets that can be observed
@@ -263,23 +3361,235 @@ pub trait Identifiable {
 /// aciton. This can be a simple action such as for example juest gibing
 /// a better description of the object, or it can be a complex action (eg.
 /// if the object under observation is a person it can flee).
+#[allow(dead_code)]
 pub trait Observable {
     /// Returns true if the object can be identified by a given property
     fn observe(&self) -> Action;
 }
 
+/// Roles of players
+/// 
+/// The role determines which commands beyond the game actions a player may
+/// use and which notifications it receives.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Role {
+    /// A regular player
+    Player,
+    /// A member of the staff running the event
+    Admin,
+    /// A member of the staff building the world (may log in during the
+    /// maintenance)
+    Builder,
+}
+
+/// An action waiting in the queue of a player
+struct QueuedAction {
+    action: Action,
+    /// The action was run by a trigger
+    triggered: bool,
+}
+
 struct Player {
     player_name: String,
-    active_session: (thrussh::ChannelId, thrussh::server::Handle),
+    account: accounts::Account,
+    role: Role,
+    history: VecDeque<String>,
+    connection: Box<dyn ClientConnection>,
     location: Option<Index>,
+    queue: VecDeque<QueuedAction>,
+    busy_until: Option<Instant>,
+    output_mode: OutputMode,
+    transcript: Option<transcripts::Transcript>,
+    firing: triggers::Firing,
+    performing_triggered: bool,
+    session: stats::SessionStats,
+    media: Option<media::Protocol>,
+    shell: Option<terminals::Shell>,
+    solved_puzzles: Vec<String>,
+    cloak: Option<stealth::Cloak>,
+    /// The drone of the player follows it (see `drones`)
+    drone_deployed: bool,
+    /// Assets the player touched since the last tick, watched by guards
+    touched: Vec<assets::AssetID>,
+    /// The player acted quickly since the last tick (see `adverbs`)
+    hurried: bool,
+    /// The player shouted since the last tick, heard by hostile NPCs
+    shouted: bool,
+    /// The crimes the player committed since the last tick (see `security`)
+    crimes: Vec<security::Crime>,
+    /// The items the player picked up (see `inventory`)
+    inventory: inventory::Inventory,
+    watching: Option<Index>,
+    idle: idle::Idle,
+    /// Errors sent to the player, counted while the player is only borrowed
+    errors: AtomicU64,
 }
 
 impl Player {
-    pub fn new(player_name: String, active_session: (thrussh::ChannelId, thrussh::server::Handle)) -> Player {
+    pub fn new(player_name: String, connection: Box<dyn ClientConnection>) -> Player {
         Player {
+            account: accounts::Account::new(&player_name),
             player_name,
-            active_session,
+            role: Role::Player,
+            history: VecDeque::new(),
+            connection,
             location: None,
+            queue: VecDeque::new(),
+            busy_until: None,
+            output_mode: OutputMode::Text,
+            transcript: None,
+            firing: triggers::Firing::default(),
+            performing_triggered: false,
+            session: stats::SessionStats::new(),
+            media: None,
+            shell: None,
+            solved_puzzles: Vec::new(),
+            cloak: None,
+            drone_deployed: false,
+            touched: Vec::new(),
+            hurried: false,
+            shouted: false,
+            crimes: Vec::new(),
+            inventory: inventory::Inventory::default(),
+            watching: None,
+            idle: idle::Idle::new(Instant::now()),
+            errors: AtomicU64::new(0),
+        }
+    }
+
+    /// The name the player is shown with to others
+    /// 
+    /// This is the handle of the player followed by its title, if any.
+    fn display_name(&self) -> String {
+        match &self.account.title {
+            Some(t) => format!("{} {}", self.player_name, t),
+            None => self.player_name.clone(),
+        }
+    }
+
+    /// What others see of the player, on a run of the given challenge (see
+    /// `appearance`)
+    fn profile<'a>(&'a self, run: Option<&'a str>) -> appearance::Profile<'a> {
+        appearance::Profile {
+            name: self.display_name(),
+            description: self.account.description.as_deref(),
+            items: &self.account.items,
+            drone: self.account.drone.as_ref().filter(|_| self.drone_deployed).map(|d| d.model.as_str()),
+            reputation: Some(&self.account.reputation),
+            run,
+        }
+    }
+
+    /// Remember a command of the player
+    /// 
+    /// Only the most recent commands are kept.
+    fn remember(&mut self, command: String) {
+        if self.history.len() >= HISTORY_LENGTH {
+            self.history.pop_front();
+        }
+        self.history.push_back(command);
+    }
+
+    /// Queue the actions of the triggers fired since the last tick
+    /// 
+    /// Returns the number of triggers skipped because the player fired too
+    /// many triggers or its queue is full.
+    fn queue_triggered(&mut self, now: Instant) -> usize {
+        let (commands, mut skipped) = self.firing.take(now);
+        for command in commands {
+            match Action::try_from(command.as_str()) {
+                Ok(a) if self.queue.len() < MAX_QUEUED_ACTIONS => {
+                    debug!("Trigger of player {} queued action {}.", self.player_name, a);
+                    self.queue.push_back(QueuedAction { action: a, triggered: true });
+                },
+                Ok(_) => skipped += 1,
+                Err(e) => debug!("Trigger of player {} has an invalid command: {}", self.player_name, e),
+            }
+        }
+        skipped
+    }
+
+    /// Send a message to the player
+    /// 
+    /// The message is rendered according to the output mode of the session.
+    /// If the session is recorded, the message is added to the transcript.
+    /// The message fires the triggers of the player, unless it is caused by
+    /// a triggered action.
+    async fn send(&self, message: Message) {
+        self.send_with_triggers(message, !self.performing_triggered).await;
+    }
+
+    /// Send a message to the player, firing its triggers or not
+    async fn send_with_triggers(&self, message: Message, fire_triggers: bool) {
+        if fire_triggers {
+            self.firing.check(&self.account.triggers, message.text());
+        }
+        if message.event() == Event::Error {
+            self.errors.fetch_add(1, Ordering::Relaxed);
+        }
+        if let Some(transcript) = &self.transcript {
+            if let Err(e) = transcript.record_output(&message.render(OutputMode::Text)).await {
+                error!("Could not write transcript of {}: {}", self.player_name, e);
+            }
+        }
+        let mut buf = buffers::take();
+        message.render_into(self.output_mode, &mut buf);
+        self.push(buf).await;
+    }
+
+    /// Show a screen to the player
+    /// 
+    /// Animated screens are played, unless the player opted out of
+    /// animations or uses robot mode. Then only the final frame is shown.
+    async fn show(&self, screen: &states::Screen) {
+        if screen.is_animated() && !self.account.still_screens && self.output_mode == OutputMode::Text {
+            match self.connection.send(Push::Animation(screen.frames())).await {
+                Ok(_) => {},
+                Err(SendError::Full) => warn!("Client of player {} cannot keep up. Discarding animation.", self.player_name),
+                Err(SendError::Closed) => debug!("Client of player {} is gone. Discarding animation.", self.player_name),
+            }
+        } else {
+            self.push(buffers::from_slice(screen.final_frame())).await;
+        }
+    }
+
+    /// Play a sound to the player
+    /// 
+    /// Only clients that announced a media protocol get sounds, and only if
+    /// the player did not mute them.
+    async fn play(&self, sound: &str, url: &str) {
+        if let (Some(protocol), false) = (self.media, self.account.muted) {
+            self.push(buffers::from_slice(&protocol.packet(sound, url))).await;
+        }
+    }
+
+    /// Mirror data to the client of the player as a GMCP package
+    /// 
+    /// Only clients that announced GMCP with the "media" command get it.
+    async fn mirror(&self, package: &str, data: &serde_json::Value) {
+        if self.media == Some(media::Protocol::Gmcp) {
+            self.push(buffers::from_slice(&media::gmcp(package, data))).await;
+        }
+    }
+
+    /// Show a prompt to the player
+    /// 
+    /// The prompt is not followed by a line break. Robots get no prompt.
+    async fn prompt(&self, prompt: &str) {
+        if self.output_mode == OutputMode::Text {
+            self.push(buffers::from_slice(prompt.as_bytes())).await;
+        }
+    }
+
+    /// Push raw data to the client of the player
+    /// 
+    /// The data is forwarded to the client by its connection handler. If the
+    /// client is gone or cannot keep up, the data is discarded.
+    async fn push(&self, data: Output) {
+        match self.connection.send(Push::Data(data)).await {
+            Ok(_) => {},
+            Err(SendError::Full) => warn!("Client of player {} cannot keep up. Discarding data.", self.player_name),
+            Err(SendError::Closed) => debug!("Client of player {} is gone. Discarding data.", self.player_name),
         }
     }
 }
@@ -288,6 +3598,10 @@ impl Spawnable for Player {
     fn set_spawn_point_index(&mut self, index: Index) {
         self.location = Some(index);
     }
+
+    fn may_enter(&self, node: &assets::Node) -> bool {
+        node.acl().is_none_or(|acl| acl.allows(self.role, &self.account.flags))
+    }
 }
 
 // TODO: We should somehow give information about the session
@@ -295,7 +3609,11 @@ impl std::fmt::Debug for Player {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Player")
          .field("player_name", &self.player_name)
+         .field("role", &self.role)
          .field("player_location", &self.location)
+         .field("queued_actions", &self.queue.len())
+         .field("output_mode", &self.output_mode)
+         .field("transcript", &self.transcript.is_some())
          .finish()
     }
 }
\ No newline at end of file
@@ -0,0 +1,89 @@
+//! News
+//!
+//! Recent news of the world, for the feed the con website embeds (see
+//! `feed`). News are
+//!  * announcements of the admins (@banner)
+//!  * new results on the leaderboard
+//!
+//! Only the latest news are kept, in memory. Clones share the news, so the
+//! world can publish them while the feed reads them.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use super::timestamp;
+
+/// Number of news kept
+pub const MAX_NEWS: usize = 50;
+
+/// Kinds of news
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Kind {
+    /// An announcement of the admins
+    Announcement,
+    /// A new result on the leaderboard
+    Record,
+}
+
+impl Kind {
+    /// The name of the kind, as tagged in the feed
+    pub fn name(&self) -> &'static str {
+        match self {
+            Kind::Announcement => "announcement",
+            Kind::Record => "record",
+        }
+    }
+}
+
+/// An item of news
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Item {
+    /// Unique and increasing
+    pub id: u64,
+    pub kind: Kind,
+    pub title: String,
+    pub text: String,
+    /// The players the news are about
+    pub players: Vec<String>,
+    /// Seconds since the epoch when the news were published
+    pub timestamp: u64,
+}
+
+/// The latest news, newest first
+#[derive(Debug, Clone, Default)]
+pub struct News {
+    items: Arc<Mutex<VecDeque<Item>>>,
+}
+
+impl News {
+    /// Create empty news
+    pub fn new() -> News {
+        News::default()
+    }
+
+    /// Publish news
+    ///
+    /// The oldest news are dropped if more than `MAX_NEWS` are kept.
+    pub fn publish(&self, kind: Kind, title: &str, text: &str, players: Vec<String>) {
+        let mut items = self.items.lock().unwrap_or_else(|e| e.into_inner());
+        let id = items.front().map_or(1, |i| i.id + 1);
+        items.push_front(Item { id, kind, title: title.to_string(), text: text.to_string(), players,
+            timestamp: timestamp() });
+        items.truncate(MAX_NEWS);
+    }
+
+    /// The latest news, newest first
+    pub fn latest(&self, count: usize) -> Vec<Item> {
+        let items = self.items.lock().unwrap_or_else(|e| e.into_inner());
+        items.iter().take(count).cloned().collect()
+    }
+
+    /// Forget a player
+    ///
+    /// News about the player are removed.
+    pub fn remove(&self, player: &str) {
+        let mut items = self.items.lock().unwrap_or_else(|e| e.into_inner());
+        items.retain(|i| !i.players.iter().any(|p| p == player));
+    }
+}
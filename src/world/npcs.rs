@@ -0,0 +1,401 @@
+//! NPCs
+//!
+//! Non-player characters, eg. ICE patrolling a zone. NPCs are spawned from
+//! prototypes by spawners placed in nodes. On every tick a spawner keeps its
+//! node at the population it targets while players are in the node:
+//!  * missing NPCs are spawned
+//!  * killed NPCs respawn once their kill timer ran out
+//!  * all NPCs despawn when the last player left the node, so empty zones
+//!    cost nothing
+//!
+//! An NPC away from the node of its spawner (eg. pursuing a player, see
+//! `ai`) stays until the node it is in is empty, too. Reinforcements called
+//! by NPCs and ICE dispatched by security (see `security`) have no spawner,
+//! they despawn with the last player leaving their node and do not respawn.
+//!
+//! Hostile NPCs scale with the players in their node (see `difficulty`).
+//! The timers run on the time of the world (see `clock`).
+
+use std::convert::TryFrom;
+use std::sync::Arc;
+use std::time::Duration;
+
+use generational_arena::Index;
+use tokio::time::Instant;
+
+use super::ai::{self, Act, Behavior, Blackboard, View};
+use super::difficulty::{self, BASE_SCALE};
+use super::intern::intern;
+use super::threat::{HACK_THREAT, HASTE_THREAT, NOISE_THREAT, THEFT_THREAT};
+
+/// Default time a killed NPC takes to respawn
+const DEFAULT_RESPAWN: Duration = Duration::from_secs(60);
+
+/// Default integrity of NPCs, the damage they take before they are destroyed
+const DEFAULT_INTEGRITY: u32 = 30;
+
+/// Identifies an NPC
+pub type NpcID = u64;
+
+/// The blueprint NPCs are spawned from
+#[derive(Debug, Clone)]
+pub struct Prototype {
+    name: Arc<str>,
+    description: Arc<str>,
+    hostile: bool,
+    integrity: u32,
+    behaviors: Vec<Behavior>,
+    /// What the NPCs say to the players looking at them
+    line: Option<Arc<str>>,
+}
+
+impl Prototype {
+    /// Create a peaceful prototype
+    ///
+    /// The name is what players call the NPCs (eg. "sentinel").
+    pub fn new(name: &str, description: &str) -> Prototype {
+        Prototype { name: intern(name), description: intern(description), hostile: false,
+            integrity: DEFAULT_INTEGRITY, behaviors: Vec::new(), line: None }
+    }
+
+    /// Make the NPCs of the prototype hostile
+    pub fn hostile(mut self) -> Prototype {
+        self.hostile = true;
+        self
+    }
+
+    /// Set the damage the NPCs of the prototype take before they are
+    /// destroyed
+    pub fn with_integrity(mut self, integrity: u32) -> Prototype {
+        self.integrity = integrity;
+        self
+    }
+
+    /// Add a behavior to the NPCs of the prototype
+    ///
+    /// Behaviors added first take priority.
+    pub fn with_behavior(mut self, behavior: Behavior) -> Prototype {
+        self.behaviors.push(behavior);
+        self
+    }
+
+    /// Set what the NPCs of the prototype say to the players looking at
+    /// them
+    pub fn with_line(mut self, line: &str) -> Prototype {
+        self.line = Some(intern(line));
+        self
+    }
+}
+
+/// An NPC in the world
+#[derive(Debug, Clone)]
+pub struct Npc {
+    pub uid: NpcID,
+    pub location: Index,
+    prototype: Prototype,
+    /// The spawner the NPC came from, None for reinforcements
+    spawner: Option<usize>,
+    /// The damage the NPC takes before it is destroyed
+    integrity: u32,
+    /// The scale of the NPC, in percent (see `difficulty`)
+    scale: u32,
+    blackboard: Blackboard,
+}
+
+impl Npc {
+    /// The name players call the NPC
+    pub fn name(&self) -> &str {
+        &self.prototype.name
+    }
+
+    /// The description of the NPC
+    pub fn description(&self) -> &str {
+        &self.prototype.description
+    }
+
+    /// What the NPC says to the players looking at it, if anything
+    pub fn line(&self) -> Option<&str> {
+        self.prototype.line.as_deref()
+    }
+
+    /// True if the NPC is hostile
+    pub fn is_hostile(&self) -> bool {
+        self.prototype.hostile
+    }
+
+    /// The damage the NPC takes before it is destroyed, when unharmed
+    pub fn max_integrity(&self) -> u32 {
+        difficulty::apply(self.prototype.integrity, self.scale)
+    }
+
+    /// The scale of the NPC, in percent (see `difficulty`)
+    pub fn scale(&self) -> u32 {
+        self.scale
+    }
+
+    /// The player the NPC traces
+    pub fn target(&self) -> Option<&str> {
+        self.blackboard.target.as_deref()
+    }
+}
+
+/// Keeps a node at a population of NPCs of a prototype
+#[derive(Debug, Clone)]
+pub struct Spawner {
+    node: Index,
+    prototype: Prototype,
+    population: usize,
+    respawn: Duration,
+    /// When the NPCs killed respawn
+    respawns: Vec<Instant>,
+    /// The season the spawner is active in (see `seasons`)
+    season: Option<Arc<str>>,
+}
+
+impl Spawner {
+    /// Create a spawner keeping the node at a population of NPCs of the
+    /// prototype
+    pub fn new(node: Index, prototype: Prototype, population: usize) -> Spawner {
+        Spawner { node, prototype, population, respawn: DEFAULT_RESPAWN, respawns: Vec::new(), season: None }
+    }
+
+    /// Only spawn NPCs while a season runs
+    pub fn during(mut self, season: &str) -> Spawner {
+        self.season = Some(intern(season));
+        self
+    }
+
+    /// Set the time a killed NPC takes to respawn
+    pub fn respawn_after(mut self, respawn: Duration) -> Spawner {
+        self.respawn = respawn;
+        self
+    }
+}
+
+/// A change of the population of a node
+#[derive(Debug, Clone)]
+pub enum Change {
+    Spawned(Npc),
+    Despawned(Npc),
+}
+
+/// The spawners and the NPCs of the world
+#[derive(Debug, Default)]
+pub struct Npcs {
+    spawners: Vec<Spawner>,
+    npcs: Vec<Npc>,
+    next_uid: NpcID,
+}
+
+impl Npcs {
+    /// Add a spawner
+    pub fn add_spawner(&mut self, spawner: Spawner) {
+        self.spawners.push(spawner);
+    }
+
+    /// The NPCs in a node
+    pub fn in_node(&self, node: Index) -> impl Iterator<Item = &Npc> {
+        self.npcs.iter().filter(move |n| n.location == node)
+    }
+
+    /// Find an NPC by its uid
+    pub fn get(&self, uid: NpcID) -> Option<&Npc> {
+        self.npcs.iter().find(|n| n.uid == uid)
+    }
+
+    /// Find an NPC in a node by its name (ignoring case) and ordinal
+    /// (counting from one, the first if None)
+    pub fn find(&self, node: Index, name: &str, ordinal: Option<usize>) -> Option<&Npc> {
+        self.in_node(node)
+            .filter(|n| n.name().eq_ignore_ascii_case(name))
+            .nth(ordinal.unwrap_or(1).checked_sub(1)?)
+    }
+
+    /// Hack a hostile NPC, adding the damage as threat of the player
+    ///
+    /// Returns the integrity left, the NPC is killed when it runs out. None
+    /// if there is no such hostile NPC.
+    pub fn hack(&mut self, uid: NpcID, player: &str, damage: u32, now: Instant) -> Option<u32> {
+        let npc = self.npcs.iter_mut().find(|n| n.uid == uid && n.is_hostile())?;
+        npc.integrity = npc.integrity.saturating_sub(damage);
+        npc.blackboard.threat.add(player, damage);
+        let integrity = npc.integrity;
+        if integrity == 0 {
+            self.kill(uid, now);
+        }
+        Some(integrity)
+    }
+
+    /// Taunt a hostile NPC, putting the player on top of its threat table
+    ///
+    /// Returns false if there is no such hostile NPC.
+    pub fn taunt(&mut self, uid: NpcID, player: &str) -> bool {
+        match self.npcs.iter_mut().find(|n| n.uid == uid && n.is_hostile()) {
+            Some(npc) => {
+                npc.blackboard.threat.taunt(player);
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Kill an NPC, starting the timer to respawn it
+    pub fn kill(&mut self, uid: NpcID, now: Instant) -> Option<Npc> {
+        let position = self.npcs.iter().position(|n| n.uid == uid)?;
+        let npc = self.npcs.remove(position);
+        if let Some(spawner) = npc.spawner.and_then(|i| self.spawners.get_mut(i)) {
+            spawner.respawns.push(now + spawner.respawn);
+        }
+        Some(npc)
+    }
+
+    /// Keep the population of the nodes on a tick
+    ///
+    /// `occupied` tells if players are in a node, `active` if the season of
+    /// a spawner runs. The NPCs of spawners out of season despawn. Returns
+    /// the NPCs spawned and despawned.
+    pub fn tick(&mut self, occupied: impl Fn(Index) -> bool, active: impl Fn(Option<&str>) -> bool,
+                now: Instant) -> Vec<Change> {
+        let mut changes = Vec::new();
+        let in_season: Vec<bool> = self.spawners.iter().map(|s| active(s.season.as_deref())).collect();
+        let (gone, staying): (Vec<Npc>, Vec<Npc>) = std::mem::take(&mut self.npcs).into_iter()
+            .partition(|n| match n.spawner {
+                Some(i) if !in_season[i] => true,
+                spawner => !occupied(n.location) && spawner.is_none_or(|i| !occupied(self.spawners[i].node)),
+            });
+        self.npcs = staying;
+        changes.extend(gone.into_iter().map(Change::Despawned));
+        for (i, spawner) in self.spawners.iter_mut().enumerate() {
+            if !occupied(spawner.node) || !in_season[i] {
+                spawner.respawns.clear();
+                continue;
+            }
+            spawner.respawns.retain(|t| *t > now);
+            let alive = self.npcs.iter().filter(|n| n.spawner == Some(i)).count();
+            for _ in (alive + spawner.respawns.len())..spawner.population {
+                let npc = Npc { uid: self.next_uid, location: spawner.node, prototype: spawner.prototype.clone(),
+                    spawner: Some(i), integrity: spawner.prototype.integrity, scale: BASE_SCALE,
+                    blackboard: Blackboard::default() };
+                self.next_uid += 1;
+                self.npcs.push(npc.clone());
+                changes.push(Change::Spawned(npc));
+            }
+        }
+        changes
+    }
+
+    /// Let the NPCs decide what they do on a tick (see `ai`)
+    ///
+    /// Hostile NPCs take the assets hacked, the hasty actions, the noise
+    /// heard and the thefts caught in their node as threat first.
+    /// Returns the acts of the NPCs, carried out by the world.
+    pub fn think(&mut self, view: &View, now: Instant) -> Vec<(NpcID, Act)> {
+        let mut acts = Vec::new();
+        for npc in self.npcs.iter_mut() {
+            if npc.is_hostile() {
+                let location = npc.location;
+                for (_, _, player) in view.touched.iter().filter(|(n, _, _)| *n == location) {
+                    npc.blackboard.threat.add(player, HACK_THREAT);
+                }
+                for (_, player) in view.hurried.iter().filter(|(n, _)| *n == location) {
+                    npc.blackboard.threat.add(player, HASTE_THREAT);
+                }
+                for (_, player) in view.heard.iter().filter(|(n, _)| *n == location) {
+                    npc.blackboard.threat.add(player, NOISE_THREAT);
+                }
+                for (_, player) in view.caught.iter().filter(|(n, _)| *n == location) {
+                    npc.blackboard.threat.add(player, THEFT_THREAT);
+                }
+            }
+            let decided = ai::think(&npc.prototype.behaviors, npc.location, &mut npc.blackboard, view, now);
+            acts.extend(decided.into_iter().map(|a| match a {
+                Act::Strike { player, jam } => Act::Strike { player, jam: difficulty::apply_duration(jam, npc.scale) },
+                a => a,
+            }).map(|a| (npc.uid, a)));
+        }
+        acts
+    }
+
+    /// Scale the hostile NPCs to the players in their nodes on a tick
+    ///
+    /// `scale` tells the scale of a node (see `difficulty`), None keeps the
+    /// scale of the NPCs in it. An NPC keeps the share of its integrity
+    /// left.
+    pub fn scale(&mut self, scale: impl Fn(Index) -> Option<u32>) {
+        for npc in self.npcs.iter_mut().filter(|n| n.is_hostile()) {
+            let scale = match scale(npc.location) {
+                Some(s) if s != npc.scale => s,
+                _ => continue,
+            };
+            let max = npc.max_integrity();
+            npc.scale = scale;
+            let integrity = u64::from(npc.integrity) * u64::from(npc.max_integrity()) / u64::from(max.max(1));
+            npc.integrity = u32::try_from(integrity).unwrap_or(u32::MAX).max(1);
+        }
+    }
+
+    /// Replace the behaviors of an NPC (eg. a boss entering a phase, see
+    /// `bosses`)
+    pub fn set_behaviors(&mut self, uid: NpcID, behaviors: Vec<Behavior>) -> bool {
+        match self.npcs.iter_mut().find(|n| n.uid == uid) {
+            Some(npc) => {
+                npc.prototype.behaviors = behaviors;
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Change what the NPCs of a name say, those spawned from now on too
+    /// (eg. after a world flag was set, see `goals`)
+    pub fn set_line(&mut self, name: &str, line: &str) {
+        let prototypes = self.spawners.iter_mut().map(|s| &mut s.prototype)
+            .chain(self.npcs.iter_mut().map(|n| &mut n.prototype));
+        for prototype in prototypes.filter(|p| p.name.eq_ignore_ascii_case(name)) {
+            prototype.line = Some(intern(line));
+        }
+    }
+
+    /// Move an NPC to another node
+    pub fn relocate(&mut self, uid: NpcID, node: Index) -> Option<&Npc> {
+        let npc = self.npcs.iter_mut().find(|n| n.uid == uid)?;
+        npc.location = node;
+        Some(npc)
+    }
+
+    /// Spawn NPCs of the prototype of an NPC in its node, tracing its target
+    /// with its threat table
+    ///
+    /// The reinforcements do not call more reinforcements for the target.
+    pub fn reinforce(&mut self, uid: NpcID, count: usize) -> Vec<Npc> {
+        let caller = match self.npcs.iter().find(|n| n.uid == uid) {
+            Some(n) => n.clone(),
+            None => return Vec::new(),
+        };
+        let mut spawned = Vec::new();
+        for _ in 0..count {
+            let mut blackboard = Blackboard::default();
+            blackboard.target = caller.blackboard.target.clone();
+            blackboard.threat = caller.blackboard.threat.clone();
+            blackboard.reinforced = true;
+            let npc = Npc { uid: self.next_uid, location: caller.location, prototype: caller.prototype.clone(),
+                spawner: None, integrity: caller.max_integrity(), scale: caller.scale, blackboard };
+            self.next_uid += 1;
+            self.npcs.push(npc.clone());
+            spawned.push(npc);
+        }
+        spawned
+    }
+
+    /// Spawn an NPC of a prototype in a node, tracing a player (eg. the ICE
+    /// security dispatches to an offender)
+    pub fn dispatch(&mut self, prototype: &Prototype, node: Index, target: &str) -> Npc {
+        let mut blackboard = Blackboard::default();
+        blackboard.target = Some(target.to_string());
+        let npc = Npc { uid: self.next_uid, location: node, prototype: prototype.clone(), spawner: None,
+            integrity: prototype.integrity, scale: BASE_SCALE, blackboard };
+        self.next_uid += 1;
+        self.npcs.push(npc.clone());
+        npc
+    }
+}
@@ -0,0 +1,46 @@
+//! Ownership
+//!
+//! Items may belong to an account (eg. the stash of a fixer, see the
+//! `owner` of an item in the world file). Only the owner takes an owned
+//! item. Others have to steal it ("steal <item>"), if the item is lootable:
+//! items that are not stay where the owner left them.
+//!
+//! Stealing is a stealth check: only cloaked players try, and the theft
+//! wears the cloak down. The thief goes unnoticed with a chance of the
+//! strength left (in percent of a fresh cloak). A thief caught drops the
+//! cloak, the owner learns who tried, and the hostile NPCs in the node trace
+//! the thief (see `threat`). An item stolen still belongs to its owner: the
+//! thief carries it, but cannot sell it (see `market` and `economy`).
+
+use serde::{Deserialize, Serialize};
+
+use super::stealth::{Cloak, CLOAK_STRENGTH};
+
+/// Strength a theft wears off the cloak of the thief
+pub const THEFT_WEAR: u32 = 25;
+
+/// Who an item belongs to
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Ownership {
+    /// The account owning the item
+    pub owner: String,
+    /// True if others may steal the item
+    #[serde(default)]
+    pub lootable: bool,
+}
+
+impl Ownership {
+    /// True if the item belongs to the player
+    pub fn is_owner(&self, player: &str) -> bool {
+        self.owner.eq_ignore_ascii_case(player)
+    }
+}
+
+/// The stealth check of a theft, with a roll from 0 to `CLOAK_STRENGTH`
+/// (exclusive)
+///
+/// Wears the cloak down by `THEFT_WEAR`. Returns true if the thief goes
+/// unnoticed.
+pub fn steal(cloak: &mut Cloak, roll: u32) -> bool {
+    !cloak.wear(THEFT_WEAR) && roll % CLOAK_STRENGTH < cloak.strength()
+}
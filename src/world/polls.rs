@@ -0,0 +1,141 @@
+//! Polls
+//!
+//! Game masters run quick decisions among the players online (eg. "which
+//! zone unlocks tonight?"). An admin opens a poll with "@poll create", the
+//! question and its options are told to everybody. Players vote with
+//! "vote <option>", by the number or the text of the option, once per
+//! account. "@poll close" ends the poll and tells everybody the results.
+//!
+//! There is one poll at a time, kept in memory only.
+
+use std::collections::{BTreeMap, HashMap};
+use tracing::info;
+
+use crate::connection_manager::ClientId;
+use super::actions::Action;
+use super::admin::PollStep;
+use super::messages::{Event, Message};
+use super::{GameWorld, Player};
+
+/// Least number of options of a poll
+pub const MIN_OPTIONS: usize = 2;
+
+/// A poll among the players
+#[derive(Debug, Clone)]
+pub struct Poll {
+    question: String,
+    options: Vec<String>,
+    /// The index of the option every account voted for, by the name of the
+    /// account (in lowercase)
+    votes: BTreeMap<String, usize>,
+}
+
+impl Poll {
+    /// Create a poll, None if it has less than `MIN_OPTIONS` options
+    pub fn new(question: &str, options: Vec<String>) -> Option<Poll> {
+        if options.len() < MIN_OPTIONS {
+            return None;
+        }
+        Some(Poll { question: question.to_string(), options, votes: BTreeMap::new() })
+    }
+
+    /// Cast the vote of an account for an option, by its number (counting
+    /// from one) or its text
+    ///
+    /// Returns the option voted for, or why the vote is not counted.
+    pub fn vote(&mut self, account: &str, choice: &str) -> Result<&str, String> {
+        if self.votes.contains_key(&account.to_lowercase()) {
+            return Err("You voted already, every account has one vote.".to_string());
+        }
+        let index = match choice.trim().parse::<usize>() {
+            Ok(n) => n.checked_sub(1).filter(|i| *i < self.options.len()),
+            Err(_) => self.options.iter().position(|o| o.eq_ignore_ascii_case(choice.trim())),
+        };
+        let index = index.ok_or_else(|| format!("There is no option {}. {}", choice.trim(), self.ballot()))?;
+        self.votes.insert(account.to_lowercase(), index);
+        Ok(&self.options[index])
+    }
+
+    /// The options and their votes, in the order of the options
+    pub fn tally(&self) -> Vec<(&str, usize)> {
+        self.options.iter().enumerate()
+            .map(|(i, o)| (o.as_str(), self.votes.values().filter(|v| **v == i).count()))
+            .collect()
+    }
+
+    /// The number of votes cast
+    pub fn turnout(&self) -> usize {
+        self.votes.len()
+    }
+
+    /// The question and the options to vote for
+    pub fn ballot(&self) -> String {
+        let options: Vec<String> = self.options.iter().enumerate().map(|(i, o)| format!("{}. {}", i + 1, o)).collect();
+        format!("Poll: {} Vote with \"vote <option>\": {}", self.question, options.join(", "))
+    }
+
+    /// The question and the votes every option got
+    pub fn results(&self) -> String {
+        let lines: Vec<String> = self.tally().iter().enumerate()
+            .map(|(i, (o, votes))| format!("  {}. {}: {} votes", i + 1, o, votes))
+            .collect();
+        format!("Poll: {} ({} votes)\r\n{}", self.question, self.turnout(), lines.join("\r\n"))
+    }
+}
+
+/// Vote for an option of the poll running, or show the poll
+///
+/// Every account has one vote.
+pub(super) async fn vote(a: &Action, world: &mut GameWorld, client_id: ClientId,
+                         players: &HashMap<ClientId, Player>) {
+    let (choice, player) = match (a, players.get(&client_id)) {
+        (Action::Vote(c), Some(p)) => (c, p),
+        _ => return,
+    };
+    let message = match (&mut world.poll, choice) {
+        (None, _) => Message::new(Event::Error, "There is no poll running."),
+        (Some(poll), None) => Message::new(Event::Info, &poll.ballot()),
+        (Some(poll), Some(choice)) => match poll.vote(&player.player_name, choice) {
+            Ok(option) => Message::new(Event::Info, &format!("You voted for {}.", option)).with_field("vote", option),
+            Err(reason) => Message::new(Event::Error, &reason),
+        },
+    };
+    player.send(message).await;
+}
+
+/// Show, open or close the poll for an admin, returns the reply
+///
+/// Opening and closing the poll is told to everybody online.
+pub(super) async fn administer(step: PollStep, world: &mut GameWorld, players: &HashMap<ClientId, Player>) -> String {
+    match step {
+        PollStep::Show => match &world.poll {
+            Some(poll) => poll.results(),
+            None => "There is no poll running.".to_string(),
+        },
+        PollStep::Create { question, options } => match (&world.poll, Poll::new(&question, options)) {
+            (Some(_), _) => "A poll is running already, close it first.".to_string(),
+            (None, None) => format!("A poll needs at least {} options.", MIN_OPTIONS),
+            (None, Some(poll)) => {
+                let message = Message::new(Event::Info, &poll.ballot()).with_field("poll", question.clone());
+                for p in players.values() {
+                    p.send(message.clone()).await;
+                }
+                info!("Opened the poll {}.", question);
+                world.poll = Some(poll);
+                format!("Opened the poll for the {} players online.", players.len())
+            },
+        },
+        PollStep::Close => match world.poll.take() {
+            Some(poll) => {
+                let message = Message::new(Event::Info, &format!("The poll is closed.\r\n{}", poll.results()))
+                    .with_field("tally", poll.tally());
+                for p in players.values() {
+                    p.send(message.clone()).await;
+                }
+                info!("Closed the poll with {} votes.", poll.turnout());
+                format!("Closed the poll with {} votes.", poll.turnout())
+            },
+            None => "There is no poll running.".to_string(),
+        },
+    }
+}
@@ -0,0 +1,187 @@
+//! Privacy
+//!
+//! Export and deletion of the personal data the world stores about a player,
+//! as required for running a public service in the EU. Personal data is the
+//! account of the player, the bug reports it filed, its transcripts, its
+//! entry in the list of visitors, its results on the leaderboard, the events
+//! it is reminded of, the node it was in at the last checkpoint, its market
+//! listings and mail, the keys bound to it and its badges.
+//!
+//! Forgetting a player deletes its data and anonymizes the logs naming it:
+//! its bug reports, the transcripts of the other players and the mail of the
+//! market.
+
+use regex::Regex;
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use tokio::fs;
+
+use crate::connection_manager::keyring::Keyring;
+use crate::connection_manager::ClientId;
+use crate::provisioning::{Badge, Badges};
+use super::accounts::Account;
+use super::assets::AssetID;
+use super::challenges::{Leaderboard, Record};
+use super::checkpoint::Checkpoint;
+use super::market::{Listing, Market, Parcel};
+use super::reports::BugReport;
+use super::schedule::Schedule;
+use super::transcripts::Transcript;
+use super::visitors::Visitors;
+use super::{timestamp, GameWorld, Player};
+
+/// Name of the directory exports are written to (inside the data directory)
+pub const EXPORT_DIR: &str = "exports";
+
+/// Name forgotten players are replaced by in the logs
+pub const ANONYMOUS: &str = "anonymous";
+
+/// All personal data stored about a player
+#[derive(Debug, Serialize)]
+pub struct Export {
+    /// Seconds since the epoch when the export was made
+    pub timestamp: u64,
+    /// Name of the player
+    pub player: String,
+    /// The stored account, if any
+    pub account: Option<Account>,
+    /// The bug reports filed by the player
+    pub bug_reports: Vec<BugReport>,
+    /// The transcripts of the player by file name
+    pub transcripts: Vec<(String, String)>,
+    /// The player is counted as visitor of the event
+    pub visitor: bool,
+    /// The results of the player on the leaderboard by challenge
+    pub records: Vec<(String, Record)>,
+    /// The events the player is reminded of, by id and title
+    pub reminders: Vec<(u64, String)>,
+    /// The uid of the node the player was in at the last checkpoint
+    pub location: Option<AssetID>,
+    /// The items the player sells on the market
+    pub listings: Vec<Listing>,
+    /// The parcels waiting for the player
+    pub mail: Vec<Parcel>,
+    /// The public keys bound to the player
+    pub keys: Vec<String>,
+    /// The badges of the player by serial
+    pub badges: Vec<(String, Badge)>,
+}
+
+/// Export the personal data of a player
+///
+/// Writes all data stored about the player as JSON archive to the export
+/// directory and returns the path of the archive.
+pub async fn export(data_dir: &Path, keyring: &Keyring, player: &str) -> io::Result<PathBuf> {
+    let account = Account::load_stored(data_dir, player).await?;
+    let bug_reports = BugReport::load_all(data_dir).await?
+        .into_iter()
+        .filter(|r| r.player == player)
+        .collect();
+    let market = Market::load(data_dir).await?;
+    let export = Export {
+        timestamp: timestamp(),
+        player: player.to_string(),
+        account,
+        bug_reports,
+        transcripts: Transcript::read_all(data_dir, player).await?,
+        visitor: Visitors::load(data_dir).await?.contains(player),
+        records: Leaderboard::load(data_dir).await?.records_of(player),
+        reminders: Schedule::load(data_dir).await?.subscriptions(player).iter()
+            .map(|e| (e.id, e.title.clone()))
+            .collect(),
+        location: Checkpoint::load(data_dir).await?.and_then(|c| c.players.get(&player.to_lowercase()).copied()),
+        listings: market.listings_of(player).into_iter().cloned().collect(),
+        mail: market.mail_of(player).to_vec(),
+        keys: keyring.bound_to(player),
+        badges: Badges::load(data_dir).await?.of(player),
+    };
+
+    fs::create_dir_all(data_dir.join(EXPORT_DIR)).await?;
+    let path = data_dir.join(EXPORT_DIR).join(format!("{}-{}.json", export.timestamp,
+        player.replace(|c: char| !c.is_ascii_alphanumeric(), "_")));
+    fs::write(&path, serde_json::to_vec_pretty(&export)?).await?;
+    Ok(path)
+}
+
+/// Forget a player
+///
+/// Deletes the account, the transcripts, the leaderboard results, the
+/// reminders, the checkpointed location, the listings and the mail, the
+/// bound keys and the badges of the player and anonymizes the logs naming
+/// it. The bug reports are kept, but neither the name nor the commands of
+/// the player remain in them.
+pub async fn forget(data_dir: &Path, keyring: &Keyring, player: &str) -> io::Result<()> {
+    Account::delete(data_dir, player).await?;
+    Transcript::delete_all(data_dir, player).await?;
+    Visitors::remove_stored(data_dir, player).await?;
+    Leaderboard::remove_stored(data_dir, player).await?;
+    Schedule::remove_stored(data_dir, player).await?;
+    Checkpoint::remove_stored(data_dir, player).await?;
+    keyring.unbind_all(player).await?;
+    Badges::remove_stored(data_dir, player).await?;
+
+    let mut market = Market::load(data_dir).await?;
+    market.remove(player);
+    market.rewrite_mail(|t| anonymize(t, player));
+    market.save(data_dir).await?;
+    Transcript::rewrite_all(data_dir, |t| anonymize(t, player)).await?;
+
+    let mut reports = BugReport::load_all(data_dir).await?;
+    if reports.iter().any(|r| r.player == player) {
+        for report in reports.iter_mut().filter(|r| r.player == player) {
+            report.player = ANONYMOUS.to_string();
+            report.history.clear();
+        }
+        BugReport::save_all(data_dir, &reports).await?;
+    }
+    Ok(())
+}
+
+/// Replace the name of a player in a text by `ANONYMOUS` (ignoring case),
+/// None if the text does not name the player
+pub fn anonymize(text: &str, player: &str) -> Option<String> {
+    let name = Regex::new(&format!(r"(?i)\b{}\b", regex::escape(player))).ok()?;
+    if !name.is_match(text) {
+        return None;
+    }
+    Some(name.replace_all(text, ANONYMOUS).into_owned())
+}
+
+/// Export the personal data of a player on behalf of an admin and describe
+/// the outcome
+pub(super) async fn administer_export(player: &str, world: &GameWorld) -> String {
+    match export(&world.data_dir, &world.keyring, player).await {
+        Ok(path) => format!("Exported the data of {} to {}.", player, path.display()),
+        Err(e) => format!("Could not export the data of {}: {}", player, e),
+    }
+}
+
+/// Forget a player on behalf of an admin and describe the outcome
+pub(super) async fn administer_forget(player: &str, world: &mut GameWorld,
+                                      players: &mut HashMap<ClientId, Player>) -> String {
+    // Neither the checkpoint being written nor the next one may bring
+    // the player back
+    super::finish_checkpoint(world).await;
+    world.resumes.remove(&player.to_lowercase());
+    match forget(&world.data_dir, &world.keyring, player).await {
+        Ok(_) => {
+            // A connected player continues with a fresh account
+            world.visitors.remove(player);
+            world.challenges.leaderboard.remove(player);
+            world.news.remove(player);
+            // The stored market and schedule are forgotten already
+            world.market.remove(player);
+            world.market.rewrite_mail(|t| anonymize(t, player));
+            world.schedule.unsubscribe_all(player);
+            if let Some(p) = players.values_mut().find(|p| p.player_name == player) {
+                p.account = Account::new(player);
+                p.history.clear();
+                p.transcript = None;
+            }
+            format!("Forgot {}. The personal data is deleted and the logs naming the player are \
+                anonymized.", player)
+        },
+        Err(e) => format!("Could not forget {}: {}", player, e),
+    }
+}
@@ -0,0 +1,80 @@
+//! Propagation
+//!
+//! Loud events carry beyond the node they happen in: a shout or an alarm
+//! passes the ports (in either direction, see `GameWorld::within_earshot`)
+//! to the neighbouring nodes and on, fading with every port. The noise
+//! decides how many ports it passes (its range), the distance how it is
+//! heard:
+//!  * clear in the node of the event
+//!  * muffled further away: the players hear what it is and where it comes
+//!    from (eg. the words of a shout)
+//!  * faint at the range: the players hear that something happened, but not
+//!    what
+//!
+//! Hostile NPCs hear the noise of players as far as players do and take it
+//! as threat (see `threat`), so a shout may call the ICE of the next node.
+
+use std::collections::VecDeque;
+
+use generational_arena::Index;
+
+/// A loud event
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Noise {
+    /// A player shouting (see `Action::Shout`)
+    Shout,
+    /// An alarm going off in a zone (see `timers`)
+    Alarm,
+}
+
+impl Noise {
+    /// The number of ports the noise passes
+    pub fn range(self) -> u32 {
+        match self {
+            Noise::Shout => 2,
+            Noise::Alarm => 3,
+        }
+    }
+}
+
+/// How a noise is heard
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    /// In the node of the event
+    Clear,
+    /// Closer than the range
+    Muffled,
+    /// At the range
+    Faint,
+}
+
+impl Level {
+    /// How a noise is heard the given number of ports away
+    pub fn at(distance: u32, noise: Noise) -> Level {
+        match distance {
+            0 => Level::Clear,
+            d if d < noise.range() => Level::Muffled,
+            _ => Level::Faint,
+        }
+    }
+}
+
+/// The nodes a noise in a node is heard in and how, the closest first
+///
+/// `neighbours` gives the nodes next to a node (including itself).
+pub fn spread(origin: Index, noise: Noise, neighbours: impl Fn(Index) -> Vec<Index>) -> Vec<(Index, Level)> {
+    let mut reached = vec![(origin, 0)];
+    let mut queue = VecDeque::from([(origin, 0)]);
+    while let Some((node, distance)) = queue.pop_front() {
+        if distance == noise.range() {
+            continue;
+        }
+        for next in neighbours(node) {
+            if reached.iter().all(|(n, _)| *n != next) {
+                reached.push((next, distance + 1));
+                queue.push_back((next, distance + 1));
+            }
+        }
+    }
+    reached.into_iter().map(|(node, distance)| (node, Level::at(distance, noise))).collect()
+}
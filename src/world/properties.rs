@@ -9,7 +9,8 @@ use std::convert::TryFrom;
 use crate::world::errors::Error;
 
 /// Properties of game assets
-#[derive(Debug)]
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
 pub enum Property {
     Color(Color),
     Rigidity(Rigidity),
@@ -20,6 +21,30 @@ pub enum Property {
     Custom(String),
 }
 
+impl Property {
+    /// The key of the property (eg. "color")
+    pub fn key(&self) -> &str {
+        match self {
+            Property::Color(_) => "color",
+            Property::Rigidity(_) => "rigidity",
+            Property::Temperature(_) => "temperature",
+            Property::Lighting(_) => "lighting",
+            Property::Custom(_) => "custom",
+        }
+    }
+
+    /// The value of the property (eg. "purple")
+    pub fn value(&self) -> String {
+        match self {
+            Property::Color(c) => format!("{:?}", c).to_lowercase(),
+            Property::Rigidity(r) => format!("{:?}", r).to_lowercase(),
+            Property::Temperature(t) => format!("{:?}", t).to_lowercase(),
+            Property::Lighting(l) => format!("{:?}", l).to_lowercase(),
+            Property::Custom(c) => c.clone(),
+        }
+    }
+}
+
 /// Parse a string into a property
 /// 
 /// This implementation of TryFrom attempts to deconstruct a given string into
@@ -56,7 +81,7 @@ impl From<&str> for Property {
 }
 
 /// Color properties
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Color {
     Red,
     Blue,
@@ -93,13 +118,13 @@ impl TryFrom<&str> for Color {
             "white" => Ok(Color::White),
             "violet" => Ok(Color::Violet),
             "purple" => Ok(Color::Purple),
-            _ => return Err(Error::PropertyConversionFailed),
+            _ => Err(Error::PropertyConversionFailed),
         }
     }
 }
 
 /// Rigidity properties
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Rigidity {
     Rigid,
     Solid,
@@ -128,13 +153,13 @@ impl TryFrom<&str> for Rigidity {
             "aerially" => Ok(Rigidity::Aerially),
             "frozen" => Ok(Rigidity::Frozen),
             "molten" => Ok(Rigidity::Molten),
-            _ => return Err(Error::PropertyConversionFailed),
+            _ => Err(Error::PropertyConversionFailed),
         }
     }
 }
 
 /// Temperature properties
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Temperature {
     Cold,
     Cool,
@@ -159,13 +184,13 @@ impl TryFrom<&str> for Temperature {
             "cool" => Ok(Temperature::Cool),
             "warm" => Ok(Temperature::Warm),
             "hot" => Ok(Temperature::Hot),
-            _ => return Err(Error::PropertyConversionFailed),
+            _ => Err(Error::PropertyConversionFailed),
         }
     }
 }
 
 /// Lighting properties
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Lighting {
     Pulsing,
     Radiating,
@@ -194,7 +219,7 @@ impl TryFrom<&str> for Lighting {
             "bright" => Ok(Lighting::Bright),
             "dark" => Ok(Lighting::Dark),
             "glowing" => Ok(Lighting::Glowing),
-            _ => return Err(Error::PropertyConversionFailed),
+            _ => Err(Error::PropertyConversionFailed),
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,234 @@
+//! Puzzles
+//!
+//! Text puzzles attached to ports and terminals. Players "solve" a puzzle by
+//! giving an answer and may buy "hint"s, which lower the points the puzzle
+//! is worth. Puzzles come in kinds:
+//!  * substitution ciphers, solved by the plain text
+//!  * hashes, solved by any text with the given SHA-256 digest
+//!  * pattern locks, solved by the dots of the pattern on a 3x3 keypad in
+//!    order (eg. "1-5-9")
+//!
+//! The progress of a player (attempts, hints bought, solved) is kept in the
+//! account of the player. Solving a puzzle runs its hooks, which feed
+//! quests and achievements (see `hooks`).
+
+use std::sync::Arc;
+
+use sha2::{Digest, Sha256};
+
+use super::hooks::Hook;
+use super::intern::intern;
+
+/// Kinds of puzzles
+#[derive(Debug, Clone, PartialEq)]
+pub enum Kind {
+    /// A substitution cipher, shown as cipher text
+    Substitution { ciphertext: Arc<str>, plaintext: Arc<str> },
+    /// A SHA-256 digest (hex encoded) of the answer
+    Hash { digest: String },
+    /// A pattern lock on a 3x3 keypad (dots numbered 1 to 9)
+    Pattern { dots: Vec<u8> },
+}
+
+/// A hint and the points it costs
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hint {
+    pub text: Arc<str>,
+    pub cost: u32,
+}
+
+/// The progress of a player on a puzzle
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Progress {
+    /// Wrong answers given
+    pub attempts: u32,
+    /// Hints bought
+    pub hints: usize,
+    /// The player solved the puzzle
+    pub solved: bool,
+}
+
+/// Outcome of an attempt to solve a puzzle
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+    /// The answer was right, the player earned the points
+    Solved { points: u32 },
+    /// The answer was wrong
+    Wrong { attempts_left: Option<u32> },
+    /// The player used up all attempts
+    Locked,
+    /// The player solved the puzzle before
+    AlreadySolved,
+}
+
+/// A puzzle
+#[derive(Debug, Clone)]
+pub struct Puzzle {
+    id: Arc<str>,
+    kind: Kind,
+    prompt: Arc<str>,
+    hints: Vec<Hint>,
+    points: u32,
+    max_attempts: Option<u32>,
+    on_solve: Vec<Hook>,
+    season: Option<Arc<str>>,
+}
+
+impl Puzzle {
+    /// Create a new puzzle worth no points, with unlimited attempts
+    ///
+    /// The id identifies the progress of players on the puzzle, so it must
+    /// be unique in the world.
+    pub fn new(id: &str, kind: Kind, prompt: &str) -> Puzzle {
+        Puzzle {
+            id: intern(id),
+            kind,
+            prompt: intern(prompt),
+            hints: Vec::new(),
+            points: 0,
+            max_attempts: None,
+            on_solve: Vec::new(),
+            season: None,
+        }
+    }
+
+    /// A substitution cipher of the plain text with the given key
+    ///
+    /// The key maps the letters "a" to "z" to their substitutes in order.
+    /// Characters that are not letters are kept.
+    pub fn substitution(plaintext: &str, key: &str) -> Kind {
+        let key: Vec<char> = key.chars().collect();
+        let ciphertext: String = plaintext.chars()
+            .map(|c| match c.to_ascii_lowercase() {
+                l @ 'a'..='z' => {
+                    let s = key.get(l as usize - 'a' as usize).copied().unwrap_or(l);
+                    if c.is_ascii_uppercase() { s.to_ascii_uppercase() } else { s }
+                },
+                _ => c,
+            })
+            .collect();
+        Kind::Substitution { ciphertext: intern(&ciphertext), plaintext: intern(plaintext) }
+    }
+
+    /// A hash puzzle solved by the given answer
+    pub fn hash(answer: &str) -> Kind {
+        Kind::Hash { digest: sha256(answer) }
+    }
+
+    /// Add a hint (hints are bought in the order they were added)
+    pub fn with_hint(mut self, text: &str, cost: u32) -> Puzzle {
+        self.hints.push(Hint { text: intern(text), cost });
+        self
+    }
+
+    /// Set the points the puzzle is worth
+    pub fn with_points(mut self, points: u32) -> Puzzle {
+        self.points = points;
+        self
+    }
+
+    /// Limit the wrong answers a player may give
+    pub fn with_max_attempts(mut self, max_attempts: u32) -> Puzzle {
+        self.max_attempts = Some(max_attempts);
+        self
+    }
+
+    /// Run a hook when a player solves the puzzle
+    pub fn on_solve(mut self, hook: Hook) -> Puzzle {
+        self.on_solve.push(hook);
+        self
+    }
+
+    /// Only let players solve the puzzle while a season runs
+    pub fn during(mut self, season: &str) -> Puzzle {
+        self.season = Some(intern(season));
+        self
+    }
+
+    /// The season the puzzle can be solved in (see `seasons`)
+    pub fn season(&self) -> Option<&str> {
+        self.season.as_deref()
+    }
+
+    /// The id of the puzzle
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// The hooks run when a player solves the puzzle
+    pub fn hooks(&self) -> &[Hook] {
+        &self.on_solve
+    }
+
+    /// Describe the puzzle as shown to players
+    pub fn describe(&self) -> String {
+        match &self.kind {
+            Kind::Substitution { ciphertext, .. } => format!("{} The cipher reads \"{}\".", self.prompt, ciphertext),
+            Kind::Hash { digest } => format!("{} The hash reads \"{}\".", self.prompt, digest),
+            Kind::Pattern { .. } => format!("{} A keypad shows nine dots, numbered 1 to 9.", self.prompt),
+        }
+    }
+
+    /// Check if an answer solves the puzzle
+    ///
+    /// Cipher answers ignore case and everything but letters and digits,
+    /// pattern answers everything but the dots.
+    pub fn check(&self, answer: &str) -> bool {
+        match &self.kind {
+            Kind::Substitution { plaintext, .. } => normalize(answer) == normalize(plaintext),
+            Kind::Hash { digest } => sha256(answer.trim()) == *digest,
+            Kind::Pattern { dots } => {
+                let answer: Vec<u8> = answer.chars()
+                    .filter_map(|c| c.to_digit(10))
+                    .map(|d| d as u8)
+                    .collect();
+                answer == *dots
+            },
+        }
+    }
+
+    /// Attempt to solve the puzzle, tracking the progress of the player
+    pub fn attempt(&self, progress: &mut Progress, answer: &str) -> Outcome {
+        if progress.solved {
+            return Outcome::AlreadySolved;
+        }
+        if self.max_attempts.is_some_and(|m| progress.attempts >= m) {
+            return Outcome::Locked;
+        }
+        if self.check(answer) {
+            progress.solved = true;
+            return Outcome::Solved { points: self.points_for(progress) };
+        }
+        progress.attempts += 1;
+        Outcome::Wrong { attempts_left: self.max_attempts.map(|m| m.saturating_sub(progress.attempts)) }
+    }
+
+    /// Buy the next hint, if there is one left
+    pub fn hint(&self, progress: &mut Progress) -> Option<&Hint> {
+        let hint = self.hints.get(progress.hints)?;
+        progress.hints += 1;
+        Some(hint)
+    }
+
+    /// The points a player earns with the given progress (the points of
+    /// the puzzle minus the cost of the hints bought)
+    pub fn points_for(&self, progress: &Progress) -> u32 {
+        let cost: u32 = self.hints.iter().take(progress.hints).map(|h| h.cost).sum();
+        self.points.saturating_sub(cost)
+    }
+}
+
+/// The hex encoded SHA-256 digest of a text
+fn sha256(text: &str) -> String {
+    Sha256::digest(text.as_bytes()).iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Only the letters and digits of a text, in lower case
+fn normalize(text: &str) -> String {
+    text.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
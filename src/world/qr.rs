@@ -0,0 +1,32 @@
+//! QR codes
+//!
+//! Renders QR codes as text, two rows of modules per line of half blocks, so
+//! staff can show them on a terminal for attendees to scan (eg. login
+//! tokens).
+
+use qrcodegen::{QrCode, QrCodeEcc};
+
+/// Width of the light border around the code, in modules
+const QUIET_ZONE: i32 = 2;
+
+/// Render a text as QR code
+///
+/// Light modules are drawn, dark modules are left blank, so the code reads
+/// on the dark background of a terminal. Returns None if the text is too
+/// long for a QR code.
+pub fn render(text: &str) -> Option<String> {
+    let code = QrCode::encode_text(text, QrCodeEcc::Low).ok()?;
+    let light = |x: i32, y: i32| !code.get_module(x, y);
+    let (start, end) = (-QUIET_ZONE, code.size() + QUIET_ZONE);
+    let lines: Vec<String> = (start..end).step_by(2)
+        .map(|y| (start..end)
+            .map(|x| match (light(x, y), y + 1 < end && light(x, y + 1)) {
+                (true, true) => '█',
+                (true, false) => '▀',
+                (false, true) => '▄',
+                (false, false) => ' ',
+            })
+            .collect())
+        .collect();
+    Some(lines.join("\r\n"))
+}
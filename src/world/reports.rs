@@ -0,0 +1,119 @@
+//! Reports
+//!
+//! Bug reports filed by players from within the game. Each report captures
+//! the context the player was in when filing it, so the staff can reproduce
+//! the problem during a live event.
+
+use std::io;
+use std::path::Path;
+use tokio::fs::{self, OpenOptions};
+use tokio::io::AsyncWriteExt;
+use tracing::{error, info};
+
+use super::assets::GameAsset;
+use super::messages::{Event, Message};
+use super::{timestamp, GameWorld, Player};
+
+/// Name of the file bug reports are appended to (inside the data directory)
+pub const BUG_REPORT_FILE: &str = "bug_reports.jsonl";
+
+/// A bug report filed by a player
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BugReport {
+    /// Seconds since the epoch when the report was filed
+    pub timestamp: u64,
+    /// Version of the server
+    pub version: String,
+    /// Name of the reporting player
+    pub player: String,
+    /// Uid of the node the player was in, if any
+    pub location: Option<u64>,
+    /// The most recent commands of the player, oldest first
+    pub history: Vec<String>,
+    /// What the player describes as the bug
+    pub description: String,
+}
+
+impl BugReport {
+    /// Create a new bug report filed now
+    pub fn new(player: &str, location: Option<u64>, history: Vec<String>, description: &str) -> BugReport {
+        BugReport {
+            timestamp: timestamp(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            player: player.to_string(),
+            location,
+            history,
+            description: description.to_string(),
+        }
+    }
+
+    /// Append the report to the bug report file in the data directory
+    ///
+    /// Reports are stored as one JSON object per line. The data directory is
+    /// created if it does not exist yet.
+    pub async fn append_to(&self, data_dir: &Path) -> io::Result<()> {
+        fs::create_dir_all(data_dir).await?;
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(data_dir.join(BUG_REPORT_FILE)).await?;
+        let line = serde_json::to_string(self)? + "\n";
+        file.write_all(line.as_bytes()).await
+    }
+
+    /// Load all bug reports from the bug report file in the data directory
+    ///
+    /// If no bug report was filed yet, no reports are returned.
+    pub async fn load_all(data_dir: &Path) -> io::Result<Vec<BugReport>> {
+        let buf = match fs::read_to_string(data_dir.join(BUG_REPORT_FILE)).await {
+            Ok(b) => b,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        let mut reports = Vec::new();
+        for line in buf.lines().filter(|l| !l.trim().is_empty()) {
+            reports.push(serde_json::from_str(line)?);
+        }
+        Ok(reports)
+    }
+
+    /// Replace the bug report file in the data directory with the given reports
+    pub async fn save_all(data_dir: &Path, reports: &[BugReport]) -> io::Result<()> {
+        let mut buf = String::new();
+        for report in reports {
+            buf.push_str(&serde_json::to_string(report)?);
+            buf.push('\n');
+        }
+        fs::write(data_dir.join(BUG_REPORT_FILE), buf).await
+    }
+}
+
+/// File a bug report of a player with the context it is in
+///
+/// Returns the notification for the admins, None if no report was filed.
+pub(super) async fn file(description: &str, world: &GameWorld, player: &Player) -> Option<Message> {
+    if description.is_empty() {
+        player.send(Message::new(Event::Error, 
+            "Describe what went wrong, eg. \"bug the purple port does not open\".")).await;
+        return None;
+    }
+    let location = player.location
+        .and_then(|l| world.nodes.get(l))
+        .map(|n| n.uid());
+    let report = BugReport::new(&player.player_name, location, player.history.iter().cloned().collect(), description);
+    match report.append_to(&world.data_dir).await {
+        Ok(_) => {
+            info!("Player {} filed a bug report: {}", player.player_name, description);
+            player.send(Message::new(Event::Info, "Thank you. Your bug report was filed.")).await;
+            Some(Message::new(Event::Info, &format!("[bug] {} reports: {}", player.player_name, description))
+                .with_field("player", player.player_name.clone())
+                .with_field("location", location))
+        },
+        Err(e) => {
+            error!("Could not write bug report: {}", e);
+            player.send(Message::new(Event::Error, 
+                "Your bug report got lost in the matrix. Please tell the staff.")).await;
+            None
+        },
+    }
+}
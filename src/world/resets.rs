@@ -0,0 +1,174 @@
+//! Zone resets
+//!
+//! Resets bring the content of a zone back to its initial state for the
+//! next wave of attendees. A reset runs its steps on a timer (eg. every 30
+//! minutes) or when the staff issues "@reset <zone>". The steps are:
+//!  * resetting the progress of the players on the unsolved puzzles of the
+//!    zone, so players that used up their attempts may try again (solved
+//!    puzzles stay solved, nobody earns the points twice)
+//!  * re-arming the ICE on the terminals of the zone by closing the shells
+//!    accessing them
+//!  * announcing the reset to the players in the zone
+//!
+//! The timers run on the time of the world (see `clock`), so a paused world
+//! does not reset.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::Instant;
+use tracing::{error, info, warn};
+
+use crate::connection_manager::ClientId;
+use super::assets::GameAsset;
+use super::intern::intern;
+use super::messages::{Event, Message};
+use super::{GameWorld, Player};
+
+/// A step of a reset
+#[derive(Debug, Clone, PartialEq)]
+pub enum Step {
+    /// Reset the progress on the unsolved puzzles of the zone
+    Puzzles,
+    /// Re-arm the ICE on the terminals of the zone
+    Ice,
+    /// Tell the players in the zone
+    Announce(String),
+}
+
+/// The reset of a zone
+#[derive(Debug, Clone)]
+pub struct ZoneReset {
+    zone: Arc<str>,
+    every: Option<Duration>,
+    steps: Vec<Step>,
+    /// When the reset runs next, if it runs on a timer
+    due: Option<Instant>,
+}
+
+impl ZoneReset {
+    /// Create a reset of the zone (by name) without steps, run by the staff
+    /// only
+    pub fn new(zone: &str) -> ZoneReset {
+        ZoneReset { zone: intern(zone), every: None, steps: Vec::new(), due: None }
+    }
+
+    /// Run the reset on a timer
+    pub fn every(mut self, interval: Duration) -> ZoneReset {
+        self.every = Some(interval);
+        self
+    }
+
+    /// Add a step (steps run in the order they were added)
+    pub fn with_step(mut self, step: Step) -> ZoneReset {
+        self.steps.push(step);
+        self
+    }
+
+    /// The name of the zone
+    pub fn zone(&self) -> &str {
+        &self.zone
+    }
+
+    /// The steps of the reset
+    pub fn steps(&self) -> &[Step] {
+        &self.steps
+    }
+}
+
+/// The resets of the zones of the world
+#[derive(Debug, Default)]
+pub struct Resets {
+    resets: Vec<ZoneReset>,
+}
+
+impl Resets {
+    /// Add the reset of a zone (replacing an earlier reset of the zone)
+    pub fn add(&mut self, reset: ZoneReset) {
+        self.resets.retain(|r| !r.zone.eq_ignore_ascii_case(&reset.zone));
+        self.resets.push(reset);
+    }
+
+    /// Find the reset of a zone by its name (ignoring case)
+    pub fn get(&self, zone: &str) -> Option<&ZoneReset> {
+        self.resets.iter().find(|r| r.zone.eq_ignore_ascii_case(zone))
+    }
+
+    /// The resets due at the given time
+    ///
+    /// The timers of the resets returned start over. Timers start with the
+    /// first call.
+    pub fn due(&mut self, now: Instant) -> Vec<ZoneReset> {
+        let mut due = Vec::new();
+        for reset in self.resets.iter_mut() {
+            let every = match reset.every {
+                Some(e) => e,
+                None => continue,
+            };
+            match reset.due {
+                Some(d) if d <= now => {
+                    reset.due = Some(now + every);
+                    due.push(reset.clone());
+                },
+                Some(_) => {},
+                None => reset.due = Some(now + every),
+            }
+        }
+        due
+    }
+}
+
+/// Run the steps of the reset of a zone
+/// 
+/// Returns what was reset (shown to the staff).
+pub(super) async fn reset(reset: &ZoneReset, world: &GameWorld, players: &mut HashMap<ClientId, Player>) -> String {
+    let index = match world.find_zone(reset.zone()) {
+        Some(i) => i,
+        None => {
+            warn!("Cannot reset zone {}, it does not exist.", reset.zone());
+            return format!("There is no zone {}.", reset.zone());
+        },
+    };
+    let node = &world.nodes[index];
+    let mut done = Vec::new();
+    for step in reset.steps() {
+        match step {
+            Step::Puzzles => {
+                let ids: HashSet<&str> = node.puzzles().map(|p| p.id()).collect();
+                let mut count = 0;
+                for p in players.values_mut() {
+                    let before = p.account.puzzles.len();
+                    p.account.puzzles.retain(|id, progress| progress.solved || !ids.contains(id.as_str()));
+                    if p.account.puzzles.len() == before {
+                        continue;
+                    }
+                    count += 1;
+                    if let Err(e) = p.account.save(&world.data_dir).await {
+                        error!("Could not save account of {}: {}", p.player_name, e);
+                    }
+                }
+                done.push(format!("reset the puzzles of {} players", count));
+            },
+            Step::Ice => {
+                let message = Message::new(Event::Info, "The connection drops. The ICE of the terminal re-arms.")
+                    .with_field("terminal", Option::<String>::None);
+                let mut count = 0;
+                for p in players.values_mut().filter(|p| p.shell.as_ref().is_some_and(|s| s.node == index)) {
+                    p.shell = None;
+                    p.send(message.clone()).await;
+                    count += 1;
+                }
+                done.push(format!("closed {} shells", count));
+            },
+            Step::Announce(text) => {
+                let message = Message::new(Event::Info, text);
+                for p in players.values().filter(|p| p.location == Some(index)) {
+                    p.send(message.clone()).await;
+                }
+                done.push("announced the reset".to_string());
+            },
+        }
+    }
+    info!("Reset zone {}: {}.", node.name(), done.join(", "));
+    format!("Reset {}: {}.", node.name(), done.join(", "))
+}
@@ -0,0 +1,68 @@
+//! Safety
+//!
+//! Zones protect players with rules set in the zone data (see
+//! `assets::Node`):
+//!  * safe zones: NPCs neither trace nor strike players in the zone, so no
+//!    deck is jammed and no drone wrecked there, and nobody may start a
+//!    fight in it. Players cannot fight each other anywhere, so safe zones
+//!    only need to stop the fights with NPCs.
+//!  * newbie restrictions: players below the level of the zone may not run
+//!    dangerous commands (fighting and gambling) in it.
+//!
+//! The level of a player grows with its score. The rules are checked when an
+//! action of a player is due (see `process_queues`), so every command goes
+//! through them, whether typed, queued or run by a trigger.
+
+use std::fmt;
+
+use super::actions::Action;
+
+/// Points of score per level
+pub const LEVEL_POINTS: u64 = 50;
+
+/// The level of a player with the score, starting at level 1
+pub fn level(score: u64) -> u64 {
+    score / LEVEL_POINTS + 1
+}
+
+/// The safety rules of a zone
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Safety {
+    /// No traces, strikes or fights in the zone
+    pub safe: bool,
+    /// The level players need to run dangerous commands in the zone
+    pub min_level: u64,
+}
+
+impl Safety {
+    /// Check if a player of the level may perform the action in the zone
+    pub fn check(&self, action: &Action, level: u64) -> Result<(), Refusal> {
+        if self.safe && action.is_combat() {
+            return Err(Refusal::Safe);
+        }
+        if action.is_dangerous() && level < self.min_level {
+            return Err(Refusal::Level(self.min_level));
+        }
+        Ok(())
+    }
+}
+
+/// Why the rules of a zone refuse an action
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Refusal {
+    /// Nobody fights in a safe zone
+    Safe,
+    /// The player is below the level the zone requires
+    Level(u64),
+}
+
+/// Display a refusal as told to the player
+impl fmt::Display for Refusal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Refusal::Safe => write!(f, "This is a safe zone. Nobody fights here."),
+            Refusal::Level(min) => write!(f, "You need level {} for that here ({} points). Solve puzzles to level up.",
+                min, min.saturating_sub(1) * LEVEL_POINTS),
+        }
+    }
+}
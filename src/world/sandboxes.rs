@@ -0,0 +1,262 @@
+//! Sandboxes
+//!
+//! Builders experiment in a personal, throwaway copy of a zone ("@sandbox
+//! enter" for the zone the builder is in, or "@sandbox enter <node uid>")
+//! instead of the live world. The copy is a node of its own, with fresh uids
+//! for the node and its assets. Nobody spawns there but the builder, who is
+//! moved into it. While in the sandbox, the edits of the builder (see
+//! `edits`) only reach the copy, "@undo" undoes them one by one. Puzzles can
+//! be tried and terminals accessed, but solving a puzzle or running a hook
+//! records nothing in the account of the builder.
+//!
+//! The builder leaves the sandbox with
+//!  * "@sandbox discard": the copy is dropped
+//!  * "@sandbox promote": the edits are applied to the live zone, as if the
+//!    builder made them there (they go to the change log and can be undone),
+//!    or none, if one cannot be applied
+//!
+//! and returns to where it was. A sandbox is discarded when its builder
+//! hangs up.
+
+use std::collections::HashMap;
+
+use generational_arena::{Arena, Index};
+use tracing::info;
+
+use crate::connection_manager::ClientId;
+use super::admin::SandboxStep;
+use super::assets::{AssetID, GameAsset, Node};
+use super::edits::{self, Edit};
+use super::{GameWorld, Player, Spawnable};
+
+/// The copy of a zone a builder experiments in
+#[derive(Debug, Clone)]
+pub struct Sandbox {
+    /// The uid of the live zone
+    pub zone: AssetID,
+    /// The copy in the world
+    pub index: Index,
+    /// Where the builder was before it entered the sandbox
+    pub returns_to: Option<Index>,
+    /// The live uids by the uids of their copies
+    origins: HashMap<AssetID, AssetID>,
+    /// The edits applied to the copy and the edits undoing them
+    edits: Vec<(Edit, Edit)>,
+}
+
+impl Sandbox {
+    /// Number of edits applied to the copy
+    pub fn edits(&self) -> usize {
+        self.edits.len()
+    }
+
+    /// Apply the edits to the live zone
+    ///
+    /// Returns the edits applied and the edits undoing them, or why an edit
+    /// cannot be applied. The edits are tried on a copy of the world first,
+    /// so either all or none are applied.
+    pub fn promote(&self, nodes: &mut Arena<Node>) -> Result<Vec<(Edit, Edit)>, String> {
+        self.replay(&mut nodes.clone())?;
+        self.replay(nodes)
+    }
+
+    /// Apply the edits, translated to the live uids
+    fn replay(&self, nodes: &mut Arena<Node>) -> Result<Vec<(Edit, Edit)>, String> {
+        let mut origins = self.origins.clone();
+        let mut applied = Vec::new();
+        for (edit, undo) in &self.edits {
+            let live = |uid: &AssetID| origins.get(uid).copied()
+                .ok_or_else(|| format!("#{} is not in the live zone.", uid));
+            let edit = match edit {
+                Edit::Describe { uid, description } => {
+                    Edit::Describe { uid: live(uid)?, description: description.clone() }
+                },
+                Edit::Create { node, kind, description } => {
+                    Edit::Create { node: live(node)?, kind: kind.clone(), description: description.clone() }
+                },
+                Edit::Remove { node, uid } => Edit::Remove { node: live(node)?, uid: live(uid)? },
+                Edit::Restrict { node, acl } => Edit::Restrict { node: live(node)?, acl: acl.clone() },
+            };
+            let live_undo = edits::apply(nodes, &edit)?;
+            // Assets created in the sandbox are created in the live zone
+            if let (Edit::Create { .. }, Edit::Remove { uid, .. }, Edit::Remove { uid: live_uid, .. })
+                = (&edit, undo, &live_undo) {
+                origins.insert(*uid, *live_uid);
+            }
+            applied.push((edit, live_undo));
+        }
+        Ok(applied)
+    }
+}
+
+/// The sandboxes of the builders, by builder
+#[derive(Debug, Default)]
+pub struct Sandboxes {
+    sandboxes: HashMap<String, Sandbox>,
+}
+
+impl Sandboxes {
+    /// Copy a zone into a sandbox for a builder
+    ///
+    /// Returns where the copy is in the world, or why there is no sandbox.
+    pub fn enter(&mut self, nodes: &mut Arena<Node>, builder: &str, zone: AssetID, returns_to: Option<Index>)
+        -> Result<Index, String> {
+        let key = builder.to_lowercase();
+        if self.sandboxes.contains_key(&key) {
+            return Err("You have a sandbox already. Promote or discard it first.".to_string());
+        }
+        let live = nodes.iter()
+            .map(|(_, n)| n)
+            .find(|n| n.uid() == zone)
+            .ok_or_else(|| format!("There is no node #{}.", zone))?;
+        if self.sandboxes.values().any(|s| nodes.get(s.index).is_some_and(|n| n.uid() == zone)) {
+            return Err(format!("Node #{} is a sandbox.", zone));
+        }
+        let mut copy = live.clone();
+        copy.update_name(&format!("{} [sandbox]", live.name()));
+        let mut uid = edits::next_uid(nodes);
+        let mut origins = HashMap::from([(uid, zone)]);
+        copy.renumber(uid);
+        for asset in copy.assets_mut() {
+            uid += 1;
+            origins.insert(uid, asset.uid());
+            asset.renumber(uid);
+        }
+        let index = nodes.insert(copy);
+        self.sandboxes.insert(key, Sandbox { zone, index, returns_to, origins, edits: Vec::new() });
+        Ok(index)
+    }
+
+    /// The sandbox of a builder, if it has one
+    pub fn get(&self, builder: &str) -> Option<&Sandbox> {
+        self.sandboxes.get(&builder.to_lowercase())
+    }
+
+    /// True if the location is a sandbox
+    pub fn contains(&self, location: Option<Index>) -> bool {
+        location.is_some_and(|l| self.sandboxes.values().any(|s| s.index == l))
+    }
+
+    /// Apply an edit of a builder to its sandbox
+    ///
+    /// Returns the edit undoing it, or why it cannot be applied (eg. as it
+    /// changes another node than the sandbox).
+    pub fn apply(&mut self, nodes: &mut Arena<Node>, builder: &str, edit: Edit) -> Result<Edit, String> {
+        let sandbox = self.sandboxes.get_mut(&builder.to_lowercase()).ok_or("You have no sandbox.")?;
+        let node = nodes.get(sandbox.index).map(|n| n.uid());
+        if node.is_none() || edits::affected(nodes, &edit) != node {
+            return Err(format!("You are in a sandbox. Only edit its node #{} and its assets.",
+                node.unwrap_or_default()));
+        }
+        let undo = edits::apply(nodes, &edit)?;
+        sandbox.edits.push((edit, undo.clone()));
+        Ok(undo)
+    }
+
+    /// Undo the last edit of a builder in its sandbox
+    ///
+    /// Returns the edit undoing it, or None if there is nothing to undo.
+    pub fn undo(&mut self, nodes: &mut Arena<Node>, builder: &str) -> Option<Result<Edit, String>> {
+        let sandbox = self.sandboxes.get_mut(&builder.to_lowercase())?;
+        let undo = sandbox.edits.last()?.1.clone();
+        let result = edits::apply(nodes, &undo).map(|_| undo);
+        if result.is_ok() {
+            sandbox.edits.pop();
+        }
+        Some(result)
+    }
+
+    /// Close the sandbox of a builder, removing the copy from the world
+    pub fn close(&mut self, nodes: &mut Arena<Node>, builder: &str) -> Option<Sandbox> {
+        let sandbox = self.sandboxes.remove(&builder.to_lowercase())?;
+        nodes.remove(sandbox.index);
+        Some(sandbox)
+    }
+}
+
+/// Apply an edit of a builder to its sandbox
+pub(super) fn edit(edit: Edit, builder: &str, world: &mut GameWorld) -> String {
+    let text = edit.to_string();
+    match world.sandboxes.apply(&mut world.nodes, builder, edit) {
+        Ok(Edit::Remove { uid, .. }) => format!("Created asset #{} in the sandbox.", uid),
+        Ok(_) => format!("Changed the sandbox: {}.", text),
+        Err(e) => e,
+    }
+}
+
+/// Undo the latest edit of a builder in its sandbox
+pub(super) fn undo(builder: &str, world: &mut GameWorld) -> String {
+    match world.sandboxes.undo(&mut world.nodes, builder) {
+        Some(Ok(undo)) => format!("Undone in the sandbox: {}.", undo),
+        Some(Err(e)) => format!("Cannot undo ({}).", e),
+        None => "There is nothing to undo in the sandbox.".to_string(),
+    }
+}
+
+/// Move a builder into a sandbox, or out of it by promoting or discarding
+/// the sandbox
+pub(super) fn administer(step: SandboxStep, builder: &str, client_id: ClientId, world: &mut GameWorld,
+                         players: &mut HashMap<ClientId, Player>) -> String {
+    match step {
+        SandboxStep::Enter(zone) => {
+            let returns_to = players.get(&client_id).and_then(|p| p.location);
+            let zone = zone.or_else(|| returns_to.and_then(|l| world.nodes.get(l)).map(|n| n.uid()));
+            match zone.map(|z| world.sandboxes.enter(&mut world.nodes, builder, z, returns_to)) {
+                Some(Ok(index)) => {
+                    if let Some(admin) = players.get_mut(&client_id) {
+                        admin.set_spawn_point_index(index);
+                    }
+                    let uid = world.nodes.get(index).map(|n| n.uid()).unwrap_or_default();
+                    info!("Builder {} entered a sandbox of #{}.", builder, zone.unwrap_or_default());
+                    format!("You are in your sandbox #{}. Your edits only change it until you \"@sandbox promote\" \
+                        or \"@sandbox discard\" it.", uid)
+                },
+                Some(Err(e)) => e,
+                None => "In limbo there is nothing to copy. Give the uid of a node.".to_string(),
+            }
+        },
+        SandboxStep::Promote => {
+            let promoted = match world.sandboxes.get(builder) {
+                Some(sandbox) => sandbox.promote(&mut world.nodes).map(|applied| (sandbox.zone, applied)),
+                None => Err("You have no sandbox.".to_string()),
+            };
+            match promoted {
+                Ok((zone, applied)) => {
+                    let count = applied.len();
+                    for (edit, undo) in applied {
+                        info!("Builder {} changed the world: {}.", builder, edit);
+                        world.history.record(builder, edit, undo);
+                    }
+                    leave(world, players, client_id, builder);
+                    format!("Promoted {} edits to the live node #{}.", count, zone)
+                },
+                Err(e) => format!("Nothing promoted, the edits cannot be applied: {}", e),
+            }
+        },
+        SandboxStep::Discard => {
+            match leave(world, players, client_id, builder) {
+                Some(edits) => format!("Discarded the sandbox and its {} edits.", edits),
+                None => "You have no sandbox.".to_string(),
+            }
+        },
+    }
+}
+
+/// Close the sandbox of a builder and move the builder back to where it
+/// was, or to a spawn node
+///
+/// Returns the number of edits made in the sandbox, or None if the builder
+/// has no sandbox.
+fn leave(world: &mut GameWorld, players: &mut HashMap<ClientId, Player>, client_id: ClientId,
+         builder: &str) -> Option<usize> {
+    let sandbox = world.sandboxes.close(&mut world.nodes, builder)?;
+    if let Some(player) = players.get_mut(&client_id) {
+        match sandbox.returns_to.filter(|i| world.nodes.contains(*i)) {
+            Some(index) => player.set_spawn_point_index(index),
+            None => if world.spawn(player).is_err() {
+                player.location = None;
+            },
+        }
+    }
+    Some(sandbox.edits())
+}
@@ -0,0 +1,306 @@
+//! Schedule
+//!
+//! "schedule" shows the upcoming events of the con: talks and parties in the
+//! real world and events in the world (eg. a raid on the mainframe). Players
+//! subscribe to a reminder with "schedule remind <id>", the herald tells
+//! them when the event starts in `REMIND_BEFORE` or less, once.
+//!
+//! The events are stored in the schedule file inside the data directory.
+//! Admins add and remove events at runtime ("@schedule add", "@schedule
+//! remove"), or edit the file by hand and load it again with "@schedule
+//! reload". Forgotten players are removed from the subscribers (see
+//! `privacy`). The format of the file:
+//! ```ignore
+//!     [{ "id": <id>, "kind": "con" | "world", "start": <seconds since the epoch>,
+//!        "title": <text>, "subscribers": [<player>, ...], "reminded": <bool> }, ...]
+//! ```
+
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use tokio::fs;
+use tracing::error;
+
+use crate::connection_manager::ClientId;
+use super::actions::Action;
+use super::admin::ScheduleStep;
+use super::messages::{Event, Message};
+use super::{stats, timestamp, GameWorld, Player};
+
+/// Name of the file the schedule is stored in (inside the data directory)
+pub const SCHEDULE_FILE: &str = "schedule.json";
+
+/// Time before an event its subscribers are reminded, in seconds
+pub const REMIND_BEFORE: u64 = 10 * 60;
+
+/// Number of upcoming events shown
+pub const UPCOMING: usize = 10;
+
+/// Where an event takes place
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Kind {
+    /// At the con in the real world (eg. a talk)
+    Con,
+    /// In the world (eg. a raid)
+    World,
+}
+
+/// Display the kind as typed by admins
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Kind::Con => write!(f, "con"),
+            Kind::World => write!(f, "world"),
+        }
+    }
+}
+
+/// An event of the schedule
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Entry {
+    pub id: u64,
+    pub kind: Kind,
+    /// Seconds since the epoch the event starts at
+    pub start: u64,
+    pub title: String,
+    /// The players reminded of the event (in lowercase)
+    #[serde(default)]
+    pub subscribers: BTreeSet<String>,
+    /// True once the subscribers were reminded
+    #[serde(default)]
+    pub reminded: bool,
+}
+
+/// What a player does with the schedule
+#[derive(Debug, Clone, PartialEq)]
+pub enum Scheduling {
+    /// Show the upcoming events
+    List,
+    /// Subscribe to the reminder of an event (by id)
+    Remind(u64),
+    /// Unsubscribe from the reminder of an event (by id)
+    Forget(u64),
+}
+
+/// Display a request as typed by the player
+impl fmt::Display for Scheduling {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Scheduling::List => write!(f, "schedule"),
+            Scheduling::Remind(id) => write!(f, "schedule remind {}", id),
+            Scheduling::Forget(id) => write!(f, "schedule forget {}", id),
+        }
+    }
+}
+
+/// The events of the con
+#[derive(Debug, Default)]
+pub struct Schedule {
+    entries: Vec<Entry>,
+}
+
+impl Schedule {
+    /// Load the schedule stored in the data directory, an empty schedule if
+    /// there is none
+    pub async fn load(data_dir: &Path) -> io::Result<Schedule> {
+        match fs::read(Schedule::path(data_dir)).await {
+            Ok(buf) => Ok(Schedule { entries: serde_json::from_slice(&buf)? }),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Schedule::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Add an event
+    ///
+    /// Returns the id of the event.
+    pub fn add(&mut self, kind: Kind, start: u64, title: &str) -> u64 {
+        let id = self.entries.iter().map(|e| e.id).max().unwrap_or_default() + 1;
+        self.entries.push(Entry { id, kind, start, title: title.to_string(), subscribers: BTreeSet::new(),
+            reminded: false });
+        id
+    }
+
+    /// Remove an event, None if there is none of the id
+    pub fn remove(&mut self, id: u64) -> Option<Entry> {
+        let position = self.entries.iter().position(|e| e.id == id)?;
+        Some(self.entries.remove(position))
+    }
+
+    /// The events starting from the given time on, the next first
+    pub fn upcoming(&self, now: u64) -> Vec<&Entry> {
+        let mut upcoming: Vec<&Entry> = self.entries.iter().filter(|e| e.start >= now).collect();
+        upcoming.sort_by_key(|e| e.start);
+        upcoming.truncate(UPCOMING);
+        upcoming
+    }
+
+    /// Describe the upcoming events for a player, marking those it is
+    /// reminded of
+    pub fn describe(&self, now: u64, player: &str) -> String {
+        let upcoming = self.upcoming(now);
+        if upcoming.is_empty() {
+            return "There are no upcoming events.".to_string();
+        }
+        let player = player.to_lowercase();
+        let lines: Vec<String> = upcoming.iter()
+            .map(|e| {
+                let reminder = if e.subscribers.contains(&player) { ", reminder set" } else { "" };
+                format!("  #{} in {}: {} ({}{})", e.id, stats::duration(e.start - now), e.title, e.kind, reminder)
+            })
+            .collect();
+        format!("Upcoming events (\"schedule remind <id>\" for a reminder):\r\n{}", lines.join("\r\n"))
+    }
+
+    /// Subscribe a player to the reminder of an upcoming event
+    ///
+    /// Returns the event, or why the player cannot subscribe.
+    pub fn subscribe(&mut self, id: u64, player: &str, now: u64) -> Result<&Entry, String> {
+        let entry = self.entries.iter_mut().find(|e| e.id == id && e.start >= now)
+            .ok_or_else(|| format!("There is no upcoming event #{}.", id))?;
+        if entry.reminded {
+            return Err(format!("{} starts in {}.", entry.title, stats::duration(entry.start - now)));
+        }
+        entry.subscribers.insert(player.to_lowercase());
+        Ok(entry)
+    }
+
+    /// Unsubscribe a player from the reminder of an event
+    ///
+    /// Returns the event, None if the player is not reminded of it.
+    pub fn unsubscribe(&mut self, id: u64, player: &str) -> Option<&Entry> {
+        let entry = self.entries.iter_mut().find(|e| e.id == id)?;
+        if !entry.subscribers.remove(&player.to_lowercase()) {
+            return None;
+        }
+        Some(entry)
+    }
+
+    /// The events a player is reminded of
+    pub fn subscriptions(&self, player: &str) -> Vec<&Entry> {
+        let player = player.to_lowercase();
+        self.entries.iter().filter(|e| e.subscribers.contains(&player)).collect()
+    }
+
+    /// Unsubscribe a player from the reminders of all events (eg. when the
+    /// player is forgotten)
+    pub fn unsubscribe_all(&mut self, player: &str) {
+        let player = player.to_lowercase();
+        for entry in self.entries.iter_mut() {
+            entry.subscribers.remove(&player);
+        }
+    }
+
+    /// The events due to be reminded of at the given time, each only once
+    pub fn due(&mut self, now: u64) -> Vec<Entry> {
+        let mut due = Vec::new();
+        for entry in self.entries.iter_mut().filter(|e| !e.reminded && e.start <= now + REMIND_BEFORE) {
+            entry.reminded = true;
+            if entry.start >= now && !entry.subscribers.is_empty() {
+                due.push(entry.clone());
+            }
+        }
+        due
+    }
+
+    /// Save the schedule to the data directory
+    pub async fn save(&self, data_dir: &Path) -> io::Result<()> {
+        fs::create_dir_all(data_dir).await?;
+        fs::write(Schedule::path(data_dir), serde_json::to_vec_pretty(&self.entries)?).await
+    }
+
+    /// Remove a player from the subscribers in the schedule file
+    pub async fn remove_stored(data_dir: &Path, player: &str) -> io::Result<()> {
+        let mut schedule = Schedule::load(data_dir).await?;
+        if schedule.subscriptions(player).is_empty() {
+            return Ok(());
+        }
+        schedule.unsubscribe_all(player);
+        schedule.save(data_dir).await
+    }
+
+    fn path(data_dir: &Path) -> PathBuf {
+        data_dir.join(SCHEDULE_FILE)
+    }
+}
+
+/// Show the upcoming events of the con to a player or set its reminders
+pub(super) async fn consult(a: &Action, world: &mut GameWorld, client_id: ClientId,
+                            players: &HashMap<ClientId, Player>) {
+    let (scheduling, player) = match (a, players.get(&client_id)) {
+        (Action::Schedule(s), Some(p)) => (s, p),
+        _ => return,
+    };
+    let now = timestamp();
+    let result = match scheduling {
+        Scheduling::List => {
+            player.send(Message::new(Event::Info, &world.schedule.describe(now, &player.player_name))
+                .with_field("events", world.schedule.upcoming(now))).await;
+            return;
+        },
+        Scheduling::Remind(id) => world.schedule.subscribe(*id, &player.player_name, now)
+            .map(|e| format!("The herald will remind you of {} {} minutes before it starts.", e.title,
+                REMIND_BEFORE / 60)),
+        Scheduling::Forget(id) => world.schedule.unsubscribe(*id, &player.player_name)
+            .map(|e| format!("You will not be reminded of {}.", e.title))
+            .ok_or_else(|| format!("You have no reminder for event #{}.", id)),
+    };
+    match result {
+        Ok(text) => {
+            if let Err(e) = world.schedule.save(&world.data_dir).await {
+                error!("Could not save the schedule: {}", e);
+            }
+            player.send(Message::new(Event::Info, &text)).await;
+        },
+        Err(reason) => player.send(Message::new(Event::Error, &reason)).await,
+    }
+}
+
+/// Remind the players subscribed of the events starting soon, as tells of
+/// the herald
+pub(super) async fn process(world: &mut GameWorld, players: &HashMap<ClientId, Player>) {
+    let now = timestamp();
+    let due = world.schedule.due(now);
+    if due.is_empty() {
+        return;
+    }
+    if let Err(e) = world.schedule.save(&world.data_dir).await {
+        error!("Could not save the schedule: {}", e);
+    }
+    for entry in due {
+        let text = format!("The herald tells you: {} starts in {}.", entry.title,
+            stats::duration(entry.start.saturating_sub(now)));
+        let message = Message::new(Event::Chat, &text).with_field("from", "herald").with_field("event", entry.id);
+        for p in players.values().filter(|p| entry.subscribers.contains(&p.player_name.to_lowercase())) {
+            p.send(message.clone()).await;
+        }
+    }
+}
+
+/// Add, remove or reload the events for an admin, returns the reply
+pub(super) async fn administer(step: ScheduleStep, world: &mut GameWorld) -> String {
+    match step {
+        ScheduleStep::Add { kind, minutes, title } => {
+            let id = world.schedule.add(kind, timestamp() + minutes * 60, &title);
+            match world.schedule.save(&world.data_dir).await {
+                Ok(()) => format!("Added event #{} ({}), starting in {} minutes.", id, title, minutes),
+                Err(e) => format!("Added event #{}, but could not save the schedule: {}", id, e),
+            }
+        },
+        ScheduleStep::Remove(id) => match world.schedule.remove(id) {
+            Some(entry) => match world.schedule.save(&world.data_dir).await {
+                Ok(()) => format!("Removed event #{} ({}).", id, entry.title),
+                Err(e) => format!("Removed event #{}, but could not save the schedule: {}", id, e),
+            },
+            None => format!("There is no event #{}.", id),
+        },
+        ScheduleStep::Reload => match Schedule::load(&world.data_dir).await {
+            Ok(loaded) => {
+                world.schedule = loaded;
+                format!("Reloaded the schedule, {} upcoming events.", world.schedule.upcoming(timestamp()).len())
+            },
+            Err(e) => format!("Could not reload the schedule, keeping the current one: {}", e),
+        },
+    }
+}
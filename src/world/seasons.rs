@@ -0,0 +1,96 @@
+//! Seasons
+//!
+//! Limited-time content, eg. quests only running during the con. A season
+//! is a content flag with a start and an end (seconds since the epoch, set
+//! in the settings). Nodes, spawners of NPCs and puzzles tagged with a
+//! season are only active while it runs:
+//!  * players cannot spawn in an inactive node, players in a node whose
+//!    season ends are moved to a spawn node
+//!  * spawners of an inactive season spawn no NPCs and their NPCs despawn
+//!  * the puzzles of an inactive season cannot be solved
+//!
+//! Content tagged with a season the world does not know is never active.
+//! Admins force seasons on or off for testing with "@flags".
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// When a season runs
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Season {
+    /// Seconds since the epoch the season starts
+    pub start: u64,
+    /// Seconds since the epoch the season ends
+    pub end: u64,
+}
+
+/// The seasons of the world and the overrides of the admins
+#[derive(Debug, Clone, Default)]
+pub struct Seasons {
+    seasons: BTreeMap<String, Season>,
+    overrides: BTreeMap<String, bool>,
+}
+
+impl Seasons {
+    /// Add a season running from start to end
+    pub fn add(&mut self, name: &str, season: Season) {
+        self.seasons.insert(name.to_string(), season);
+    }
+
+    /// True if content of the season is active at a time
+    pub fn is_active(&self, name: &str, now: u64) -> bool {
+        match (self.overrides.get(name), self.seasons.get(name)) {
+            (Some(forced), _) => *forced,
+            (None, Some(season)) => season.start <= now && now < season.end,
+            (None, None) => false,
+        }
+    }
+
+    /// True if content tagged with the season (or untagged content) is
+    /// active at a time
+    pub fn allows(&self, season: Option<&str>, now: u64) -> bool {
+        season.is_none_or(|s| self.is_active(s, now))
+    }
+
+    /// Force a season on or off (Some) or let it follow its dates again
+    /// (None)
+    ///
+    /// Returns false if there is no such season.
+    pub fn set_override(&mut self, name: &str, forced: Option<bool>) -> bool {
+        if !self.seasons.contains_key(name) {
+            return false;
+        }
+        match forced {
+            Some(f) => self.overrides.insert(name.to_string(), f),
+            None => self.overrides.remove(name),
+        };
+        true
+    }
+
+    /// The names of the seasons active at a time
+    pub fn active(&self, now: u64) -> Vec<&str> {
+        self.seasons.keys().filter(|s| self.is_active(s, now)).map(|s| s.as_str()).collect()
+    }
+
+    /// Describe the seasons and their state at a time for the admins
+    pub fn describe(&self, now: u64) -> String {
+        if self.seasons.is_empty() {
+            return "There are no seasons.".to_string();
+        }
+        let lines: Vec<String> = self.seasons.iter()
+            .map(|(name, season)| {
+                let state = if self.is_active(name, now) { "active" } else { "inactive" };
+                let forced = if self.overrides.contains_key(name) { ", forced" } else { "" };
+                format!("  {} {} ({}{})", name, season, state, forced)
+            })
+            .collect();
+        format!("Seasons:\r\n{}", lines.join("\r\n"))
+    }
+}
+
+/// Display a season by its dates
+impl fmt::Display for Season {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} to {}", crate::feed::rfc3339(self.start), crate::feed::rfc3339(self.end))
+    }
+}
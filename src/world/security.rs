@@ -0,0 +1,162 @@
+//! Security
+//!
+//! Zones have a security level (0 for none, see `assets::Node`). Illegal
+//! actions in a secured zone are crimes: being caught stealing (see
+//! `ownership`) and cracking the puzzle of a port. A crime heats the
+//! offender up by its severity times the security level of the zone, and
+//! the heat cools down over time. While a player has heat:
+//!  * guard NPCs trace the player on sight (see `ai`)
+//!  * once the heat reaches `DISPATCH_HEAT`, security dispatches its ICE to
+//!    the node of the offender, tracing it
+//!  * "bribe" buys the heat off, the credits are destroyed
+//!
+//! When the heat is gone (cooled down or bought off), the ICE dispatched
+//! stands down. The heat is kept in memory only.
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+use super::npcs::{NpcID, Prototype};
+
+/// Heat at which security dispatches its ICE
+pub const DISPATCH_HEAT: u32 = 30;
+
+/// Time the heat takes to cool down by one
+const COOLDOWN: Duration = Duration::from_secs(6);
+
+/// Credits buying off one heat
+pub const BRIBE_PER_HEAT: u64 = 5;
+
+/// An illegal action
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Crime {
+    /// Being caught stealing an item
+    Theft,
+    /// Cracking the puzzle of a port
+    Cracking,
+}
+
+impl Crime {
+    /// The heat the crime adds per security level
+    pub fn severity(self) -> u32 {
+        match self {
+            Crime::Theft => 20,
+            Crime::Cracking => 10,
+        }
+    }
+}
+
+impl fmt::Display for Crime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Crime::Theft => write!(f, "theft"),
+            Crime::Cracking => write!(f, "cracking"),
+        }
+    }
+}
+
+/// What security makes of a crime
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Response {
+    /// The heat of the offender
+    pub heat: u32,
+    /// Security dispatches its ICE to the offender
+    pub dispatch: bool,
+}
+
+/// The heat of a player
+#[derive(Debug, Clone)]
+struct Heat {
+    amount: u32,
+    /// When the amount was set, it cools down from then
+    since: Instant,
+    /// The ICE dispatched to the player
+    responders: Vec<NpcID>,
+}
+
+impl Heat {
+    /// The amount left at the given time
+    fn at(&self, now: Instant) -> u32 {
+        let cooled = now.saturating_duration_since(self.since).as_secs() / COOLDOWN.as_secs();
+        self.amount.saturating_sub(u32::try_from(cooled).unwrap_or(u32::MAX))
+    }
+}
+
+/// The security of the world: the ICE it dispatches and the heat of the
+/// players
+#[derive(Debug, Default)]
+pub struct Security {
+    responder: Option<Prototype>,
+    heat: HashMap<String, Heat>,
+}
+
+impl Security {
+    /// Set the prototype of the ICE dispatched to offenders (none is
+    /// dispatched without one)
+    pub fn set_responder(&mut self, responder: Prototype) {
+        self.responder = Some(responder);
+    }
+
+    /// The prototype of the ICE dispatched to offenders
+    pub fn responder(&self) -> Option<&Prototype> {
+        self.responder.as_ref()
+    }
+
+    /// Record a crime of a player in a zone of the security level
+    ///
+    /// Returns the response of security, None if the zone is not secured.
+    /// Security dispatches its ICE once per heat, when it reaches
+    /// `DISPATCH_HEAT`.
+    pub fn commit(&mut self, player: &str, crime: Crime, level: u32, now: Instant) -> Option<Response> {
+        if level == 0 {
+            return None;
+        }
+        let heat = self.heat.entry(player.to_string())
+            .or_insert_with(|| Heat { amount: 0, since: now, responders: Vec::new() });
+        heat.amount = heat.at(now).saturating_add(crime.severity().saturating_mul(level));
+        heat.since = now;
+        let dispatch = heat.amount >= DISPATCH_HEAT && heat.responders.is_empty() && self.responder.is_some();
+        Some(Response { heat: heat.amount, dispatch })
+    }
+
+    /// Note the ICE dispatched to a player
+    pub fn dispatched(&mut self, player: &str, npc: NpcID) {
+        if let Some(heat) = self.heat.get_mut(player) {
+            heat.responders.push(npc);
+        }
+    }
+
+    /// The heat of a player at the given time
+    pub fn heat(&self, player: &str, now: Instant) -> u32 {
+        self.heat.get(player).map_or(0, |h| h.at(now))
+    }
+
+    /// The players with heat at the given time
+    pub fn wanted(&self, now: Instant) -> Vec<String> {
+        self.heat.iter().filter(|(_, h)| h.at(now) > 0).map(|(p, _)| p.clone()).collect()
+    }
+
+    /// Forget the players whose heat cooled down at the given time
+    ///
+    /// Returns the players and the ICE dispatched to them.
+    pub fn cool_down(&mut self, now: Instant) -> Vec<(String, Vec<NpcID>)> {
+        let cooled: Vec<String> = self.heat.iter().filter(|(_, h)| h.at(now) == 0).map(|(p, _)| p.clone()).collect();
+        cooled.into_iter().filter_map(|p| self.clear(&p).map(|responders| (p, responders))).collect()
+    }
+
+    /// The credits buying off the heat of a player, None if it has none
+    pub fn bribe(&self, player: &str, now: Instant) -> Option<u64> {
+        Some(self.heat(player, now)).filter(|h| *h > 0).map(|h| u64::from(h) * BRIBE_PER_HEAT)
+    }
+
+    /// Clear the heat of a player (eg. bought off)
+    ///
+    /// Returns the ICE dispatched to the player, None if it had no heat.
+    pub fn clear(&mut self, player: &str) -> Option<Vec<NpcID>> {
+        self.heat.remove(player).map(|h| h.responders)
+    }
+}
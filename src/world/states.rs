@@ -1,47 +1,135 @@
-use std::result;
-use std::io;
-use std::path::PathBuf;
-use std::fs::File;
-use std::io::prelude::*;
-use std::env;
-
-use tracing::debug;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
 use tracing::error;
 
+use crate::connection_manager::Frame;
+use super::loader;
+
+/// Line starting a new frame in a screen file, followed by the delay of the
+/// frame in milliseconds (eg. "%%frame 150")
+pub const FRAME_MARKER: &[u8] = b"%%frame";
+
+/// Delay of a frame if the marker does not give one
+pub const DEFAULT_FRAME_DELAY: Duration = Duration::from_millis(100);
+
 /// Struct to describe the state machine of the BBS
 /// Stores states in the form of nodes and transitions in the form of vectors
 /// signifying conditions and the next state
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum ScreenType {
+    /// Shown when the client connects
     Welcome,
+    /// Shown when the player joined the world, followed by the number of
+    /// visitors
+    Login,
+    /// Shown when the player leaves, followed by the statistics of the session
+    Goodbye,
+    /// Shown to players turned away while the world is down for maintenance
+    Maintenance,
 }
 
 impl ScreenType {
+    /// All screens
+    pub const ALL: [ScreenType; 4] = [ScreenType::Welcome, ScreenType::Login, ScreenType::Goodbye, 
+        ScreenType::Maintenance];
 
-    pub fn display_ansi(&self) -> result::Result<Vec<u8>, io::Error> {
+    /// Name of the file holding the screen (inside the screen directory)
+    pub fn file_name(&self) -> &'static str {
         match self {
-            ScreenType::Welcome=> {
-                // TODO set path in configuration an pass here
-                let path: PathBuf = env::current_dir()
-                    .unwrap()
-                    .join("screens")
-                    .join("00_welcome.ans");
-                match File::open(path) {
-                    Err(why) => {
-                        error!("Couldn't open welcome screen: {}", why);
-                        return Err(why)
-                    },
-                    Ok(file) => {
-                        let mut buffered = io::BufReader::new(file);
-                        let buf = &mut vec![];
-
-                        match buffered.read_to_end(buf) {
-                            Ok(_) => return Ok(buf.to_vec()),
-                            Err(e) => return Err(e),
-                        };
-                    },
-                };
+            ScreenType::Welcome => "00_welcome.ans",
+            ScreenType::Login => "01_login.ans",
+            ScreenType::Goodbye => "99_goodbye.ans",
+            ScreenType::Maintenance => "98_maintenance.ans",
+        }
+    }
+}
+
+/// An ANSI screen, animated if it has more than one frame
+///
+/// The frames of an animated screen are separated by marker lines ("%%frame"
+/// followed by the delay of the frame after it in milliseconds). Text before the
+/// first marker is a frame shown without delay (eg. to clear the screen).
+/// The final frame is all that is shown to players that do not want to see
+/// animations, so it should be complete on its own.
+#[derive(Debug, Clone)]
+pub struct Screen {
+    frames: Arc<[Frame]>,
+}
+
+impl Screen {
+    /// Split the content of a screen file into its frames
+    pub fn parse(data: &[u8]) -> Screen {
+        let mut frames = Vec::new();
+        let mut current = Frame { data: Vec::new(), delay: Duration::ZERO };
+        for line in data.split_inclusive(|b| *b == b'\n') {
+            let delay = match line.strip_prefix(FRAME_MARKER) {
+                Some(delay) => delay,
+                None => {
+                    current.data.extend_from_slice(line);
+                    continue;
+                },
+            };
+            let delay = std::str::from_utf8(delay).ok()
+                .and_then(|d| d.trim().parse::<u64>().ok())
+                .map_or(DEFAULT_FRAME_DELAY, Duration::from_millis);
+            let next = Frame { data: Vec::new(), delay };
+            let frame = std::mem::replace(&mut current, next);
+            if !frames.is_empty() || !frame.data.is_empty() {
+                frames.push(frame);
             }
         }
+        frames.push(current);
+        Screen { frames: frames.into() }
+    }
+
+    /// True if the screen has more than one frame
+    pub fn is_animated(&self) -> bool {
+        self.frames.len() > 1
     }
+
+    /// The frames of the screen
+    pub fn frames(&self) -> Arc<[Frame]> {
+        self.frames.clone()
+    }
+
+    /// The data of the final frame (all there is for screens that are not
+    /// animated)
+    pub fn final_frame(&self) -> &[u8] {
+        self.frames.last().map_or(&[], |f| f.data.as_slice())
+    }
+}
+
+/// The ANSI screens of the BBS
+/// 
+/// All screens are loaded at startup, so displaying a screen does not block
+/// the world.
+#[derive(Debug, Default)]
+pub struct Screens {
+    screens: HashMap<ScreenType, Screen>,
 }
 
+impl Screens {
+    /// Load all screens from the given directory
+    /// 
+    /// Screens that cannot be loaded are logged and left out.
+    pub async fn load(dir: &Path) -> Screens {
+        let mut screens = HashMap::new();
+        for screen in ScreenType::ALL.iter() {
+            match loader::load(&dir.join(screen.file_name())).await {
+                Ok(buf) => {
+                    screens.insert(*screen, Screen::parse(&buf));
+                },
+                Err(e) => error!("Couldn't load screen {}: {}", screen.file_name(), e),
+            }
+        }
+        Screens { screens }
+    }
+
+    /// A screen, if it was loaded
+    pub fn get(&self, screen: ScreenType) -> Option<&Screen> {
+        self.screens.get(&screen)
+    }
+}
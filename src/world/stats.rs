@@ -0,0 +1,266 @@
+//! Statistics of the world loop
+//!
+//! The world runs in a single task, so everything it does delays everything
+//! else. To find the hotspots, the loop times the commands, the data messages
+//! and every action it performs. The timings are recorded in tracing spans
+//! (eg. for tokio-console) and summed up in counters shown to the admins.
+//! The loop also beats a heartbeat on every tick, so the health check can
+//! tell if the world is wedged. Besides the loop, the world keeps statistics
+//! of every player session, shown to the player when it leaves. Sessions add
+//! up to the activity of the player, kept in its account and shown with
+//! "score". The activity of all accounts sums up to the anonymized totals
+//! shown on the statistics screen at the end of the con.
+
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fmt;
+use std::future::Future;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use tokio::time::Instant;
+use tracing::{Instrument, Span};
+
+use super::assets::AssetID;
+
+/// Latencies of one kind of work
+#[derive(Debug, Clone, Default)]
+pub struct Latency {
+    /// Number of times the work was done
+    pub count: u64,
+    /// Total time spent
+    pub total: Duration,
+    /// Longest time spent at once
+    pub max: Duration,
+}
+
+impl Latency {
+    /// Record the time spent once
+    pub fn record(&mut self, elapsed: Duration) {
+        self.count += 1;
+        self.total += elapsed;
+        self.max = self.max.max(elapsed);
+    }
+
+    /// Average time spent
+    pub fn average(&self) -> Duration {
+        if self.count == 0 {
+            Duration::ZERO
+        } else {
+            self.total / self.count as u32
+        }
+    }
+}
+
+/// Display the latencies
+impl fmt::Display for Latency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} times, avg {}us, max {}us", self.count, self.average().as_micros(),
+            self.max.as_micros())
+    }
+}
+
+/// Statistics of the world loop
+#[derive(Debug, Clone, Default)]
+pub struct LoopStats {
+    /// Processing of commands (eg. registering a player)
+    pub commands: Latency,
+    /// Processing of data messages (parsing and queueing player input)
+    pub data: Latency,
+    /// Processing of the action queues on the tick
+    pub ticks: Latency,
+    /// Performing actions, by verb
+    pub actions: BTreeMap<&'static str, Latency>,
+}
+
+/// Display the statistics, one line per kind of work
+impl fmt::Display for LoopStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "commands: {}\r\ndata: {}\r\nticks: {}", self.commands, self.data, self.ticks)?;
+        for (verb, latency) in self.actions.iter() {
+            write!(f, "\r\naction {}: {}", verb, latency)?;
+        }
+        Ok(())
+    }
+}
+
+/// Heartbeat of the world loop
+///
+/// Clones share the heartbeat, so the world can beat it while the health
+/// check reads it.
+#[derive(Debug, Clone)]
+pub struct Heartbeat {
+    started: Instant,
+    // Milliseconds since started
+    last_beat: Arc<AtomicU64>,
+}
+
+impl Heartbeat {
+    /// Create a heartbeat that beats now
+    pub fn new() -> Heartbeat {
+        Heartbeat {
+            started: Instant::now(),
+            last_beat: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Record that the world loop is alive
+    pub fn beat(&self) {
+        let now = self.started.elapsed().as_millis() as u64;
+        self.last_beat.store(now, Ordering::Relaxed);
+    }
+
+    /// Time since the last beat
+    pub fn age(&self) -> Duration {
+        let last_beat = Duration::from_millis(self.last_beat.load(Ordering::Relaxed));
+        self.started.elapsed().saturating_sub(last_beat)
+    }
+}
+
+impl Default for Heartbeat {
+    fn default() -> Heartbeat {
+        Heartbeat::new()
+    }
+}
+
+/// Statistics of a player session
+#[derive(Debug)]
+pub struct SessionStats {
+    /// When the session started
+    pub started: Instant,
+    /// Nodes the player was in
+    pub explored: HashSet<AssetID>,
+    /// Number of actions the player performed
+    pub actions: u64,
+    /// Number of commands the player sent
+    pub commands: u64,
+}
+
+impl SessionStats {
+    /// Start the statistics of a new session
+    pub fn new() -> SessionStats {
+        SessionStats {
+            started: Instant::now(),
+            explored: HashSet::new(),
+            actions: 0,
+            commands: 0,
+        }
+    }
+
+    /// Time since the session started
+    pub fn played(&self) -> Duration {
+        self.started.elapsed()
+    }
+}
+
+impl Default for SessionStats {
+    fn default() -> SessionStats {
+        SessionStats::new()
+    }
+}
+
+/// Display the statistics, eg. "1h 2m 3s jacked in, 2 nodes explored, 42 actions"
+impl fmt::Display for SessionStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} jacked in, {} nodes explored, {} actions", duration(self.played().as_secs()),
+            self.explored.len(), self.actions)
+    }
+}
+
+/// Format seconds as played time, eg. "1h 2m 3s"
+pub fn duration(seconds: u64) -> String {
+    let mut text = String::new();
+    if seconds >= 3_600 {
+        text += &format!("{}h ", seconds / 3_600);
+    }
+    if seconds >= 60 {
+        text += &format!("{}m ", seconds % 3_600 / 60);
+    }
+    text + &format!("{}s", seconds % 60)
+}
+
+/// The activity of a player over all its sessions
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Activity {
+    /// Number of sessions
+    pub sessions: u64,
+    /// Seconds played
+    pub played_s: u64,
+    /// Number of commands the player sent
+    pub commands: u64,
+    /// Nodes the player visited
+    pub nodes: BTreeSet<AssetID>,
+}
+
+impl Activity {
+    /// Add a session that is over
+    pub fn record(&mut self, session: &SessionStats) {
+        self.sessions += 1;
+        self.played_s += session.played().as_secs();
+        self.commands += session.commands;
+        self.nodes.extend(session.explored.iter().copied());
+    }
+
+    /// The activity including the current session
+    pub fn with(&self, session: &SessionStats) -> Activity {
+        let mut activity = self.clone();
+        activity.record(session);
+        activity
+    }
+}
+
+/// The activity of all players, summed up without telling who did what
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Totals {
+    /// Players that played at least one session
+    pub players: u64,
+    /// Number of sessions
+    pub sessions: u64,
+    /// Seconds played
+    pub played_s: u64,
+    /// Number of commands sent
+    pub commands: u64,
+    /// Nodes visited by anybody
+    nodes: BTreeSet<AssetID>,
+}
+
+impl Totals {
+    /// Add the activity of a player
+    pub fn add(&mut self, activity: &Activity) {
+        if activity.sessions == 0 {
+            return;
+        }
+        self.players += 1;
+        self.sessions += activity.sessions;
+        self.played_s += activity.played_s;
+        self.commands += activity.commands;
+        self.nodes.extend(activity.nodes.iter().copied());
+    }
+
+    /// Number of nodes visited by anybody
+    pub fn nodes(&self) -> usize {
+        self.nodes.len()
+    }
+}
+
+/// Display the totals as the statistics screen at the end of the con
+impl fmt::Display for Totals {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let per_player = |n: u64| n.checked_div(self.players).unwrap_or(0);
+        write!(f, "The con in numbers:\r\n  {} deckers jacked in {} times\r\n  {} in the matrix, {} per decker\r\n  \
+            {} commands typed, {} per decker\r\n  {} nodes explored", self.players, self.sessions,
+            duration(self.played_s), duration(per_player(self.played_s)), self.commands, per_player(self.commands),
+            self.nodes.len())
+    }
+}
+
+/// Run a future in a span and time it
+///
+/// The span must have an `elapsed_us` field, which is set to the time it took
+/// to complete the future (in microseconds).
+pub async fn timed<F: Future>(span: Span, future: F) -> (F::Output, Duration) {
+    let start = Instant::now();
+    let output = future.instrument(span.clone()).await;
+    let elapsed = start.elapsed();
+    span.record("elapsed_us", elapsed.as_micros() as u64);
+    (output, elapsed)
+}
@@ -0,0 +1,45 @@
+//! Stealth
+//!
+//! Players can "cloak" to hide from the occupant lists of their node and
+//! from NPCs, which neither trace nor strike cloaked players. A cloak does
+//! not last:
+//!  * noisy actions (eg. hacking, solving puzzles, talking) drop it
+//!  * ICE perceiving the node wears it down on every check, until the
+//!    player is spotted (see `ai`)
+//!  * other players reveal cloaked players with a scanner ("scan")
+//!  * stealing wears it down, and a thief caught drops it (see `ownership`)
+
+/// Strength of a fresh cloak
+pub const CLOAK_STRENGTH: u32 = 100;
+
+/// The cloak of a player
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Cloak {
+    strength: u32,
+}
+
+impl Cloak {
+    /// Create a fresh cloak
+    pub fn new() -> Cloak {
+        Cloak { strength: CLOAK_STRENGTH }
+    }
+
+    /// Wear the cloak down by the perception of a check
+    ///
+    /// Returns true if the cloak is worn out and the player spotted.
+    pub fn wear(&mut self, perception: u32) -> bool {
+        self.strength = self.strength.saturating_sub(perception);
+        self.strength == 0
+    }
+
+    /// The strength left
+    pub fn strength(&self) -> u32 {
+        self.strength
+    }
+}
+
+impl Default for Cloak {
+    fn default() -> Self {
+        Cloak::new()
+    }
+}
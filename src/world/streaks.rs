@@ -0,0 +1,76 @@
+//! Login streaks
+//!
+//! Players that jack in on consecutive days of the con build a streak. The
+//! first login of a day counts the day and grants the rewards of the day of
+//! the streak (see `hooks`), eg. credits, consumables or a title. Longer
+//! streaks earn more, streaks longer than the rewards configured get the
+//! rewards of the last day. Missing a day starts the streak over.
+//!
+//! Milestones (eg. the third day in a row) are announced to everybody
+//! online. Days are counted in UTC.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use super::hooks::Hook;
+
+/// Seconds of a day
+const DAY: u64 = 24 * 60 * 60;
+
+/// The login streak of an account
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Streak {
+    /// The day (since the epoch) the player last logged in
+    pub last_day: Option<u64>,
+    /// Consecutive days the player logged in, up to the last day
+    pub days: u32,
+    /// The longest streak of the player
+    pub best: u32,
+}
+
+impl Streak {
+    /// Count a login at a time (seconds since the epoch)
+    ///
+    /// Returns the day of the streak if it is the first login of the day.
+    pub fn login(&mut self, now: u64) -> Option<u32> {
+        let today = now / DAY;
+        match self.last_day {
+            Some(day) if day >= today => return None,
+            Some(day) if day + 1 == today => self.days += 1,
+            _ => self.days = 1,
+        }
+        self.last_day = Some(today);
+        self.best = self.best.max(self.days);
+        Some(self.days)
+    }
+}
+
+/// The rewards of the days of a streak
+#[derive(Debug, Clone, Default)]
+pub struct Rewards {
+    days: BTreeMap<u32, Vec<Hook>>,
+    milestones: BTreeSet<u32>,
+}
+
+impl Rewards {
+    /// Add a reward for a day of the streak (the first day is 1)
+    pub fn on_day(mut self, day: u32, hook: Hook) -> Rewards {
+        self.days.entry(day).or_default().push(hook);
+        self
+    }
+
+    /// Announce streaks reaching a day
+    pub fn milestone(mut self, day: u32) -> Rewards {
+        self.milestones.insert(day);
+        self
+    }
+
+    /// The rewards for a day of the streak
+    pub fn for_day(&self, day: u32) -> &[Hook] {
+        self.days.range(..=day).next_back().map_or(&[], |(_, hooks)| hooks)
+    }
+
+    /// True if a streak reaching the day is announced
+    pub fn is_milestone(&self, day: u32) -> bool {
+        self.milestones.contains(&day)
+    }
+}
@@ -0,0 +1,333 @@
+//! Terminals
+//!
+//! Terminals are assets players can "access". Accessing a terminal switches
+//! the session of the player into a shell with its own prompt and commands,
+//! working on the virtual filesystem of the terminal. The shell is left
+//! with "exit". Entries locked by ICE are cracked by running a program
+//! (eg. "run /bin/icebreaker /vault", see `filesystem`).
+//!
+//! The commands of the shell are:
+//! ```ignore
+//!     <shell_command> ::= "help" | "pwd" | "ls" (<blank> "-a" | E) (<blank> <path> | E)
+//!         | "cd" (<blank> <path> | E) | "cat" <blank> <path>
+//!         | "run" <blank> <path> <blank> <path> | "exit" | "logout"
+//! ```
+
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use generational_arena::Index;
+
+use super::actions::Action;
+use super::assets::{Actor, AssetID, AssetResponse, GameAsset, Port};
+use super::filesystem::{self, FileSystem, Kind};
+use super::hooks::Hook;
+use super::intern::intern;
+use super::properties::Property;
+use super::puzzles::Puzzle;
+
+/// Help text of the shell
+const HELP: &str = "Commands: ls [-a] [path], cd [path], cat <file>, run <program> <target>, pwd, help, exit";
+
+/// A terminal, giving access to its filesystem
+#[derive(Debug, Clone)]
+pub struct Terminal {
+    id: AssetID,
+    hostname: Arc<str>,
+    description: Arc<str>,
+    fs: FileSystem,
+    puzzle: Option<Puzzle>,
+}
+
+impl Terminal {
+    /// Create a new terminal with an empty filesystem
+    pub fn new(id: AssetID, hostname: &str) -> Terminal {
+        Terminal {
+            id,
+            hostname: intern(hostname),
+            description: intern(""),
+            fs: FileSystem::new(),
+            puzzle: None,
+        }
+    }
+
+    /// Describe a terminal
+    pub fn update_description(&mut self, description: &str) {
+        self.description = intern(description);
+    }
+
+    /// The filesystem of the terminal, to fill it
+    pub fn fs_mut(&mut self) -> &mut FileSystem {
+        &mut self.fs
+    }
+
+    /// Attach a puzzle to the terminal (or remove it)
+    pub fn update_puzzle(&mut self, puzzle: Option<Puzzle>) {
+        self.puzzle = puzzle;
+    }
+
+    /// The name of the terminal shown in the prompt
+    pub fn hostname(&self) -> &str {
+        &self.hostname
+    }
+}
+
+impl GameAsset for Terminal {
+    /// Return the uid of the terminal
+    fn uid(&self) -> AssetID {
+        self.id
+    }
+
+    /// Returns the name terminals are referenced by
+    fn name(&self) -> &str {
+        "terminal"
+    }
+
+    /// Terminals have no properties yet
+    fn properties(&self) -> Option<&Vec<Property>> {
+        None
+    }
+
+    /// Describe the terminal
+    fn describe(&self) -> String {
+        match &self.puzzle {
+            Some(puzzle) => format!("{} The display reads \"{}\". {}", self.description, self.hostname, 
+                puzzle.describe()),
+            None => format!("{} The display reads \"{}\".", self.description, self.hostname),
+        }
+    }
+
+    /// Returns the description of the terminal
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    /// Replace the description of the terminal
+    fn rewrite(&mut self, description: &str) {
+        self.update_description(description);
+    }
+
+    /// React to
+    ///
+    /// Accessing the terminal is handled by the world, as it changes the
+    /// session of the player.
+    fn react_to(&mut self, _actor: &Actor<'_>, a: &Action) -> AssetResponse {
+        match a {
+            Action::Look { .. } => self.describe().into(),
+            _ => AssetResponse::default(),
+        }
+    }
+
+    /// Terminals make no sound
+    fn sound(&self) -> Option<&str> {
+        None
+    }
+
+    /// A terminal is a terminal
+    fn terminal(&self) -> Option<&Terminal> {
+        Some(self)
+    }
+
+    /// A terminal is not a port
+    fn port(&self) -> Option<&Port> {
+        None
+    }
+
+    /// A terminal is not a port
+    fn port_mut(&mut self) -> Option<&mut Port> {
+        None
+    }
+
+    /// Returns the puzzle shown on the terminal
+    fn puzzle(&self) -> Option<&Puzzle> {
+        self.puzzle.as_ref()
+    }
+
+    /// Terminals do not lead anywhere
+    fn portal(&self) -> Option<&str> {
+        None
+    }
+
+    /// Terminals are too heavy to carry
+    fn is_takeable(&self) -> bool {
+        false
+    }
+
+    /// A terminal is accessed, and solved if it shows a puzzle
+    fn verbs(&self) -> Vec<&'static str> {
+        let mut verbs = vec!["look", "access"];
+        if self.puzzle.is_some() {
+            verbs.extend(["solve", "hint"]);
+        }
+        verbs
+    }
+
+    /// Returns a copy of the terminal and its filesystem
+    fn clone_box(&self) -> Box<dyn GameAsset> {
+        Box::new(self.clone())
+    }
+
+    /// Gives the terminal another uid
+    fn renumber(&mut self, uid: AssetID) {
+        self.id = uid;
+    }
+}
+
+/// Reply of the shell to a command
+#[derive(Debug, PartialEq)]
+pub enum Reply {
+    /// Output of the command (may be empty)
+    Output(String),
+    /// Output of reading a file and the hook the reading runs
+    Hooked(String, Hook),
+    /// The player left the shell
+    Exit,
+}
+
+/// The shell of a player accessing a terminal
+///
+/// The ICE the player cracked stays cracked until the player leaves the
+/// shell.
+#[derive(Debug)]
+pub struct Shell {
+    /// The node the terminal is in
+    pub node: Index,
+    /// The uid of the terminal
+    pub terminal: AssetID,
+    cwd: filesystem::Path,
+    cracked: HashSet<filesystem::Path>,
+}
+
+impl Shell {
+    /// Start a shell in the root directory of a terminal
+    pub fn new(node: Index, terminal: AssetID) -> Shell {
+        Shell { node, terminal, cwd: Vec::new(), cracked: HashSet::new() }
+    }
+
+    /// The working directory
+    pub fn cwd(&self) -> String {
+        filesystem::display(&self.cwd)
+    }
+
+    /// The prompt shown before every command
+    pub fn prompt(&self, terminal: &Terminal, user: &str) -> String {
+        format!("{}@{}:{}$ ", user, terminal.hostname(), self.cwd())
+    }
+
+    /// Run a command line on a terminal
+    pub fn run(&mut self, terminal: &Terminal, line: &str) -> Reply {
+        let mut words = line.split_whitespace();
+        let command = match words.next() {
+            Some(c) => c,
+            None => return Reply::Output(String::new()),
+        };
+        let mut arguments: Vec<&str> = words.collect();
+        let fs = &terminal.fs;
+        let output = match (command, arguments.as_slice()) {
+            ("exit", _) | ("logout", _) => return Reply::Exit,
+            ("help", _) => HELP.to_string(),
+            ("pwd", _) => self.cwd(),
+            ("ls", _) => {
+                let all = arguments.first() == Some(&"-a");
+                if all {
+                    arguments.remove(0);
+                }
+                let path = filesystem::resolve(&self.cwd, arguments.first().unwrap_or(&"."));
+                match (fs.dir(&path), fs.get(&path)) {
+                    _ if !self.may_access(fs, &path) => denied("ls", &path),
+                    (Some(directory), _) => directory.entries()
+                        .filter(|(_, entry)| all || !entry.is_hidden())
+                        .map(|(name, entry)| match entry.kind() {
+                            Kind::Directory(_) => format!("{}/", name),
+                            Kind::File(_) => name.clone(),
+                        })
+                        .collect::<Vec<String>>()
+                        .join("  "),
+                    (None, Some(_)) => filesystem::display(&path),
+                    _ => not_found("ls", &path),
+                }
+            },
+            ("cd", _) => {
+                let path = filesystem::resolve(&self.cwd, arguments.first().unwrap_or(&"/"));
+                match (fs.dir(&path), fs.get(&path)) {
+                    (None, None) => not_found("cd", &path),
+                    (None, Some(_)) => format!("cd: {}: Not a directory", filesystem::display(&path)),
+                    _ if !self.may_access(fs, &path) => denied("cd", &path),
+                    (Some(_), _) => {
+                        self.cwd = path;
+                        String::new()
+                    },
+                }
+            },
+            ("cat", [path, ..]) => {
+                let path = filesystem::resolve(&self.cwd, path);
+                match fs.get(&path).map(|e| e.kind()) {
+                    None => not_found("cat", &path),
+                    Some(Kind::Directory(_)) => format!("cat: {}: Is a directory", filesystem::display(&path)),
+                    Some(_) if !self.may_access(fs, &path) => denied("cat", &path),
+                    Some(Kind::File(file)) => {
+                        let contents = file.contents().trim_end().replace("\r\n", "\n").replace('\n', "\r\n");
+                        if let Some(hook) = file.hook() {
+                            return Reply::Hooked(contents, hook.clone());
+                        }
+                        contents
+                    },
+                }
+            },
+            ("cat", []) => "usage: cat <file>".to_string(),
+            ("run", [program, target, ..]) => {
+                let program = filesystem::resolve(&self.cwd, program);
+                let target = filesystem::resolve(&self.cwd, target);
+                match fs.get(&program).and_then(|e| e.file()).and_then(|f| f.program()) {
+                    None if fs.get(&program).is_none() => not_found("run", &program),
+                    None => format!("run: {}: Not a program", filesystem::display(&program)),
+                    Some(_) if !self.may_access(fs, &program) => denied("run", &program),
+                    Some(strength) => self.crack(fs, &target, strength),
+                }
+            },
+            ("run", _) => "usage: run <program> <target>".to_string(),
+            (c, _) => format!("{}: command not found", c),
+        };
+        Reply::Output(output)
+    }
+
+    /// Check if the player cracked all ICE on the path to an entry
+    fn may_access(&self, fs: &FileSystem, path: &[String]) -> bool {
+        fs.ice_on(path).iter().all(|(locked, _)| self.cracked.contains(locked))
+    }
+
+    /// Crack the ICE on the path to an entry with a program of the given
+    /// strength
+    fn crack(&mut self, fs: &FileSystem, target: &[String], strength: u8) -> String {
+        if fs.get(target).is_none() {
+            return not_found("run", target);
+        }
+        let ice: Vec<(filesystem::Path, u8)> = fs.ice_on(target).into_iter()
+            .filter(|(locked, _)| !self.cracked.contains(locked))
+            .collect();
+        if ice.is_empty() {
+            return format!("There is no ICE on {}.", filesystem::display(target));
+        }
+        let mut output = Vec::new();
+        for (locked, ice) in ice {
+            if ice > strength {
+                output.push(format!("The ICE on {} is too strong (level {}). The program fails.", 
+                    filesystem::display(&locked), ice));
+                break;
+            }
+            output.push(format!("ICE on {} cracked (level {}).", filesystem::display(&locked), ice));
+            self.cracked.insert(locked);
+        }
+        output.join("\r\n")
+    }
+}
+
+/// Output of a command that was denied access
+fn denied(command: &str, path: &[String]) -> String {
+    format!("{}: {}: Permission denied (ICE)", command, filesystem::display(path))
+}
+
+/// Output of a command that did not find an entry
+fn not_found(command: &str, path: &[String]) -> String {
+    format!("{}: {}: No such file or directory", command, filesystem::display(path))
+}
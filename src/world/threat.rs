@@ -0,0 +1,124 @@
+//! Threat
+//!
+//! Hostile NPCs keep a threat table of the players fighting them and target
+//! the player with the most threat (see `ai`). Hacking an NPC adds the damage
+//! dealt as threat, hacking assets in its node (eg. solving the puzzle of a
+//! port), acting quickly there (see `adverbs`) or being heard there (eg.
+//! shouting nearby, see `propagation`) adds a little threat, being caught
+//! stealing there (see `ownership`) a lot, and taunting it with a decoy
+//! program puts the player on top of the table. In group fights one decker
+//! can thus keep the ICE busy while the others hack it.
+
+use std::collections::HashMap;
+use tokio::time::Instant;
+
+use crate::connection_manager::ClientId;
+use super::actions::Action;
+use super::messages::{Event, Message};
+use super::{bosses, combat, drones, goals, send_combat, GameWorld, Player};
+
+/// Damage a hack deals to an NPC
+const HACK_DAMAGE: u32 = 10;
+
+/// Threat added for hacking an asset in the node of an NPC
+pub const HACK_THREAT: u32 = 5;
+
+/// Threat added for acting quickly in the node of an NPC (see `adverbs`)
+pub const HASTE_THREAT: u32 = 2;
+
+/// Threat added for making noise heard in the node of an NPC
+pub const NOISE_THREAT: u32 = 1;
+
+/// Threat added for being caught stealing in the node of an NPC (see
+/// `ownership`)
+pub const THEFT_THREAT: u32 = 20;
+
+/// Threat a taunt puts the player above the top of the table
+const TAUNT_MARGIN: u32 = 10;
+
+/// The threat of the players an NPC fights
+#[derive(Debug, Clone, Default)]
+pub struct Threat {
+    table: Vec<(String, u32)>,
+}
+
+impl Threat {
+    /// Add threat for a player
+    pub fn add(&mut self, player: &str, amount: u32) {
+        match self.table.iter_mut().find(|(p, _)| p == player) {
+            Some((_, threat)) => *threat = threat.saturating_add(amount),
+            None => self.table.push((player.to_string(), amount)),
+        }
+    }
+
+    /// Put a player on top of the table
+    pub fn taunt(&mut self, player: &str) {
+        let top = self.table.iter().map(|(_, t)| *t).max().unwrap_or(0);
+        self.table.retain(|(p, _)| p != player);
+        self.table.push((player.to_string(), top.saturating_add(TAUNT_MARGIN)));
+    }
+
+    /// The player with the most threat, the earlier one on a tie
+    pub fn top(&self) -> Option<&str> {
+        self.table.iter()
+            .rev()
+            .max_by_key(|(_, t)| *t)
+            .map(|(p, _)| p.as_str())
+    }
+
+    /// Keep the threat of the players for which the predicate holds
+    pub fn retain(&mut self, mut f: impl FnMut(&str) -> bool) {
+        self.table.retain(|(p, _)| f(p));
+    }
+}
+
+/// Hack a hostile NPC (or let the drone of the player hack it) or taunt it
+/// 
+/// Hacking damages the NPC and destroys it when its integrity runs out. Both
+/// add to the threat of the player, the players in the node see the fight.
+pub(super) async fn fight(a: &Action, world: &mut GameWorld, client_id: ClientId,
+                          players: &mut HashMap<ClientId, Player>, now: Instant) {
+    let player = match players.get(&client_id) {
+        Some(p) => p,
+        None => return,
+    };
+    let (location, (target, ordinal)) = match (player.location, a) {
+        (Some(l), Action::Hack { target, ordinal }) | (Some(l), Action::Taunt { target, ordinal })
+            | (Some(l), Action::Drone(drones::Order::Attack { target, ordinal })) => (l, (target, *ordinal)),
+        _ => return,
+    };
+    let npc = match world.npcs.find(location, target, ordinal) {
+        Some(n) if n.is_hostile() => n,
+        Some(n) => {
+            player.send(Message::new(Event::Error, &format!("The {} is not hostile.", n.name()))).await;
+            return;
+        },
+        None => {
+            player.send(Message::new(Event::Error, &format!("There is no {} here.", target))).await;
+            return;
+        },
+    };
+    let (uid, name, max) = (npc.uid, npc.name().to_string(), npc.max_integrity());
+    let event = match a {
+        Action::Hack { .. } | Action::Drone(_) => {
+            let (drone, damage) = match a {
+                Action::Drone(_) => (true, drones::DRONE_DAMAGE),
+                _ => (false, HACK_DAMAGE),
+            };
+            match world.npcs.hack(uid, &player.player_name, damage, now) {
+                Some(integrity) => combat::CombatEvent::Hack { player: player.player_name.clone(), drone, npc: uid,
+                    name, damage, integrity, max },
+                None => return,
+            }
+        },
+        _ => {
+            world.npcs.taunt(uid, &player.player_name);
+            combat::CombatEvent::Taunt { player: player.player_name.clone(), npc: uid, name }
+        },
+    };
+    send_combat(players, location, &event).await;
+    bosses::hit(&event, location, world, players, now).await;
+    if let combat::CombatEvent::Hack { name, integrity: 0, .. } = &event {
+        goals::accomplish(&goals::Deed::Destroy(name.clone()), world, players).await;
+    }
+}
@@ -0,0 +1,130 @@
+//! Timers
+//!
+//! Content schedules changes of the world for later with timers, eg. a port
+//! closing behind the player 30 seconds after it solved the puzzle, or an
+//! alarm resetting the zone a minute after it went off. A hook starts a
+//! timer (see `Hook::Timer`), the world applies its effect on the first tick
+//! after the timer is due.
+//!
+//! The world stores the timers in the timer file inside the data directory
+//! whenever they change, so pending timers survive a restart. They run on
+//! the wall clock: a timer that became due while the server was down fires
+//! on the first tick after the start, a timer that became due while the
+//! world was paused on the first tick after it resumes. A timer is only started once: a timer
+//! with the same effect pending is not started again, so an alarm going off
+//! twice resets the zone once.
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::fs;
+
+use serde::{Deserialize, Serialize};
+
+use super::assets::AssetID;
+
+/// Name of the file the timers are stored in (inside the data directory)
+pub const TIMER_FILE: &str = "timers.json";
+
+/// The change of the world when a timer is due
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "effect", rename_all = "lowercase")]
+pub enum Effect {
+    /// Open a port (by uid) to everybody
+    Open { port: AssetID },
+    /// Close a port (by uid), letting only the players pass that solved its
+    /// puzzle
+    Close { port: AssetID },
+    /// Run the reset of a zone (see `resets`)
+    Reset { zone: String },
+    /// Tell the players in a zone
+    Announce { zone: String, text: String },
+    /// Sound an alarm in a zone, heard in the nodes around it (see
+    /// `propagation`)
+    Alarm { zone: String },
+}
+
+/// A pending change of the world
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Timer {
+    pub id: u64,
+    /// When the timer is due (unix time in seconds)
+    pub due: u64,
+    pub effect: Effect,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Pending {
+    next_id: u64,
+    timers: Vec<Timer>,
+}
+
+/// The pending timers of the world
+///
+/// Hooks run on a shared world, so the timers change behind a lock.
+#[derive(Debug, Default)]
+pub struct Timers {
+    pending: Mutex<Pending>,
+}
+
+impl Timers {
+    /// Load the timers stored in the data directory
+    pub async fn load(data_dir: &Path) -> io::Result<Timers> {
+        let pending = match fs::read(Timers::path(data_dir)).await {
+            Ok(buf) => serde_json::from_slice(&buf)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Pending::default(),
+            Err(e) => return Err(e),
+        };
+        Ok(Timers { pending: Mutex::new(pending) })
+    }
+
+    /// Start a timer applying an effect after the given time (from now, in
+    /// unix time)
+    ///
+    /// Returns the id of the timer, or of the pending timer with the same
+    /// effect.
+    pub fn start(&self, after: Duration, effect: Effect, now: u64) -> u64 {
+        let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(timer) = pending.timers.iter().find(|t| t.effect == effect) {
+            return timer.id;
+        }
+        pending.next_id += 1;
+        let id = pending.next_id;
+        pending.timers.push(Timer { id, due: now + after.as_secs(), effect });
+        id
+    }
+
+    /// Cancel a timer, returning it if it was pending
+    pub fn cancel(&self, id: u64) -> Option<Timer> {
+        let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+        let position = pending.timers.iter().position(|t| t.id == id)?;
+        Some(pending.timers.remove(position))
+    }
+
+    /// Take the timers due at the given time (in unix time)
+    pub fn due(&self, now: u64) -> Vec<Timer> {
+        let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+        let (due, timers) = std::mem::take(&mut pending.timers).into_iter().partition(|t| t.due <= now);
+        pending.timers = timers;
+        due
+    }
+
+    /// The pending timers, the next due first
+    pub fn pending(&self) -> Vec<Timer> {
+        let mut timers = self.pending.lock().unwrap_or_else(|e| e.into_inner()).timers.clone();
+        timers.sort_by_key(|t| (t.due, t.id));
+        timers
+    }
+
+    /// Save the timers to the data directory
+    pub async fn save(&self, data_dir: &Path) -> io::Result<()> {
+        let buf = serde_json::to_vec_pretty(&*self.pending.lock().unwrap_or_else(|e| e.into_inner()))?;
+        fs::create_dir_all(data_dir).await?;
+        fs::write(Timers::path(data_dir), buf).await
+    }
+
+    fn path(data_dir: &Path) -> PathBuf {
+        data_dir.join(TIMER_FILE)
+    }
+}
@@ -0,0 +1,177 @@
+//! Transcripts
+//!
+//! Players can opt in to a transcript of their session, eg. to look up how
+//! they solved a puzzle when writing it up after the event. A transcript
+//! records the commands of the player and the text sent to it. Transcripts
+//! are stored as one file per session in a directory per player inside the
+//! `transcripts` directory of the data directory. Players get their
+//! transcripts with "transcript mail me", in their mailbox (see `market`).
+
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use tokio::fs;
+use tokio::io::AsyncWriteExt;
+use tracing::{error, info};
+
+use crate::connection_manager::ClientId;
+use super::accounts::encode_name;
+use super::actions::Action;
+use super::market::Parcel;
+use super::messages::{Event, Message};
+use super::{timestamp, GameWorld, Player};
+
+/// Name of the directory transcripts are stored in (inside the data directory)
+pub const TRANSCRIPT_DIR: &str = "transcripts";
+
+/// The transcript of a running session
+#[derive(Debug, Clone)]
+pub struct Transcript {
+    /// The file the transcript is written to
+    path: PathBuf,
+}
+
+impl Transcript {
+    /// Start a new transcript for the player with the given name
+    ///
+    /// The transcript directory of the player is created if it does not exist
+    /// yet.
+    pub async fn start(data_dir: &Path, player: &str) -> io::Result<Transcript> {
+        let dir = Transcript::dir(data_dir, player);
+        fs::create_dir_all(&dir).await?;
+        let started = timestamp();
+        let transcript = Transcript { path: dir.join(format!("{}.log", started)) };
+        transcript.append(&format!("# Transcript of {} started at {}\n", player, started)).await?;
+        Ok(transcript)
+    }
+
+    /// Record a command of the player
+    pub async fn record_input(&self, command: &str) -> io::Result<()> {
+        self.append(&format!("> {}\n", command.trim_end())).await
+    }
+
+    /// Record text sent to the player
+    pub async fn record_output(&self, text: &str) -> io::Result<()> {
+        self.append(&format!("{}\n", text.trim_end().replace("\r\n", "\n"))).await
+    }
+
+    /// Read all transcripts of the player with the given name
+    ///
+    /// Returns the file name and content of every transcript, oldest first.
+    pub async fn read_all(data_dir: &Path, player: &str) -> io::Result<Vec<(String, String)>> {
+        let mut entries = match fs::read_dir(Transcript::dir(data_dir, player)).await {
+            Ok(e) => e,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        let mut transcripts = Vec::new();
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            let name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+            transcripts.push((name, fs::read_to_string(&path).await?));
+        }
+        transcripts.sort();
+        Ok(transcripts)
+    }
+
+    /// Delete all transcripts of the player with the given name
+    pub async fn delete_all(data_dir: &Path, player: &str) -> io::Result<()> {
+        match fs::remove_dir_all(Transcript::dir(data_dir, player)).await {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    /// Rewrite the transcripts of all players, keeping those the rewrite
+    /// returns None for (eg. to anonymize a player)
+    pub async fn rewrite_all(data_dir: &Path, rewrite: impl Fn(&str) -> Option<String>) -> io::Result<()> {
+        let mut players = match fs::read_dir(data_dir.join(TRANSCRIPT_DIR)).await {
+            Ok(p) => p,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        while let Some(player) = players.next_entry().await? {
+            if !player.file_type().await?.is_dir() {
+                continue;
+            }
+            let mut entries = fs::read_dir(player.path()).await?;
+            while let Some(entry) = entries.next_entry().await? {
+                if let Some(text) = rewrite(&fs::read_to_string(entry.path()).await?) {
+                    fs::write(entry.path(), text).await?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Append text to the transcript file
+    async fn append(&self, text: &str) -> io::Result<()> {
+        let mut file = fs::OpenOptions::new().create(true).append(true).open(&self.path).await?;
+        file.write_all(text.as_bytes()).await
+    }
+
+    /// Directory holding the transcripts of the player with the given name
+    pub fn dir(data_dir: &Path, player: &str) -> PathBuf {
+        data_dir.join(TRANSCRIPT_DIR).join(encode_name(player))
+    }
+}
+
+/// Start or stop recording the session of a player, or tell whether it is
+/// recorded
+pub(super) async fn record(enabled: Option<bool>, world: &GameWorld, player: &mut Player) {
+    let message = match (enabled, &player.transcript) {
+        (None, Some(_)) | (Some(true), Some(_)) => 
+            "Your session is recorded. Use \"transcript mail me\" to get your transcripts.".to_string(),
+        (None, None) | (Some(false), None) => 
+            "Your session is not recorded. Use \"transcript on\" to record it.".to_string(),
+        (Some(true), None) => {
+            match Transcript::start(&world.data_dir, &player.player_name).await {
+                Ok(t) => {
+                    player.transcript = Some(t);
+                    "Your session is now recorded. Use \"transcript mail me\" to get your transcripts."
+                        .to_string()
+                },
+                Err(e) => {
+                    error!("Could not start transcript of {}: {}", player.player_name, e);
+                    "The recorder glitches. Your session is not recorded.".to_string()
+                },
+            }
+        },
+        (Some(false), Some(_)) => {
+            player.transcript = None;
+            "Your session is no longer recorded.".to_string()
+        },
+    };
+    player.send(Message::new(Event::Info, &message)
+        .with_field("transcript", player.transcript.is_some())).await;
+}
+
+/// Mail the transcripts of the player to its mailbox, one parcel per
+/// transcript
+pub(super) async fn mail(world: &mut GameWorld, client_id: ClientId, players: &HashMap<ClientId, Player>) {
+    let player = match players.get(&client_id) {
+        Some(p) => p,
+        None => return,
+    };
+    info!("Player {} is performing action {}.", player.player_name, Action::MailTranscript);
+    let text = match Transcript::read_all(&world.data_dir, &player.player_name).await {
+        Ok(transcripts) if transcripts.is_empty() => 
+            "You have no transcripts. Use \"transcript on\" to record your session.".to_string(),
+        Ok(transcripts) => {
+            for (_, transcript) in &transcripts {
+                let parcel = Parcel { text: format!("Your transcript:\n{}", transcript.trim_end()), item: None,
+                    credits: 0 };
+                world.market.send(&player.player_name, parcel);
+            }
+            if let Err(e) = world.market.save(&world.data_dir).await {
+                error!("Could not save the market: {}", e);
+            }
+            format!("You mail yourself your transcripts ({}). Collect them with \"mail\".", transcripts.len())
+        },
+        Err(e) => {
+            error!("Could not read the transcripts of {}: {}", player.player_name, e);
+            "The recorder glitches. Your transcripts cannot be mailed.".to_string()
+        },
+    };
+    player.send(Message::new(Event::Response, &text)).await;
+}
@@ -0,0 +1,174 @@
+//! Triggers
+//!
+//! Players can define triggers that run a command whenever their output
+//! contains a text, eg. "when you see 'ICE detected', run cloak". Triggers
+//! are stored in the account of the player and evaluated by the world over
+//! the output sent to the player. To keep triggers from flooding the world:
+//!  * output caused by a command a trigger ran never fires a trigger,
+//!  * a player fires at most `MAX_FIRES` triggers per `FIRE_WINDOW`,
+//!  * a player has at most `MAX_TRIGGERS` triggers.
+
+use std::collections::VecDeque;
+use std::convert::TryFrom;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::time::Instant;
+
+use tracing::error;
+
+use crate::world::actions::Action;
+use crate::world::errors::Error;
+use crate::world::messages::{Event, Message};
+use crate::world::{GameWorld, Player};
+
+/// Maximum number of triggers of a player
+pub const MAX_TRIGGERS: usize = 10;
+
+/// Maximum length of the text a trigger waits for
+pub const MAX_PATTERN_LENGTH: usize = 64;
+
+/// Maximum number of triggers fired within the fire window
+pub const MAX_FIRES: usize = 10;
+
+/// Window the fired triggers are counted in
+pub const FIRE_WINDOW: Duration = Duration::from_secs(60);
+
+/// A trigger running a command when the player sees a text
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Trigger {
+    /// Text the trigger waits for (ignoring case)
+    pub pattern: String,
+    /// Command run when the text is seen
+    pub command: String,
+}
+
+impl Trigger {
+    /// Create a trigger after checking the pattern and the command
+    ///
+    /// Only game actions can be triggered, not session commands like "stop"
+    /// or "trigger" itself.
+    pub fn new(pattern: &str, command: &str) -> Result<Trigger, Error> {
+        let pattern = pattern.trim();
+        let command = command.trim();
+        if pattern.is_empty() || pattern.chars().count() > MAX_PATTERN_LENGTH {
+            return Err(Error::InvalidLength);
+        }
+        if command.is_empty() || !Action::try_from(command)?.is_queued() {
+            return Err(Error::InvalidCommand);
+        }
+        Ok(Trigger { pattern: pattern.to_string(), command: command.to_string() })
+    }
+
+    /// Check if the trigger fires for a text
+    pub fn matches(&self, text: &str) -> bool {
+        text.to_lowercase().contains(&self.pattern.to_lowercase())
+    }
+}
+
+/// Triggers fired during a session
+///
+/// Fired triggers are collected while output is sent to the player and taken
+/// by the world on the next tick.
+#[derive(Debug, Default)]
+pub struct Firing {
+    // Sending output only borrows the player
+    pending: Mutex<Vec<String>>,
+    fired: VecDeque<Instant>,
+}
+
+impl Firing {
+    /// Collect the commands of the triggers matching the text
+    pub fn check(&self, triggers: &[Trigger], text: &str) {
+        if triggers.is_empty() {
+            return;
+        }
+        if let Ok(mut pending) = self.pending.lock() {
+            for trigger in triggers.iter().filter(|t| t.matches(text)) {
+                if pending.len() < MAX_TRIGGERS {
+                    pending.push(trigger.command.clone());
+                }
+            }
+        }
+    }
+
+    /// Take the commands to run
+    ///
+    /// Returns the commands within the rate limit and the number of commands
+    /// skipped because they exceeded it.
+    pub fn take(&mut self, now: Instant) -> (Vec<String>, usize) {
+        let pending = match self.pending.get_mut() {
+            Ok(p) => std::mem::take(p),
+            Err(_) => return (Vec::new(), 0),
+        };
+        while self.fired.front().is_some_and(|t| now.duration_since(*t) >= FIRE_WINDOW) {
+            self.fired.pop_front();
+        }
+        let mut commands = Vec::new();
+        let mut skipped = 0;
+        for command in pending {
+            if self.fired.len() < MAX_FIRES {
+                self.fired.push_back(now);
+                commands.push(command);
+            } else {
+                skipped += 1;
+            }
+        }
+        (commands, skipped)
+    }
+}
+
+/// List the triggers of a player or define a new one
+pub(super) async fn define(trigger: Option<(String, String)>, world: &GameWorld, player: &mut Player) {
+    match trigger {
+        None => {
+            let triggers = &player.account.triggers;
+            let mut text = if triggers.is_empty() {
+                "You have no triggers. Eg. \"trigger \"ICE detected\" look\" looks around \
+                    whenever you see \"ICE detected\".".to_string()
+            } else {
+                "Your triggers:".to_string()
+            };
+            for (number, trigger) in triggers.iter().enumerate() {
+                text += format!("\r\n {:>3}. \"{}\" -> {}", number + 1, trigger.pattern, trigger.command).as_str();
+            }
+            player.send(Message::new(Event::Info, &text)
+                .with_field("triggers", triggers.clone())).await;
+        },
+        Some((pattern, command)) => {
+            let message = if player.account.triggers.len() >= MAX_TRIGGERS {
+                format!("You cannot keep track of more than {} triggers. Use \"untrigger\" to remove one.", 
+                    MAX_TRIGGERS)
+            } else {
+                match Trigger::new(&pattern, &command) {
+                    Ok(trigger) => {
+                        player.account.triggers.push(trigger);
+                        if let Err(e) = player.account.save(&world.data_dir).await {
+                            error!("Could not save account of {}: {}", player.player_name, e);
+                        }
+                        format!("Whenever you see \"{}\", you {}.", pattern.trim(), command.trim())
+                    },
+                    Err(Error::InvalidLength) => format!("The text to wait for must be 1 to {} characters.", 
+                        MAX_PATTERN_LENGTH),
+                    Err(_) => format!("\"{}\" cannot be triggered. Only actions in the world can.", command.trim()),
+                }
+            };
+            player.send(Message::new(Event::Info, &message)
+                .with_field("triggers", player.account.triggers.len())).await;
+        },
+    }
+}
+
+/// Remove a trigger of a player by its number
+pub(super) async fn remove(number: usize, world: &GameWorld, player: &mut Player) {
+    let message = if number == 0 || number > player.account.triggers.len() {
+        format!("You have no trigger {}.", number)
+    } else {
+        let trigger = player.account.triggers.remove(number - 1);
+        if let Err(e) = player.account.save(&world.data_dir).await {
+            error!("Could not save account of {}: {}", player.player_name, e);
+        }
+        format!("You no longer react to \"{}\".", trigger.pattern)
+    };
+    player.send(Message::new(Event::Info, &message)
+        .with_field("triggers", player.account.triggers.len())).await;
+}
@@ -0,0 +1,149 @@
+//! Verbs
+//!
+//! Verbs registered at runtime (eg. by a plugin or a script), next to the
+//! verbs built into the grammar. Every entry of the verb table has
+//!  * a name: the word the command starts with, eg. "wave"
+//!  * a parser: turns the rest of the command into the arguments of the
+//!    action, or refuses it (by default the words of the rest)
+//!  * a handler: performs the action, replying to the player and optionally
+//!    to the others in the node
+//!  * a duration: the time the action takes (see `Action::duration`)
+//!
+//! The grammar looks up the table for every word that is no built-in verb,
+//! and the world passes the actions to their handler on the tick, so a new
+//! verb needs no change of the grammar, the world or the assets. Built-in
+//! verbs cannot be replaced.
+
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use lazy_static::lazy_static;
+
+use super::actions::Action;
+use super::assets::Node;
+use super::errors::Error;
+
+/// Turns the arguments typed into the arguments of the action
+pub type Parser = Arc<dyn Fn(&str) -> Result<Vec<String>, Error> + Send + Sync>;
+
+/// Performs an action, returning the reply or why the action failed
+pub type Handler = Arc<dyn Fn(&Call<'_>) -> Result<Reply, String> + Send + Sync>;
+
+/// An action passed to its handler
+pub struct Call<'a> {
+    /// The name the player is shown with
+    pub player: &'a str,
+    /// The node the player is in
+    pub node: Option<&'a Node>,
+    /// The arguments of the action, as parsed
+    pub arguments: &'a [String],
+}
+
+/// The reply of a handler
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Reply {
+    /// Sent to the player
+    pub text: String,
+    /// Sent to the other players in the node, if any
+    pub node: Option<String>,
+}
+
+/// A verb of the verb table
+#[derive(Clone)]
+pub struct Verb {
+    name: &'static str,
+    parser: Parser,
+    handler: Handler,
+    duration: Duration,
+}
+
+impl Verb {
+    /// Create a verb taking the words following it as arguments and no time
+    /// to perform
+    pub fn new(name: &str, handler: impl Fn(&Call<'_>) -> Result<Reply, String> + Send + Sync + 'static) -> Verb {
+        Verb {
+            // Verbs live as long as the server, the name is counted by the
+            // analytics like the built-in verbs
+            name: Box::leak(name.to_lowercase().into_boxed_str()),
+            parser: Arc::new(|arguments| Ok(arguments.split_whitespace().map(str::to_string).collect())),
+            handler: Arc::new(handler),
+            duration: Duration::ZERO,
+        }
+    }
+
+    /// Parse the arguments with the given parser
+    pub fn with_parser(mut self, parser: impl Fn(&str) -> Result<Vec<String>, Error> + Send + Sync + 'static)
+                       -> Verb {
+        self.parser = Arc::new(parser);
+        self
+    }
+
+    /// Take the given time to perform
+    pub fn with_duration(mut self, duration: Duration) -> Verb {
+        self.duration = duration;
+        self
+    }
+
+    /// The name of the verb
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+}
+
+impl fmt::Debug for Verb {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Verb").field("name", &self.name).field("duration", &self.duration).finish()
+    }
+}
+
+lazy_static! {
+    static ref VERBS: RwLock<BTreeMap<&'static str, Verb>> = RwLock::new(BTreeMap::new());
+}
+
+/// Register a verb
+///
+/// A verb registered before under the same name is replaced. Returns an
+/// error if the name is no single word or a built-in verb.
+pub fn register(verb: Verb) -> Result<(), Error> {
+    let is_word = !verb.name.is_empty() && verb.name.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_');
+    let built_in = !matches!(Action::try_from(verb.name), Err(Error::VerbUnknownError) | Ok(Action::Plugin { .. }));
+    if !is_word || built_in {
+        return Err(Error::VerbEncodingError);
+    }
+    VERBS.write().unwrap_or_else(|e| e.into_inner()).insert(verb.name, verb);
+    Ok(())
+}
+
+/// The verb registered under a name (ignoring case)
+pub fn find(name: &str) -> Option<Verb> {
+    VERBS.read().unwrap_or_else(|e| e.into_inner()).get(name.to_lowercase().as_str()).cloned()
+}
+
+/// The names of the registered verbs, in alphabetical order
+pub fn names() -> Vec<&'static str> {
+    VERBS.read().unwrap_or_else(|e| e.into_inner()).keys().copied().collect()
+}
+
+/// Parse a command starting with a registered verb
+///
+/// Returns None if the name is no registered verb.
+pub fn parse(name: &str, arguments: &str) -> Option<Result<Action, Error>> {
+    let verb = find(name)?;
+    Some((verb.parser)(arguments.trim()).map(|arguments| Action::Plugin { verb: verb.name, arguments }))
+}
+
+/// The time a registered verb takes to perform (none if it is gone)
+pub fn duration(name: &str) -> Duration {
+    find(name).map_or(Duration::ZERO, |v| v.duration)
+}
+
+/// Perform an action of a registered verb
+pub fn perform(name: &str, call: &Call<'_>) -> Result<Reply, String> {
+    match find(name) {
+        Some(verb) => (verb.handler)(call),
+        None => Err(format!("Nobody knows how to {} anymore.", name)),
+    }
+}
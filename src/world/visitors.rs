@@ -0,0 +1,93 @@
+//! Visitors
+//!
+//! The world counts the unique visitors of the event. The names of all
+//! players that ever registered are stored in the visitor file inside the
+//! data directory, one encoded name per line. The file is appended to when
+//! a new player registers, so the count survives restarts.
+
+use std::collections::HashSet;
+use std::io;
+use std::path::{Path, PathBuf};
+use tokio::fs;
+use tokio::io::AsyncWriteExt;
+
+use super::accounts::encode_name;
+
+/// Name of the file the visitors are stored in (inside the data directory)
+pub const VISITOR_FILE: &str = "visitors.txt";
+
+/// The unique visitors of the event
+#[derive(Debug, Default)]
+pub struct Visitors {
+    names: HashSet<String>,
+}
+
+impl Visitors {
+    /// Load the visitors stored in the data directory
+    pub async fn load(data_dir: &Path) -> io::Result<Visitors> {
+        let names = match fs::read_to_string(Visitors::path(data_dir)).await {
+            Ok(s) => s.lines().filter(|l| !l.is_empty()).map(|l| l.to_string()).collect(),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => HashSet::new(),
+            Err(e) => return Err(e),
+        };
+        Ok(Visitors { names })
+    }
+
+    /// Record the visit of a player
+    ///
+    /// Returns true if it is the first visit of the player.
+    pub async fn visit(&mut self, data_dir: &Path, name: &str) -> io::Result<bool> {
+        let encoded = encode_name(name);
+        if self.names.contains(&encoded) {
+            return Ok(false);
+        }
+        fs::create_dir_all(data_dir).await?;
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(Visitors::path(data_dir)).await?;
+        file.write_all(format!("{}\n", encoded).as_bytes()).await?;
+        self.names.insert(encoded);
+        Ok(true)
+    }
+
+    /// Number of unique visitors
+    pub fn count(&self) -> usize {
+        self.names.len()
+    }
+
+    /// Check if a player visited
+    pub fn contains(&self, name: &str) -> bool {
+        self.names.contains(&encode_name(name))
+    }
+
+    /// Forget a player
+    ///
+    /// Only the loaded visitors are changed, see `remove_stored` for the
+    /// visitor file.
+    pub fn remove(&mut self, name: &str) {
+        self.names.remove(&encode_name(name));
+    }
+
+    /// Remove a player from the visitor file
+    pub async fn remove_stored(data_dir: &Path, name: &str) -> io::Result<()> {
+        let mut visitors = Visitors::load(data_dir).await?;
+        if !visitors.contains(name) {
+            return Ok(());
+        }
+        visitors.remove(name);
+        let mut names: Vec<&String> = visitors.names.iter().collect();
+        names.sort();
+        let mut buf = String::new();
+        for name in names {
+            buf.push_str(name);
+            buf.push('\n');
+        }
+        fs::write(Visitors::path(data_dir), buf).await
+    }
+
+    /// Path of the visitor file
+    fn path(data_dir: &Path) -> PathBuf {
+        data_dir.join(VISITOR_FILE)
+    }
+}
@@ -0,0 +1,210 @@
+//! Worlds
+//!
+//! One server hosts several named worlds (eg. the main world and a sandbox
+//! for workshops). Every world runs in its own task with its own command and
+//! data channels (see `world::run`), and keeps its own data directory. The
+//! router sits between the connection handlers and the worlds, passing the
+//! commands and the data of a client to the world the client is in:
+//!
+//! - players choose the world at login by adding its name to their user
+//!   name ("neo+workshop"), without it they enter the default world (the
+//!   first one).
+//! - portals leading to another world of the server move the player there
+//!   without reconnecting. The world the player leaves hands it to the
+//!   router (see `Gate`), which registers it with the other world. Like a
+//!   traveler to a peer world (see `federation`), the player brings its
+//!   title, score and quest flags along.
+//!
+//! Commands that do not belong to a client (eg. from the federation link)
+//! go to the default world.
+
+use std::collections::HashMap;
+
+use tokio::sync::mpsc;
+use tracing::{debug, error, info, warn};
+
+use crate::connection_manager::{ClientConnection, ClientId, Command, DataMessage};
+use crate::connection_manager::channels::{self, MeteredReceiver, MeteredSender, Metrics, SendError};
+use crate::federation::Traveler;
+
+/// Number of transfers between worlds that can be queued
+pub const QUEUE_CAPACITY: usize = 64;
+
+/// Split a login into the name of the player and the world it chose, eg.
+/// "neo+workshop" into "neo" and "workshop"
+pub fn split_login(login: &str) -> (&str, Option<&str>) {
+    match login.split_once('+') {
+        Some((name, world)) => (name, Some(world)),
+        None => (login, None),
+    }
+}
+
+/// A player moving to another world of the server
+#[derive(Debug)]
+pub struct Transfer {
+    pub client_id: ClientId,
+    /// The world the player moves to
+    pub world: String,
+    /// The connection to the client of the player
+    pub connection: Box<dyn ClientConnection>,
+    pub traveler: Traveler,
+}
+
+/// The way out of a world to the other worlds of the server
+#[derive(Debug, Clone)]
+pub struct Gate {
+    /// Names of the other worlds
+    worlds: Vec<String>,
+    tx: mpsc::Sender<Transfer>,
+}
+
+impl Gate {
+    /// Create a gate to the named worlds, passing transfers to the router
+    pub fn new(worlds: Vec<String>, tx: mpsc::Sender<Transfer>) -> Gate {
+        Gate { worlds, tx }
+    }
+
+    /// The name of the world the gate leads to, if any (ignoring case)
+    pub fn leads_to(&self, world: &str) -> Option<&str> {
+        self.worlds.iter().find(|w| w.eq_ignore_ascii_case(world)).map(String::as_str)
+    }
+
+    /// True if the router takes another transfer
+    pub fn has_room(&self) -> bool {
+        self.tx.capacity() > 0
+    }
+
+    /// Hand a player to the router
+    ///
+    /// Does not block the world. Returns the transfer if too many transfers
+    /// are queued.
+    pub fn send(&self, transfer: Transfer) -> Result<(), Box<Transfer>> {
+        self.tx.try_send(transfer).map_err(|e| Box::new(e.into_inner()))
+    }
+}
+
+/// The channels to a world
+#[derive(Debug)]
+pub struct Route {
+    pub name: String,
+    commands: MeteredSender<Command>,
+    data: MeteredSender<DataMessage>,
+}
+
+impl Route {
+    /// Create the channels to a world
+    ///
+    /// Returns the receivers for the world, for `world::run`.
+    pub fn new(name: &str, metrics: &Metrics) -> (Route, MeteredReceiver<Command>, MeteredReceiver<DataMessage>) {
+        let (commands, command_rx) = channels::channel(&metrics.command);
+        let (data, data_rx) = channels::channel(&metrics.data);
+        (Route { name: name.to_string(), commands, data }, command_rx, data_rx)
+    }
+
+    /// Pass a command to the world
+    async fn command(&self, command: Command) {
+        match self.commands.send(command).await {
+            Ok(_) => {},
+            Err(SendError::Full) => warn!("World {} is overloaded. Dropping an admin command.", self.name),
+            Err(SendError::Closed) => error!("World {} is gone. Dropping a command.", self.name),
+        }
+    }
+}
+
+/// The router between the connection handlers and the worlds
+#[derive(Debug)]
+pub struct Router {
+    /// The worlds, the default world first
+    routes: Vec<Route>,
+    /// The world every client is in, by the index of its route
+    clients: HashMap<ClientId, usize>,
+}
+
+impl Router {
+    /// Create a router to the worlds, the default world first
+    pub fn new(routes: Vec<Route>) -> Router {
+        Router { routes, clients: HashMap::new() }
+    }
+
+    /// The index of the route to the named world (ignoring case)
+    fn find(&self, world: &str) -> Option<usize> {
+        self.routes.iter().position(|r| r.name.eq_ignore_ascii_case(world))
+    }
+
+    /// The route to the world a client is in
+    fn of(&self, client_id: ClientId) -> &Route {
+        &self.routes[self.clients.get(&client_id).copied().unwrap_or(0)]
+    }
+
+    /// Pass a command from a connection handler to its world
+    pub async fn command(&mut self, command: Command) {
+        match command {
+            Command::Register(client_id, login, connection) => {
+                let (name, world) = split_login(&login);
+                let index = match world.map(|w| (w, self.find(w))) {
+                    Some((_, Some(i))) => i,
+                    Some((w, None)) => {
+                        info!("{} asked for world {}, which is not hosted here.", name, w);
+                        0
+                    },
+                    None => 0,
+                };
+                self.clients.insert(client_id, index);
+                self.routes[index].command(Command::Register(client_id, name.to_string(), connection)).await;
+            },
+            Command::Hangup(client_id) => {
+                let index = self.clients.remove(&client_id).unwrap_or(0);
+                self.routes[index].command(Command::Hangup(client_id)).await;
+            },
+            Command::Admin(data_message) => {
+                self.of(data_message.client_id).command(Command::Admin(data_message)).await;
+            },
+            command => self.routes[0].command(command).await,
+        }
+    }
+
+    /// Pass data from a connection handler to its world
+    pub async fn data(&self, data_message: DataMessage) {
+        let route = self.of(data_message.client_id);
+        if route.data.send(data_message).await.is_err() {
+            debug!("Dropping data for world {}.", route.name);
+        }
+    }
+
+    /// Move a player to another world
+    pub async fn transfer(&mut self, transfer: Transfer) {
+        let index = match self.find(&transfer.world) {
+            Some(i) => i,
+            None => {
+                warn!("{} cannot move to world {}, which is not hosted here.", transfer.traveler.name,
+                    transfer.world);
+                return;
+            },
+        };
+        info!("{} moves to world {}.", transfer.traveler.name, self.routes[index].name);
+        self.clients.insert(transfer.client_id, index);
+        let name = transfer.traveler.name.clone();
+        let route = &self.routes[index];
+        route.command(Command::Arrival(transfer.traveler)).await;
+        route.command(Command::Register(transfer.client_id, name, transfer.connection)).await;
+    }
+}
+
+/// Run the router
+///
+/// Commands and transfers are passed on before data, like the world handles
+/// them (see `world::run`).
+pub async fn run(mut command_rx: MeteredReceiver<Command>, mut data_rx: MeteredReceiver<DataMessage>,
+                 mut transfer_rx: mpsc::Receiver<Transfer>, mut router: Router) {
+    loop {
+        tokio::select! {
+            biased;
+
+            Some(command) = command_rx.recv() => router.command(command).await,
+            Some(transfer) = transfer_rx.recv() => router.transfer(transfer).await,
+            Some(data_message) = data_rx.recv() => router.data(data_message).await,
+            else => break,
+        }
+    }
+    info!("All connection handlers are gone. The router stops.");
+}